@@ -251,6 +251,54 @@ test("simple pragma table_list", async (t) => {
   t.deepEqual(actual, expectedValue);
 });
 
+test("Database.backup() copies the database", async (t) => {
+  const [db] = await connect(":memory:");
+  db.exec("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)");
+  db.exec("INSERT INTO users (id, name) VALUES (1, 'Alice')");
+  const backupPath = path.join(fs.mkdtempSync("limbo-"), "backup.db");
+  const result = await db.backup(backupPath);
+  t.true(result.totalPages > 0);
+  t.is(result.remainingPages, 0);
+  const [restored] = await connect(backupPath);
+  t.is(restored.prepare("SELECT name FROM users").get().name, "Alice");
+});
+
+test("Database.serialize() returns the database image", async (t) => {
+  const [db] = await connect(":memory:");
+  db.exec("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)");
+  db.exec("INSERT INTO users (id, name) VALUES (1, 'Alice')");
+  const buffer = db.serialize();
+  t.true(Buffer.isBuffer(buffer));
+  t.is(buffer.subarray(0, 15).toString(), "SQLite format 3");
+  t.is(buffer.length % 4096, 0);
+});
+
+test("Database hooks report changes and can veto commits", async (t) => {
+  const [db] = await connect(":memory:");
+  db.exec("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)");
+  const changes = [];
+  let commits = 0;
+  let rollbacks = 0;
+  db.updateHook((op, dbName, table, rowid) => changes.push([op, dbName, table, rowid]));
+  db.commitHook(() => ++commits > 3);
+  db.rollbackHook(() => rollbacks++);
+
+  db.exec("INSERT INTO users (id, name) VALUES (1, 'Alice')");
+  db.exec("UPDATE users SET name = 'Bob' WHERE id = 1");
+  db.exec("DELETE FROM users WHERE id = 1");
+  t.deepEqual(changes, [
+    ["INSERT", "main", "users", 1],
+    ["UPDATE", "main", "users", 1],
+    ["DELETE", "main", "users", 1],
+  ]);
+  t.is(commits, 3);
+
+  t.throws(() => db.exec("INSERT INTO users (id, name) VALUES (2, 'Carol')"));
+  t.is(rollbacks, 1);
+  db.commitHook(null);
+  t.is(db.prepare("SELECT count(*) AS n FROM users").get().n, 0);
+});
+
 const connect = async (path) => {
   const db = new Database(path);
   return [db];
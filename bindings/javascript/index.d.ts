@@ -18,8 +18,11 @@ export declare class Database {
   prepare(sql: string): Statement
   transaction(): void
   pragma(): void
-  backup(): void
-  serialize(): void
+  backup(path: string): number
+  serialize(): Buffer
+  updateHook(callback: ((op: string, db: string, table: string, rowid: number) => void) | null): void
+  commitHook(callback: (() => boolean) | null): void
+  rollbackHook(callback: (() => void) | null): void
   function(): void
   aggregate(): void
   table(): void
@@ -9,7 +9,10 @@ use std::sync::Arc;
 use limbo_core::types::Text;
 use limbo_core::{maybe_init_database_file, LimboError, StepResult};
 use napi::iterator::Generator;
-use napi::{bindgen_prelude::ObjectFinalize, Env, JsUnknown};
+use napi::{
+    bindgen_prelude::{Buffer, ObjectFinalize},
+    Env, JsFunction, JsUnknown, Ref,
+};
 use napi_derive::napi;
 
 #[napi(object)]
@@ -117,14 +120,112 @@ impl Database {
         }
     }
 
+    /// Copies the database into the database file at `path` and returns the number of pages copied.
     #[napi]
-    pub fn backup(&self) {
-        todo!()
+    pub fn backup(&self, path: String) -> napi::Result<u32> {
+        let io: Arc<dyn limbo_core::IO> = if path == ":memory:" {
+            Arc::new(limbo_core::MemoryIO::new())
+        } else {
+            Arc::new(limbo_core::PlatformIO::new().map_err(into_napi_error)?)
+        };
+        let file = io
+            .open_file(&path, limbo_core::OpenFlags::Create, false)
+            .map_err(into_napi_error)?;
+        maybe_init_database_file(&file, &io).map_err(into_napi_error)?;
+        let db_file = Arc::new(DatabaseFile::new(file));
+        let db = limbo_core::Database::open(io, &path, db_file, false).map_err(into_napi_error)?;
+        let dest = db.connect().map_err(into_napi_error)?;
+
+        let mut backup = limbo_core::Backup::new(&self.conn, &dest).map_err(into_napi_error)?;
+        match backup.step(None).map_err(into_napi_error)? {
+            limbo_core::BackupStepResult::Done => {}
+            step => {
+                return Err(napi::Error::new(
+                    napi::Status::GenericFailure,
+                    format!("{:?}", step),
+                ))
+            }
+        }
+        let pages = backup.pagecount() as u32;
+        drop(backup);
+        dest.close().map_err(into_napi_error)?;
+        Ok(pages)
     }
 
+    /// Returns the contents of the database as it would be stored on disk.
     #[napi]
-    pub fn serialize(&self) {
-        todo!()
+    pub fn serialize(&self) -> napi::Result<Buffer> {
+        let data = self.conn.serialize().map_err(into_napi_error)?;
+        Ok(data.into())
+    }
+
+    /// Calls `callback` with the operation, the database name, the table name and the rowid
+    /// whenever a row is inserted, updated or deleted. `null` removes the callback.
+    #[napi]
+    pub fn update_hook(&self, env: Env, callback: Option<JsFunction>) -> napi::Result<()> {
+        let hook = match callback {
+            Some(callback) => {
+                let callback = JsCallback::new(env, callback)?;
+                Some(Box::new(
+                    move |kind: limbo_core::UpdateKind, db: &str, table: &str, rowid: i64| {
+                        let op = match kind {
+                            limbo_core::UpdateKind::Insert => "INSERT",
+                            limbo_core::UpdateKind::Update => "UPDATE",
+                            limbo_core::UpdateKind::Delete => "DELETE",
+                        };
+                        // An exception thrown by the callback stays pending and is thrown once
+                        // the statement returns to JavaScript.
+                        let _ = callback.call(|env| {
+                            Ok(vec![
+                                env.create_string(op)?.into_unknown(),
+                                env.create_string(db)?.into_unknown(),
+                                env.create_string(table)?.into_unknown(),
+                                env.create_int64(rowid)?.into_unknown(),
+                            ])
+                        });
+                    },
+                ) as Box<limbo_core::UpdateHook>)
+            }
+            None => None,
+        };
+        self.conn.update_hook(hook);
+        Ok(())
+    }
+
+    /// Calls `callback` before a transaction commits. If it returns a truthy value or throws,
+    /// the transaction is rolled back instead. `null` removes the callback.
+    #[napi]
+    pub fn commit_hook(&self, env: Env, callback: Option<JsFunction>) -> napi::Result<()> {
+        let hook = match callback {
+            Some(callback) => {
+                let callback = JsCallback::new(env, callback)?;
+                Some(Box::new(move || {
+                    callback
+                        .call(|_| Ok(vec![]))
+                        .and_then(|veto| veto.coerce_to_bool()?.get_value())
+                        .unwrap_or(true)
+                }) as Box<limbo_core::CommitHook>)
+            }
+            None => None,
+        };
+        self.conn.commit_hook(hook);
+        Ok(())
+    }
+
+    /// Calls `callback` after a transaction is rolled back. `null` removes the callback.
+    #[napi]
+    pub fn rollback_hook(&self, env: Env, callback: Option<JsFunction>) -> napi::Result<()> {
+        let hook = match callback {
+            Some(callback) => {
+                let callback = JsCallback::new(env, callback)?;
+                Some(Box::new(move || {
+                    let _ = callback.call(|_| Ok(vec![]));
+                }) as Box<limbo_core::RollbackHook>)
+            }
+            None => None,
+        };
+        self.conn.rollback_hook(hook);
+        Ok(())
     }
 
     #[napi]
@@ -589,6 +690,35 @@ impl limbo_core::DatabaseStorage for DatabaseFile {
     }
 }
 
+/// A JavaScript function kept alive so that it can be called from the connection's hooks.
+/// Hooks run while a statement is stepped, so the function is always called on the
+/// JavaScript thread.
+struct JsCallback {
+    env: Env,
+    function: Ref<()>,
+}
+
+impl JsCallback {
+    fn new(env: Env, function: JsFunction) -> napi::Result<Self> {
+        let function = env.create_reference(function)?;
+        Ok(Self { env, function })
+    }
+
+    fn call(
+        &self,
+        args: impl FnOnce(&Env) -> napi::Result<Vec<JsUnknown>>,
+    ) -> napi::Result<JsUnknown> {
+        let function: JsFunction = self.env.get_reference_value(&self.function)?;
+        function.call(None, &args(&self.env)?)
+    }
+}
+
+impl Drop for JsCallback {
+    fn drop(&mut self) {
+        let _ = self.function.unref(self.env);
+    }
+}
+
 #[inline]
 pub fn into_napi_error(limbo_error: LimboError) -> napi::Error {
     napi::Error::new(napi::Status::GenericFailure, format!("{limbo_error}"))
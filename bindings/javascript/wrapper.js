@@ -97,12 +97,51 @@ class Database {
       : this.db.pragma(source);
   }
 
-  backup(filename, options) {
-    throw new Error("not implemented");
+  /**
+   * Backs up the database into the database file at `filename`.
+   *
+   * @param {string} filename - Path of the database file to write the backup to.
+   */
+  async backup(filename, options) {
+    if (typeof filename !== "string")
+      throw new TypeError("Expected first argument to be a string");
+
+    const totalPages = this.db.backup(filename);
+    return { totalPages, remainingPages: 0 };
   }
 
+  /**
+   * Returns the contents of the database as a Buffer.
+   */
   serialize(options) {
-    throw new Error("not implemented");
+    return this.db.serialize();
+  }
+
+  /**
+   * Registers a function that is called whenever a row is inserted, updated or deleted.
+   *
+   * @param {function|null} fn - Called with the operation ("INSERT", "UPDATE" or "DELETE"), the database name, the table name and the rowid.
+   */
+  updateHook(fn) {
+    this.db.updateHook(fn);
+  }
+
+  /**
+   * Registers a function that is called before a transaction commits. If it returns a truthy value, the transaction is rolled back instead.
+   *
+   * @param {function|null} fn - The function to call, or null to remove it.
+   */
+  commitHook(fn) {
+    this.db.commitHook(fn);
+  }
+
+  /**
+   * Registers a function that is called after a transaction is rolled back.
+   *
+   * @param {function|null} fn - The function to call, or null to remove it.
+   */
+  rollbackHook(fn) {
+    this.db.rollbackHook(fn);
   }
 
   function(name, options, fn) {
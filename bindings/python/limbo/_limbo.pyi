@@ -1,4 +1,4 @@
-from typing import Any, List, Optional, Tuple
+from typing import Any, Callable, List, Optional, Tuple
 
 __version__: str
 
@@ -36,6 +36,52 @@ class Connection:
         """
         ...
 
+    def set_update_hook(self, callback: Optional[Callable[[str, str, str, int], None]]) -> None:
+        """
+        Registers a callback that is called whenever a row is inserted, updated or deleted.
+
+        :param callback: Called with the operation ("INSERT", "UPDATE" or "DELETE"), the database name, the table name
+                         and the rowid of the row, or None to remove the callback.
+        """
+        ...
+
+    def set_commit_hook(self, callback: Optional[Callable[[], bool]]) -> None:
+        """
+        Registers a callback that is called before a transaction commits.
+
+        :param callback: Returns a true value, or raises, to roll the transaction back instead, or None to remove the
+                         callback.
+        """
+        ...
+
+    def set_rollback_hook(self, callback: Optional[Callable[[], None]]) -> None:
+        """
+        Registers a callback that is called after a transaction is rolled back.
+
+        :param callback: The callback, or None to remove the callback.
+        """
+        ...
+
+    def serialize(self, *, name: str = "main") -> bytes:
+        """
+        Serializes the database into a bytes object.
+
+        :param name: The name of the database to serialize. Only "main" is supported.
+        :return: The contents of the database, as it would be stored on disk.
+        :raises OperationalError: If there is an error reading the database.
+        """
+        ...
+
+    def deserialize(self, data: bytes, /, *, name: str = "main") -> None:
+        """
+        Replaces the database with an in-memory copy of a serialized database.
+
+        :param data: The serialized database, e.g. as returned by serialize().
+        :param name: The name of the database to replace. Only "main" is supported.
+        :raises DatabaseError: If the data is not a valid database image.
+        """
+        ...
+
 class Cursor:
     arraysize: int
     description: Optional[
@@ -266,9 +266,89 @@ impl Connection {
     }
 
     pub fn rollback(&self) -> PyResult<()> {
-        Err(PyErr::new::<NotSupportedError, _>(
-            "Transactions are not supported in this version",
-        ))
+        if !self.conn.get_auto_commit() {
+            self.conn.execute("ROLLBACK").map_err(|e| {
+                PyErr::new::<OperationalError, _>(format!("Failed to rollback: {:?}", e))
+            })?;
+
+            self.conn.execute("BEGIN").map_err(|e| {
+                PyErr::new::<OperationalError, _>(format!("Failed to rollback: {:?}", e))
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn set_update_hook(&self, callback: Option<PyObject>) {
+        let hook = callback.map(|callback| {
+            Box::new(move |kind, db: &str, table: &str, rowid| {
+                let op = match kind {
+                    limbo_core::UpdateKind::Insert => "INSERT",
+                    limbo_core::UpdateKind::Update => "UPDATE",
+                    limbo_core::UpdateKind::Delete => "DELETE",
+                };
+                Python::with_gil(|py| {
+                    if let Err(e) = callback.call1(py, (op, db, table, rowid)) {
+                        e.write_unraisable(py, Some(callback.bind(py)));
+                    }
+                })
+            }) as Box<limbo_core::UpdateHook>
+        });
+        self.conn.update_hook(hook);
+    }
+
+    pub fn set_commit_hook(&self, callback: Option<PyObject>) {
+        let hook = callback.map(|callback| {
+            Box::new(move || {
+                Python::with_gil(|py| {
+                    // A hook that raises can't vouch for the transaction, so roll it back.
+                    match callback.call0(py).and_then(|veto| veto.is_truthy(py)) {
+                        Ok(veto) => veto,
+                        Err(e) => {
+                            e.write_unraisable(py, Some(callback.bind(py)));
+                            true
+                        }
+                    }
+                })
+            }) as Box<limbo_core::CommitHook>
+        });
+        self.conn.commit_hook(hook);
+    }
+
+    pub fn set_rollback_hook(&self, callback: Option<PyObject>) {
+        let hook = callback.map(|callback| {
+            Box::new(move || {
+                Python::with_gil(|py| {
+                    if let Err(e) = callback.call0(py) {
+                        e.write_unraisable(py, Some(callback.bind(py)));
+                    }
+                })
+            }) as Box<limbo_core::RollbackHook>
+        });
+        self.conn.rollback_hook(hook);
+    }
+
+    #[pyo3(signature = (*, name = "main"))]
+    pub fn serialize<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyBytes>> {
+        check_main_schema(name)?;
+        let data = self.conn.serialize().map_err(|e| {
+            PyErr::new::<OperationalError, _>(format!("Failed to serialize database: {:?}", e))
+        })?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    #[pyo3(signature = (data, /, *, name = "main"))]
+    pub fn deserialize(&mut self, data: &[u8], name: &str) -> PyResult<()> {
+        check_main_schema(name)?;
+        let (io, db) = limbo_core::Database::deserialize(data).map_err(|e| {
+            PyErr::new::<DatabaseError, _>(format!("Failed to deserialize database: {:?}", e))
+        })?;
+        let conn = db.connect().map_err(|e| {
+            PyErr::new::<OperationalError, _>(format!("Failed to deserialize database: {:?}", e))
+        })?;
+        self.close()?;
+        self.conn = conn;
+        self.io = io;
+        Ok(())
     }
 
     fn __enter__(&self) -> PyResult<Self> {
@@ -285,6 +365,16 @@ impl Connection {
     }
 }
 
+fn check_main_schema(name: &str) -> PyResult<()> {
+    if name != "main" {
+        return Err(PyErr::new::<NotSupportedError, _>(format!(
+            "Unknown database: {}",
+            name
+        )));
+    }
+    Ok(())
+}
+
 impl Drop for Connection {
     fn drop(&mut self) {
         self.conn
@@ -170,9 +170,57 @@ def test_with_statement(provider):
         assert max_id == (2,)
 
 
+@pytest.mark.parametrize("provider", ["sqlite3", "limbo"])
+def test_serialize_deserialize(provider):
+    conn = connect(provider, "tests/database.db")
+    data = conn.serialize()
+    conn.close()
+
+    conn = connect(provider, ":memory:")
+    conn.deserialize(data)
+    cursor = conn.cursor()
+    cursor.execute("SELECT username FROM users ORDER BY id")
+
+    assert cursor.fetchall() == [("alice",), ("bob",)]
+
+
+def test_hooks():
+    conn = limbo.connect(":memory:")
+    cursor = conn.cursor()
+    cursor.execute("CREATE TABLE t (x INTEGER)")
+
+    changes = []
+    commits = []
+    rollbacks = []
+    conn.set_update_hook(lambda op, db, table, rowid: changes.append((op, db, table, rowid)))
+    conn.set_commit_hook(lambda: commits.append(None) or len(commits) > 3)
+    conn.set_rollback_hook(lambda: rollbacks.append(None))
+
+    cursor.execute("INSERT INTO t VALUES (1)")
+    cursor.execute("UPDATE t SET x = 2")
+    cursor.execute("DELETE FROM t")
+    assert changes == [
+        ("INSERT", "main", "t", 1),
+        ("UPDATE", "main", "t", 1),
+        ("DELETE", "main", "t", 1),
+    ]
+    assert len(commits) == 3
+
+    # The fourth commit is vetoed by the commit hook
+    with pytest.raises(limbo.OperationalError):
+        cursor.execute("INSERT INTO t VALUES (3)")
+    assert len(rollbacks) == 1
+
+    conn.set_commit_hook(None)
+    cursor.execute("SELECT count(*) FROM t")
+    assert cursor.fetchone() == (0,)
+    conn.close()
+
+
 def connect(provider, database):
     if provider == "limbo":
         return limbo.connect(database)
     if provider == "sqlite3":
         return sqlite3.connect(database)
     raise Exception(f"Provider `{provider}` is not supported")
+
@@ -3,12 +3,19 @@ pub mod value;
 
 pub use value::Value;
 
+pub use limbo_core::BackupStepResult;
+pub use limbo_core::MemoryTracker;
+pub use limbo_core::StatementCacheStats;
+pub use limbo_core::UpdateKind;
+
 pub use params::params_from_iter;
 
 use crate::params::*;
 use std::fmt::Debug;
+use std::io;
 use std::num::NonZero;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -18,11 +25,18 @@ pub enum Error {
     MutexError(String),
     #[error("SQL execution failure: `{0}`")]
     SqlExecutionFailure(String),
+    #[error("Interrupted")]
+    Interrupted,
+    #[error("Out of memory")]
+    OutOfMemory,
 }
 
 impl From<limbo_core::LimboError> for Error {
     fn from(err: limbo_core::LimboError) -> Self {
-        Error::SqlExecutionFailure(err.to_string())
+        match err {
+            limbo_core::LimboError::OutOfMemory => Error::OutOfMemory,
+            err => Error::SqlExecutionFailure(err.to_string()),
+        }
     }
 }
 
@@ -72,6 +86,12 @@ impl Debug for Database {
 }
 
 impl Database {
+    /// Opens an in-memory database from an image produced by [Connection::serialize].
+    pub fn deserialize(data: &[u8]) -> Result<Database> {
+        let (_io, db) = limbo_core::Database::deserialize(data)?;
+        Ok(Database { inner: db })
+    }
+
     pub fn connect(&self) -> Result<Connection> {
         let conn = self.inner.connect()?;
         #[allow(clippy::arc_with_non_send_sync)]
@@ -146,6 +166,336 @@ impl Connection {
         })?;
         Ok(())
     }
+
+    /// Retries with backoff for up to `timeout` when the database is locked by another
+    /// connection, instead of failing right away. A zero timeout turns retrying off.
+    pub fn busy_timeout(&self, timeout: Duration) -> Result<()> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        conn.busy_timeout(timeout);
+        Ok(())
+    }
+
+    /// Calls `handler` every `period` VDBE instructions while a statement runs. If the handler
+    /// returns `true`, the statement fails with [Error::Interrupted]. `None` removes the
+    /// handler.
+    pub fn progress_handler<F>(&self, period: u64, handler: Option<F>) -> Result<()>
+    where
+        F: Fn() -> bool + 'static,
+    {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        conn.progress_handler(
+            period,
+            handler.map(|handler| Box::new(handler) as Box<limbo_core::ProgressCallback>),
+        );
+        Ok(())
+    }
+
+    /// Interrupts statements that run for longer than `timeout` with [Error::Interrupted]. A
+    /// zero timeout removes the limit.
+    pub fn statement_timeout(&self, timeout: Duration) -> Result<()> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        conn.statement_timeout(timeout);
+        Ok(())
+    }
+
+    /// Returns the number of bytes of memory currently used by the connection.
+    pub fn memory_used(&self) -> Result<usize> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(conn.memory_used())
+    }
+
+    /// Sets the soft heap limit of the connection in bytes, over which the page cache evicts
+    /// pages instead of growing, and returns the previous limit. Zero removes the limit.
+    pub fn set_soft_heap_limit(&self, limit: usize) -> Result<usize> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(conn.set_soft_heap_limit(limit))
+    }
+
+    /// Sets the hard heap limit of the connection in bytes, over which statements fail with
+    /// [Error::OutOfMemory], and returns the previous limit. Zero removes the limit.
+    pub fn set_hard_heap_limit(&self, limit: usize) -> Result<usize> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(conn.set_hard_heap_limit(limit))
+    }
+
+    /// Frees as much memory as possible, returning the number of bytes released.
+    pub fn shrink_memory(&self) -> Result<usize> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(conn.shrink_memory())
+    }
+
+    /// Sets the number of compiled statements the connection keeps for reuse by
+    /// [Connection::query], [Connection::execute] and [Connection::prepare]. Zero turns the
+    /// statement cache off.
+    pub fn set_prepared_statement_cache_capacity(&self, capacity: usize) -> Result<()> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        conn.set_statement_cache_capacity(capacity);
+        Ok(())
+    }
+
+    /// Returns the hit and miss counters of the statement cache.
+    pub fn statement_cache_stats(&self) -> Result<StatementCacheStats> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(conn.statement_cache_stats())
+    }
+
+    /// Calls `hook` with the kind of change, the database name, the table name and the rowid
+    /// whenever a row is inserted, updated or deleted through this connection. `None` removes
+    /// the hook.
+    pub fn update_hook<F>(&self, hook: Option<F>) -> Result<()>
+    where
+        F: Fn(UpdateKind, &str, &str, i64) + 'static,
+    {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        conn.update_hook(hook.map(|hook| Box::new(hook) as Box<limbo_core::UpdateHook>));
+        Ok(())
+    }
+
+    /// Calls `hook` before a transaction commits. If `hook` returns `true`, the transaction is
+    /// rolled back instead and the statement fails. `None` removes the hook.
+    pub fn commit_hook<F>(&self, hook: Option<F>) -> Result<()>
+    where
+        F: Fn() -> bool + 'static,
+    {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        conn.commit_hook(hook.map(|hook| Box::new(hook) as Box<limbo_core::CommitHook>));
+        Ok(())
+    }
+
+    /// Calls `hook` after a transaction is rolled back. `None` removes the hook.
+    pub fn rollback_hook<F>(&self, hook: Option<F>) -> Result<()>
+    where
+        F: Fn() + 'static,
+    {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        conn.rollback_hook(hook.map(|hook| Box::new(hook) as Box<limbo_core::RollbackHook>));
+        Ok(())
+    }
+
+    /// Returns the contents of the database with the layout of a database file.
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(conn.serialize()?)
+    }
+
+    /// Starts an online backup of this connection's database into the database of `dest`.
+    pub fn backup(&self, dest: &Connection) -> Result<Backup> {
+        if Arc::ptr_eq(&self.inner, &dest.inner) {
+            return Err(Error::SqlExecutionFailure(
+                "source and destination must be distinct databases".to_string(),
+            ));
+        }
+        let source = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        let dest = dest
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        let backup = limbo_core::Backup::new(&source, &dest)?;
+        #[allow(clippy::arc_with_non_send_sync)]
+        let backup = Backup {
+            inner: Arc::new(Mutex::new(backup)),
+        };
+        Ok(backup)
+    }
+
+    /// Copies the whole database of this connection into the database of `dest`.
+    pub fn backup_to(&self, dest: &Connection) -> Result<()> {
+        let mut backup = self.backup(dest)?;
+        match backup.step(None)? {
+            BackupStepResult::Done => Ok(()),
+            BackupStepResult::Busy => {
+                Err(Error::SqlExecutionFailure("database is locked".to_string()))
+            }
+            BackupStepResult::More => unreachable!("backup of all pages should complete"),
+        }
+    }
+
+    /// Opens the BLOB or TEXT value of `column` in the row `rowid` of `table` for incremental
+    /// I/O.
+    pub fn blob_open(
+        &self,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        read_only: bool,
+    ) -> Result<Blob> {
+        let conn = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        let blob = conn.blob_open(table, column, rowid, !read_only)?;
+        #[allow(clippy::arc_with_non_send_sync)]
+        let blob = Blob {
+            inner: Arc::new(Mutex::new(blob)),
+            pos: 0,
+        };
+        Ok(blob)
+    }
+}
+
+/// An online backup started with [Connection::backup].
+pub struct Backup {
+    inner: Arc<Mutex<limbo_core::Backup>>,
+}
+
+unsafe impl Send for Backup {}
+unsafe impl Sync for Backup {}
+
+impl Backup {
+    /// Copies up to `pages` pages, or all remaining pages if `pages` is `None`.
+    pub fn step(&mut self, pages: Option<usize>) -> Result<BackupStepResult> {
+        let mut backup = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(backup.step(pages)?)
+    }
+
+    /// Number of pages still to be copied.
+    pub fn remaining(&self) -> Result<usize> {
+        let backup = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(backup.remaining())
+    }
+
+    /// Total number of pages in the source database.
+    pub fn pagecount(&self) -> Result<usize> {
+        let backup = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(backup.pagecount())
+    }
+}
+
+/// A handle on a single BLOB or TEXT value opened with [Connection::blob_open].
+///
+/// The size of the value cannot be changed: reads and writes stop at the end of the value.
+pub struct Blob {
+    inner: Arc<Mutex<limbo_core::Blob>>,
+    pos: usize,
+}
+
+unsafe impl Send for Blob {}
+unsafe impl Sync for Blob {}
+
+impl Blob {
+    /// Size of the value in bytes.
+    pub fn size(&self) -> Result<usize> {
+        let blob = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        Ok(blob.size())
+    }
+
+    /// Moves the handle to the same column of another row and rewinds it.
+    pub fn reopen(&mut self, rowid: i64) -> Result<()> {
+        let mut blob = self
+            .inner
+            .lock()
+            .map_err(|e| Error::MutexError(e.to_string()))?;
+        blob.reopen(rowid)?;
+        self.pos = 0;
+        Ok(())
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, limbo_core::Blob>> {
+        self.inner
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+impl io::Read for Blob {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let blob = self.lock()?;
+        let n = buf.len().min(blob.size().saturating_sub(self.pos));
+        blob.read_at(&mut buf[..n], self.pos)
+            .map_err(io::Error::other)?;
+        drop(blob);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl io::Write for Blob {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let blob = self.lock()?;
+        let n = buf.len().min(blob.size().saturating_sub(self.pos));
+        blob.write_at(&buf[..n], self.pos)
+            .map_err(io::Error::other)?;
+        drop(blob);
+        self.pos += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::Seek for Blob {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let size = self.lock()?.size() as i64;
+        let pos = match pos {
+            io::SeekFrom::Start(offset) => offset as i64,
+            io::SeekFrom::End(offset) => size + offset,
+            io::SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if pos < 0 || pos > size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek out of the bounds of the blob",
+            ));
+        }
+        self.pos = pos as usize;
+        Ok(self.pos as u64)
+    }
 }
 
 pub struct Statement {
@@ -309,8 +659,8 @@ impl Rows {
                     continue;
                 }
                 Ok(limbo_core::StepResult::Busy) => return Ok(None),
-                Ok(limbo_core::StepResult::Interrupt) => return Ok(None),
-                _ => return Ok(None),
+                Ok(limbo_core::StepResult::Interrupt) => return Err(Error::Interrupted),
+                Err(err) => return Err(err.into()),
             }
         }
     }
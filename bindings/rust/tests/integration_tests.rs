@@ -1,4 +1,6 @@
-use limbo::Builder;
+use limbo::{BackupStepResult, Builder, Database, Error, UpdateKind};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
 #[tokio::test]
 async fn test_rows_next() {
@@ -25,3 +27,188 @@ async fn test_rows_next() {
     );
     assert!(res.next().await.unwrap().is_none());
 }
+
+#[tokio::test]
+async fn test_backup() {
+    let source = Builder::new_local(":memory:").build().await.unwrap();
+    let source = source.connect().unwrap();
+    source
+        .execute("CREATE TABLE test (x INTEGER)", ())
+        .await
+        .unwrap();
+    source
+        .execute("INSERT INTO test (x) VALUES (42)", ())
+        .await
+        .unwrap();
+
+    let dest = Builder::new_local(":memory:").build().await.unwrap();
+    let dest = dest.connect().unwrap();
+    let mut backup = source.backup(&dest).unwrap();
+    assert_eq!(backup.step(None).unwrap(), BackupStepResult::Done);
+    assert_eq!(backup.remaining().unwrap(), 0);
+    drop(backup);
+
+    let mut res = dest.query("SELECT x FROM test", ()).await.unwrap();
+    assert_eq!(
+        res.next().await.unwrap().unwrap().get_value(0).unwrap(),
+        42.into()
+    );
+    assert!(res.next().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_serialize_deserialize() {
+    let db = Builder::new_local(":memory:").build().await.unwrap();
+    let conn = db.connect().unwrap();
+    conn.execute("CREATE TABLE test (x TEXT)", ())
+        .await
+        .unwrap();
+    conn.execute("INSERT INTO test (x) VALUES ('hello')", ())
+        .await
+        .unwrap();
+    let image = conn.serialize().unwrap();
+
+    let copy = Database::deserialize(&image).unwrap();
+    let copy = copy.connect().unwrap();
+    let mut res = copy.query("SELECT x FROM test", ()).await.unwrap();
+    assert_eq!(
+        res.next().await.unwrap().unwrap().get_value(0).unwrap(),
+        "hello".into()
+    );
+    assert!(res.next().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_blob_io() {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let db = Builder::new_local(":memory:").build().await.unwrap();
+    let conn = db.connect().unwrap();
+    conn.execute("CREATE TABLE test (x BLOB)", ())
+        .await
+        .unwrap();
+    conn.execute("INSERT INTO test (x) VALUES (zeroblob(10000))", ())
+        .await
+        .unwrap();
+
+    let mut blob = conn.blob_open("test", "x", 1, false).unwrap();
+    assert_eq!(blob.size().unwrap(), 10000);
+    blob.seek(SeekFrom::Start(9000)).unwrap();
+    blob.write_all(b"hello").unwrap();
+    blob.seek(SeekFrom::End(-3)).unwrap();
+    assert_eq!(blob.write(b"world").unwrap(), 3);
+    assert!(blob.write_all(b"!").is_err());
+
+    let mut data = Vec::new();
+    blob.rewind().unwrap();
+    blob.read_to_end(&mut data).unwrap();
+    assert_eq!(data.len(), 10000);
+    assert_eq!(&data[9000..9005], b"hello");
+    assert_eq!(&data[9997..], b"wor");
+
+    let mut res = conn.query("SELECT x FROM test", ()).await.unwrap();
+    assert_eq!(
+        res.next().await.unwrap().unwrap().get_value(0).unwrap(),
+        limbo::Value::Blob(data)
+    );
+}
+
+#[tokio::test]
+async fn test_hooks() {
+    let db = Builder::new_local(":memory:").build().await.unwrap();
+    let conn = db.connect().unwrap();
+    conn.execute("CREATE TABLE test (x INTEGER)", ())
+        .await
+        .unwrap();
+
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let commits = Rc::new(Cell::new(0));
+    let rollbacks = Rc::new(Cell::new(0));
+    let veto = Rc::new(Cell::new(false));
+    conn.update_hook(Some({
+        let changes = changes.clone();
+        move |kind, db: &str, table: &str, rowid| {
+            changes
+                .borrow_mut()
+                .push((kind, db.to_string(), table.to_string(), rowid))
+        }
+    }))
+    .unwrap();
+    conn.commit_hook(Some({
+        let commits = commits.clone();
+        let veto = veto.clone();
+        move || {
+            commits.set(commits.get() + 1);
+            veto.get()
+        }
+    }))
+    .unwrap();
+    conn.rollback_hook(Some({
+        let rollbacks = rollbacks.clone();
+        move || rollbacks.set(rollbacks.get() + 1)
+    }))
+    .unwrap();
+
+    conn.execute("INSERT INTO test (x) VALUES (1)", ())
+        .await
+        .unwrap();
+    conn.execute("UPDATE test SET x = 2", ()).await.unwrap();
+    conn.execute("DELETE FROM test", ()).await.unwrap();
+    let expected = [UpdateKind::Insert, UpdateKind::Update, UpdateKind::Delete]
+        .map(|kind| (kind, "main".to_string(), "test".to_string(), 1));
+    assert_eq!(*changes.borrow(), expected);
+    assert_eq!(commits.get(), 3);
+
+    veto.set(true);
+    assert!(conn
+        .execute("INSERT INTO test (x) VALUES (3)", ())
+        .await
+        .is_err());
+    assert_eq!(commits.get(), 4);
+    assert_eq!(rollbacks.get(), 1);
+
+    conn.commit_hook(None::<fn() -> bool>).unwrap();
+    let mut res = conn.query("SELECT count(*) FROM test", ()).await.unwrap();
+    assert_eq!(
+        res.next().await.unwrap().unwrap().get_value(0).unwrap(),
+        0.into()
+    );
+}
+
+#[tokio::test]
+async fn test_progress_handler() {
+    let db = Builder::new_local(":memory:").build().await.unwrap();
+    let conn = db.connect().unwrap();
+    conn.execute("CREATE TABLE test (x INTEGER)", ())
+        .await
+        .unwrap();
+    for i in 0..20 {
+        conn.execute("INSERT INTO test (x) VALUES (?1)", [i])
+            .await
+            .unwrap();
+    }
+
+    let calls = Rc::new(Cell::new(0));
+    conn.progress_handler(
+        100,
+        Some({
+            let calls = calls.clone();
+            move || {
+                calls.set(calls.get() + 1);
+                calls.get() == 5
+            }
+        }),
+    )
+    .unwrap();
+    let query = "SELECT count(*) FROM test a, test b, test c";
+    let mut res = conn.query(query, ()).await.unwrap();
+    assert!(matches!(res.next().await, Err(Error::Interrupted)));
+    assert_eq!(calls.get(), 5);
+
+    conn.progress_handler(0, None::<fn() -> bool>).unwrap();
+    let mut res = conn.query(query, ()).await.unwrap();
+    assert_eq!(
+        res.next().await.unwrap().unwrap().get_value(0).unwrap(),
+        8000.into()
+    );
+}
@@ -302,6 +302,10 @@ impl limbo_core::IO for PlatformIO {
     fn get_memory_io(&self) -> Arc<limbo_core::MemoryIO> {
         Arc::new(limbo_core::MemoryIO::new())
     }
+
+    fn sleep(&self, _duration: std::time::Duration) {
+        // The browser cannot block the thread, so busy locks are retried right away.
+    }
 }
 
 #[wasm_bindgen]
@@ -11,7 +11,7 @@ use crate::{
     HISTORY_FILE,
 };
 use comfy_table::{Attribute, Cell, CellAlignment, ContentArrangement, Row, Table};
-use limbo_core::{Database, LimboError, Statement, StepResult, Value};
+use limbo_core::{Backup, BackupStepResult, Database, LimboError, Statement, StepResult, Value};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
@@ -62,6 +62,8 @@ pub struct Opts {
 }
 
 const PROMPT: &str = "limbo> ";
+/// Number of pages copied per step by `.backup` and `.restore`.
+const BACKUP_STEP_PAGES: usize = 100;
 
 pub struct Limbo {
     pub prompt: String,
@@ -364,6 +366,20 @@ impl Limbo {
         Ok(())
     }
 
+    fn backup_db(&mut self, path: &str) -> anyhow::Result<()> {
+        let io = get_io(DbLocation::Path, &self.opts.io.to_string())?;
+        let dest = Database::open_file(io, path, false)?.connect()?;
+        run_backup(&self.conn, &dest)?;
+        dest.close()?;
+        Ok(())
+    }
+
+    fn restore_db(&mut self, path: &str) -> anyhow::Result<()> {
+        let io = get_io(DbLocation::Path, &self.opts.io.to_string())?;
+        let source = Database::open_file(io, path, false)?.connect()?;
+        run_backup(&source, &self.conn)
+    }
+
     fn set_output_file(&mut self, path: &str) -> Result<(), String> {
         if path.is_empty() || path.trim().eq_ignore_ascii_case("stdout") {
             self.set_output_stdout();
@@ -580,6 +596,16 @@ impl Limbo {
                         let _ = self.writeln("Error: Unable to open database file.");
                     }
                 }
+                Command::Backup(args) => {
+                    if let Err(e) = self.backup_db(&args.path) {
+                        let _ = self.writeln(format!("Error: {}", e));
+                    }
+                }
+                Command::Restore(args) => {
+                    if let Err(e) = self.restore_db(&args.path) {
+                        let _ = self.writeln(format!("Error: {}", e));
+                    }
+                }
                 Command::Schema(args) => {
                     if let Err(e) = self.display_schema(args.table_name.as_deref()) {
                         let _ = self.writeln(e.to_string());
@@ -1089,3 +1115,18 @@ impl Drop for Limbo {
         self.save_history()
     }
 }
+
+/// Copies the whole database behind `source` into the database behind `dest`.
+fn run_backup(
+    source: &Arc<limbo_core::Connection>,
+    dest: &Arc<limbo_core::Connection>,
+) -> anyhow::Result<()> {
+    let mut backup = Backup::new(source, dest)?;
+    loop {
+        match backup.step(Some(BACKUP_STEP_PAGES))? {
+            BackupStepResult::More => {}
+            BackupStepResult::Done => return Ok(()),
+            BackupStepResult::Busy => anyhow::bail!("database is locked"),
+        }
+    }
+}
@@ -27,6 +27,20 @@ pub struct OpenArgs {
     pub vfs_name: Option<String>,
 }
 
+#[derive(Debug, Clone, Args)]
+pub struct BackupArgs {
+    /// Path of the database file to write the backup to
+    #[arg(add = ArgValueCompleter::new(PathCompleter::file()))]
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RestoreArgs {
+    /// Path of the database file to restore from
+    #[arg(add = ArgValueCompleter::new(PathCompleter::file()))]
+    pub path: String,
+}
+
 #[derive(Debug, Clone, Args)]
 pub struct SchemaArgs {
     // TODO depends on PRAGMA table_list for completions
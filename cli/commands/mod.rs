@@ -2,8 +2,9 @@ pub mod args;
 pub mod import;
 
 use args::{
-    CwdArgs, EchoArgs, ExitArgs, IndexesArgs, LoadExtensionArgs, NullValueArgs, OpcodesArgs,
-    OpenArgs, OutputModeArgs, SchemaArgs, SetOutputArgs, TablesArgs, TimerArgs,
+    BackupArgs, CwdArgs, EchoArgs, ExitArgs, IndexesArgs, LoadExtensionArgs, NullValueArgs,
+    OpcodesArgs, OpenArgs, OutputModeArgs, RestoreArgs, SchemaArgs, SetOutputArgs, TablesArgs,
+    TimerArgs,
 };
 use clap::Parser;
 use import::ImportArgs;
@@ -35,6 +36,12 @@ pub enum Command {
     /// Open a database file
     #[command(display_name = ".open")]
     Open(OpenArgs),
+    /// Backup the database to FILE
+    #[command(display_name = ".backup")]
+    Backup(BackupArgs),
+    /// Restore the content of the database from FILE
+    #[command(display_name = ".restore")]
+    Restore(RestoreArgs),
     /// Display schema for a table
     #[command(display_name = ".schema")]
     Schema(SchemaArgs),
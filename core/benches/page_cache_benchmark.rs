@@ -0,0 +1,196 @@
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion, SamplingMode, Throughput,
+};
+use limbo_core::{DumbLruPageCache, Page, PageCache, PageCacheKey, S3FifoPageCache};
+use pprof::criterion::{Output, PProfProfiler};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Title: Page cache replacement policies
+//
+// Each workload is a trace of page numbers replayed the way the pager uses the cache: a lookup,
+// followed by an insertion of the page on a miss. Besides the time a replay takes, the fraction
+// of its lookups that miss the cache is reported, with the MissRatio measurement.
+
+const CACHE_SIZE: usize = 1000;
+const TRACE_LEN: usize = 100_000;
+
+/// Lookups on a hot set that fits in the cache, interleaved with a table scan.
+fn hot_set_with_scan() -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut next_scan_page = 10_000;
+    (0..TRACE_LEN)
+        .map(|i| {
+            if i % 2 == 0 {
+                rng.gen_range(1..=CACHE_SIZE / 2)
+            } else {
+                next_scan_page += 1;
+                next_scan_page
+            }
+        })
+        .collect()
+}
+
+/// Lookups skewed towards low page numbers, like the interior pages of b-trees.
+fn skewed() -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..TRACE_LEN)
+        .map(|_| {
+            let x: f64 = rng.gen();
+            1 + (x * x * x * (CACHE_SIZE * 10) as f64) as usize
+        })
+        .collect()
+}
+
+/// Uniform lookups over twice as many pages as the cache holds.
+fn uniform() -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..TRACE_LEN)
+        .map(|_| rng.gen_range(1..=CACHE_SIZE * 2))
+        .collect()
+}
+
+fn replay(cache: &mut dyn PageCache, trace: &[usize]) {
+    for &pgno in trace {
+        let key = PageCacheKey::new(pgno);
+        if cache.get(&key).is_none() {
+            #[allow(clippy::arc_with_non_send_sync)]
+            let page = Arc::new(Page::new(pgno));
+            cache.insert(key, page).unwrap();
+        }
+    }
+}
+
+/// Builds an empty page cache of `CACHE_SIZE` pages.
+type NewCache = fn() -> Box<dyn PageCache>;
+
+fn caches() -> [(&'static str, NewCache); 2] {
+    [
+        ("LRU", || Box::new(DumbLruPageCache::new(CACHE_SIZE))),
+        ("S3-FIFO", || Box::new(S3FifoPageCache::new(CACHE_SIZE))),
+    ]
+}
+
+fn workloads() -> [(&'static str, Vec<usize>); 3] {
+    [
+        ("hot set with scan", hot_set_with_scan()),
+        ("skewed", skewed()),
+        ("uniform", uniform()),
+    ]
+}
+
+/// Measures the fraction of the lookups of a replay that miss the cache instead of its time.
+struct MissRatio;
+
+impl Measurement for MissRatio {
+    type Intermediate = ();
+    type Value = f64;
+
+    fn start(&self) -> Self::Intermediate {}
+
+    fn end(&self, _: Self::Intermediate) -> Self::Value {
+        unreachable!("miss ratios are only measured with iter_custom")
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0.0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        self
+    }
+}
+
+impl ValueFormatter for MissRatio {
+    fn scale_values(&self, _typical_value: f64, values: &mut [f64]) -> &'static str {
+        for value in values.iter_mut() {
+            *value *= 100.0;
+        }
+        "% misses"
+    }
+
+    fn scale_throughputs(
+        &self,
+        typical_value: f64,
+        _throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        self.scale_values(typical_value, values)
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "miss ratio"
+    }
+}
+
+fn bench_miss_ratio(criterion: &mut Criterion<MissRatio>) {
+    for (workload, trace) in workloads().iter() {
+        let mut group = criterion.benchmark_group(format!("Page cache miss ratio: {}", workload));
+        // A replay misses the same lookups every time, so a few samples are enough.
+        group.sample_size(10).sampling_mode(SamplingMode::Flat);
+
+        for (name, new_cache) in caches() {
+            group.bench_function(BenchmarkId::from_parameter(name), |b| {
+                b.iter_custom(|iters| {
+                    (0..iters)
+                        .map(|_| {
+                            let mut cache = new_cache();
+                            replay(cache.as_mut(), trace);
+                            1.0 - cache.stats().hit_ratio()
+                        })
+                        .sum()
+                });
+            });
+        }
+
+        group.finish();
+    }
+}
+
+fn bench(criterion: &mut Criterion) {
+    for (workload, trace) in workloads().iter() {
+        let mut group = criterion.benchmark_group(format!("Page cache: {}", workload));
+        group.throughput(Throughput::Elements(trace.len() as u64));
+
+        for (name, new_cache) in caches() {
+            group.bench_function(BenchmarkId::from_parameter(name), |b| {
+                b.iter(|| {
+                    let mut cache = new_cache();
+                    replay(cache.as_mut(), black_box(trace));
+                    black_box(cache.stats())
+                });
+            });
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = bench
+}
+
+criterion_group! {
+    name = miss_ratio_benches;
+    // Every sample of a miss ratio is the same, which the plots can't show.
+    config = Criterion::default()
+        .with_measurement(MissRatio)
+        .without_plots()
+        .warm_up_time(Duration::from_millis(500))
+        .measurement_time(Duration::from_secs(1));
+    targets = bench_miss_ratio
+}
+
+criterion_main!(benches, miss_ratio_benches);
@@ -0,0 +1,136 @@
+//! Busy handling for the WAL locks.
+//!
+//! When a statement cannot take the read or write lock because another connection holds it,
+//! the connection's [BusyHandler] decides whether the statement waits and tries again or
+//! returns [crate::StepResult::Busy] to the caller. Statements that wait yield with
+//! [crate::StepResult::IO] after the delay, and checkpoints and WAL frame insertion retry in
+//! place. Delays are waited out with [crate::IO::sleep], which the simulator implements by
+//! advancing its [crate::Clock], so the retry schedule is deterministic there.
+
+use std::time::Duration;
+
+/// Delays between retries of the handler installed by a busy timeout. This is the schedule
+/// used by SQLite's default busy handler.
+const DELAYS: [Duration; 12] = [
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(15),
+    Duration::from_millis(20),
+    Duration::from_millis(25),
+    Duration::from_millis(25),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+];
+
+/// A user supplied busy handler. It is called with the number of times it has already been
+/// called for the same lock and returns `true` to retry, or `false` to give up.
+pub type BusyCallback = dyn Fn(u32) -> bool;
+
+#[derive(Default)]
+pub enum BusyHandler {
+    /// Give up as soon as a lock is busy.
+    #[default]
+    None,
+    /// Retry with backoff until the total time spent waiting reaches the timeout.
+    Timeout(Duration),
+    /// Ask a callback whether to retry.
+    Callback(Box<BusyCallback>),
+}
+
+impl BusyHandler {
+    /// Returns how long to wait before retrying a busy lock for the `count + 1`th time, or
+    /// `None` if the caller should give up.
+    pub(crate) fn retry_delay(&self, count: u32) -> Option<Duration> {
+        match self {
+            BusyHandler::None => None,
+            BusyHandler::Timeout(timeout) => {
+                let count = count as usize;
+                let (delay, waited) = match DELAYS.get(count) {
+                    Some(delay) => (*delay, DELAYS[..count].iter().sum::<Duration>()),
+                    None => {
+                        let last = DELAYS[DELAYS.len() - 1];
+                        let extra = last * (count - DELAYS.len()) as u32;
+                        (last, DELAYS.iter().sum::<Duration>() + extra)
+                    }
+                };
+                let remaining = timeout.saturating_sub(waited);
+                if remaining.is_zero() {
+                    return None;
+                }
+                Some(delay.min(remaining))
+            }
+            BusyHandler::Callback(callback) => callback(count).then_some(Duration::ZERO),
+        }
+    }
+
+    /// The timeout of a handler installed by a busy timeout, zero for any other handler.
+    pub(crate) fn timeout(&self) -> Duration {
+        match self {
+            BusyHandler::Timeout(timeout) => *timeout,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn total_wait(handler: &BusyHandler) -> (u32, Duration) {
+        let mut count = 0;
+        let mut total = Duration::ZERO;
+        while let Some(delay) = handler.retry_delay(count) {
+            count += 1;
+            total += delay;
+        }
+        (count, total)
+    }
+
+    #[test]
+    fn test_no_handler_gives_up() {
+        assert_eq!(BusyHandler::None.retry_delay(0), None);
+    }
+
+    #[test]
+    fn test_timeout_waits_for_exactly_the_timeout() {
+        let handler = BusyHandler::Timeout(Duration::from_millis(100));
+        assert_eq!(handler.retry_delay(0), Some(Duration::from_millis(1)));
+        assert_eq!(handler.retry_delay(3), Some(Duration::from_millis(10)));
+        let (count, total) = total_wait(&handler);
+        assert_eq!(count, 8);
+        assert_eq!(total, Duration::from_millis(100));
+
+        let handler = BusyHandler::Timeout(Duration::from_millis(2000));
+        let (_, total) = total_wait(&handler);
+        assert_eq!(total, Duration::from_millis(2000));
+        assert_eq!(handler.retry_delay(20), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_zero_timeout_gives_up() {
+        let handler = BusyHandler::Timeout(Duration::ZERO);
+        assert_eq!(handler.retry_delay(0), None);
+    }
+
+    #[test]
+    fn test_callback_decides_retries() {
+        let calls = Rc::new(Cell::new(0));
+        let handler = BusyHandler::Callback(Box::new({
+            let calls = calls.clone();
+            move |count| {
+                calls.set(calls.get() + 1);
+                count < 3
+            }
+        }));
+        let (count, total) = total_wait(&handler);
+        assert_eq!(count, 3);
+        assert_eq!(total, Duration::ZERO);
+        assert_eq!(calls.get(), 4);
+    }
+}
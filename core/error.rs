@@ -57,6 +57,12 @@ pub enum LimboError {
     ReadOnly,
     #[error("Database is busy")]
     Busy,
+    #[error("Out of memory")]
+    OutOfMemory,
+    #[error("Database or disk is full")]
+    DatabaseFull,
+    #[error("Blob handle has expired: the row was deleted or modified")]
+    BlobExpired,
 }
 
 #[macro_export]
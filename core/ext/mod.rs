@@ -99,6 +99,10 @@ impl Database {
             "syscall" => Arc::new(SyscallIO::new()?),
             #[cfg(all(target_os = "linux", feature = "io_uring"))]
             "io_uring" => Arc::new(UringIO::new()?),
+            #[cfg(feature = "compression")]
+            "compressed" => Arc::new(crate::CompressedIO::new(
+                Arc::new(crate::PlatformIO::new()?),
+            )),
             other => match get_vfs_modules().iter().find(|v| v.0 == vfs) {
                 Some((_, vfs)) => vfs.clone(),
                 None => {
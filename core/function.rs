@@ -316,6 +316,7 @@ pub enum ScalarFunc {
     StrfTime,
     Printf,
     Likely,
+    Unlikely,
     TimeDiff,
     Likelihood,
 }
@@ -373,6 +374,7 @@ impl ScalarFunc {
             ScalarFunc::StrfTime => false,
             ScalarFunc::Printf => false,
             ScalarFunc::Likely => true,
+            ScalarFunc::Unlikely => true,
             ScalarFunc::TimeDiff => false,
             ScalarFunc::Likelihood => true,
         }
@@ -432,6 +434,7 @@ impl Display for ScalarFunc {
             Self::StrfTime => "strftime".to_string(),
             Self::Printf => "printf".to_string(),
             Self::Likely => "likely".to_string(),
+            Self::Unlikely => "unlikely".to_string(),
             Self::TimeDiff => "timediff".to_string(),
             Self::Likelihood => "likelihood".to_string(),
         };
@@ -723,6 +726,7 @@ impl Func {
             "sqlite_source_id" => Ok(Self::Scalar(ScalarFunc::SqliteSourceId)),
             "replace" => Ok(Self::Scalar(ScalarFunc::Replace)),
             "likely" => Ok(Self::Scalar(ScalarFunc::Likely)),
+            "unlikely" => Ok(Self::Scalar(ScalarFunc::Unlikely)),
             "likelihood" => Ok(Self::Scalar(ScalarFunc::Likelihood)),
             #[cfg(feature = "json")]
             "json" => Ok(Self::Json(JsonFunc::Json)),
@@ -0,0 +1,56 @@
+//! Hooks that let an application observe the changes made through a connection, e.g. to
+//! invalidate caches. Hooks run synchronously on the thread stepping the statement and must not
+//! use the connection that invoked them.
+
+use crate::schema::TEMP_DB;
+
+/// The kind of row change reported to an update hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Called after a row of a rowid table is inserted, updated or deleted, with the kind of change,
+/// the database name, the table name and the rowid of the row.
+pub type UpdateHook = dyn Fn(UpdateKind, &str, &str, i64);
+
+/// Called when a write transaction is about to commit. Returning `true` rolls the transaction
+/// back instead.
+pub type CommitHook = dyn Fn() -> bool;
+
+/// Called after a transaction is rolled back.
+pub type RollbackHook = dyn Fn();
+
+#[derive(Default)]
+pub(crate) struct Hooks {
+    pub(crate) update: Option<Box<UpdateHook>>,
+    pub(crate) commit: Option<Box<CommitHook>>,
+    pub(crate) rollback: Option<Box<RollbackHook>>,
+}
+
+impl Hooks {
+    /// Reports a row change in database `db` to the update hook. Changes to the internal `sqlite_`
+    /// tables and to tables without a name, i.e. ephemeral tables, are not reported.
+    pub(crate) fn row_changed(&self, kind: UpdateKind, db: usize, table_name: &str, rowid: i64) {
+        if table_name.is_empty() || table_name.starts_with("sqlite_") {
+            return;
+        }
+        if let Some(hook) = &self.update {
+            let db_name = if db == TEMP_DB { "temp" } else { "main" };
+            hook(kind, db_name, table_name, rowid);
+        }
+    }
+
+    /// Asks the commit hook whether the transaction may commit.
+    pub(crate) fn allow_commit(&self) -> bool {
+        self.commit.as_ref().is_none_or(|hook| !hook())
+    }
+
+    pub(crate) fn rolled_back(&self) {
+        if let Some(hook) = &self.rollback {
+            hook();
+        }
+    }
+}
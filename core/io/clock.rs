@@ -1,9 +1,22 @@
+use std::time::Duration;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Instant {
     pub secs: i64,
     pub micros: u32,
 }
 
+impl Instant {
+    /// Returns the instant `duration` after this one.
+    pub fn add_duration(&self, duration: Duration) -> Instant {
+        let micros = self.micros as u64 + duration.subsec_micros() as u64;
+        Instant {
+            secs: self.secs + duration.as_secs() as i64 + (micros / 1_000_000) as i64,
+            micros: (micros % 1_000_000) as u32,
+        }
+    }
+}
+
 pub trait Clock {
     fn now(&self) -> Instant;
 }
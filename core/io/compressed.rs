@@ -0,0 +1,1117 @@
+//! Transparent page compression.
+//!
+//! [CompressedIO] wraps another [IO] and stores database files compressed with LZ4, while the
+//! database still reads and writes them as regular SQLite files. The logical file is split into
+//! blocks of [BLOCK_SIZE] bytes, the default page size, and every block is compressed on its
+//! own and stored in a run of sectors of the underlying file:
+//!
+//! | sector | contents                                                              |
+//! |--------|-----------------------------------------------------------------------|
+//! | 0, 1   | two copies of the header: block size, logical size and page map       |
+//! | 2..    | compressed blocks and the page map, in no particular order           |
+//!
+//! The page map holds the location and the compressed length of every block. Blocks are copied
+//! on write: a new version of a block always goes to free sectors, and the sectors of the
+//! previous version are only freed once a page map that no longer refers to them is durable.
+//!
+//! On every sync the page map is written to new sectors and, once it is durable, a header
+//! pointing to it replaces the older of the two copies. The headers carry a generation and a
+//! checksum, and the newest valid one is used when the file is opened, so a crash at any point
+//! leaves a consistent page map in place.
+//!
+//! Only database files are compressed. WAL and journal files, recognized by their suffix, are
+//! passed through to the underlying IO: they are appended to frame by frame and are emptied by
+//! checkpoints anyway.
+use super::{Buffer, Completion, File, MemoryIO, OpenFlags, ReadCompletion, IO};
+use super::{SyncCompletion, WriteCompletion};
+use crate::io::clock::{Clock, Instant};
+use crate::{LimboError, Result};
+use parking_lot::Mutex;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tracing::debug;
+
+/// Size of the blocks the logical file is compressed by.
+pub const BLOCK_SIZE: usize = 4096;
+/// Allocation unit of the underlying file.
+const SECTOR_SIZE: usize = 512;
+const MAGIC: &[u8; 16] = b"limbo compressed";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 64;
+/// Number of sectors holding the copies of the header.
+const HEADER_SECTORS: u64 = 2;
+const MAP_ENTRY_SIZE: usize = 16;
+/// Flag of a block that did not compress and is stored as is.
+const FLAG_RAW: u32 = 1;
+/// Result passed to a completion when the underlying IO could not be started, -EIO.
+const IO_ERROR: i32 = -5;
+
+pub struct CompressedIO {
+    inner: Arc<dyn IO>,
+    /// Files that are open, so that a file opened twice shares its page map.
+    files: Mutex<HashMap<String, Weak<CompressedFile>>>,
+}
+
+/// Space used by a compressed file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Size of the file as seen by the database.
+    pub logical_bytes: u64,
+    /// Total size of the compressed blocks.
+    pub compressed_bytes: u64,
+    /// Size of the underlying file, including the headers, the page map and free sectors.
+    pub file_bytes: u64,
+}
+
+impl CompressionStats {
+    /// Returns the ratio of the logical size to the size of the underlying file.
+    pub fn ratio(&self) -> f64 {
+        if self.file_bytes == 0 {
+            return 1.0;
+        }
+        self.logical_bytes as f64 / self.file_bytes as f64
+    }
+}
+
+impl CompressedIO {
+    pub fn new(inner: Arc<dyn IO>) -> Self {
+        debug!("Using IO backend 'compressed'");
+        Self {
+            inner,
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the space used by the compressed file at `path`, if it is open.
+    pub fn stats(&self, path: &str) -> Result<Option<CompressionStats>> {
+        let file = self.files.lock().get(path).and_then(Weak::upgrade);
+        file.map(|file| file.stats()).transpose()
+    }
+}
+
+impl Clock for CompressedIO {
+    fn now(&self) -> Instant {
+        self.inner.now()
+    }
+}
+
+impl IO for CompressedIO {
+    fn open_file(&self, path: &str, flags: OpenFlags, direct: bool) -> Result<Arc<dyn File>> {
+        if path.ends_with("-wal") || path.ends_with("-journal") {
+            return self.inner.open_file(path, flags, direct);
+        }
+        let mut files = self.files.lock();
+        if let Some(file) = files.get(path).and_then(Weak::upgrade) {
+            return Ok(file);
+        }
+        let file = self.inner.open_file(path, flags, direct)?;
+        let file = Arc::new(CompressedFile::open(self.inner.clone(), file)?);
+        files.retain(|_, file| file.strong_count() > 0);
+        files.insert(path.to_string(), Arc::downgrade(&file));
+        Ok(file)
+    }
+
+    fn run_once(&self) -> Result<()> {
+        self.inner.run_once()
+    }
+
+    fn wait_for_completion(&self, c: Arc<Completion>) -> Result<()> {
+        while !c.is_completed() {
+            self.run_once()?;
+        }
+        Ok(())
+    }
+
+    fn generate_random_number(&self) -> i64 {
+        self.inner.generate_random_number()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.inner.sleep(duration)
+    }
+
+    fn get_memory_io(&self) -> Arc<MemoryIO> {
+        self.inner.get_memory_io()
+    }
+}
+
+/// Location of a compressed block in the underlying file.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    sector: u64,
+    len: u32,
+    flags: u32,
+}
+
+impl Slot {
+    fn sectors(&self) -> u64 {
+        sectors_for(self.len as usize)
+    }
+
+    fn pos(&self) -> usize {
+        self.sector as usize * SECTOR_SIZE
+    }
+}
+
+fn sectors_for(len: usize) -> u64 {
+    len.div_ceil(SECTOR_SIZE) as u64
+}
+
+/// Decodes a block as it is stored in its slot.
+fn decode_block(data: &[u8], flags: u32, block_size: usize) -> Result<Vec<u8>> {
+    if flags & FLAG_RAW != 0 {
+        return Ok(data.to_vec());
+    }
+    let block = lz4_flex::block::decompress(data, block_size)
+        .map_err(|e| LimboError::Corrupt(format!("failed to decompress block: {}", e)))?;
+    if block.len() != block_size {
+        return Err(LimboError::Corrupt(
+            "decompressed block has the wrong size".to_string(),
+        ));
+    }
+    Ok(block)
+}
+
+/// A copy of the header, which is stored in the sector of the parity of its generation.
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    generation: u64,
+    block_size: u32,
+    logical_size: u64,
+    map_slot: Option<Slot>,
+}
+
+impl Header {
+    fn encode(&self) -> Vec<u8> {
+        let mut header = vec![0; HEADER_SIZE];
+        header[0..16].copy_from_slice(MAGIC);
+        header[16..20].copy_from_slice(&VERSION.to_be_bytes());
+        header[20..24].copy_from_slice(&self.block_size.to_be_bytes());
+        header[24..32].copy_from_slice(&self.logical_size.to_be_bytes());
+        if let Some(slot) = self.map_slot {
+            header[32..40].copy_from_slice(&slot.sector.to_be_bytes());
+            header[40..44].copy_from_slice(&slot.len.to_be_bytes());
+        }
+        header[48..56].copy_from_slice(&self.generation.to_be_bytes());
+        let checksum = header_checksum(&header[..56]);
+        header[56..64].copy_from_slice(&checksum.to_be_bytes());
+        header
+    }
+
+    /// Returns the header stored in `header`, or None if it is not a complete copy of one.
+    fn decode(header: &[u8]) -> Result<Option<Self>> {
+        if header.len() < HEADER_SIZE
+            || &header[0..16] != MAGIC
+            || header_checksum(&header[..56]).to_be_bytes() != header[56..64]
+        {
+            return Ok(None);
+        }
+        let version = u32::from_be_bytes(header[16..20].try_into().unwrap());
+        if version != VERSION {
+            return Err(LimboError::Corrupt(format!(
+                "unsupported compressed file version {}",
+                version
+            )));
+        }
+        let map_slot = Slot {
+            sector: u64::from_be_bytes(header[32..40].try_into().unwrap()),
+            len: u32::from_be_bytes(header[40..44].try_into().unwrap()),
+            flags: 0,
+        };
+        Ok(Some(Self {
+            generation: u64::from_be_bytes(header[48..56].try_into().unwrap()),
+            block_size: u32::from_be_bytes(header[20..24].try_into().unwrap()),
+            logical_size: u64::from_be_bytes(header[24..32].try_into().unwrap()),
+            map_slot: (map_slot.len > 0).then_some(map_slot),
+        }))
+    }
+
+    fn pos(&self) -> usize {
+        (self.generation % HEADER_SECTORS) as usize * SECTOR_SIZE
+    }
+}
+
+/// The checksum of the WAL frames, over the big-endian words of `data`.
+fn header_checksum(data: &[u8]) -> u64 {
+    let (mut s0, mut s1) = (0u32, 0u32);
+    for words in data.chunks_exact(8) {
+        let v0 = u32::from_be_bytes(words[0..4].try_into().unwrap());
+        let v1 = u32::from_be_bytes(words[4..8].try_into().unwrap());
+        s0 = s0.wrapping_add(v0.wrapping_add(s1));
+        s1 = s1.wrapping_add(v1.wrapping_add(s0));
+    }
+    ((s0 as u64) << 32) | s1 as u64
+}
+
+/// A write of a block, which waits for the writes of the block issued before it.
+type BlockWrite = Box<dyn FnOnce()>;
+/// Continuation of an IO, called with its result.
+type Then = Box<dyn FnOnce(i32)>;
+
+struct FileState {
+    block_size: usize,
+    logical_size: u64,
+    map: Vec<Option<Slot>>,
+    /// Sectors of the page map the newest header points to.
+    map_slot: Option<Slot>,
+    /// Generation of the newest header.
+    generation: u64,
+    /// Free runs of sectors, by first sector.
+    free: BTreeMap<u64, u64>,
+    /// Runs of sectors that the durable page map still refers to, freed by the next sync.
+    pending_free: Vec<(u64, u64)>,
+    /// First sector past the allocated ones.
+    end_sector: u64,
+    dirty: bool,
+    /// Blocks being written, with the writes of each that wait for the one in progress.
+    /// Writes of a block are serialized, since a partial write reads the block it changes.
+    busy_blocks: HashMap<usize, VecDeque<BlockWrite>>,
+    /// Number of block writes that are queued or in progress.
+    writes_in_flight: usize,
+    /// Syncs waiting for the block writes in flight to complete.
+    syncs_waiting: Vec<Box<dyn FnOnce()>>,
+}
+
+impl FileState {
+    fn new() -> Self {
+        Self {
+            block_size: BLOCK_SIZE,
+            logical_size: 0,
+            map: Vec::new(),
+            map_slot: None,
+            generation: 0,
+            free: BTreeMap::new(),
+            pending_free: Vec::new(),
+            end_sector: HEADER_SECTORS,
+            dirty: false,
+            busy_blocks: HashMap::new(),
+            writes_in_flight: 0,
+            syncs_waiting: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self, sectors: u64) -> u64 {
+        let found = self
+            .free
+            .iter()
+            .find(|(_, count)| **count >= sectors)
+            .map(|(start, count)| (*start, *count));
+        match found {
+            Some((start, count)) => {
+                self.free.remove(&start);
+                if count > sectors {
+                    self.free.insert(start + sectors, count - sectors);
+                }
+                start
+            }
+            None => {
+                let start = self.end_sector;
+                self.end_sector += sectors;
+                start
+            }
+        }
+    }
+
+    fn release(&mut self, mut start: u64, mut count: u64) {
+        if count == 0 {
+            return;
+        }
+        if let Some((&prev, &prev_count)) = self.free.range(..start).next_back() {
+            if prev + prev_count == start {
+                self.free.remove(&prev);
+                start = prev;
+                count += prev_count;
+            }
+        }
+        if let Some(next_count) = self.free.remove(&(start + count)) {
+            count += next_count;
+        }
+        self.free.insert(start, count);
+    }
+}
+
+/// Calls `done` with the first error, or 0, once `tick` was called `count` times.
+struct Countdown {
+    remaining: Cell<usize>,
+    result: Cell<i32>,
+    done: RefCell<Option<Then>>,
+}
+
+impl Countdown {
+    fn new(count: usize, done: impl FnOnce(i32) + 'static) -> Rc<Self> {
+        Rc::new(Self {
+            remaining: Cell::new(count),
+            result: Cell::new(0),
+            done: RefCell::new(Some(Box::new(done))),
+        })
+    }
+
+    fn tick(&self, result: i32) {
+        if result < 0 && self.result.get() >= 0 {
+            self.result.set(result);
+        }
+        self.remaining.set(self.remaining.get() - 1);
+        if self.remaining.get() == 0 {
+            let done = self.done.borrow_mut().take();
+            if let Some(done) = done {
+                done(self.result.get());
+            }
+        }
+    }
+}
+
+/// Wraps `f` to be called by a completion, which only calls it once.
+fn once<T>(f: impl FnOnce(T) + 'static) -> impl Fn(T) {
+    let f = RefCell::new(Some(f));
+    move |arg| {
+        let f = f.borrow_mut().take();
+        if let Some(f) = f {
+            f(arg)
+        }
+    }
+}
+
+/// A page map being made durable by a sync.
+struct Persist {
+    header: Header,
+    map: Vec<u8>,
+    /// Runs of sectors to free once the header is durable.
+    freed: Vec<(u64, u64)>,
+}
+
+/// The part of a [CompressedFile] that its completions refer to.
+struct Shared {
+    file: Arc<dyn File>,
+    state: RefCell<FileState>,
+}
+
+impl Shared {
+    /// Reads `len` bytes at `pos` of the underlying file and calls `then` with them.
+    fn read_then(
+        &self,
+        pos: usize,
+        len: usize,
+        then: impl FnOnce(Vec<u8>) + 'static,
+    ) -> Result<()> {
+        let drop_fn = Rc::new(|_buf| {});
+        #[allow(clippy::arc_with_non_send_sync)]
+        let buf = Arc::new(RefCell::new(Buffer::allocate(len, drop_fn)));
+        let then = once(then);
+        let c = Completion::Read(ReadCompletion::new(
+            buf,
+            Box::new(move |buf| then(buf.borrow().as_slice().to_vec())),
+        ));
+        self.file.pread(pos, Arc::new(c))
+    }
+
+    /// Writes `data` at `pos` of the underlying file and calls `then` with the result.
+    fn write_then(&self, pos: usize, data: Vec<u8>, then: impl FnOnce(i32) + 'static) {
+        let drop_fn = Rc::new(|_buf| {});
+        #[allow(clippy::arc_with_non_send_sync)]
+        let buf = Arc::new(RefCell::new(Buffer::new(Pin::new(data), drop_fn)));
+        let then = Rc::new(once(then));
+        let c = {
+            let then = then.clone();
+            Completion::Write(WriteCompletion::new(Box::new(move |res| then(res))))
+        };
+        if let Err(e) = self.file.pwrite(pos, buf, Arc::new(c)) {
+            tracing::error!("failed to write to compressed file: {}", e);
+            then(IO_ERROR);
+        }
+    }
+
+    /// Syncs the underlying file and calls `then` with the result.
+    fn sync_then(&self, then: impl FnOnce(i32) + 'static) {
+        let then = Rc::new(once(then));
+        let c = {
+            let then = then.clone();
+            Completion::Sync(SyncCompletion::new(Box::new(move |res| then(res))))
+        };
+        if let Err(e) = self.file.sync(Arc::new(c)) {
+            tracing::error!("failed to sync compressed file: {}", e);
+            then(IO_ERROR);
+        }
+    }
+
+    /// Writes `data` at `offset` of `block` once the writes of the block issued before are done,
+    /// and calls `done` with the result.
+    fn write_block(
+        self: &Rc<Self>,
+        block: usize,
+        offset: usize,
+        data: Vec<u8>,
+        done: impl FnOnce(i32) + 'static,
+    ) {
+        let write: BlockWrite = {
+            let shared = self.clone();
+            Box::new(move || shared.start_block_write(block, offset, data, done))
+        };
+        {
+            let mut state = self.state.borrow_mut();
+            state.writes_in_flight += 1;
+            match state.busy_blocks.get_mut(&block) {
+                Some(queue) => {
+                    queue.push_back(write);
+                    return;
+                }
+                None => {
+                    state.busy_blocks.insert(block, VecDeque::new());
+                }
+            }
+        }
+        write();
+    }
+
+    fn start_block_write(
+        self: &Rc<Self>,
+        block: usize,
+        offset: usize,
+        data: Vec<u8>,
+        done: impl FnOnce(i32) + 'static,
+    ) {
+        let (block_size, slot) = {
+            let state = self.state.borrow();
+            (state.block_size, state.map.get(block).copied().flatten())
+        };
+        if offset == 0 && data.len() == block_size {
+            return self.store_block(block, data, done);
+        }
+        let Some(slot) = slot else {
+            let mut block_data = vec![0; block_size];
+            block_data[offset..offset + data.len()].copy_from_slice(&data);
+            return self.store_block(block, block_data, done);
+        };
+        // A partial write changes the current contents of the block.
+        let done = Rc::new(RefCell::new(Some(done)));
+        let result = {
+            let shared = self.clone();
+            let done = done.clone();
+            self.read_then(slot.pos(), slot.len as usize, move |raw| {
+                let Some(done) = done.borrow_mut().take() else {
+                    return;
+                };
+                match decode_block(&raw, slot.flags, block_size) {
+                    Ok(mut block_data) => {
+                        block_data[offset..offset + data.len()].copy_from_slice(&data);
+                        shared.store_block(block, block_data, done);
+                    }
+                    Err(e) => {
+                        tracing::error!("failed to read block {} of compressed file: {}", block, e);
+                        shared.finish_block_write(block, None, IO_ERROR, done);
+                    }
+                }
+            })
+        };
+        if let Err(e) = result {
+            tracing::error!("failed to read block {} of compressed file: {}", block, e);
+            if let Some(done) = done.borrow_mut().take() {
+                self.finish_block_write(block, None, IO_ERROR, done);
+            }
+        }
+    }
+
+    /// Writes the new contents of `block` to free sectors.
+    fn store_block(self: &Rc<Self>, block: usize, data: Vec<u8>, done: impl FnOnce(i32) + 'static) {
+        let compressed = lz4_flex::block::compress(&data);
+        let (payload, flags) = match compressed.len() < data.len() {
+            true => (compressed, 0),
+            false => (data, FLAG_RAW),
+        };
+        let len = payload.len() as u32;
+        let sector = self.state.borrow_mut().allocate(sectors_for(payload.len()));
+        let slot = Slot { sector, len, flags };
+        let shared = self.clone();
+        self.write_then(slot.pos(), payload, move |res| {
+            shared.finish_block_write(block, Some(slot), res, done)
+        });
+    }
+
+    /// Points the page map to the new version of `block` in `slot`, if it was written,
+    /// and starts the next write of the block.
+    fn finish_block_write(
+        &self,
+        block: usize,
+        slot: Option<Slot>,
+        res: i32,
+        done: impl FnOnce(i32),
+    ) {
+        let (next, syncs) = {
+            let mut state = self.state.borrow_mut();
+            if let Some(slot) = slot {
+                if res >= 0 {
+                    if state.map.len() <= block {
+                        state.map.resize(block + 1, None);
+                    }
+                    if let Some(old) = state.map[block].replace(slot) {
+                        state.pending_free.push((old.sector, old.sectors()));
+                    }
+                    state.dirty = true;
+                } else {
+                    state.release(slot.sector, slot.sectors());
+                }
+            }
+            let queue = state.busy_blocks.get_mut(&block).unwrap();
+            let next = queue.pop_front();
+            if next.is_none() {
+                state.busy_blocks.remove(&block);
+            }
+            state.writes_in_flight -= 1;
+            let syncs = match state.writes_in_flight {
+                0 => std::mem::take(&mut state.syncs_waiting),
+                _ => Vec::new(),
+            };
+            (next, syncs)
+        };
+        done(res);
+        if let Some(next) = next {
+            next();
+        }
+        for sync in syncs {
+            sync();
+        }
+    }
+
+    /// Makes the page map durable if it changed, and syncs the file otherwise.
+    fn persist(self: &Rc<Self>, done: impl FnOnce(i32) + 'static) {
+        let persist = {
+            let mut state = self.state.borrow_mut();
+            if !state.dirty {
+                None
+            } else {
+                let mut map = Vec::with_capacity(state.map.len() * MAP_ENTRY_SIZE);
+                for slot in &state.map {
+                    let slot = slot.unwrap_or(Slot {
+                        sector: 0,
+                        len: 0,
+                        flags: 0,
+                    });
+                    map.extend_from_slice(&slot.sector.to_be_bytes());
+                    map.extend_from_slice(&slot.len.to_be_bytes());
+                    map.extend_from_slice(&slot.flags.to_be_bytes());
+                }
+                let map_slot = (!map.is_empty()).then(|| Slot {
+                    sector: state.allocate(sectors_for(map.len())),
+                    len: map.len() as u32,
+                    flags: 0,
+                });
+                state.dirty = false;
+                Some(Persist {
+                    header: Header {
+                        generation: state.generation + 1,
+                        block_size: state.block_size as u32,
+                        logical_size: state.logical_size,
+                        map_slot,
+                    },
+                    map,
+                    freed: std::mem::take(&mut state.pending_free),
+                })
+            }
+        };
+        let Some(mut persist) = persist else {
+            return self.sync_then(done);
+        };
+        let map = std::mem::take(&mut persist.map);
+        let map_slot = persist.header.map_slot;
+        let shared = self.clone();
+        let write_header = move |res: i32| {
+            if res < 0 {
+                return shared.finish_persist(persist, res, done);
+            }
+            let header = persist.header.encode();
+            let shared2 = shared.clone();
+            shared.write_then(persist.header.pos(), header, move |res| {
+                if res < 0 {
+                    return shared2.finish_persist(persist, res, done);
+                }
+                let shared3 = shared2.clone();
+                shared2.sync_then(move |res| shared3.finish_persist(persist, res, done));
+            });
+        };
+        // The header may only point to the page map once the map and the blocks are durable.
+        match map_slot {
+            Some(slot) => {
+                let shared = self.clone();
+                self.write_then(slot.pos(), map, move |res| match res < 0 {
+                    true => write_header(res),
+                    false => shared.sync_then(write_header),
+                });
+            }
+            None => self.sync_then(write_header),
+        }
+    }
+
+    fn finish_persist(&self, persist: Persist, res: i32, done: impl FnOnce(i32)) {
+        {
+            let mut state = self.state.borrow_mut();
+            if res < 0 {
+                // The previous header is still the newest valid one: keep what it refers to.
+                state.dirty = true;
+                state.pending_free.extend(persist.freed);
+                if let Some(slot) = persist.header.map_slot {
+                    state.release(slot.sector, slot.sectors());
+                }
+            } else {
+                state.generation = persist.header.generation;
+                if let Some(old) = std::mem::replace(&mut state.map_slot, persist.header.map_slot) {
+                    state.release(old.sector, old.sectors());
+                }
+                for (sector, count) in persist.freed {
+                    state.release(sector, count);
+                }
+            }
+        }
+        done(res);
+    }
+}
+
+pub struct CompressedFile {
+    io: Arc<dyn IO>,
+    shared: Rc<Shared>,
+}
+unsafe impl Send for CompressedFile {}
+unsafe impl Sync for CompressedFile {}
+
+impl CompressedFile {
+    fn open(io: Arc<dyn IO>, file: Arc<dyn File>) -> Result<Self> {
+        let file = Self {
+            io,
+            shared: Rc::new(Shared {
+                file,
+                state: RefCell::new(FileState::new()),
+            }),
+        };
+        if file.shared.file.size()? > 0 {
+            file.load()?;
+        }
+        Ok(file)
+    }
+
+    /// Reads the newest header and its page map of an existing file.
+    fn load(&self) -> Result<()> {
+        let mut header: Option<Header> = None;
+        for copy in 0..HEADER_SECTORS as usize {
+            let Some(copy) = Header::decode(&self.read_at(copy * SECTOR_SIZE, HEADER_SIZE)?)?
+            else {
+                continue;
+            };
+            if header.is_none_or(|header| header.generation < copy.generation) {
+                header = Some(copy);
+            }
+        }
+        let Some(header) = header else {
+            return Err(LimboError::Corrupt(
+                "file is not a compressed database".to_string(),
+            ));
+        };
+        let mut state = self.shared.state.borrow_mut();
+        state.generation = header.generation;
+        state.block_size = header.block_size as usize;
+        state.logical_size = header.logical_size;
+        let mut used = Vec::new();
+        if let Some(map_slot) = header.map_slot {
+            let map = self.read_at(map_slot.pos(), map_slot.len as usize)?;
+            for entry in map.chunks_exact(MAP_ENTRY_SIZE) {
+                let slot = Slot {
+                    sector: u64::from_be_bytes(entry[0..8].try_into().unwrap()),
+                    len: u32::from_be_bytes(entry[8..12].try_into().unwrap()),
+                    flags: u32::from_be_bytes(entry[12..16].try_into().unwrap()),
+                };
+                if slot.sector == 0 {
+                    state.map.push(None);
+                } else {
+                    used.push((slot.sector, slot.sectors()));
+                    state.map.push(Some(slot));
+                }
+            }
+            used.push((map_slot.sector, map_slot.sectors()));
+            state.map_slot = Some(map_slot);
+        }
+        // Everything between the used runs of sectors is free.
+        used.sort_unstable();
+        let mut next = HEADER_SECTORS;
+        for (sector, count) in used {
+            if sector < next {
+                return Err(LimboError::Corrupt(
+                    "overlapping blocks in compressed file".to_string(),
+                ));
+            }
+            state.release(next, sector - next);
+            next = sector + count;
+        }
+        state.end_sector = next;
+        Ok(())
+    }
+
+    fn stats(&self) -> Result<CompressionStats> {
+        let state = self.shared.state.borrow();
+        Ok(CompressionStats {
+            logical_bytes: state.logical_size,
+            compressed_bytes: state.map.iter().flatten().map(|s| s.len as u64).sum(),
+            file_bytes: self.shared.file.size()?,
+        })
+    }
+
+    /// Reads `len` bytes at `pos` of the underlying file, waiting for the read to complete.
+    /// Only used to open the file.
+    fn read_at(&self, pos: usize, len: usize) -> Result<Vec<u8>> {
+        let data = Rc::new(RefCell::new(None));
+        {
+            let data = data.clone();
+            self.shared
+                .read_then(pos, len, move |buf| *data.borrow_mut() = Some(buf))?;
+        }
+        loop {
+            if let Some(data) = data.borrow_mut().take() {
+                return Ok(data);
+            }
+            self.io.run_once()?;
+        }
+    }
+}
+
+impl Drop for CompressedFile {
+    fn drop(&mut self) {
+        // Not every write to the database file is followed by a sync, e.g. passive checkpoints
+        // leave it to the OS, so the page map of those writes is persisted on close.
+        {
+            let state = self.shared.state.borrow();
+            if !state.dirty && state.writes_in_flight == 0 {
+                return;
+            }
+        }
+        let c = Arc::new(Completion::Sync(SyncCompletion::new(Box::new(|res| {
+            if res < 0 {
+                tracing::error!("failed to persist page map of compressed file: {}", res);
+            }
+        }))));
+        if let Err(e) = self.sync(c.clone()) {
+            tracing::error!("failed to persist page map of compressed file: {}", e);
+            return;
+        }
+        while !c.is_completed() {
+            if let Err(e) = self.io.run_once() {
+                tracing::error!("failed to persist page map of compressed file: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+impl File for CompressedFile {
+    fn lock_file(&self, exclusive: bool) -> Result<()> {
+        self.shared.file.lock_file(exclusive)
+    }
+
+    fn unlock_file(&self) -> Result<()> {
+        self.shared.file.unlock_file()
+    }
+
+    fn pread(&self, pos: usize, c: Arc<Completion>) -> Result<()> {
+        let (block_size, read_len, slots) = {
+            let state = self.shared.state.borrow();
+            let read_len = c
+                .as_read()
+                .buf()
+                .len()
+                .min((state.logical_size as usize).saturating_sub(pos));
+            let mut slots = Vec::new();
+            let mut offset = pos;
+            while offset < pos + read_len {
+                let block = offset / state.block_size;
+                let block_offset = offset % state.block_size;
+                let n = (pos + read_len - offset).min(state.block_size - block_offset);
+                let slot = state.map.get(block).copied().flatten();
+                slots.push((slot, block_offset, n, offset - pos));
+                offset += n;
+            }
+            (state.block_size, read_len, slots)
+        };
+        // Blocks that were never written read as zeroes, the others once they are read.
+        c.as_read().buf_mut().as_mut_slice()[..read_len].fill(0);
+        let countdown = {
+            let c = c.clone();
+            Countdown::new(slots.len() + 1, move |_| c.complete(read_len as i32))
+        };
+        for (slot, block_offset, n, buf_offset) in slots {
+            let Some(slot) = slot else {
+                countdown.tick(0);
+                continue;
+            };
+            let c = c.clone();
+            let countdown = countdown.clone();
+            self.shared
+                .read_then(slot.pos(), slot.len as usize, move |raw| {
+                    match decode_block(&raw, slot.flags, block_size) {
+                        Ok(block) => c.as_read().buf_mut().as_mut_slice()
+                            [buf_offset..buf_offset + n]
+                            .copy_from_slice(&block[block_offset..block_offset + n]),
+                        Err(e) => tracing::error!("failed to read compressed file: {}", e),
+                    }
+                    countdown.tick(0);
+                })?;
+        }
+        countdown.tick(0);
+        Ok(())
+    }
+
+    fn pwrite(&self, pos: usize, buffer: Arc<RefCell<Buffer>>, c: Arc<Completion>) -> Result<()> {
+        let data = buffer.borrow().as_slice().to_vec();
+        let block_size = {
+            let mut state = self.shared.state.borrow_mut();
+            state.logical_size = state.logical_size.max((pos + data.len()) as u64);
+            state.block_size
+        };
+        let mut chunks = Vec::new();
+        let mut offset = pos;
+        while offset < pos + data.len() {
+            let block_offset = offset % block_size;
+            let n = (pos + data.len() - offset).min(block_size - block_offset);
+            chunks.push((offset / block_size, block_offset, offset - pos, n));
+            offset += n;
+        }
+        let write_len = data.len() as i32;
+        let countdown = Countdown::new(chunks.len() + 1, move |res| {
+            c.complete(if res < 0 { res } else { write_len })
+        });
+        for (block, block_offset, buf_offset, n) in chunks {
+            let countdown = countdown.clone();
+            self.shared.write_block(
+                block,
+                block_offset,
+                data[buf_offset..buf_offset + n].to_vec(),
+                move |res| countdown.tick(res),
+            );
+        }
+        countdown.tick(0);
+        Ok(())
+    }
+
+    fn sync(&self, c: Arc<Completion>) -> Result<()> {
+        // The page map is persisted once the writes issued before the sync are done.
+        let persist = {
+            let shared = self.shared.clone();
+            move || shared.persist(move |res| c.complete(res))
+        };
+        {
+            let mut state = self.shared.state.borrow_mut();
+            if state.writes_in_flight > 0 {
+                state.syncs_waiting.push(Box::new(persist));
+                return Ok(());
+            }
+        }
+        persist();
+        Ok(())
+    }
+
+    fn size(&self) -> Result<u64> {
+        Ok(self.shared.state.borrow().logical_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PlatformIO;
+
+    fn open(path: &str) -> (Arc<CompressedIO>, Arc<dyn File>) {
+        let io = Arc::new(CompressedIO::new(Arc::new(PlatformIO::new().unwrap())));
+        let file = io.open_file(path, OpenFlags::Create, false).unwrap();
+        (io, file)
+    }
+
+    fn write(io: &CompressedIO, file: &Arc<dyn File>, pos: usize, data: &[u8]) {
+        let buf = Arc::new(RefCell::new(Buffer::new(
+            Pin::new(data.to_vec()),
+            Rc::new(|_| {}),
+        )));
+        let c = Arc::new(Completion::Write(WriteCompletion::new(Box::new(|_| {}))));
+        file.pwrite(pos, buf, c.clone()).unwrap();
+        io.wait_for_completion(c).unwrap();
+    }
+
+    fn read(io: &CompressedIO, file: &Arc<dyn File>, pos: usize, len: usize) -> Vec<u8> {
+        let buf = Arc::new(RefCell::new(Buffer::allocate(len, Rc::new(|_| {}))));
+        let c = Arc::new(Completion::Read(ReadCompletion::new(
+            buf.clone(),
+            Box::new(|_| {}),
+        )));
+        file.pread(pos, c.clone()).unwrap();
+        io.wait_for_completion(c).unwrap();
+        let data = buf.borrow().as_slice().to_vec();
+        data
+    }
+
+    fn sync(io: &CompressedIO, file: &Arc<dyn File>) {
+        let c = Arc::new(Completion::Sync(SyncCompletion::new(Box::new(|_| {}))));
+        file.sync(c.clone()).unwrap();
+        io.wait_for_completion(c).unwrap();
+    }
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        getrandom::getrandom(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_unaligned_writes_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let path = path.to_str().unwrap();
+        let mut expected = vec![0; 3 * BLOCK_SIZE + 100];
+        {
+            let (io, file) = open(path);
+            let text = b"compressible ".repeat(400);
+            write(&io, &file, 0, &text[..BLOCK_SIZE]);
+            expected[..BLOCK_SIZE].copy_from_slice(&text[..BLOCK_SIZE]);
+            // Spans the end of a block and a hole.
+            let random = random_bytes(1000);
+            write(&io, &file, 3 * BLOCK_SIZE - 900, &random);
+            expected[3 * BLOCK_SIZE - 900..3 * BLOCK_SIZE + 100].copy_from_slice(&random);
+            write(&io, &file, 10, b"patched");
+            expected[10..17].copy_from_slice(b"patched");
+            assert_eq!(file.size().unwrap(), expected.len() as u64);
+            assert_eq!(read(&io, &file, 0, expected.len()), expected);
+            sync(&io, &file);
+        }
+        let (io, file) = open(path);
+        assert_eq!(file.size().unwrap(), expected.len() as u64);
+        assert_eq!(read(&io, &file, 0, expected.len()), expected);
+        assert_eq!(
+            read(&io, &file, BLOCK_SIZE - 5, 10),
+            expected[BLOCK_SIZE - 5..BLOCK_SIZE + 5]
+        );
+        let stats = io.stats(path).unwrap().unwrap();
+        assert_eq!(stats.logical_bytes, expected.len() as u64);
+        assert!(stats.compressed_bytes < expected.len() as u64);
+    }
+
+    #[test]
+    fn test_moved_blocks_free_their_sectors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let path = path.to_str().unwrap();
+        let (io, file) = open(path);
+        let zeros = vec![0; BLOCK_SIZE];
+        let mut blocks = Vec::new();
+        let mut file_bytes = 0;
+        for _ in 0..4 {
+            // Every write moves its block to new sectors.
+            blocks = (0..8).map(|_| random_bytes(BLOCK_SIZE)).collect();
+            for i in 0..8 {
+                write(&io, &file, i * BLOCK_SIZE, &zeros);
+            }
+            sync(&io, &file);
+            for (i, block) in blocks.iter().enumerate() {
+                write(&io, &file, i * BLOCK_SIZE, block);
+            }
+            sync(&io, &file);
+            file_bytes = io.stats(path).unwrap().unwrap().file_bytes;
+        }
+        // Every round moves all blocks twice, without reusing the sectors freed by a sync the
+        // file would hold four copies of the random ones.
+        assert!(file_bytes < 3 * 8 * BLOCK_SIZE as u64);
+        drop(file);
+
+        let (io, file) = open(path);
+        for (i, block) in blocks.iter().enumerate() {
+            assert_eq!(&read(&io, &file, i * BLOCK_SIZE, BLOCK_SIZE), block);
+        }
+    }
+
+    #[test]
+    fn test_release_coalesces_free_sectors() {
+        let mut state = FileState::new();
+        state.end_sector = 1;
+        assert_eq!(state.allocate(2), 1);
+        assert_eq!(state.allocate(3), 3);
+        assert_eq!(state.allocate(1), 6);
+        state.release(1, 2);
+        state.release(6, 1);
+        state.release(3, 3);
+        assert_eq!(state.free, BTreeMap::from([(1, 6)]));
+        assert_eq!(state.allocate(4), 1);
+        assert_eq!(state.allocate(4), 7);
+        assert_eq!(state.allocate(2), 5);
+        assert!(state.free.is_empty());
+    }
+
+    #[test]
+    fn test_writes_before_sync_are_lost_on_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let path = path.to_str().unwrap();
+        let synced = random_bytes(2 * BLOCK_SIZE);
+        {
+            let (io, file) = open(path);
+            write(&io, &file, 0, &synced);
+            sync(&io, &file);
+            write(&io, &file, 0, &random_bytes(BLOCK_SIZE));
+            write(&io, &file, 100, b"lost");
+            // Crash: the page map of the last writes is never persisted.
+            std::mem::forget(file);
+        }
+        // The blocks were copied on write, so the synced ones are still in place.
+        let (io, file) = open(path);
+        assert_eq!(read(&io, &file, 0, synced.len()), synced);
+    }
+
+    #[test]
+    fn test_torn_header_falls_back_to_previous_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let path = path.to_str().unwrap();
+        let first = random_bytes(BLOCK_SIZE);
+        {
+            let (io, file) = open(path);
+            write(&io, &file, 0, &first);
+            sync(&io, &file);
+            write(&io, &file, 0, &random_bytes(BLOCK_SIZE));
+            sync(&io, &file);
+        }
+        {
+            // Tear the header of the second sync, which went to sector 0.
+            let io = PlatformIO::new().unwrap();
+            let raw = io.open_file(path, OpenFlags::None, false).unwrap();
+            let buf = Arc::new(RefCell::new(Buffer::new(
+                Pin::new(vec![0xff; 40]),
+                Rc::new(|_| {}),
+            )));
+            let c = Arc::new(Completion::Write(WriteCompletion::new(Box::new(|_| {}))));
+            raw.pwrite(20, buf, c.clone()).unwrap();
+            io.wait_for_completion(c).unwrap();
+        }
+        let (io, file) = open(path);
+        assert_eq!(read(&io, &file, 0, BLOCK_SIZE), first);
+    }
+
+    #[test]
+    fn test_writes_to_one_block_are_applied_in_order() {
+        let io = Arc::new(CompressedIO::new(Arc::new(MemoryIO::new())));
+        let file = io.open_file("test.db", OpenFlags::Create, false).unwrap();
+        let mut expected = vec![0; BLOCK_SIZE];
+        let mut completions = Vec::new();
+        // Partial writes of the same block read and replace it, so each waits for the previous.
+        for i in 0..8 {
+            let data = vec![i as u8 + 1; 1024];
+            let pos = i * 384;
+            expected[pos..pos + data.len()].copy_from_slice(&data);
+            let buf = Arc::new(RefCell::new(Buffer::new(Pin::new(data), Rc::new(|_| {}))));
+            let c = Arc::new(Completion::Write(WriteCompletion::new(Box::new(|_| {}))));
+            file.pwrite(pos, buf, c.clone()).unwrap();
+            completions.push(c);
+        }
+        let c = Arc::new(Completion::Sync(SyncCompletion::new(Box::new(|_| {}))));
+        file.sync(c.clone()).unwrap();
+        completions.push(c);
+        for c in completions {
+            io.wait_for_completion(c).unwrap();
+        }
+        expected.truncate(7 * 384 + 1024);
+        assert_eq!(read(&io, &file, 0, expected.len()), expected);
+    }
+}
@@ -123,6 +123,12 @@ impl File for GenericFile {
         let file = self.file.borrow();
         Ok(file.metadata().unwrap().len())
     }
+
+    fn truncate(&self, len: usize) -> Result<()> {
+        let file = self.file.borrow();
+        file.set_len(len as u64).map_err(LimboError::IOError)?;
+        Ok(())
+    }
 }
 
 impl Drop for GenericFile {
@@ -328,6 +328,11 @@ impl File for UringFile {
     fn size(&self) -> Result<u64> {
         Ok(self.file.metadata()?.len())
     }
+
+    fn truncate(&self, len: usize) -> Result<()> {
+        self.file.set_len(len as u64)?;
+        Ok(())
+    }
 }
 
 impl Drop for UringFile {
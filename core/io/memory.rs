@@ -169,6 +169,20 @@ impl File for MemoryFile {
     fn size(&self) -> Result<u64> {
         Ok(self.size.get() as u64)
     }
+
+    fn truncate(&self, len: usize) -> Result<()> {
+        if len >= self.size.get() {
+            return Ok(());
+        }
+        let pages = unsafe { &mut *self.pages.get() };
+        pages.retain(|page_no, _| page_no * PAGE_SIZE < len);
+        // Growing the file again must read zeros past `len`.
+        if let Some(page) = pages.get_mut(&(len / PAGE_SIZE)) {
+            page[len % PAGE_SIZE..].fill(0);
+        }
+        self.size.set(len);
+        Ok(())
+    }
 }
 
 impl Drop for MemoryFile {
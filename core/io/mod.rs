@@ -3,6 +3,7 @@ use bitflags::bitflags;
 use cfg_block::cfg_block;
 use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{
     cell::{Cell, Ref, RefCell, RefMut},
     fmt::Debug,
@@ -18,6 +19,30 @@ pub trait File: Send + Sync {
     fn pwrite(&self, pos: usize, buffer: Arc<RefCell<Buffer>>, c: Arc<Completion>) -> Result<()>;
     fn sync(&self, c: Arc<Completion>) -> Result<()>;
     fn size(&self) -> Result<u64>;
+
+    /// Serves reads of the first `size` bytes of the file from a memory mapping instead of
+    /// reading them from the file, zero stops mapping the file. Backends that cannot map files
+    /// ignore it.
+    fn set_mmap_size(&self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns the number of bytes of the file that can be served from a memory mapping.
+    fn mmap_size(&self) -> usize {
+        0
+    }
+
+    /// Fits the memory mapping to the current size of the file, which another process may have
+    /// truncated. Growth of the file is mapped by the first read past the end of the mapping.
+    fn refresh_mmap(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Truncates the file to `len` bytes. Backends that cannot truncate files keep their size,
+    /// which only wastes space for the files that are truncated, e.g. a restarted WAL.
+    fn truncate(&self, _len: usize) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -47,6 +72,12 @@ pub trait IO: Clock + Send + Sync {
     fn generate_random_number(&self) -> i64;
 
     fn get_memory_io(&self) -> Arc<MemoryIO>;
+
+    /// Blocks the thread for `duration`, e.g. between the retries of a busy lock. IOs with a
+    /// simulated [Clock] advance it instead.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
 }
 
 pub type Complete = dyn Fn(Arc<RefCell<Buffer>>);
@@ -157,15 +188,27 @@ pub type BufferData = Pin<Vec<u8>>;
 
 pub type BufferDropFn = Rc<dyn Fn(BufferData)>;
 
-#[derive(Clone)]
+/// Memory mapping of a file that buffers can be views into, see [Buffer::map].
+pub type Mapping = Rc<dyn AsRef<[u8]>>;
+
 pub struct Buffer {
     data: ManuallyDrop<BufferData>,
+    /// Mapping and offset of the bytes served instead of `data` until the buffer is written to.
+    mapped: Option<(Mapping, usize)>,
     drop: BufferDropFn,
 }
 
+impl Clone for Buffer {
+    /// Copies the data into a new allocation, which is dropped normally: only the original
+    /// goes back to where it came from, such as a [crate::storage::buffer_pool::BufferPool].
+    fn clone(&self) -> Self {
+        Self::new(Pin::new(self.as_slice().to_vec()), Rc::new(|_| {}))
+    }
+}
+
 impl Debug for Buffer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.data)
+        write!(f, "{:?}", self.as_slice())
     }
 }
 
@@ -178,13 +221,28 @@ impl Drop for Buffer {
 
 impl Buffer {
     pub fn allocate(size: usize, drop: BufferDropFn) -> Self {
-        let data = ManuallyDrop::new(Pin::new(vec![0; size]));
-        Self { data, drop }
+        Self::new(Pin::new(vec![0; size]), drop)
     }
 
     pub fn new(data: BufferData, drop: BufferDropFn) -> Self {
         let data = ManuallyDrop::new(data);
-        Self { data, drop }
+        Self {
+            data,
+            mapped: None,
+            drop,
+        }
+    }
+
+    /// Serves the contents of the buffer from `mapping`, starting at `offset`, without copying
+    /// them. They are copied into the buffer the first time it is written to.
+    pub fn map(&mut self, mapping: Mapping, offset: usize) {
+        assert!(offset + self.len() <= (*mapping).as_ref().len());
+        self.mapped = Some((mapping, offset));
+    }
+
+    /// Returns whether the contents of the buffer are still served from a mapping.
+    pub fn is_mapped(&self) -> bool {
+        self.mapped.is_some()
     }
 
     pub fn len(&self) -> usize {
@@ -196,20 +254,34 @@ impl Buffer {
     }
 
     pub fn as_slice(&self) -> &[u8] {
-        &self.data
+        match &self.mapped {
+            Some((mapping, offset)) => &(**mapping).as_ref()[*offset..*offset + self.data.len()],
+            None => &self.data,
+        }
     }
 
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.unmap();
         &mut self.data
     }
 
     pub fn as_ptr(&self) -> *const u8 {
-        self.data.as_ptr()
+        self.as_slice().as_ptr()
     }
 
     pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.unmap();
         self.data.as_mut_ptr()
     }
+
+    /// Copies the bytes served from a mapping into the buffer, before it is written to.
+    fn unmap(&mut self) {
+        if let Some((mapping, offset)) = self.mapped.take() {
+            let len = self.data.len();
+            self.data
+                .copy_from_slice(&(*mapping).as_ref()[offset..offset + len]);
+        }
+    }
 }
 
 cfg_block! {
@@ -245,9 +317,13 @@ cfg_block! {
     }
 }
 
+#[cfg(feature = "compression")]
+mod compressed;
 mod memory;
 #[cfg(feature = "fs")]
 mod vfs;
+#[cfg(feature = "compression")]
+pub use compressed::{CompressedIO, CompressionStats};
 pub use memory::MemoryIO;
 pub mod clock;
 mod common;
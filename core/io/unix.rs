@@ -2,17 +2,20 @@ use crate::error::LimboError;
 use crate::io::common;
 use crate::Result;
 
-use super::{Completion, File, MemoryIO, OpenFlags, IO};
+use super::{Buffer, Completion, File, MemoryIO, OpenFlags, IO};
 use crate::io::clock::{Clock, Instant};
 use polling::{Event, Events, Poller};
 use rustix::{
     fd::{AsFd, AsRawFd},
     fs::{self, FlockOperation, OFlags, OpenOptionsExt},
     io::Errno,
+    mm::{self, MapFlags, ProtFlags},
 };
 use std::{
-    cell::{RefCell, UnsafeCell},
+    cell::{Cell, RefCell, UnsafeCell},
+    ffi::c_void,
     mem::MaybeUninit,
+    rc::Rc,
 };
 use std::{
     io::{ErrorKind, Read, Seek, Write},
@@ -212,6 +215,8 @@ impl IO for UnixIO {
             file: Arc::new(RefCell::new(file)),
             poller: BorrowedPollHandler(self.poller.as_mut().into()),
             callbacks: BorrowedCallbacks(self.callbacks.as_mut().into()),
+            mmap_size: Cell::new(0),
+            mmap: RefCell::new(None),
         });
         if std::env::var(common::ENV_DISABLE_FILE_LOCK).is_err() {
             unix_file.lock_file(!flags.contains(OpenFlags::ReadOnly))?;
@@ -284,15 +289,105 @@ enum CompletionCallback {
     ),
 }
 
+/// Largest memory mapping of a file, the default `SQLITE_MAX_MMAP_SIZE` of SQLite.
+const MAX_MMAP_SIZE: usize = 0x7fff0000;
+
+/// Read-only mapping of the start of a file. Writes to the file through `pwrite` are visible
+/// in the mapping, as both go through the page cache of the operating system. Buffers read from
+/// the mapping are views into it and keep it alive after the file is remapped.
+struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(file: &std::fs::File, len: usize) -> Result<Self> {
+        let ptr = unsafe {
+            mm::mmap(
+                std::ptr::null_mut(),
+                len,
+                ProtFlags::READ,
+                MapFlags::SHARED,
+                file.as_fd(),
+                0,
+            )?
+        };
+        Ok(Self { ptr, len })
+    }
+
+}
+
+impl AsRef<[u8]> for Mmap {
+    fn as_ref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe { mm::munmap(self.ptr, self.len).expect("Failed to unmap file") };
+    }
+}
+
 pub struct UnixFile<'io> {
     #[allow(clippy::arc_with_non_send_sync)]
     file: Arc<RefCell<std::fs::File>>,
     poller: BorrowedPollHandler<'io>,
     callbacks: BorrowedCallbacks<'io>,
+    /// Maximum number of bytes of the file to map, zero if reads don't use a mapping.
+    mmap_size: Cell<usize>,
+    /// Mapping of the first `min(mmap_size, file size)` bytes of the file.
+    mmap: RefCell<Option<Rc<Mmap>>>,
 }
 unsafe impl Send for UnixFile<'_> {}
 unsafe impl Sync for UnixFile<'_> {}
 
+impl UnixFile<'_> {
+    /// Maps the part of the file covered by `mmap_size`, replacing the current mapping if the
+    /// file has grown or shrunk since it was mapped.
+    fn remap(&self) -> Result<()> {
+        let len = match self.mmap_size.get() {
+            0 => 0,
+            mmap_size => mmap_size.min(self.size()? as usize),
+        };
+        let mut mmap = self.mmap.borrow_mut();
+        if mmap.as_ref().map_or(0, |mmap| mmap.len) == len {
+            return Ok(());
+        }
+        trace!("remap(len = {})", len);
+        // Unmap first, the address space of the old and the new mapping could be too large.
+        *mmap = None;
+        if len > 0 {
+            *mmap = Some(Rc::new(Mmap::new(&self.file.borrow(), len)?));
+        }
+        Ok(())
+    }
+
+    fn mapped_len(&self) -> usize {
+        self.mmap.borrow().as_ref().map_or(0, |mmap| mmap.len)
+    }
+
+    /// Makes `buf` a view into the mapping at `pos`. Returns false if the mapping does not
+    /// cover the bytes of `buf`, in which case they must be read from the file.
+    fn read_mapped(&self, pos: usize, buf: &mut Buffer) -> Result<bool> {
+        let end = pos + buf.len();
+        if end > self.mmap_size.get() {
+            return Ok(false);
+        }
+        if end > self.mapped_len() {
+            // The file may have grown since it was mapped.
+            self.remap()?;
+        }
+        match self.mmap.borrow().as_ref() {
+            Some(mmap) if end <= mmap.len => {
+                buf.map(mmap.clone(), pos);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
 impl File for UnixFile<'_> {
     fn lock_file(&self, exclusive: bool) -> Result<()> {
         let fd = self.file.borrow();
@@ -334,6 +429,16 @@ impl File for UnixFile<'_> {
     }
 
     fn pread(&self, pos: usize, c: Arc<Completion>) -> Result<()> {
+        let mapped = {
+            let r = c.as_read();
+            let mut buf = r.buf_mut();
+            self.read_mapped(pos, &mut buf)?
+        };
+        if mapped {
+            trace!("pread from mapping");
+            c.complete(0);
+            return Ok(());
+        }
         let file = self.file.borrow();
         let result = {
             let r = c.as_read();
@@ -411,6 +516,29 @@ impl File for UnixFile<'_> {
         let file = self.file.borrow();
         Ok(file.metadata()?.len())
     }
+
+    fn set_mmap_size(&self, size: usize) -> Result<()> {
+        self.mmap_size.set(size.min(MAX_MMAP_SIZE));
+        self.remap()
+    }
+
+    fn mmap_size(&self) -> usize {
+        self.mmap_size.get()
+    }
+
+    fn refresh_mmap(&self) -> Result<()> {
+        let mapped = self.mapped_len();
+        // Reading the mapping past the end of the file would fault.
+        if mapped == 0 || self.size()? >= mapped as u64 {
+            return Ok(());
+        }
+        self.remap()
+    }
+
+    fn truncate(&self, len: usize) -> Result<()> {
+        self.file.borrow().set_len(len as u64)?;
+        self.refresh_mmap()
+    }
 }
 
 impl Drop for UnixFile<'_> {
@@ -422,9 +550,90 @@ impl Drop for UnixFile<'_> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::io::ReadCompletion;
 
     #[test]
     fn test_multiple_processes_cannot_open_file() {
         common::tests::test_multiple_processes_cannot_open_file(UnixIO::new);
     }
+
+    fn read_buffer(
+        io: &UnixIO,
+        file: &Arc<dyn File>,
+        pos: usize,
+        len: usize,
+    ) -> Arc<RefCell<Buffer>> {
+        let buf = Arc::new(RefCell::new(Buffer::allocate(len, Rc::new(|_| {}))));
+        let c = Arc::new(Completion::Read(ReadCompletion::new(
+            buf.clone(),
+            Box::new(|_| {}),
+        )));
+        file.pread(pos, c.clone()).unwrap();
+        io.wait_for_completion(c).unwrap();
+        buf
+    }
+
+    fn read(io: &UnixIO, file: &Arc<dyn File>, pos: usize, len: usize) -> Vec<u8> {
+        let buf = read_buffer(io, file, pos, len);
+        let data = buf.borrow().as_slice().to_vec();
+        data
+    }
+
+    #[test]
+    fn test_mmap_follows_file_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mmap.db");
+        std::fs::write(&path, vec![1u8; 4096]).unwrap();
+        let io = UnixIO::new().unwrap();
+        let file = io
+            .open_file(path.to_str().unwrap(), OpenFlags::None, false)
+            .unwrap();
+        file.set_mmap_size(1 << 20).unwrap();
+        assert_eq!(file.mmap_size(), 1 << 20);
+        assert_eq!(read(&io, &file, 0, 4096), vec![1u8; 4096]);
+
+        // Growth is mapped on the first read past the end of the mapping.
+        let mut raw = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        raw.write_all(&[2u8; 4096]).unwrap();
+        assert_eq!(read(&io, &file, 4096, 4096), vec![2u8; 4096]);
+
+        // Reads past the end of a truncated file go to the file once the mapping is refreshed.
+        raw.set_len(4096).unwrap();
+        file.refresh_mmap().unwrap();
+        assert_eq!(read(&io, &file, 4096, 4096), vec![0u8; 4096]);
+        assert_eq!(read(&io, &file, 0, 4096), vec![1u8; 4096]);
+
+        // Bytes past the mmap size are read from the file.
+        file.set_mmap_size(2048).unwrap();
+        assert_eq!(read(&io, &file, 0, 4096), vec![1u8; 4096]);
+        file.set_mmap_size(0).unwrap();
+        assert_eq!(file.mmap_size(), 0);
+        assert_eq!(read(&io, &file, 0, 4096), vec![1u8; 4096]);
+    }
+
+    #[test]
+    fn test_mmap_reads_are_views() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mmap.db");
+        std::fs::write(&path, vec![1u8; 8192]).unwrap();
+        let io = UnixIO::new().unwrap();
+        let file = io
+            .open_file(path.to_str().unwrap(), OpenFlags::None, false)
+            .unwrap();
+        file.set_mmap_size(1 << 20).unwrap();
+
+        let buf = read_buffer(&io, &file, 4096, 4096);
+        assert!(buf.borrow().is_mapped());
+        // Writing to the buffer copies it first, leaving the file alone.
+        buf.borrow_mut().as_mut_slice()[0] = 2;
+        assert!(!buf.borrow().is_mapped());
+        assert_eq!(buf.borrow().as_slice()[..2], [2, 1]);
+        assert_eq!(read(&io, &file, 4096, 2), vec![1, 1]);
+
+        // A view keeps its mapping alive after the file is remapped.
+        let view = read_buffer(&io, &file, 0, 4096);
+        file.set_mmap_size(0).unwrap();
+        assert!(!read_buffer(&io, &file, 0, 4096).borrow().is_mapped());
+        assert_eq!(view.borrow().as_slice(), vec![1u8; 4096]);
+    }
 }
@@ -115,4 +115,10 @@ impl File for WindowsFile {
         let file = self.file.borrow();
         Ok(file.metadata().unwrap().len())
     }
+
+    fn truncate(&self, len: usize) -> Result<()> {
+        let file = self.file.borrow();
+        file.set_len(len as u64).map_err(LimboError::IOError)?;
+        Ok(())
+    }
 }
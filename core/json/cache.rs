@@ -1,5 +1,7 @@
 use std::cell::{Cell, UnsafeCell};
+use std::sync::Arc;
 
+use crate::memory::MemoryTracker;
 use crate::Value;
 
 use super::jsonb::Jsonb;
@@ -12,15 +14,24 @@ pub struct JsonCache {
     age: [usize; JSON_CACHE_SIZE],
     used: usize,
     counter: usize,
+    /// Tracker the cached entries are charged to, if any. Nothing is cached while the tracker
+    /// is over its soft limit.
+    memory: Option<Arc<MemoryTracker>>,
 }
 
 impl JsonCache {
+    #[cfg(test)]
     pub fn new() -> Self {
+        Self::with_memory(None)
+    }
+
+    fn with_memory(memory: Option<Arc<MemoryTracker>>) -> Self {
         Self {
             entries: [None, None, None, None],
             age: [0, 0, 0, 0],
             used: 0,
             counter: 0,
+            memory,
         }
     }
 
@@ -39,6 +50,13 @@ impl JsonCache {
     }
 
     pub fn insert(&mut self, key: &Value, value: &Jsonb) {
+        if let Some(memory) = &self.memory {
+            let size = entry_size(key, value);
+            if memory.over_soft_limit(size) {
+                return;
+            }
+            memory.allocate(size);
+        }
         if self.used < JSON_CACHE_SIZE {
             self.entries[self.used] = Some((key.clone(), value.clone()));
             self.age[self.used] = self.counter;
@@ -47,6 +65,7 @@ impl JsonCache {
         } else {
             let id = self.find_oldest_entry();
 
+            self.release(id);
             self.entries[id] = Some((key.clone(), value.clone()));
             self.age[id] = self.counter;
             self.counter += 1;
@@ -69,15 +88,43 @@ impl JsonCache {
     }
 
     pub fn clear(&mut self) {
+        for id in 0..self.used {
+            self.release(id);
+        }
         self.counter = 0;
         self.used = 0;
     }
+
+    /// Drops the entry at `id`, returning its memory to the tracker.
+    fn release(&mut self, id: usize) {
+        if let Some((key, value)) = self.entries[id].take() {
+            if let Some(memory) = &self.memory {
+                memory.release(entry_size(&key, &value));
+            }
+        }
+    }
+}
+
+impl Drop for JsonCache {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+fn entry_size(key: &Value, value: &Jsonb) -> usize {
+    let key_size = match key {
+        Value::Text(text) => text.as_str().len(),
+        Value::Blob(blob) => blob.len(),
+        _ => 0,
+    };
+    key_size + value.len()
 }
 
 #[derive(Debug)]
 pub struct JsonCacheCell {
     inner: UnsafeCell<Option<JsonCache>>,
     accessed: Cell<bool>,
+    memory: Option<Arc<MemoryTracker>>,
 }
 
 impl JsonCacheCell {
@@ -85,6 +132,15 @@ impl JsonCacheCell {
         Self {
             inner: UnsafeCell::new(None),
             accessed: Cell::new(false),
+            memory: None,
+        }
+    }
+
+    /// Creates a cache whose entries are charged to `memory`.
+    pub fn with_memory(memory: Arc<MemoryTracker>) -> Self {
+        Self {
+            memory: Some(memory),
+            ..Self::new()
         }
     }
 
@@ -97,7 +153,7 @@ impl JsonCacheCell {
         let result = unsafe {
             let cache_ptr = self.inner.get();
             if (*cache_ptr).is_none() {
-                *cache_ptr = Some(JsonCache::new());
+                *cache_ptr = Some(JsonCache::with_memory(self.memory.clone()));
             }
 
             if let Some(cache) = &mut (*cache_ptr) {
@@ -122,7 +178,7 @@ impl JsonCacheCell {
         let result = unsafe {
             let cache_ptr = self.inner.get();
             if (*cache_ptr).is_none() {
-                *cache_ptr = Some(JsonCache::new());
+                *cache_ptr = Some(JsonCache::with_memory(self.memory.clone()));
             }
 
             if let Some(cache) = &mut (*cache_ptr) {
@@ -1,21 +1,28 @@
 #![allow(clippy::arc_with_non_send_sync)]
 
+mod busy;
 mod error;
 mod ext;
 mod fast_lock;
 mod function;
 mod functions;
+mod hooks;
 mod info;
 mod io;
 #[cfg(feature = "json")]
 mod json;
+mod memory;
 pub mod mvcc;
 mod parameters;
 mod pragma;
+mod progress;
 mod pseudo;
 pub mod result;
 mod schema;
+mod session;
+mod statement_cache;
 mod storage;
+mod temp;
 mod translate;
 pub mod types;
 #[allow(dead_code)]
@@ -36,9 +43,13 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 use crate::vtab::VirtualTable;
 use crate::{fast_lock::SpinLock, translate::optimizer::optimize_plan};
+pub use busy::BusyCallback;
+use busy::BusyHandler;
 use core::str;
 pub use error::LimboError;
 use fallible_iterator::FallibleIterator;
+use hooks::Hooks;
+pub use hooks::{CommitHook, RollbackHook, UpdateHook, UpdateKind};
 pub use io::clock::{Clock, Instant};
 #[cfg(all(feature = "fs", target_family = "unix"))]
 pub use io::UnixIO;
@@ -47,9 +58,18 @@ pub use io::UringIO;
 pub use io::{
     Buffer, Completion, File, MemoryIO, OpenFlags, PlatformIO, SyscallIO, WriteCompletion, IO,
 };
+#[cfg(feature = "compression")]
+pub use io::{CompressedIO, CompressionStats};
 use limbo_sqlite3_parser::{ast, ast::Cmd, lexer::sql::Parser};
+pub use memory::MemoryTracker;
 use parking_lot::RwLock;
-use schema::Schema;
+pub use progress::ProgressCallback;
+use progress::{Progress, ProgressHandler};
+use schema::{Schema, STAT1_TABLE_NAME};
+use session::Sessions;
+pub use session::{Change, Changeset, ChangesetTable, ConflictAction, ConflictType, Session};
+use statement_cache::StatementCache;
+pub use statement_cache::{StatementCacheStats, DEFAULT_STATEMENT_CACHE_CAPACITY};
 use std::{
     borrow::Cow,
     cell::{Cell, RefCell, UnsafeCell},
@@ -58,30 +78,45 @@ use std::{
     io::Write,
     num::NonZero,
     ops::Deref,
+    pin::Pin,
     rc::Rc,
-    sync::{Arc, OnceLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
 };
 use storage::btree::{btree_init_page, BTreePageInner};
 #[cfg(feature = "fs")]
 use storage::database::DatabaseFile;
+use storage::database::FileMemoryStorage;
 pub use storage::pager::PagerCacheflushStatus;
 pub use storage::{
+    backup::{Backup, BackupStepResult},
+    blob::Blob,
     buffer_pool::BufferPool,
     database::DatabaseStorage,
+    page_cache::{
+        CacheError, CacheResizeResult, DumbLruPageCache, PageCache, PageCacheKey, PageCacheStats,
+    },
     pager::PageRef,
     pager::{Page, Pager},
+    replicator::Replicator,
+    s3fifo::S3FifoPageCache,
     wal::{CheckpointMode, CheckpointResult, CheckpointStatus, Wal, WalFile, WalFileShared},
 };
 use storage::{
-    page_cache::DumbLruPageCache,
+    encryption::{Encryption, EncryptionKey},
     pager::allocate_page,
     sqlite3_ondisk::{DatabaseHeader, DATABASE_HEADER_SIZE},
 };
+use temp::TempDatabase;
+pub use temp::TempStore;
 use tracing::{instrument, Level};
 use translate::select::prepare_select_plan;
 pub use types::RefValue;
 pub use types::Value;
-use util::parse_schema_rows;
+use util::{parse_schema_rows, parse_stat1_rows};
 use vdbe::builder::QueryMode;
 use vdbe::builder::TableRefIdCounter;
 
@@ -109,9 +144,13 @@ pub struct Database {
     page_size: u32,
     // Shared structures of a Database are the parts that are common to multiple threads that might
     // create DB connections.
-    _shared_page_cache: Arc<RwLock<DumbLruPageCache>>,
+    _shared_page_cache: Arc<RwLock<dyn PageCache>>,
     shared_wal: Arc<UnsafeCell<WalFileShared>>,
     open_flags: OpenFlags,
+    encryption: Arc<Encryption>,
+    /// Whether a [Replicator] copies frames into the database, whose connections must then not
+    /// write to it.
+    replica: AtomicBool,
 }
 
 unsafe impl Send for Database {}
@@ -136,6 +175,20 @@ impl Database {
         Self::open_with_flags(io, path, db_file, flags, enable_mvcc)
     }
 
+    /// Opens an encrypted database, or encrypts a new one, with `key`. See
+    /// [Connection::set_encryption_key] for the format of the key.
+    #[cfg(feature = "fs")]
+    pub fn open_file_with_key(
+        io: Arc<dyn IO>,
+        path: &str,
+        key: &str,
+        enable_mvcc: bool,
+    ) -> Result<Arc<Database>> {
+        let db = Self::open_file(io, path, enable_mvcc)?;
+        db.connect()?.set_encryption_key(key)?;
+        Ok(db)
+    }
+
     #[allow(clippy::arc_with_non_send_sync)]
     pub fn open(
         io: Arc<dyn IO>,
@@ -157,6 +210,12 @@ impl Database {
         let db_header = Pager::begin_open(db_file.clone())?;
         // ensure db header is there
         io.run_once()?;
+        let encryption = if db_header.lock().is_encrypted() {
+            db_header.lock().clear_encrypted_fields();
+            Arc::new(Encryption::locked())
+        } else {
+            Arc::new(Encryption::default())
+        };
 
         let page_size = db_header.lock().get_page_size();
         let wal_path = format!("{}-wal", path);
@@ -176,7 +235,7 @@ impl Database {
             None
         };
 
-        let shared_page_cache = Arc::new(RwLock::new(DumbLruPageCache::default()));
+        let shared_page_cache = Arc::new(RwLock::new(S3FifoPageCache::default()));
         let schema = Arc::new(RwLock::new(Schema::new()));
         let db = Database {
             mv_store,
@@ -188,9 +247,12 @@ impl Database {
             io: io.clone(),
             page_size,
             open_flags: flags,
+            encryption,
+            replica: AtomicBool::new(false),
         };
         let db = Arc::new(db);
-        {
+        // The schema of an encrypted database is parsed once its key is set.
+        if !db.encryption.is_locked() {
             // parse schema
             let conn = db.connect()?;
             let rows = conn.query("SELECT * FROM sqlite_schema")?;
@@ -205,28 +267,44 @@ impl Database {
                 // a warning to the user to load the module
                 eprintln!("Warning: {}", e);
             }
+            schema.schema_version = db.header.lock().schema_cookie;
+            drop(schema);
+            drop(syms);
+            conn.load_index_stats()?;
         }
         Ok(db)
     }
 
+    /// Returns true if the connections to the database must not write to it.
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.open_flags.contains(OpenFlags::ReadOnly) || self.replica.load(Ordering::SeqCst)
+    }
+
     pub fn connect(self: &Arc<Database>) -> Result<Arc<Connection>> {
-        let buffer_pool = Rc::new(BufferPool::new(self.page_size as usize));
+        let memory = Arc::new(MemoryTracker::with_parent(MemoryTracker::global().clone()));
+        let buffer_pool = Rc::new(BufferPool::with_memory(
+            self.page_size as usize,
+            memory.clone(),
+        ));
 
-        let wal = Rc::new(RefCell::new(WalFile::new(
+        let mut wal = WalFile::new(
             self.io.clone(),
             self.page_size,
             self.shared_wal.clone(),
             buffer_pool.clone(),
-        )));
+        );
+        wal.set_encryption(self.encryption.clone());
         // For now let's open database without shared cache by default.
-        let pager = Rc::new(Pager::finish_open(
+        let mut pager = Pager::finish_open(
             self.header.clone(),
             self.db_file.clone(),
-            wal,
+            Rc::new(RefCell::new(wal)),
             self.io.clone(),
-            Arc::new(RwLock::new(DumbLruPageCache::default())),
+            Arc::new(RwLock::new(S3FifoPageCache::default())),
             buffer_pool,
-        )?);
+        )?;
+        pager.set_encryption(self.encryption.clone());
+        let pager = Rc::new(pager);
         let conn = Arc::new(Connection {
             _db: self.clone(),
             pager: pager.clone(),
@@ -241,6 +319,14 @@ impl Database {
             total_changes: Cell::new(0),
             _shared_cache: false,
             cache_size: Cell::new(self.header.lock().default_page_cache_size),
+            busy_handler: RefCell::new(BusyHandler::None),
+            hooks: RefCell::new(Hooks::default()),
+            sessions: RefCell::new(Sessions::default()),
+            statement_cache: RefCell::new(StatementCache::new(DEFAULT_STATEMENT_CACHE_CAPACITY)),
+            progress: RefCell::new(Progress::default()),
+            memory,
+            temp: RefCell::new(None),
+            temp_store: Cell::new(TempStore::default()),
         });
         if let Err(e) = conn.register_builtins() {
             return Err(LimboError::ExtensionError(e));
@@ -248,6 +334,29 @@ impl Database {
         Ok(conn)
     }
 
+    /// Returns the contents of the database as a contiguous image with the layout of a
+    /// database file, including committed changes that are still in the WAL.
+    pub fn serialize(self: &Arc<Database>) -> Result<Vec<u8>> {
+        let conn = self.connect()?;
+        storage::serialize::serialize(&conn.pager)
+    }
+
+    /// Opens an in-memory database from an image produced by [Database::serialize].
+    /// The image is copied, so later changes to the database do not affect `data`.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn deserialize(data: &[u8]) -> Result<(Arc<dyn IO>, Arc<Database>)> {
+        storage::serialize::validate_image(data)?;
+        let io: Arc<dyn IO> = Arc::new(MemoryIO::new());
+        let file = io.open_file(":memory:", OpenFlags::Create, false)?;
+        let buffer = Buffer::new(Pin::new(data.to_vec()), Rc::new(|_| {}));
+        let completion = Completion::Write(WriteCompletion::new(Box::new(|_| {})));
+        file.pwrite(0, Arc::new(RefCell::new(buffer)), Arc::new(completion))?;
+        io.run_once()?;
+        let db_file = Arc::new(FileMemoryStorage::new(file));
+        let db = Self::open(io.clone(), ":memory:", db_file, false)?;
+        Ok((io, db))
+    }
+
     /// Open a new database file with a specified VFS without an existing database
     /// connection and symbol table to register extensions.
     #[cfg(feature = "fs")]
@@ -261,6 +370,8 @@ impl Database {
                 "syscall" => Arc::new(SyscallIO::new()?),
                 #[cfg(all(target_os = "linux", feature = "io_uring"))]
                 "io_uring" => Arc::new(UringIO::new()?),
+                #[cfg(feature = "compression")]
+                "compressed" => Arc::new(CompressedIO::new(Arc::new(PlatformIO::new()?))),
                 other => {
                     return Err(LimboError::InvalidArgument(format!(
                         "no such VFS: {}",
@@ -341,9 +452,22 @@ pub struct Connection {
     syms: RefCell<SymbolTable>,
     _shared_cache: bool,
     cache_size: Cell<i32>,
+    busy_handler: RefCell<BusyHandler>,
+    hooks: RefCell<Hooks>,
+    sessions: RefCell<Sessions>,
+    statement_cache: RefCell<StatementCache>,
+    progress: RefCell<Progress>,
+    /// Memory used by the connection, charged to the global tracker as well.
+    memory: Arc<MemoryTracker>,
+    /// The temp database, opened by the first statement that uses it.
+    temp: RefCell<Option<Rc<TempDatabase>>>,
+    temp_store: Cell<TempStore>,
 }
 
 impl Connection {
+    /// Compiles the first statement of `sql`. Queries and DML statements are kept in the
+    /// statement cache of the connection and prepared again without compiling them, as long as
+    /// the schema has not changed.
     #[instrument(skip_all, level = Level::TRACE)]
     pub fn prepare(self: &Arc<Connection>, sql: impl AsRef<str>) -> Result<Statement> {
         if sql.as_ref().is_empty() {
@@ -354,6 +478,20 @@ impl Connection {
 
         let sql = sql.as_ref();
         tracing::trace!("Preparing: {}", sql);
+        let schema_version = self
+            .schema
+            .try_read()
+            .ok_or(LimboError::SchemaLocked)?
+            .schema_version;
+        // The borrow must not be held while translating: some statements prepare others.
+        let cached = self.statement_cache.borrow_mut().get(sql, schema_version);
+        if let Some(program) = cached {
+            return Ok(Statement::new(
+                program,
+                self._db.mv_store.clone(),
+                self.pager.clone(),
+            ));
+        }
         let mut parser = Parser::new(sql.as_bytes());
         let cmd = parser.next()?;
         let syms = self.syms.borrow();
@@ -364,19 +502,31 @@ impl Connection {
             .trim();
         match cmd {
             Cmd::Stmt(stmt) => {
-                let program = Rc::new(translate::translate(
-                    self.schema
-                        .try_read()
-                        .ok_or(LimboError::SchemaLocked)?
-                        .deref(),
+                // Other statements may have side effects at compile time, e.g. PRAGMAs.
+                let cacheable = matches!(
                     stmt,
-                    self.header.clone(),
-                    self.pager.clone(),
-                    self.clone(),
-                    &syms,
-                    QueryMode::Normal,
-                    &input,
-                )?);
+                    ast::Stmt::Select(..)
+                        | ast::Stmt::Insert(..)
+                        | ast::Stmt::Update(..)
+                        | ast::Stmt::Delete(..)
+                );
+                let program = Rc::new(self.with_schema(|schema| {
+                    translate::translate(
+                        schema,
+                        stmt,
+                        self.header.clone(),
+                        self.pager.clone(),
+                        self.clone(),
+                        &syms,
+                        QueryMode::Normal,
+                        input,
+                    )
+                })?);
+                if cacheable {
+                    self.statement_cache
+                        .borrow_mut()
+                        .insert(sql, program.clone(), schema_version);
+                }
                 Ok(Statement::new(
                     program,
                     self._db.mv_store.clone(),
@@ -413,19 +563,20 @@ impl Connection {
         let syms = self.syms.borrow();
         match cmd {
             Cmd::Stmt(ref stmt) | Cmd::Explain(ref stmt) => {
-                let program = translate::translate(
-                    self.schema
-                        .try_read()
-                        .ok_or(LimboError::SchemaLocked)?
-                        .deref(),
-                    stmt.clone(),
-                    self.header.clone(),
-                    self.pager.clone(),
-                    self.clone(),
-                    &syms,
-                    cmd.into(),
-                    input,
-                )?;
+                let stmt = stmt.clone();
+                let query_mode = cmd.into();
+                let program = self.with_schema(|schema| {
+                    translate::translate(
+                        schema,
+                        stmt,
+                        self.header.clone(),
+                        self.pager.clone(),
+                        self.clone(),
+                        &syms,
+                        query_mode,
+                        input,
+                    )
+                })?;
                 let stmt = Statement::new(
                     program.into(),
                     self._db.mv_store.clone(),
@@ -437,24 +588,18 @@ impl Connection {
                 let mut table_ref_counter = TableRefIdCounter::new();
                 match stmt {
                     ast::Stmt::Select(select) => {
-                        let mut plan = prepare_select_plan(
-                            self.schema
-                                .try_read()
-                                .ok_or(LimboError::SchemaLocked)?
-                                .deref(),
-                            *select,
-                            &syms,
-                            &[],
-                            &mut table_ref_counter,
-                            translate::plan::QueryDestination::ResultRows,
-                        )?;
-                        optimize_plan(
-                            &mut plan,
-                            self.schema
-                                .try_read()
-                                .ok_or(LimboError::SchemaLocked)?
-                                .deref(),
-                        )?;
+                        let plan = self.with_schema(|schema| {
+                            let mut plan = prepare_select_plan(
+                                schema,
+                                *select,
+                                &syms,
+                                &[],
+                                &mut table_ref_counter,
+                                translate::plan::QueryDestination::ResultRows,
+                            )?;
+                            optimize_plan(&mut plan, schema, syms.case_sensitive_like.get())?;
+                            Ok(plan)
+                        })?;
                         let _ = std::io::stdout().write_all(plan.to_string().as_bytes());
                     }
                     _ => todo!(),
@@ -483,49 +628,51 @@ impl Connection {
         if let Some(cmd) = cmd {
             match cmd {
                 Cmd::Explain(stmt) => {
-                    let program = translate::translate(
-                        self.schema
-                            .try_read()
-                            .ok_or(LimboError::SchemaLocked)?
-                            .deref(),
-                        stmt,
-                        self.header.clone(),
-                        self.pager.clone(),
-                        self.clone(),
-                        &syms,
-                        QueryMode::Explain,
-                        &input,
-                    )?;
+                    let program = self.with_schema(|schema| {
+                        translate::translate(
+                            schema,
+                            stmt,
+                            self.header.clone(),
+                            self.pager.clone(),
+                            self.clone(),
+                            &syms,
+                            QueryMode::Explain,
+                            input,
+                        )
+                    })?;
                     let _ = std::io::stdout().write_all(program.explain().as_bytes());
                 }
                 Cmd::ExplainQueryPlan(_stmt) => todo!(),
                 Cmd::Stmt(stmt) => {
-                    let program = translate::translate(
-                        self.schema
-                            .try_read()
-                            .ok_or(LimboError::SchemaLocked)?
-                            .deref(),
-                        stmt,
-                        self.header.clone(),
-                        self.pager.clone(),
-                        self.clone(),
-                        &syms,
-                        QueryMode::Normal,
-                        &input,
-                    )?;
-
-                    let mut state =
-                        vdbe::ProgramState::new(program.max_registers, program.cursor_ref.len());
+                    let program = self.with_schema(|schema| {
+                        translate::translate(
+                            schema,
+                            stmt,
+                            self.header.clone(),
+                            self.pager.clone(),
+                            self.clone(),
+                            &syms,
+                            QueryMode::Normal,
+                            input,
+                        )
+                    })?;
+
+                    let mut state = vdbe::ProgramState::new(
+                        program.max_registers,
+                        program.cursor_ref.len(),
+                        self.memory.clone(),
+                    );
                     loop {
                         let res = program.step(
                             &mut state,
                             self._db.mv_store.clone(),
                             self.pager.clone(),
                         )?;
-                        if matches!(res, StepResult::Done) {
-                            break;
+                        match res {
+                            StepResult::Done => break,
+                            StepResult::Busy => return Err(LimboError::Busy),
+                            _ => self._db.io.run_once()?,
                         }
-                        self._db.io.run_once()?;
                     }
                 }
             }
@@ -546,6 +693,78 @@ impl Connection {
         self.pager.wal_get_frame(frame_no, p_frame, frame_len)
     }
 
+    /// Returns the WAL header as it is stored on disk. Replicas need it to insert the frames
+    /// of this WAL, see [Connection::wal_insert_begin].
+    pub fn wal_get_header(&self) -> Vec<u8> {
+        self.pager.wal_get_header().to_vec()
+    }
+
+    /// Starts inserting frames copied from the WAL of another database, the primary, whose
+    /// header is `header`. The WAL of this database must be a copy of the start of the
+    /// primary's WAL: a WAL without frames takes over the header, otherwise the headers must
+    /// match. When the primary restarted its WAL since, e.g. with
+    /// `PRAGMA wal_checkpoint(RESTART)`, the frames of this WAL are checkpointed and the WAL
+    /// takes over the new header. Holds the WAL write lock until [Connection::wal_insert_end].
+    pub fn wal_insert_begin(&self, header: &[u8]) -> Result<()> {
+        if !self.auto_commit.get() || self.transaction_state.get() != TransactionState::None {
+            return Err(LimboError::TxError(
+                "cannot insert WAL frames inside a transaction".to_string(),
+            ));
+        }
+        self.retry_busy(|| self.pager.wal_insert_begin(header))
+    }
+
+    /// Appends frame `frame_no` of the primary's WAL, as returned by
+    /// [Connection::wal_get_frame]. Frames must be inserted in order and their salts and
+    /// checksums must be valid. The frames of a transaction become visible to readers when
+    /// its commit frame is inserted.
+    pub fn wal_insert_frame(self: &Arc<Connection>, frame_no: u64, frame: &[u8]) -> Result<()> {
+        // Reload the schema whenever the transaction rewrote page 1, which holds the schema
+        // cookie and the root of sqlite_schema.
+        if self.pager.wal_insert_frame(frame_no, frame)? {
+            self.reload_schema()?;
+        }
+        Ok(())
+    }
+
+    /// Stops inserting frames, discarding the frames of a transaction whose commit frame was
+    /// not inserted.
+    pub fn wal_insert_end(&self) -> Result<()> {
+        self.pager.wal_insert_end()
+    }
+
+    /// Sets the encryption key of the database, like `PRAGMA key`. The key is either a
+    /// passphrase or a raw 256-bit key written as a blob literal, `x'...'`. A database that
+    /// is still empty is encrypted with the key, so the key must be set before anything is
+    /// written to a new database.
+    pub fn set_encryption_key(self: &Arc<Connection>, key: &str) -> Result<()> {
+        self.apply_encryption_key(key)?;
+        self.reload_schema()
+    }
+
+    /// Sets the encryption key without reloading the schema, which the caller must do.
+    pub(crate) fn apply_encryption_key(&self, key: &str) -> Result<()> {
+        if !self.auto_commit.get() || self.transaction_state.get() != TransactionState::None {
+            return Err(LimboError::TxError(
+                "cannot set the encryption key inside a transaction".to_string(),
+            ));
+        }
+        self.pager.set_encryption_key(&EncryptionKey::parse(key)?)
+    }
+
+    /// Re-encrypts the database with a new key, like `PRAGMA rekey`.
+    pub fn rekey(&self, key: &str) -> Result<()> {
+        if self._db.is_read_only() {
+            return Err(LimboError::ReadOnly);
+        }
+        if !self.auto_commit.get() || self.transaction_state.get() != TransactionState::None {
+            return Err(LimboError::TxError(
+                "cannot change the encryption key inside a transaction".to_string(),
+            ));
+        }
+        self.pager.rekey(&EncryptionKey::parse(key)?)
+    }
+
     /// Flush dirty pages to disk.
     /// This will write the dirty pages to the WAL and then fsync the WAL.
     /// If the WAL size is over the checkpoint threshold, it will checkpoint the WAL to
@@ -559,13 +778,80 @@ impl Connection {
         Ok(())
     }
 
+    /// Returns the hit, miss and eviction counters of the page cache of the connection.
+    pub fn page_cache_stats(&self) -> PageCacheStats {
+        self.pager.page_cache_stats()
+    }
+
+    /// Returns the number of bytes of page buffers, sorter records and cached JSON currently
+    /// allocated by the connection.
+    pub fn memory_used(&self) -> usize {
+        self.memory.used()
+    }
+
+    /// Returns the maximum of [Connection::memory_used] since the connection was opened or the
+    /// mark was last reset, resetting it if `reset` is set.
+    pub fn memory_highwater(&self, reset: bool) -> usize {
+        self.memory.highwater(reset)
+    }
+
+    /// Sets the soft heap limit of the connection in bytes and returns the previous one. Over
+    /// the limit, the page cache evicts pages instead of allocating new buffers. Zero removes
+    /// the limit. The process-wide limits are set on [MemoryTracker::global].
+    pub fn set_soft_heap_limit(&self, limit: usize) -> usize {
+        self.memory.set_soft_limit(limit)
+    }
+
+    pub fn soft_heap_limit(&self) -> usize {
+        self.memory.soft_limit()
+    }
+
+    /// Sets the hard heap limit of the connection in bytes and returns the previous one.
+    /// Allocations that would go over the limit fail with [LimboError::OutOfMemory]. Zero
+    /// removes the limit.
+    pub fn set_hard_heap_limit(&self, limit: usize) -> usize {
+        self.memory.set_hard_limit(limit)
+    }
+
+    pub fn hard_heap_limit(&self) -> usize {
+        self.memory.hard_limit()
+    }
+
+    /// Frees as much memory as possible by evicting the clean pages of the page cache and
+    /// freeing the unused page buffers. Returns the number of bytes released.
+    pub fn shrink_memory(&self) -> usize {
+        self.pager.shrink_memory()
+    }
+
+    /// Returns the contents of the database as a contiguous image, see [Database::serialize].
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        self._db.serialize()
+    }
+
+    /// Opens the BLOB or TEXT value of `column` in the row `rowid` of `table` for incremental
+    /// I/O, see [Blob].
+    pub fn blob_open(
+        self: &Arc<Connection>,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        writable: bool,
+    ) -> Result<Blob> {
+        Blob::open(self, table, column, rowid, writable)
+    }
+
     pub fn checkpoint(&self) -> Result<CheckpointResult> {
-        let checkpoint_result = self.pager.wal_checkpoint();
-        Ok(checkpoint_result)
+        self.checkpoint_with_mode(CheckpointMode::Passive)
     }
 
-    /// Close a connection and checkpoint.
+    /// Checkpoints the WAL like `PRAGMA wal_checkpoint(mode)`, see [Pager::wal_checkpoint].
+    pub fn checkpoint_with_mode(&self, mode: CheckpointMode) -> Result<CheckpointResult> {
+        self.retry_busy(|| self.pager.wal_checkpoint(mode))
+    }
+
+    /// Close a connection and checkpoint. The temp database is deleted.
     pub fn close(&self) -> Result<()> {
+        self.temp.replace(None);
         self.pager.checkpoint_shutdown()
     }
 
@@ -594,6 +880,263 @@ impl Connection {
         self.cache_size.set(size);
     }
 
+    /// Sets the number of compiled statements kept by [Connection::prepare], evicting the least
+    /// recently used ones if there are more. Zero turns the statement cache off.
+    pub fn set_statement_cache_capacity(&self, capacity: usize) {
+        self.statement_cache.borrow_mut().set_capacity(capacity);
+    }
+
+    /// Returns the hit and miss counters and the size of the statement cache.
+    pub fn statement_cache_stats(&self) -> StatementCacheStats {
+        self.statement_cache.borrow().stats()
+    }
+
+    /// Drops all compiled statements from the statement cache.
+    pub fn clear_statement_cache(&self) {
+        self.statement_cache.borrow_mut().clear();
+    }
+
+    /// Retries with backoff for up to `timeout` when the database is locked by another
+    /// connection, replacing any busy handler. A zero timeout removes the busy handler.
+    pub fn busy_timeout(&self, timeout: Duration) {
+        let handler = if timeout.is_zero() {
+            BusyHandler::None
+        } else {
+            BusyHandler::Timeout(timeout)
+        };
+        self.busy_handler.replace(handler);
+    }
+
+    /// Returns the timeout set with [Connection::busy_timeout], zero if there is none.
+    pub fn get_busy_timeout(&self) -> Duration {
+        self.busy_handler.borrow().timeout()
+    }
+
+    /// Lets `callback` decide whether to retry when the database is locked by another
+    /// connection, replacing any busy timeout. `None` removes the busy handler.
+    pub fn busy_handler(&self, callback: Option<Box<BusyCallback>>) {
+        let handler = match callback {
+            Some(callback) => BusyHandler::Callback(callback),
+            None => BusyHandler::None,
+        };
+        self.busy_handler.replace(handler);
+    }
+
+    /// Asks the busy handler whether to retry a lock held by another connection that has
+    /// already been retried `retries` times, and waits out the handler's delay if it does.
+    pub(crate) fn wait_for_busy_lock(&self, retries: u32) -> bool {
+        let delay = self.busy_handler.borrow().retry_delay(retries);
+        match delay {
+            Some(delay) => {
+                if !delay.is_zero() {
+                    self.pager.io.sleep(delay);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs `f` again while it fails with [LimboError::Busy] and the busy handler retries.
+    fn retry_busy<T>(&self, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut retries = 0;
+        loop {
+            match f() {
+                Err(LimboError::Busy) if self.wait_for_busy_lock(retries) => retries += 1,
+                result => return result,
+            }
+        }
+    }
+
+    /// Calls `callback` every `period` VDBE instructions while a statement of this connection
+    /// runs. If the callback returns `true`, the statement is interrupted and returns
+    /// [StepResult::Interrupt]. `None` or a zero period removes the progress handler.
+    pub fn progress_handler(&self, period: u64, callback: Option<Box<ProgressCallback>>) {
+        self.progress.borrow_mut().handler = match callback {
+            Some(callback) if period > 0 => Some(ProgressHandler {
+                period,
+                callback: callback.into(),
+            }),
+            _ => None,
+        };
+    }
+
+    /// Interrupts the statements of this connection that run for longer than `timeout`, from
+    /// their first step until they are done or reset, with [StepResult::Interrupt]. Time is
+    /// measured with the clock of the IO. A zero timeout removes the limit. A statement can
+    /// override the limit with [Statement::set_timeout].
+    pub fn statement_timeout(&self, timeout: Duration) {
+        self.progress.borrow_mut().timeout = timeout;
+    }
+
+    /// Returns the timeout set with [Connection::statement_timeout], zero if there is none.
+    pub fn get_statement_timeout(&self) -> Duration {
+        self.progress.borrow().timeout
+    }
+
+    /// Registers a hook that is called for every row inserted, updated or deleted in a rowid
+    /// table through this connection, returning the previously registered hook.
+    pub fn update_hook(&self, hook: Option<Box<UpdateHook>>) -> Option<Box<UpdateHook>> {
+        std::mem::replace(&mut self.hooks.borrow_mut().update, hook)
+    }
+
+    /// Registers a hook that is called before a write transaction commits and can turn the
+    /// commit into a rollback, returning the previously registered hook.
+    pub fn commit_hook(&self, hook: Option<Box<CommitHook>>) -> Option<Box<CommitHook>> {
+        std::mem::replace(&mut self.hooks.borrow_mut().commit, hook)
+    }
+
+    /// Registers a hook that is called after a transaction is rolled back, returning the
+    /// previously registered hook.
+    pub fn rollback_hook(&self, hook: Option<Box<RollbackHook>>) -> Option<Box<RollbackHook>> {
+        std::mem::replace(&mut self.hooks.borrow_mut().rollback, hook)
+    }
+
+    /// Creates a session that records the changes made through this connection to the tables
+    /// it is attached to.
+    pub fn create_session(self: &Arc<Connection>) -> Session {
+        Session::new(self)
+    }
+
+    /// Applies a changeset or patchset produced by a [Session] in a single transaction. For
+    /// every change that conflicts with the database, `conflict` is called with the conflict
+    /// type, the table name, the change and the current row, if any, and decides what to do.
+    /// If it aborts, all the changes are rolled back and an error is returned.
+    pub fn apply_changeset<F>(self: &Arc<Connection>, changeset: &[u8], conflict: F) -> Result<()>
+    where
+        F: FnMut(ConflictType, &str, &Change, Option<&[Value]>) -> ConflictAction,
+    {
+        session::apply_changeset(self, changeset, conflict)
+    }
+
+    /// Rolls back the current transaction and returns the connection to autocommit mode.
+    pub(crate) fn rollback(self: &Arc<Connection>) -> Result<()> {
+        let in_transaction = !self.auto_commit.get()
+            || !matches!(self.transaction_state.get(), TransactionState::None);
+        let wrote = matches!(self.transaction_state.get(), TransactionState::Write);
+        if let Some(mv_store) = &self._db.mv_store {
+            for tx_id in self.mv_transactions.borrow_mut().drain(..) {
+                mv_store.rollback_tx(tx_id);
+            }
+        }
+        match self.transaction_state.get() {
+            TransactionState::Write => self.pager.rollback()?,
+            TransactionState::Read => self.pager.end_read_tx()?,
+            TransactionState::None => {}
+        }
+        self.transaction_state.replace(TransactionState::None);
+        let temp = self.temp.borrow().clone();
+        if let Some(temp) = temp {
+            if temp.rollback()? {
+                // Statements may have been compiled against temp tables that were rolled back.
+                self.statement_cache.borrow_mut().clear();
+            }
+        }
+        self.auto_commit.replace(true);
+        self.sessions.borrow().rollback();
+        if wrote {
+            // The transaction may have changed the schema, so load it again from the
+            // rolled back database.
+            self.reload_schema()?;
+        }
+        if in_transaction {
+            self.hooks.borrow().rolled_back();
+        }
+        Ok(())
+    }
+
+    /// Calls `f` with the schema that statements are compiled against: the schema of the main
+    /// database, overlaid with the temp schema once the connection has a temp database.
+    fn with_schema<T>(&self, f: impl FnOnce(&Schema) -> Result<T>) -> Result<T> {
+        let schema = self.schema.try_read_arc().ok_or(LimboError::SchemaLocked)?;
+        let temp = self.temp.borrow().clone();
+        match temp {
+            Some(temp) => {
+                let temp_schema = temp
+                    .schema()
+                    .try_read_arc()
+                    .ok_or(LimboError::SchemaLocked)?;
+                f(&Schema::with_temp(schema, temp_schema))
+            }
+            None => f(schema.deref()),
+        }
+    }
+
+    /// Returns the temp database of the connection, opening it on first use.
+    pub(crate) fn temp_database(&self) -> Result<Rc<TempDatabase>> {
+        if let Some(temp) = self.temp.borrow().as_ref() {
+            return Ok(temp.clone());
+        }
+        let temp = Rc::new(TempDatabase::open(&self._db.io, self.temp_store.get())?);
+        self.temp.replace(Some(temp.clone()));
+        Ok(temp)
+    }
+
+    /// Ends the transaction on the temp database once the one on the main database ended.
+    pub(crate) fn commit_temp(&self) -> Result<()> {
+        let temp = self.temp.borrow().clone();
+        match temp {
+            Some(temp) => temp.commit(),
+            None => Ok(()),
+        }
+    }
+
+    /// Called when the temp schema changed, which the schema cookie of the main database does
+    /// not track.
+    pub(crate) fn temp_schema_changed(&self) {
+        self.statement_cache.borrow_mut().clear();
+    }
+
+    pub fn temp_store(&self) -> TempStore {
+        self.temp_store.get()
+    }
+
+    /// Sets where the temp database is stored. As in SQLite, changing it deletes the temp
+    /// database along with all the temp tables and indexes.
+    pub fn set_temp_store(&self, temp_store: TempStore) -> Result<()> {
+        if temp_store == self.temp_store.get() {
+            return Ok(());
+        }
+        if self.temp.borrow().is_some() {
+            if !self.auto_commit.get() {
+                return Err(LimboError::TxError(
+                    "temporary storage cannot be changed from within a transaction".to_string(),
+                ));
+            }
+            self.temp.replace(None);
+            self.temp_schema_changed();
+        }
+        self.temp_store.set(temp_store);
+        Ok(())
+    }
+
+    /// Returns true if LIKE is case-sensitive, see `PRAGMA case_sensitive_like`.
+    pub fn case_sensitive_like(&self) -> bool {
+        self.syms.borrow().case_sensitive_like.get()
+    }
+
+    /// Makes LIKE case-sensitive or not. The statements in the cache were planned and compiled
+    /// with the previous setting, so they are dropped.
+    pub fn set_case_sensitive_like(&self, case_sensitive: bool) {
+        self.syms.borrow().case_sensitive_like.set(case_sensitive);
+        self.statement_cache.borrow_mut().clear();
+    }
+
+    fn reload_schema(self: &Arc<Connection>) -> Result<()> {
+        let stmt = self.prepare("SELECT * FROM sqlite_schema")?;
+        let mut schema = Schema::new();
+        parse_schema_rows(
+            Some(stmt),
+            &mut schema,
+            self.pager.io.clone(),
+            &self.syms.borrow(),
+            None,
+        )?;
+        schema.schema_version = self.header.lock().schema_cookie;
+        *self.schema.write() = schema;
+        self.load_index_stats()
+    }
+
     #[cfg(feature = "fs")]
     pub fn open_new(&self, path: &str, vfs: &str) -> Result<(Arc<dyn IO>, Arc<Database>)> {
         Database::open_with_vfs(&self._db, path, vfs)
@@ -611,6 +1154,10 @@ impl Connection {
             {
                 all_vfs.push("io_uring".to_string());
             }
+            #[cfg(feature = "compression")]
+            {
+                all_vfs.push("compressed".to_string());
+            }
             all_vfs.extend(crate::ext::list_vfs_modules());
         }
         all_vfs
@@ -636,7 +1183,18 @@ impl Connection {
                 eprintln!("Warning: {}", e);
             }
         }
-        Ok(())
+        schema.schema_version = self.header.lock().schema_cookie;
+        drop(schema);
+        self.load_index_stats()
+    }
+
+    /// Loads the statistics that ANALYZE stored in sqlite_stat1 into the indexes of the schema.
+    pub(crate) fn load_index_stats(self: &Arc<Connection>) -> Result<()> {
+        if self.schema.read().get_table(STAT1_TABLE_NAME).is_none() {
+            return Ok(());
+        }
+        let rows = self.query(format!("SELECT tbl, idx, stat FROM {STAT1_TABLE_NAME}"))?;
+        parse_stat1_rows(rows, &mut self.schema.write(), self.pager.io.clone())
     }
 
     // Clearly there is something to improve here, Vec<Vec<Value>> isn't a couple of tea
@@ -737,6 +1295,8 @@ impl Connection {
 
 pub struct Statement {
     program: Rc<vdbe::Program>,
+    /// Keeps the connection of the program alive while the statement exists.
+    _connection: Arc<Connection>,
     state: vdbe::ProgramState,
     mv_store: Option<Rc<MvStore>>,
     pager: Rc<Pager>,
@@ -748,9 +1308,15 @@ impl Statement {
         mv_store: Option<Rc<MvStore>>,
         pager: Rc<Pager>,
     ) -> Self {
-        let state = vdbe::ProgramState::new(program.max_registers, program.cursor_ref.len());
+        let connection = program.connection();
+        let state = vdbe::ProgramState::new(
+            program.max_registers,
+            program.cursor_ref.len(),
+            connection.memory.clone(),
+        );
         Self {
             program,
+            _connection: connection,
             state,
             mv_store,
             pager,
@@ -765,6 +1331,12 @@ impl Statement {
         self.state.interrupt();
     }
 
+    /// Interrupts the statement with [StepResult::Interrupt] if it runs for longer than
+    /// `timeout`, instead of the timeout of its connection. A zero timeout removes the limit.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.state.timeout = Some(timeout);
+    }
+
     pub fn step(&mut self) -> Result<StepResult> {
         self.program
             .step(&mut self.state, self.mv_store.clone(), self.pager.clone())
@@ -819,6 +1391,9 @@ pub struct SymbolTable {
     pub functions: HashMap<String, Rc<function::ExternalFunc>>,
     pub vtabs: HashMap<String, Rc<VirtualTable>>,
     pub vtab_modules: HashMap<String, Rc<crate::ext::VTabImpl>>,
+    /// Whether LIKE is case-sensitive, see `PRAGMA case_sensitive_like`. It is kept with the
+    /// functions since SQLite implements the pragma by registering another like() function.
+    pub case_sensitive_like: Cell<bool>,
 }
 
 impl std::fmt::Debug for SymbolTable {
@@ -862,6 +1437,7 @@ impl SymbolTable {
             functions: HashMap::new(),
             vtabs: HashMap::new(),
             vtab_modules: HashMap::new(),
+            case_sensitive_like: Cell::new(false),
         }
     }
 
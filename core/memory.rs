@@ -0,0 +1,182 @@
+//! Accounting of the memory used by the database engine.
+//!
+//! Each connection charges the page buffers, sorter records and cached JSON it allocates to its
+//! own [MemoryTracker], whose parent is the process-wide tracker returned by
+//! [MemoryTracker::global]. A tracker can have a soft limit, over which the page cache evicts
+//! pages before allocating new buffers and the JSON cache stops caching, and a hard limit, over
+//! which allocations fail with [LimboError::OutOfMemory]. A limit of zero means no limit.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::{LimboError, Result};
+
+static GLOBAL_MEMORY: OnceLock<Arc<MemoryTracker>> = OnceLock::new();
+
+#[derive(Debug, Default)]
+pub struct MemoryTracker {
+    used: AtomicUsize,
+    highwater: AtomicUsize,
+    soft_limit: AtomicUsize,
+    hard_limit: AtomicUsize,
+    parent: Option<Arc<MemoryTracker>>,
+}
+
+impl MemoryTracker {
+    /// Returns the tracker of the memory used by all connections of the process.
+    pub fn global() -> &'static Arc<MemoryTracker> {
+        GLOBAL_MEMORY.get_or_init(|| Arc::new(MemoryTracker::default()))
+    }
+
+    /// Creates a tracker whose allocations are also charged to `parent`.
+    pub fn with_parent(parent: Arc<MemoryTracker>) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Default::default()
+        }
+    }
+
+    /// Bytes currently allocated.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Maximum number of bytes allocated at once since the tracker was created or the highwater
+    /// mark was last reset. If `reset` is set, the mark is reset to the bytes currently used.
+    pub fn highwater(&self, reset: bool) -> usize {
+        if reset {
+            self.highwater.swap(self.used(), Ordering::Relaxed)
+        } else {
+            self.highwater.load(Ordering::Relaxed)
+        }
+    }
+
+    pub fn soft_limit(&self) -> usize {
+        self.soft_limit.load(Ordering::Relaxed)
+    }
+
+    /// Sets the soft limit and returns the previous one. As in SQLite, the soft limit cannot be
+    /// raised above the hard limit.
+    pub fn set_soft_limit(&self, limit: usize) -> usize {
+        let hard_limit = self.hard_limit();
+        let limit = if hard_limit > 0 && (limit == 0 || limit > hard_limit) {
+            hard_limit
+        } else {
+            limit
+        };
+        self.soft_limit.swap(limit, Ordering::Relaxed)
+    }
+
+    pub fn hard_limit(&self) -> usize {
+        self.hard_limit.load(Ordering::Relaxed)
+    }
+
+    /// Sets the hard limit and returns the previous one, lowering the soft limit to the new hard
+    /// limit if it was above it.
+    pub fn set_hard_limit(&self, limit: usize) -> usize {
+        let previous = self.hard_limit.swap(limit, Ordering::Relaxed);
+        let soft_limit = self.soft_limit();
+        if limit > 0 && (soft_limit == 0 || soft_limit > limit) {
+            self.soft_limit.store(limit, Ordering::Relaxed);
+        }
+        previous
+    }
+
+    /// Returns whether allocating `bytes` more would go over the soft limit of this tracker or
+    /// of one of its ancestors.
+    pub fn over_soft_limit(&self, bytes: usize) -> bool {
+        let soft_limit = self.soft_limit();
+        (soft_limit > 0 && self.used().saturating_add(bytes) > soft_limit)
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.over_soft_limit(bytes))
+    }
+
+    /// Returns whether allocating `bytes` more would go over the hard limit of this tracker or
+    /// of one of its ancestors.
+    pub fn over_hard_limit(&self, bytes: usize) -> bool {
+        let hard_limit = self.hard_limit();
+        (hard_limit > 0 && self.used().saturating_add(bytes) > hard_limit)
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.over_hard_limit(bytes))
+    }
+
+    /// Charges `bytes`, failing with [LimboError::OutOfMemory] if that would go over a hard
+    /// limit.
+    pub fn try_allocate(&self, bytes: usize) -> Result<()> {
+        if self.over_hard_limit(bytes) {
+            return Err(LimboError::OutOfMemory);
+        }
+        self.allocate(bytes);
+        Ok(())
+    }
+
+    /// Charges `bytes` regardless of the limits, for allocations that were checked beforehand.
+    pub fn allocate(&self, bytes: usize) {
+        let used = self.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.highwater.fetch_max(used, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.allocate(bytes);
+        }
+    }
+
+    /// Returns `bytes` previously charged with [Self::allocate] or [Self::try_allocate].
+    pub fn release(&self, bytes: usize) {
+        let _ = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+        if let Some(parent) = &self.parent {
+            parent.release(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_tracker_charges_parent() {
+        let parent = Arc::new(MemoryTracker::default());
+        let child = MemoryTracker::with_parent(parent.clone());
+        child.allocate(100);
+        assert_eq!(child.used(), 100);
+        assert_eq!(parent.used(), 100);
+        child.release(40);
+        assert_eq!(child.used(), 60);
+        assert_eq!(parent.used(), 60);
+        assert_eq!(parent.highwater(true), 100);
+        assert_eq!(parent.highwater(false), 60);
+    }
+
+    #[test]
+    fn test_memory_tracker_hard_limit() {
+        let parent = Arc::new(MemoryTracker::default());
+        let child = MemoryTracker::with_parent(parent.clone());
+        parent.set_hard_limit(150);
+        child.try_allocate(100).unwrap();
+        assert!(matches!(
+            child.try_allocate(100),
+            Err(LimboError::OutOfMemory)
+        ));
+        assert_eq!(child.used(), 100);
+        assert!(child.over_soft_limit(51));
+        assert!(!child.over_soft_limit(50));
+    }
+
+    #[test]
+    fn test_memory_tracker_soft_limit_capped_by_hard_limit() {
+        let tracker = MemoryTracker::default();
+        tracker.set_soft_limit(1000);
+        tracker.set_hard_limit(500);
+        assert_eq!(tracker.soft_limit(), 500);
+        tracker.set_soft_limit(2000);
+        assert_eq!(tracker.soft_limit(), 500);
+        tracker.set_soft_limit(100);
+        assert_eq!(tracker.soft_limit(), 100);
+    }
+}
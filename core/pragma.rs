@@ -34,6 +34,7 @@ fn pragma_for(pragma: PragmaName) -> Pragma {
     use PragmaName::*;
 
     match pragma {
+        BusyTimeout => Pragma::new(PragmaFlags::Result0, &["timeout"]),
         CacheSize => Pragma::new(
             PragmaFlags::NeedSchema
                 | PragmaFlags::Result0
@@ -41,13 +42,17 @@ fn pragma_for(pragma: PragmaName) -> Pragma {
                 | PragmaFlags::NoColumns1,
             &["cache_size"],
         ),
+        CaseSensitiveLike => Pragma::new(PragmaFlags::NoColumns, &[]),
+        HardHeapLimit => Pragma::new(PragmaFlags::Result0, &["hard_heap_limit"]),
         JournalMode => Pragma::new(
             PragmaFlags::NeedSchema | PragmaFlags::Result0 | PragmaFlags::SchemaReq,
             &["journal_mode"],
         ),
+        Key | Rekey => Pragma::new(PragmaFlags::empty(), &[]),
         LegacyFileFormat => {
             unreachable!("pragma_for() called with LegacyFileFormat, which is unsupported")
         }
+        MmapSize => Pragma::new(PragmaFlags::Result0, &["mmap_size"]),
         PageCount => Pragma::new(
             PragmaFlags::NeedSchema | PragmaFlags::Result0 | PragmaFlags::SchemaReq,
             &["page_count"],
@@ -60,10 +65,16 @@ fn pragma_for(pragma: PragmaName) -> Pragma {
             PragmaFlags::NoColumns1 | PragmaFlags::Result0,
             &["schema_version"],
         ),
+        ShrinkMemory => Pragma::new(PragmaFlags::NoColumns, &[]),
+        SoftHeapLimit => Pragma::new(PragmaFlags::Result0, &["soft_heap_limit"]),
         TableInfo => Pragma::new(
             PragmaFlags::NeedSchema | PragmaFlags::Result1 | PragmaFlags::SchemaOpt,
             &["cid", "name", "type", "notnull", "dflt_value", "pk"],
         ),
+        TempStore => Pragma::new(
+            PragmaFlags::NoColumns1 | PragmaFlags::Result0,
+            &["temp_store"],
+        ),
         UserVersion => Pragma::new(
             PragmaFlags::NoColumns1 | PragmaFlags::Result0,
             &["user_version"],
@@ -0,0 +1,44 @@
+//! Bounding the execution of statements.
+//!
+//! A running statement periodically asks the connection's [ProgressHandler] whether to go on
+//! and checks its timeout against the IO's [crate::Clock]. A statement that is stopped either
+//! way is interrupted: it returns [crate::StepResult::Interrupt], like after
+//! [crate::Statement::interrupt].
+
+use std::rc::Rc;
+use std::time::Duration;
+
+/// A user supplied progress callback. Returning `true` interrupts the running statement.
+pub type ProgressCallback = dyn Fn() -> bool;
+
+/// Number of VDBE instructions a statement executes between two checks of its timeout.
+pub(crate) const TIMEOUT_CHECK_PERIOD: u64 = 1000;
+
+pub(crate) struct ProgressHandler {
+    /// Number of VDBE instructions executed between two calls of the callback.
+    pub(crate) period: u64,
+    pub(crate) callback: Rc<ProgressCallback>,
+}
+
+/// Limits on how long the statements of a connection run.
+#[derive(Default)]
+pub(crate) struct Progress {
+    pub(crate) handler: Option<ProgressHandler>,
+    /// Timeout of the statements that don't set their own, zero if there is none.
+    pub(crate) timeout: Duration,
+}
+
+impl Progress {
+    /// Number of VDBE instructions between two calls of the progress callback, zero if there
+    /// is no callback.
+    pub(crate) fn period(&self) -> u64 {
+        self.handler.as_ref().map_or(0, |handler| handler.period)
+    }
+
+    /// Returns the progress callback, to be called once the progress is no longer borrowed.
+    pub(crate) fn callback(&self) -> Option<Rc<ProgressCallback>> {
+        self.handler
+            .as_ref()
+            .map(|handler| handler.callback.clone())
+    }
+}
@@ -9,6 +9,7 @@ use limbo_sqlite3_parser::{
     ast::{Cmd, CreateTableBody, QualifiedName, ResultColumn, Stmt},
     lexer::sql::Parser,
 };
+use parking_lot::{ArcRwLockReadGuard, RawRwLock};
 use std::collections::{BTreeSet, HashMap};
 use std::rc::Rc;
 use std::sync::Arc;
@@ -16,6 +17,14 @@ use tracing::trace;
 
 const SCHEMA_TABLE_NAME: &str = "sqlite_schema";
 const SCHEMA_TABLE_NAME_ALT: &str = "sqlite_master";
+const TEMP_SCHEMA_TABLE_NAME: &str = "sqlite_temp_schema";
+const TEMP_SCHEMA_TABLE_NAME_ALT: &str = "sqlite_temp_master";
+pub const STAT1_TABLE_NAME: &str = "sqlite_stat1";
+
+/// Index of the main database in the `db` operand of instructions.
+pub const MAIN_DB: usize = 0;
+/// Index of the temp database in the `db` operand of instructions.
+pub const TEMP_DB: usize = 1;
 
 pub struct Schema {
     pub tables: HashMap<String, Arc<Table>>,
@@ -27,6 +36,18 @@ pub struct Schema {
     /// indexes.
     #[cfg(not(feature = "index_experimental"))]
     pub has_indexes: std::collections::HashSet<String>,
+    /// Value of the schema cookie in the database header when the schema was loaded.
+    /// DDL statements write `schema_version + 1` back to the header.
+    pub schema_version: u32,
+    /// The schemas a schema made by [Schema::with_temp] looks names up in.
+    databases: Option<Box<Databases>>,
+}
+
+/// The schemas of the main and the temp database of a connection, locked for reading while
+/// statements are compiled against them.
+struct Databases {
+    main: ArcRwLockReadGuard<RawRwLock, Schema>,
+    temp: ArcRwLockReadGuard<RawRwLock, Schema>,
 }
 
 impl Schema {
@@ -45,16 +66,11 @@ impl Schema {
             indexes,
             #[cfg(not(feature = "index_experimental"))]
             has_indexes,
+            schema_version: 0,
+            databases: None,
         }
     }
 
-    pub fn is_unique_idx_name(&self, name: &str) -> bool {
-        !self
-            .indexes
-            .iter()
-            .any(|idx| idx.1.iter().any(|i| i.name == name))
-    }
-
     pub fn add_btree_table(&mut self, table: Rc<BTreeTable>) {
         let name = normalize_ident(&table.name);
         self.tables.insert(name, Table::BTree(table).into());
@@ -65,28 +81,107 @@ impl Schema {
         self.tables.insert(name, Table::Virtual(table).into());
     }
 
+    /// Looks up a table whose name is not qualified by a database: TEMP tables hide the main
+    /// tables of the same name.
     pub fn get_table(&self, name: &str) -> Option<Arc<Table>> {
         let name = normalize_ident(name);
-        let name = if name.eq_ignore_ascii_case(&SCHEMA_TABLE_NAME_ALT) {
+        if let Some(databases) = &self.databases {
+            if name == SCHEMA_TABLE_NAME || name == SCHEMA_TABLE_NAME_ALT {
+                return databases.main.get_table(&name);
+            }
+            return databases
+                .temp_table(&name)
+                .or_else(|| databases.main.get_table(&name));
+        }
+        let name = if name.eq_ignore_ascii_case(SCHEMA_TABLE_NAME_ALT) {
             SCHEMA_TABLE_NAME
+        } else if name.eq_ignore_ascii_case(TEMP_SCHEMA_TABLE_NAME_ALT) {
+            TEMP_SCHEMA_TABLE_NAME
         } else {
             &name
         };
         self.tables.get(name).cloned()
     }
 
+    /// Looks up a table of database `db`, [MAIN_DB] or [TEMP_DB].
+    pub fn get_table_in(&self, db: usize, name: &str) -> Option<Arc<Table>> {
+        match (&self.databases, db) {
+            (Some(databases), MAIN_DB) => databases.main.get_table(name),
+            (Some(databases), _) => databases.temp_table(&normalize_ident(name)),
+            (None, MAIN_DB) => self.get_table(name),
+            (None, _) => None,
+        }
+    }
+
+    /// Looks up a table by its name as written in a statement, `db.name` or `name`.
+    pub fn get_qualified_table(&self, name: &QualifiedName) -> Result<Option<Arc<Table>>> {
+        match &name.db_name {
+            Some(db_name) => Ok(self.get_table_in(database_index(db_name)?, &name.name.0)),
+            None => Ok(self.get_table(&name.name.0)),
+        }
+    }
+
+    /// Returns a schema that looks unqualified names up in `temp`, then in `main`, so the
+    /// tables of `temp` hide the ones of the same name. Names qualified with `main.` or `temp.`
+    /// are only looked up in that database. The schema table of the temp database is
+    /// available as `sqlite_temp_schema`. Both schemas stay locked for reading until the
+    /// returned schema is dropped.
+    pub fn with_temp(
+        main: ArcRwLockReadGuard<RawRwLock, Schema>,
+        temp: ArcRwLockReadGuard<RawRwLock, Schema>,
+    ) -> Schema {
+        Schema {
+            tables: HashMap::new(),
+            indexes: HashMap::new(),
+            #[cfg(not(feature = "index_experimental"))]
+            has_indexes: std::collections::HashSet::new(),
+            schema_version: main.schema_version,
+            databases: Some(Box::new(Databases { main, temp })),
+        }
+    }
+
+    /// Returns the b-tree tables of all databases, main tables hidden by TEMP tables included.
+    pub fn btree_tables(&self) -> Vec<Rc<BTreeTable>> {
+        let Some(databases) = &self.databases else {
+            return self
+                .tables
+                .values()
+                .filter_map(|table| table.btree())
+                .collect();
+        };
+        let temp_tables = databases
+            .temp
+            .tables
+            .keys()
+            .filter_map(|name| databases.temp_table(name))
+            .filter_map(|table| table.btree());
+        databases
+            .main
+            .btree_tables()
+            .into_iter()
+            .chain(temp_tables)
+            .collect()
+    }
+
+    /// Returns the root pages of the tables of the main database.
+    pub fn main_table_root_pages(&self) -> Vec<usize> {
+        if let Some(databases) = &self.databases {
+            return databases.main.main_table_root_pages();
+        }
+        self.tables
+            .values()
+            .filter_map(|table| table.btree())
+            .map(|table| table.root_page)
+            .collect()
+    }
+
     pub fn remove_table(&mut self, table_name: &str) {
         let name = normalize_ident(table_name);
         self.tables.remove(&name);
     }
 
     pub fn get_btree_table(&self, name: &str) -> Option<Rc<BTreeTable>> {
-        let name = normalize_ident(name);
-        if let Some(table) = self.tables.get(&name) {
-            table.btree()
-        } else {
-            None
-        }
+        self.get_table(name)?.btree()
     }
 
     #[cfg(feature = "index_experimental")]
@@ -98,21 +193,72 @@ impl Schema {
             .push(index.clone())
     }
 
+    /// Returns the indexes of the table that [Schema::get_table] finds for `table_name`.
     pub fn get_indices(&self, table_name: &str) -> &[Arc<Index>] {
         let name = normalize_ident(table_name);
+        if let Some(databases) = &self.databases {
+            return databases.schema_of(&name).get_indices(&name);
+        }
         self.indexes
             .get(&name)
             .map_or_else(|| &[] as &[Arc<Index>], |v| v.as_slice())
     }
 
-    pub fn get_index(&self, table_name: &str, index_name: &str) -> Option<&Arc<Index>> {
-        let name = normalize_ident(table_name);
-        self.indexes
-            .get(&name)?
-            .iter()
+    /// Returns the indexes of `table`, looked up in the database of the table.
+    pub fn get_table_indices(&self, table: &BTreeTable) -> &[Arc<Index>] {
+        match (&self.databases, table.db()) {
+            (Some(databases), MAIN_DB) => databases.main.get_indices(&table.name),
+            (Some(databases), _) => databases.temp.get_indices(&table.name),
+            (None, MAIN_DB) => self.get_indices(&table.name),
+            (None, _) => &[],
+        }
+    }
+
+    /// Looks up an index of database `db`, [MAIN_DB] or [TEMP_DB], by name.
+    pub fn find_index_in(&self, db: usize, index_name: &str) -> Option<&Arc<Index>> {
+        let schema = match (&self.databases, db) {
+            (Some(databases), MAIN_DB) => &databases.main,
+            (Some(databases), _) => &databases.temp,
+            (None, MAIN_DB) => self,
+            (None, _) => return None,
+        };
+        schema
+            .indexes
+            .values()
+            .flatten()
             .find(|index| index.name == index_name)
     }
 
+    /// Looks up an index by its name as written in a statement, `db.name` or `name`, and
+    /// returns it with its database. A name that is not qualified by a database is looked up
+    /// in the temp database first.
+    pub fn find_qualified_index(
+        &self,
+        name: &QualifiedName,
+    ) -> Result<Option<(usize, &Arc<Index>)>> {
+        let index_name = normalize_ident(&name.name.0);
+        let dbs = match &name.db_name {
+            Some(db_name) => vec![database_index(db_name)?],
+            None => vec![TEMP_DB, MAIN_DB],
+        };
+        Ok(dbs
+            .into_iter()
+            .find_map(|db| self.find_index_in(db, &index_name).map(|index| (db, index))))
+    }
+
+    /// Sets the sqlite_stat1 statistics of index `index_name` of table `table_name`.
+    pub fn set_index_stat(&mut self, table_name: &str, index_name: &str, stat: Vec<u64>) {
+        let Some(indexes) = self.indexes.get_mut(&normalize_ident(table_name)) else {
+            return;
+        };
+        if let Some(index) = indexes
+            .iter_mut()
+            .find(|index| index.name.eq_ignore_ascii_case(index_name))
+        {
+            Arc::make_mut(index).stat = Some(stat);
+        }
+    }
+
     pub fn remove_indices_for_table(&mut self, table_name: &str) {
         let name = normalize_ident(table_name);
         self.indexes.remove(&name);
@@ -128,6 +274,11 @@ impl Schema {
 
     #[cfg(not(feature = "index_experimental"))]
     pub fn table_has_indexes(&self, table_name: &str) -> bool {
+        if let Some(databases) = &self.databases {
+            return databases
+                .schema_of(&normalize_ident(table_name))
+                .table_has_indexes(table_name);
+        }
         self.has_indexes.contains(table_name)
     }
 
@@ -137,6 +288,66 @@ impl Schema {
     }
 }
 
+impl Databases {
+    /// Looks up a table of the temp database, which is marked as such. The names of the schema
+    /// table all refer to the schema table of the temp database, `sqlite_temp_schema`.
+    fn temp_table(&self, name: &str) -> Option<Arc<Table>> {
+        let name = if is_schema_table_name(name) {
+            SCHEMA_TABLE_NAME
+        } else {
+            name
+        };
+        let Table::BTree(table) = self.temp.tables.get(name)?.as_ref() else {
+            return None;
+        };
+        let name = if name == SCHEMA_TABLE_NAME {
+            TEMP_SCHEMA_TABLE_NAME
+        } else {
+            name
+        };
+        Some(Arc::new(Table::BTree(Rc::new(BTreeTable {
+            name: name.to_string(),
+            is_temp: true,
+            ..table.as_ref().clone()
+        }))))
+    }
+
+    /// Returns the schema of the database that holds the table a name that is not qualified
+    /// by a database refers to.
+    fn schema_of(&self, name: &str) -> &Schema {
+        if !is_schema_table_name(name) && self.temp.tables.contains_key(name) {
+            &self.temp
+        } else {
+            &self.main
+        }
+    }
+}
+
+/// Returns true if `name` is one of the names of the schema tables.
+fn is_schema_table_name(name: &str) -> bool {
+    [
+        SCHEMA_TABLE_NAME,
+        SCHEMA_TABLE_NAME_ALT,
+        TEMP_SCHEMA_TABLE_NAME,
+        TEMP_SCHEMA_TABLE_NAME_ALT,
+    ]
+    .iter()
+    .any(|table_name| name.eq_ignore_ascii_case(table_name))
+}
+
+/// Returns the index of the database named `db_name` in a statement, [MAIN_DB] or [TEMP_DB].
+pub fn database_index(db_name: &ast::Name) -> Result<usize> {
+    let name = normalize_ident(&db_name.0);
+    match name.as_str() {
+        "main" => Ok(MAIN_DB),
+        "temp" => Ok(TEMP_DB),
+        _ => Err(LimboError::ParseError(format!(
+            "unknown database {}",
+            db_name.0
+        ))),
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Table {
     BTree(Rc<BTreeTable>),
@@ -184,6 +395,14 @@ impl Table {
         }
     }
 
+    /// The database that holds the table, [MAIN_DB] or [TEMP_DB].
+    pub fn db(&self) -> usize {
+        match self {
+            Self::BTree(table) => table.db(),
+            _ => MAIN_DB,
+        }
+    }
+
     pub fn btree(&self) -> Option<Rc<BTreeTable>> {
         match self {
             Self::BTree(table) => Some(table.clone()),
@@ -221,9 +440,23 @@ pub struct BTreeTable {
     pub has_rowid: bool,
     pub is_strict: bool,
     pub unique_sets: Option<Vec<Vec<(String, SortOrder)>>>,
+    /// Whether the table lives in the temp database of the connection.
+    pub is_temp: bool,
+    /// Whether the rowid alias is declared `AUTOINCREMENT`, so that rowids are never reused
+    /// and the largest one is kept in `sqlite_sequence`.
+    pub has_autoincrement: bool,
 }
 
 impl BTreeTable {
+    /// The database that holds the table, [MAIN_DB] or [TEMP_DB].
+    pub fn db(&self) -> usize {
+        if self.is_temp {
+            TEMP_DB
+        } else {
+            MAIN_DB
+        }
+    }
+
     pub fn get_rowid_alias_column(&self) -> Option<(usize, &Column)> {
         if self.primary_key_columns.len() == 1 {
             let (idx, col) = self.get_column(&self.primary_key_columns[0].0)?;
@@ -371,7 +604,7 @@ impl Ord for UniqueColumnProps {
     }
 }
 
-fn create_table(
+pub(crate) fn create_table(
     tbl_name: QualifiedName,
     body: CreateTableBody,
     root_page: usize,
@@ -384,6 +617,7 @@ fn create_table(
     let is_strict: bool;
     // BtreeSet here to preserve order of inserted keys
     let mut unique_sets: Vec<BTreeSet<UniqueColumnProps>> = vec![];
+    let mut has_autoincrement = false;
     match body {
         CreateTableBody::ColumnsAndConstraints {
             columns,
@@ -394,9 +628,12 @@ fn create_table(
             if let Some(constraints) = constraints {
                 for c in constraints {
                     if let limbo_sqlite3_parser::ast::TableConstraint::PrimaryKey {
-                        columns, ..
+                        columns,
+                        auto_increment,
+                        ..
                     } = c.constraint
                     {
+                        has_autoincrement |= auto_increment;
                         for column in columns {
                             let col_name = match column.expr {
                                 Expr::Id(id) => normalize_ident(&id.0),
@@ -496,9 +733,11 @@ fn create_table(
                     match &c_def.constraint {
                         limbo_sqlite3_parser::ast::ColumnConstraint::PrimaryKey {
                             order: o,
+                            auto_increment,
                             ..
                         } => {
                             primary_key = true;
+                            has_autoincrement |= auto_increment;
                             if let Some(o) = o {
                                 order = o.clone();
                             }
@@ -558,6 +797,18 @@ fn create_table(
             col.is_rowid_alias = false;
         }
     }
+    if has_autoincrement {
+        if !has_rowid {
+            return Err(LimboError::ParseError(
+                "AUTOINCREMENT not allowed on WITHOUT ROWID tables".to_string(),
+            ));
+        }
+        if !cols.iter().any(|col| col.is_rowid_alias) {
+            return Err(LimboError::ParseError(
+                "AUTOINCREMENT is only allowed on an INTEGER PRIMARY KEY".to_string(),
+            ));
+        }
+    }
     Ok(BTreeTable {
         root_page,
         name: table_name,
@@ -581,6 +832,8 @@ fn create_table(
                     .collect(),
             )
         },
+        is_temp: false,
+        has_autoincrement,
     })
 }
 
@@ -955,11 +1208,13 @@ pub fn sqlite_schema_table() -> BTreeTable {
             },
         ],
         unique_sets: None,
+        is_temp: false,
+        has_autoincrement: false,
     }
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Index {
     pub name: String,
     pub table_name: String,
@@ -973,6 +1228,13 @@ pub struct Index {
     /// For example, WITHOUT ROWID tables (not supported in Limbo yet),
     /// and  SELECT DISTINCT ephemeral indexes will not have a rowid.
     pub has_rowid: bool,
+    /// The WHERE clause of a partial index, as written in its CREATE INDEX statement.
+    /// Only the rows of the table for which it is true are in the index.
+    pub where_clause: Option<Box<Expr>>,
+    /// The statistics ANALYZE stored for the index in sqlite_stat1: the number of entries of the
+    /// index, then the average number of entries that share a value of its first column, of its
+    /// first two columns, and so on. None if there are no statistics for the index.
+    pub stat: Option<Vec<u64>>,
 }
 
 #[allow(dead_code)]
@@ -988,6 +1250,39 @@ pub struct IndexColumn {
     pub pos_in_table: usize,
     pub collation: Option<CollationSeq>,
     pub default: Option<Expr>,
+    /// The expression of a column of an index on expressions, as written in its CREATE INDEX statement.
+    /// for example:
+    /// CREATE INDEX idx ON t(lower(b))
+    /// expr == Some(lower(b)), and pos_in_table is usize::MAX since no column of the table is indexed directly.
+    pub expr: Option<Box<Expr>>,
+}
+
+impl IndexColumn {
+    /// Creates the column of an index on the expression `expr`.
+    /// An explicit COLLATE at the top of the expression gives the collation of the column.
+    pub fn from_expr(expr: Expr, order: SortOrder) -> Result<IndexColumn> {
+        let collation = match &expr {
+            Expr::Collate(_, collation_name) => Some(CollationSeq::new(collation_name)?),
+            _ => None,
+        };
+        Ok(IndexColumn {
+            name: expr.to_string(),
+            order,
+            pos_in_table: usize::MAX,
+            collation,
+            default: None,
+            expr: Some(Box::new(expr)),
+        })
+    }
+}
+
+/// Returns the normalized name of the table column an indexed column of a CREATE INDEX statement
+/// refers to, or None if the indexed column is an expression.
+pub fn indexed_column_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Id(ast::Id(name)) | Expr::Name(ast::Name(name)) => Some(normalize_ident(name)),
+        _ => None,
+    }
 }
 
 impl Index {
@@ -1000,12 +1295,17 @@ impl Index {
                 tbl_name,
                 columns,
                 unique,
+                where_clause,
                 ..
             })) => {
                 let index_name = normalize_ident(&idx_name.name.0);
                 let mut index_columns = Vec::with_capacity(columns.len());
                 for col in columns.into_iter() {
-                    let name = normalize_ident(&col.expr.to_string());
+                    let order = col.order.unwrap_or(SortOrder::Asc);
+                    let Some(name) = indexed_column_name(&col.expr) else {
+                        index_columns.push(IndexColumn::from_expr(col.expr, order)?);
+                        continue;
+                    };
                     let Some((pos_in_table, _)) = table.get_column(&name) else {
                         return Err(crate::LimboError::InternalError(format!(
                             "Column {} is in index {} but not found in table {}",
@@ -1015,10 +1315,11 @@ impl Index {
                     let (_, column) = table.get_column(&name).unwrap();
                     index_columns.push(IndexColumn {
                         name,
-                        order: col.order.unwrap_or(SortOrder::Asc),
+                        order,
                         pos_in_table,
                         collation: column.collation,
                         default: column.default.clone(),
+                        expr: None,
                     });
                 }
                 Ok(Index {
@@ -1029,6 +1330,8 @@ impl Index {
                     unique,
                     ephemeral: false,
                     has_rowid: table.has_rowid,
+                    where_clause,
+                    stat: None,
                 })
             }
             _ => todo!("Expected create index statement"),
@@ -1083,6 +1386,7 @@ impl Index {
                         pos_in_table,
                         collation: column.collation,
                         default: column.default.clone(),
+                        expr: None,
                     }
                 })
                 .collect::<Vec<_>>();
@@ -1095,6 +1399,8 @@ impl Index {
                 unique: true,
                 ephemeral: false,
                 has_rowid: table.has_rowid,
+                where_clause: None,
+                stat: None,
             });
         }
 
@@ -1125,10 +1431,13 @@ impl Index {
                             pos_in_table,
                             collation: column.collation,
                             default: column.default.clone(),
+                            expr: None,
                         }],
                         unique: true,
                         ephemeral: false,
                         has_rowid: table.has_rowid,
+                        where_clause: None,
+                        stat: None,
                     })
                 } else {
                     None
@@ -1193,6 +1502,7 @@ impl Index {
                             pos_in_table,
                             collation: column.collation,
                             default: column.default.clone(),
+                            expr: None,
                         }
                     });
                     Index {
@@ -1203,6 +1513,8 @@ impl Index {
                         unique: true,
                         ephemeral: false,
                         has_rowid: table.has_rowid,
+                        where_clause: None,
+                        stat: None,
                     }
                 });
             indices.extend(unique_set_indices);
@@ -1226,6 +1538,11 @@ impl Index {
             .iter()
             .position(|c| c.pos_in_table == table_pos)
     }
+
+    /// Whether some column of the index is an expression rather than a column of the table.
+    pub fn has_expressions(&self) -> bool {
+        self.columns.iter().any(|c| c.expr.is_some())
+    }
 }
 
 #[cfg(test)]
@@ -1603,6 +1920,8 @@ mod tests {
                 collation: None,
             }],
             unique_sets: None,
+            is_temp: false,
+            has_autoincrement: false,
         };
 
         let _result = Index::automatic_from_primary_key_and_unique(
@@ -0,0 +1,340 @@
+//! Applying a changeset to a database.
+use std::num::NonZero;
+use std::sync::Arc;
+
+use super::changeset::{corrupt, Change, Changeset, ChangesetTable};
+use super::same_value;
+use crate::schema::BTreeTable;
+use crate::util::normalize_ident;
+use crate::{Connection, LimboError, Result, Statement, StepResult, UpdateKind, Value};
+
+/// Why a change could not be applied as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictType {
+    /// The row to update or delete exists, but its values are not the old values of the change.
+    Data,
+    /// The row to update or delete does not exist.
+    NotFound,
+    /// The row to insert already exists.
+    Conflict,
+    /// Applying the change violates a constraint.
+    Constraint,
+}
+
+/// What to do with a change that conflicts with the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictAction {
+    /// Skip the change.
+    Omit,
+    /// Apply the change anyway, overwriting the current row. Only valid for
+    /// [ConflictType::Data] and [ConflictType::Conflict].
+    Replace,
+    /// Stop and roll back all the changes applied so far.
+    Abort,
+}
+
+/// Applies `changeset` in a transaction of its own, see [Connection::apply_changeset].
+pub(crate) fn apply_changeset<F>(
+    conn: &Arc<Connection>,
+    changeset: &[u8],
+    mut conflict: F,
+) -> Result<()>
+where
+    F: FnMut(ConflictType, &str, &Change, Option<&[Value]>) -> ConflictAction,
+{
+    let changeset = Changeset::parse(changeset)?;
+    conn.execute("BEGIN")?;
+    let result = changeset
+        .tables
+        .iter()
+        .try_for_each(|table| apply_table(conn, changeset.patchset, table, &mut conflict));
+    match result {
+        Ok(()) => conn.execute("COMMIT"),
+        Err(err) => {
+            if !conn.get_auto_commit() {
+                conn.execute("ROLLBACK")?;
+            }
+            Err(err)
+        }
+    }
+}
+
+fn apply_table<F>(
+    conn: &Arc<Connection>,
+    patchset: bool,
+    table: &ChangesetTable,
+    conflict: &mut F,
+) -> Result<()>
+where
+    F: FnMut(ConflictType, &str, &Change, Option<&[Value]>) -> ConflictAction,
+{
+    let Some(schema_table) = conn.schema.read().get_btree_table(&table.name) else {
+        tracing::warn!(
+            "changeset: skipping changes to missing table {}",
+            table.name
+        );
+        return Ok(());
+    };
+    let Some(columns) = column_names(&schema_table, table) else {
+        tracing::warn!(
+            "changeset: skipping changes to mismatched table {}",
+            table.name
+        );
+        return Ok(());
+    };
+    let applier = TableApplier {
+        conn,
+        patchset,
+        table,
+        columns,
+    };
+    for change in &table.changes {
+        applier.apply(change, conflict)?;
+    }
+    Ok(())
+}
+
+/// Returns the quoted column names of `schema_table`, or `None` if the table doesn't have the
+/// columns and primary key of the changeset table.
+fn column_names(schema_table: &BTreeTable, table: &ChangesetTable) -> Option<Vec<String>> {
+    if schema_table.columns.len() != table.primary_key.len() {
+        return None;
+    }
+    let mut columns = Vec::with_capacity(schema_table.columns.len());
+    for (column, &pk_position) in schema_table.columns.iter().zip(&table.primary_key) {
+        let name = column.name.as_ref()?;
+        let expected = schema_table
+            .primary_key_columns
+            .iter()
+            .position(|(pk, _)| normalize_ident(pk) == normalize_ident(name))
+            .map_or(0, |pos| pos + 1);
+        if expected != pk_position as usize {
+            return None;
+        }
+        columns.push(quote(name));
+    }
+    Some(columns)
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+struct TableApplier<'a> {
+    conn: &'a Arc<Connection>,
+    patchset: bool,
+    table: &'a ChangesetTable,
+    columns: Vec<String>,
+}
+
+impl TableApplier<'_> {
+    fn apply<F>(&self, change: &Change, conflict: &mut F) -> Result<()>
+    where
+        F: FnMut(ConflictType, &str, &Change, Option<&[Value]>) -> ConflictAction,
+    {
+        let mut on_conflict = |kind, current: Option<&[Value]>| {
+            let action = conflict(kind, &self.table.name, change, current);
+            match (action, kind) {
+                (ConflictAction::Abort, _) => Err(LimboError::Constraint(format!(
+                    "changeset conflict on table {}",
+                    self.table.name
+                ))),
+                (ConflictAction::Replace, ConflictType::NotFound | ConflictType::Constraint) => {
+                    Err(LimboError::InvalidArgument(format!(
+                        "cannot replace a change with a {:?} conflict",
+                        kind
+                    )))
+                }
+                (action, _) => Ok(action == ConflictAction::Replace),
+            }
+        };
+        match change.op {
+            UpdateKind::Insert => {
+                let new = defined_values(&change.new)?;
+                if let Some(current) = self.find(&new)? {
+                    if !on_conflict(ConflictType::Conflict, Some(&current))? {
+                        return Ok(());
+                    }
+                    self.delete(&new)?;
+                }
+                match self.insert(&new) {
+                    Err(LimboError::Constraint(_)) => {
+                        on_conflict(ConflictType::Constraint, None)?;
+                        Ok(())
+                    }
+                    result => result,
+                }
+            }
+            UpdateKind::Delete => {
+                let Some(current) = self.find_old(&change.old)? else {
+                    on_conflict(ConflictType::NotFound, None)?;
+                    return Ok(());
+                };
+                if !self.matches_old(&change.old, &current)
+                    && !on_conflict(ConflictType::Data, Some(&current))?
+                {
+                    return Ok(());
+                }
+                self.delete_old(&change.old)
+            }
+            UpdateKind::Update => {
+                let Some(current) = self.find_old(&change.old)? else {
+                    on_conflict(ConflictType::NotFound, None)?;
+                    return Ok(());
+                };
+                if !self.matches_old(&change.old, &current)
+                    && !on_conflict(ConflictType::Data, Some(&current))?
+                {
+                    return Ok(());
+                }
+                match self.update(change) {
+                    Err(LimboError::Constraint(_)) => {
+                        on_conflict(ConflictType::Constraint, None)?;
+                        Ok(())
+                    }
+                    result => result,
+                }
+            }
+        }
+    }
+
+    /// Returns true if the current row has the old values of the change. A patchset doesn't
+    /// carry old values, so the check always succeeds for it.
+    fn matches_old(&self, old: &[Option<Value>], current: &[Value]) -> bool {
+        self.patchset
+            || old
+                .iter()
+                .zip(current)
+                .all(|(old, current)| old.as_ref().is_none_or(|old| same_value(old, current)))
+    }
+
+    fn primary_key<'v>(
+        &self,
+        values: impl Iterator<Item = Option<&'v Value>>,
+    ) -> Result<Vec<Value>> {
+        values
+            .enumerate()
+            .filter(|(column, _)| self.table.is_primary_key(*column))
+            .map(|(_, value)| {
+                value
+                    .cloned()
+                    .ok_or_else(|| corrupt("undefined primary key field"))
+            })
+            .collect()
+    }
+
+    fn pk_condition(&self) -> String {
+        self.columns
+            .iter()
+            .enumerate()
+            .filter(|(column, _)| self.table.is_primary_key(*column))
+            .map(|(_, name)| format!("{} = ?", name))
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    fn find(&self, row: &[Value]) -> Result<Option<Vec<Value>>> {
+        self.find_by_key(self.primary_key(row.iter().map(Some))?)
+    }
+
+    fn find_old(&self, old: &[Option<Value>]) -> Result<Option<Vec<Value>>> {
+        self.find_by_key(self.primary_key(old.iter().map(Option::as_ref))?)
+    }
+
+    fn find_by_key(&self, key: Vec<Value>) -> Result<Option<Vec<Value>>> {
+        let sql = format!(
+            "SELECT * FROM {} WHERE {}",
+            quote(&self.table.name),
+            self.pk_condition()
+        );
+        let mut found = None;
+        run(self.conn, &sql, key, |row| found = Some(row))?;
+        Ok(found)
+    }
+
+    fn insert(&self, row: &[Value]) -> Result<()> {
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(&self.table.name),
+            self.columns.join(", "),
+            vec!["?"; row.len()].join(", ")
+        );
+        run(self.conn, &sql, row.to_vec(), |_| {})
+    }
+
+    fn delete(&self, row: &[Value]) -> Result<()> {
+        self.delete_by_key(self.primary_key(row.iter().map(Some))?)
+    }
+
+    fn delete_old(&self, old: &[Option<Value>]) -> Result<()> {
+        self.delete_by_key(self.primary_key(old.iter().map(Option::as_ref))?)
+    }
+
+    fn delete_by_key(&self, key: Vec<Value>) -> Result<()> {
+        let sql = format!(
+            "DELETE FROM {} WHERE {}",
+            quote(&self.table.name),
+            self.pk_condition()
+        );
+        run(self.conn, &sql, key, |_| {})
+    }
+
+    fn update(&self, change: &Change) -> Result<()> {
+        let (assignments, mut params): (Vec<_>, Vec<_>) = change
+            .new
+            .iter()
+            .enumerate()
+            .filter(|(column, _)| !self.table.is_primary_key(*column))
+            .filter_map(|(column, value)| {
+                let value = value.clone()?;
+                Some((format!("{} = ?", self.columns[column]), value))
+            })
+            .unzip();
+        if assignments.is_empty() {
+            return Ok(());
+        }
+        params.extend(self.primary_key(change.old.iter().map(Option::as_ref))?);
+        let sql = format!(
+            "UPDATE {} SET {} WHERE {}",
+            quote(&self.table.name),
+            assignments.join(", "),
+            self.pk_condition()
+        );
+        run(self.conn, &sql, params, |_| {})
+    }
+}
+
+fn defined_values(values: &[Option<Value>]) -> Result<Vec<Value>> {
+    values
+        .iter()
+        .map(|value| {
+            value
+                .clone()
+                .ok_or_else(|| corrupt("undefined field in an inserted row"))
+        })
+        .collect()
+}
+
+/// Runs `sql` to completion with `params` bound, passing each result row to `on_row`.
+fn run(
+    conn: &Arc<Connection>,
+    sql: &str,
+    params: Vec<Value>,
+    mut on_row: impl FnMut(Vec<Value>),
+) -> Result<()> {
+    let mut stmt: Statement = conn.prepare(sql)?;
+    for (i, value) in params.into_iter().enumerate() {
+        stmt.bind_at(NonZero::new(i + 1).unwrap(), value);
+    }
+    loop {
+        match stmt.step()? {
+            StepResult::Row => {
+                let row = stmt.row().unwrap().get_values().cloned().collect();
+                on_row(row);
+            }
+            StepResult::IO => stmt.run_once()?,
+            StepResult::Done => return Ok(()),
+            StepResult::Interrupt | StepResult::Busy => return Err(LimboError::Busy),
+        }
+    }
+}
@@ -0,0 +1,428 @@
+//! The binary changeset and patchset formats of SQLite's session extension.
+//!
+//! A changeset is a sequence of tables, each a header followed by the changes to the table:
+//!
+//! ```text
+//! table:  'T' (changeset) or 'P' (patchset), varint column count, one byte per column with
+//!         its position in the primary key (0 if not part of it), NUL-terminated table name
+//! change: op (INSERT 0x12, UPDATE 0x17, DELETE 0x09), indirect flag, old and/or new record
+//! ```
+//!
+//! Records hold one field per column, each a type byte followed by the value: 0x00 undefined,
+//! 0x01 8-byte big-endian integer, 0x02 8-byte big-endian float, 0x03 text and 0x04 blob
+//! prefixed with a varint length, 0x05 NULL.
+//!
+//! In a changeset, an INSERT carries the new row and a DELETE the old row. An UPDATE carries
+//! an old record with the primary key and the old values of the changed columns and a new
+//! record with the new values of the changed columns, all other fields being undefined.
+//!
+//! A patchset leaves out what is only needed to detect conflicts: a DELETE carries only the
+//! primary key fields and an UPDATE a single record with the primary key and the new values
+//! of the changed columns.
+use crate::storage::sqlite3_ondisk::{read_varint, write_varint_to_vec};
+use crate::types::{Text, TextSubtype};
+use crate::{LimboError, Result, UpdateKind, Value};
+
+const TABLE_CHANGESET: u8 = b'T';
+const TABLE_PATCHSET: u8 = b'P';
+
+const OP_DELETE: u8 = 9;
+const OP_INSERT: u8 = 18;
+const OP_UPDATE: u8 = 23;
+
+const FIELD_UNDEFINED: u8 = 0;
+const FIELD_INTEGER: u8 = 1;
+const FIELD_FLOAT: u8 = 2;
+const FIELD_TEXT: u8 = 3;
+const FIELD_BLOB: u8 = 4;
+const FIELD_NULL: u8 = 5;
+
+/// A parsed changeset or patchset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Changeset {
+    pub patchset: bool,
+    pub tables: Vec<ChangesetTable>,
+}
+
+/// The changes to one table of a changeset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangesetTable {
+    pub name: String,
+    /// For each column, its 1-based position in the primary key, or 0 if it is not part of it.
+    pub primary_key: Vec<u8>,
+    pub changes: Vec<Change>,
+}
+
+/// A change to a single row. `old` and `new` have one entry per column, `None` where the
+/// value is undefined, and are empty when the change has no such record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub op: UpdateKind,
+    pub indirect: bool,
+    pub old: Vec<Option<Value>>,
+    pub new: Vec<Option<Value>>,
+}
+
+impl ChangesetTable {
+    pub fn is_primary_key(&self, column: usize) -> bool {
+        self.primary_key[column] != 0
+    }
+}
+
+impl Changeset {
+    /// Parses a changeset or a patchset.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        let mut tables: Vec<ChangesetTable> = Vec::new();
+        let mut patchset = None;
+        while !reader.is_empty() {
+            let marker = reader.byte()?;
+            match marker {
+                TABLE_CHANGESET | TABLE_PATCHSET => {
+                    let is_patchset = marker == TABLE_PATCHSET;
+                    if *patchset.get_or_insert(is_patchset) != is_patchset {
+                        return Err(corrupt("mixed changeset and patchset tables"));
+                    }
+                    tables.push(reader.table_header()?);
+                }
+                OP_INSERT | OP_UPDATE | OP_DELETE => {
+                    let Some(table) = tables.last_mut() else {
+                        return Err(corrupt("change before the first table header"));
+                    };
+                    let change = reader.change(marker, table, patchset.unwrap_or_default())?;
+                    table.changes.push(change);
+                }
+                _ => return Err(corrupt(&format!("unexpected byte 0x{:02x}", marker))),
+            }
+        }
+        Ok(Self {
+            patchset: patchset.unwrap_or_default(),
+            tables,
+        })
+    }
+
+    /// Serializes the changeset in the binary format of SQLite's session extension.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for table in &self.tables {
+            if table.changes.is_empty() {
+                continue;
+            }
+            out.push(if self.patchset {
+                TABLE_PATCHSET
+            } else {
+                TABLE_CHANGESET
+            });
+            write_varint_to_vec(table.primary_key.len() as u64, &mut out);
+            out.extend_from_slice(&table.primary_key);
+            out.extend_from_slice(table.name.as_bytes());
+            out.push(0);
+            for change in &table.changes {
+                out.push(match change.op {
+                    UpdateKind::Insert => OP_INSERT,
+                    UpdateKind::Update => OP_UPDATE,
+                    UpdateKind::Delete => OP_DELETE,
+                });
+                out.push(change.indirect as u8);
+                match (change.op, self.patchset) {
+                    (UpdateKind::Insert, _) => write_record(&mut out, &change.new),
+                    (UpdateKind::Delete, false) => write_record(&mut out, &change.old),
+                    (UpdateKind::Delete, true) => {
+                        for (column, value) in change.old.iter().enumerate() {
+                            if table.is_primary_key(column) {
+                                write_field(&mut out, value.as_ref());
+                            }
+                        }
+                    }
+                    (UpdateKind::Update, false) => {
+                        write_record(&mut out, &change.old);
+                        write_record(&mut out, &change.new);
+                    }
+                    (UpdateKind::Update, true) => {
+                        for column in 0..table.primary_key.len() {
+                            let value = if table.is_primary_key(column) {
+                                change.old[column].as_ref()
+                            } else {
+                                change.new[column].as_ref()
+                            };
+                            write_field(&mut out, value);
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn write_record(out: &mut Vec<u8>, record: &[Option<Value>]) {
+    for value in record {
+        write_field(out, value.as_ref());
+    }
+}
+
+fn write_field(out: &mut Vec<u8>, value: Option<&Value>) {
+    match value {
+        None => out.push(FIELD_UNDEFINED),
+        Some(Value::Null) => out.push(FIELD_NULL),
+        Some(Value::Integer(i)) => {
+            out.push(FIELD_INTEGER);
+            out.extend_from_slice(&i.to_be_bytes());
+        }
+        Some(Value::Float(f)) => {
+            out.push(FIELD_FLOAT);
+            out.extend_from_slice(&f.to_bits().to_be_bytes());
+        }
+        Some(Value::Text(text)) => {
+            out.push(FIELD_TEXT);
+            write_varint_to_vec(text.value.len() as u64, out);
+            out.extend_from_slice(&text.value);
+        }
+        Some(Value::Blob(blob)) => {
+            out.push(FIELD_BLOB);
+            write_varint_to_vec(blob.len() as u64, out);
+            out.extend_from_slice(blob);
+        }
+    }
+}
+
+/// Encodes `value` as a changeset field, which identifies the value by type and contents.
+pub(crate) fn encode_field(out: &mut Vec<u8>, value: &Value) {
+    write_field(out, Some(value));
+}
+
+pub(super) fn corrupt(message: &str) -> LimboError {
+    LimboError::Corrupt(format!("malformed changeset: {}", message))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| corrupt("unexpected end of data"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        // read_varint expects a full 9 bytes for the longest varints.
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let mut buf = [0u8; 9];
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        let (value, n) = read_varint(&buf)
+            .ok()
+            .filter(|(_, n)| *n <= len)
+            .ok_or_else(|| corrupt("unexpected end of data"))?;
+        self.pos += n;
+        Ok(value)
+    }
+
+    fn table_header(&mut self) -> Result<ChangesetTable> {
+        let num_columns = self.varint()? as usize;
+        if num_columns == 0 {
+            return Err(corrupt("table without columns"));
+        }
+        let primary_key = self.bytes(num_columns)?.to_vec();
+        let name_len = self.data[self.pos..]
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| corrupt("unterminated table name"))?;
+        let name = std::str::from_utf8(self.bytes(name_len)?)
+            .map_err(|_| corrupt("table name is not UTF-8"))?
+            .to_string();
+        self.byte()?;
+        Ok(ChangesetTable {
+            name,
+            primary_key,
+            changes: Vec::new(),
+        })
+    }
+
+    fn change(&mut self, op: u8, table: &ChangesetTable, patchset: bool) -> Result<Change> {
+        let num_columns = table.primary_key.len();
+        let indirect = self.byte()? != 0;
+        let (op, old, new) = match (op, patchset) {
+            (OP_INSERT, _) => (UpdateKind::Insert, vec![], self.record(num_columns)?),
+            (OP_DELETE, false) => (UpdateKind::Delete, self.record(num_columns)?, vec![]),
+            (OP_DELETE, true) => {
+                let mut old = vec![None; num_columns];
+                for (column, value) in old.iter_mut().enumerate() {
+                    if table.is_primary_key(column) {
+                        *value = self.field()?;
+                    }
+                }
+                (UpdateKind::Delete, old, vec![])
+            }
+            (OP_UPDATE, false) => {
+                let old = self.record(num_columns)?;
+                let new = self.record(num_columns)?;
+                (UpdateKind::Update, old, new)
+            }
+            (OP_UPDATE, true) => {
+                let mut old = vec![None; num_columns];
+                let mut new = vec![None; num_columns];
+                for column in 0..num_columns {
+                    let value = self.field()?;
+                    if table.is_primary_key(column) {
+                        old[column] = value;
+                    } else {
+                        new[column] = value;
+                    }
+                }
+                (UpdateKind::Update, old, new)
+            }
+            _ => unreachable!(),
+        };
+        Ok(Change {
+            op,
+            indirect,
+            old,
+            new,
+        })
+    }
+
+    fn record(&mut self, num_columns: usize) -> Result<Vec<Option<Value>>> {
+        (0..num_columns).map(|_| self.field()).collect()
+    }
+
+    fn field(&mut self) -> Result<Option<Value>> {
+        let value = match self.byte()? {
+            FIELD_UNDEFINED => return Ok(None),
+            FIELD_NULL => Value::Null,
+            FIELD_INTEGER => Value::Integer(i64::from_be_bytes(self.bytes(8)?.try_into().unwrap())),
+            FIELD_FLOAT => Value::Float(f64::from_bits(u64::from_be_bytes(
+                self.bytes(8)?.try_into().unwrap(),
+            ))),
+            FIELD_TEXT => {
+                let len = self.varint()? as usize;
+                Value::Text(Text {
+                    value: self.bytes(len)?.to_vec(),
+                    subtype: TextSubtype::Text,
+                })
+            }
+            FIELD_BLOB => {
+                let len = self.varint()? as usize;
+                Value::Blob(self.bytes(len)?.to_vec())
+            }
+            other => return Err(corrupt(&format!("unknown field type {}", other))),
+        };
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(patchset: bool, changes: Vec<Change>) -> Changeset {
+        Changeset {
+            patchset,
+            tables: vec![ChangesetTable {
+                name: "t".to_string(),
+                primary_key: vec![1, 0],
+                changes,
+            }],
+        }
+    }
+
+    fn changes() -> Vec<Change> {
+        vec![
+            Change {
+                op: UpdateKind::Insert,
+                indirect: false,
+                old: vec![],
+                new: vec![Some(Value::Integer(1)), Some(Value::build_text("a"))],
+            },
+            Change {
+                op: UpdateKind::Update,
+                indirect: false,
+                old: vec![Some(Value::Integer(2)), Some(Value::Float(1.5))],
+                new: vec![None, Some(Value::Null)],
+            },
+            Change {
+                op: UpdateKind::Delete,
+                indirect: true,
+                old: vec![Some(Value::Integer(3)), Some(Value::Blob(vec![0xab]))],
+                new: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_changeset_format() {
+        let changeset = table(false, changes());
+        let bytes = changeset.to_bytes();
+        #[rustfmt::skip]
+        let expected = [
+            b'T', 2, 1, 0, b't', 0,
+            OP_INSERT, 0,
+            1, 0, 0, 0, 0, 0, 0, 0, 1,
+            3, 1, b'a',
+            OP_UPDATE, 0,
+            1, 0, 0, 0, 0, 0, 0, 0, 2,
+            2, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0,
+            0,
+            5,
+            OP_DELETE, 1,
+            1, 0, 0, 0, 0, 0, 0, 0, 3,
+            4, 1, 0xab,
+        ];
+        assert_eq!(bytes, expected);
+        assert_eq!(Changeset::parse(&bytes).unwrap(), changeset);
+    }
+
+    #[test]
+    fn test_patchset_format() {
+        let changeset = table(true, changes());
+        let bytes = changeset.to_bytes();
+        #[rustfmt::skip]
+        let expected = [
+            b'P', 2, 1, 0, b't', 0,
+            OP_INSERT, 0,
+            1, 0, 0, 0, 0, 0, 0, 0, 1,
+            3, 1, b'a',
+            OP_UPDATE, 0,
+            1, 0, 0, 0, 0, 0, 0, 0, 2,
+            5,
+            OP_DELETE, 1,
+            1, 0, 0, 0, 0, 0, 0, 0, 3,
+        ];
+        assert_eq!(bytes, expected);
+
+        // The fields that a patchset leaves out are undefined when it's parsed.
+        let mut changes = changes();
+        changes[1].old[1] = None;
+        changes[2].old[1] = None;
+        assert_eq!(Changeset::parse(&bytes).unwrap(), table(true, changes));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let bytes = table(false, changes()).to_bytes();
+        for len in 1..bytes.len() {
+            if let Ok(changeset) = Changeset::parse(&bytes[..len]) {
+                // Truncating right after a change leaves a valid changeset.
+                assert!(changeset.tables[0].changes.len() < 3);
+            }
+        }
+        assert!(Changeset::parse(&[OP_INSERT, 0]).is_err());
+        assert!(Changeset::parse(b"X").is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! Change data capture compatible with SQLite's session extension.
+//!
+//! A [Session] records the row changes that its connection makes to the attached tables and
+//! turns them into a changeset or patchset (see [Changeset]), which
+//! [Connection::apply_changeset] replays on another database.
+//!
+//! Like in SQLite, only tables with a PRIMARY KEY are recorded, rows are identified by their
+//! primary key and rows with a NULL in the primary key are ignored. Changes to the same row are
+//! merged: inserting and then deleting a row leaves no change, deleting and inserting it again
+//! is an update. Changes of transactions that are rolled back are discarded.
+mod apply;
+mod changeset;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+
+pub(crate) use apply::apply_changeset;
+pub use apply::{ConflictAction, ConflictType};
+pub use changeset::{Change, Changeset, ChangesetTable};
+
+use crate::schema::{BTreeTable, Schema};
+use crate::util::normalize_ident;
+use crate::{Connection, UpdateKind, Value};
+
+/// Records the changes made through a connection, see the [module docs](self).
+pub struct Session {
+    conn: Arc<Connection>,
+    recorder: Rc<RefCell<Recorder>>,
+}
+
+impl Session {
+    pub(crate) fn new(conn: &Arc<Connection>) -> Self {
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+        conn.sessions.borrow_mut().add(&recorder);
+        Self {
+            conn: conn.clone(),
+            recorder,
+        }
+    }
+
+    /// Starts recording the changes to `table`, or to all tables if `None`.
+    pub fn attach(&self, table: Option<&str>) {
+        let mut recorder = self.recorder.borrow_mut();
+        match table {
+            Some(table) => {
+                let table = normalize_ident(table);
+                if !recorder.attached.contains(&table) {
+                    recorder.attached.push(table);
+                }
+            }
+            None => recorder.attach_all = true,
+        }
+    }
+
+    /// Pauses or resumes recording. Like `sqlite3session_enable`, the changes recorded so far
+    /// are kept, and changes made while the session is disabled are not recorded at all, even
+    /// to rows that were already changed.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.recorder.borrow_mut().enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.recorder.borrow().enabled
+    }
+
+    /// Returns true if the session has not recorded any change.
+    pub fn is_empty(&self) -> bool {
+        self.changes(false).tables.is_empty()
+    }
+
+    /// Returns the recorded changes in the changeset format.
+    pub fn changeset(&self) -> Vec<u8> {
+        self.changes(false).to_bytes()
+    }
+
+    /// Returns the recorded changes in the patchset format, which is smaller than a changeset
+    /// but only lets [Connection::apply_changeset] detect some conflicts.
+    pub fn patchset(&self) -> Vec<u8> {
+        self.changes(true).to_bytes()
+    }
+
+    /// Returns the recorded changes, one change per row whose values differ from the values it
+    /// had when it was first changed.
+    pub fn changes(&self, patchset: bool) -> Changeset {
+        let recorder = self.recorder.borrow();
+        let tables = recorder
+            .tables
+            .iter()
+            .map(|table| ChangesetTable {
+                name: table.name.clone(),
+                primary_key: table.primary_key.clone(),
+                changes: table
+                    .rows
+                    .iter()
+                    .filter_map(|row| row_change(&table.primary_key, row, patchset))
+                    .collect(),
+            })
+            .filter(|table| !table.changes.is_empty())
+            .collect();
+        Changeset { patchset, tables }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.conn.sessions.borrow_mut().remove(&self.recorder);
+    }
+}
+
+fn row_change(primary_key: &[u8], row: &RowChange, patchset: bool) -> Option<Change> {
+    let is_primary_key = |column: usize| primary_key[column] != 0;
+    let defined = |values: &[Value]| values.iter().cloned().map(Some).collect::<Vec<_>>();
+    let change = match (&row.old, &row.new) {
+        (None, None) => return None,
+        (None, Some(new)) => Change {
+            op: UpdateKind::Insert,
+            indirect: false,
+            old: vec![],
+            new: defined(new),
+        },
+        (Some(old), None) => Change {
+            op: UpdateKind::Delete,
+            indirect: false,
+            old: if patchset {
+                old.iter()
+                    .enumerate()
+                    .map(|(column, value)| is_primary_key(column).then(|| value.clone()))
+                    .collect()
+            } else {
+                defined(old)
+            },
+            new: vec![],
+        },
+        (Some(old), Some(new)) => {
+            let changed = |column: usize| !same_value(&old[column], &new[column]);
+            if !(0..old.len()).any(changed) {
+                return None;
+            }
+            Change {
+                op: UpdateKind::Update,
+                indirect: false,
+                old: (0..old.len())
+                    .map(|column| {
+                        let keep = is_primary_key(column) || (!patchset && changed(column));
+                        keep.then(|| old[column].clone())
+                    })
+                    .collect(),
+                new: (0..new.len())
+                    .map(|column| {
+                        let keep = !is_primary_key(column) && changed(column);
+                        keep.then(|| new[column].clone())
+                    })
+                    .collect(),
+            }
+        }
+    };
+    Some(change)
+}
+
+/// Compares values the way changesets do: by type and contents.
+pub(crate) fn same_value(a: &Value, b: &Value) -> bool {
+    let (mut a_bytes, mut b_bytes) = (Vec::new(), Vec::new());
+    changeset::encode_field(&mut a_bytes, a);
+    changeset::encode_field(&mut b_bytes, b);
+    a_bytes == b_bytes
+}
+
+/// The sessions of a connection, which the VDBE notifies of the row changes it makes.
+#[derive(Default)]
+pub(crate) struct Sessions {
+    recorders: Vec<Weak<RefCell<Recorder>>>,
+}
+
+impl Sessions {
+    fn add(&mut self, recorder: &Rc<RefCell<Recorder>>) {
+        self.recorders.push(Rc::downgrade(recorder));
+    }
+
+    fn remove(&mut self, recorder: &Rc<RefCell<Recorder>>) {
+        let recorder = Rc::downgrade(recorder);
+        self.recorders.retain(|r| !r.ptr_eq(&recorder));
+    }
+
+    fn recorders(&self) -> impl Iterator<Item = Rc<RefCell<Recorder>>> + '_ {
+        self.recorders.iter().filter_map(Weak::upgrade)
+    }
+
+    /// Returns true if a session records the changes to `table_name`.
+    pub(crate) fn is_recording(&self, table_name: &str) -> bool {
+        !self.recorders.is_empty()
+            && self
+                .recorders()
+                .any(|recorder| recorder.borrow().records(table_name))
+    }
+
+    /// Records a row being inserted into `table_name`, with the values of its record.
+    pub(crate) fn record_insert(
+        &self,
+        schema: &Schema,
+        table_name: &str,
+        rowid: i64,
+        record: Vec<Value>,
+    ) {
+        self.record(schema, table_name, rowid, record, false);
+    }
+
+    /// Records a row being deleted from `table_name`, with the values of its record.
+    pub(crate) fn record_delete(
+        &self,
+        schema: &Schema,
+        table_name: &str,
+        rowid: i64,
+        record: Vec<Value>,
+    ) {
+        self.record(schema, table_name, rowid, record, true);
+    }
+
+    fn record(
+        &self,
+        schema: &Schema,
+        table_name: &str,
+        rowid: i64,
+        record: Vec<Value>,
+        delete: bool,
+    ) {
+        let Some(table) = schema.get_btree_table(table_name) else {
+            return;
+        };
+        let Some(row) = row_values(&table, rowid, record) else {
+            return;
+        };
+        for recorder in self.recorders() {
+            let mut recorder = recorder.borrow_mut();
+            if recorder.records(table_name) {
+                recorder.record(&table, &row, delete);
+            }
+        }
+    }
+
+    /// Forgets how to undo the changes recorded in the transaction that just committed.
+    pub(crate) fn commit(&self) {
+        for recorder in self.recorders() {
+            recorder.borrow_mut().undo_log.clear();
+        }
+    }
+
+    /// Discards the changes recorded in the transaction that was just rolled back.
+    pub(crate) fn rollback(&self) {
+        for recorder in self.recorders() {
+            recorder.borrow_mut().rollback();
+        }
+    }
+}
+
+/// Returns the values of the columns of a row of `table`, or `None` if the row can't be
+/// recorded because the table has no primary key or the row has a NULL in it.
+fn row_values(table: &BTreeTable, rowid: i64, mut record: Vec<Value>) -> Option<Vec<Value>> {
+    if table.primary_key_columns.is_empty() {
+        return None;
+    }
+    // Columns added with ALTER TABLE are missing from the records written before.
+    record.resize(table.columns.len(), Value::Null);
+    for (column, value) in table.columns.iter().zip(record.iter_mut()) {
+        if column.is_rowid_alias {
+            *value = Value::Integer(rowid);
+        } else if column.primary_key && matches!(value, Value::Null) {
+            return None;
+        }
+    }
+    Some(record)
+}
+
+struct Recorder {
+    enabled: bool,
+    attach_all: bool,
+    attached: Vec<String>,
+    /// Tables in the order they were first changed.
+    tables: Vec<RecordedTable>,
+    /// How to undo the changes recorded in the current transaction, in the order they were
+    /// recorded.
+    undo_log: Vec<Undo>,
+}
+
+struct RecordedTable {
+    name: String,
+    primary_key: Vec<u8>,
+    /// Changed rows in the order they were first changed.
+    rows: Vec<RowChange>,
+    /// Position of the rows in `rows` by their encoded primary key.
+    row_positions: HashMap<Vec<u8>, usize>,
+}
+
+#[derive(Clone)]
+struct RowChange {
+    /// The row before it was first changed, `None` if it didn't exist.
+    old: Option<Vec<Value>>,
+    /// The row now, `None` if it has been deleted.
+    new: Option<Vec<Value>>,
+}
+
+enum Undo {
+    /// Remove the last row of the table, which was first changed in the transaction.
+    RemoveRow { table: usize, key: Vec<u8> },
+    /// Restore the new values the row had before the transaction changed it.
+    RestoreRow {
+        table: usize,
+        row: usize,
+        new: Option<Vec<Value>>,
+    },
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            attach_all: false,
+            attached: Vec::new(),
+            tables: Vec::new(),
+            undo_log: Vec::new(),
+        }
+    }
+}
+
+impl Recorder {
+    fn records(&self, table_name: &str) -> bool {
+        if table_name.is_empty() || table_name.starts_with("sqlite_") {
+            return false;
+        }
+        let is_attached = || {
+            let table_name = normalize_ident(table_name);
+            self.attached.contains(&table_name)
+        };
+        self.enabled && (self.attach_all || is_attached())
+    }
+
+    fn record(&mut self, table: &BTreeTable, row: &[Value], delete: bool) {
+        let table_idx = match self.tables.iter().position(|t| t.name == table.name) {
+            Some(idx) => idx,
+            None => {
+                let primary_key = table
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        column.name.as_ref().map_or(0, |name| {
+                            table
+                                .primary_key_columns
+                                .iter()
+                                .position(|(pk, _)| normalize_ident(pk) == normalize_ident(name))
+                                .map_or(0, |pos| pos as u8 + 1)
+                        })
+                    })
+                    .collect();
+                self.tables.push(RecordedTable {
+                    name: table.name.clone(),
+                    primary_key,
+                    rows: Vec::new(),
+                    row_positions: HashMap::new(),
+                });
+                self.tables.len() - 1
+            }
+        };
+        let recorded = &mut self.tables[table_idx];
+        if recorded.primary_key.len() != row.len() {
+            // The table was altered since its first change was recorded.
+            return;
+        }
+        let mut key = Vec::new();
+        for (column, value) in row.iter().enumerate() {
+            if recorded.primary_key[column] != 0 {
+                changeset::encode_field(&mut key, value);
+            }
+        }
+        let new = (!delete).then(|| row.to_vec());
+        match recorded.row_positions.get(&key) {
+            Some(&row_idx) => {
+                let previous = std::mem::replace(&mut recorded.rows[row_idx].new, new);
+                self.undo_log.push(Undo::RestoreRow {
+                    table: table_idx,
+                    row: row_idx,
+                    new: previous,
+                });
+            }
+            None => {
+                let old = delete.then(|| row.to_vec());
+                recorded
+                    .row_positions
+                    .insert(key.clone(), recorded.rows.len());
+                recorded.rows.push(RowChange { old, new });
+                self.undo_log.push(Undo::RemoveRow {
+                    table: table_idx,
+                    key,
+                });
+            }
+        }
+    }
+
+    fn rollback(&mut self) {
+        while let Some(undo) = self.undo_log.pop() {
+            match undo {
+                Undo::RemoveRow { table, key } => {
+                    let table = &mut self.tables[table];
+                    table.rows.pop();
+                    table.row_positions.remove(&key);
+                }
+                Undo::RestoreRow { table, row, new } => {
+                    self.tables[table].rows[row].new = new;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! LRU cache of compiled programs keyed by SQL text, used by [crate::Connection::prepare].
+//!
+//! A program is compiled against the schema it was prepared with, so every entry remembers
+//! the schema version at compile time and is dropped once the schema cookie has moved on.
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::vdbe::Program;
+
+pub const DEFAULT_STATEMENT_CACHE_CAPACITY: usize = 16;
+
+/// Counters of the statement cache of a connection, accumulated since the connection was
+/// opened, see [crate::Connection::statement_cache_stats].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StatementCacheStats {
+    /// Prepares that reused a cached program.
+    pub hits: u64,
+    /// Prepares that compiled their SQL, including those whose cached program was compiled
+    /// against an older schema. Only statements that can be cached count.
+    pub misses: u64,
+    /// Programs removed from the cache to make room for other programs.
+    pub evictions: u64,
+    /// Programs currently in the cache.
+    pub size: usize,
+    /// Maximum number of programs in the cache.
+    pub capacity: usize,
+}
+
+impl StatementCacheStats {
+    /// Fraction of prepares that reused a cached program.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+
+struct CachedProgram {
+    program: Rc<Program>,
+    schema_version: u32,
+    /// Value of [StatementCache::clock] when the program was last used.
+    last_used: u64,
+}
+
+pub(crate) struct StatementCache {
+    capacity: usize,
+    entries: HashMap<String, CachedProgram>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Returns the program compiled for `sql`, unless it was compiled against another schema
+    /// than `schema_version`.
+    pub fn get(&mut self, sql: &str, schema_version: u32) -> Option<Rc<Program>> {
+        self.clock += 1;
+        match self.entries.get_mut(sql) {
+            Some(entry) if entry.schema_version == schema_version => {
+                entry.last_used = self.clock;
+                self.hits += 1;
+                Some(entry.program.clone())
+            }
+            Some(_) => {
+                self.entries.remove(sql);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Caches `program`, compiled after [StatementCache::get] found nothing for `sql`, evicting
+    /// the least recently used program if the cache is full. Whether a statement can be cached
+    /// is only known once it is parsed, so the miss is counted here.
+    pub fn insert(&mut self, sql: &str, program: Rc<Program>, schema_version: u32) {
+        self.misses += 1;
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.entries.contains_key(sql) {
+            self.evict(self.capacity - 1);
+        }
+        self.clock += 1;
+        self.entries.insert(
+            sql.to_string(),
+            CachedProgram {
+                program,
+                schema_version,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict(capacity);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn stats(&self) -> StatementCacheStats {
+        StatementCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+            size: self.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+
+    /// Evicts the least recently used programs until at most `len` are left.
+    fn evict(&mut self, len: usize) {
+        while self.entries.len() > len {
+            let lru = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(sql, _)| sql.clone())
+                .expect("cache is not empty");
+            self.entries.remove(&lru);
+            self.evictions += 1;
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Online backup of a database into another database.
+//!
+//! A [Backup] copies the pages of a source database into a destination database while the
+//! source stays available to other connections. Pages are copied incrementally with
+//! [Backup::step]: every step reads from a consistent snapshot of the source and, if the source
+//! was modified since the previous step, the copy restarts from the first page so that the
+//! destination always ends up with a single consistent snapshot of the source.
+//!
+//! The destination is write-locked from the first step until the backup completes or the
+//! [Backup] is dropped, so the destination connection must not be used in the meantime. The
+//! copied pages are committed at once when the last page is copied: until then readers of the
+//! destination keep seeing its old contents, and a backup that fails or is dropped leaves the
+//! destination unchanged.
+use std::sync::Arc;
+
+use tracing::trace;
+
+use crate::result::LimboResult;
+use crate::schema::Schema;
+use crate::storage::pager::{PageRef, Pager, PagerCacheflushStatus};
+use crate::storage::sqlite3_ondisk::{self, DatabaseHeader, DATABASE_HEADER_PAGE_ID};
+use crate::util::parse_schema_rows;
+use crate::{Connection, LimboError, Result, TransactionState};
+
+/// Number of pages copied before the destination page cache is spilled to its WAL.
+const FLUSH_BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupStepResult {
+    /// Some pages remain to be copied.
+    More,
+    /// All pages of the source have been copied to the destination.
+    Done,
+    /// The source or the destination is locked by another connection. The step can be retried.
+    Busy,
+}
+
+pub struct Backup {
+    /// Private connection to the source database, so that the copy never observes
+    /// uncommitted changes of the connection the backup was started from.
+    source: Arc<Connection>,
+    dest: Arc<Connection>,
+    /// Schema cookie of the destination before the backup.
+    dest_schema_cookie: u32,
+    /// Next source page to copy (1-indexed).
+    next_page: usize,
+    /// Number of pages in the source snapshot seen by the last step.
+    page_count: usize,
+    /// WAL read mark of the source snapshot the copy is based on.
+    snapshot: Option<u64>,
+    /// Whether a write transaction is open on the destination.
+    dest_locked: bool,
+    done: bool,
+}
+
+impl Backup {
+    /// Prepares a backup of the database behind `source` into the database behind `dest`.
+    pub fn new(source: &Arc<Connection>, dest: &Arc<Connection>) -> Result<Self> {
+        if Arc::ptr_eq(&source._db, &dest._db) {
+            return Err(LimboError::InvalidArgument(
+                "source and destination must be distinct databases".to_string(),
+            ));
+        }
+        let source_page_size = source._db.page_size;
+        let dest_page_size = dest._db.page_size;
+        if source_page_size != dest_page_size {
+            return Err(LimboError::InvalidArgument(format!(
+                "backup between databases with different page sizes is not supported ({} and {})",
+                source_page_size, dest_page_size
+            )));
+        }
+        // Encryption keeps its nonce and tag in the bytes reserved at the end of every page.
+        let source_reserved_space = source.header.lock().reserved_space;
+        let dest_reserved_space = dest.header.lock().reserved_space;
+        if source_reserved_space != dest_reserved_space {
+            return Err(LimboError::InvalidArgument(format!(
+                "backup between databases with different reserved bytes per page is not supported ({} and {})",
+                source_reserved_space, dest_reserved_space
+            )));
+        }
+        Ok(Self {
+            source: source._db.connect()?,
+            dest: dest.clone(),
+            dest_schema_cookie: dest.header.lock().schema_cookie,
+            next_page: 1,
+            page_count: 0,
+            snapshot: None,
+            dest_locked: false,
+            done: false,
+        })
+    }
+
+    /// Copies up to `max_pages` pages from the source to the destination, or all remaining
+    /// pages if `max_pages` is `None`.
+    pub fn step(&mut self, max_pages: Option<usize>) -> Result<BackupStepResult> {
+        if self.done {
+            return Ok(BackupStepResult::Done);
+        }
+        if !self.dest_locked {
+            if !self.dest.auto_commit.get()
+                || self.dest.transaction_state.get() != TransactionState::None
+            {
+                return Err(LimboError::TxError(
+                    "destination database is in use".to_string(),
+                ));
+            }
+            let pager = &self.dest.pager;
+            if let LimboResult::Busy = pager.begin_read_tx()? {
+                return Ok(BackupStepResult::Busy);
+            }
+            if let LimboResult::Busy = pager.begin_write_tx()? {
+                pager.end_read_tx()?;
+                return Ok(BackupStepResult::Busy);
+            }
+            pager.clear_page_cache();
+            self.dest_locked = true;
+        }
+
+        let source = self.source.pager.clone();
+        if let LimboResult::Busy = source.begin_read_tx()? {
+            return Ok(BackupStepResult::Busy);
+        }
+        let result = self.copy_pages(&source, max_pages);
+        source.end_read_tx()?;
+        source.clear_page_cache();
+
+        match result {
+            Ok(BackupStepResult::Done) => {
+                self.finish_dest()?;
+                self.done = true;
+                Ok(BackupStepResult::Done)
+            }
+            Ok(result) => Ok(result),
+            Err(e) => {
+                self.release_dest();
+                Err(e)
+            }
+        }
+    }
+
+    /// Number of pages still to be copied, as of the last step.
+    pub fn remaining(&self) -> usize {
+        self.page_count.saturating_sub(self.next_page - 1)
+    }
+
+    /// Total number of pages in the source, as of the last step.
+    pub fn pagecount(&self) -> usize {
+        self.page_count
+    }
+
+    fn copy_pages(&mut self, source: &Pager, max_pages: Option<usize>) -> Result<BackupStepResult> {
+        let snapshot = source.wal_read_mark();
+        if self.snapshot != Some(snapshot) {
+            if self.snapshot.is_some() {
+                trace!("backup: source changed, restarting");
+            }
+            self.snapshot = Some(snapshot);
+            self.next_page = 1;
+        }
+
+        let header_page = read_page_sync(source, DATABASE_HEADER_PAGE_ID)?;
+        let mut header = DatabaseHeader::default();
+        sqlite3_ondisk::read_header_from_buf(header_page.get_contents().as_ptr(), &mut header);
+        self.page_count = header.database_size.max(1) as usize;
+
+        let dest = &self.dest.pager;
+        let last_page = match max_pages {
+            Some(n) => (self.next_page + n).saturating_sub(1).min(self.page_count),
+            None => self.page_count,
+        };
+        // Frames written to the destination WAL carry the size of the source snapshot.
+        self.dest.header.lock().database_size = self.page_count as u32;
+        let mut batch = 0;
+        while self.next_page <= last_page {
+            let page_idx = self.next_page;
+            let page = read_page_sync(source, page_idx)?;
+            dest.write_raw_page(page_idx, page.get_contents().as_ptr())?;
+            if page_idx == DATABASE_HEADER_PAGE_ID {
+                // Like SQLite, move the schema cookie of the destination on, so that statements
+                // compiled against its old schema are recompiled.
+                let mut header = header.clone();
+                header.schema_cookie = self.dest_schema_cookie.wrapping_add(1);
+                dest.write_database_header(&header)?;
+                *self.dest.header.lock() = header;
+            }
+            self.next_page += 1;
+            batch += 1;
+            if batch == FLUSH_BATCH_SIZE {
+                spill_sync(dest)?;
+                source.clear_page_cache();
+                batch = 0;
+            }
+        }
+        spill_sync(dest)?;
+
+        if self.next_page > self.page_count {
+            Ok(BackupStepResult::Done)
+        } else {
+            Ok(BackupStepResult::More)
+        }
+    }
+
+    /// Commits the copied pages and reloads the destination schema.
+    fn finish_dest(&mut self) -> Result<()> {
+        let dest = self.dest.pager.clone();
+        // The copied pages were all spilled, so write the header page again to have a commit
+        // frame that carries the database size.
+        let header = self.dest.header.lock().clone();
+        dest.write_database_header(&header)?;
+        while let PagerCacheflushStatus::IO = dest.end_tx()? {
+            dest.io.run_once()?;
+        }
+        self.dest_locked = false;
+
+        let stmt = self.dest.prepare("SELECT * FROM sqlite_schema")?;
+        let mut schema = Schema::new();
+        parse_schema_rows(
+            Some(stmt),
+            &mut schema,
+            dest.io.clone(),
+            &self.dest.syms.borrow(),
+            None,
+        )?;
+        schema.schema_version = self.dest.header.lock().schema_cookie;
+        *self.dest.schema.write() = schema;
+        Ok(())
+    }
+
+    /// Discards the copied pages, restores the destination header and releases the
+    /// destination locks.
+    fn release_dest(&mut self) {
+        if !self.dest_locked {
+            return;
+        }
+        if let Err(e) = self.dest.pager.rollback() {
+            trace!("backup: failed to roll back the destination: {}", e);
+        }
+        self.dest_locked = false;
+    }
+}
+
+impl Drop for Backup {
+    fn drop(&mut self) {
+        self.release_dest();
+    }
+}
+
+pub(crate) fn read_page_sync(pager: &Pager, page_idx: usize) -> Result<PageRef> {
+    let page = pager.read_page(page_idx)?;
+    while page.is_locked() {
+        pager.io.run_once()?;
+    }
+    if page.is_error() {
+        return Err(LimboError::InternalError(format!(
+            "failed to read page {}",
+            page_idx
+        )));
+    }
+    Ok(page)
+}
+
+fn spill_sync(pager: &Pager) -> Result<()> {
+    loop {
+        match pager.spill()? {
+            PagerCacheflushStatus::IO => pager.io.run_once()?,
+            PagerCacheflushStatus::Done(_) => return Ok(()),
+        }
+    }
+}
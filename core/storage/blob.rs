@@ -0,0 +1,310 @@
+//! Incremental I/O on BLOB and TEXT values.
+//!
+//! A [Blob] gives access to a single value of a table row without materializing it: byte ranges
+//! are read from and written to the b-tree cell of the row, following its overflow chain as
+//! needed. The size of the value is fixed when the handle is opened, so writes can only
+//! overwrite existing bytes.
+//!
+//! Every read or write runs in the transaction of the connection. In autocommit mode, each
+//! operation runs in its own transaction, so many small writes are best wrapped in an explicit
+//! transaction.
+use std::sync::Arc;
+
+use crate::result::LimboResult;
+use crate::storage::btree::BTreeCursor;
+use crate::storage::pager::{Pager, PagerCacheflushStatus};
+use crate::storage::sqlite3_ondisk::read_varint;
+use crate::translate::index::index_column_dependencies;
+use crate::types::{CursorResult, SeekKey, SeekOp, SerialType, SerialTypeKind};
+use crate::{Connection, LimboError, Result, TransactionState};
+
+pub struct Blob {
+    conn: Arc<Connection>,
+    root_page: usize,
+    /// Index of the column in the table records.
+    column: usize,
+    rowid: i64,
+    /// Size of the value when the handle was opened.
+    size: u32,
+    writable: bool,
+}
+
+impl Blob {
+    /// Opens the value of `column` in the row `rowid` of `table`.
+    pub(crate) fn open(
+        conn: &Arc<Connection>,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        writable: bool,
+    ) -> Result<Self> {
+        if conn._db.mv_store.is_some() {
+            return Err(LimboError::InvalidArgument(
+                "blob I/O is not supported with MVCC".to_string(),
+            ));
+        }
+        if writable && conn._db.is_read_only() {
+            return Err(LimboError::ReadOnly);
+        }
+        let (root_page, column) = {
+            let schema = conn.schema.read();
+            let Some(btree) = schema.get_btree_table(table) else {
+                return Err(LimboError::ParseError(format!("no such table: {}", table)));
+            };
+            if !btree.has_rowid {
+                return Err(LimboError::InvalidArgument(format!(
+                    "cannot open table without rowid: {}",
+                    table
+                )));
+            }
+            let Some((column_idx, _)) = btree.get_column(column) else {
+                return Err(LimboError::ParseError(format!(
+                    "no such column: \"{}\"",
+                    column
+                )));
+            };
+            if writable {
+                // A column is also indexed when an index on expressions refers to it.
+                for index in schema.get_indices(table) {
+                    for index_column in index.columns.iter() {
+                        if index_column_dependencies(&btree, index_column)?.contains(&column_idx) {
+                            return Err(LimboError::InvalidArgument(
+                                "cannot open indexed column for writing".to_string(),
+                            ));
+                        }
+                    }
+                }
+            }
+            (btree.root_page, column_idx)
+        };
+        let mut blob = Self {
+            conn: conn.clone(),
+            root_page,
+            column,
+            rowid,
+            size: 0,
+            writable,
+        };
+        blob.reopen(rowid)?;
+        Ok(blob)
+    }
+
+    /// Size of the value in bytes.
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Rowid of the row the handle points to.
+    pub fn rowid(&self) -> i64 {
+        self.rowid
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Moves the handle to the same column of another row of the table.
+    pub fn reopen(&mut self, rowid: i64) -> Result<()> {
+        let pager = self.conn.pager.clone();
+        let column = self.column;
+        let (_, size) = self
+            .run_in_tx(false, rowid, |cursor| locate_value(&pager, cursor, column))
+            .map_err(|e| match e {
+                LimboError::BlobExpired => {
+                    LimboError::InvalidArgument(format!("no such rowid: {}", rowid))
+                }
+                e => e,
+            })?;
+        self.rowid = rowid;
+        self.size = size;
+        Ok(())
+    }
+
+    /// Fills `buf` with the bytes of the value starting at `offset`.
+    pub fn read_at(&self, buf: &mut [u8], offset: usize) -> Result<()> {
+        self.check_range(offset, buf.len())?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let mut data = Vec::with_capacity(buf.len());
+        self.run_in_tx(false, self.rowid, |cursor| {
+            let start = self.value_offset(cursor)? + offset as u32;
+            run_sync(&self.conn.pager, || {
+                cursor.read_write_payload_with_offset(start, &mut data, buf.len() as u32, false)
+            })
+        })?;
+        buf.copy_from_slice(&data);
+        Ok(())
+    }
+
+    /// Overwrites the bytes of the value starting at `offset` with `data`.
+    pub fn write_at(&self, data: &[u8], offset: usize) -> Result<()> {
+        if !self.writable {
+            return Err(LimboError::ReadOnly);
+        }
+        self.check_range(offset, data.len())?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut data = data.to_vec();
+        self.run_in_tx(true, self.rowid, |cursor| {
+            let start = self.value_offset(cursor)? + offset as u32;
+            let amount = data.len() as u32;
+            run_sync(&self.conn.pager, || {
+                cursor.read_write_payload_with_offset(start, &mut data, amount, true)
+            })
+        })
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> Result<()> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.size() => Ok(()),
+            _ => Err(LimboError::InvalidArgument(format!(
+                "range {}..{} is out of bounds for a blob of {} bytes",
+                offset,
+                offset.saturating_add(len),
+                self.size
+            ))),
+        }
+    }
+
+    /// Locates the value again, as the row may have moved since the handle was opened.
+    fn value_offset(&self, cursor: &mut BTreeCursor) -> Result<u32> {
+        let (offset, size) = locate_value(&self.conn.pager, cursor, self.column)?;
+        if size != self.size {
+            return Err(LimboError::BlobExpired);
+        }
+        Ok(offset)
+    }
+
+    /// Positions a cursor on `rowid` and runs `f` in the transaction of the connection,
+    /// starting one if needed. A transaction started here is ended before returning unless the
+    /// connection is in an explicit transaction. Fails with [LimboError::BlobExpired] if the row
+    /// does not exist.
+    fn run_in_tx<T>(
+        &self,
+        write: bool,
+        rowid: i64,
+        f: impl FnOnce(&mut BTreeCursor) -> Result<T>,
+    ) -> Result<T> {
+        let conn = &self.conn;
+        let pager = conn.pager.clone();
+        let current_state = conn.transaction_state.get();
+        let new_state = match (current_state, write) {
+            (TransactionState::None, false) => TransactionState::Read,
+            (TransactionState::Read, false) => TransactionState::Read,
+            (_, true) | (TransactionState::Write, false) => TransactionState::Write,
+        };
+        if current_state == TransactionState::None {
+            if let LimboResult::Busy = pager.begin_read_tx()? {
+                return Err(LimboError::Busy);
+            }
+        }
+        if new_state == TransactionState::Write && current_state != TransactionState::Write {
+            if let LimboResult::Busy = pager.begin_write_tx()? {
+                if current_state == TransactionState::None {
+                    pager.end_read_tx()?;
+                }
+                return Err(LimboError::Busy);
+            }
+        }
+        conn.transaction_state.replace(new_state);
+
+        let mut cursor = BTreeCursor::new_table(None, pager.clone(), self.root_page);
+        let result = run_sync(&pager, || {
+            cursor.seek(SeekKey::TableRowId(rowid), SeekOp::GE { eq_only: true })
+        })
+        .and_then(|found| {
+            if found {
+                f(&mut cursor)
+            } else {
+                Err(LimboError::BlobExpired)
+            }
+        });
+
+        if current_state == TransactionState::None && conn.auto_commit.get() {
+            if result.is_err() {
+                // Changes of a failed operation must not be committed.
+                pager.clear_page_cache();
+            }
+            match new_state {
+                TransactionState::Write => {
+                    while let PagerCacheflushStatus::IO = pager.end_tx()? {
+                        pager.io.run_once()?;
+                    }
+                }
+                _ => pager.end_read_tx()?,
+            }
+            conn.transaction_state.replace(TransactionState::None);
+        }
+        result
+    }
+}
+
+/// Finds the offset and the size of the value of `column` in the record the cursor points to.
+fn locate_value(pager: &Pager, cursor: &mut BTreeCursor, column: usize) -> Result<(u32, u32)> {
+    let payload_size = run_sync(pager, || cursor.payload_size())?;
+    if payload_size > u32::MAX as u64 {
+        return Err(LimboError::Corrupt(format!(
+            "payload too large: {}",
+            payload_size
+        )));
+    }
+    let payload_size = payload_size as u32;
+
+    let mut header = Vec::new();
+    run_sync(pager, || {
+        cursor.read_write_payload_with_offset(0, &mut header, payload_size.min(9), false)
+    })?;
+    let (header_size, mut pos) = read_varint(&header)?;
+    if header_size > payload_size as u64 || (header_size as usize) < pos {
+        return Err(LimboError::Corrupt(format!(
+            "invalid record header size: {}",
+            header_size
+        )));
+    }
+    let header_size = header_size as usize;
+    header.clear();
+    run_sync(pager, || {
+        cursor.read_write_payload_with_offset(0, &mut header, header_size as u32, false)
+    })?;
+
+    let mut offset = header_size as u64;
+    let mut idx = 0;
+    while pos < header_size {
+        let (serial_type, n) = read_varint(&header[pos..header_size])?;
+        pos += n;
+        let serial_type = SerialType::try_from(serial_type)?;
+        if idx == column {
+            return match serial_type.kind() {
+                SerialTypeKind::Blob | SerialTypeKind::Text => {
+                    let size = serial_type.size() as u64;
+                    if offset + size > payload_size as u64 {
+                        return Err(LimboError::Corrupt("record value out of bounds".into()));
+                    }
+                    Ok((offset as u32, size as u32))
+                }
+                SerialTypeKind::Null => Err(cannot_open("null")),
+                SerialTypeKind::F64 => Err(cannot_open("real")),
+                _ => Err(cannot_open("integer")),
+            };
+        }
+        offset += serial_type.size() as u64;
+        idx += 1;
+    }
+    // Columns added after the row was written are not stored in the record.
+    Err(cannot_open("null"))
+}
+
+fn cannot_open(ty: &str) -> LimboError {
+    LimboError::InvalidArgument(format!("cannot open value of type {}", ty))
+}
+
+fn run_sync<T>(pager: &Pager, mut f: impl FnMut() -> Result<CursorResult<T>>) -> Result<T> {
+    loop {
+        match f()? {
+            CursorResult::Ok(value) => return Ok(value),
+            CursorResult::IO => pager.io.run_once()?,
+        }
+    }
+}
@@ -107,6 +107,19 @@ macro_rules! return_if_locked {
         if $expr.is_locked() {
             return Ok(CursorResult::IO);
         }
+        return_if_page_error!($expr);
+    }};
+}
+
+/// Fail if the page could not be read, e.g. because it did not decrypt.
+macro_rules! return_if_page_error {
+    ($expr:expr) => {{
+        if $expr.is_error() {
+            return Err(LimboError::Corrupt(format!(
+                "failed to read page {}",
+                $expr.get().id
+            )));
+        }
     }};
 }
 
@@ -125,6 +138,7 @@ macro_rules! return_if_locked_maybe_load {
         if $btree_page.get().is_locked() {
             return Ok(CursorResult::IO);
         }
+        return_if_page_error!($btree_page.get());
         if !$btree_page.get().is_loaded() {
             let page = $pager.read_page($btree_page.get().get().id)?;
             $btree_page.page.replace(page);
@@ -155,6 +169,8 @@ enum DestroyState {
 
 struct DestroyInfo {
     state: DestroyState,
+    /// Whether the root page is kept as an empty leaf page instead of being freed, see [BTreeCursor::btree_clear].
+    keep_root: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -823,6 +839,30 @@ impl BTreeCursor {
         Ok((n_local, payload_len))
     }
 
+    /// Returns the size of the payload of the cell the cursor is pointing to, including the part
+    /// of the payload stored in overflow pages.
+    pub fn payload_size(&self) -> Result<CursorResult<u64>> {
+        let page = self.stack.top();
+        return_if_locked_maybe_load!(self.pager, page);
+        let page = page.get();
+        let contents = page.get_contents();
+        let usable_size = self.usable_space();
+        let cell = contents.cell_get(
+            self.stack.current_cell_index() as usize,
+            payload_overflow_threshold_max(contents.page_type(), usable_size as u16),
+            payload_overflow_threshold_min(contents.page_type(), usable_size as u16),
+            usable_size,
+        )?;
+        match cell {
+            BTreeCell::TableLeafCell(cell) => Ok(CursorResult::Ok(cell.payload_size)),
+            BTreeCell::IndexLeafCell(cell) => Ok(CursorResult::Ok(cell.payload_size)),
+            BTreeCell::IndexInteriorCell(cell) => Ok(CursorResult::Ok(cell.payload_size)),
+            BTreeCell::TableInteriorCell(_) => Err(LimboError::Corrupt(
+                "Cannot access payload of table interior cell".into(),
+            )),
+        }
+    }
+
     /// This function is used to read/write into the payload of a cell that
     /// cursor is pointing to.
     /// Parameters:
@@ -854,7 +894,7 @@ impl BTreeCursor {
 
         let page = page_btree.get();
         let contents = page.get().contents.as_ref().unwrap();
-        let cell_idx = self.stack.current_cell_index() as usize - 1;
+        let cell_idx = self.stack.current_cell_index() as usize;
 
         if cell_idx >= contents.cell_count() {
             return Err(LimboError::Corrupt("Invalid cell index".into()));
@@ -901,7 +941,7 @@ impl BTreeCursor {
                     offset,
                     local_amount,
                     payload,
-                    buffer,
+                    &buffer[..local_amount as usize],
                     page_btree.clone(),
                 );
             } else {
@@ -958,8 +998,8 @@ impl BTreeCursor {
                     is_write,
                 }) => {
                     if *pages_left_to_skip == 0 {
+                        // The page is loaded, if needed, when it is processed.
                         let page = self.read_page(*next_page as usize)?;
-                        return_if_locked_maybe_load!(self.pager, page);
                         self.state =
                             CursorState::ReadWritePayload(PayloadOverflowWithOffset::ProcessPage {
                                 next_page: *next_page,
@@ -974,7 +1014,10 @@ impl BTreeCursor {
                     }
 
                     let page = self.read_page(*next_page as usize)?;
-                    return_if_locked_maybe_load!(self.pager, page);
+                    if page.get().is_locked() {
+                        self.state = state;
+                        return Ok(CursorResult::IO);
+                    }
                     let page = page.get();
                     let contents = page.get_contents();
                     let next = contents.read_u32_no_offset(0);
@@ -1040,7 +1083,7 @@ impl BTreeCursor {
                             payload_offset as u32,
                             bytes_to_process,
                             page_payload,
-                            buffer,
+                            &buffer[*buffer_offset..*buffer_offset + bytes_to_process as usize],
                             page_btree.clone(),
                         );
                     } else {
@@ -1069,6 +1112,7 @@ impl BTreeCursor {
                     *next_page = next;
                     *current_offset = 0; // Reset offset for new page
                     *page_btree = self.read_page(next as usize)?;
+                    self.state = state;
 
                     // Return IO to allow other operations
                     return Ok(CursorResult::IO);
@@ -1102,7 +1146,7 @@ impl BTreeCursor {
         payload_offset: u32,
         num_bytes: u32,
         payload: &[u8],
-        buffer: &mut Vec<u8>,
+        buffer: &[u8],
         page: BTreePage,
     ) {
         page.get().set_dirty();
@@ -4688,6 +4732,7 @@ impl BTreeCursor {
             self.move_to_root();
             self.state = CursorState::Destroy(DestroyInfo {
                 state: DestroyState::Start,
+                keep_root: false,
             });
         }
 
@@ -4849,6 +4894,27 @@ impl BTreeCursor {
                     let page = self.stack.top();
                     let page_id = page.get().get().id;
 
+                    let keep_root = self
+                        .state
+                        .destroy_info()
+                        .expect("unable to get a reference to destroy state in cursor")
+                        .keep_root;
+                    if keep_root && !self.stack.has_parent() {
+                        let (page_type, offset) = {
+                            let page = page.get();
+                            let contents = page.get().contents.as_ref().unwrap();
+                            (contents.page_type(), contents.offset)
+                        };
+                        let leaf_type = match page_type {
+                            PageType::TableInterior | PageType::TableLeaf => PageType::TableLeaf,
+                            PageType::IndexInterior | PageType::IndexLeaf => PageType::IndexLeaf,
+                        };
+                        self.pager.add_dirty(page_id);
+                        btree_init_page(&page, leaf_type, offset, self.usable_space() as u16);
+                        self.state = CursorState::None;
+                        return Ok(CursorResult::Ok(None));
+                    }
+
                     self.pager.free_page(Some(page.get()), page_id)?;
 
                     if self.stack.has_parent() {
@@ -4869,6 +4935,23 @@ impl BTreeCursor {
         }
     }
 
+    /// Deletes all the contents of a B-tree but keeps the B-tree itself: all of its pages are freed
+    /// like in [BTreeCursor::btree_destroy], except for the root page, which becomes an empty leaf page.
+    #[instrument(skip(self), level = Level::TRACE)]
+    pub fn btree_clear(&mut self) -> Result<CursorResult<()>> {
+        if let CursorState::None = &self.state {
+            self.move_to_root();
+            self.state = CursorState::Destroy(DestroyInfo {
+                state: DestroyState::Start,
+                keep_root: true,
+            });
+        }
+        Ok(match self.btree_destroy()? {
+            CursorResult::Ok(_) => CursorResult::Ok(()),
+            CursorResult::IO => CursorResult::IO,
+        })
+    }
+
     pub fn table_id(&self) -> usize {
         self.root_page
     }
@@ -4981,15 +5064,19 @@ impl BTreeCursor {
             mem_page = mem_page_rc.get();
             contents = mem_page.get().contents.as_ref().unwrap();
 
-            /* If this is a leaf page or the tree is not an int-key tree, then
-             ** this page contains countable entries. Increment the entry counter
-             ** accordingly.
-             */
-            if !matches!(contents.page_type(), PageType::TableInterior) {
-                self.count += contents.cell_count();
-            }
+            // Pages are counted when first visited. After IO, the loop can resume on a parent
+            // page whose children are partly visited, which must not be counted nor advanced.
+            if self.stack.current_cell_index() < 0 {
+                /* If this is a leaf page or the tree is not an int-key tree, then
+                 ** this page contains countable entries. Increment the entry counter
+                 ** accordingly.
+                 */
+                if !matches!(contents.page_type(), PageType::TableInterior) {
+                    self.count += contents.cell_count();
+                }
 
-            self.stack.advance();
+                self.stack.advance();
+            }
             let cell_idx = self.stack.current_cell_index() as usize;
 
             // Second condition is necessary in case we return if the page is locked in the loop below
@@ -7636,6 +7723,48 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_btree_clear() -> Result<()> {
+        let (pager, db_header) = setup_test_env(3);
+        let mut cursor = BTreeCursor::new_table(None, pager.clone(), 2);
+        let root_page = cursor.read_page(2)?;
+        btree_init_page(&root_page, PageType::TableLeaf, 0, 512);
+
+        // Insert enough rows for the root page to split
+        let value = ImmutableRecord::from_registers(&[Register::Value(Value::Blob(vec![0; 100]))]);
+        for rowid in 0..20 {
+            run_until_done(
+                || cursor.seek(SeekKey::TableRowId(rowid), SeekOp::GE { eq_only: true }),
+                pager.deref(),
+            )?;
+            run_until_done(
+                || cursor.insert(&BTreeKey::new_table_rowid(rowid, Some(&value)), true),
+                pager.deref(),
+            )?;
+        }
+        // The root page is now an interior page with 5 leaves
+        {
+            let root_page = cursor.read_page(2)?;
+            let root_page = root_page.get();
+            let contents = root_page.get().contents.as_ref().unwrap();
+            assert_eq!(contents.page_type(), PageType::TableInterior);
+            assert_eq!(contents.cell_count(), 4);
+        }
+        assert_eq!(db_header.lock().freelist_pages, 0);
+
+        run_until_done(|| cursor.btree_clear(), pager.deref())?;
+
+        let pages_freed = db_header.lock().freelist_pages;
+        assert_eq!(pages_freed, 5, "should free the 5 leaves but not the root");
+        let root_page = cursor.read_page(2)?;
+        let root_page = root_page.get();
+        let contents = root_page.get().contents.as_ref().unwrap();
+        assert_eq!(contents.page_type(), PageType::TableLeaf);
+        assert_eq!(contents.cell_count(), 0);
+
+        Ok(())
+    }
+
     #[test]
     pub fn test_defragment() {
         let db = get_database();
@@ -8451,9 +8580,14 @@ mod tests {
         )
         .unwrap();
 
-        cursor
-            .stack
-            .set_cell_index(cursor.stack.current_cell_index() + 1);
+        run_until_done(
+            || {
+                let key = SeekKey::TableRowId(1);
+                cursor.seek(key, SeekOp::GE { eq_only: true })
+            },
+            pager.deref(),
+        )
+        .unwrap();
 
         let mut read_buffer = Vec::new();
         run_until_done(
@@ -8527,9 +8661,14 @@ mod tests {
         )
         .unwrap();
 
-        cursor
-            .stack
-            .set_cell_index(cursor.stack.current_cell_index() + 1);
+        run_until_done(
+            || {
+                let key = SeekKey::TableRowId(1);
+                cursor.seek(key, SeekOp::GE { eq_only: true })
+            },
+            pager.deref(),
+        )
+        .unwrap();
 
         let offset_to_hello_world = 4 + (large_blob.len() - 11) as u32; // this offset depends on the records type.
         let mut read_buffer = Vec::new();
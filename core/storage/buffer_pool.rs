@@ -1,31 +1,112 @@
 use crate::io::BufferData;
-use std::cell::RefCell;
+use crate::memory::MemoryTracker;
+use std::cell::{Cell, RefCell};
 use std::pin::Pin;
+use std::sync::Arc;
 
 pub struct BufferPool {
     pub free_buffers: RefCell<Vec<BufferData>>,
     page_size: usize,
+    /// Number of buffers owned by the pool, whether in use or free.
+    allocated: Cell<usize>,
+    memory: Arc<MemoryTracker>,
 }
 
 impl BufferPool {
     pub fn new(page_size: usize) -> Self {
+        Self::with_memory(page_size, MemoryTracker::global().clone())
+    }
+
+    /// Creates a pool whose buffers are charged to `memory`.
+    pub fn with_memory(page_size: usize, memory: Arc<MemoryTracker>) -> Self {
         Self {
             free_buffers: RefCell::new(Vec::new()),
             page_size,
+            allocated: Cell::new(0),
+            memory,
         }
     }
 
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn memory(&self) -> &Arc<MemoryTracker> {
+        &self.memory
+    }
+
+    pub fn has_free_buffers(&self) -> bool {
+        !self.free_buffers.borrow().is_empty()
+    }
+
     pub fn get(&self) -> BufferData {
         let mut free_buffers = self.free_buffers.borrow_mut();
         if let Some(buffer) = free_buffers.pop() {
             buffer
         } else {
+            self.allocated.set(self.allocated.get() + 1);
+            self.memory.allocate(self.page_size);
             Pin::new(vec![0; self.page_size])
         }
     }
 
     pub fn put(&self, buffer: BufferData) {
-        let mut free_buffers = self.free_buffers.borrow_mut();
-        free_buffers.push(buffer);
+        // Over the soft heap limit, buffers are freed instead of being kept for reuse.
+        if self.memory.over_soft_limit(0) {
+            self.allocated.set(self.allocated.get() - 1);
+            self.memory.release(self.page_size);
+            return;
+        }
+        self.free_buffers.borrow_mut().push(buffer);
+    }
+
+    /// Frees the buffers that are not in use and returns the number of bytes released.
+    pub fn shrink(&self) -> usize {
+        let freed = std::mem::take(&mut *self.free_buffers.borrow_mut()).len();
+        self.allocated.set(self.allocated.get() - freed);
+        self.memory.release(freed * self.page_size);
+        freed * self.page_size
+    }
+}
+
+impl Drop for BufferPool {
+    fn drop(&mut self) {
+        self.memory.release(self.allocated.get() * self.page_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+    use std::rc::Rc;
+
+    fn buffer(pool: &Rc<BufferPool>) -> Buffer {
+        let pool_clone = pool.clone();
+        Buffer::new(pool.get(), Rc::new(move |buf| pool_clone.put(buf)))
+    }
+
+    #[test]
+    fn test_buffer_pool_keeps_only_its_buffers() {
+        let memory = Arc::new(MemoryTracker::default());
+        let pool = Rc::new(BufferPool::with_memory(100, memory.clone()));
+        let a = buffer(&pool);
+        let b = buffer(&pool);
+        assert_eq!(memory.used(), 200);
+
+        // A copy of a buffer is not charged and does not go back to the pool.
+        drop(a.clone());
+        assert!(!pool.has_free_buffers());
+        drop(a);
+        assert_eq!(pool.free_buffers.borrow().len(), 1);
+        assert_eq!(memory.used(), 200);
+
+        // Over the soft limit, buffers are freed instead of being kept.
+        memory.set_soft_limit(1);
+        drop(b);
+        assert_eq!(pool.free_buffers.borrow().len(), 1);
+        assert_eq!(memory.used(), 100);
+        assert_eq!(pool.shrink(), 100);
+        assert_eq!(memory.used(), 0);
     }
 }
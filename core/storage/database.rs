@@ -16,6 +16,22 @@ pub trait DatabaseStorage: Send + Sync {
         c: Arc<Completion>,
     ) -> Result<()>;
     fn sync(&self, c: Arc<Completion>) -> Result<()>;
+
+    /// Reads the first `size` bytes of the database from a memory mapping, see
+    /// [crate::File::set_mmap_size].
+    fn set_mmap_size(&self, _size: usize) -> Result<()> {
+        Ok(())
+    }
+
+    fn mmap_size(&self) -> usize {
+        0
+    }
+
+    /// Fits the memory mapping to the current size of the database, see
+    /// [crate::File::refresh_mmap].
+    fn refresh_mmap(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(feature = "fs")]
@@ -61,6 +77,18 @@ impl DatabaseStorage for DatabaseFile {
     fn sync(&self, c: Arc<Completion>) -> Result<()> {
         self.file.sync(c)
     }
+
+    fn set_mmap_size(&self, size: usize) -> Result<()> {
+        self.file.set_mmap_size(size)
+    }
+
+    fn mmap_size(&self) -> usize {
+        self.file.mmap_size()
+    }
+
+    fn refresh_mmap(&self) -> Result<()> {
+        self.file.refresh_mmap()
+    }
 }
 
 #[cfg(feature = "fs")]
@@ -0,0 +1,371 @@
+//! Page-level encryption at rest.
+//!
+//! Every page of an encrypted database is encrypted with AES-256-GCM when it is written to the
+//! database file or to the WAL, and decrypted when it is read back. The nonce and the
+//! authentication tag of a page are stored in the bytes reserved at the end of every page, see
+//! the `reserved_space` field of the database header, so encrypted pages have the same size as
+//! plaintext pages and the b-tree layer only sees the usable part of the page.
+//!
+//! Encrypted page layout:
+//!
+//! ```text
+//! +--------------------------------------+-------------+-----------+
+//! | ciphertext (page size - 28 bytes)    | nonce (12)  | tag (16)  |
+//! +--------------------------------------+-------------+-----------+
+//! ```
+//!
+//! The page number is authenticated with the page, so pages cannot be swapped around. Page 1
+//! is special: its first 16 bytes, the "SQLite format 3" magic in a plaintext database, hold
+//! the random salt the key is derived from, and bytes 16 to 24 of the database header (page
+//! size, file format versions and reserved space) are stored in plaintext but authenticated,
+//! so a database can be opened before its key is known.
+//!
+//! The key is either a passphrase, stretched with PBKDF2-HMAC-SHA256, or a raw 256-bit key
+//! given as `x'<64 hex digits>'`.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::{LimboError, Result};
+
+/// Length of the random salt stored at the start of page 1.
+pub const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// Bytes reserved at the end of every page of an encrypted database.
+pub const ENCRYPTION_RESERVED_BYTES: u8 = (NONCE_LEN + TAG_LEN) as u8;
+const KEY_LEN: usize = 32;
+/// PBKDF2 iterations used to derive a key from a passphrase.
+#[cfg(feature = "encryption")]
+const KDF_ITERATIONS: u32 = 256_000;
+
+/// The plaintext magic of page 1.
+const MAGIC: &[u8; SALT_LEN] = b"SQLite format 3\0";
+/// Range of the database header stored in plaintext in an encrypted page 1.
+#[cfg(feature = "encryption")]
+const PLAINTEXT_HEADER: std::ops::Range<usize> = 16..24;
+
+/// Returns true if `page` is page 1 of an encrypted database, i.e. its magic was replaced by a
+/// salt.
+pub fn is_encrypted_header(page: &[u8]) -> bool {
+    page[0..SALT_LEN] != *MAGIC
+}
+
+/// Secret used to encrypt a database.
+#[derive(Clone)]
+pub enum EncryptionKey {
+    Passphrase(String),
+    Raw([u8; KEY_LEN]),
+}
+
+impl EncryptionKey {
+    /// Parses a key as accepted by `PRAGMA key`: either `x'<64 hex digits>'` for a raw key or
+    /// a passphrase.
+    pub fn parse(key: &str) -> Result<Self> {
+        if key.is_empty() {
+            return Err(LimboError::InvalidArgument(
+                "encryption key must not be empty".to_string(),
+            ));
+        }
+        let raw = key
+            .strip_prefix("x'")
+            .or_else(|| key.strip_prefix("X'"))
+            .and_then(|hex| hex.strip_suffix('\''));
+        match raw {
+            Some(hex) => {
+                let mut raw = [0; KEY_LEN];
+                hex::decode_to_slice(hex, &mut raw).map_err(|_| {
+                    LimboError::InvalidArgument(format!(
+                        "raw encryption key must be {} hex digits",
+                        KEY_LEN * 2
+                    ))
+                })?;
+                Ok(Self::Raw(raw))
+            }
+            None => Ok(Self::Passphrase(key.to_string())),
+        }
+    }
+}
+
+/// An encryption key bound to the salt of a database.
+pub(crate) struct Cipher {
+    #[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+    salt: [u8; SALT_LEN],
+    #[cfg(feature = "encryption")]
+    key: ring::aead::LessSafeKey,
+}
+
+impl Cipher {
+    /// Creates a cipher with a new random salt.
+    pub fn generate(key: &EncryptionKey) -> Result<Self> {
+        let mut salt = [0; SALT_LEN];
+        getrandom::getrandom(&mut salt)
+            .map_err(|e| LimboError::InternalError(format!("failed to generate salt: {}", e)))?;
+        Self::new(key, salt)
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn new(key: &EncryptionKey, salt: [u8; SALT_LEN]) -> Result<Self> {
+        use ring::aead::{LessSafeKey, UnboundKey, AES_256_GCM};
+
+        let mut derived = [0; KEY_LEN];
+        let key = match key {
+            EncryptionKey::Raw(raw) => raw,
+            EncryptionKey::Passphrase(passphrase) => {
+                ring::pbkdf2::derive(
+                    ring::pbkdf2::PBKDF2_HMAC_SHA256,
+                    std::num::NonZeroU32::new(KDF_ITERATIONS).unwrap(),
+                    &salt,
+                    passphrase.as_bytes(),
+                    &mut derived,
+                );
+                &derived
+            }
+        };
+        let key = UnboundKey::new(&AES_256_GCM, key)
+            .map_err(|_| LimboError::InternalError("invalid encryption key".to_string()))?;
+        Ok(Self {
+            salt,
+            key: LessSafeKey::new(key),
+        })
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn new(_key: &EncryptionKey, _salt: [u8; SALT_LEN]) -> Result<Self> {
+        Err(LimboError::InvalidArgument(
+            "limbo was built without encryption support".to_string(),
+        ))
+    }
+
+    #[cfg(test)]
+    pub fn salt(&self) -> &[u8; SALT_LEN] {
+        &self.salt
+    }
+
+    /// Returns the bytes of `page` that are encrypted, the start of the page being the
+    /// authenticated additional data.
+    #[cfg(feature = "encryption")]
+    fn split(page_no: usize, page: &mut [u8]) -> (Vec<u8>, &mut [u8], &mut [u8]) {
+        let mut aad = (page_no as u32).to_be_bytes().to_vec();
+        let start = if page_no == 1 {
+            aad.extend_from_slice(&page[PLAINTEXT_HEADER]);
+            PLAINTEXT_HEADER.end
+        } else {
+            0
+        };
+        let end = page.len() - ENCRYPTION_RESERVED_BYTES as usize;
+        let (data, trailer) = page[start..].split_at_mut(end - start);
+        (aad, data, trailer)
+    }
+
+    /// Encrypts page `page_no` in place.
+    #[cfg(feature = "encryption")]
+    pub fn encrypt_page(&self, page_no: usize, page: &mut [u8]) -> Result<()> {
+        use ring::aead::{Aad, Nonce};
+
+        let mut nonce = [0; NONCE_LEN];
+        getrandom::getrandom(&mut nonce)
+            .map_err(|e| LimboError::InternalError(format!("failed to generate nonce: {}", e)))?;
+        if page_no == 1 {
+            page[0..SALT_LEN].copy_from_slice(&self.salt);
+        }
+        let (aad, data, trailer) = Self::split(page_no, page);
+        let tag = self
+            .key
+            .seal_in_place_separate_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), data)
+            .map_err(|_| {
+                LimboError::InternalError(format!("failed to encrypt page {}", page_no))
+            })?;
+        trailer[..NONCE_LEN].copy_from_slice(&nonce);
+        trailer[NONCE_LEN..].copy_from_slice(tag.as_ref());
+        Ok(())
+    }
+
+    /// Decrypts page `page_no` in place, failing if the page was not encrypted with this key or
+    /// was tampered with.
+    #[cfg(feature = "encryption")]
+    pub fn decrypt_page(&self, page_no: usize, page: &mut [u8]) -> Result<()> {
+        use ring::aead::{Aad, Nonce, Tag};
+
+        let (aad, data, trailer) = Self::split(page_no, page);
+        let nonce = Nonce::try_assume_unique_for_key(&trailer[..NONCE_LEN]).unwrap();
+        let tag = Tag::try_from(&trailer[NONCE_LEN..]).unwrap();
+        self.key
+            .open_in_place_separate_tag(nonce, Aad::from(aad), tag, data, 0..)
+            .map_err(|_| LimboError::Corrupt(format!("page {} failed to decrypt", page_no)))?;
+        trailer.fill(0);
+        if page_no == 1 {
+            page[0..SALT_LEN].copy_from_slice(MAGIC);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn encrypt_page(&self, _page_no: usize, _page: &mut [u8]) -> Result<()> {
+        unreachable!("ciphers cannot be created without encryption support")
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    pub fn decrypt_page(&self, _page_no: usize, _page: &mut [u8]) -> Result<()> {
+        unreachable!("ciphers cannot be created without encryption support")
+    }
+}
+
+/// Encryption state of a database, shared by the pagers and WALs of all its connections.
+#[derive(Default)]
+pub(crate) struct Encryption {
+    /// Whether the pages of the database are encrypted.
+    enabled: AtomicBool,
+    /// The cipher of the database, `None` until the key is set.
+    cipher: RwLock<Option<Arc<Cipher>>>,
+    /// Incremented whenever the key or the format of the pages change, so that connections can
+    /// drop the pages they cached.
+    epoch: AtomicU64,
+}
+
+impl Encryption {
+    /// Encryption state of a database whose pages are encrypted with an unknown key.
+    pub fn locked() -> Self {
+        Self {
+            enabled: AtomicBool::new(true),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the database is encrypted but its key was not set yet.
+    pub fn is_locked(&self) -> bool {
+        self.is_enabled() && self.cipher.read().is_none()
+    }
+
+    pub fn cipher(&self) -> Option<Arc<Cipher>> {
+        self.cipher.read().clone()
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Encrypts the pages of the database with `cipher` from now on.
+    pub fn set_cipher(&self, cipher: impl Into<Arc<Cipher>>) {
+        *self.cipher.write() = Some(cipher.into());
+        self.enabled.store(true, Ordering::SeqCst);
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Encrypts a page about to be written. Returns `None` if the database is not encrypted, in
+    /// which case `page` is written as is.
+    pub fn encrypt_page(&self, page_no: usize, page: &[u8]) -> Result<Option<Vec<u8>>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let cipher = self.cipher().ok_or(LimboError::NotADB)?;
+        let mut encrypted = page.to_vec();
+        cipher.encrypt_page(page_no, &mut encrypted)?;
+        Ok(Some(encrypted))
+    }
+
+    /// Decrypts a page that was just read.
+    pub fn decrypt_page(&self, page_no: usize, page: &mut [u8]) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let cipher = self.cipher().ok_or(LimboError::NotADB)?;
+        cipher.decrypt_page(page_no, page)
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    const PAGE_SIZE: usize = 4096;
+
+    fn raw_key(byte: u8) -> EncryptionKey {
+        EncryptionKey::parse(&format!("x'{}'", hex::encode([byte; KEY_LEN]))).unwrap()
+    }
+
+    fn page(page_no: usize) -> Vec<u8> {
+        let mut page: Vec<u8> = (0..PAGE_SIZE).map(|i| (i % 251) as u8).collect();
+        if page_no == 1 {
+            page[0..SALT_LEN].copy_from_slice(MAGIC);
+        }
+        page[PAGE_SIZE - ENCRYPTION_RESERVED_BYTES as usize..].fill(0);
+        page
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert!(matches!(
+            EncryptionKey::parse("secret").unwrap(),
+            EncryptionKey::Passphrase(p) if p == "secret"
+        ));
+        assert!(matches!(raw_key(7), EncryptionKey::Raw(raw) if raw == [7; KEY_LEN]));
+        assert!(EncryptionKey::parse("x'0011'").is_err());
+        assert!(EncryptionKey::parse("").is_err());
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_page() {
+        let cipher = Cipher::generate(&raw_key(1)).unwrap();
+        for page_no in [1, 2] {
+            let plaintext = page(page_no);
+            let mut encrypted = plaintext.clone();
+            cipher.encrypt_page(page_no, &mut encrypted).unwrap();
+            assert_ne!(encrypted, plaintext);
+            if page_no == 1 {
+                assert_eq!(&encrypted[0..SALT_LEN], cipher.salt());
+                assert!(is_encrypted_header(&encrypted));
+                assert_eq!(encrypted[PLAINTEXT_HEADER], plaintext[PLAINTEXT_HEADER]);
+            }
+            // The same page encrypts differently every time.
+            let mut again = plaintext.clone();
+            cipher.encrypt_page(page_no, &mut again).unwrap();
+            assert_ne!(again, encrypted);
+
+            let mut decrypted = encrypted.clone();
+            cipher.decrypt_page(page_no, &mut decrypted).unwrap();
+            assert_eq!(decrypted, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_failures() {
+        let cipher = Cipher::generate(&raw_key(1)).unwrap();
+        let mut encrypted = page(2);
+        cipher.encrypt_page(2, &mut encrypted).unwrap();
+
+        // Wrong key.
+        let other = Cipher::new(&raw_key(2), *cipher.salt()).unwrap();
+        assert!(other.decrypt_page(2, &mut encrypted.clone()).is_err());
+        // Wrong page number.
+        assert!(cipher.decrypt_page(3, &mut encrypted.clone()).is_err());
+        // Tampered ciphertext, nonce and tag.
+        for offset in [0, PAGE_SIZE - 20, PAGE_SIZE - 1] {
+            let mut tampered = encrypted.clone();
+            tampered[offset] ^= 1;
+            assert!(cipher.decrypt_page(2, &mut tampered).is_err());
+        }
+        // Tampered plaintext header of page 1.
+        let mut encrypted = page(1);
+        cipher.encrypt_page(1, &mut encrypted).unwrap();
+        encrypted[PLAINTEXT_HEADER.start] ^= 1;
+        assert!(cipher.decrypt_page(1, &mut encrypted).is_err());
+    }
+
+    #[test]
+    fn test_passphrase_depends_on_salt() {
+        let key = EncryptionKey::parse("secret").unwrap();
+        let cipher = Cipher::generate(&key).unwrap();
+        let mut encrypted = page(2);
+        cipher.encrypt_page(2, &mut encrypted).unwrap();
+        let same = Cipher::new(&key, *cipher.salt()).unwrap();
+        assert!(same.decrypt_page(2, &mut encrypted.clone()).is_ok());
+        let other_salt = Cipher::generate(&key).unwrap();
+        assert!(other_salt.decrypt_page(2, &mut encrypted).is_err());
+    }
+}
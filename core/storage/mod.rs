@@ -10,12 +10,18 @@
 //! for reading and writing pages to the database file, either local or
 //! remote. The `Wal` struct is responsible for managing the write-ahead log
 //! for the database, also either local or remote.
+pub(crate) mod backup;
+pub(crate) mod blob;
 pub(crate) mod btree;
 pub(crate) mod buffer_pool;
 pub(crate) mod database;
+pub(crate) mod encryption;
 pub(crate) mod page_cache;
 #[allow(clippy::arc_with_non_send_sync)]
 pub(crate) mod pager;
+pub(crate) mod replicator;
+pub(crate) mod s3fifo;
+pub(crate) mod serialize;
 pub(crate) mod sqlite3_ondisk;
 #[allow(clippy::arc_with_non_send_sync)]
 pub(crate) mod wal;
@@ -5,7 +5,7 @@ use tracing::{debug, trace};
 
 use super::pager::PageRef;
 
-const DEFAULT_PAGE_CACHE_SIZE_IN_PAGES: usize = 2000;
+pub(crate) const DEFAULT_PAGE_CACHE_SIZE_IN_PAGES: usize = 2000;
 
 #[derive(Debug, Eq, Hash, PartialEq, Clone)]
 pub struct PageCacheKey {
@@ -22,9 +22,11 @@ struct PageCacheEntry {
 
 pub struct DumbLruPageCache {
     capacity: usize,
+    memory_limit: Option<MemoryLimit>,
     map: RefCell<PageHashMap>,
     head: RefCell<Option<NonNull<PageCacheEntry>>>,
     tail: RefCell<Option<NonNull<PageCacheEntry>>>,
+    stats: PageCacheStats,
 }
 unsafe impl Send for DumbLruPageCache {}
 unsafe impl Sync for DumbLruPageCache {}
@@ -58,6 +60,100 @@ pub enum CacheResizeResult {
     PendingEvictions,
 }
 
+/// Counters of a page cache, accumulated since the cache was created.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PageCacheStats {
+    /// Lookups that found the page in the cache.
+    pub hits: u64,
+    /// Lookups that did not find the page in the cache.
+    pub misses: u64,
+    /// Pages removed from the cache to make room for other pages.
+    pub evictions: u64,
+}
+
+impl PageCacheStats {
+    /// Fraction of lookups that found the page in the cache.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+
+/// Limit on the memory used by the pages of a cache.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MemoryLimit {
+    pub max_bytes: usize,
+    pub page_size: usize,
+}
+
+impl MemoryLimit {
+    /// Number of pages that fit in the limit, capped at `capacity`.
+    pub fn max_pages(limit: Option<MemoryLimit>, capacity: usize) -> usize {
+        match limit {
+            Some(limit) => capacity.min((limit.max_bytes / limit.page_size.max(1)).max(1)),
+            None => capacity,
+        }
+    }
+}
+
+/// Cache of the pages read by a pager.
+///
+/// Pages that are locked or dirty are never evicted; a cache that cannot make room for a page
+/// because of them reports [CacheError::Full].
+pub trait PageCache: Send + Sync {
+    fn contains_key(&mut self, key: &PageCacheKey) -> bool;
+
+    /// Inserts a page. Fails with [CacheError::KeyExists] if the key is already cached.
+    fn insert(&mut self, key: PageCacheKey, value: PageRef) -> Result<(), CacheError>;
+
+    /// Inserts a page, replacing the page cached under the same key if any.
+    fn insert_ignore_existing(
+        &mut self,
+        key: PageCacheKey,
+        value: PageRef,
+    ) -> Result<(), CacheError>;
+
+    /// Removes a page and unloads its contents. Returns Ok if the key is not found.
+    fn delete(&mut self, key: PageCacheKey) -> Result<(), CacheError>;
+
+    /// Looks a page up, counting a hit or a miss.
+    fn get(&mut self, key: &PageCacheKey) -> Option<PageRef>;
+
+    /// Looks a page up without updating the statistics. The page is only marked as recently
+    /// used if `touch` is set.
+    fn peek(&mut self, key: &PageCacheKey, touch: bool) -> Option<PageRef>;
+
+    /// Sets the maximum number of pages.
+    fn resize(&mut self, capacity: usize) -> CacheResizeResult;
+
+    /// Limits the memory used by the cache to `max_bytes`, each page taking `page_size` bytes.
+    /// The limit applies in addition to the page capacity; `None` removes it.
+    fn set_max_bytes(&mut self, max_bytes: Option<usize>, page_size: usize) -> CacheResizeResult;
+
+    /// Removes all pages. Fails if a page is locked or dirty.
+    fn clear(&mut self) -> Result<(), CacheError>;
+
+    /// Evicts up to `n` pages that are neither locked nor dirty, returning the number of pages
+    /// evicted.
+    fn evict(&mut self, n: usize) -> usize;
+
+    fn unset_dirty_all_pages(&mut self);
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of pages the cache can hold, taking the memory limit into account.
+    fn capacity(&self) -> usize;
+
+    fn stats(&self) -> PageCacheStats;
+}
+
 impl PageCacheKey {
     pub fn new(pgno: usize) -> Self {
         Self { pgno }
@@ -68,9 +164,11 @@ impl DumbLruPageCache {
         assert!(capacity > 0, "capacity of cache should be at least 1");
         Self {
             capacity,
+            memory_limit: None,
             map: RefCell::new(PageHashMap::new(capacity)),
             head: RefCell::new(None),
             tail: RefCell::new(None),
+            stats: PageCacheStats::default(),
         }
     }
 
@@ -148,7 +246,12 @@ impl DumbLruPageCache {
     }
 
     pub fn get(&mut self, key: &PageCacheKey) -> Option<PageRef> {
-        self.peek(key, true)
+        let page = self.peek(key, true);
+        match page {
+            Some(_) => self.stats.hits += 1,
+            None => self.stats.misses += 1,
+        }
+        page
     }
 
     /// Get page without promoting entry
@@ -175,6 +278,30 @@ impl DumbLruPageCache {
         }
     }
 
+    pub fn set_max_bytes(
+        &mut self,
+        max_bytes: Option<usize>,
+        page_size: usize,
+    ) -> CacheResizeResult {
+        self.memory_limit = max_bytes.map(|max_bytes| MemoryLimit {
+            max_bytes,
+            page_size,
+        });
+        match self.make_room_for(0) {
+            Ok(_) => CacheResizeResult::Done,
+            Err(_) => CacheResizeResult::PendingEvictions,
+        }
+    }
+
+    /// Maximum number of pages, taking the memory limit into account.
+    pub fn capacity(&self) -> usize {
+        MemoryLimit::max_pages(self.memory_limit, self.capacity)
+    }
+
+    pub fn stats(&self) -> PageCacheStats {
+        self.stats
+    }
+
     fn detach(
         &mut self,
         mut entry: NonNull<PageCacheEntry>,
@@ -246,13 +373,14 @@ impl DumbLruPageCache {
     }
 
     pub fn make_room_for(&mut self, n: usize) -> Result<(), CacheError> {
-        if n > self.capacity {
+        let capacity = self.capacity();
+        if n > capacity {
             return Err(CacheError::Full);
         }
 
         let len = self.len();
-        let available = self.capacity.saturating_sub(len);
-        if n <= available && len <= self.capacity {
+        let available = capacity.saturating_sub(len);
+        if n <= available && len <= capacity {
             return Ok(());
         }
 
@@ -264,9 +392,8 @@ impl DumbLruPageCache {
         })?;
 
         // Handle len > capacity, too
-        let available = self.capacity.saturating_sub(len);
         let x = n.saturating_sub(available);
-        let mut need_to_evict = x.saturating_add(len.saturating_sub(self.capacity));
+        let mut need_to_evict = x.saturating_add(len.saturating_sub(capacity));
 
         let mut current_opt = Some(tail);
         while need_to_evict > 0 && current_opt.is_some() {
@@ -275,7 +402,10 @@ impl DumbLruPageCache {
             current_opt = entry.prev; // Pick prev before modifying entry
             match self.delete(entry.key.clone()) {
                 Err(_) => {}
-                Ok(_) => need_to_evict -= 1,
+                Ok(_) => {
+                    need_to_evict -= 1;
+                    self.stats.evictions += 1;
+                }
             }
         }
 
@@ -285,6 +415,23 @@ impl DumbLruPageCache {
         }
     }
 
+    pub fn evict(&mut self, n: usize) -> usize {
+        let mut evicted = 0;
+        let mut current_opt = *self.tail.borrow();
+        while evicted < n {
+            let Some(current) = current_opt else {
+                break;
+            };
+            let entry = unsafe { current.as_ref() };
+            current_opt = entry.prev; // Pick prev before modifying entry
+            if self.delete(entry.key.clone()).is_ok() {
+                evicted += 1;
+                self.stats.evictions += 1;
+            }
+        }
+        evicted
+    }
+
     pub fn clear(&mut self) -> Result<(), CacheError> {
         let mut current = *self.head.borrow();
         while let Some(current_entry) = current {
@@ -476,6 +623,68 @@ impl DumbLruPageCache {
     }
 }
 
+impl PageCache for DumbLruPageCache {
+    fn contains_key(&mut self, key: &PageCacheKey) -> bool {
+        DumbLruPageCache::contains_key(self, key)
+    }
+
+    fn insert(&mut self, key: PageCacheKey, value: PageRef) -> Result<(), CacheError> {
+        DumbLruPageCache::insert(self, key, value)
+    }
+
+    fn insert_ignore_existing(
+        &mut self,
+        key: PageCacheKey,
+        value: PageRef,
+    ) -> Result<(), CacheError> {
+        DumbLruPageCache::insert_ignore_existing(self, key, value)
+    }
+
+    fn delete(&mut self, key: PageCacheKey) -> Result<(), CacheError> {
+        DumbLruPageCache::delete(self, key)
+    }
+
+    fn get(&mut self, key: &PageCacheKey) -> Option<PageRef> {
+        DumbLruPageCache::get(self, key)
+    }
+
+    fn peek(&mut self, key: &PageCacheKey, touch: bool) -> Option<PageRef> {
+        DumbLruPageCache::peek(self, key, touch)
+    }
+
+    fn resize(&mut self, capacity: usize) -> CacheResizeResult {
+        DumbLruPageCache::resize(self, capacity)
+    }
+
+    fn set_max_bytes(&mut self, max_bytes: Option<usize>, page_size: usize) -> CacheResizeResult {
+        DumbLruPageCache::set_max_bytes(self, max_bytes, page_size)
+    }
+
+    fn clear(&mut self) -> Result<(), CacheError> {
+        DumbLruPageCache::clear(self)
+    }
+
+    fn evict(&mut self, n: usize) -> usize {
+        DumbLruPageCache::evict(self, n)
+    }
+
+    fn unset_dirty_all_pages(&mut self) {
+        DumbLruPageCache::unset_dirty_all_pages(self)
+    }
+
+    fn len(&self) -> usize {
+        DumbLruPageCache::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        DumbLruPageCache::capacity(self)
+    }
+
+    fn stats(&self) -> PageCacheStats {
+        DumbLruPageCache::stats(self)
+    }
+}
+
 impl Default for DumbLruPageCache {
     fn default() -> Self {
         DumbLruPageCache::new(DEFAULT_PAGE_CACHE_SIZE_IN_PAGES)
@@ -1,25 +1,30 @@
 use crate::fast_lock::SpinLock;
+use crate::io::{Completion, ReadCompletion, WriteCompletion};
 use crate::result::LimboResult;
 use crate::storage::btree::BTreePageInner;
 use crate::storage::buffer_pool::BufferPool;
 use crate::storage::database::DatabaseStorage;
+use crate::storage::encryption::{
+    is_encrypted_header, Cipher, Encryption, EncryptionKey, ENCRYPTION_RESERVED_BYTES, SALT_LEN,
+};
 use crate::storage::sqlite3_ondisk::{
-    self, DatabaseHeader, PageContent, PageType, DATABASE_HEADER_PAGE_ID,
+    self, read_header_from_buf, read_u32, DatabaseHeader, PageContent, PageType, WalHeader,
+    DATABASE_HEADER_PAGE_ID, DATABASE_HEADER_SIZE, WAL_FRAME_HEADER_SIZE, WAL_HEADER_SIZE,
 };
 use crate::storage::wal::{CheckpointResult, Wal, WalFsyncStatus};
 use crate::types::CursorResult;
-use crate::Completion;
 use crate::{Buffer, LimboError, Result};
 use parking_lot::RwLock;
-use std::cell::{RefCell, UnsafeCell};
+use std::cell::{Cell, RefCell, UnsafeCell};
 use std::collections::HashSet;
+use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tracing::trace;
 
 use super::btree::BTreePage;
-use super::page_cache::{CacheError, CacheResizeResult, DumbLruPageCache, PageCacheKey};
+use super::page_cache::{CacheError, CacheResizeResult, PageCache, PageCacheKey, PageCacheStats};
 use super::wal::{CheckpointMode, CheckpointStatus};
 
 #[cfg(not(feature = "omit_autovacuum"))]
@@ -201,7 +206,7 @@ pub struct Pager {
     /// The write-ahead log (WAL) for the database.
     wal: Rc<RefCell<dyn Wal>>,
     /// A page cache for the database.
-    page_cache: Arc<RwLock<DumbLruPageCache>>,
+    page_cache: Arc<RwLock<dyn PageCache>>,
     /// Buffer pool for temporary data storage.
     buffer_pool: Rc<BufferPool>,
     /// I/O interface for input/output operations.
@@ -214,6 +219,16 @@ pub struct Pager {
     checkpoint_inflight: Rc<RefCell<usize>>,
     syncing: Rc<RefCell<bool>>,
     auto_vacuum_mode: RefCell<AutoVacuumMode>,
+    /// The database header as it was when the current write transaction began, restored if
+    /// the transaction is rolled back.
+    header_before_write: RefCell<Option<DatabaseHeader>>,
+    /// The database header of the last page 1 inserted with `wal_insert_frame`, installed when
+    /// the frames are committed.
+    inserted_header: RefCell<Option<DatabaseHeader>>,
+    /// Encryption state of the database, shared with the other connections.
+    pub(crate) encryption: Arc<Encryption>,
+    /// Encryption epoch the cached pages were read in.
+    encryption_epoch: Cell<u64>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -246,7 +261,7 @@ impl Pager {
         db_file: Arc<dyn DatabaseStorage>,
         wal: Rc<RefCell<dyn Wal>>,
         io: Arc<dyn crate::io::IO>,
-        page_cache: Arc<RwLock<DumbLruPageCache>>,
+        page_cache: Arc<RwLock<dyn PageCache>>,
         buffer_pool: Rc<BufferPool>,
     ) -> Result<Self> {
         Ok(Self {
@@ -265,9 +280,18 @@ impl Pager {
             checkpoint_inflight: Rc::new(RefCell::new(0)),
             buffer_pool,
             auto_vacuum_mode: RefCell::new(AutoVacuumMode::None),
+            header_before_write: RefCell::new(None),
+            inserted_header: RefCell::new(None),
+            encryption: Arc::new(Encryption::default()),
+            encryption_epoch: Cell::new(0),
         })
     }
 
+    pub(crate) fn set_encryption(&mut self, encryption: Arc<Encryption>) {
+        self.encryption_epoch.set(encryption.epoch());
+        self.encryption = encryption;
+    }
+
     pub fn get_auto_vacuum_mode(&self) -> AutoVacuumMode {
         *self.auto_vacuum_mode.borrow()
     }
@@ -550,12 +574,53 @@ impl Pager {
 
     #[inline(always)]
     pub fn begin_read_tx(&self) -> Result<LimboResult> {
-        self.wal.borrow_mut().begin_read_tx()
+        if self.encryption.is_locked() {
+            return Err(LimboError::NotADB);
+        }
+        // The key or the format of the pages changed, see [Connection::set_encryption_key].
+        if self.encryption.epoch() != self.encryption_epoch.get() {
+            self.encryption_epoch.set(self.encryption.epoch());
+            self.clear_page_cache();
+        }
+        let (max_frame, salts) = {
+            let wal = self.wal.borrow();
+            let header = wal.get_header();
+            (wal.get_max_frame(), (header.salt_1, header.salt_2))
+        };
+        let result = self.wal.borrow_mut().begin_read_tx()?;
+        if let LimboResult::Ok = result {
+            let wal = self.wal.borrow();
+            let header = wal.get_header();
+            if (header.salt_1, header.salt_2) != salts || wal.get_max_frame() < max_frame {
+                // The WAL was restarted, so its frames can't tell which pages changed. The
+                // checkpoint that restarted it may have truncated the database file too.
+                self.clear_page_cache();
+                self.db_file.refresh_mmap()?;
+            } else if wal.get_max_frame() > max_frame {
+                // Another connection committed since our last read transaction, so the pages
+                // it wrote may be stale.
+                let stale_pages = wal.pages_in_frames_after(max_frame);
+                let mut cache = self.page_cache.write();
+                let deleted = stale_pages
+                    .into_iter()
+                    .try_for_each(|page_id| cache.delete(PageCacheKey::new(page_id as usize)));
+                drop(cache);
+                if deleted.is_err() {
+                    self.clear_page_cache();
+                }
+            }
+        }
+        Ok(result)
     }
 
     #[inline(always)]
     pub fn begin_write_tx(&self) -> Result<LimboResult> {
-        self.wal.borrow_mut().begin_write_tx()
+        let result = self.wal.borrow_mut().begin_write_tx()?;
+        if let LimboResult::Ok = result {
+            self.header_before_write
+                .replace(Some(self.db_header.lock().clone()));
+        }
+        Ok(result)
     }
 
     pub fn end_tx(&self) -> Result<PagerCacheflushStatus> {
@@ -563,6 +628,7 @@ impl Pager {
         return match cacheflush_status {
             PagerCacheflushStatus::IO => Ok(PagerCacheflushStatus::IO),
             PagerCacheflushStatus::Done(_) => {
+                self.header_before_write.replace(None);
                 self.wal.borrow().end_write_tx()?;
                 self.wal.borrow().end_read_tx()?;
                 Ok(cacheflush_status)
@@ -570,6 +636,21 @@ impl Pager {
         };
     }
 
+    /// Discards the changes of the current write transaction and releases its locks.
+    /// Changed pages are only written to the WAL on commit, or spilled to it as frames that
+    /// readers don't see before the commit, so dropping the dirty pages from the page cache and
+    /// the spilled frames, and restoring the header is enough to undo the transaction.
+    pub fn rollback(&self) -> Result<()> {
+        self.clear_page_cache();
+        self.wal.borrow_mut().discard_inserted_frames();
+        if let Some(header) = self.header_before_write.take() {
+            *self.db_header.lock() = header;
+        }
+        self.wal.borrow().end_write_tx()?;
+        self.wal.borrow().end_read_tx()?;
+        Ok(())
+    }
+
     pub fn end_read_tx(&self) -> Result<()> {
         self.wal.borrow().end_read_tx()?;
         Ok(())
@@ -584,6 +665,7 @@ impl Pager {
             tracing::trace!("read_page(page_idx = {}) = cached", page_idx);
             return Ok(page.clone());
         }
+        self.reserve_page_memory(&mut *page_cache)?;
         let page = Arc::new(Page::new(page_idx));
         page.set_locked();
 
@@ -614,6 +696,7 @@ impl Pager {
 
         sqlite3_ondisk::begin_read_page(
             self.db_file.clone(),
+            self.encryption.clone(),
             self.buffer_pool.clone(),
             page.clone(),
             page_idx,
@@ -656,6 +739,52 @@ impl Pager {
         Ok(page_cache.resize(capacity))
     }
 
+    /// Limits the memory used by the page cache to `max_bytes`, or removes the limit.
+    pub fn set_page_cache_max_bytes(&self, max_bytes: Option<usize>) -> Result<CacheResizeResult> {
+        let page_size = self.db_header.lock().get_page_size() as usize;
+        let mut page_cache = self.page_cache.write();
+        Ok(page_cache.set_max_bytes(max_bytes, page_size))
+    }
+
+    /// Makes room for the buffer of a page about to be read or allocated. Unless the buffer pool
+    /// has a free buffer to reuse, clean pages are evicted while a new buffer would go over the
+    /// soft heap limit, and the page is refused if it would go over the hard heap limit.
+    fn reserve_page_memory(&self, page_cache: &mut dyn PageCache) -> Result<()> {
+        let page_size = self.buffer_pool.page_size();
+        let memory = self.buffer_pool.memory();
+        while !self.buffer_pool.has_free_buffers() && memory.over_soft_limit(page_size) {
+            if page_cache.evict(1) == 0 {
+                break;
+            }
+        }
+        if !self.buffer_pool.has_free_buffers() && memory.over_hard_limit(page_size) {
+            return Err(LimboError::OutOfMemory);
+        }
+        Ok(())
+    }
+
+    /// Evicts the clean pages of the page cache and frees the buffers that are not in use,
+    /// returning the number of bytes released.
+    pub fn shrink_memory(&self) -> usize {
+        self.page_cache.write().evict(usize::MAX);
+        self.buffer_pool.shrink()
+    }
+
+    /// Reads the pages of the database file that lie in its first `size` bytes from a memory
+    /// mapping. Pages in the WAL are still read from the WAL.
+    pub fn set_mmap_size(&self, size: usize) -> Result<()> {
+        self.db_file.set_mmap_size(size)
+    }
+
+    pub fn mmap_size(&self) -> usize {
+        self.db_file.mmap_size()
+    }
+
+    /// Returns the hit, miss and eviction counters of the page cache.
+    pub fn page_cache_stats(&self) -> PageCacheStats {
+        self.page_cache.read().stats()
+    }
+
     pub fn add_dirty(&self, page_id: usize) {
         // TODO: check duplicates?
         let mut dirty_pages = RefCell::borrow_mut(&self.dirty_pages);
@@ -666,18 +795,40 @@ impl Pager {
         Ok(self.wal.borrow().get_max_frame_in_wal())
     }
 
+    /// Returns the last WAL frame visible to the current read transaction.
+    pub fn wal_read_mark(&self) -> u64 {
+        self.wal.borrow().get_max_frame()
+    }
+
     /// Flush dirty pages to disk.
     /// In the base case, it will write the dirty pages to the WAL and then fsync the WAL.
     /// If the WAL size is over the checkpoint threshold, it will checkpoint the WAL to
     /// the database file and then fsync the database file.
     pub fn cacheflush(&self) -> Result<PagerCacheflushStatus> {
+        self.flush_dirty_pages(true)
+    }
+
+    /// Writes the dirty pages to the WAL without committing them, to make room in the page
+    /// cache during a write transaction that changes many pages. Readers don't see the spilled
+    /// pages until the transaction commits, and [Pager::rollback] discards them. They are
+    /// committed along with the dirty pages of the commit, so a page must be dirty by then.
+    pub fn spill(&self) -> Result<PagerCacheflushStatus> {
+        self.flush_dirty_pages(false)
+    }
+
+    fn flush_dirty_pages(&self, commit: bool) -> Result<PagerCacheflushStatus> {
         let mut checkpoint_result = CheckpointResult::default();
         loop {
             let state = self.flush_info.borrow().state;
             trace!("cacheflush {:?}", state);
             match state {
                 FlushState::Start => {
-                    let db_size = self.db_header.lock().database_size;
+                    // Only commit frames carry the database size.
+                    let db_size = if commit {
+                        self.db_header.lock().database_size
+                    } else {
+                        0
+                    };
                     for page_id in self.dirty_pages.borrow().iter() {
                         let mut cache = self.page_cache.write();
                         let page_key = PageCacheKey::new(*page_id);
@@ -702,11 +853,17 @@ impl Pager {
                 }
                 FlushState::WaitAppendFrames => {
                     let in_flight = *self.flush_info.borrow().in_flight_writes.borrow();
-                    if in_flight == 0 {
-                        self.flush_info.borrow_mut().state = FlushState::SyncWal;
-                    } else {
+                    if in_flight != 0 {
                         return Ok(PagerCacheflushStatus::IO);
                     }
+                    if !commit {
+                        // The WAL is synced when the spilled frames are committed.
+                        self.flush_info.borrow_mut().state = FlushState::Start;
+                        return Ok(PagerCacheflushStatus::Done(
+                            PagerCacheflushResult::WalWritten,
+                        ));
+                    }
+                    self.flush_info.borrow_mut().state = FlushState::SyncWal;
                 }
                 FlushState::SyncWal => {
                     if WalFsyncStatus::IO == self.wal.borrow_mut().sync()? {
@@ -756,12 +913,309 @@ impl Pager {
         frame_len: u32,
     ) -> Result<Arc<Completion>> {
         let wal = self.wal.borrow();
-        return wal.read_frame_raw(
-            frame_no.into(),
-            self.buffer_pool.clone(),
-            p_frame,
-            frame_len,
+        wal.read_frame_raw(frame_no.into(), p_frame, frame_len)
+    }
+
+    /// Returns the WAL header as it is stored on disk.
+    pub fn wal_get_header(&self) -> [u8; WAL_HEADER_SIZE] {
+        self.wal.borrow().get_header().to_be_bytes()
+    }
+
+    /// Takes the WAL write lock to insert frames copied from the WAL with header `header`.
+    /// A WAL without frames takes over the header, otherwise it must already have it or, if the
+    /// other WAL was restarted since, its frames are checkpointed before taking it over.
+    pub fn wal_insert_begin(&self, header: &[u8]) -> Result<()> {
+        let header = WalHeader::from_be_bytes(header)?;
+        if header.page_size != self.wal.borrow().get_header().page_size {
+            return Err(LimboError::InvalidArgument(format!(
+                "WAL page size {} does not match the database page size",
+                header.page_size
+            )));
+        }
+        if let LimboResult::Busy = self.wal.borrow_mut().begin_write_tx()? {
+            return Err(LimboError::Busy);
+        }
+        let result = self.take_over_wal_header(header);
+        if result.is_err() {
+            self.wal.borrow().end_write_tx()?;
+        }
+        result
+    }
+
+    fn take_over_wal_header(&self, header: WalHeader) -> Result<()> {
+        let current = self.wal.borrow().get_header();
+        if current.to_be_bytes() == header.to_be_bytes() {
+            return Ok(());
+        }
+        if self.wal.borrow().get_max_frame_in_wal() > 0 {
+            // A restart gives the WAL a later checkpoint sequence number and new salts.
+            if header.checkpoint_seq <= current.checkpoint_seq
+                || (header.salt_1, header.salt_2) == (current.salt_1, current.salt_2)
+            {
+                return Err(LimboError::InvalidArgument(
+                    "WAL header does not match the WAL of this database".to_string(),
+                ));
+            }
+            let checkpoint = loop {
+                match self.checkpoint()? {
+                    CheckpointStatus::IO => self.io.run_once()?,
+                    CheckpointStatus::Done(result) => break result,
+                }
+            };
+            if checkpoint.num_checkpointed_frames != checkpoint.num_wal_frames {
+                return Err(LimboError::Busy);
+            }
+            let c = self.wal.borrow_mut().restart()?;
+            self.io.wait_for_completion(c)?;
+        }
+        let c = self.wal.borrow_mut().set_header(header)?;
+        self.io.wait_for_completion(c)
+    }
+
+    /// Appends a frame copied from another WAL, see [Wal::insert_frame_raw]. When the frame
+    /// is a commit frame, the WAL is synced and the frames of the transaction become visible.
+    /// Returns true if the frame committed a transaction that changed page 1.
+    pub fn wal_insert_frame(&self, frame_no: u64, frame: &[u8]) -> Result<bool> {
+        let write_counter = Rc::new(RefCell::new(0));
+        let db_size =
+            self.wal
+                .borrow_mut()
+                .insert_frame_raw(frame_no, frame, write_counter.clone())?;
+        if read_u32(frame, 0) == DATABASE_HEADER_PAGE_ID as u32 {
+            let mut header = DatabaseHeader::default();
+            read_header_from_buf(&frame[WAL_FRAME_HEADER_SIZE..], &mut header);
+            self.inserted_header.replace(Some(header));
+        }
+        while *write_counter.borrow() > 0 {
+            self.io.run_once()?;
+        }
+        if db_size == 0 {
+            return Ok(false);
+        }
+        while let WalFsyncStatus::IO = self.wal.borrow_mut().sync()? {
+            self.io.run_once()?;
+        }
+        self.wal.borrow_mut().commit_inserted_frames();
+        self.clear_page_cache();
+        match self.inserted_header.take() {
+            Some(header) => {
+                *self.db_header.lock() = header;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Discards the inserted frames that were not committed and releases the WAL write lock.
+    pub fn wal_insert_end(&self) -> Result<()> {
+        self.wal.borrow_mut().discard_inserted_frames();
+        self.inserted_header.replace(None);
+        self.wal.borrow().end_write_tx()?;
+        Ok(())
+    }
+
+    /// Sets the key of the database. The key of an encrypted database is checked against page
+    /// 1, while a plaintext database is encrypted with the key if it is still empty: the bytes
+    /// encryption needs at the end of every page can't be reserved once pages are in use.
+    pub fn set_encryption_key(&self, key: &EncryptionKey) -> Result<()> {
+        // A rekey leaves page 1, and the salt it starts with, in the WAL until a checkpoint.
+        let mut page = self.read_page_raw(DATABASE_HEADER_PAGE_ID)?;
+        if !is_encrypted_header(&page) {
+            return self.with_write_lock(|| self.encrypt_empty_database(key));
+        }
+        if page[20] != ENCRYPTION_RESERVED_BYTES {
+            return Err(LimboError::NotADB);
+        }
+        let cipher = Cipher::new(key, page[..SALT_LEN].try_into().unwrap())?;
+        cipher
+            .decrypt_page(DATABASE_HEADER_PAGE_ID, &mut page)
+            .map_err(|_| LimboError::NotADB)?;
+        read_header_from_buf(&page, &mut self.db_header.lock());
+        self.encryption.set_cipher(cipher);
+        Ok(())
+    }
+
+    fn encrypt_empty_database(&self, key: &EncryptionKey) -> Result<()> {
+        let page = self.read_db_page_raw(DATABASE_HEADER_PAGE_ID)?;
+        let cell_count = u16::from_be_bytes([
+            page[DATABASE_HEADER_SIZE + 3],
+            page[DATABASE_HEADER_SIZE + 4],
+        ]);
+        let mut header = self.db_header.lock().clone();
+        if header.database_size > 1
+            || cell_count > 0
+            || self.wal.borrow().get_max_frame_in_wal() > 0
+        {
+            return Err(LimboError::InvalidArgument(
+                "only an empty database can be encrypted".to_string(),
+            ));
+        }
+        header.reserved_space = ENCRYPTION_RESERVED_BYTES;
+        let page1 = Arc::new(BTreePageInner {
+            page: RefCell::new(allocate_page(
+                DATABASE_HEADER_PAGE_ID,
+                &self.buffer_pool,
+                DATABASE_HEADER_SIZE,
+            )),
+        });
+        crate::btree_init_page(
+            &page1,
+            PageType::TableLeaf,
+            DATABASE_HEADER_SIZE,
+            (header.get_page_size() - header.reserved_space as u32) as u16,
         );
+        let mut page = {
+            let page1 = page1.get();
+            let contents = page1.get().contents.as_ref().unwrap();
+            contents.write_database_header(&header);
+            contents.as_ptr().to_vec()
+        };
+        let cipher = Cipher::generate(key)?;
+        cipher.encrypt_page(DATABASE_HEADER_PAGE_ID, &mut page)?;
+        self.write_db_page_raw(DATABASE_HEADER_PAGE_ID, page)?;
+        self.sync_db_file()?;
+        *self.db_header.lock() = header;
+        self.encryption.set_cipher(cipher);
+        Ok(())
+    }
+
+    /// Re-encrypts the database with a new key. The WAL is checkpointed and started over, then
+    /// every page is appended to it encrypted with the new key and committed at once, so that a
+    /// crash leaves the database readable with either the old or the new key. The frames reach
+    /// the database file with the next checkpoint.
+    pub fn rekey(&self, key: &EncryptionKey) -> Result<()> {
+        let Some(old) = self.encryption.cipher() else {
+            return Err(match self.encryption.is_enabled() {
+                true => LimboError::NotADB,
+                false => LimboError::InvalidArgument(
+                    "database is not encrypted, use PRAGMA key to encrypt an empty database"
+                        .to_string(),
+                ),
+            });
+        };
+        self.with_write_lock(|| {
+            let checkpoint = loop {
+                match self.checkpoint()? {
+                    CheckpointStatus::IO => self.io.run_once()?,
+                    CheckpointStatus::Done(result) => break result,
+                }
+            };
+            if checkpoint.num_checkpointed_frames != checkpoint.num_wal_frames {
+                return Err(LimboError::Busy);
+            }
+            // The frames left in the WAL are encrypted with the old key.
+            let c = self.wal.borrow_mut().restart()?;
+            self.io.wait_for_completion(c)?;
+            let database_size = self.db_header.lock().database_size as usize;
+            // Check that every page decrypts before rewriting any of them.
+            for page_idx in 1..=database_size {
+                old.decrypt_page(page_idx, &mut self.read_db_page_raw(page_idx)?)?;
+            }
+            self.encryption.set_cipher(Cipher::generate(key)?);
+            if let Err(e) = self.append_rekeyed_frames(&old, database_size) {
+                self.wal.borrow_mut().discard_inserted_frames();
+                self.encryption.set_cipher(old);
+                return Err(e);
+            }
+            while let WalFsyncStatus::IO = self.wal.borrow_mut().sync()? {
+                self.io.run_once()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Appends the pages of the database file to the WAL, decrypted with `old` and encrypted
+    /// with the current cipher. The last page is the commit frame.
+    fn append_rekeyed_frames(&self, old: &Cipher, database_size: usize) -> Result<()> {
+        let write_counter = Rc::new(RefCell::new(0));
+        for page_idx in 1..=database_size {
+            let mut buf = self.read_db_page_raw(page_idx)?;
+            old.decrypt_page(page_idx, &mut buf)?;
+            let page = allocate_page(page_idx, &self.buffer_pool, 0);
+            page.get()
+                .contents
+                .as_ref()
+                .unwrap()
+                .as_ptr()
+                .copy_from_slice(&buf);
+            let db_size = if page_idx == database_size {
+                database_size as u32
+            } else {
+                0
+            };
+            self.wal
+                .borrow_mut()
+                .append_frame(page, db_size, write_counter.clone())?;
+        }
+        while *write_counter.borrow() > 0 {
+            self.io.run_once()?;
+        }
+        Ok(())
+    }
+
+    /// Reads the latest version of a page as it is stored on disk, from the WAL if the page has
+    /// a frame there.
+    fn read_page_raw(&self, page_idx: usize) -> Result<Vec<u8>> {
+        if let LimboResult::Busy = self.wal.borrow_mut().begin_read_tx()? {
+            return Err(LimboError::Busy);
+        }
+        let result = match self.wal.borrow().find_frame(page_idx as u64) {
+            Ok(Some(frame_id)) => {
+                let page_size = self.db_header.lock().get_page_size() as usize;
+                let mut frame = vec![0; WAL_FRAME_HEADER_SIZE + page_size];
+                self.wal
+                    .borrow()
+                    .read_frame_raw(frame_id, frame.as_mut_ptr(), frame.len() as u32)
+                    .and_then(|c| self.io.wait_for_completion(c))
+                    .map(|_| frame.split_off(WAL_FRAME_HEADER_SIZE))
+            }
+            Ok(None) => self.read_db_page_raw(page_idx),
+            Err(e) => Err(e),
+        };
+        self.wal.borrow().end_read_tx()?;
+        result
+    }
+
+    /// Runs `f` while holding the WAL write lock.
+    fn with_write_lock(&self, f: impl FnOnce() -> Result<()>) -> Result<()> {
+        if let LimboResult::Busy = self.wal.borrow_mut().begin_write_tx()? {
+            return Err(LimboError::Busy);
+        }
+        let result = f();
+        self.wal.borrow().end_write_tx()?;
+        result
+    }
+
+    /// Reads a page of the database file as it is stored on disk.
+    fn read_db_page_raw(&self, page_idx: usize) -> Result<Vec<u8>> {
+        let page_size = self.db_header.lock().get_page_size() as usize;
+        let drop_fn = Rc::new(|_buf| {});
+        let buf = Arc::new(RefCell::new(Buffer::allocate(page_size, drop_fn)));
+        let c = Arc::new(Completion::Read(ReadCompletion::new(
+            buf.clone(),
+            Box::new(|_| {}),
+        )));
+        self.db_file.read_page(page_idx, c.clone())?;
+        self.io.wait_for_completion(c)?;
+        let page = buf.borrow().as_slice().to_vec();
+        Ok(page)
+    }
+
+    /// Writes a page to the database file as is.
+    fn write_db_page_raw(&self, page_idx: usize, page: Vec<u8>) -> Result<()> {
+        let drop_fn = Rc::new(|_buf| {});
+        let buf = Arc::new(RefCell::new(Buffer::new(Pin::new(page), drop_fn)));
+        let c = Arc::new(Completion::Write(WriteCompletion::new(Box::new(|_| {}))));
+        self.db_file.write_page(page_idx, buf, c.clone())?;
+        self.io.wait_for_completion(c)
+    }
+
+    fn sync_db_file(&self) -> Result<()> {
+        sqlite3_ondisk::begin_sync(self.db_file.clone(), self.syncing.clone())?;
+        while *self.syncing.borrow() {
+            self.io.run_once()?;
+        }
+        Ok(())
     }
 
     pub fn checkpoint(&self) -> Result<CheckpointStatus> {
@@ -836,11 +1290,44 @@ impl Pager {
                 attempts += 1;
             }
         }
-        self.wal_checkpoint();
+        self.wal_checkpoint_passive();
         Ok(())
     }
 
-    pub fn wal_checkpoint(&self) -> CheckpointResult {
+    /// Checkpoints the WAL like `PRAGMA wal_checkpoint(mode)`. The modes other than PASSIVE
+    /// lock writers out, checkpoint every frame and sync the database file, and fail with
+    /// [LimboError::Busy] if a writer holds the lock or a reader keeps frames from being
+    /// checkpointed. RESTART and TRUNCATE then restart the WAL, once no reader uses it anymore,
+    /// and TRUNCATE truncates the WAL file.
+    pub fn wal_checkpoint(&self, mode: CheckpointMode) -> Result<CheckpointResult> {
+        if let CheckpointMode::Passive = mode {
+            return Ok(self.wal_checkpoint_passive());
+        }
+        let mut result = CheckpointResult::default();
+        self.with_write_lock(|| {
+            result = loop {
+                match self.checkpoint()? {
+                    CheckpointStatus::IO => self.io.run_once()?,
+                    CheckpointStatus::Done(result) => break result,
+                }
+            };
+            if result.num_checkpointed_frames != result.num_wal_frames {
+                return Err(LimboError::Busy);
+            }
+            if let CheckpointMode::Restart | CheckpointMode::Truncate = mode {
+                let c = self.wal.borrow_mut().restart()?;
+                self.io.wait_for_completion(c)?;
+            }
+            if let CheckpointMode::Truncate = mode {
+                self.wal.borrow_mut().truncate()?;
+            }
+            Ok(())
+        })?;
+        self.clear_page_cache();
+        Ok(result)
+    }
+
+    fn wal_checkpoint_passive(&self) -> CheckpointResult {
         let checkpoint_result: CheckpointResult;
         loop {
             match self.wal.borrow_mut().checkpoint(
@@ -946,6 +1433,7 @@ impl Pager {
     // FIXME: handle no room in page cache
     #[allow(clippy::readonly_write_lock)]
     pub fn allocate_page(&self) -> Result<PageRef> {
+        self.reserve_page_memory(&mut *self.page_cache.write())?;
         let header = &self.db_header;
         let mut header = header.lock();
         header.database_size += 1;
@@ -1021,6 +1509,37 @@ impl Pager {
         Ok(())
     }
 
+    /// Replaces the contents of page `page_idx` with the raw page image `data` and marks it
+    /// dirty, so that it is written out on the next cache flush.
+    pub fn write_raw_page(&self, page_idx: usize, data: &[u8]) -> Result<()> {
+        let offset = if page_idx == DATABASE_HEADER_PAGE_ID {
+            DATABASE_HEADER_SIZE
+        } else {
+            0
+        };
+        let page = allocate_page(page_idx, &self.buffer_pool, offset);
+        page.get_contents().as_ptr().copy_from_slice(data);
+        page.set_dirty();
+        self.add_dirty(page_idx);
+
+        let mut cache = self.page_cache.write();
+        let page_key = PageCacheKey::new(page_idx);
+        if let Err(e) = cache.delete(page_key.clone()) {
+            return Err(LimboError::InternalError(format!(
+                "Failed to evict page {} from cache: {:?}",
+                page_idx, e
+            )));
+        }
+        match cache.insert(page_key, page) {
+            Ok(_) => Ok(()),
+            Err(CacheError::Full) => Err(LimboError::CacheFull),
+            Err(e) => Err(LimboError::InternalError(format!(
+                "Failed to insert page {} into cache: {:?}",
+                page_idx, e
+            ))),
+        }
+    }
+
     pub fn usable_size(&self) -> usize {
         let db_header = self.db_header.lock();
         (db_header.get_page_size() - db_header.reserved_space as u32) as usize
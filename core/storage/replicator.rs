@@ -0,0 +1,121 @@
+//! WAL streaming replication between two databases.
+//!
+//! A [Replicator] keeps a replica database in sync with a primary database by copying the
+//! frames appended to the primary's WAL to the replica's WAL with
+//! [Connection::wal_insert_frame]. The replica's WAL becomes a byte-for-byte copy of the
+//! primary's WAL, so every frame is checked against the salts and the running checksum of the
+//! primary's WAL before it is written, and the frames of a transaction only become visible on
+//! the replica once its commit frame has been copied.
+//!
+//! The replica must start from the same database file as the primary when the primary's WAL
+//! was empty, e.g. both freshly created, and must not be written to other than by the
+//! [Replicator]: its connections fail with [LimboError::ReadOnly] on writes while the
+//! [Replicator] lives. A write from another process makes the replica's WAL diverge from the
+//! primary's, which the next [Replicator::sync] detects by comparing the checksums of the last
+//! replicated frame.
+//!
+//! A RESTART or TRUNCATE checkpoint of the primary restarts the primary's WAL with new salts
+//! once all of its frames are in the database file. The next [Replicator::sync] then
+//! checkpoints the replica's WAL and starts over with the primary's new WAL. The frames the
+//! primary checkpointed before they were replicated are lost to the replica, so a replica must
+//! be synced before the primary's WAL is restarted.
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::storage::sqlite3_ondisk::WAL_FRAME_HEADER_SIZE;
+use crate::{Connection, LimboError, Result};
+
+/// Bytes of the salts in a WAL header.
+const WAL_HEADER_SALTS: std::ops::Range<usize> = 16..24;
+
+pub struct Replicator {
+    /// Private connection to the primary database.
+    primary: Arc<Connection>,
+    replica: Arc<Connection>,
+}
+
+impl Replicator {
+    /// Prepares the replication of the database behind `primary` into the database behind
+    /// `replica`.
+    pub fn new(primary: &Arc<Connection>, replica: &Arc<Connection>) -> Result<Self> {
+        if Arc::ptr_eq(&primary._db, &replica._db) {
+            return Err(LimboError::InvalidArgument(
+                "primary and replica must be distinct databases".to_string(),
+            ));
+        }
+        let primary_page_size = primary._db.page_size;
+        let replica_page_size = replica._db.page_size;
+        if primary_page_size != replica_page_size {
+            return Err(LimboError::InvalidArgument(format!(
+                "replication between databases with different page sizes is not supported ({} and {})",
+                primary_page_size, replica_page_size
+            )));
+        }
+        let primary = primary._db.connect()?;
+        replica._db.replica.store(true, Ordering::SeqCst);
+        Ok(Self {
+            primary,
+            replica: replica.clone(),
+        })
+    }
+
+    /// Copies the frames appended to the primary's WAL since the last sync to the replica and
+    /// returns the number of frames the replica committed. Frames of a transaction that is
+    /// still being written on the primary are copied again by the next sync.
+    pub fn sync(&self) -> Result<u64> {
+        let header = self.primary.wal_get_header();
+        let primary_frames = self.primary.wal_frame_count()?;
+        let mut replica_frames = self.replica.wal_frame_count()?;
+        // The salts of the WAL headers only differ once the replica has frames if the primary
+        // restarted its WAL, whose frames then all have to be copied.
+        let restarted = replica_frames > 0
+            && header[WAL_HEADER_SALTS] != self.replica.wal_get_header()[WAL_HEADER_SALTS];
+        if restarted {
+            replica_frames = 0;
+        } else if primary_frames < replica_frames {
+            return Err(LimboError::InvalidArgument(format!(
+                "replica WAL has {} frames but the primary WAL only {}",
+                replica_frames, primary_frames
+            )));
+        }
+        if replica_frames > 0
+            && read_frame(&self.primary, replica_frames)?[16..24]
+                != read_frame(&self.replica, replica_frames)?[16..24]
+        {
+            return Err(LimboError::InvalidArgument(format!(
+                "replica WAL diverged from the primary WAL at frame {}",
+                replica_frames
+            )));
+        }
+        if primary_frames == replica_frames && !restarted {
+            return Ok(0);
+        }
+        self.replica.wal_insert_begin(&header)?;
+        let result = self.copy_frames(replica_frames + 1..=primary_frames);
+        self.replica.wal_insert_end()?;
+        result?;
+        Ok(self.replica.wal_frame_count()? - replica_frames)
+    }
+
+    fn copy_frames(&self, frames: std::ops::RangeInclusive<u64>) -> Result<()> {
+        for frame_no in frames {
+            let frame = read_frame(&self.primary, frame_no)?;
+            self.replica.wal_insert_frame(frame_no, &frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Replicator {
+    fn drop(&mut self) {
+        self.replica._db.replica.store(false, Ordering::SeqCst);
+    }
+}
+
+fn read_frame(conn: &Arc<Connection>, frame_no: u64) -> Result<Vec<u8>> {
+    let frame_len = WAL_FRAME_HEADER_SIZE + conn._db.page_size as usize;
+    let mut frame = vec![0; frame_len];
+    let c = conn.wal_get_frame(frame_no as u32, frame.as_mut_ptr(), frame_len as u32)?;
+    conn.pager.io.wait_for_completion(c)?;
+    Ok(frame)
+}
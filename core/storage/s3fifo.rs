@@ -0,0 +1,689 @@
+//! S3-FIFO page cache.
+//!
+//! S3-FIFO ("FIFO queues are all you need for cache eviction", SOSP '23) keeps pages in two FIFO
+//! queues: a small one that receives new pages and a main one that holds the working set. A page
+//! that is used again while in the small queue moves to the main queue when it reaches the head
+//! of the small queue; the others are evicted. Pages read only once, as in a table scan, thus
+//! leave through the small queue without evicting the working set. A ghost queue remembers the
+//! keys of pages recently evicted from the small queue, so that a page read again soon after its
+//! eviction is inserted directly in the main queue.
+//!
+//! Hits only increment a small per-page counter, which the main queue uses as a second chance
+//! when a page reaches its head.
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::Arc;
+
+use tracing::{debug, trace};
+
+use super::page_cache::{
+    CacheError, CacheResizeResult, MemoryLimit, PageCache, PageCacheKey, PageCacheStats,
+    DEFAULT_PAGE_CACHE_SIZE_IN_PAGES,
+};
+use super::pager::PageRef;
+
+/// Maximum value of the access counter of a page.
+const MAX_FREQUENCY: u8 = 3;
+/// Share of the capacity used by the small queue, in percent.
+const SMALL_QUEUE_PERCENT: usize = 10;
+
+/// Page numbers need no protection against collision attacks, so they are hashed with a
+/// multiplication instead of SipHash.
+#[derive(Default)]
+struct PageNumberHasher(u64);
+
+impl Hasher for PageNumberHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u64(self.0.rotate_left(8) ^ *byte as u64);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+}
+
+type PageMap<V> = HashMap<PageCacheKey, V, BuildHasherDefault<PageNumberHasher>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Queue {
+    Small,
+    Main,
+}
+
+struct Entry {
+    page: PageRef,
+    freq: u8,
+    queue: Queue,
+    /// Identifies the queue item of the entry. Items left behind by deleted entries have an
+    /// outdated generation and are skipped.
+    generation: u64,
+}
+
+pub struct S3FifoPageCache {
+    capacity: usize,
+    memory_limit: Option<MemoryLimit>,
+    entries: PageMap<Entry>,
+    /// Oldest items are at the front of the queues.
+    small: VecDeque<(PageCacheKey, u64)>,
+    main: VecDeque<(PageCacheKey, u64)>,
+    /// Number of entries in the small queue, not counting stale items.
+    small_len: usize,
+    ghost: VecDeque<(PageCacheKey, u64)>,
+    ghost_keys: PageMap<u64>,
+    next_generation: u64,
+    stats: PageCacheStats,
+}
+unsafe impl Send for S3FifoPageCache {}
+unsafe impl Sync for S3FifoPageCache {}
+
+impl S3FifoPageCache {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity of cache should be at least 1");
+        Self {
+            capacity,
+            memory_limit: None,
+            entries: PageMap::default(),
+            small: VecDeque::new(),
+            main: VecDeque::new(),
+            small_len: 0,
+            ghost: VecDeque::new(),
+            ghost_keys: PageMap::default(),
+            next_generation: 0,
+            stats: PageCacheStats::default(),
+        }
+    }
+
+    fn _insert(
+        &mut self,
+        key: PageCacheKey,
+        value: PageRef,
+        ignore_exists: bool,
+    ) -> Result<(), CacheError> {
+        trace!("insert(key={:?})", key);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            if !ignore_exists {
+                assert!(
+                    Arc::ptr_eq(&value, &entry.page),
+                    "Attempted to insert different page with same key"
+                );
+                return Err(CacheError::KeyExists);
+            }
+            entry.page = value;
+            return Ok(());
+        }
+        self.make_room_for(1)?;
+        let queue = match self.ghost_keys.remove(&key) {
+            Some(_) => Queue::Main,
+            None => Queue::Small,
+        };
+        let generation = self.push(queue, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                page: value,
+                freq: 0,
+                queue,
+                generation,
+            },
+        );
+        Ok(())
+    }
+
+    fn make_room_for(&mut self, n: usize) -> Result<(), CacheError> {
+        let capacity = PageCache::capacity(self);
+        if n > capacity {
+            return Err(CacheError::Full);
+        }
+        while self.entries.len() + n > capacity {
+            if !self.evict_one() {
+                return Err(CacheError::Full);
+            }
+        }
+        Ok(())
+    }
+
+    fn small_target(&self) -> usize {
+        (PageCache::capacity(self) * SMALL_QUEUE_PERCENT / 100).max(1)
+    }
+
+    fn ghost_capacity(&self) -> usize {
+        PageCache::capacity(self)
+            .saturating_sub(self.small_target())
+            .max(1)
+    }
+
+    /// Evicts a page, moving pages between queues as needed. Returns false if every page is
+    /// locked or dirty.
+    fn evict_one(&mut self) -> bool {
+        // A page is moved at most MAX_FREQUENCY + 1 times before reaching a point where it is
+        // evicted, so running out of budget means nothing can be evicted.
+        let mut budget = (self.small.len() + self.main.len()) * (MAX_FREQUENCY as usize + 2);
+        while budget > 0 {
+            budget -= 1;
+            let from_small = self.small_len > 0
+                && (self.small_len >= self.small_target() || self.main_len() == 0);
+            let queue = if from_small {
+                Queue::Small
+            } else {
+                Queue::Main
+            };
+            let Some(key) = self.pop(queue) else {
+                return false;
+            };
+            let entry = self.entries.get_mut(&key).unwrap();
+            let evictable = !entry.page.is_locked() && !entry.page.is_dirty();
+            match queue {
+                Queue::Small if entry.freq > 0 || !evictable => {
+                    // Used again since it was inserted, or pinned: keep it in the main queue.
+                    entry.freq = 0;
+                    self.requeue(key, Queue::Main);
+                }
+                Queue::Main if entry.freq > 0 => {
+                    entry.freq -= 1;
+                    self.requeue(key, Queue::Main);
+                }
+                Queue::Main if !evictable => self.requeue(key, Queue::Main),
+                _ => {
+                    let entry = self.entries.remove(&key).unwrap();
+                    clean_page(&entry.page);
+                    if queue == Queue::Small {
+                        self.remember(key);
+                    }
+                    self.stats.evictions += 1;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn main_len(&self) -> usize {
+        self.entries.len() - self.small_len
+    }
+
+    /// Appends `key` to `queue` and returns the generation of the new item.
+    fn push(&mut self, queue: Queue, key: PageCacheKey) -> u64 {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        match queue {
+            Queue::Small => {
+                self.small.push_back((key, generation));
+                self.small_len += 1;
+            }
+            Queue::Main => self.main.push_back((key, generation)),
+        }
+        generation
+    }
+
+    /// Moves an entry that was just popped to the back of `queue`.
+    fn requeue(&mut self, key: PageCacheKey, queue: Queue) {
+        let generation = self.push(queue, key.clone());
+        let entry = self.entries.get_mut(&key).unwrap();
+        entry.queue = queue;
+        entry.generation = generation;
+    }
+
+    /// Removes the oldest entry of `queue`, skipping stale items.
+    fn pop(&mut self, queue: Queue) -> Option<PageCacheKey> {
+        loop {
+            let (key, generation) = match queue {
+                Queue::Small => self.small.pop_front()?,
+                Queue::Main => self.main.pop_front()?,
+            };
+            match self.entries.get(&key) {
+                Some(entry) if entry.generation == generation => {
+                    if queue == Queue::Small {
+                        self.small_len -= 1;
+                    }
+                    return Some(key);
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Adds `key` to the ghost queue.
+    fn remember(&mut self, key: PageCacheKey) {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.ghost_keys.insert(key.clone(), generation);
+        self.ghost.push_back((key, generation));
+        let capacity = self.ghost_capacity();
+        while self.ghost_keys.len() > capacity {
+            let Some((key, generation)) = self.ghost.pop_front() else {
+                break;
+            };
+            if self.ghost_keys.get(&key) == Some(&generation) {
+                self.ghost_keys.remove(&key);
+            }
+        }
+        if self.ghost.len() > 2 * self.ghost_keys.len() + 32 {
+            let ghost_keys = &self.ghost_keys;
+            self.ghost
+                .retain(|(key, generation)| ghost_keys.get(key) == Some(generation));
+        }
+    }
+
+    /// Drops the items left behind by deleted entries once they outnumber the live ones.
+    fn compact(&mut self) {
+        let entries = &self.entries;
+        let is_live = |(key, generation): &(PageCacheKey, u64)| {
+            entries
+                .get(key)
+                .is_some_and(|entry| entry.generation == *generation)
+        };
+        if self.small.len() > 2 * self.small_len + 32 {
+            self.small.retain(is_live);
+        }
+        if self.main.len() > 2 * (entries.len() - self.small_len) + 32 {
+            self.main.retain(is_live);
+        }
+    }
+
+    #[cfg(test)]
+    fn in_main_queue(&self, key: &PageCacheKey) -> bool {
+        self.entries
+            .get(key)
+            .is_some_and(|entry| entry.queue == Queue::Main)
+    }
+}
+
+impl PageCache for S3FifoPageCache {
+    fn contains_key(&mut self, key: &PageCacheKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn insert(&mut self, key: PageCacheKey, value: PageRef) -> Result<(), CacheError> {
+        self._insert(key, value, false)
+    }
+
+    fn insert_ignore_existing(
+        &mut self,
+        key: PageCacheKey,
+        value: PageRef,
+    ) -> Result<(), CacheError> {
+        self._insert(key, value, true)
+    }
+
+    fn delete(&mut self, key: PageCacheKey) -> Result<(), CacheError> {
+        trace!("cache_delete(key={:?})", key);
+        let Some(entry) = self.entries.get(&key) else {
+            return Ok(());
+        };
+        if entry.page.is_locked() {
+            return Err(CacheError::Locked);
+        }
+        if entry.page.is_dirty() {
+            return Err(CacheError::Dirty);
+        }
+        let entry = self.entries.remove(&key).unwrap();
+        clean_page(&entry.page);
+        if entry.queue == Queue::Small {
+            self.small_len -= 1;
+        }
+        self.compact();
+        Ok(())
+    }
+
+    fn get(&mut self, key: &PageCacheKey) -> Option<PageRef> {
+        let page = self.peek(key, true);
+        match page {
+            Some(_) => self.stats.hits += 1,
+            None => self.stats.misses += 1,
+        }
+        page
+    }
+
+    fn peek(&mut self, key: &PageCacheKey, touch: bool) -> Option<PageRef> {
+        trace!("cache_get(key={:?})", key);
+        let entry = self.entries.get_mut(key)?;
+        if touch {
+            entry.freq = (entry.freq + 1).min(MAX_FREQUENCY);
+        }
+        Some(entry.page.clone())
+    }
+
+    // To match SQLite behavior, just set capacity and try to shrink as much as possible.
+    // In case of failure, the caller should request further evictions (e.g. after I/O).
+    fn resize(&mut self, capacity: usize) -> CacheResizeResult {
+        self.capacity = capacity;
+        match self.make_room_for(0) {
+            Ok(_) => CacheResizeResult::Done,
+            Err(_) => CacheResizeResult::PendingEvictions,
+        }
+    }
+
+    fn set_max_bytes(&mut self, max_bytes: Option<usize>, page_size: usize) -> CacheResizeResult {
+        self.memory_limit = max_bytes.map(|max_bytes| MemoryLimit {
+            max_bytes,
+            page_size,
+        });
+        match self.make_room_for(0) {
+            Ok(_) => CacheResizeResult::Done,
+            Err(_) => CacheResizeResult::PendingEvictions,
+        }
+    }
+
+    fn clear(&mut self) -> Result<(), CacheError> {
+        for entry in self.entries.values() {
+            if entry.page.is_locked() {
+                return Err(CacheError::Locked);
+            }
+            if entry.page.is_dirty() {
+                return Err(CacheError::Dirty);
+            }
+        }
+        // The cache is cleared after every commit: remember the working set so that it goes
+        // back to the main queue when it is read again.
+        let entries = std::mem::take(&mut self.entries);
+        for (key, entry) in entries {
+            clean_page(&entry.page);
+            if entry.queue == Queue::Main {
+                self.remember(key);
+            }
+        }
+        self.small.clear();
+        self.main.clear();
+        self.small_len = 0;
+        Ok(())
+    }
+
+    fn evict(&mut self, n: usize) -> usize {
+        let mut evicted = 0;
+        while evicted < n && self.evict_one() {
+            evicted += 1;
+        }
+        evicted
+    }
+
+    fn unset_dirty_all_pages(&mut self) {
+        for entry in self.entries.values() {
+            entry.page.clear_dirty();
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn capacity(&self) -> usize {
+        MemoryLimit::max_pages(self.memory_limit, self.capacity)
+    }
+
+    fn stats(&self) -> PageCacheStats {
+        self.stats
+    }
+}
+
+impl Default for S3FifoPageCache {
+    fn default() -> Self {
+        S3FifoPageCache::new(DEFAULT_PAGE_CACHE_SIZE_IN_PAGES)
+    }
+}
+
+fn clean_page(page: &PageRef) {
+    page.clear_loaded();
+    debug!("cleaning up page {}", page.get().id);
+    let _ = page.get().contents.take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{Buffer, BufferData};
+    use crate::storage::pager::Page;
+    use crate::storage::sqlite3_ondisk::PageContent;
+    use rand_chacha::{
+        rand_core::{RngCore, SeedableRng},
+        ChaCha8Rng,
+    };
+    use std::cell::RefCell;
+    use std::pin::Pin;
+    use std::rc::Rc;
+
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn page_with_content(page_id: usize) -> PageRef {
+        let page = Arc::new(Page::new(page_id));
+        {
+            let buffer_drop_fn = Rc::new(|_data: BufferData| {});
+            let buffer = Buffer::new(Pin::new(vec![0; 4096]), buffer_drop_fn);
+            let page_content = PageContent {
+                offset: 0,
+                buffer: Arc::new(RefCell::new(buffer)),
+                overflow_cells: Vec::new(),
+            };
+            page.get().contents = Some(page_content);
+            page.set_loaded();
+        }
+        page
+    }
+
+    fn insert_page(cache: &mut S3FifoPageCache, id: usize) -> PageCacheKey {
+        let key = PageCacheKey::new(id);
+        assert!(cache.insert(key.clone(), page_with_content(id)).is_ok());
+        key
+    }
+
+    #[test]
+    fn test_s3fifo_insert_and_get() {
+        let mut cache = S3FifoPageCache::new(2);
+        let key1 = insert_page(&mut cache, 1);
+        let key2 = insert_page(&mut cache, 2);
+        assert_eq!(cache.get(&key1).unwrap().get().id, 1);
+        assert_eq!(cache.get(&key2).unwrap().get().id, 2);
+        assert!(cache.get(&PageCacheKey::new(3)).is_none());
+        assert_eq!(
+            cache.stats(),
+            PageCacheStats {
+                hits: 2,
+                misses: 1,
+                evictions: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_s3fifo_insert_existing_key_fail() {
+        let mut cache = S3FifoPageCache::new(2);
+        let key = insert_page(&mut cache, 1);
+        let page = cache.peek(&key, false).unwrap();
+        assert_eq!(cache.insert(key.clone(), page), Err(CacheError::KeyExists));
+        let other = page_with_content(1);
+        assert!(cache
+            .insert_ignore_existing(key.clone(), other.clone())
+            .is_ok());
+        assert!(Arc::ptr_eq(&cache.peek(&key, false).unwrap(), &other));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_s3fifo_evict_unused_first() {
+        let mut cache = S3FifoPageCache::new(3);
+        let key1 = insert_page(&mut cache, 1);
+        let key2 = insert_page(&mut cache, 2);
+        let key3 = insert_page(&mut cache, 3);
+        assert!(cache.get(&key1).is_some());
+        let page2 = cache.peek(&key2, false).unwrap();
+        insert_page(&mut cache, 4);
+        assert_eq!(cache.len(), 3);
+        assert!(cache.contains_key(&key1));
+        assert!(!cache.contains_key(&key2));
+        assert!(cache.contains_key(&key3));
+        assert!(!page2.is_loaded());
+        assert!(page2.get().contents.is_none());
+        assert!(cache.in_main_queue(&key1));
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_s3fifo_scan_resistance() {
+        let mut cache = S3FifoPageCache::new(100);
+        let hot: Vec<_> = (0..50).map(|id| insert_page(&mut cache, id)).collect();
+        for key in &hot {
+            assert!(cache.get(key).is_some());
+        }
+        // Each page of the scan is read once and must not push the hot pages out.
+        for id in 1000..5000 {
+            insert_page(&mut cache, id);
+            if id % 10 == 0 {
+                for key in &hot {
+                    assert!(cache.get(key).is_some(), "hot page {:?} was evicted", key);
+                }
+            }
+        }
+        assert_eq!(cache.len(), 100);
+    }
+
+    #[test]
+    fn test_s3fifo_ghost_goes_to_main() {
+        let mut cache = S3FifoPageCache::new(10);
+        let key = insert_page(&mut cache, 1);
+        for id in 2..=11 {
+            insert_page(&mut cache, id);
+        }
+        assert!(!cache.contains_key(&key));
+        insert_page(&mut cache, 1);
+        assert!(cache.in_main_queue(&key));
+    }
+
+    #[test]
+    fn test_s3fifo_clear_remembers_main_queue() {
+        let mut cache = S3FifoPageCache::new(10);
+        let key1 = insert_page(&mut cache, 1);
+        let key2 = insert_page(&mut cache, 2);
+        cache.get(&key1);
+        for id in 3..=11 {
+            insert_page(&mut cache, id);
+        }
+        assert!(cache.in_main_queue(&key1));
+        let page1 = cache.peek(&key1, false).unwrap();
+        assert!(cache.clear().is_ok());
+        assert_eq!(cache.len(), 0);
+        assert!(!page1.is_loaded());
+        insert_page(&mut cache, 1);
+        insert_page(&mut cache, 12);
+        assert!(cache.in_main_queue(&key1));
+        assert!(!cache.in_main_queue(&PageCacheKey::new(12)));
+        assert!(!cache.contains_key(&key2));
+    }
+
+    #[test]
+    fn test_s3fifo_skip_locked_and_dirty_pages() {
+        let mut cache = S3FifoPageCache::new(2);
+        let key1 = insert_page(&mut cache, 1);
+        let key2 = insert_page(&mut cache, 2);
+        cache.peek(&key1, false).unwrap().set_locked();
+        cache.peek(&key2, false).unwrap().set_dirty();
+        let key3 = PageCacheKey::new(3);
+        assert_eq!(
+            cache.insert(key3.clone(), page_with_content(3)),
+            Err(CacheError::Full)
+        );
+        assert_eq!(cache.delete(key1.clone()), Err(CacheError::Locked));
+        assert_eq!(cache.delete(key2.clone()), Err(CacheError::Dirty));
+        assert!(cache.clear().is_err());
+
+        cache.peek(&key1, false).unwrap().clear_locked();
+        insert_page(&mut cache, 3);
+        assert!(!cache.contains_key(&key1));
+        assert!(cache.contains_key(&key2));
+        assert!(cache.contains_key(&key3));
+        cache.unset_dirty_all_pages();
+        assert!(cache.clear().is_ok());
+    }
+
+    #[test]
+    fn test_s3fifo_resize() {
+        let mut cache = S3FifoPageCache::new(5);
+        for id in 1..=5 {
+            insert_page(&mut cache, id);
+        }
+        assert_eq!(cache.resize(3), CacheResizeResult::Done);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.capacity(), 3);
+        let key = insert_page(&mut cache, 6);
+        assert_eq!(cache.len(), 3);
+        cache.peek(&key, false).unwrap().set_dirty();
+        for id in 7..=8 {
+            let key = insert_page(&mut cache, id);
+            cache.peek(&key, false).unwrap().set_dirty();
+        }
+        assert_eq!(cache.resize(1), CacheResizeResult::PendingEvictions);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_s3fifo_memory_limit() {
+        let mut cache = S3FifoPageCache::new(100);
+        for id in 0..50 {
+            insert_page(&mut cache, id);
+        }
+        assert_eq!(
+            cache.set_max_bytes(Some(10 * 4096), 4096),
+            CacheResizeResult::Done
+        );
+        assert_eq!(cache.capacity(), 10);
+        assert_eq!(cache.len(), 10);
+        for id in 50..100 {
+            insert_page(&mut cache, id);
+            assert!(cache.len() <= 10);
+        }
+        assert_eq!(cache.set_max_bytes(None, 4096), CacheResizeResult::Done);
+        assert_eq!(cache.capacity(), 100);
+    }
+
+    #[test]
+    fn test_s3fifo_fuzz() {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        tracing::info!("super seed: {}", seed);
+        let capacity = 10;
+        let max_pages = 40;
+        let mut cache = S3FifoPageCache::new(capacity);
+        for _ in 0..20000 {
+            let key = PageCacheKey::new((rng.next_u64() % max_pages) as usize);
+            match rng.next_u64() % 4 {
+                0 | 1 => {
+                    if cache.peek(&key, false).is_none() {
+                        let id = (rng.next_u64() % max_pages) as usize;
+                        assert!(cache.insert(key.clone(), page_with_content(id)).is_ok());
+                        assert!(cache.contains_key(&key));
+                    }
+                }
+                2 => {
+                    cache.get(&key);
+                }
+                3 => {
+                    assert!(cache.delete(key.clone()).is_ok());
+                    assert!(!cache.contains_key(&key));
+                }
+                _ => unreachable!(),
+            }
+            assert!(cache.len() <= capacity);
+            let small_len = cache
+                .entries
+                .values()
+                .filter(|entry| entry.queue == Queue::Small)
+                .count();
+            assert_eq!(small_len, cache.small_len);
+            assert!(cache.small.len() + cache.main.len() <= 2 * capacity + 64);
+            assert!(cache.ghost_keys.len() <= cache.ghost_capacity());
+            for entry in cache.entries.values() {
+                assert!(entry.page.is_loaded());
+            }
+        }
+    }
+}
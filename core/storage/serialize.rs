@@ -0,0 +1,70 @@
+//! Conversion of a database to and from a contiguous in-memory image.
+//!
+//! The image has the same layout as a database file: page `n` is stored at offset
+//! `(n - 1) * page_size`. Pages that only live in the WAL are included, so the image
+//! always reflects the last committed state of the database.
+use crate::result::LimboResult;
+use crate::storage::backup::read_page_sync;
+use crate::storage::pager::Pager;
+use crate::storage::sqlite3_ondisk::{DATABASE_HEADER_PAGE_ID, DATABASE_HEADER_SIZE};
+use crate::{LimboError, Result};
+
+/// Number of pages read before the page cache is cleared.
+const CACHE_CLEAR_BATCH_SIZE: usize = 256;
+
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// Reads every page of the database behind `pager` into a single buffer.
+pub(crate) fn serialize(pager: &Pager) -> Result<Vec<u8>> {
+    if let LimboResult::Busy = pager.begin_read_tx()? {
+        return Err(LimboError::Busy);
+    }
+    let result = read_all_pages(pager);
+    pager.end_read_tx()?;
+    pager.clear_page_cache();
+    result
+}
+
+fn read_all_pages(pager: &Pager) -> Result<Vec<u8>> {
+    let header_page = read_page_sync(pager, DATABASE_HEADER_PAGE_ID)?;
+    let buf = header_page.get_contents().as_ptr();
+    let page_size = page_size_from_header(buf)?;
+    let page_count = u32::from_be_bytes([buf[28], buf[29], buf[30], buf[31]]).max(1) as usize;
+
+    let mut image = Vec::with_capacity(page_count * page_size);
+    for page_idx in 1..=page_count {
+        let page = read_page_sync(pager, page_idx)?;
+        image.extend_from_slice(page.get_contents().as_ptr());
+        if page_idx % CACHE_CLEAR_BATCH_SIZE == 0 {
+            pager.clear_page_cache();
+        }
+    }
+    Ok(image)
+}
+
+/// Checks that `data` looks like a complete database image.
+pub(crate) fn validate_image(data: &[u8]) -> Result<()> {
+    if data.len() < DATABASE_HEADER_SIZE || &data[0..16] != SQLITE_MAGIC {
+        return Err(LimboError::NotADB);
+    }
+    let page_size = page_size_from_header(data)?;
+    if data.len() % page_size != 0 {
+        return Err(LimboError::Corrupt(format!(
+            "database image of {} bytes is not a multiple of the page size {}",
+            data.len(),
+            page_size
+        )));
+    }
+    Ok(())
+}
+
+fn page_size_from_header(buf: &[u8]) -> Result<usize> {
+    let page_size = match u16::from_be_bytes([buf[16], buf[17]]) {
+        1 => 65536,
+        n => n as usize,
+    };
+    if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+        return Err(LimboError::NotADB);
+    }
+    Ok(page_size)
+}
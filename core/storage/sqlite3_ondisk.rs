@@ -50,6 +50,7 @@ use crate::fast_lock::SpinLock;
 use crate::io::{Buffer, Complete, Completion, ReadCompletion, SyncCompletion, WriteCompletion};
 use crate::storage::buffer_pool::BufferPool;
 use crate::storage::database::DatabaseStorage;
+use crate::storage::encryption::{is_encrypted_header, Encryption};
 use crate::storage::pager::Pager;
 use crate::types::{
     ImmutableRecord, RawSlice, RefValue, SerialType, SerialTypeKind, TextRef, TextSubtype,
@@ -266,6 +267,21 @@ impl Default for DatabaseHeader {
 }
 
 impl DatabaseHeader {
+    pub(crate) fn is_encrypted(&self) -> bool {
+        is_encrypted_header(&self.magic)
+    }
+
+    /// Resets the fields of an encrypted header that were read as ciphertext. Only the page
+    /// size and the reserved space are stored in plaintext.
+    pub(crate) fn clear_encrypted_fields(&mut self) {
+        *self = DatabaseHeader {
+            magic: self.magic,
+            page_size: self.page_size,
+            reserved_space: self.reserved_space,
+            ..DatabaseHeader::default()
+        };
+    }
+
     pub fn update_page_size(&mut self, size: u32) {
         if !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&size) || (size & (size - 1) != 0) {
             return;
@@ -310,8 +326,12 @@ fn finish_read_database_header(
     header: Arc<SpinLock<DatabaseHeader>>,
 ) -> Result<()> {
     let buf = buf.borrow();
-    let buf = buf.as_slice();
     let mut header = header.lock();
+    read_header_from_buf(buf.as_slice(), &mut header);
+    Ok(())
+}
+
+pub fn read_header_from_buf(buf: &[u8], header: &mut DatabaseHeader) {
     header.magic.copy_from_slice(&buf[0..16]);
     header.page_size = u16::from_be_bytes([buf[16], buf[17]]);
     header.write_version = buf[18];
@@ -338,7 +358,6 @@ fn finish_read_database_header(
     header.reserved_for_expansion.copy_from_slice(&buf[72..92]);
     header.version_valid_for = u32::from_be_bytes([buf[92], buf[93], buf[94], buf[95]]);
     header.version_number = u32::from_be_bytes([buf[96], buf[97], buf[98], buf[99]]);
-    Ok(())
 }
 
 pub fn write_header_to_buf(buf: &mut [u8], header: &DatabaseHeader) {
@@ -781,6 +800,7 @@ impl PageContent {
 
 pub fn begin_read_page(
     db_file: Arc<dyn DatabaseStorage>,
+    encryption: Arc<Encryption>,
     buffer_pool: Rc<BufferPool>,
     page: PageRef,
     page_idx: usize,
@@ -795,8 +815,13 @@ pub fn begin_read_page(
     let buf = Arc::new(RefCell::new(Buffer::new(buf, drop_fn)));
     let complete = Box::new(move |buf: Arc<RefCell<Buffer>>| {
         let page = page.clone();
-        if finish_read_page(page_idx, buf, page.clone()).is_err() {
+        let decrypted = encryption.decrypt_page(page_idx, buf.borrow_mut().as_mut_slice());
+        if decrypted
+            .and_then(|_| finish_read_page(page_idx, buf, page.clone()))
+            .is_err()
+        {
             page.set_error();
+            page.clear_locked();
         }
     });
     let c = Completion::Read(ReadCompletion::new(buf, complete));
@@ -841,6 +866,17 @@ pub fn begin_write_btree_page(
         let contents = page.contents.as_ref().unwrap();
         contents.buffer.clone()
     };
+    let encrypted = pager
+        .encryption
+        .encrypt_page(page_id, buffer.borrow().as_slice())?;
+    let buffer = match encrypted {
+        Some(encrypted) => {
+            let drop_fn = Rc::new(|_buf| {});
+            #[allow(clippy::arc_with_non_send_sync)]
+            Arc::new(RefCell::new(Buffer::new(Pin::new(encrypted), drop_fn)))
+        }
+        None => buffer,
+    };
 
     *write_counter.borrow_mut() += 1;
     let write_complete = {
@@ -1429,6 +1465,10 @@ pub fn read_entire_wal_dumb(file: &Arc<dyn File>) -> Result<Arc<UnsafeCell<WalFi
 
         let mut current_offset = WAL_HEADER_SIZE;
         let mut frame_idx = 1_u64;
+        // Like in SQLite, only the frames up to the last commit frame are part of the WAL.
+        let mut max_frame = 0;
+        let mut last_checksum = cumulative_checksum;
+        let mut uncommitted_frames = Vec::new();
 
         let wfs_data = unsafe { &mut *wal_file_shared_for_completion.get() };
 
@@ -1440,7 +1480,7 @@ pub fn read_entire_wal_dumb(file: &Arc<dyn File>) -> Result<Arc<UnsafeCell<WalFi
 
             let frame_h_page_number =
                 u32::from_be_bytes(frame_header_slice[0..4].try_into().unwrap());
-            let _frame_h_db_size = u32::from_be_bytes(frame_header_slice[4..8].try_into().unwrap());
+            let frame_h_db_size = u32::from_be_bytes(frame_header_slice[4..8].try_into().unwrap());
             let frame_h_salt_1 = u32::from_be_bytes(frame_header_slice[8..12].try_into().unwrap());
             let frame_h_salt_2 = u32::from_be_bytes(frame_header_slice[12..16].try_into().unwrap());
             let frame_h_checksum_1 =
@@ -1473,37 +1513,40 @@ pub fn read_entire_wal_dumb(file: &Arc<dyn File>) -> Result<Arc<UnsafeCell<WalFi
                 use_native_endian_checksum,
             );
 
+            // The frames of a transaction that was never committed may be followed by frames
+            // chained to other frames that were written over them.
             if calculated_frame_checksum != (frame_h_checksum_1, frame_h_checksum_2) {
-                panic!(
-                    "WAL frame checksum mismatch. Expected ({}, {}), Got ({}, {})",
+                tracing::trace!(
+                    "WAL frame {} checksum mismatch: expected ({}, {}), got ({}, {}), ignoring frame",
+                    frame_idx,
                     frame_h_checksum_1,
                     frame_h_checksum_2,
                     calculated_frame_checksum.0,
                     calculated_frame_checksum.1
                 );
+                break;
             }
 
             cumulative_checksum = calculated_frame_checksum;
-
-            wfs_data
-                .frame_cache
-                .lock()
-                .entry(frame_h_page_number as u64)
-                .or_default()
-                .push(frame_idx);
-            wfs_data
-                .pages_in_frames
-                .lock()
-                .push(frame_h_page_number as u64);
+            uncommitted_frames.push((frame_h_page_number as u64, frame_idx));
+
+            if frame_h_db_size != 0 {
+                let mut frame_cache = wfs_data.frame_cache.lock();
+                let mut pages_in_frames = wfs_data.pages_in_frames.lock();
+                for (page_id, frame_id) in uncommitted_frames.drain(..) {
+                    frame_cache.entry(page_id).or_default().push(frame_id);
+                    pages_in_frames.push(page_id);
+                }
+                max_frame = frame_idx;
+                last_checksum = cumulative_checksum;
+            }
 
             frame_idx += 1;
             current_offset += WAL_FRAME_HEADER_SIZE + page_size;
         }
 
-        wfs_data
-            .max_frame
-            .store(frame_idx.saturating_sub(1), Ordering::SeqCst);
-        wfs_data.last_checksum = cumulative_checksum;
+        wfs_data.max_frame.store(max_frame, Ordering::SeqCst);
+        wfs_data.last_checksum = last_checksum;
         wfs_data.loaded.store(true, Ordering::SeqCst);
     });
     let c = Completion::Read(ReadCompletion::new(buf_for_pread, complete));
@@ -1531,7 +1574,8 @@ pub fn begin_read_wal_frame(
     Ok(c)
 }
 
-#[instrument(skip(io, page, write_counter, wal_header, checksums), level = Level::TRACE)]
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(io, page, write_counter, wal_header, checksums, encryption), level = Level::TRACE)]
 pub fn begin_write_wal_frame(
     io: &Arc<dyn File>,
     offset: usize,
@@ -1541,6 +1585,7 @@ pub fn begin_write_wal_frame(
     write_counter: Rc<RefCell<usize>>,
     wal_header: &WalHeader,
     checksums: (u32, u32),
+    encryption: &Encryption,
 ) -> Result<(u32, u32)> {
     let page_finish = page.clone();
     let page_id = page.get().id;
@@ -1570,6 +1615,8 @@ pub fn begin_write_wal_frame(
         buf[12..16].copy_from_slice(&header.salt_2.to_be_bytes());
 
         let contents_buf = contents.as_ptr();
+        let encrypted = encryption.encrypt_page(page_id, contents_buf)?;
+        let contents_buf = encrypted.as_deref().unwrap_or(&*contents_buf);
         let content_len = contents_buf.len();
         buf[WAL_FRAME_HEADER_SIZE..WAL_FRAME_HEADER_SIZE + content_len]
             .copy_from_slice(contents_buf);
@@ -1618,21 +1665,40 @@ pub fn begin_write_wal_frame(
     Ok(checksums)
 }
 
-pub fn begin_write_wal_header(io: &Arc<dyn File>, header: &WalHeader) -> Result<()> {
+/// Writes a frame copied from another WAL, header included, as is.
+pub fn begin_write_wal_frame_raw(
+    io: &Arc<dyn File>,
+    offset: usize,
+    frame: &[u8],
+    write_counter: Rc<RefCell<usize>>,
+) -> Result<()> {
+    let drop_fn = Rc::new(|_buf| {});
+    let mut buffer = Buffer::allocate(frame.len(), drop_fn);
+    buffer.as_mut_slice().copy_from_slice(frame);
+    #[allow(clippy::arc_with_non_send_sync)]
+    let buffer = Arc::new(RefCell::new(buffer));
+
+    *write_counter.borrow_mut() += 1;
+    let frame_len = frame.len();
+    let write_complete = Box::new(move |bytes_written: i32| {
+        *write_counter.borrow_mut() -= 1;
+        if bytes_written < frame_len as i32 {
+            tracing::error!("wrote({bytes_written}) less than expected({frame_len})");
+        }
+    });
+    #[allow(clippy::arc_with_non_send_sync)]
+    let c = Arc::new(Completion::Write(WriteCompletion::new(write_complete)));
+    io.pwrite(offset, buffer, c)?;
+    Ok(())
+}
+
+pub fn begin_write_wal_header(io: &Arc<dyn File>, header: &WalHeader) -> Result<Arc<Completion>> {
     let buffer = {
         let drop_fn = Rc::new(|_buf| {});
 
         let mut buffer = Buffer::allocate(512, drop_fn);
         let buf = buffer.as_mut_slice();
-
-        buf[0..4].copy_from_slice(&header.magic.to_be_bytes());
-        buf[4..8].copy_from_slice(&header.file_format.to_be_bytes());
-        buf[8..12].copy_from_slice(&header.page_size.to_be_bytes());
-        buf[12..16].copy_from_slice(&header.checkpoint_seq.to_be_bytes());
-        buf[16..20].copy_from_slice(&header.salt_1.to_be_bytes());
-        buf[20..24].copy_from_slice(&header.salt_2.to_be_bytes());
-        buf[24..28].copy_from_slice(&header.checksum_1.to_be_bytes());
-        buf[28..32].copy_from_slice(&header.checksum_2.to_be_bytes());
+        buf[..WAL_HEADER_SIZE].copy_from_slice(&header.to_be_bytes());
 
         #[allow(clippy::arc_with_non_send_sync)]
         Arc::new(RefCell::new(buffer))
@@ -1649,8 +1715,8 @@ pub fn begin_write_wal_header(io: &Arc<dyn File>, header: &WalHeader) -> Result<
     };
     #[allow(clippy::arc_with_non_send_sync)]
     let c = Arc::new(Completion::Write(WriteCompletion::new(write_complete)));
-    io.pwrite(0, buffer.clone(), c)?;
-    Ok(())
+    io.pwrite(0, buffer.clone(), c.clone())?;
+    Ok(c)
 }
 
 /// Checks if payload will overflow a cell based on the maximum allowed size.
@@ -1727,6 +1793,56 @@ impl WalHeader {
     pub fn as_bytes(&self) -> &[u8] {
         unsafe { std::mem::transmute::<&WalHeader, &[u8; size_of::<WalHeader>()]>(self) }
     }
+
+    /// Encodes the header as it is stored on disk.
+    pub fn to_be_bytes(self) -> [u8; WAL_HEADER_SIZE] {
+        let mut buf = [0; WAL_HEADER_SIZE];
+        buf[0..4].copy_from_slice(&self.magic.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.file_format.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.page_size.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.checkpoint_seq.to_be_bytes());
+        buf[16..20].copy_from_slice(&self.salt_1.to_be_bytes());
+        buf[20..24].copy_from_slice(&self.salt_2.to_be_bytes());
+        buf[24..28].copy_from_slice(&self.checksum_1.to_be_bytes());
+        buf[28..32].copy_from_slice(&self.checksum_2.to_be_bytes());
+        buf
+    }
+
+    /// Decodes a header stored on disk, checking its magic number and checksum.
+    pub fn from_be_bytes(buf: &[u8]) -> Result<Self> {
+        if buf.len() != WAL_HEADER_SIZE {
+            return Err(LimboError::Corrupt(format!(
+                "WAL header must be {} bytes, got {}",
+                WAL_HEADER_SIZE,
+                buf.len()
+            )));
+        }
+        let header = Self {
+            magic: read_u32(buf, 0),
+            file_format: read_u32(buf, 4),
+            page_size: read_u32(buf, 8),
+            checkpoint_seq: read_u32(buf, 12),
+            salt_1: read_u32(buf, 16),
+            salt_2: read_u32(buf, 20),
+            checksum_1: read_u32(buf, 24),
+            checksum_2: read_u32(buf, 28),
+        };
+        if header.magic != WAL_MAGIC_LE && header.magic != WAL_MAGIC_BE {
+            return Err(LimboError::Corrupt("invalid WAL header magic".to_string()));
+        }
+        let checksum = checksum_wal(&buf[0..24], &header, (0, 0), header.native_checksum());
+        if checksum != (header.checksum_1, header.checksum_2) {
+            return Err(LimboError::Corrupt(
+                "WAL header checksum mismatch".to_string(),
+            ));
+        }
+        Ok(header)
+    }
+
+    /// Returns true if the checksums of this WAL interpret the data in native byte order.
+    pub fn native_checksum(&self) -> bool {
+        cfg!(target_endian = "big") == ((self.magic & 1) != 0)
+    }
 }
 
 pub fn read_u32(buf: &[u8], pos: usize) -> u32 {
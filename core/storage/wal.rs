@@ -17,18 +17,19 @@ use std::{
 };
 
 use crate::fast_lock::SpinLock;
-use crate::io::{File, SyncCompletion, IO};
+use crate::io::{File, ReadCompletion, SyncCompletion, IO};
 use crate::result::LimboResult;
 use crate::storage::sqlite3_ondisk::{
-    begin_read_wal_frame, begin_write_wal_frame, finish_read_page, WAL_FRAME_HEADER_SIZE,
-    WAL_HEADER_SIZE,
+    begin_read_wal_frame, begin_write_wal_frame, begin_write_wal_frame_raw, finish_read_page,
+    read_u32, WAL_FRAME_HEADER_SIZE, WAL_HEADER_SIZE,
 };
-use crate::{Buffer, Result};
+use crate::{Buffer, LimboError, Result};
 use crate::{Completion, Page};
 
 use self::sqlite3_ondisk::{checksum_wal, PageContent, WAL_MAGIC_BE, WAL_MAGIC_LE};
 
 use super::buffer_pool::BufferPool;
+use super::encryption::Encryption;
 use super::pager::{PageRef, Pager};
 use super::sqlite3_ondisk::{self, begin_write_btree_page, WalHeader};
 
@@ -210,19 +211,24 @@ pub trait Wal {
     /// Find the latest frame containing a page.
     fn find_frame(&self, page_id: u64) -> Result<Option<u64>>;
 
+    /// Returns the pages written by the frames after `frame_id` that the current read
+    /// transaction sees.
+    fn pages_in_frames_after(&self, frame_id: u64) -> Vec<u64>;
+
     /// Read a frame from the WAL.
     fn read_frame(&self, frame_id: u64, page: PageRef, buffer_pool: Rc<BufferPool>) -> Result<()>;
 
-    /// Read a frame from the WAL.
+    /// Read a frame from the WAL, frame header included.
     fn read_frame_raw(
         &self,
         frame_id: u64,
-        buffer_pool: Rc<BufferPool>,
         frame: *mut u8,
         frame_len: u32,
     ) -> Result<Arc<Completion>>;
 
-    /// Write a frame to the WAL.
+    /// Write a frame to the WAL. A frame with a `db_size` of 0 is not a commit frame: like the
+    /// frames inserted with [Wal::insert_frame_raw], it is not visible to readers until a commit
+    /// frame is appended or [Wal::commit_inserted_frames] is called.
     fn append_frame(
         &mut self,
         page: PageRef,
@@ -241,6 +247,39 @@ pub trait Wal {
     fn get_max_frame_in_wal(&self) -> u64;
     fn get_max_frame(&self) -> u64;
     fn get_min_frame(&self) -> u64;
+
+    /// Returns the WAL header.
+    fn get_header(&self) -> WalHeader;
+
+    /// Replaces the header of a WAL without frames, so that frames copied from another WAL
+    /// with this header can be inserted.
+    fn set_header(&mut self, header: WalHeader) -> Result<Arc<Completion>>;
+
+    /// Writes a frame copied from another WAL with the same header as frame `frame_id`, after
+    /// checking that it is the next frame and that its salts and checksum are valid. The frame
+    /// is not visible to readers until [Wal::commit_inserted_frames] is called.
+    /// Returns the database size stored in the frame, which is only set for commit frames.
+    fn insert_frame_raw(
+        &mut self,
+        frame_id: u64,
+        frame: &[u8],
+        write_counter: Rc<RefCell<usize>>,
+    ) -> Result<u32>;
+
+    /// Makes the inserted frames visible to new read transactions.
+    fn commit_inserted_frames(&mut self);
+
+    /// Forgets the frames inserted since the last commit.
+    fn discard_inserted_frames(&mut self);
+
+    /// Starts the WAL over from its first frame with new salts, which invalidates the frames
+    /// already written. Every frame must have been backfilled into the database file and synced,
+    /// and the write lock must be held. Fails with [LimboError::Busy] while a reader may still
+    /// read frames.
+    fn restart(&mut self) -> Result<Arc<Completion>>;
+
+    /// Truncates the WAL file to its header and syncs it, after a restart.
+    fn truncate(&mut self) -> Result<()>;
 }
 
 /// A dummy WAL implementation that does nothing.
@@ -270,6 +309,10 @@ impl Wal for DummyWAL {
         Ok(None)
     }
 
+    fn pages_in_frames_after(&self, _frame_id: u64) -> Vec<u64> {
+        Vec::new()
+    }
+
     fn read_frame(
         &self,
         _frame_id: u64,
@@ -282,7 +325,6 @@ impl Wal for DummyWAL {
     fn read_frame_raw(
         &self,
         _frame_id: u64,
-        _buffer_pool: Rc<BufferPool>,
         _frame: *mut u8,
         _frame_len: u32,
     ) -> Result<Arc<Completion>> {
@@ -328,6 +370,41 @@ impl Wal for DummyWAL {
     fn get_min_frame(&self) -> u64 {
         0
     }
+
+    fn get_header(&self) -> WalHeader {
+        WalHeader::default()
+    }
+
+    fn set_header(&mut self, _header: WalHeader) -> Result<Arc<Completion>> {
+        Err(LimboError::InvalidArgument(
+            "database has no WAL to insert frames into".to_string(),
+        ))
+    }
+
+    fn insert_frame_raw(
+        &mut self,
+        _frame_id: u64,
+        _frame: &[u8],
+        _write_counter: Rc<RefCell<usize>>,
+    ) -> Result<u32> {
+        Err(LimboError::InvalidArgument(
+            "database has no WAL to insert frames into".to_string(),
+        ))
+    }
+
+    fn commit_inserted_frames(&mut self) {}
+
+    fn discard_inserted_frames(&mut self) {}
+
+    fn restart(&mut self) -> Result<Arc<Completion>> {
+        Err(LimboError::InvalidArgument(
+            "database has no WAL to restart".to_string(),
+        ))
+    }
+
+    fn truncate(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 // Syncing requires a state machine because we need to schedule a sync and then wait until it is
@@ -407,6 +484,12 @@ pub struct WalFile {
     max_frame: u64,
     /// Start of range to look for frames range=(minframe..max_frame)
     min_frame: u64,
+    /// Page and frame ids of the frames written by this connection that are not committed.
+    inserted_frames: Vec<(u64, u64)>,
+    /// Cumulative checksum of the last inserted frame.
+    inserted_checksum: (u32, u32),
+    /// Encryption state of the database, shared with the other connections.
+    encryption: Arc<Encryption>,
 }
 
 impl fmt::Debug for WalFile {
@@ -558,6 +641,15 @@ impl Wal for WalFile {
 
     /// Find the latest frame containing a page.
     fn find_frame(&self, page_id: u64) -> Result<Option<u64>> {
+        // The frames this connection did not commit yet hold its latest changes.
+        if let Some(&(_, frame_id)) = self
+            .inserted_frames
+            .iter()
+            .rev()
+            .find(|(inserted_page_id, _)| *inserted_page_id == page_id)
+        {
+            return Ok(Some(frame_id));
+        }
         let shared = self.get_shared();
         let frames = shared.frame_cache.lock();
         let frames = frames.get(&page_id);
@@ -573,15 +665,38 @@ impl Wal for WalFile {
         Ok(None)
     }
 
+    fn pages_in_frames_after(&self, frame_id: u64) -> Vec<u64> {
+        let shared = self.get_shared();
+        let frame_cache = shared.frame_cache.lock();
+        frame_cache
+            .iter()
+            .filter(|(_, frames)| {
+                frames
+                    .iter()
+                    .any(|&frame| frame > frame_id && frame <= self.max_frame)
+            })
+            .map(|(&page_id, _)| page_id)
+            .collect()
+    }
+
     /// Read a frame from the WAL.
     fn read_frame(&self, frame_id: u64, page: PageRef, buffer_pool: Rc<BufferPool>) -> Result<()> {
         tracing::debug!("read_frame({})", frame_id);
         let offset = self.frame_offset(frame_id);
         page.set_locked();
         let frame = page.clone();
+        let encryption = self.encryption.clone();
         let complete = Box::new(move |buf: Arc<RefCell<Buffer>>| {
             let frame = frame.clone();
-            finish_read_page(page.get().id, buf, frame).unwrap();
+            let page_id = page.get().id;
+            let decrypted = encryption.decrypt_page(page_id, buf.borrow_mut().as_mut_slice());
+            if decrypted
+                .and_then(|_| finish_read_page(page_id, buf, frame.clone()))
+                .is_err()
+            {
+                frame.set_error();
+                frame.clear_locked();
+            }
         });
         begin_read_wal_frame(
             &self.get_shared().file,
@@ -595,25 +710,25 @@ impl Wal for WalFile {
     fn read_frame_raw(
         &self,
         frame_id: u64,
-        buffer_pool: Rc<BufferPool>,
         frame: *mut u8,
         frame_len: u32,
     ) -> Result<Arc<Completion>> {
-        tracing::debug!("read_frame({})", frame_id);
+        tracing::debug!("read_frame_raw({})", frame_id);
         let offset = self.frame_offset(frame_id);
         let complete = Box::new(move |buf: Arc<RefCell<Buffer>>| {
             let buf = buf.borrow();
-            let buf_ptr = buf.as_ptr();
+            let len = buf.len().min(frame_len as usize);
             unsafe {
-                std::ptr::copy_nonoverlapping(buf_ptr, frame, frame_len as usize);
+                std::ptr::copy_nonoverlapping(buf.as_ptr(), frame, len);
             }
         });
-        let c = begin_read_wal_frame(
-            &self.get_shared().file,
-            offset + WAL_FRAME_HEADER_SIZE,
-            buffer_pool,
+        let drop_fn = Rc::new(|_buf| {});
+        let buf = Buffer::allocate(WAL_FRAME_HEADER_SIZE + self.page_size as usize, drop_fn);
+        let c = Arc::new(Completion::Read(ReadCompletion::new(
+            Arc::new(RefCell::new(buf)),
             complete,
-        )?;
+        )));
+        self.get_shared().file.pread(offset, c.clone())?;
         Ok(c)
     }
 
@@ -627,7 +742,7 @@ impl Wal for WalFile {
         let page_id = page.get().id;
         let shared = self.get_shared();
         let max_frame = shared.max_frame.load(Ordering::SeqCst);
-        let frame_id = if max_frame == 0 { 1 } else { max_frame + 1 };
+        let frame_id = max_frame + self.inserted_frames.len() as u64 + 1;
         let offset = self.frame_offset(frame_id);
         tracing::debug!(
             "append_frame(frame={}, offset={}, page_id={})",
@@ -637,7 +752,11 @@ impl Wal for WalFile {
         );
         let header = shared.wal_header.clone();
         let header = header.lock();
-        let checksums = shared.last_checksum;
+        let checksums = if self.inserted_frames.is_empty() {
+            shared.last_checksum
+        } else {
+            self.inserted_checksum
+        };
         let checksums = begin_write_wal_frame(
             &shared.file,
             offset,
@@ -647,19 +766,12 @@ impl Wal for WalFile {
             write_counter,
             &header,
             checksums,
+            &self.encryption,
         )?;
-        shared.last_checksum = checksums;
-        shared.max_frame.store(frame_id, Ordering::SeqCst);
-        {
-            let mut frame_cache = shared.frame_cache.lock();
-            let frames = frame_cache.get_mut(&(page_id as u64));
-            match frames {
-                Some(frames) => frames.push(frame_id),
-                None => {
-                    frame_cache.insert(page_id as u64, vec![frame_id]);
-                    shared.pages_in_frames.lock().push(page_id as u64);
-                }
-            }
+        self.inserted_frames.push((page_id as u64, frame_id));
+        self.inserted_checksum = checksums;
+        if db_size != 0 {
+            self.commit_inserted_frames();
         }
         Ok(())
     }
@@ -759,6 +871,12 @@ impl Wal for WalFile {
                 CheckpointState::WaitReadFrame => {
                     if self.ongoing_checkpoint.page.is_locked() {
                         return Ok(CheckpointStatus::IO);
+                    } else if self.ongoing_checkpoint.page.is_error() {
+                        self.ongoing_checkpoint.state = CheckpointState::Start;
+                        return Err(LimboError::Corrupt(format!(
+                            "failed to read page {} from the WAL",
+                            self.ongoing_checkpoint.page.get().id
+                        )));
                     } else {
                         self.ongoing_checkpoint.state = CheckpointState::WritePage;
                     }
@@ -871,6 +989,148 @@ impl Wal for WalFile {
     fn get_min_frame(&self) -> u64 {
         self.min_frame
     }
+
+    fn get_header(&self) -> WalHeader {
+        *self.get_shared().wal_header.lock()
+    }
+
+    fn set_header(&mut self, header: WalHeader) -> Result<Arc<Completion>> {
+        let shared = self.get_shared();
+        assert_eq!(shared.max_frame.load(Ordering::SeqCst), 0);
+        let c = sqlite3_ondisk::begin_write_wal_header(&shared.file, &header)?;
+        *shared.wal_header.lock() = header;
+        shared.last_checksum = (header.checksum_1, header.checksum_2);
+        Ok(c)
+    }
+
+    fn insert_frame_raw(
+        &mut self,
+        frame_id: u64,
+        frame: &[u8],
+        write_counter: Rc<RefCell<usize>>,
+    ) -> Result<u32> {
+        let shared = self.get_shared();
+        let header = *shared.wal_header.lock();
+        let expected_frame_id =
+            shared.max_frame.load(Ordering::SeqCst) + self.inserted_frames.len() as u64 + 1;
+        if frame_id != expected_frame_id {
+            return Err(LimboError::InvalidArgument(format!(
+                "expected WAL frame {}, got frame {}",
+                expected_frame_id, frame_id
+            )));
+        }
+        if frame.len() != WAL_FRAME_HEADER_SIZE + self.page_size as usize {
+            return Err(LimboError::InvalidArgument(format!(
+                "WAL frame must be {} bytes, got {}",
+                WAL_FRAME_HEADER_SIZE + self.page_size as usize,
+                frame.len()
+            )));
+        }
+        let page_id = read_u32(frame, 0) as u64;
+        let db_size = read_u32(frame, 4);
+        if (read_u32(frame, 8), read_u32(frame, 12)) != (header.salt_1, header.salt_2) {
+            return Err(LimboError::InvalidArgument(format!(
+                "WAL frame {} belongs to another WAL: salt mismatch",
+                frame_id
+            )));
+        }
+        let checksum = if self.inserted_frames.is_empty() {
+            shared.last_checksum
+        } else {
+            self.inserted_checksum
+        };
+        let native = header.native_checksum();
+        let checksum = checksum_wal(&frame[0..8], &header, checksum, native);
+        let checksum = checksum_wal(&frame[WAL_FRAME_HEADER_SIZE..], &header, checksum, native);
+        if checksum != (read_u32(frame, 16), read_u32(frame, 20)) {
+            return Err(LimboError::Corrupt(format!(
+                "WAL frame {} checksum mismatch",
+                frame_id
+            )));
+        }
+        tracing::debug!("insert_frame_raw(frame={}, page_id={})", frame_id, page_id);
+        let offset = self.frame_offset(frame_id);
+        begin_write_wal_frame_raw(&shared.file, offset, frame, write_counter)?;
+        self.inserted_frames.push((page_id, frame_id));
+        self.inserted_checksum = checksum;
+        Ok(db_size)
+    }
+
+    fn commit_inserted_frames(&mut self) {
+        let inserted_frames = std::mem::take(&mut self.inserted_frames);
+        let Some(&(_, max_frame)) = inserted_frames.last() else {
+            return;
+        };
+        let shared = self.get_shared();
+        {
+            let mut frame_cache = shared.frame_cache.lock();
+            for (page_id, frame_id) in inserted_frames {
+                match frame_cache.get_mut(&page_id) {
+                    Some(frames) => frames.push(frame_id),
+                    None => {
+                        frame_cache.insert(page_id, vec![frame_id]);
+                        shared.pages_in_frames.lock().push(page_id);
+                    }
+                }
+            }
+        }
+        shared.last_checksum = self.inserted_checksum;
+        shared.max_frame.store(max_frame, Ordering::SeqCst);
+    }
+
+    fn discard_inserted_frames(&mut self) {
+        self.inserted_frames.clear();
+    }
+
+    fn restart(&mut self) -> Result<Arc<Completion>> {
+        let shared = self.get_shared();
+        // The frames are about to be written over, so no reader may use them anymore.
+        for i in 0..shared.read_locks.len() {
+            if !shared.read_locks[i].write() {
+                for lock in shared.read_locks[..i].iter_mut() {
+                    lock.unlock();
+                }
+                return Err(LimboError::Busy);
+            }
+        }
+        let mut header = *shared.wal_header.lock();
+        header.checkpoint_seq = header.checkpoint_seq.wrapping_add(1);
+        header.salt_1 = header.salt_1.wrapping_add(1);
+        header.salt_2 = self.io.generate_random_number() as u32;
+        let checksums = checksum_wal(
+            &header.as_bytes()[..WAL_HEADER_SIZE - 2 * 4],
+            &header,
+            (0, 0),
+            cfg!(target_endian = "big"),
+        );
+        header.checksum_1 = checksums.0;
+        header.checksum_2 = checksums.1;
+        tracing::debug!("restart(checkpoint_seq={})", header.checkpoint_seq);
+        let c = sqlite3_ondisk::begin_write_wal_header(&shared.file, &header)?;
+        *shared.wal_header.lock() = header;
+        shared.last_checksum = checksums;
+        shared.frame_cache.lock().clear();
+        shared.pages_in_frames.lock().clear();
+        shared.max_frame.store(0, Ordering::SeqCst);
+        shared.nbackfills.store(0, Ordering::SeqCst);
+        // Like in SQLite, the next readers read the database file only until frames are added.
+        for (i, lock) in shared.read_locks.iter_mut().enumerate() {
+            let mark = if i == 0 { 0 } else { READMARK_NOT_USED };
+            lock.value.store(mark, Ordering::SeqCst);
+            lock.unlock();
+        }
+        self.max_frame = 0;
+        self.min_frame = 0;
+        Ok(c)
+    }
+
+    fn truncate(&mut self) -> Result<()> {
+        let shared = self.get_shared();
+        shared.file.truncate(WAL_HEADER_SIZE)?;
+        let c = Arc::new(Completion::Sync(SyncCompletion::new(Box::new(|_| {}))));
+        shared.file.sync(c.clone())?;
+        self.io.wait_for_completion(c)
+    }
 }
 
 impl WalFile {
@@ -910,9 +1170,16 @@ impl WalFile {
             max_frame: 0,
             min_frame: 0,
             max_frame_read_lock_index: 0,
+            inserted_frames: Vec::new(),
+            inserted_checksum: (0, 0),
+            encryption: Arc::new(Encryption::default()),
         }
     }
 
+    pub(crate) fn set_encryption(&mut self, encryption: Arc<Encryption>) {
+        self.encryption = encryption;
+    }
+
     fn frame_offset(&self, frame_id: u64) -> usize {
         assert!(frame_id > 0, "Frame ID must be 1-based");
         let page_size = self.page_size;
@@ -1009,3 +1276,102 @@ impl WalFileShared {
         Ok(Arc::new(UnsafeCell::new(shared)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::pager::allocate_page;
+    use crate::PlatformIO;
+
+    const PAGE_SIZE: u32 = 4096;
+
+    fn open_wal(io: &Arc<dyn IO>, path: &str) -> (WalFile, WalFile) {
+        let shared = WalFileShared::open_shared(io, path, PAGE_SIZE).unwrap();
+        let buffer_pool = Rc::new(BufferPool::new(PAGE_SIZE as usize));
+        let writer = WalFile::new(io.clone(), PAGE_SIZE, shared.clone(), buffer_pool.clone());
+        let reader = WalFile::new(io.clone(), PAGE_SIZE, shared, buffer_pool);
+        (writer, reader)
+    }
+
+    fn append(io: &Arc<dyn IO>, wal: &mut WalFile, page_id: usize, db_size: u32) {
+        let page = allocate_page(page_id, &wal.buffer_pool, 0);
+        page.get_contents().as_ptr().fill(page_id as u8);
+        let write_counter = Rc::new(RefCell::new(0));
+        wal.append_frame(page, db_size, write_counter.clone())
+            .unwrap();
+        while *write_counter.borrow() > 0 {
+            io.run_once().unwrap();
+        }
+    }
+
+    fn visible_frame(wal: &mut WalFile, page_id: u64) -> Option<u64> {
+        assert!(matches!(wal.begin_read_tx().unwrap(), LimboResult::Ok));
+        let frame = wal.find_frame(page_id).unwrap();
+        wal.end_read_tx().unwrap();
+        frame
+    }
+
+    #[test]
+    fn test_non_commit_frames_are_visible_after_commit_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-wal");
+        let io: Arc<dyn IO> = Arc::new(PlatformIO::new().unwrap());
+        let (mut writer, mut reader) = open_wal(&io, path.to_str().unwrap());
+
+        append(&io, &mut writer, 2, 0);
+        append(&io, &mut writer, 3, 0);
+        // The writer reads its own frames, readers don't see them yet.
+        assert_eq!(writer.find_frame(2).unwrap(), Some(1));
+        assert_eq!(visible_frame(&mut reader, 2), None);
+        assert_eq!(writer.get_max_frame_in_wal(), 0);
+
+        append(&io, &mut writer, 2, 3);
+        assert_eq!(writer.get_max_frame_in_wal(), 3);
+        assert_eq!(visible_frame(&mut reader, 2), Some(3));
+        assert_eq!(visible_frame(&mut reader, 3), Some(2));
+    }
+
+    #[test]
+    fn test_recovery_stops_at_last_commit_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-wal");
+        let path = path.to_str().unwrap();
+        let io: Arc<dyn IO> = Arc::new(PlatformIO::new().unwrap());
+        {
+            let (mut writer, _) = open_wal(&io, path);
+            append(&io, &mut writer, 1, 1);
+            // A transaction whose commit frame was never written.
+            append(&io, &mut writer, 2, 0);
+            append(&io, &mut writer, 3, 0);
+        }
+        let (mut writer, mut reader) = open_wal(&io, path);
+        assert_eq!(writer.get_max_frame_in_wal(), 1);
+        assert_eq!(visible_frame(&mut reader, 2), None);
+
+        // The next transaction writes over the first of the uncommitted frames, after which
+        // the second one no longer matches the checksum chain.
+        append(&io, &mut writer, 4, 4);
+        drop(writer);
+        drop(reader);
+        let (_, mut reader) = open_wal(&io, path);
+        assert_eq!(reader.get_max_frame_in_wal(), 2);
+        assert_eq!(visible_frame(&mut reader, 4), Some(2));
+        assert_eq!(visible_frame(&mut reader, 3), None);
+    }
+
+    #[test]
+    fn test_discarded_frames_are_written_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db-wal");
+        let io: Arc<dyn IO> = Arc::new(PlatformIO::new().unwrap());
+        let (mut writer, mut reader) = open_wal(&io, path.to_str().unwrap());
+
+        append(&io, &mut writer, 2, 0);
+        writer.discard_inserted_frames();
+        assert_eq!(writer.find_frame(2).unwrap(), None);
+        append(&io, &mut writer, 3, 3);
+        assert_eq!(writer.get_max_frame_in_wal(), 1);
+        assert_eq!(visible_frame(&mut reader, 3), Some(1));
+        assert_eq!(visible_frame(&mut reader, 2), None);
+    }
+}
@@ -0,0 +1,221 @@
+//! The temp database of a connection.
+//!
+//! TEMP tables and their indexes live in a database private to the connection, opened the
+//! first time a statement uses it and deleted when the connection is closed. Statements are
+//! compiled against the main schema overlaid with the temp schema, see [Schema::with_temp], so
+//! temp tables hide main tables of the same name unless the name is qualified with `main.`.
+//! Transactions on the temp database start with the `Transaction` instruction of the
+//! statements that use it and end with the transaction of the main database.
+//!
+//! `PRAGMA temp_store` decides where the temp database is kept: `FILE` keeps it in a
+//! temporary file opened through the IO of the main database, `DEFAULT` and `MEMORY` keep it
+//! in memory.
+
+use std::rc::Rc;
+#[cfg(feature = "fs")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::result::LimboResult;
+use crate::schema::Schema;
+#[cfg(feature = "fs")]
+use crate::storage::database::DatabaseFile;
+use crate::storage::database::FileMemoryStorage;
+use crate::util::parse_schema_rows;
+use crate::{
+    maybe_init_database_file, Connection, Database, LimboError, MemoryIO, OpenFlags, Pager,
+    PagerCacheflushStatus, Result, TransactionState, IO,
+};
+
+#[cfg(feature = "fs")]
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Where the temp database is stored, as set by `PRAGMA temp_store`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TempStore {
+    #[default]
+    Default,
+    File,
+    Memory,
+}
+
+impl TempStore {
+    pub fn from_i64(value: i64) -> Option<Self> {
+        match value {
+            0 => Some(Self::Default),
+            1 => Some(Self::File),
+            2 => Some(Self::Memory),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Some(Self::Default),
+            "file" => Some(Self::File),
+            "memory" => Some(Self::Memory),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(self) -> i64 {
+        match self {
+            Self::Default => 0,
+            Self::File => 1,
+            Self::Memory => 2,
+        }
+    }
+}
+
+pub(crate) struct TempDatabase {
+    /// The only connection to the temp database.
+    conn: Arc<Connection>,
+    /// The temporary file holding the database, deleted with it.
+    path: Option<String>,
+}
+
+impl TempDatabase {
+    pub(crate) fn open(io: &Arc<dyn IO>, store: TempStore) -> Result<Self> {
+        let temp = Self::open_store(io, store)?;
+        // Transactions end with the ones of the main connection, so statements run on the temp
+        // connection itself, like the ones loading its schema, must not commit.
+        temp.conn.auto_commit.set(false);
+        Ok(temp)
+    }
+
+    fn open_store(io: &Arc<dyn IO>, store: TempStore) -> Result<Self> {
+        match store {
+            #[cfg(feature = "fs")]
+            TempStore::File => {
+                let path = std::env::temp_dir()
+                    .join(format!(
+                        "limbo-temp-{}-{}.db",
+                        std::process::id(),
+                        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+                    ))
+                    .to_string_lossy()
+                    .into_owned();
+                let file = io.open_file(&path, OpenFlags::Create, true)?;
+                maybe_init_database_file(&file, io)?;
+                let db =
+                    Database::open(io.clone(), &path, Arc::new(DatabaseFile::new(file)), false)?;
+                Ok(Self {
+                    conn: db.connect()?,
+                    path: Some(path),
+                })
+            }
+            _ => {
+                let io: Arc<dyn IO> = Arc::new(MemoryIO::new());
+                let file = io.open_file(":memory:", OpenFlags::Create, false)?;
+                maybe_init_database_file(&file, &io)?;
+                let db = Database::open(
+                    io,
+                    ":memory:",
+                    Arc::new(FileMemoryStorage::new(file)),
+                    false,
+                )?;
+                Ok(Self {
+                    conn: db.connect()?,
+                    path: None,
+                })
+            }
+        }
+    }
+
+    pub(crate) fn pager(&self) -> &Rc<Pager> {
+        &self.conn.pager
+    }
+
+    pub(crate) fn schema(&self) -> &Arc<RwLock<Schema>> {
+        &self.conn.schema
+    }
+
+    /// Starts a read or write transaction, or upgrades the current one to a write
+    /// transaction. Nothing else uses the temp database, so it is never busy.
+    pub(crate) fn begin_tx(&self, write: bool) -> Result<()> {
+        let conn = &self.conn;
+        if conn.transaction_state.get() == TransactionState::None {
+            if let LimboResult::Busy = conn.pager.begin_read_tx()? {
+                return Err(LimboError::Busy);
+            }
+            conn.transaction_state.set(TransactionState::Read);
+        }
+        if write && conn.transaction_state.get() == TransactionState::Read {
+            if let LimboResult::Busy = conn.pager.begin_write_tx()? {
+                conn.pager.end_read_tx()?;
+                conn.transaction_state.set(TransactionState::None);
+                return Err(LimboError::Busy);
+            }
+            conn.transaction_state.set(TransactionState::Write);
+        }
+        Ok(())
+    }
+
+    /// Commits the current transaction, if any.
+    pub(crate) fn commit(&self) -> Result<()> {
+        let conn = &self.conn;
+        match conn.transaction_state.get() {
+            TransactionState::Write => loop {
+                match conn.pager.end_tx()? {
+                    PagerCacheflushStatus::Done(_) => break,
+                    PagerCacheflushStatus::IO => conn.pager.io.run_once()?,
+                }
+            },
+            TransactionState::Read => conn.pager.end_read_tx()?,
+            TransactionState::None => {}
+        }
+        conn.transaction_state.set(TransactionState::None);
+        Ok(())
+    }
+
+    /// Rolls back the current transaction, if any, and returns whether it had written to the
+    /// temp database, in which case the temp schema was loaded again.
+    pub(crate) fn rollback(&self) -> Result<bool> {
+        let wrote = self.conn.transaction_state.get() == TransactionState::Write;
+        self.conn.rollback()?;
+        self.conn.auto_commit.set(false);
+        Ok(wrote)
+    }
+
+    /// Loads the entries of the temp schema table that match `where_clause`, or all of them
+    /// into a new schema, like the `ParseSchema` instruction does for the main database.
+    pub(crate) fn parse_schema(&self, where_clause: Option<&str>) -> Result<()> {
+        let conn = &self.conn;
+        let stmt = match where_clause {
+            Some(where_clause) => conn.prepare(format!(
+                "SELECT * FROM sqlite_schema WHERE {}",
+                where_clause
+            ))?,
+            None => conn.prepare("SELECT * FROM sqlite_schema")?,
+        };
+        let mut new = Schema::new();
+        let mut schema = conn.schema.write();
+        // TODO: This function below is synchronous, make it async
+        parse_schema_rows(
+            Some(stmt),
+            if where_clause.is_some() {
+                &mut schema
+            } else {
+                &mut new
+            },
+            conn.pager.io.clone(),
+            &conn.syms.borrow(),
+            None,
+        )?;
+        if where_clause.is_none() {
+            *schema = new;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TempDatabase {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+            let _ = std::fs::remove_file(format!("{}-wal", path));
+        }
+    }
+}
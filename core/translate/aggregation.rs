@@ -13,7 +13,7 @@ use super::{
     emitter::{Resolver, TranslateCtx},
     expr::translate_expr,
     plan::{Aggregate, Distinctness, SelectPlan, TableReferences},
-    result_row::emit_select_result,
+    result_row::{emit_offset, emit_select_result},
 };
 
 /// Emits the bytecode for processing an aggregate without a GROUP BY clause.
@@ -43,6 +43,10 @@ pub fn emit_ungrouped_aggregation<'a>(
     }
     t_ctx.resolver.enable_expr_to_reg_cache();
 
+    // The single result row is skipped by any OFFSET of at least 1
+    let label_skip_result_row = program.allocate_label();
+    emit_offset(program, plan, label_skip_result_row, t_ctx.reg_offset)?;
+
     // This always emits a ResultRow because currently it can only be used for a single row result
     // Limit is None because we early exit on limit 0 and the max rows here is 1
     emit_select_result(
@@ -56,6 +60,7 @@ pub fn emit_ungrouped_aggregation<'a>(
         t_ctx.reg_result_cols_start.unwrap(),
         t_ctx.limit_ctx,
     )?;
+    program.preassign_label_to_next_insn(label_skip_result_row);
 
     Ok(())
 }
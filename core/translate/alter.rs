@@ -13,7 +13,10 @@ use crate::{
 };
 
 use super::{
-    emitter::TransactionMode, schema::SQLITE_TABLEID, update::translate_update_with_after,
+    emitter::TransactionMode,
+    expr::{walk_expr, walk_expr_mut, WalkControl},
+    schema::{emit_schema_cookie_change, emit_sequence_row_update, SQLITE_TABLEID},
+    update::translate_update_with_after,
 };
 
 pub fn translate_alter_table(
@@ -22,8 +25,8 @@ pub fn translate_alter_table(
     schema: &Schema,
     mut program: ProgramBuilder,
 ) -> Result<ProgramBuilder> {
-    let (table_name, alter_table) = alter;
-    let ast::Name(table_name) = table_name.name;
+    let (qualified_name, alter_table) = alter;
+    let table_name = qualified_name.name.0.clone();
     #[cfg(not(feature = "index_experimental"))]
     {
         if schema.table_has_indexes(&table_name) && cfg!(not(feature = "index_experimental")) {
@@ -36,7 +39,7 @@ pub fn translate_alter_table(
     }
 
     let Some(original_btree) = schema
-        .get_table(&table_name)
+        .get_qualified_table(&qualified_name)?
         .and_then(|table| table.btree())
     else {
         return Err(LimboError::ParseError(format!(
@@ -44,6 +47,12 @@ pub fn translate_alter_table(
         )));
     };
 
+    if original_btree.is_temp {
+        return Err(LimboError::ParseError(format!(
+            "ALTER TABLE is not supported for TEMP table {table_name} yet"
+        )));
+    }
+
     let mut btree = (*original_btree).clone();
 
     Ok(match alter_table {
@@ -82,6 +91,28 @@ pub fn translate_alter_table(
                 )));
             }
 
+            // An index left without one of its columns, or whose expressions or WHERE clause
+            // refer to the column, could no longer be parsed back from the schema.
+            let column_name = normalize_ident(&column_name);
+            for index in schema.get_table_indices(&original_btree) {
+                let mut referenced = index
+                    .where_clause
+                    .as_deref()
+                    .map_or(Ok(false), |expr| references_column(expr, &column_name))?;
+                for column in &index.columns {
+                    referenced |= match &column.expr {
+                        Some(expr) => references_column(expr, &column_name)?,
+                        None => column.pos_in_table == dropped_index,
+                    };
+                }
+                if referenced {
+                    return Err(LimboError::ParseError(format!(
+                        "error in index {} after drop column: no such column: {column_name}",
+                        index.name
+                    )));
+                }
+            }
+
             btree.columns.remove(dropped_index);
 
             let sql = btree.to_sql();
@@ -118,6 +149,7 @@ pub fn translate_alter_table(
                         cursor_id,
                         root_page: RegisterOrLiteral::Literal(root_page),
                         name: table_name.clone(),
+                        db: 0,
                     });
 
                     program.cursor_loop(cursor_id, |program, rowid| {
@@ -153,6 +185,7 @@ pub fn translate_alter_table(
                         });
                     });
 
+                    emit_schema_cookie_change(program, schema, 0);
                     program.emit_insn(Insn::ParseSchema {
                         db: usize::MAX, // TODO: This value is unused, change when we do something with it
                         where_clause: None,
@@ -213,6 +246,7 @@ pub fn translate_alter_table(
                 syms,
                 program,
                 |program| {
+                    emit_schema_cookie_change(program, schema, 0);
                     program.emit_insn(Insn::ParseSchema {
                         db: usize::MAX, // TODO: This value is unused, change when we do something with it
                         where_clause: None,
@@ -248,6 +282,7 @@ pub fn translate_alter_table(
                 cursor_id,
                 root_page: RegisterOrLiteral::Literal(sqlite_schema.root_page),
                 name: sqlite_schema.name.clone(),
+                db: 0,
             });
 
             program.cursor_loop(cursor_id, |program, rowid| {
@@ -299,6 +334,7 @@ pub fn translate_alter_table(
                 });
             });
 
+            emit_schema_cookie_change(&mut program, schema, 0);
             program.emit_insn(Insn::ParseSchema {
                 db: usize::MAX, // TODO: This value is unused, change when we do something with it
                 where_clause: None,
@@ -311,7 +347,10 @@ pub fn translate_alter_table(
         ast::AlterTableBody::RenameTo(new_name) => {
             let ast::Name(new_name) = new_name;
 
-            if schema.get_table(&new_name).is_some() {
+            if schema
+                .get_table_in(original_btree.db(), &new_name)
+                .is_some()
+            {
                 return Err(LimboError::ParseError(format!(
                     "there is already another table or index with this name: {new_name}"
                 )));
@@ -329,6 +368,7 @@ pub fn translate_alter_table(
                 cursor_id,
                 root_page: RegisterOrLiteral::Literal(sqlite_schema.root_page),
                 name: sqlite_schema.name.clone(),
+                db: 0,
             });
 
             program.cursor_loop(cursor_id, |program, rowid| {
@@ -377,6 +417,11 @@ pub fn translate_alter_table(
                 });
             });
 
+            if original_btree.has_autoincrement {
+                emit_sequence_row_update(&mut program, schema, &table_name, Some(&new_name));
+            }
+
+            emit_schema_cookie_change(&mut program, schema, 0);
             program.emit_insn(Insn::ParseSchema {
                 db: usize::MAX, // TODO: This value is unused, change when we do something with it
                 where_clause: None,
@@ -388,3 +433,34 @@ pub fn translate_alter_table(
         }
     })
 }
+
+/// Returns true if `expr` refers to the column named `column`.
+fn references_column(expr: &ast::Expr, column: &str) -> Result<bool> {
+    let mut found = false;
+    walk_expr(expr, &mut |expr| {
+        if let ast::Expr::Id(ast::Id(name))
+        | ast::Expr::Name(ast::Name(name))
+        | ast::Expr::Qualified(_, ast::Name(name)) = expr
+        {
+            found |= normalize_ident(name) == column;
+        }
+        Ok(WalkControl::Continue)
+    })?;
+    Ok(found)
+}
+
+/// Renames the references to column `from` in `expr` to `to`, for the expressions and the
+/// WHERE clause of an index whose column is renamed.
+pub(crate) fn rename_column_references(expr: &mut ast::Expr, from: &str, to: &str) -> Result<()> {
+    walk_expr_mut(expr, &mut |expr| {
+        if let ast::Expr::Id(ast::Id(name))
+        | ast::Expr::Name(ast::Name(name))
+        | ast::Expr::Qualified(_, ast::Name(name)) = expr
+        {
+            if normalize_ident(name) == from {
+                *name = to.to_string();
+            }
+        }
+        Ok(())
+    })
+}
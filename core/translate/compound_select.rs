@@ -237,6 +237,7 @@ fn create_union_dedupe_index(
                 pos_in_table: 0,
                 default: None,
                 collation: None, // FIXME: this should be inferred
+                expr: None,
             })
             .collect(),
         name: "union_dedupe".to_string(),
@@ -245,6 +246,8 @@ fn create_union_dedupe_index(
         table_name: String::new(),
         unique: true,
         has_rowid: false,
+        where_clause: None,
+        stat: None,
     });
     let cursor_id = program.alloc_cursor_id(CursorType::BTreeIndex(dedupe_index.clone()));
     program.emit_insn(Insn::OpenEphemeral {
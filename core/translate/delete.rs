@@ -2,17 +2,19 @@ use crate::schema::Table;
 use crate::translate::emitter::emit_program;
 use crate::translate::optimizer::optimize_plan;
 use crate::translate::plan::{DeletePlan, Operation, Plan};
-use crate::translate::planner::{parse_limit, parse_where};
+use crate::translate::planner::{parse_limit, parse_where, resolve_index_hint};
 use crate::vdbe::builder::{ProgramBuilder, ProgramBuilderOpts, QueryMode, TableRefIdCounter};
 use crate::{schema::Schema, Result, SymbolTable};
-use limbo_sqlite3_parser::ast::{Expr, Limit, QualifiedName};
+use limbo_sqlite3_parser::ast::{Expr, Indexed, Limit, QualifiedName};
 
 use super::plan::{ColumnUsedMask, IterationDirection, JoinedTable, TableReferences};
 
+#[allow(clippy::too_many_arguments)]
 pub fn translate_delete(
     query_mode: QueryMode,
     schema: &Schema,
     tbl_name: &QualifiedName,
+    indexed: Option<Indexed>,
     where_clause: Option<Box<Expr>>,
     limit: Option<Box<Limit>>,
     syms: &SymbolTable,
@@ -31,11 +33,12 @@ pub fn translate_delete(
     let mut delete_plan = prepare_delete_plan(
         schema,
         tbl_name,
+        indexed,
         where_clause,
         limit,
         &mut program.table_reference_counter,
     )?;
-    optimize_plan(&mut delete_plan, schema)?;
+    optimize_plan(&mut delete_plan, schema, syms.case_sensitive_like.get())?;
     let Plan::Delete(ref delete) = delete_plan else {
         panic!("delete_plan is not a DeletePlan");
     };
@@ -53,11 +56,12 @@ pub fn translate_delete(
 pub fn prepare_delete_plan(
     schema: &Schema,
     tbl_name: &QualifiedName,
+    indexed: Option<Indexed>,
     where_clause: Option<Box<Expr>>,
     limit: Option<Box<Limit>>,
     table_ref_counter: &mut TableRefIdCounter,
 ) -> Result<Plan> {
-    let table = match schema.get_table(tbl_name.name.0.as_str()) {
+    let table = match schema.get_qualified_table(tbl_name)? {
         Some(table) => table,
         None => crate::bail_parse_error!("no such table: {}", tbl_name),
     };
@@ -69,11 +73,11 @@ pub fn prepare_delete_plan(
         crate::bail_parse_error!("Table is neither a virtual table nor a btree table");
     };
     let name = tbl_name.name.0.as_str().to_string();
-    let indexes = schema
-        .get_indices(table.get_name())
-        .iter()
-        .cloned()
-        .collect();
+    let indexes = table
+        .btree()
+        .map_or(&[] as &[_], |btree| schema.get_table_indices(&btree))
+        .to_vec();
+    let index_hint = resolve_index_hint(schema, &table, indexed)?;
     let joined_tables = vec![JoinedTable {
         table,
         identifier: name,
@@ -84,6 +88,7 @@ pub fn prepare_delete_plan(
         },
         join_info: None,
         col_used_mask: ColumnUsedMask::new(),
+        index_hint,
     }];
     let mut table_references = TableReferences::new(joined_tables, vec![]);
 
@@ -9,7 +9,8 @@ use limbo_sqlite3_parser::{
 use crate::{schema::Table, translate::plan::TableReferences};
 
 use super::plan::{
-    Aggregate, DeletePlan, JoinedTable, Operation, Plan, Search, SelectPlan, UpdatePlan,
+    Aggregate, DeletePlan, HashJoin, JoinedTable, MultiIndexOr, Operation, Plan, Search,
+    SelectPlan, UpdatePlan,
 };
 
 impl Display for Aggregate {
@@ -70,6 +71,36 @@ impl Display for Plan {
     }
 }
 
+/// Format the key columns of a hash join like the constraints of an index search, e.g. `a=? AND b=?`.
+fn hash_join_key_columns(reference: &JoinedTable, hash_join: &HashJoin) -> String {
+    hash_join
+        .build_columns
+        .iter()
+        .map(|&pos| {
+            let column = &reference.columns()[pos];
+            format!("{}=?", column.name.as_deref().unwrap_or("rowid"))
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Format the branches of a multi-index OR, e.g. `INDEX idx_a, INTEGER PRIMARY KEY`.
+fn multi_index_or_branches(multi_index_or: &MultiIndexOr) -> String {
+    multi_index_or
+        .branches
+        .iter()
+        .map(|branch| match branch {
+            Search::RowidEq { .. } | Search::Seek { index: None, .. } => {
+                "INTEGER PRIMARY KEY".to_string()
+            }
+            Search::Seek {
+                index: Some(index), ..
+            } => format!("INDEX {}", index.name),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl Display for SelectPlan {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         writeln!(f, "QUERY PLAN")?;
@@ -88,14 +119,21 @@ impl Display for SelectPlan {
             };
 
             match &reference.op {
-                Operation::Scan { .. } => {
+                Operation::Scan { index, .. } => {
                     let table_name = if reference.table.get_name() == reference.identifier {
                         reference.identifier.clone()
                     } else {
                         format!("{} AS {}", reference.table.get_name(), reference.identifier)
                     };
 
-                    writeln!(f, "{}SCAN {}", indent, table_name)?;
+                    match index {
+                        Some(index) => writeln!(
+                            f,
+                            "{}SCAN {} USING INDEX {}",
+                            indent, table_name, index.name
+                        )?,
+                        None => writeln!(f, "{}SCAN {}", indent, table_name)?,
+                    }
                 }
                 Operation::Search(search) => match search {
                     Search::RowidEq { .. } | Search::Seek { index: None, .. } => {
@@ -105,6 +143,17 @@ impl Display for SelectPlan {
                             indent, reference.identifier
                         )?;
                     }
+                    Search::Seek {
+                        index: Some(index),
+                        skip_scan: true,
+                        ..
+                    } => {
+                        writeln!(
+                            f,
+                            "{}SEARCH {} USING INDEX {} (ANY({}))",
+                            indent, reference.identifier, index.name, index.columns[0].name
+                        )?;
+                    }
                     Search::Seek {
                         index: Some(index), ..
                     } => {
@@ -115,6 +164,24 @@ impl Display for SelectPlan {
                         )?;
                     }
                 },
+                Operation::HashJoin(hash_join) => {
+                    writeln!(
+                        f,
+                        "{}SEARCH {} USING HASH JOIN ({})",
+                        indent,
+                        reference.identifier,
+                        hash_join_key_columns(reference, hash_join)
+                    )?;
+                }
+                Operation::MultiIndexOr(multi_index_or) => {
+                    writeln!(
+                        f,
+                        "{}MULTI-INDEX OR {} ({})",
+                        indent,
+                        reference.identifier,
+                        multi_index_or_branches(multi_index_or)
+                    )?;
+                }
             }
         }
         Ok(())
@@ -142,6 +209,12 @@ impl Display for DeletePlan {
                 Operation::Search { .. } => {
                     panic!("DELETE plans should not contain search operations");
                 }
+                Operation::HashJoin(_) => {
+                    panic!("DELETE plans should not contain hash join operations");
+                }
+                Operation::MultiIndexOr(_) => {
+                    panic!("DELETE plans should not contain multi-index OR operations");
+                }
             }
         }
         Ok(())
@@ -186,6 +259,17 @@ impl fmt::Display for UpdatePlan {
                             indent, reference.identifier
                         )?;
                     }
+                    Search::Seek {
+                        index: Some(index),
+                        skip_scan: true,
+                        ..
+                    } => {
+                        writeln!(
+                            f,
+                            "{}SEARCH {} USING INDEX {} (ANY({}))",
+                            indent, reference.identifier, index.name, index.columns[0].name
+                        )?;
+                    }
                     Search::Seek {
                         index: Some(index), ..
                     } => {
@@ -196,6 +280,24 @@ impl fmt::Display for UpdatePlan {
                         )?;
                     }
                 },
+                Operation::HashJoin(hash_join) => {
+                    writeln!(
+                        f,
+                        "{}SEARCH {} USING HASH JOIN ({})",
+                        indent,
+                        reference.identifier,
+                        hash_join_key_columns(reference, hash_join)
+                    )?;
+                }
+                Operation::MultiIndexOr(multi_index_or) => {
+                    writeln!(
+                        f,
+                        "{}MULTI-INDEX OR {} ({})",
+                        indent,
+                        reference.identifier,
+                        multi_index_or_branches(multi_index_or)
+                    )?;
+                }
             }
         }
         if let Some(order_by) = &self.order_by {
@@ -11,8 +11,12 @@ use super::expr::translate_expr;
 use super::group_by::{
     group_by_agg_phase, group_by_emit_row_phase, init_group_by, GroupByMetadata, GroupByRowSource,
 };
+use super::index::{
+    emit_index_key_columns, emit_partial_index_check, unique_index_violation_description, IndexRow,
+};
 use super::main_loop::{
     close_loop, emit_loop, init_distinct, init_loop, open_loop, LeftJoinMetadata, LoopLabels,
+    MultiIndexOrMetadata, SkipScanMetadata,
 };
 use super::order_by::{emit_order_by, init_order_by, SortMetadata};
 use super::plan::{
@@ -130,6 +134,10 @@ pub struct TranslateCtx<'a> {
     /// mapping between table loop index and associated metadata (for left joins only)
     /// this metadata exists for the right table in a given left join
     pub meta_left_joins: Vec<Option<LeftJoinMetadata>>,
+    /// mapping between table loop index and associated metadata (for multi-index OR operations only)
+    pub meta_multi_index_ors: Vec<Option<MultiIndexOrMetadata>>,
+    /// mapping between table loop index and associated metadata (for skip-scans only)
+    pub meta_skip_scans: Vec<Option<SkipScanMetadata>>,
     // We need to emit result columns in the order they are present in the SELECT, but they may not be in the same order in the ORDER BY sorter.
     // This vector holds the indexes of the result columns in the ORDER BY sorter.
     pub result_column_indexes_in_orderby_sorter: Vec<usize>,
@@ -169,6 +177,8 @@ impl<'a> TranslateCtx<'a> {
             reg_result_cols_start: None,
             meta_group_by: None,
             meta_left_joins: (0..table_count).map(|_| None).collect(),
+            meta_multi_index_ors: (0..table_count).map(|_| None).collect(),
+            meta_skip_scans: (0..table_count).map(|_| None).collect(),
             meta_sort: None,
             result_column_indexes_in_orderby_sorter: (0..result_column_count).collect(),
             result_columns_to_skip_in_orderby_sorter: None,
@@ -480,6 +490,12 @@ fn emit_delete_insns(
                 index.clone(),
             )),
         },
+        Operation::HashJoin(_) => {
+            unreachable!("the first table in the join order cannot be hash joined")
+        }
+        Operation::MultiIndexOr(_) => {
+            unreachable!("indexes are not used for DELETE and UPDATE")
+        }
     };
     let main_table_cursor_id =
         program.resolve_cursor_id(&CursorKey::table(table_reference.internal_id));
@@ -529,21 +545,31 @@ fn emit_delete_insns(
         });
 
         if let Some(index_refs) = index_refs_opt {
+            let btree_table = table_reference
+                .btree()
+                .expect("only btree tables have indexes");
             for (index, index_cursor_id) in index_refs {
+                // A partial index has no entry for a row that doesn't satisfy its WHERE clause.
+                let skip_index_label = program.allocate_label();
+                emit_partial_index_check(
+                    program,
+                    &t_ctx.resolver,
+                    &btree_table,
+                    &index,
+                    IndexRow::Cursor(table_reference.internal_id),
+                    skip_index_label,
+                )?;
                 let num_regs = index.columns.len() + 1;
                 let start_reg = program.alloc_registers(num_regs);
                 // Emit columns that are part of the index
-                index
-                    .columns
-                    .iter()
-                    .enumerate()
-                    .for_each(|(reg_offset, column_index)| {
-                        program.emit_column(
-                            main_table_cursor_id,
-                            column_index.pos_in_table,
-                            start_reg + reg_offset,
-                        );
-                    });
+                emit_index_key_columns(
+                    program,
+                    &t_ctx.resolver,
+                    &btree_table,
+                    &index,
+                    IndexRow::Cursor(table_reference.internal_id),
+                    start_reg,
+                )?;
                 program.emit_insn(Insn::RowId {
                     cursor_id: main_table_cursor_id,
                     dest: start_reg + num_regs - 1,
@@ -553,11 +579,14 @@ fn emit_delete_insns(
                     num_regs,
                     cursor_id: index_cursor_id,
                 });
+                program.preassign_label_to_next_insn(skip_index_label);
             }
         }
 
         program.emit_insn(Insn::Delete {
             cursor_id: main_table_cursor_id,
+            table_name: table_reference.table.get_name().to_string(),
+            is_part_of_update: false,
         });
     }
     if let Some(limit_ctx) = t_ctx.limit_ctx {
@@ -614,6 +643,13 @@ fn emit_program_for_update(
     )?;
     // Open indexes for update.
     let mut index_cursors = Vec::with_capacity(plan.indexes_to_update.len());
+    let db = plan
+        .table_references
+        .joined_tables()
+        .first()
+        .unwrap()
+        .table
+        .db();
     for index in &plan.indexes_to_update {
         if let Some(index_cursor) = program.resolve_cursor_id_safe(&CursorKey::index(
             plan.table_references
@@ -633,6 +669,7 @@ fn emit_program_for_update(
             cursor_id: index_cursor,
             root_page: RegisterOrLiteral::Literal(index.root_page),
             name: index.name.clone(),
+            db,
         });
         let record_reg = program.alloc_register();
         index_cursors.push((index_cursor, record_reg));
@@ -703,6 +740,12 @@ fn emit_update_insns(
                 false,
             ),
         },
+        Operation::HashJoin(_) => {
+            unreachable!("the first table in the join order cannot be hash joined")
+        }
+        Operation::MultiIndexOr(_) => {
+            unreachable!("indexes are not used for DELETE and UPDATE")
+        }
     };
 
     let beg = program.alloc_registers(
@@ -853,23 +896,38 @@ fn emit_update_insns(
     }
 
     for (index, (idx_cursor_id, record_reg)) in plan.indexes_to_update.iter().zip(&index_cursors) {
+        let btree_table = table_ref.btree().expect("only btree tables have indexes");
+        let new_row = IndexRow::Registers {
+            columns_start_reg: start,
+            rowid_reg: beg,
+        };
+        // The updated row is not checked against a partial index whose WHERE clause it doesn't satisfy.
+        let skip_index_label = program.allocate_label();
+        emit_partial_index_check(
+            program,
+            &t_ctx.resolver,
+            &btree_table,
+            index,
+            new_row,
+            skip_index_label,
+        )?;
+
         let num_cols = index.columns.len();
         // allocate scratch registers for the index columns plus rowid
         let idx_start_reg = program.alloc_registers(num_cols + 1);
 
         let rowid_reg = beg;
-        let idx_cols_start_reg = beg + 1;
-
-        // copy each index column from the table's column registers into these scratch regs
-        for (i, col) in index.columns.iter().enumerate() {
-            // copy from the table's column register over to the index's scratch register
 
-            program.emit_insn(Insn::Copy {
-                src_reg: idx_cols_start_reg + col.pos_in_table,
-                dst_reg: idx_start_reg + i,
-                amount: 0,
-            });
-        }
+        // copy each index column from the table's column registers into these scratch regs,
+        // or compute it from them for a column on an expression
+        emit_index_key_columns(
+            program,
+            &t_ctx.resolver,
+            &btree_table,
+            index,
+            new_row,
+            idx_start_reg,
+        )?;
         // last register is the rowid
         program.emit_insn(Insn::Copy {
             src_reg: rowid_reg,
@@ -886,6 +944,7 @@ fn emit_update_insns(
         });
 
         if !index.unique {
+            program.preassign_label_to_next_insn(skip_index_label);
             continue;
         }
 
@@ -898,19 +957,7 @@ fn emit_update_insns(
             num_regs: num_cols,
         });
 
-        let column_names = index.columns.iter().enumerate().fold(
-            String::with_capacity(50),
-            |mut accum, (idx, col)| {
-                if idx > 0 {
-                    accum.push_str(", ");
-                }
-                accum.push_str(&table_ref.table.get_name());
-                accum.push('.');
-                accum.push_str(&col.name);
-
-                accum
-            },
-        );
+        let column_names = unique_index_violation_description(&btree_table, index);
 
         let idx_rowid_reg = program.alloc_register();
         program.emit_insn(Insn::IdxRowId {
@@ -932,6 +979,7 @@ fn emit_update_insns(
         });
 
         program.preassign_label_to_next_insn(constraint_check);
+        program.preassign_label_to_next_insn(skip_index_label);
     }
 
     if let Some(btree_table) = table_ref.btree() {
@@ -1002,18 +1050,24 @@ fn emit_update_insns(
             let num_regs = index.columns.len() + 1;
             let start_reg = program.alloc_registers(num_regs);
 
-            // Delete existing index key
-            index
-                .columns
-                .iter()
-                .enumerate()
-                .for_each(|(reg_offset, column_index)| {
-                    program.emit_column(
-                        cursor_id,
-                        column_index.pos_in_table,
-                        start_reg + reg_offset,
-                    );
-                });
+            // Delete existing index key, unless the old row is not in the partial index
+            let skip_delete_label = program.allocate_label();
+            emit_partial_index_check(
+                program,
+                &t_ctx.resolver,
+                &btree_table,
+                index,
+                IndexRow::Cursor(table_ref.internal_id),
+                skip_delete_label,
+            )?;
+            emit_index_key_columns(
+                program,
+                &t_ctx.resolver,
+                &btree_table,
+                index,
+                IndexRow::Cursor(table_ref.internal_id),
+                start_reg,
+            )?;
 
             program.emit_insn(Insn::RowId {
                 cursor_id,
@@ -1025,8 +1079,22 @@ fn emit_update_insns(
                 num_regs,
                 cursor_id: idx_cursor_id,
             });
-
-            // Insert new index key (filled further above with values from set_clauses)
+            program.preassign_label_to_next_insn(skip_delete_label);
+
+            // Insert new index key (filled further above with values from set_clauses),
+            // unless the new row is not in the partial index
+            let skip_insert_label = program.allocate_label();
+            emit_partial_index_check(
+                program,
+                &t_ctx.resolver,
+                &btree_table,
+                index,
+                IndexRow::Registers {
+                    columns_start_reg: start,
+                    rowid_reg: beg,
+                },
+                skip_insert_label,
+            )?;
             program.emit_insn(Insn::IdxInsert {
                 cursor_id: idx_cursor_id,
                 record_reg: record_reg,
@@ -1034,16 +1102,21 @@ fn emit_update_insns(
                 unpacked_count: Some((index.columns.len() + 1) as u16),
                 flags: IdxInsertFlags::new(),
             });
+            program.preassign_label_to_next_insn(skip_insert_label);
         }
 
-        program.emit_insn(Insn::Delete { cursor_id });
+        program.emit_insn(Insn::Delete {
+            cursor_id,
+            table_name: table_ref.table.get_name().to_string(),
+            is_part_of_update: true,
+        });
 
         program.emit_insn(Insn::Insert {
             cursor: cursor_id,
             key_reg: rowid_set_clause_reg.unwrap_or(beg),
             record_reg,
             flag: InsertFlags::new().update(true),
-            table_name: table_ref.identifier.clone(),
+            table_name: table_ref.table.get_name().to_string(),
         });
     } else if let Some(_) = table_ref.virtual_table() {
         let arg_count = table_ref.columns().len() + 2;
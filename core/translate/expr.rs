@@ -1647,16 +1647,17 @@ pub fn translate_expr(
                             target_register,
                             func_ctx,
                         ),
-                        ScalarFunc::Likely => {
+                        ScalarFunc::Likely | ScalarFunc::Unlikely => {
                             let args = if let Some(args) = args {
                                 if args.len() != 1 {
                                     crate::bail_parse_error!(
-                                        "likely function must have exactly 1 argument",
+                                        "{} function must have exactly 1 argument",
+                                        srf
                                     );
                                 }
                                 args
                             } else {
-                                crate::bail_parse_error!("likely function with no arguments",);
+                                crate::bail_parse_error!("{} function with no arguments", srf);
                             };
                             let start_reg = program.alloc_register();
                             translate_expr(
@@ -2610,10 +2611,16 @@ pub fn sanitize_string(input: &str) -> String {
 
 /// Returns the components of a binary expression
 /// e.g. t.x = 5 -> Some((t.x, =, 5))
+/// A likely(), unlikely() or likelihood() call around the expression is looked through,
+/// e.g. likely(t.x = 5) -> Some((t.x, =, 5))
 pub fn as_binary_components(
     expr: &ast::Expr,
 ) -> Result<Option<(&ast::Expr, ast::Operator, &ast::Expr)>> {
-    match unwrap_parens(expr)? {
+    let mut expr = unwrap_parens(expr)?;
+    while let Some((inner, _)) = as_likelihood_hint(expr) {
+        expr = unwrap_parens(inner)?;
+    }
+    match expr {
         ast::Expr::Binary(lhs, operator, rhs)
             if matches!(
                 operator,
@@ -2630,6 +2637,63 @@ pub fn as_binary_components(
     }
 }
 
+/// Returns the components of a LIKE or GLOB expression that has neither NOT nor ESCAPE
+/// e.g. t.x LIKE 'a%' -> Some((t.x, LIKE, 'a%'))
+/// A likely(), unlikely() or likelihood() call around the expression is looked through,
+/// as in [as_binary_components].
+pub fn as_like_components(
+    expr: &ast::Expr,
+) -> Result<Option<(&ast::Expr, ast::LikeOperator, &ast::Expr)>> {
+    let mut expr = unwrap_parens(expr)?;
+    while let Some((inner, _)) = as_likelihood_hint(expr) {
+        expr = unwrap_parens(inner)?;
+    }
+    match expr {
+        ast::Expr::Like {
+            lhs,
+            not: false,
+            op: op @ (ast::LikeOperator::Like | ast::LikeOperator::Glob),
+            rhs,
+            escape: None,
+        } => Ok(Some((lhs.as_ref(), *op, rhs.as_ref()))),
+        _ => Ok(None),
+    }
+}
+
+/// The probability that likely(X) is true, as assumed by SQLite.
+const LIKELY_PROBABILITY: f64 = 0.9375;
+/// The probability that unlikely(X) is true, as assumed by SQLite.
+const UNLIKELY_PROBABILITY: f64 = 0.0625;
+
+/// Returns the expression wrapped by a likely(), unlikely() or likelihood() call,
+/// along with the probability of it being true that the call tells the query planner to assume.
+/// e.g. likelihood(t.x = 5, 0.25) -> Some((t.x = 5, 0.25))
+pub fn as_likelihood_hint(expr: &ast::Expr) -> Option<(&ast::Expr, f64)> {
+    let ast::Expr::FunctionCall {
+        name,
+        args: Some(args),
+        ..
+    } = unwrap_parens(expr).ok()?
+    else {
+        return None;
+    };
+    match Func::resolve_function(&normalize_ident(&name.0), args.len()).ok()? {
+        Func::Scalar(ScalarFunc::Likely) if args.len() == 1 => Some((&args[0], LIKELY_PROBABILITY)),
+        Func::Scalar(ScalarFunc::Unlikely) if args.len() == 1 => {
+            Some((&args[0], UNLIKELY_PROBABILITY))
+        }
+        Func::Scalar(ScalarFunc::Likelihood) if args.len() == 2 => match &args[1] {
+            ast::Expr::Literal(ast::Literal::Numeric(value)) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|probability| (0.0..=1.0).contains(probability))
+                .map(|probability| (&args[0], probability)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 /// Recursively unwrap parentheses from an expression
 /// e.g. (((t.x > 5))) -> t.x > 5
 fn unwrap_parens(expr: &ast::Expr) -> Result<&ast::Expr> {
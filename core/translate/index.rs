@@ -1,32 +1,53 @@
-use std::sync::Arc;
+use std::{rc::Rc, sync::Arc};
 
 use crate::vdbe::insn::CmpInsFlags;
 use crate::{
-    schema::{BTreeTable, Column, Index, IndexColumn, PseudoTable, Schema},
+    error::SQLITE_CONSTRAINT_PRIMARYKEY,
+    function::Func,
+    schema::{
+        database_index, indexed_column_name, BTreeTable, Index, IndexColumn, PseudoTable, Schema,
+        Table,
+    },
     storage::pager::CreateBTreeFlags,
     util::normalize_ident,
     vdbe::{
-        builder::{CursorType, ProgramBuilder, QueryMode},
+        builder::{CursorKey, CursorType, ProgramBuilder, QueryMode},
         insn::{IdxInsertFlags, Insn, RegisterOrLiteral},
+        BranchOffset,
+    },
+    SymbolTable,
+};
+use limbo_sqlite3_parser::ast::{Expr, QualifiedName, SortOrder, SortedColumn, TableInternalId};
+
+use super::{
+    collate::CollationSeq,
+    emitter::Resolver,
+    expr::{translate_condition_expr, translate_expr, walk_expr, ConditionMetadata, WalkControl},
+    optimizer::rewrite_expr,
+    plan::{ColumnUsedMask, IterationDirection, JoinedTable, Operation, TableReferences},
+    planner::bind_column_references,
+    schema::{
+        emit_schema_cookie_change, emit_schema_entry, schema_table, SchemaEntryType, SQLITE_TABLEID,
     },
 };
-use limbo_sqlite3_parser::ast::{self, Expr, Id, SortOrder, SortedColumn};
-
-use super::schema::{emit_schema_entry, SchemaEntryType, SQLITE_TABLEID};
 
+#[allow(clippy::too_many_arguments)]
 pub fn translate_create_index(
     mode: QueryMode,
     unique_if_not_exists: (bool, bool),
-    idx_name: &str,
+    idx_name: &QualifiedName,
     tbl_name: &str,
     columns: &[SortedColumn],
+    where_clause: Option<Box<Expr>>,
     schema: &Schema,
+    syms: &SymbolTable,
     mut program: ProgramBuilder,
 ) -> crate::Result<ProgramBuilder> {
     if cfg!(not(feature = "index_experimental")) {
         crate::bail_parse_error!("CREATE INDEX enabled only with index_experimental feature");
     }
-    let idx_name = normalize_ident(idx_name);
+    let db_name = idx_name.db_name.as_ref();
+    let idx_name = normalize_ident(&idx_name.name.0);
     let tbl_name = normalize_ident(tbl_name);
     let opts = crate::vdbe::builder::ProgramBuilderOpts {
         query_mode: mode,
@@ -37,57 +58,49 @@ pub fn translate_create_index(
     program.extend(&opts);
 
     // Check if the index is being created on a valid btree table and
-    // the name is globally unique in the schema.
-    if !schema.is_unique_idx_name(&idx_name) {
-        crate::bail_parse_error!("Error: index with name '{idx_name}' already exists.");
-    }
-    let Some(tbl) = schema.tables.get(&tbl_name) else {
+    // the name is unique in the database of the table.
+    let tbl = match db_name {
+        Some(db_name) => schema.get_table_in(database_index(db_name)?, &tbl_name),
+        None => schema.get_table(&tbl_name),
+    };
+    let Some(tbl) = tbl else {
         crate::bail_parse_error!("Error: table '{tbl_name}' does not exist.");
     };
     let Some(tbl) = tbl.btree() else {
         crate::bail_parse_error!("Error: table '{tbl_name}' is not a b-tree table.");
     };
-    let columns = resolve_sorted_columns(&tbl, columns)?;
+    // Like in SQLite, the index goes in the database of its table.
+    let db = tbl.db();
+    if schema.find_index_in(db, &idx_name).is_some() {
+        crate::bail_parse_error!("Error: index with name '{idx_name}' already exists.");
+    }
+    let table_ref_id = program.table_reference_counter.next();
+    let columns = resolve_sorted_columns(&tbl, columns, table_ref_id)?;
+    if let Some(where_clause) = &where_clause {
+        // Fail early if the predicate of the partial index refers to anything but the table.
+        bind_partial_index_predicate(where_clause, &tbl, table_ref_id)?;
+    }
 
     let idx = Arc::new(Index {
         name: idx_name.clone(),
         table_name: tbl.name.clone(),
         root_page: 0, //  we dont have access till its created, after we parse the schema table
-        columns: columns
-            .iter()
-            .map(|((pos_in_table, col), order)| IndexColumn {
-                name: col.name.as_ref().unwrap().clone(),
-                order: *order,
-                pos_in_table: *pos_in_table,
-                collation: col.collation,
-                default: col.default.clone(),
-            })
-            .collect(),
+        columns,
         unique: unique_if_not_exists.0,
         ephemeral: false,
         has_rowid: tbl.has_rowid,
+        where_clause,
+        stat: None,
     });
 
-    // Allocate the necessary cursors:
-    //
-    // 1. sqlite_schema_cursor_id - sqlite_schema table
-    // 2. btree_cursor_id         - new index btree
-    // 3. table_cursor_id         - table we are creating the index on
-    // 4. sorter_cursor_id        - sorter
-    // 5. pseudo_cursor_id        - pseudo table to store the sorted index values
-    let sqlite_table = schema.get_btree_table(SQLITE_TABLEID).unwrap();
+    let sqlite_table = schema_table(schema, db);
     let sqlite_schema_cursor_id =
         program.alloc_cursor_id(CursorType::BTreeTable(sqlite_table.clone()));
-    let btree_cursor_id = program.alloc_cursor_id(CursorType::BTreeIndex(idx.clone()));
-    let table_cursor_id = program.alloc_cursor_id(CursorType::BTreeTable(tbl.clone()));
-    let sorter_cursor_id = program.alloc_cursor_id(CursorType::Sorter);
-    let pseudo_table = PseudoTable::new_with_columns(tbl.columns.clone());
-    let pseudo_cursor_id = program.alloc_cursor_id(CursorType::Pseudo(pseudo_table.into()));
 
     // Create a new B-Tree and store the root page index in a register
     let root_page_reg = program.alloc_register();
     program.emit_insn(Insn::CreateBtree {
-        db: 0,
+        db,
         root: root_page_reg,
         flags: CreateBTreeFlags::new_index(),
     });
@@ -97,8 +110,15 @@ pub fn translate_create_index(
         cursor_id: sqlite_schema_cursor_id,
         root_page: RegisterOrLiteral::Literal(sqlite_table.root_page),
         name: sqlite_table.name.clone(),
+        db,
     });
-    let sql = create_idx_stmt_to_sql(&tbl_name, &idx_name, unique_if_not_exists, &columns);
+    let sql = create_idx_stmt_to_sql(
+        &tbl_name,
+        &idx_name,
+        unique_if_not_exists,
+        &idx.columns,
+        idx.where_clause.as_deref(),
+    );
     emit_schema_entry(
         &mut program,
         sqlite_schema_cursor_id,
@@ -109,29 +129,88 @@ pub fn translate_create_index(
         Some(sql),
     );
 
+    // Fill the new index with the rows of the table. The schema table is kept open to emit ParseSchema.
+    let resolver = Resolver::new(schema, syms);
+    emit_index_build(
+        &mut program,
+        &resolver,
+        &tbl,
+        &idx,
+        table_ref_id,
+        RegisterOrLiteral::Register(root_page_reg),
+    )?;
+
+    emit_schema_cookie_change(&mut program, schema, db);
+
+    // Parse the schema table to get the index root page and add new index to Schema
+    let parse_schema_where_clause = format!("name = '{}' AND type = 'index'", idx_name);
+    program.emit_insn(Insn::ParseSchema {
+        db,
+        where_clause: Some(parse_schema_where_clause),
+    });
+    // Close the final sqlite_schema cursor
+    program.emit_insn(Insn::Close {
+        cursor_id: sqlite_schema_cursor_id,
+    });
+
+    // Epilogue:
+    program.epilogue(super::emitter::TransactionMode::Write);
+
+    Ok(program)
+}
+
+/// Fills the empty b-tree of `idx`, whose root page is given by `root_page`, with the entries of the
+/// rows of `tbl`: the entries are first collected in a sorter and then appended to the index in order.
+pub fn emit_index_build(
+    program: &mut ProgramBuilder,
+    resolver: &Resolver,
+    tbl: &Rc<BTreeTable>,
+    idx: &Arc<Index>,
+    table_ref_id: TableInternalId,
+    root_page: RegisterOrLiteral<usize>,
+) -> crate::Result<()> {
+    let db = tbl.db();
+    // Allocate the necessary cursors:
+    //
+    // 1. btree_cursor_id  - index btree
+    // 2. table_cursor_id  - table the index is on
+    // 3. sorter_cursor_id - sorter
+    // 4. pseudo_cursor_id - pseudo table to store the sorted index values
+    let btree_cursor_id = program.alloc_cursor_id(CursorType::BTreeIndex(idx.clone()));
+    let table_cursor_id = program.alloc_cursor_id_keyed(
+        CursorKey::table(table_ref_id),
+        CursorType::BTreeTable(tbl.clone()),
+    );
+    let sorter_cursor_id = program.alloc_cursor_id(CursorType::Sorter);
+    let pseudo_table = PseudoTable::new_with_columns(tbl.columns.clone());
+    let pseudo_cursor_id = program.alloc_cursor_id(CursorType::Pseudo(pseudo_table.into()));
+
     // determine the order of the columns in the index for the sorter
     let order = idx.columns.iter().map(|c| c.order.clone()).collect();
+    let collations = idx.columns.iter().map(|c| c.collation).collect();
     // open the sorter and the pseudo table
     program.emit_insn(Insn::SorterOpen {
         cursor_id: sorter_cursor_id,
-        columns: columns.len(),
+        columns: idx.columns.len(),
         order,
-        collations: tbl.column_collations(),
+        collations,
     });
     let content_reg = program.alloc_register();
     program.emit_insn(Insn::OpenPseudo {
         cursor_id: pseudo_cursor_id,
         content_reg,
-        num_fields: columns.len() + 1,
+        num_fields: idx.columns.len() + 1,
     });
 
     // open the table we are creating the index on for reading
     program.emit_insn(Insn::OpenRead {
         cursor_id: table_cursor_id,
         root_page: tbl.root_page,
+        db,
     });
 
     let loop_start_label = program.allocate_label();
+    let loop_next_label = program.allocate_label();
     let loop_end_label = program.allocate_label();
     program.emit_insn(Insn::Rewind {
         cursor_id: table_cursor_id,
@@ -139,16 +218,31 @@ pub fn translate_create_index(
     });
     program.preassign_label_to_next_insn(loop_start_label);
 
+    // Rows that don't satisfy the WHERE clause of a partial index are left out.
+    emit_partial_index_check(
+        program,
+        resolver,
+        tbl,
+        idx,
+        IndexRow::Cursor(table_ref_id),
+        loop_next_label,
+    )?;
+
     // Loop start:
     // Collect index values into start_reg..rowid_reg
     // emit MakeRecord (index key + rowid) into record_reg.
     //
     // Then insert the record into the sorter
-    let start_reg = program.alloc_registers(columns.len() + 1);
-    for (i, (col, _)) in columns.iter().enumerate() {
-        program.emit_column(table_cursor_id, col.0, start_reg + i);
-    }
-    let rowid_reg = start_reg + columns.len();
+    let start_reg = program.alloc_registers(idx.columns.len() + 1);
+    emit_index_key_columns(
+        program,
+        resolver,
+        tbl,
+        idx,
+        IndexRow::Cursor(table_ref_id),
+        start_reg,
+    )?;
+    let rowid_reg = start_reg + idx.columns.len();
     program.emit_insn(Insn::RowId {
         cursor_id: table_cursor_id,
         dest: rowid_reg,
@@ -156,15 +250,16 @@ pub fn translate_create_index(
     let record_reg = program.alloc_register();
     program.emit_insn(Insn::MakeRecord {
         start_reg,
-        count: columns.len() + 1,
+        count: idx.columns.len() + 1,
         dest_reg: record_reg,
-        index_name: Some(idx_name.clone()),
+        index_name: Some(idx.name.clone()),
     });
     program.emit_insn(Insn::SorterInsert {
         cursor_id: sorter_cursor_id,
         record_reg,
     });
 
+    program.preassign_label_to_next_insn(loop_next_label);
     program.emit_insn(Insn::Next {
         cursor_id: table_cursor_id,
         pc_if_next: loop_start_label,
@@ -175,8 +270,9 @@ pub fn translate_create_index(
     // newly sorted index records.
     program.emit_insn(Insn::OpenWrite {
         cursor_id: btree_cursor_id,
-        root_page: RegisterOrLiteral::Register(root_page_reg),
-        name: idx_name.clone(),
+        root_page,
+        name: idx.name.clone(),
+        db,
     });
 
     let sorted_loop_start = program.allocate_label();
@@ -195,6 +291,32 @@ pub fn translate_create_index(
         dest_reg: sorted_record_reg,
     });
 
+    // Like in SQLite, a unique index can't be built over rows that have the same key. The entries are
+    // appended in order, so a duplicate key is already in the index when the next entry comes.
+    if idx.unique {
+        let key_start_reg = program.alloc_registers(idx.columns.len());
+        for i in 0..idx.columns.len() {
+            program.emit_insn(Insn::Column {
+                cursor_id: pseudo_cursor_id,
+                column: i,
+                dest: key_start_reg + i,
+                default: None,
+            });
+        }
+        let label_no_conflict = program.allocate_label();
+        program.emit_insn(Insn::NoConflict {
+            cursor_id: btree_cursor_id,
+            target_pc: label_no_conflict,
+            record_reg: key_start_reg,
+            num_regs: idx.columns.len(),
+        });
+        program.emit_insn(Insn::Halt {
+            err_code: SQLITE_CONSTRAINT_PRIMARYKEY,
+            description: unique_index_violation_description(tbl, idx),
+        });
+        program.preassign_label_to_next_insn(label_no_conflict);
+    }
+
     // seek to the end of the index btree to position the cursor for appending
     program.emit_insn(Insn::SeekEnd {
         cursor_id: btree_cursor_id,
@@ -213,49 +335,41 @@ pub fn translate_create_index(
     });
     program.preassign_label_to_next_insn(sorted_loop_end);
 
-    // End of the outer loop
-    //
-    // Keep schema table open to emit ParseSchema, close the other cursors.
     program.close_cursors(&[sorter_cursor_id, table_cursor_id, btree_cursor_id]);
 
-    // TODO: SetCookie for schema change
-    //
-    // Parse the schema table to get the index root page and add new index to Schema
-    let parse_schema_where_clause = format!("name = '{}' AND type = 'index'", idx_name);
-    program.emit_insn(Insn::ParseSchema {
-        db: sqlite_schema_cursor_id,
-        where_clause: Some(parse_schema_where_clause),
-    });
-    // Close the final sqlite_schema cursor
-    program.emit_insn(Insn::Close {
-        cursor_id: sqlite_schema_cursor_id,
-    });
-
-    // Epilogue:
-    program.epilogue(super::emitter::TransactionMode::Write);
-
-    Ok(program)
+    Ok(())
 }
 
-fn resolve_sorted_columns<'a>(
-    table: &'a BTreeTable,
+/// Resolves the indexed columns of a CREATE INDEX statement, which are either columns of the table
+/// or expressions over them. Expressions are bound to `table_ref_id` to check that they are valid.
+fn resolve_sorted_columns(
+    table: &Rc<BTreeTable>,
     cols: &[SortedColumn],
-) -> crate::Result<Vec<((usize, &'a Column), SortOrder)>> {
+    table_ref_id: TableInternalId,
+) -> crate::Result<Vec<IndexColumn>> {
     let mut resolved = Vec::with_capacity(cols.len());
     for sc in cols {
-        let ident = normalize_ident(match &sc.expr {
-            // SQLite supports indexes on arbitrary expressions, but we don't (yet).
+        let order = sc.order.unwrap_or(SortOrder::Asc);
+        let Some(ident) = indexed_column_name(&sc.expr) else {
             // See "How to use indexes on expressions" in https://www.sqlite.org/expridx.html
-            Expr::Id(Id(col_name)) | Expr::Name(ast::Name(col_name)) => col_name,
-            _ => crate::bail_parse_error!("Error: cannot use expressions in CREATE INDEX"),
-        });
-        let Some(col) = table.get_column(&ident) else {
+            bind_index_expr(&sc.expr, table, table_ref_id)?;
+            resolved.push(IndexColumn::from_expr(sc.expr.clone(), order)?);
+            continue;
+        };
+        let Some((pos_in_table, col)) = table.get_column(&ident) else {
             crate::bail_parse_error!(
                 "Error: column '{ident}' does not exist in table '{}'",
                 table.name
             );
         };
-        resolved.push((col, sc.order.unwrap_or(SortOrder::Asc)));
+        resolved.push(IndexColumn {
+            name: col.name.as_ref().unwrap().clone(),
+            order,
+            pos_in_table,
+            collation: col.collation,
+            default: col.default.clone(),
+            expr: None,
+        });
     }
     Ok(resolved)
 }
@@ -264,7 +378,8 @@ fn create_idx_stmt_to_sql(
     tbl_name: &str,
     idx_name: &str,
     unique_if_not_exists: (bool, bool),
-    cols: &[((usize, &Column), SortOrder)],
+    cols: &[IndexColumn],
+    where_clause: Option<&Expr>,
 ) -> String {
     let mut sql = String::with_capacity(128);
     sql.push_str("CREATE ");
@@ -279,22 +394,324 @@ fn create_idx_stmt_to_sql(
     sql.push_str(" ON ");
     sql.push_str(tbl_name);
     sql.push_str(" (");
-    for (i, (col, order)) in cols.iter().enumerate() {
+    for (i, col) in cols.iter().enumerate() {
         if i > 0 {
             sql.push_str(", ");
         }
-        sql.push_str(col.1.name.as_ref().unwrap());
-        if *order == SortOrder::Desc {
+        match &col.expr {
+            Some(expr) => sql.push_str(&expr.to_string()),
+            None => sql.push_str(&col.name),
+        }
+        if col.order == SortOrder::Desc {
             sql.push_str(" DESC");
         }
     }
     sql.push(')');
+    if let Some(where_clause) = where_clause {
+        sql.push_str(" WHERE ");
+        sql.push_str(&where_clause.to_string());
+    }
     sql
 }
 
+/// Where the row of a table that is checked against the WHERE clause of a partial index,
+/// or whose index key is computed, is read from.
+#[derive(Clone, Copy)]
+pub enum IndexRow {
+    /// The row the table cursor of the table reference is positioned on.
+    Cursor(TableInternalId),
+    /// Registers holding the columns of the row in table order, and the register holding its rowid.
+    Registers {
+        columns_start_reg: usize,
+        rowid_reg: usize,
+    },
+}
+
+/// Calls `f` with what is needed to translate an expression over the columns of `table` for `row`:
+/// the table reference the expression must be bound to, the table references to translate it with,
+/// and a resolver that reads the columns from the registers of the row if it is in registers.
+fn with_index_row<T>(
+    program: &mut ProgramBuilder,
+    resolver: &Resolver,
+    table: &Rc<BTreeTable>,
+    row: IndexRow,
+    f: impl FnOnce(
+        &mut ProgramBuilder,
+        TableInternalId,
+        &TableReferences,
+        &Resolver,
+    ) -> crate::Result<T>,
+) -> crate::Result<T> {
+    let table_ref_id = match row {
+        IndexRow::Cursor(table_ref_id) => table_ref_id,
+        IndexRow::Registers { .. } => program.table_reference_counter.next(),
+    };
+    let table_references = partial_index_table_references(table, table_ref_id);
+
+    // When the row is in registers, the column references of the expression are resolved to them.
+    let row_registers = match row {
+        IndexRow::Cursor(_) => vec![],
+        IndexRow::Registers {
+            columns_start_reg,
+            rowid_reg,
+        } => table
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let column_expr = Expr::Column {
+                    database: None,
+                    table: table_ref_id,
+                    column: i,
+                    is_rowid_alias: column.is_rowid_alias,
+                };
+                if column.is_rowid_alias {
+                    (column_expr, rowid_reg)
+                } else {
+                    (column_expr, columns_start_reg + i)
+                }
+            })
+            .chain(std::iter::once((
+                Expr::RowId {
+                    database: None,
+                    table: table_ref_id,
+                },
+                rowid_reg,
+            )))
+            .collect(),
+    };
+    let mut row_resolver = Resolver::new(resolver.schema, resolver.symbol_table);
+    if !row_registers.is_empty() {
+        row_resolver.enable_expr_to_reg_cache();
+        row_resolver
+            .expr_to_reg_cache
+            .extend(row_registers.iter().map(|(expr, reg)| (expr, *reg)));
+    }
+    f(program, table_ref_id, &table_references, &row_resolver)
+}
+
+/// Emits a jump to `skip_label` if the row does not belong in the partial `index`,
+/// i.e. if the WHERE clause of the index is false or NULL for it.
+/// Nothing is emitted for an index that is not partial.
+pub fn emit_partial_index_check(
+    program: &mut ProgramBuilder,
+    resolver: &Resolver,
+    table: &Rc<BTreeTable>,
+    index: &Index,
+    row: IndexRow,
+    skip_label: BranchOffset,
+) -> crate::Result<()> {
+    let Some(where_clause) = &index.where_clause else {
+        return Ok(());
+    };
+    with_index_row(
+        program,
+        resolver,
+        table,
+        row,
+        |program, table_ref_id, table_references, row_resolver| {
+            let predicate = bind_partial_index_predicate(where_clause, table, table_ref_id)?;
+            let jump_target_when_true = program.allocate_label();
+            translate_condition_expr(
+                program,
+                table_references,
+                &predicate,
+                ConditionMetadata {
+                    jump_if_condition_is_true: false,
+                    jump_target_when_true,
+                    jump_target_when_false: skip_label,
+                },
+                row_resolver,
+            )?;
+            program.preassign_label_to_next_insn(jump_target_when_true);
+            Ok(())
+        },
+    )
+}
+
+/// Emits the key columns of `index` for the row into the registers starting at `start_reg`,
+/// in index order. The value of a column on an expression is computed from the row.
+pub fn emit_index_key_columns(
+    program: &mut ProgramBuilder,
+    resolver: &Resolver,
+    table: &Rc<BTreeTable>,
+    index: &Index,
+    row: IndexRow,
+    start_reg: usize,
+) -> crate::Result<()> {
+    with_index_row(
+        program,
+        resolver,
+        table,
+        row,
+        |program, table_ref_id, table_references, row_resolver| {
+            for (i, column) in index.columns.iter().enumerate() {
+                let dest_reg = start_reg + i;
+                match (&column.expr, row) {
+                    (Some(expr), _) => {
+                        let expr = bind_index_expr(expr, table, table_ref_id)?;
+                        translate_expr(
+                            program,
+                            Some(table_references),
+                            &expr,
+                            dest_reg,
+                            row_resolver,
+                        )?;
+                    }
+                    (None, IndexRow::Cursor(table_ref_id)) => {
+                        let cursor_id = program.resolve_cursor_id(&CursorKey::table(table_ref_id));
+                        program.emit_column(cursor_id, column.pos_in_table, dest_reg);
+                    }
+                    (
+                        None,
+                        IndexRow::Registers {
+                            columns_start_reg, ..
+                        },
+                    ) => {
+                        program.emit_insn(Insn::Copy {
+                            src_reg: columns_start_reg + column.pos_in_table,
+                            dst_reg: dest_reg,
+                            amount: 0,
+                        });
+                    }
+                }
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Returns the description of a violation of the UNIQUE constraint of `index`, as in SQLite:
+/// the indexed columns, or the name of the index if it is on expressions.
+pub fn unique_index_violation_description(table: &BTreeTable, index: &Index) -> String {
+    if index.has_expressions() {
+        return format!("index '{}'", index.name);
+    }
+    index
+        .columns
+        .iter()
+        .map(|col| {
+            let name = table.columns[col.pos_in_table]
+                .name
+                .as_deref()
+                .unwrap_or(&col.name);
+            format!("{}.{}", table.name, name)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the WHERE clause of a partial index with its column references bound to
+/// the table reference `table_ref_id`, in the form the optimizer and the emitter expect.
+pub fn bind_partial_index_predicate(
+    where_clause: &Expr,
+    table: &Rc<BTreeTable>,
+    table_ref_id: TableInternalId,
+) -> crate::Result<Expr> {
+    bind_table_expr(
+        where_clause,
+        table,
+        table_ref_id,
+        "partial index WHERE clauses",
+    )
+}
+
+/// Returns the expression of a column of an index on expressions with its column references bound to
+/// the table reference `table_ref_id`, in the form the optimizer and the emitter expect.
+pub fn bind_index_expr(
+    expr: &Expr,
+    table: &Rc<BTreeTable>,
+    table_ref_id: TableInternalId,
+) -> crate::Result<Expr> {
+    bind_table_expr(expr, table, table_ref_id, "index expressions")
+}
+
+/// Returns the positions in `table` of the columns the value of an index column depends on:
+/// the indexed column itself, or the columns its expression refers to.
+pub fn index_column_dependencies(
+    table: &Rc<BTreeTable>,
+    column: &IndexColumn,
+) -> crate::Result<Vec<usize>> {
+    let Some(expr) = &column.expr else {
+        return Ok(vec![column.pos_in_table]);
+    };
+    let expr = bind_index_expr(expr, table, TableInternalId::default())?;
+    let mut dependencies = Vec::new();
+    walk_expr(&expr, &mut |expr: &Expr| -> crate::Result<WalkControl> {
+        match expr {
+            Expr::Column { column, .. } => dependencies.push(*column),
+            Expr::RowId { .. } => {
+                dependencies.extend(table.get_rowid_alias_column().map(|(pos, _)| pos))
+            }
+            _ => {}
+        }
+        Ok(WalkControl::Continue)
+    })?;
+    Ok(dependencies)
+}
+
+/// Binds an expression that an index stores in the schema, such as the WHERE clause of a partial index,
+/// to the table reference `table_ref_id` after checking that it only uses what such an expression may:
+/// `what` names the kind of expression in the errors.
+fn bind_table_expr(
+    expr: &Expr,
+    table: &Rc<BTreeTable>,
+    table_ref_id: TableInternalId,
+    what: &str,
+) -> crate::Result<Expr> {
+    walk_expr(expr, &mut |expr: &Expr| -> crate::Result<WalkControl> {
+        match expr {
+            Expr::Variable(_) => {
+                crate::bail_parse_error!("parameters prohibited in {what}")
+            }
+            Expr::Exists(_) | Expr::InSelect { .. } | Expr::Subquery(_) => {
+                crate::bail_parse_error!("subqueries prohibited in {what}")
+            }
+            Expr::FunctionCall { name, args, .. } => {
+                // Functions unknown here are external ones, which are reported when translated if missing.
+                if let Ok(func) =
+                    Func::resolve_function(&name.0, args.as_ref().map_or(0, |args| args.len()))
+                {
+                    if !func.is_deterministic() {
+                        crate::bail_parse_error!("non-deterministic functions prohibited in {what}")
+                    }
+                }
+                Ok(WalkControl::Continue)
+            }
+            _ => Ok(WalkControl::Continue),
+        }
+    })?;
+    let mut bound = expr.clone();
+    rewrite_expr(&mut bound, &mut 1)?;
+    let mut table_references = partial_index_table_references(table, table_ref_id);
+    bind_column_references(&mut bound, &mut table_references, None)?;
+    Ok(bound)
+}
+
+fn partial_index_table_references(
+    table: &Rc<BTreeTable>,
+    table_ref_id: TableInternalId,
+) -> TableReferences {
+    TableReferences::new(
+        vec![JoinedTable {
+            op: Operation::Scan {
+                iter_dir: IterationDirection::Forwards,
+                index: None,
+            },
+            table: Table::BTree(table.clone()),
+            identifier: table.name.clone(),
+            internal_id: table_ref_id,
+            join_info: None,
+            col_used_mask: ColumnUsedMask::new(),
+            index_hint: None,
+        }],
+        vec![],
+    )
+}
+
 pub fn translate_drop_index(
     mode: QueryMode,
-    idx_name: &str,
+    qualified_name: &QualifiedName,
     if_exists: bool,
     schema: &Schema,
     mut program: ProgramBuilder,
@@ -302,7 +719,7 @@ pub fn translate_drop_index(
     if cfg!(not(feature = "index_experimental")) {
         crate::bail_parse_error!("DROP INDEX enabled only with index_experimental feature");
     }
-    let idx_name = normalize_ident(idx_name);
+    let idx_name = normalize_ident(&qualified_name.name.0);
     let opts = crate::vdbe::builder::ProgramBuilderOpts {
         query_mode: mode,
         num_cursors: 5,
@@ -312,18 +729,7 @@ pub fn translate_drop_index(
     program.extend(&opts);
 
     // Find the index in Schema
-    let mut maybe_index = None;
-    for val in schema.indexes.values() {
-        if maybe_index.is_some() {
-            break;
-        }
-        for idx in val {
-            if idx.name == idx_name {
-                maybe_index = Some(idx);
-                break;
-            }
-        }
-    }
+    let maybe_index = schema.find_qualified_index(qualified_name)?;
 
     // If there's no index if_exist is true,
     // then return normaly, otherwise show an error.
@@ -352,16 +758,19 @@ pub fn translate_drop_index(
     // for r[5]=rowid
     let row_id_reg = program.alloc_register();
 
+    let (db, index) = maybe_index.unwrap();
+
     // We're going to use this cursor to search through sqlite_schema
-    let sqlite_table = schema.get_btree_table(SQLITE_TABLEID).unwrap();
+    let sqlite_table = schema_table(schema, db);
     let sqlite_schema_cursor_id =
         program.alloc_cursor_id(CursorType::BTreeTable(sqlite_table.clone()));
 
-    // Open root=1 iDb=0; sqlite_schema for writing
+    // Open root=1; sqlite_schema for writing
     program.emit_insn(Insn::OpenWrite {
         cursor_id: sqlite_schema_cursor_id,
         root_page: RegisterOrLiteral::Literal(sqlite_table.root_page),
         name: sqlite_table.name.clone(),
+        db,
     });
 
     let loop_start_label = program.allocate_label();
@@ -412,6 +821,8 @@ pub fn translate_drop_index(
 
     program.emit_insn(Insn::Delete {
         cursor_id: sqlite_schema_cursor_id,
+        table_name: SQLITE_TABLEID.to_string(),
+        is_part_of_update: false,
     });
 
     program.resolve_label(next_label, program.offset());
@@ -422,28 +833,125 @@ pub fn translate_drop_index(
 
     program.resolve_label(loop_end_label, program.offset());
 
-    /* TODO do set cookie for real
-    program.emit_insn(Insn::SetCookie {
-        ...
-    });
-    */
+    emit_schema_cookie_change(&mut program, schema, db);
 
     // Destroy index btree
     program.emit_insn(Insn::Destroy {
-        root: maybe_index.unwrap().root_page,
+        root: index.root_page,
         former_root_reg: 0,
-        is_temp: 0,
+        is_temp: db,
     });
 
     // Remove from the Schema any mention of the index
-    if let Some(idx) = maybe_index {
-        program.emit_insn(Insn::DropIndex {
-            index: idx.clone(),
-            db: 0,
+    program.emit_insn(Insn::DropIndex {
+        index: index.clone(),
+        db,
+    });
+
+    // Epilogue:
+    program.epilogue(super::emitter::TransactionMode::Write);
+
+    Ok(program)
+}
+
+/// Translates REINDEX, which rebuilds from scratch all the indexes, the indexes of a table, a single
+/// index or, like in SQLite, the indexes having a column that uses a given collation sequence.
+pub fn translate_reindex(
+    mode: QueryMode,
+    obj_name: Option<QualifiedName>,
+    schema: &Schema,
+    syms: &SymbolTable,
+    mut program: ProgramBuilder,
+) -> crate::Result<ProgramBuilder> {
+    if cfg!(not(feature = "index_experimental")) {
+        crate::bail_parse_error!("REINDEX enabled only with index_experimental feature");
+    }
+    let opts = crate::vdbe::builder::ProgramBuilderOpts {
+        query_mode: mode,
+        num_cursors: 4,
+        approx_num_insns: 40,
+        approx_num_labels: 5,
+    };
+    program.extend(&opts);
+
+    let db = match obj_name.as_ref().and_then(|name| name.db_name.as_ref()) {
+        None => None,
+        Some(db_name) => Some(database_index(db_name)?),
+    };
+    // The indexes of the tables of the database named by the statement, if any, sorted by table
+    // name for the program to be deterministic.
+    let mut tables = schema
+        .btree_tables()
+        .into_iter()
+        .filter(|table| db.is_none_or(|db| table.db() == db))
+        .collect::<Vec<_>>();
+    tables.sort_by(|a, b| (a.db(), &a.name).cmp(&(b.db(), &b.name)));
+    let indexes = tables
+        .iter()
+        .flat_map(|table| {
+            schema
+                .get_table_indices(table)
+                .iter()
+                .map(move |index| (table.clone(), index.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    let indexes = match &obj_name {
+        None => indexes,
+        Some(obj_name) => {
+            let name = normalize_ident(&obj_name.name.0);
+            // A name that is not qualified by a database is first looked up as a collation.
+            let collation = match obj_name.db_name {
+                None => CollationSeq::new(&name).ok(),
+                Some(_) => None,
+            };
+            if let Some(collation) = collation {
+                indexes
+                    .into_iter()
+                    .filter(|(_, index)| {
+                        index
+                            .columns
+                            .iter()
+                            .any(|column| column.collation.unwrap_or_default() == collation)
+                    })
+                    .collect()
+            } else if let Some(target) = schema
+                .get_qualified_table(obj_name)?
+                .and_then(|table| table.btree())
+            {
+                indexes
+                    .into_iter()
+                    .filter(|(table, _)| table.name == target.name && table.db() == target.db())
+                    .collect()
+            } else if let Some((db, _)) = schema.find_qualified_index(obj_name)? {
+                indexes
+                    .into_iter()
+                    .filter(|(table, index)| index.name == name && table.db() == db)
+                    .collect()
+            } else {
+                crate::bail_parse_error!("unable to identify the object to be reindexed");
+            }
+        }
+    };
+
+    // Empty each index and fill it again with the rows of its table.
+    let resolver = Resolver::new(schema, syms);
+    for (table, index) in indexes.iter() {
+        program.emit_insn(Insn::Clear {
+            root: index.root_page,
+            db: table.db(),
         });
+        let table_ref_id = program.table_reference_counter.next();
+        emit_index_build(
+            &mut program,
+            &resolver,
+            table,
+            index,
+            table_ref_id,
+            RegisterOrLiteral::Literal(index.root_page),
+        )?;
     }
 
-    // Epilogue:
     program.epilogue(super::emitter::TransactionMode::Write);
 
     Ok(program)
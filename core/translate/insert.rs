@@ -5,10 +5,10 @@ use limbo_sqlite3_parser::ast::{
 };
 
 use crate::error::{SQLITE_CONSTRAINT_NOTNULL, SQLITE_CONSTRAINT_PRIMARYKEY};
-use crate::schema::{IndexColumn, Table};
+use crate::schema::Table;
 use crate::util::normalize_ident;
 use crate::vdbe::builder::{ProgramBuilderOpts, QueryMode};
-use crate::vdbe::insn::{IdxInsertFlags, InsertFlags, RegisterOrLiteral};
+use crate::vdbe::insn::{CmpInsFlags, IdxInsertFlags, InsertFlags, RegisterOrLiteral};
 use crate::vdbe::BranchOffset;
 use crate::{
     schema::{Column, Schema},
@@ -21,8 +21,12 @@ use crate::{Result, SymbolTable, VirtualTable};
 
 use super::emitter::Resolver;
 use super::expr::{translate_expr, translate_expr_no_constant_opt, NoConstantOptReason};
+use super::index::{
+    emit_index_key_columns, emit_partial_index_check, unique_index_violation_description, IndexRow,
+};
 use super::optimizer::rewrite_expr;
 use super::plan::QueryDestination;
+use super::schema::SQLITE_SEQUENCE_TABLEID;
 use super::select::translate_select;
 
 struct TempTableCtx {
@@ -31,6 +35,17 @@ struct TempTableCtx {
     loop_end_label: BranchOffset,
 }
 
+/// Registers holding the `sqlite_sequence` row of an AUTOINCREMENT table while inserting.
+struct AutoincrementCtx {
+    /// Cursor on sqlite_sequence
+    cursor_id: usize,
+    /// Name of the table, followed by the largest rowid ever used in the next register
+    name_reg: usize,
+    seq_reg: usize,
+    /// Rowid of the sqlite_sequence row, NULL if there is none yet
+    rowid_reg: usize,
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn translate_insert(
     query_mode: QueryMode,
@@ -69,7 +84,7 @@ pub fn translate_insert(
         }
     }
     let table_name = &tbl_name.name;
-    let table = match schema.get_table(table_name.0.as_str()) {
+    let table = match schema.get_qualified_table(&tbl_name)? {
         Some(table) => table,
         None => crate::bail_parse_error!("no such table: {}", table_name),
     };
@@ -97,6 +112,7 @@ pub fn translate_insert(
     }
 
     let root_page = btree_table.root_page;
+    let db = btree_table.db();
 
     let mut values: Option<Vec<Expr>> = None;
     let inserting_multiple_rows = match &mut body {
@@ -121,6 +137,16 @@ pub fn translate_insert(
     let halt_label = program.allocate_label();
     let loop_start_label = program.allocate_label();
 
+    let autoincrement = if btree_table.has_autoincrement {
+        Some(emit_autoincrement_load(
+            &mut program,
+            schema,
+            &btree_table.name,
+        )?)
+    } else {
+        None
+    };
+
     let mut yield_reg_opt = None;
     let mut temp_table_ctx = None;
     let (num_values, cursor_id) = match body {
@@ -237,12 +263,14 @@ pub fn translate_insert(
                         cursor_id,
                         root_page: RegisterOrLiteral::Literal(root_page),
                         name: table_name.0.clone(),
+                        db,
                     });
                 } else {
                     program.emit_insn(Insn::OpenWrite {
                         cursor_id,
                         root_page: RegisterOrLiteral::Literal(root_page),
                         name: table_name.0.clone(),
+                        db,
                     });
 
                     // Main loop
@@ -267,8 +295,8 @@ pub fn translate_insert(
 
     // allocate cursor id's for each btree index cursor we'll need to populate the indexes
     // (idx name, root_page, idx cursor id)
-    let idx_cursors = schema
-        .get_indices(&table_name.0)
+    let indexes = schema.get_table_indices(&btree_table);
+    let idx_cursors = indexes
         .iter()
         .map(|idx| {
             (
@@ -329,6 +357,7 @@ pub fn translate_insert(
             cursor_id,
             root_page: RegisterOrLiteral::Literal(root_page),
             name: table_name.0.clone(),
+            db,
         });
 
         populate_column_registers(
@@ -346,6 +375,7 @@ pub fn translate_insert(
             cursor_id: idx_cursor.2,
             root_page: idx_cursor.1.into(),
             name: idx_cursor.0.clone(),
+            db,
         });
     }
     // Common record insertion logic for both single and multiple rows
@@ -377,13 +407,19 @@ pub fn translate_insert(
     program.emit_insn(Insn::NewRowid {
         cursor: cursor_id,
         rowid_reg,
-        prev_largest_reg: 0,
+        prev_largest_reg: autoincrement.as_ref().map_or(0, |ctx| ctx.seq_reg),
     });
 
     if let Some(must_be_int_label) = check_rowid_is_integer_label {
         program.resolve_label(must_be_int_label, program.offset());
         // If the user provided a rowid, it must be an integer.
         program.emit_insn(Insn::MustBeInt { reg: rowid_reg });
+        if let Some(ctx) = &autoincrement {
+            program.emit_insn(Insn::MemMax {
+                dest_reg: ctx.seq_reg,
+                src_reg: rowid_reg,
+            });
+        }
     }
 
     // Check uniqueness constraint for rowid if it was provided by user.
@@ -426,29 +462,41 @@ pub fn translate_insert(
         _ => (),
     }
 
-    let index_col_mappings = resolve_indicies_for_insert(schema, table.as_ref(), &column_mappings)?;
-    for index_col_mapping in index_col_mappings {
-        // find which cursor we opened earlier for this index
-        let idx_cursor_id = idx_cursors
+    for &(idx_name, _, idx_cursor_id) in idx_cursors.iter() {
+        let index = indexes
             .iter()
-            .find(|(name, _, _)| *name == &index_col_mapping.idx_name)
-            .map(|(_, _, c_id)| *c_id)
-            .expect("no cursor found for index");
+            .find(|index| &index.name == idx_name)
+            .expect("index should be present");
+        let row = IndexRow::Registers {
+            columns_start_reg: column_registers_start,
+            rowid_reg,
+        };
 
-        let num_cols = index_col_mapping.columns.len();
+        // A row that doesn't satisfy the WHERE clause of a partial index is not added to it.
+        let skip_index_label = program.allocate_label();
+        emit_partial_index_check(
+            &mut program,
+            &resolver,
+            &btree_table,
+            index,
+            row,
+            skip_index_label,
+        )?;
+
+        let num_cols = index.columns.len();
         // allocate scratch registers for the index columns plus rowid
         let idx_start_reg = program.alloc_registers(num_cols + 1);
 
-        // copy each index column from the table's column registers into these scratch regs
-        for (i, col) in index_col_mapping.columns.iter().enumerate() {
-            // copy from the table's column register over to the index's scratch register
-
-            program.emit_insn(Insn::Copy {
-                src_reg: column_registers_start + col.0,
-                dst_reg: idx_start_reg + i,
-                amount: 0,
-            });
-        }
+        // copy each index column from the table's column registers into these scratch regs,
+        // or compute it from them for a column on an expression
+        emit_index_key_columns(
+            &mut program,
+            &resolver,
+            &btree_table,
+            index,
+            row,
+            idx_start_reg,
+        )?;
         // last register is the rowid
         program.emit_insn(Insn::Copy {
             src_reg: rowid_reg,
@@ -456,16 +504,12 @@ pub fn translate_insert(
             amount: 0,
         });
 
-        let index = schema
-            .get_index(&table_name.0, &index_col_mapping.idx_name)
-            .expect("index should be present");
-
         let record_reg = program.alloc_register();
         program.emit_insn(Insn::MakeRecord {
             start_reg: idx_start_reg,
             count: num_cols + 1,
             dest_reg: record_reg,
-            index_name: Some(index_col_mapping.idx_name),
+            index_name: Some(index.name.clone()),
         });
 
         if index.unique {
@@ -476,28 +520,7 @@ pub fn translate_insert(
                 record_reg: idx_start_reg,
                 num_regs: num_cols,
             });
-            let column_names = index_col_mapping.columns.iter().enumerate().fold(
-                String::with_capacity(50),
-                |mut accum, (idx, (index, _))| {
-                    if idx > 0 {
-                        accum.push_str(", ");
-                    }
-
-                    accum.push_str(&btree_table.name);
-                    accum.push('.');
-
-                    let name = btree_table
-                        .columns
-                        .get(*index)
-                        .unwrap()
-                        .name
-                        .as_ref()
-                        .expect("column name is None");
-                    accum.push_str(name);
-
-                    accum
-                },
-            );
+            let column_names = unique_index_violation_description(&btree_table, index);
 
             program.emit_insn(Insn::Halt {
                 err_code: SQLITE_CONSTRAINT_PRIMARYKEY,
@@ -516,6 +539,7 @@ pub fn translate_insert(
             // TODO: figure out how to determine whether or not we need to seek prior to insert.
             flags: IdxInsertFlags::new(),
         });
+        program.preassign_label_to_next_insn(skip_index_label);
     }
 
     for (i, col) in column_mappings
@@ -573,11 +597,107 @@ pub fn translate_insert(
     }
 
     program.resolve_label(halt_label, program.offset());
+    if let Some(ctx) = autoincrement {
+        emit_autoincrement_store(&mut program, &ctx);
+    }
     program.epilogue(super::emitter::TransactionMode::Write);
 
     Ok(program)
 }
 
+/// Opens `sqlite_sequence` and loads the largest rowid ever used by the AUTOINCREMENT table
+/// `table_name`, or 0 if it has no row there yet.
+fn emit_autoincrement_load(
+    program: &mut ProgramBuilder,
+    schema: &Schema,
+    table_name: &str,
+) -> Result<AutoincrementCtx> {
+    let Some(sequence_table) = schema.get_btree_table(SQLITE_SEQUENCE_TABLEID) else {
+        crate::bail_parse_error!("no such table: {}", SQLITE_SEQUENCE_TABLEID);
+    };
+    let cursor_id = program.alloc_cursor_id(CursorType::BTreeTable(sequence_table.clone()));
+    program.emit_insn(Insn::OpenWrite {
+        cursor_id,
+        root_page: RegisterOrLiteral::Literal(sequence_table.root_page),
+        name: SQLITE_SEQUENCE_TABLEID.to_string(),
+        db: sequence_table.db(),
+    });
+    let name_reg = program.alloc_registers(2);
+    let seq_reg = name_reg + 1;
+    let rowid_reg = program.alloc_register();
+    let scratch_reg = program.alloc_register();
+    program.emit_string8(table_name.to_string(), name_reg);
+    program.emit_int(0, seq_reg);
+    program.emit_null(rowid_reg, None);
+
+    let loop_start_label = program.allocate_label();
+    let loop_end_label = program.allocate_label();
+    let next_label = program.allocate_label();
+    program.emit_insn(Insn::Rewind {
+        cursor_id,
+        pc_if_empty: loop_end_label,
+    });
+    program.preassign_label_to_next_insn(loop_start_label);
+    program.emit_column(cursor_id, 0, scratch_reg);
+    program.emit_insn(Insn::Ne {
+        lhs: scratch_reg,
+        rhs: name_reg,
+        target_pc: next_label,
+        flags: CmpInsFlags::default(),
+        collation: program.curr_collation(),
+    });
+    program.emit_insn(Insn::RowId {
+        cursor_id,
+        dest: rowid_reg,
+    });
+    program.emit_column(cursor_id, 1, seq_reg);
+    program.emit_insn(Insn::Goto {
+        target_pc: loop_end_label,
+    });
+    program.preassign_label_to_next_insn(next_label);
+    program.emit_insn(Insn::Next {
+        cursor_id,
+        pc_if_next: loop_start_label,
+    });
+    program.preassign_label_to_next_insn(loop_end_label);
+
+    Ok(AutoincrementCtx {
+        cursor_id,
+        name_reg,
+        seq_reg,
+        rowid_reg,
+    })
+}
+
+/// Writes the largest rowid used by the AUTOINCREMENT table back to `sqlite_sequence`.
+fn emit_autoincrement_store(program: &mut ProgramBuilder, ctx: &AutoincrementCtx) {
+    let has_row_label = program.allocate_label();
+    program.emit_insn(Insn::NotNull {
+        reg: ctx.rowid_reg,
+        target_pc: has_row_label,
+    });
+    program.emit_insn(Insn::NewRowid {
+        cursor: ctx.cursor_id,
+        rowid_reg: ctx.rowid_reg,
+        prev_largest_reg: 0,
+    });
+    program.preassign_label_to_next_insn(has_row_label);
+    let record_reg = program.alloc_register();
+    program.emit_insn(Insn::MakeRecord {
+        start_reg: ctx.name_reg,
+        count: 2,
+        dest_reg: record_reg,
+        index_name: None,
+    });
+    program.emit_insn(Insn::Insert {
+        cursor: ctx.cursor_id,
+        key_reg: ctx.rowid_reg,
+        record_reg,
+        flag: InsertFlags::new(),
+        table_name: SQLITE_SEQUENCE_TABLEID.to_string(),
+    });
+}
+
 #[derive(Debug)]
 /// Represents how a column should be populated during an INSERT.
 /// Contains both the column definition and optionally the index into the VALUES tuple.
@@ -669,69 +789,6 @@ fn resolve_columns_for_insert<'a>(
     Ok(mappings)
 }
 
-/// Represents how a column in an index should be populated during an INSERT.
-/// Similar to ColumnMapping above but includes the index name, as well as multiple
-/// possible value indices for each.
-#[derive(Debug, Default)]
-struct IndexColMapping {
-    idx_name: String,
-    columns: Vec<(usize, IndexColumn)>,
-    value_indicies: Vec<Option<usize>>,
-}
-
-impl IndexColMapping {
-    fn new(name: String) -> Self {
-        IndexColMapping {
-            idx_name: name,
-            ..Default::default()
-        }
-    }
-}
-
-/// Example:
-/// Table 'test': (a, b, c);
-/// Index 'idx': test(a, b);
-///________________________________
-/// Insert (a, c): (2, 3)
-/// Record: (2, NULL, 3)
-/// IndexColMapping: (a, b) = (2, NULL)
-fn resolve_indicies_for_insert(
-    schema: &Schema,
-    table: &Table,
-    columns: &[ColumnMapping<'_>],
-) -> Result<Vec<IndexColMapping>> {
-    let mut index_col_mappings = Vec::new();
-    // Iterate over all indices for this table
-    for index in schema.get_indices(table.get_name()) {
-        let mut idx_map = IndexColMapping::new(index.name.clone());
-        // For each column in the index (in the order defined by the index),
-        // try to find the corresponding column in the insert’s column mapping.
-        for idx_col in &index.columns {
-            let target_name = normalize_ident(idx_col.name.as_str());
-            if let Some((i, col_mapping)) = columns.iter().enumerate().find(|(_, mapping)| {
-                mapping
-                    .column
-                    .name
-                    .as_ref()
-                    .map_or(false, |name| name.eq_ignore_ascii_case(&target_name))
-            }) {
-                idx_map.columns.push((i, idx_col.clone()));
-                idx_map.value_indicies.push(col_mapping.value_index);
-            } else {
-                return Err(crate::LimboError::ParseError(format!(
-                    "Column {} not found in index {}",
-                    target_name, index.name
-                )));
-            }
-        }
-        // Add the mapping if at least one column was found.
-        if !idx_map.columns.is_empty() {
-            index_col_mappings.push(idx_map);
-        }
-    }
-    Ok(index_col_mappings)
-}
-
 fn populate_columns_multiple_rows(
     program: &mut ProgramBuilder,
     column_mappings: &[ColumnMapping],
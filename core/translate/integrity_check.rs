@@ -11,13 +11,9 @@ pub fn translate_integrity_check(
     schema: &Schema,
     program: &mut ProgramBuilder,
 ) -> crate::Result<()> {
-    let mut root_pages = Vec::with_capacity(schema.tables.len() + schema.indexes.len());
-    // Collect root pages to run integrity check on
-    for table in schema.tables.values() {
-        if let crate::schema::Table::BTree(table) = table.as_ref() {
-            root_pages.push(table.root_page);
-        };
-    }
+    // Collect root pages to run integrity check on. Only the main database is checked, temp
+    // tables live in another file.
+    let root_pages = schema.main_table_root_pages();
     let message_register = program.alloc_register();
     program.emit_insn(Insn::IntegrityCk {
         max_errors: MAX_INTEGRITY_CHECK_ERRORS,
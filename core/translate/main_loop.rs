@@ -1,10 +1,10 @@
 use limbo_ext::VTabKind;
-use limbo_sqlite3_parser::ast::{self, SortOrder};
+use limbo_sqlite3_parser::ast::{self, SortOrder, TableInternalId};
 
-use std::sync::Arc;
+use std::{rc::Rc, sync::Arc};
 
 use crate::{
-    schema::{Affinity, Index, IndexColumn, Table},
+    schema::{Affinity, BTreeTable, Index, IndexColumn, Table},
     translate::{
         plan::{DistinctCtx, Distinctness},
         result_row::emit_select_result,
@@ -29,8 +29,9 @@ use super::{
     optimizer::Optimizable,
     order_by::{order_by_sorter_insert, sorter_insert},
     plan::{
-        convert_where_to_vtab_constraint, Aggregate, GroupBy, IterationDirection, JoinOrderMember,
-        Operation, QueryDestination, Search, SeekDef, SelectPlan, TableReferences, WhereTerm,
+        convert_where_to_vtab_constraint, Aggregate, GroupBy, HashJoin, IterationDirection,
+        JoinOrderMember, MultiIndexOr, Operation, QueryDestination, Search, SeekDef, SelectPlan,
+        TableReferences, WhereTerm,
     },
 };
 
@@ -45,6 +46,28 @@ pub struct LeftJoinMetadata {
     pub label_match_flag_check_value: BranchOffset,
 }
 
+/// Metadata for a table accessed with a multi-index OR
+#[derive(Debug, Clone, Copy)]
+pub struct MultiIndexOrMetadata {
+    /// register that holds the RowSet of the rowids already visited by the branches
+    pub reg_rowset: usize,
+    /// register that holds the rowid of the row found by a branch
+    pub reg_rowid: usize,
+    /// register that holds the return address of the loop body, which is a subroutine called by each branch
+    pub reg_body_return: usize,
+}
+
+/// Metadata for a table accessed with a skip-scan of an index, see [Search::Seek]
+#[derive(Debug, Clone, Copy)]
+pub struct SkipScanMetadata {
+    /// register that holds the current value of the leading column of the index, i.e. the first register of the seek key
+    pub reg_leading_value: usize,
+    /// label for the instruction that reads the leading column of the index and seeks the rows with that value
+    pub label_seek: BranchOffset,
+    /// label for the instructions that move the cursor to the next distinct value of the leading column
+    pub label_next_leading_value: BranchOffset,
+}
+
 /// Jump labels for each loop in the query's main execution loop
 #[derive(Debug, Clone, Copy)]
 pub struct LoopLabels {
@@ -83,10 +106,13 @@ pub fn init_distinct(program: &mut ProgramBuilder, plan: &SelectPlan) -> Distinc
                 pos_in_table: i,
                 collation: None, // FIXME: this should be determined based on the result column expression!
                 default: None, // FIXME: this should be determined based on the result column expression!
+                expr: None,
             })
             .collect(),
         unique: false,
         has_rowid: false,
+        where_clause: None,
+        stat: None,
     });
     let cursor_id = program.alloc_cursor_id(CursorType::BTreeIndex(index.clone()));
     let ctx = DistinctCtx {
@@ -139,9 +165,12 @@ pub fn init_loop(
                 pos_in_table: 0,
                 collation: None, // FIXME: this should be inferred from the expression
                 default: None,   // FIXME: this should be inferred from the expression
+                expr: None,
             }],
             has_rowid: false,
+            where_clause: None,
             unique: false,
+            stat: None,
         });
         let cursor_id = program.alloc_cursor_id(CursorType::BTreeIndex(index.clone()));
         if group_by.is_none() {
@@ -173,6 +202,7 @@ pub fn init_loop(
             }
         }
         let (table_cursor_id, index_cursor_id) = table.open_cursors(program, mode)?;
+        let db = table.table.db();
         match &table.op {
             Operation::Scan { index, .. } => match (mode, &table.table) {
                 (OperationMode::SELECT, Table::BTree(btree)) => {
@@ -181,12 +211,14 @@ pub fn init_loop(
                         program.emit_insn(Insn::OpenRead {
                             cursor_id,
                             root_page,
+                            db,
                         });
                     }
                     if let Some(index_cursor_id) = index_cursor_id {
                         program.emit_insn(Insn::OpenRead {
                             cursor_id: index_cursor_id,
                             root_page: index.as_ref().unwrap().root_page,
+                            db,
                         });
                     }
                 }
@@ -197,34 +229,35 @@ pub fn init_loop(
                             .expect("table cursor is always opened in OperationMode::DELETE"),
                         root_page: root_page.into(),
                         name: btree.name.clone(),
+                        db,
                     });
                     if let Some(index_cursor_id) = index_cursor_id {
                         program.emit_insn(Insn::OpenWrite {
                             cursor_id: index_cursor_id,
                             root_page: index.as_ref().unwrap().root_page.into(),
                             name: index.as_ref().unwrap().name.clone(),
+                            db,
                         });
                     }
                     // For delete, we need to open all the other indexes too for writing
-                    if let Some(indexes) = t_ctx.resolver.schema.indexes.get(&btree.name) {
-                        for index in indexes {
-                            if table
-                                .op
-                                .index()
-                                .is_some_and(|table_index| table_index.name == index.name)
-                            {
-                                continue;
-                            }
-                            let cursor_id = program.alloc_cursor_id_keyed(
-                                CursorKey::index(table.internal_id, index.clone()),
-                                CursorType::BTreeIndex(index.clone()),
-                            );
-                            program.emit_insn(Insn::OpenWrite {
-                                cursor_id,
-                                root_page: index.root_page.into(),
-                                name: index.name.clone(),
-                            });
+                    for index in t_ctx.resolver.schema.get_table_indices(btree) {
+                        if table
+                            .op
+                            .index()
+                            .is_some_and(|table_index| table_index.name == index.name)
+                        {
+                            continue;
                         }
+                        let cursor_id = program.alloc_cursor_id_keyed(
+                            CursorKey::index(table.internal_id, index.clone()),
+                            CursorType::BTreeIndex(index.clone()),
+                        );
+                        program.emit_insn(Insn::OpenWrite {
+                            cursor_id,
+                            root_page: index.root_page.into(),
+                            name: index.name.clone(),
+                            db,
+                        });
                     }
                 }
                 (OperationMode::UPDATE, Table::BTree(btree)) => {
@@ -234,12 +267,14 @@ pub fn init_loop(
                             .expect("table cursor is always opened in OperationMode::UPDATE"),
                         root_page: root_page.into(),
                         name: btree.name.clone(),
+                        db,
                     });
                     if let Some(index_cursor_id) = index_cursor_id {
                         program.emit_insn(Insn::OpenWrite {
                             cursor_id: index_cursor_id,
                             root_page: index.as_ref().unwrap().root_page.into(),
                             name: index.as_ref().unwrap().name.clone(),
+                            db,
                         });
                     }
                 }
@@ -257,6 +292,7 @@ pub fn init_loop(
                             program.emit_insn(Insn::OpenRead {
                                 cursor_id: table_cursor_id,
                                 root_page: table.table.get_root_page(),
+                                db,
                             });
                         }
                     }
@@ -266,14 +302,13 @@ pub fn init_loop(
                             cursor_id: table_cursor_id,
                             root_page: table.table.get_root_page().into(),
                             name: table.table.get_name().to_string(),
+                            db,
                         });
                         // For DELETE, we need to open all the indexes for writing
                         // UPDATE opens these in emit_program_for_update() separately
                         if mode == OperationMode::DELETE {
-                            if let Some(indexes) =
-                                t_ctx.resolver.schema.indexes.get(table.table.get_name())
-                            {
-                                for index in indexes {
+                            if let Some(btree) = table.table.btree() {
+                                for index in t_ctx.resolver.schema.get_table_indices(&btree) {
                                     if table
                                         .op
                                         .index()
@@ -289,6 +324,7 @@ pub fn init_loop(
                                         cursor_id,
                                         root_page: index.root_page.into(),
                                         name: index.name.clone(),
+                                        db,
                                     });
                                 }
                             }
@@ -311,6 +347,7 @@ pub fn init_loop(
                                     cursor_id: index_cursor_id
                                         .expect("index cursor is always opened in Seek with index"),
                                     root_page: index.root_page,
+                                    db,
                                 });
                             }
                             OperationMode::UPDATE | OperationMode::DELETE => {
@@ -319,6 +356,7 @@ pub fn init_loop(
                                         .expect("index cursor is always opened in Seek with index"),
                                     root_page: index.root_page.into(),
                                     name: index.name.clone(),
+                                    db,
                                 });
                             }
                             _ => {
@@ -328,6 +366,37 @@ pub fn init_loop(
                     }
                 }
             }
+            Operation::HashJoin(_) => {
+                // The hash table is opened and filled when its loop is first entered, see open_loop().
+                assert!(
+                    mode == OperationMode::SELECT,
+                    "Hash joins are only used in SELECT queries"
+                );
+            }
+            Operation::MultiIndexOr(multi_index_or) => {
+                assert!(
+                    mode == OperationMode::SELECT,
+                    "Multi-index ORs are only used in SELECT queries"
+                );
+                program.emit_insn(Insn::OpenRead {
+                    cursor_id: table_cursor_id.expect("Multi-index OR requires a table cursor"),
+                    root_page: table.table.get_root_page(),
+                    db,
+                });
+                for index in multi_index_or.indexes() {
+                    program.emit_insn(Insn::OpenRead {
+                        cursor_id: program
+                            .resolve_cursor_id(&CursorKey::index(table.internal_id, index.clone())),
+                        root_page: index.root_page,
+                        db,
+                    });
+                }
+                t_ctx.meta_multi_index_ors[table_index] = Some(MultiIndexOrMetadata {
+                    reg_rowset: program.alloc_register(),
+                    reg_rowid: program.alloc_register(),
+                    reg_body_return: program.alloc_register(),
+                });
+            }
         }
     }
 
@@ -640,11 +709,42 @@ pub fn open_loop(
                     let seek_cursor_id = index_cursor_id.unwrap_or_else(|| {
                         table_cursor_id.expect("Either index or table cursor must be opened")
                     });
-                    let Search::Seek { seek_def, .. } = search else {
+                    let Search::Seek {
+                        seek_def,
+                        skip_scan,
+                        ..
+                    } = search
+                    else {
                         unreachable!("Rowid equality point lookup should have been handled above");
                     };
 
                     let start_reg = program.alloc_registers(seek_def.key.len());
+                    // A skip-scan seeks once per distinct value of the leading column of the index,
+                    // and moves on to the next value instead of exiting the loop when there are no more rows to visit.
+                    let seek_loop_end = if *skip_scan {
+                        let meta = SkipScanMetadata {
+                            reg_leading_value: start_reg,
+                            label_seek: program.allocate_label(),
+                            label_next_leading_value: program.allocate_label(),
+                        };
+                        if seek_def.iter_dir == IterationDirection::Backwards {
+                            program.emit_insn(Insn::Last {
+                                cursor_id: seek_cursor_id,
+                                pc_if_empty: loop_end,
+                            });
+                        } else {
+                            program.emit_insn(Insn::Rewind {
+                                cursor_id: seek_cursor_id,
+                                pc_if_empty: loop_end,
+                            });
+                        }
+                        program.preassign_label_to_next_insn(meta.label_seek);
+                        program.emit_column(seek_cursor_id, 0, meta.reg_leading_value);
+                        t_ctx.meta_skip_scans[joined_table_index] = Some(meta);
+                        meta.label_next_leading_value
+                    } else {
+                        loop_end
+                    };
                     emit_seek(
                         program,
                         table_references,
@@ -652,8 +752,9 @@ pub fn open_loop(
                         t_ctx,
                         seek_cursor_id,
                         start_reg,
-                        loop_end,
+                        seek_loop_end,
                         is_index,
+                        *skip_scan,
                     )?;
                     emit_seek_termination(
                         program,
@@ -663,7 +764,7 @@ pub fn open_loop(
                         seek_cursor_id,
                         start_reg,
                         loop_start,
-                        loop_end,
+                        seek_loop_end,
                         is_index,
                     )?;
 
@@ -678,6 +779,100 @@ pub fn open_loop(
                     }
                 }
 
+                for cond in predicates
+                    .iter()
+                    .filter(|cond| cond.should_eval_at_loop(join_index, join_order))
+                {
+                    let jump_target_when_true = program.allocate_label();
+                    let condition_metadata = ConditionMetadata {
+                        jump_if_condition_is_true: false,
+                        jump_target_when_true,
+                        jump_target_when_false: next,
+                    };
+                    translate_condition_expr(
+                        program,
+                        table_references,
+                        &cond.expr,
+                        condition_metadata,
+                        &t_ctx.resolver,
+                    )?;
+                    program.preassign_label_to_next_insn(jump_target_when_true);
+                }
+            }
+            Operation::HashJoin(hash_join) => {
+                let Table::BTree(btree) = &table.table else {
+                    unreachable!("Hash joins are only used on btree tables");
+                };
+                let hash_table_cursor_id =
+                    table_cursor_id.expect("Hash join requires a hash table cursor");
+                emit_hash_join_build(program, btree, hash_join, hash_table_cursor_id)?;
+
+                // Probe the hash table with the current values of the outer tables' join columns.
+                let num_keys = hash_join.probe_exprs.len();
+                let key_start_reg = program.alloc_registers(num_keys);
+                for (i, expr) in hash_join.probe_exprs.iter().enumerate() {
+                    translate_expr(
+                        program,
+                        Some(table_references),
+                        expr,
+                        key_start_reg + i,
+                        &t_ctx.resolver,
+                    )?;
+                }
+                program.emit_insn(Insn::HashProbe {
+                    cursor_id: hash_table_cursor_id,
+                    key_start_reg,
+                    num_keys,
+                    target_pc: loop_end,
+                });
+                program.preassign_label_to_next_insn(loop_start);
+
+                for cond in predicates
+                    .iter()
+                    .filter(|cond| cond.should_eval_at_loop(join_index, join_order))
+                {
+                    let jump_target_when_true = program.allocate_label();
+                    let condition_metadata = ConditionMetadata {
+                        jump_if_condition_is_true: false,
+                        jump_target_when_true,
+                        jump_target_when_false: next,
+                    };
+                    translate_condition_expr(
+                        program,
+                        table_references,
+                        &cond.expr,
+                        condition_metadata,
+                        &t_ctx.resolver,
+                    )?;
+                    program.preassign_label_to_next_insn(jump_target_when_true);
+                }
+            }
+            Operation::MultiIndexOr(multi_index_or) => {
+                let table_cursor_id =
+                    table_cursor_id.expect("Multi-index OR requires a table cursor");
+                let meta = *t_ctx.meta_multi_index_ors[joined_table_index]
+                    .as_ref()
+                    .expect("multi-index OR metadata must exist");
+                emit_multi_index_or_branches(
+                    program,
+                    t_ctx,
+                    table_references,
+                    table.internal_id,
+                    multi_index_or,
+                    table_cursor_id,
+                    &meta,
+                    loop_start,
+                    loop_end,
+                )?;
+
+                // The loop body is a subroutine, entered with the rowid of a row found by a branch.
+                program.preassign_label_to_next_insn(loop_start);
+                program.emit_insn(Insn::SeekRowid {
+                    cursor_id: table_cursor_id,
+                    src_reg: meta.reg_rowid,
+                    target_pc: next,
+                });
+
                 for cond in predicates
                     .iter()
                     .filter(|cond| cond.should_eval_at_loop(join_index, join_order))
@@ -929,6 +1124,23 @@ fn emit_loop_source<'a>(
                 program.emit_int(1, flag);
             }
 
+            // The rows are visited in the order of the argument of min() or max(),
+            // so the first non-NULL value is the result and the loop can stop there.
+            if plan.min_max_optimized {
+                let arg_reg = program.alloc_register();
+                translate_expr(
+                    program,
+                    Some(&plan.table_references),
+                    &plan.aggregates[0].args[0],
+                    arg_reg,
+                    &t_ctx.resolver,
+                )?;
+                program.emit_insn(Insn::NotNull {
+                    reg: arg_reg,
+                    target_pc: t_ctx.label_main_loop_end.unwrap(),
+                });
+            }
+
             Ok(())
         }
         LoopEmitTarget::QueryResult => {
@@ -1056,9 +1268,58 @@ pub fn close_loop(
                             pc_if_next: loop_labels.loop_start,
                         });
                     }
+                    if let Some(meta) = t_ctx.meta_skip_scans[table_index] {
+                        // Running off the end of the index ends the skip-scan, otherwise the rows of the
+                        // current leading value are exhausted and the scan continues from the first entry
+                        // with the next distinct leading value.
+                        program.emit_insn(Insn::Goto {
+                            target_pc: loop_labels.loop_end,
+                        });
+                        program.preassign_label_to_next_insn(meta.label_next_leading_value);
+                        if iter_dir == IterationDirection::Backwards {
+                            program.emit_insn(Insn::SeekLT {
+                                is_index: true,
+                                cursor_id: iteration_cursor_id,
+                                start_reg: meta.reg_leading_value,
+                                num_regs: 1,
+                                target_pc: loop_labels.loop_end,
+                            });
+                        } else {
+                            program.emit_insn(Insn::SeekGT {
+                                is_index: true,
+                                cursor_id: iteration_cursor_id,
+                                start_reg: meta.reg_leading_value,
+                                num_regs: 1,
+                                target_pc: loop_labels.loop_end,
+                            });
+                        }
+                        program.emit_insn(Insn::Goto {
+                            target_pc: meta.label_seek,
+                        });
+                    }
                 }
                 program.preassign_label_to_next_insn(loop_labels.loop_end);
             }
+            Operation::HashJoin(_) => {
+                program.resolve_label(loop_labels.next, program.offset());
+                program.emit_insn(Insn::HashNext {
+                    cursor_id: table_cursor_id.expect("Hash join requires a hash table cursor"),
+                    pc_if_next: loop_labels.loop_start,
+                });
+                program.preassign_label_to_next_insn(loop_labels.loop_end);
+            }
+            Operation::MultiIndexOr(_) => {
+                // Moving on to the next row returns to the branch that found the current one.
+                let meta = t_ctx.meta_multi_index_ors[table_index]
+                    .as_ref()
+                    .expect("multi-index OR metadata must exist");
+                program.resolve_label(loop_labels.next, program.offset());
+                program.emit_insn(Insn::Return {
+                    return_reg: meta.reg_body_return,
+                    can_fallthrough: false,
+                });
+                program.preassign_label_to_next_insn(loop_labels.loop_end);
+            }
         }
 
         // Handle OUTER JOIN logic. The reason this comes after the "loop end" mark is that we may need to still jump back
@@ -1097,9 +1358,18 @@ pub fn close_loop(
                 // and we will end up back in the IfPos instruction above, which will then
                 // check the match flag again, and since it is now 1, we will jump to the
                 // next row in the left table.
-                program.emit_insn(Insn::Goto {
-                    target_pc: lj_meta.label_match_flag_set_true,
-                });
+                // The loop body of a multi-index OR is a subroutine, so it is called instead,
+                // and returns right past the call, where the match flag is known to be set.
+                if let Some(meta) = t_ctx.meta_multi_index_ors[table_index].as_ref() {
+                    program.emit_insn(Insn::Gosub {
+                        target_pc: lj_meta.label_match_flag_set_true,
+                        return_reg: meta.reg_body_return,
+                    });
+                } else {
+                    program.emit_insn(Insn::Goto {
+                        target_pc: lj_meta.label_match_flag_set_true,
+                    });
+                }
                 program.preassign_label_to_next_insn(label_when_right_table_notnull);
             }
         }
@@ -1107,6 +1377,137 @@ pub fn close_loop(
     Ok(())
 }
 
+/// Emits the branches of a multi-index OR, one search per disjunct of the OR term.
+///
+/// For each row it finds, a branch checks the rowid against the RowSet of the rowids already
+/// found by the previous branches, and if it is new, calls the loop body as a subroutine
+/// starting at `loop_start`. Once the last branch is exhausted, the loop is exited.
+#[allow(clippy::too_many_arguments)]
+fn emit_multi_index_or_branches(
+    program: &mut ProgramBuilder,
+    t_ctx: &mut TranslateCtx,
+    tables: &TableReferences,
+    table_id: TableInternalId,
+    multi_index_or: &MultiIndexOr,
+    table_cursor_id: CursorID,
+    meta: &MultiIndexOrMetadata,
+    loop_start: BranchOffset,
+    loop_end: BranchOffset,
+) -> Result<()> {
+    program.emit_insn(Insn::Null {
+        dest: meta.reg_rowset,
+        dest_end: None,
+    });
+    let num_branches = multi_index_or.branches.len();
+    for (i, branch) in multi_index_or.branches.iter().enumerate() {
+        let branch_loop_start = program.allocate_label();
+        let branch_next = program.allocate_label();
+        let branch_end = program.allocate_label();
+        let iteration = match branch {
+            Search::RowidEq { cmp_expr } => {
+                let src_reg = program.alloc_register();
+                translate_expr(program, Some(tables), cmp_expr, src_reg, &t_ctx.resolver)?;
+                program.emit_insn(Insn::SeekRowid {
+                    cursor_id: table_cursor_id,
+                    src_reg,
+                    target_pc: branch_end,
+                });
+                program.emit_insn(Insn::RowId {
+                    cursor_id: table_cursor_id,
+                    dest: meta.reg_rowid,
+                });
+                None
+            }
+            Search::Seek {
+                index, seek_def, ..
+            } => {
+                let index_cursor_id = index.as_ref().map(|index| {
+                    program.resolve_cursor_id(&CursorKey::index(table_id, index.clone()))
+                });
+                let is_index = index_cursor_id.is_some();
+                let seek_cursor_id = index_cursor_id.unwrap_or(table_cursor_id);
+                let start_reg = program.alloc_registers(seek_def.key.len());
+                emit_seek(
+                    program,
+                    tables,
+                    seek_def,
+                    t_ctx,
+                    seek_cursor_id,
+                    start_reg,
+                    branch_end,
+                    is_index,
+                    false,
+                )?;
+                emit_seek_termination(
+                    program,
+                    tables,
+                    seek_def,
+                    t_ctx,
+                    seek_cursor_id,
+                    start_reg,
+                    branch_loop_start,
+                    branch_end,
+                    is_index,
+                )?;
+                if is_index {
+                    program.emit_insn(Insn::IdxRowId {
+                        cursor_id: seek_cursor_id,
+                        dest: meta.reg_rowid,
+                    });
+                } else {
+                    program.emit_insn(Insn::RowId {
+                        cursor_id: seek_cursor_id,
+                        dest: meta.reg_rowid,
+                    });
+                }
+                Some((seek_cursor_id, seek_def.iter_dir))
+            }
+        };
+
+        // The first branch can't find a row twice, and the rowids found by the last branch
+        // don't need to be remembered, since no branch comes after it.
+        if i == 0 {
+            program.emit_insn(Insn::RowSetAdd {
+                rowset_reg: meta.reg_rowset,
+                rowid_reg: meta.reg_rowid,
+            });
+        } else {
+            program.emit_insn(Insn::RowSetTest {
+                rowset_reg: meta.reg_rowset,
+                target_pc: branch_next,
+                rowid_reg: meta.reg_rowid,
+                set_number: if i == num_branches - 1 { -1 } else { i as i32 },
+            });
+        }
+        program.emit_insn(Insn::Gosub {
+            target_pc: loop_start,
+            return_reg: meta.reg_body_return,
+        });
+
+        program.preassign_label_to_next_insn(branch_next);
+        match iteration {
+            Some((cursor_id, IterationDirection::Backwards)) => {
+                program.emit_insn(Insn::Prev {
+                    cursor_id,
+                    pc_if_prev: branch_loop_start,
+                });
+            }
+            Some((cursor_id, IterationDirection::Forwards)) => {
+                program.emit_insn(Insn::Next {
+                    cursor_id,
+                    pc_if_next: branch_loop_start,
+                });
+            }
+            None => {}
+        }
+        program.preassign_label_to_next_insn(branch_end);
+    }
+    program.emit_insn(Insn::Goto {
+        target_pc: loop_end,
+    });
+    Ok(())
+}
+
 /// Emits instructions for an index seek. See e.g. [crate::translate::plan::SeekDef]
 /// for more details about the seek definition.
 ///
@@ -1116,6 +1517,9 @@ pub fn close_loop(
 ///
 /// If either 1. the seek finds no rows or 2. the termination condition is reached,
 /// the loop for that given table/index is fully exited.
+///
+/// For a skip-scan, the value of the leading column of the index is already in `start_reg`,
+/// and `loop_end` moves on to the next distinct value of that column, see [SkipScanMetadata].
 #[allow(clippy::too_many_arguments)]
 fn emit_seek(
     program: &mut ProgramBuilder,
@@ -1126,6 +1530,7 @@ fn emit_seek(
     start_reg: usize,
     loop_end: BranchOffset,
     is_index: bool,
+    skip_scan: bool,
 ) -> Result<()> {
     let Some(seek) = seek_def.seek.as_ref() else {
         // If there is no seek key, we start from the first or last row of the index,
@@ -1157,6 +1562,9 @@ fn emit_seek(
                     dest_end: None,
                 });
             }
+        } else if skip_scan && i == 0 {
+            // The leading value of a skip-scan was read from the index, and is sought even if it is NULL.
+            continue;
         } else {
             let expr = &seek_def.key[i].0;
             translate_expr_no_constant_opt(
@@ -1273,6 +1681,18 @@ fn emit_seek_termination(
                 dest: reg,
                 dest_end: None,
             });
+        // the last value of the termination key may differ from the one used for the seek, see [SeekDef::termination_last_key].
+        } else if let (true, Some(termination_last_key)) =
+            (is_last, seek_def.termination_last_key.as_ref())
+        {
+            translate_expr_no_constant_opt(
+                program,
+                Some(tables),
+                termination_last_key,
+                reg,
+                &t_ctx.resolver,
+                NoConstantOptReason::RegisterReuse,
+            )?;
         // if the seek key is shorter than the termination key, we need to translate the remaining suffix of the termination key.
         // if not, we just reuse what was emitted for the seek.
         } else if seek_len < termination.len {
@@ -1437,3 +1857,69 @@ fn emit_autoindex(
     program.preassign_label_to_next_insn(label_ephemeral_build_end);
     Ok(index_cursor_id)
 }
+
+/// Emits the build phase of a hash join: the table is scanned and each row is inserted into the
+/// hash table, keyed on the join columns. Like an automatic index, the hash table is only built once,
+/// the first time the loop of the table is entered.
+fn emit_hash_join_build(
+    program: &mut ProgramBuilder,
+    btree: &Rc<BTreeTable>,
+    hash_join: &HashJoin,
+    hash_table_cursor_id: CursorID,
+) -> Result<()> {
+    let label_build_end = program.allocate_label();
+    program.emit_insn(Insn::Once {
+        target_pc_when_reentered: label_build_end,
+    });
+    program.emit_insn(Insn::HashOpen {
+        cursor_id: hash_table_cursor_id,
+    });
+    let build_cursor_id = program.alloc_cursor_id(CursorType::BTreeTable(btree.clone()));
+    program.emit_insn(Insn::OpenRead {
+        cursor_id: build_cursor_id,
+        root_page: btree.root_page,
+        db: btree.db(),
+    });
+    let label_build_loop_start = program.allocate_label();
+    program.emit_insn(Insn::Rewind {
+        cursor_id: build_cursor_id,
+        pc_if_empty: label_build_end,
+    });
+    program.preassign_label_to_next_insn(label_build_loop_start);
+    let num_keys = hash_join.build_columns.len();
+    let key_start_reg = program.alloc_registers(num_keys);
+    for (i, &col) in hash_join.build_columns.iter().enumerate() {
+        let reg = key_start_reg + i;
+        if btree.columns[col].is_rowid_alias {
+            program.emit_insn(Insn::RowId {
+                cursor_id: build_cursor_id,
+                dest: reg,
+            });
+        } else {
+            program.emit_column(build_cursor_id, col, reg);
+        }
+    }
+    let rowid_reg = program.alloc_register();
+    program.emit_insn(Insn::RowId {
+        cursor_id: build_cursor_id,
+        dest: rowid_reg,
+    });
+    let record_reg = program.alloc_register();
+    program.emit_insn(Insn::RowData {
+        cursor_id: build_cursor_id,
+        dest: record_reg,
+    });
+    program.emit_insn(Insn::HashInsert {
+        cursor_id: hash_table_cursor_id,
+        key_start_reg,
+        num_keys,
+        record_reg,
+        rowid_reg,
+    });
+    program.emit_insn(Insn::Next {
+        cursor_id: build_cursor_id,
+        pc_if_next: label_build_loop_start,
+    });
+    program.preassign_label_to_next_insn(label_build_end);
+    Ok(())
+}
@@ -42,7 +42,7 @@ use crate::vdbe::builder::{ProgramBuilder, ProgramBuilderOpts, QueryMode};
 use crate::vdbe::Program;
 use crate::{bail_parse_error, Connection, Result, SymbolTable};
 use alter::translate_alter_table;
-use index::{translate_create_index, translate_drop_index};
+use index::{translate_create_index, translate_drop_index, translate_reindex};
 use insert::translate_insert;
 use limbo_sqlite3_parser::ast::{self, Delete, Insert};
 use schema::{translate_create_table, translate_create_virtual_table, translate_drop_table};
@@ -50,7 +50,7 @@ use select::translate_select;
 use std::rc::Rc;
 use std::sync::Arc;
 use tracing::{instrument, Level};
-use transaction::{translate_tx_begin, translate_tx_commit};
+use transaction::{translate_tx_begin, translate_tx_commit, translate_tx_rollback};
 use update::translate_update;
 
 #[instrument(skip_all, level = Level::TRACE)]
@@ -124,14 +124,16 @@ pub fn translate_inner(
             idx_name,
             tbl_name,
             columns,
-            ..
+            where_clause,
         } => translate_create_index(
             query_mode,
             (unique, if_not_exists),
-            &idx_name.name.0,
+            &idx_name,
             &tbl_name.0,
             &columns,
+            where_clause,
             schema,
+            syms,
             program,
         )?,
         ast::Stmt::CreateTable {
@@ -156,6 +158,7 @@ pub fn translate_inner(
         ast::Stmt::Delete(delete) => {
             let Delete {
                 tbl_name,
+                indexed,
                 where_clause,
                 limit,
                 ..
@@ -164,6 +167,7 @@ pub fn translate_inner(
                 query_mode,
                 schema,
                 &tbl_name,
+                indexed,
                 where_clause,
                 limit,
                 syms,
@@ -174,7 +178,7 @@ pub fn translate_inner(
         ast::Stmt::DropIndex {
             if_exists,
             idx_name,
-        } => translate_drop_index(query_mode, &idx_name.name.0, if_exists, schema, program)?,
+        } => translate_drop_index(query_mode, &idx_name, if_exists, schema, program)?,
         ast::Stmt::DropTable {
             if_exists,
             tbl_name,
@@ -184,9 +188,15 @@ pub fn translate_inner(
         ast::Stmt::Pragma(..) => {
             bail_parse_error!("PRAGMA statement cannot be evaluated in a nested context")
         }
-        ast::Stmt::Reindex { .. } => bail_parse_error!("REINDEX not supported yet"),
+        ast::Stmt::Reindex { obj_name } => {
+            translate_reindex(query_mode, obj_name, schema, syms, program)?
+        }
         ast::Stmt::Release(_) => bail_parse_error!("RELEASE not supported yet"),
-        ast::Stmt::Rollback { .. } => bail_parse_error!("ROLLBACK not supported yet"),
+        ast::Stmt::Rollback {
+            tx_name,
+            savepoint_name: None,
+        } => translate_tx_rollback(tx_name, program)?,
+        ast::Stmt::Rollback { .. } => bail_parse_error!("ROLLBACK TO not supported yet"),
         ast::Stmt::Savepoint(_) => bail_parse_error!("SAVEPOINT not supported yet"),
         ast::Stmt::Select(select) => {
             translate_select(
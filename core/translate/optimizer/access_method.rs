@@ -1,16 +1,27 @@
 use std::sync::Arc;
 
-use limbo_sqlite3_parser::ast::SortOrder;
+use limbo_sqlite3_parser::ast::{self, SortOrder};
 
 use crate::{
     schema::Index,
-    translate::plan::{IterationDirection, JoinOrderMember, JoinedTable},
+    translate::{
+        expr::{as_binary_components, unwrap_parens_owned},
+        index::bind_partial_index_predicate,
+        plan::{IndexHint, IterationDirection, JoinOrderMember, JoinedTable, WhereTerm},
+    },
+    util::exprs_are_equivalent,
     Result,
 };
 
 use super::{
-    constraints::{usable_constraints_for_join_order, ConstraintRef, TableConstraints},
-    cost::{estimate_cost_for_scan_or_seek, Cost, IndexInfo},
+    constraints::{
+        usable_constraints_for_join_order, usable_hash_join_constraints, ConstraintRef,
+        TableConstraints,
+    },
+    cost::{
+        estimate_cost_for_hash_join, estimate_cost_for_scan_or_seek, estimate_cost_for_skip_scan,
+        Cost, IndexInfo, SKIP_SCAN_MIN_ROWS_PER_LEADING_VALUE,
+    },
     order::OrderTarget,
 };
 
@@ -29,11 +40,22 @@ pub struct AccessMethod<'a> {
     /// An empty list of constraint refs means a scan (full table or index);
     /// a non-empty list means a search.
     pub constraint_refs: &'a [ConstraintRef],
+    /// If the table is accessed with a hash join, the positions in [TableConstraints::constraints]
+    /// of the equality constraints that make up the hash key.
+    pub hash_join_constraints: Option<Vec<usize>>,
+    /// If the table is accessed with a multi-index OR, the position in [TableConstraints::or_candidates]
+    /// of the OR term, and the access method of each of its disjuncts.
+    pub or_branches: Option<(usize, Vec<AccessMethod<'a>>)>,
+    /// Whether the index is accessed with a skip-scan, in which case the constraint refs start from
+    /// the second column of the index. See [crate::translate::plan::Search::Seek].
+    pub skip_scan: bool,
 }
 
 impl<'a> AccessMethod<'a> {
     pub fn is_scan(&self) -> bool {
         self.constraint_refs.is_empty()
+            && self.hash_join_constraints.is_none()
+            && self.or_branches.is_none()
     }
 
     pub fn new_table_scan(input_cardinality: f64, iter_dir: IterationDirection) -> Self {
@@ -42,6 +64,9 @@ impl<'a> AccessMethod<'a> {
             iter_dir,
             index: None,
             constraint_refs: &[],
+            hash_join_constraints: None,
+            or_branches: None,
+            skip_scan: false,
         }
     }
 }
@@ -55,12 +80,26 @@ pub fn find_best_access_method_for_join_order<'a>(
     input_cardinality: f64,
 ) -> Result<AccessMethod<'a>> {
     let table_no = join_order.last().unwrap().table_id;
+    let index_hint = rhs_table.index_hint.as_ref();
     let mut best_access_method =
         AccessMethod::new_table_scan(input_cardinality, IterationDirection::Forwards);
+    // With INDEXED BY, the table must be accessed using the index, so a full table scan is not an option.
+    if let Some(IndexHint::IndexedBy(_)) = index_hint {
+        best_access_method.cost = Cost(f64::MAX);
+    }
+    let mut hinted_index_is_usable = false;
     let rowid_column_idx = rhs_table.columns().iter().position(|c| c.is_rowid_alias);
 
     // Estimate cost for each candidate index (including the rowid index) and replace best_access_method if the cost is lower.
     for candidate in rhs_constraints.candidates.iter() {
+        match (index_hint, candidate.index.as_ref()) {
+            (Some(IndexHint::IndexedBy(hinted)), Some(index)) if hinted.name == index.name => {
+                hinted_index_is_usable = true;
+            }
+            (Some(IndexHint::IndexedBy(_)), _) => continue,
+            (Some(IndexHint::NotIndexed), Some(_)) => continue,
+            _ => {}
+        }
         let index_info = match candidate.index.as_ref() {
             Some(index) => IndexInfo {
                 unique: index.unique,
@@ -96,10 +135,11 @@ pub fn find_best_access_method_for_join_order<'a>(
                 let correct_table = order_target.0[i].table_id == table_no;
                 let correct_column = {
                     match &candidate.index {
-                        Some(index) => index.columns[i].pos_in_table == order_target.0[i].column_no,
-                        None => {
-                            rowid_column_idx.map_or(false, |idx| idx == order_target.0[i].column_no)
+                        Some(index) => {
+                            order_target.0[i].is_index_column(&index.columns[i], rhs_table)
                         }
+                        None => rowid_column_idx
+                            .map_or(false, |idx| Some(idx) == order_target.0[i].column_no),
                     }
                 };
                 if !correct_table || !correct_column {
@@ -140,9 +180,193 @@ pub fn find_best_access_method_for_join_order<'a>(
                 index: candidate.index.clone(),
                 iter_dir,
                 constraint_refs: &usable_constraint_refs,
+                hash_join_constraints: None,
+                or_branches: None,
+                skip_scan: false,
+            };
+        }
+
+        let Some(index) = candidate.index.as_ref() else {
+            continue;
+        };
+        if candidate.skip_scan_refs.is_empty() {
+            continue;
+        }
+        let Some(distinct_leading_values) = distinct_leading_values(index) else {
+            continue;
+        };
+        let usable_skip_scan_refs = usable_constraints_for_join_order(
+            &rhs_constraints.constraints,
+            &candidate.skip_scan_refs,
+            join_order,
+        );
+        if usable_skip_scan_refs.is_empty() {
+            continue;
+        }
+        let cost = estimate_cost_for_skip_scan(
+            index_info,
+            &rhs_constraints.constraints,
+            usable_skip_scan_refs,
+            distinct_leading_values,
+            input_cardinality,
+        );
+        // A skip-scan visits the entries of the index in order too, so it satisfies the same order targets.
+        if cost < best_access_method.cost + order_satisfiability_bonus {
+            best_access_method = AccessMethod {
+                cost,
+                index: Some(index.clone()),
+                iter_dir,
+                constraint_refs: usable_skip_scan_refs,
+                hash_join_constraints: None,
+                or_branches: None,
+                skip_scan: true,
+            };
+        }
+    }
+
+    if let Some(hint) = index_hint {
+        // A partial index whose WHERE clause is not implied by the query has no candidate.
+        if matches!(hint, IndexHint::IndexedBy(_)) && !hinted_index_is_usable {
+            crate::bail_parse_error!("no query solution");
+        }
+        // The hash table of a hash join is an automatic index, which is not allowed either.
+        return Ok(best_access_method);
+    }
+
+    // An OR term can be evaluated by searching the table once per disjunct, if each disjunct can use an index,
+    // and visiting the union of the rows found.
+    for (or_candidate_pos, or_candidate) in rhs_constraints.or_candidates.iter().enumerate() {
+        let mut branches = Vec::with_capacity(or_candidate.branches.len());
+        for (_, branch_constraints) in or_candidate.branches.iter() {
+            let branch = find_best_access_method_for_join_order(
+                rhs_table,
+                branch_constraints,
+                join_order,
+                None,
+                input_cardinality,
+            )?;
+            if branch.is_scan() {
+                break;
+            }
+            branches.push(branch);
+        }
+        if branches.len() < or_candidate.branches.len() {
+            continue;
+        }
+        let cost = branches
+            .iter()
+            .fold(Cost(0.0), |cost, branch| cost + branch.cost);
+        if cost < best_access_method.cost {
+            best_access_method = AccessMethod {
+                cost,
+                iter_dir: IterationDirection::Forwards,
+                index: None,
+                constraint_refs: &[],
+                hash_join_constraints: None,
+                or_branches: Some((or_candidate_pos, branches)),
+                skip_scan: false,
+            };
+        }
+    }
+
+    // A hash join builds its hash table from a scan of the table, and then probes it for every row of the
+    // tables to its left in the join order, using every usable equality constraint as part of the key.
+    let hash_join_constraints = usable_hash_join_constraints(
+        &rhs_constraints.constraints,
+        &rhs_constraints.hash_join_candidates,
+        join_order,
+    );
+    if !hash_join_constraints.is_empty() {
+        let cost = estimate_cost_for_hash_join(input_cardinality);
+        if cost < best_access_method.cost {
+            best_access_method = AccessMethod {
+                cost,
+                iter_dir: IterationDirection::Forwards,
+                index: None,
+                constraint_refs: &[],
+                hash_join_constraints: Some(hash_join_constraints),
+                or_branches: None,
+                skip_scan: false,
             };
         }
     }
 
     Ok(best_access_method)
 }
+
+/// The number of distinct values of the leading column of `index`, if sqlite_stat1 says that each
+/// of them has enough rows for a skip-scan of the index to be worth it.
+fn distinct_leading_values(index: &Index) -> Option<f64> {
+    let stat = index.stat.as_ref()?;
+    let (rows, rows_per_leading_value) = (*stat.first()?, *stat.get(1)?);
+    (rows_per_leading_value >= SKIP_SCAN_MIN_ROWS_PER_LEADING_VALUE)
+        .then(|| rows as f64 / rows_per_leading_value as f64)
+}
+
+/// Whether a partial index can be used to access a table.
+/// A partial index only has entries for the rows that satisfy its WHERE clause, so it can only be used
+/// when the WHERE clause of the query implies that predicate, i.e. when every term of the predicate
+/// is also a term of the query's WHERE clause. A `x IS NOT NULL` term is also implied by a comparison on `x`.
+pub fn partial_index_is_usable(
+    index: &Index,
+    table_reference: &JoinedTable,
+    where_clause: &[WhereTerm],
+) -> Result<bool> {
+    let (Some(index_where_clause), Some(btree_table)) =
+        (&index.where_clause, table_reference.btree())
+    else {
+        return Ok(index.where_clause.is_none());
+    };
+    let predicate = bind_partial_index_predicate(
+        index_where_clause,
+        &btree_table,
+        table_reference.internal_id,
+    )?;
+    // A condition from the ON clause of another table's outer join doesn't filter the rows of this table.
+    let terms = where_clause
+        .iter()
+        .filter(|term| {
+            term.from_outer_join
+                .is_none_or(|table_id| table_id == table_reference.internal_id)
+        })
+        .collect::<Vec<_>>();
+
+    let mut conjuncts = vec![predicate];
+    while let Some(conjunct) = conjuncts.pop() {
+        let (conjunct, _) = unwrap_parens_owned(conjunct)?;
+        if let ast::Expr::Binary(lhs, ast::Operator::And, rhs) = conjunct {
+            conjuncts.push(*lhs);
+            conjuncts.push(*rhs);
+            continue;
+        }
+        let mut implied = false;
+        for term in terms.iter() {
+            if exprs_are_equivalent(&term.expr, &conjunct) {
+                implied = true;
+                break;
+            }
+            let not_null_operand = match &conjunct {
+                ast::Expr::NotNull(operand) => Some(operand.as_ref()),
+                ast::Expr::Binary(operand, ast::Operator::IsNot, null)
+                    if matches!(null.as_ref(), ast::Expr::Literal(ast::Literal::Null)) =>
+                {
+                    Some(operand.as_ref())
+                }
+                _ => None,
+            };
+            if let (Some(operand), Some((lhs, _, rhs))) =
+                (not_null_operand, as_binary_components(&term.expr)?)
+            {
+                // A comparison is never true when one of its operands is NULL.
+                if exprs_are_equivalent(lhs, operand) || exprs_are_equivalent(rhs, operand) {
+                    implied = true;
+                    break;
+                }
+            }
+        }
+        if !implied {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
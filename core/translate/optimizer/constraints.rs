@@ -1,17 +1,21 @@
-use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+use std::{cell::Cell, cmp::Ordering, collections::HashMap, sync::Arc};
 
 use crate::{
-    schema::{Column, Index},
+    schema::{Affinity, Column, Index},
     translate::{
-        expr::as_binary_components,
-        plan::{JoinOrderMember, TableReferences, WhereTerm},
-        planner::{table_mask_from_expr, TableMask},
+        collate::CollationSeq,
+        expr::{as_binary_components, as_like_components, as_likelihood_hint, sanitize_string},
+        index::bind_index_expr,
+        plan::{JoinOrderMember, JoinedTable, TableReferences, WhereTerm},
+        planner::{break_predicate_at_and_boundaries, table_mask_from_expr, TableMask},
     },
-    Result,
+    util::exprs_are_equivalent,
+    vdbe::{execute::apply_numeric_affinity, Register},
+    Result, Value,
 };
 use limbo_sqlite3_parser::ast::{self, SortOrder, TableInternalId};
 
-use super::cost::ESTIMATED_HARDCODED_ROWS_PER_TABLE;
+use super::{access_method::partial_index_is_usable, cost::ESTIMATED_HARDCODED_ROWS_PER_TABLE};
 
 /// Represents a single condition derived from a `WHERE` clause term
 /// that constrains a specific column of a table.
@@ -38,8 +42,10 @@ pub struct Constraint {
     pub where_clause_pos: (usize, BinaryExprSide),
     /// The comparison operator (e.g., `=`, `>`, `<`) used in the constraint.
     pub operator: ast::Operator,
-    /// The zero-based index of the constrained column within the table's schema.
-    pub table_col_pos: usize,
+    /// The zero-based index of the constrained column within the table's schema,
+    /// or None if the constrained expression is the expression of a column of an index on expressions,
+    /// e.g. `lower(t.x)` in SELECT * FROM t WHERE lower(t.x) = 'foo'.
+    pub table_col_pos: Option<usize>,
     /// A bitmask representing the set of tables that appear on the *constraining* side
     /// of the comparison expression. For example, in SELECT * FROM t1,t2,t3 WHERE t1.x = t2.x + t3.x,
     /// the lhs_mask contains t2 and t3. Thus, this constraint can only be used if t2 and t3
@@ -48,6 +54,22 @@ pub struct Constraint {
     /// An estimated selectivity factor (0.0 to 1.0) indicating the fraction of rows
     /// expected to satisfy this constraint. Used for cost and cardinality estimation.
     pub selectivity: f64,
+    /// For a constraint derived from a LIKE or GLOB term, the range of values that can match its pattern.
+    /// The operator of such a constraint is always `>=`, and the term itself is never consumed
+    /// by a seek on the range, since the rest of the pattern still has to be checked for every row.
+    pub prefix_range: Option<PrefixRange>,
+}
+
+/// The range of values that can match a LIKE or GLOB pattern with a constant prefix,
+/// e.g. `'abc' <= t.x < 'abd'` for `t.x LIKE 'abc%'`.
+#[derive(Debug, Clone)]
+pub struct PrefixRange {
+    /// The inclusive lower bound of the range, i.e. the prefix itself.
+    pub lower: ast::Expr,
+    /// The exclusive upper bound of the range, i.e. the prefix with its last character incremented.
+    pub upper: ast::Expr,
+    /// The collation an index column must use for the range to hold in it.
+    pub collation: CollationSeq,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -58,7 +80,11 @@ pub enum BinaryExprSide {
 
 impl Constraint {
     /// Get the constraining expression, e.g. '2+3' from 't.x = 2+3'
+    /// For a constraint derived from a LIKE or GLOB term, this is the lower bound of its [PrefixRange].
     pub fn get_constraining_expr(&self, where_clause: &[WhereTerm]) -> ast::Expr {
+        if let Some(prefix_range) = &self.prefix_range {
+            return prefix_range.lower.clone();
+        }
         let (idx, side) = self.where_clause_pos;
         let where_term = &where_clause[idx];
         let Ok(Some((lhs, _, rhs))) = as_binary_components(&where_term.expr) else {
@@ -70,6 +96,26 @@ impl Constraint {
             rhs.clone()
         }
     }
+
+    /// Get the constrained expression, e.g. 't.x' from 't.x = 2+3'
+    pub fn get_constrained_expr(&self, where_clause: &[WhereTerm]) -> ast::Expr {
+        let (idx, side) = self.where_clause_pos;
+        let where_term = &where_clause[idx];
+        if self.prefix_range.is_some() {
+            let Ok(Some((lhs, _, _))) = as_like_components(&where_term.expr) else {
+                panic!("Expected a LIKE or GLOB expression");
+            };
+            return lhs.clone();
+        }
+        let Ok(Some((lhs, _, rhs))) = as_binary_components(&where_term.expr) else {
+            panic!("Expected a valid binary expression");
+        };
+        if side == BinaryExprSide::Lhs {
+            rhs.clone()
+        } else {
+            lhs.clone()
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -120,6 +166,7 @@ impl ConstraintRef {
 ///             sort_order: SortOrder::Asc,
 ///         },
 ///     ],
+///     skip_scan_refs: [],
 /// }
 ///
 #[derive(Debug)]
@@ -128,6 +175,10 @@ pub struct ConstraintUseCandidate {
     pub index: Option<Arc<Index>>,
     /// References to the constraints that may be used as an access path for the index.
     pub refs: Vec<ConstraintRef>,
+    /// References to the constraints that may be used as an access path for a skip-scan of the index,
+    /// i.e. constraints on the columns after the leading column, when the leading column itself is unconstrained.
+    /// See [crate::translate::plan::Search::Seek] for how a skip-scan works.
+    pub skip_scan_refs: Vec<ConstraintRef>,
 }
 
 #[derive(Debug)]
@@ -139,6 +190,20 @@ pub struct TableConstraints {
     pub constraints: Vec<Constraint>,
     /// Candidates for indexes that may use the constraints to perform a lookup.
     pub candidates: Vec<ConstraintUseCandidate>,
+    /// Positions in [TableConstraints::constraints] of the equality constraints that may be used
+    /// as keys of a hash join, see [is_hash_join_key].
+    pub hash_join_candidates: Vec<usize>,
+    /// OR terms of the WHERE clause that may be evaluated with one search per disjunct.
+    pub or_candidates: Vec<OrClauseCandidate>,
+}
+
+/// An OR term of the WHERE clause whose disjuncts can each be evaluated with an index search on a table,
+/// e.g. `t.a = 1 OR t.b = 2` with indexes on both `t.a` and `t.b`.
+#[derive(Debug)]
+pub struct OrClauseCandidate {
+    /// For each disjunct, its AND-ed terms and the constraints they place on the table.
+    /// The constraints refer to the positions of the disjunct's terms, not to the WHERE clause.
+    pub branches: Vec<(Vec<WhereTerm>, TableConstraints)>,
 }
 
 /// In lieu of statistics, we estimate that an equality filter will reduce the output set to 1% of its size.
@@ -148,13 +213,18 @@ const SELECTIVITY_RANGE: f64 = 0.4;
 /// In lieu of statistics, we estimate that other filters will reduce the output set to 90% of its size.
 const SELECTIVITY_OTHER: f64 = 0.9;
 
+/// A LIKE or GLOB prefix is a range with both a lower and an upper bound, so we estimate it
+/// to be as selective as two range filters.
+const SELECTIVITY_PREFIX_RANGE: f64 = SELECTIVITY_RANGE * SELECTIVITY_RANGE;
+
 const SELECTIVITY_UNIQUE_EQUALITY: f64 = 1.0 / ESTIMATED_HARDCODED_ROWS_PER_TABLE as f64;
 
 /// Estimate the selectivity of a constraint based on the operator and the column type.
-fn estimate_selectivity(column: &Column, op: ast::Operator) -> f64 {
+/// The column is None for a constraint on the expression of an index on expressions.
+fn estimate_selectivity(column: Option<&Column>, op: ast::Operator) -> f64 {
     match op {
         ast::Operator::Equals => {
-            if column.is_rowid_alias || column.primary_key {
+            if column.is_some_and(|column| column.is_rowid_alias || column.primary_key) {
                 SELECTIVITY_UNIQUE_EQUALITY
             } else {
                 SELECTIVITY_EQ
@@ -174,200 +244,595 @@ pub fn constraints_from_where_clause(
     where_clause: &[WhereTerm],
     table_references: &TableReferences,
     available_indexes: &HashMap<String, Vec<Arc<Index>>>,
+    case_sensitive_like: bool,
 ) -> Result<Vec<TableConstraints>> {
     let mut constraints = Vec::new();
 
     // For each table, collect all the Constraints and all potential index candidates that may use them.
     for table_reference in table_references.joined_tables() {
-        let rowid_alias_column = table_reference
-            .columns()
-            .iter()
-            .position(|c| c.is_rowid_alias);
-
-        let mut cs = TableConstraints {
-            table_id: table_reference.internal_id,
-            constraints: Vec::new(),
-            candidates: available_indexes
-                .get(table_reference.table.get_name())
-                .map_or(Vec::new(), |indexes| {
-                    indexes
-                        .iter()
-                        .map(|index| ConstraintUseCandidate {
-                            index: Some(index.clone()),
-                            refs: Vec::new(),
-                        })
-                        .collect()
-                }),
-        };
-        // Add a candidate for the rowid index, which is always available when the table has a rowid alias.
-        cs.candidates.push(ConstraintUseCandidate {
-            index: None,
-            refs: Vec::new(),
-        });
+        let mut cs = table_constraints_from_where_clause(
+            table_reference,
+            where_clause,
+            table_references,
+            available_indexes,
+            case_sensitive_like,
+        )?;
+        cs.or_candidates = or_clause_candidates(
+            table_reference,
+            where_clause,
+            table_references,
+            available_indexes,
+            case_sensitive_like,
+        )?;
+        constraints.push(cs);
+    }
 
-        for (i, term) in where_clause.iter().enumerate() {
-            let Some((lhs, operator, rhs)) = as_binary_components(&term.expr)? else {
+    Ok(constraints)
+}
+
+/// Collect the OR terms of the WHERE clause that could be evaluated for the table with one index search per disjunct.
+/// A term qualifies when every one of its disjuncts constrains the table in a way that some index can use.
+fn or_clause_candidates(
+    table_reference: &JoinedTable,
+    where_clause: &[WhereTerm],
+    table_references: &TableReferences,
+    available_indexes: &HashMap<String, Vec<Arc<Index>>>,
+    case_sensitive_like: bool,
+) -> Result<Vec<OrClauseCandidate>> {
+    let mut or_candidates = Vec::new();
+    'terms: for term in where_clause.iter() {
+        if let Some(outer_join_tbl) = term.from_outer_join {
+            if outer_join_tbl != table_reference.internal_id {
                 continue;
-            };
+            }
+        }
+        let mut disjuncts = Vec::new();
+        break_predicate_at_or_boundaries(&term.expr, &mut disjuncts);
+        if disjuncts.len() < 2 {
+            continue;
+        }
+        let mut branches = Vec::with_capacity(disjuncts.len());
+        for disjunct in disjuncts {
+            let mut conjuncts = Vec::new();
+            break_predicate_at_and_boundaries(disjunct.clone(), &mut conjuncts);
+            let branch_terms = conjuncts
+                .into_iter()
+                .map(|expr| WhereTerm {
+                    expr,
+                    from_outer_join: term.from_outer_join,
+                    consumed: Cell::new(false),
+                })
+                .collect::<Vec<_>>();
+            let mut branch_constraints = table_constraints_from_where_clause(
+                table_reference,
+                &branch_terms,
+                table_references,
+                available_indexes,
+                case_sensitive_like,
+            )?;
+            // A single disjunct is always looked up by a search, never by a hash join or a skip-scan.
+            branch_constraints.hash_join_candidates.clear();
+            for candidate in branch_constraints.candidates.iter_mut() {
+                candidate.skip_scan_refs.clear();
+            }
+            if branch_constraints
+                .candidates
+                .iter()
+                .all(|candidate| candidate.refs.is_empty())
+            {
+                continue 'terms;
+            }
+            branches.push((branch_terms, branch_constraints));
+        }
+        or_candidates.push(OrClauseCandidate { branches });
+    }
+    Ok(or_candidates)
+}
 
-            // Constraints originating from a LEFT JOIN must always be evaluated in that join's RHS table's loop,
-            // regardless of which tables the constraint references.
-            if let Some(outer_join_tbl) = term.from_outer_join {
-                if outer_join_tbl != table_reference.internal_id {
-                    continue;
+/// Split an expression into its disjuncts, e.g. `a = 1 OR (b = 2 OR c = 3)` into `a = 1`, `b = 2` and `c = 3`.
+/// Parentheses around the disjuncts are removed.
+fn break_predicate_at_or_boundaries<'a>(
+    predicate: &'a ast::Expr,
+    out_predicates: &mut Vec<&'a ast::Expr>,
+) {
+    match predicate {
+        ast::Expr::Binary(left, ast::Operator::Or, right) => {
+            break_predicate_at_or_boundaries(left, out_predicates);
+            break_predicate_at_or_boundaries(right, out_predicates);
+        }
+        ast::Expr::Parenthesized(exprs) if exprs.len() == 1 => {
+            break_predicate_at_or_boundaries(&exprs[0], out_predicates);
+        }
+        _ => {
+            out_predicates.push(predicate);
+        }
+    }
+}
+
+/// Derive the [PrefixRange] of a `t.x LIKE 'abc%'` or `t.x GLOB 'abc*'` term, where `t.x` is a column
+/// of the table and the pattern starts with a constant prefix.
+/// Returns the position of the column in the table along with the range.
+///
+/// The range only holds in an index that orders the values the way the pattern compares them:
+/// - GLOB and LIKE under `PRAGMA case_sensitive_like=ON` are case-sensitive, so the index must use
+///   the BINARY collation.
+/// - Otherwise LIKE is case-insensitive, so the index must use the NOCASE collation. Since NOCASE
+///   only folds the case of ASCII characters, the prefix of the pattern ends at its first non-ASCII character.
+///
+/// Like in SQLite, when the column does not have TEXT affinity the range is only used if neither of its
+/// bounds looks like a number, since the affinity of the column would turn such a bound into a number.
+fn prefix_range_from_like(
+    expr: &ast::Expr,
+    table_reference: &JoinedTable,
+    case_sensitive_like: bool,
+) -> Result<Option<(usize, PrefixRange)>> {
+    let Some((lhs, op, rhs)) = as_like_components(expr)? else {
+        return Ok(None);
+    };
+    let ast::Expr::Column { table, column, .. } = lhs else {
+        return Ok(None);
+    };
+    if *table != table_reference.internal_id {
+        return Ok(None);
+    }
+    let ast::Expr::Literal(ast::Literal::String(pattern)) = rhs else {
+        return Ok(None);
+    };
+    let pattern = sanitize_string(pattern);
+    let (prefix, collation): (String, _) = match op {
+        ast::LikeOperator::Like if case_sensitive_like => (
+            pattern
+                .chars()
+                .take_while(|c| !matches!(c, '%' | '_'))
+                .collect(),
+            CollationSeq::Binary,
+        ),
+        ast::LikeOperator::Like => (
+            pattern
+                .chars()
+                .take_while(|c| c.is_ascii() && !matches!(c, '%' | '_'))
+                .collect(),
+            CollationSeq::NoCase,
+        ),
+        ast::LikeOperator::Glob => (
+            pattern
+                .chars()
+                .take_while(|c| !matches!(c, '*' | '?' | '['))
+                .collect(),
+            CollationSeq::Binary,
+        ),
+        _ => return Ok(None),
+    };
+    let Some(last_char) = prefix.chars().last() else {
+        return Ok(None);
+    };
+    // NOCASE compares the lowercase forms of the characters, so it is the lowercase form that is incremented.
+    let incremented = if collation == CollationSeq::NoCase {
+        last_char.to_ascii_lowercase() as u32 + 1
+    } else {
+        last_char as u32 + 1
+    };
+    let Some(incremented) = char::from_u32(incremented) else {
+        return Ok(None);
+    };
+    let mut upper = prefix[..prefix.len() - last_char.len_utf8()].to_string();
+    upper.push(incremented);
+    if table_reference.columns()[*column].affinity() != Affinity::Text
+        && (prefix == "-" || looks_like_number(&prefix) || looks_like_number(&upper))
+    {
+        return Ok(None);
+    }
+    let string_literal =
+        |s: &str| ast::Expr::Literal(ast::Literal::String(format!("'{}'", s.replace('\'', "''"))));
+    Ok(Some((
+        *column,
+        PrefixRange {
+            lower: string_literal(&prefix),
+            upper: string_literal(&upper),
+            collation,
+        },
+    )))
+}
+
+/// Whether a text value would be stored as a number in a column with NUMERIC affinity.
+fn looks_like_number(text: &str) -> bool {
+    apply_numeric_affinity(&mut Register::Value(Value::build_text(text)), false)
+}
+
+/// Collect the [Constraint]s and the [ConstraintUseCandidate]s of a single table from a WHERE clause.
+fn table_constraints_from_where_clause(
+    table_reference: &JoinedTable,
+    where_clause: &[WhereTerm],
+    table_references: &TableReferences,
+    available_indexes: &HashMap<String, Vec<Arc<Index>>>,
+    case_sensitive_like: bool,
+) -> Result<TableConstraints> {
+    let rowid_alias_column = table_reference
+        .columns()
+        .iter()
+        .position(|c| c.is_rowid_alias);
+
+    let mut candidates = Vec::new();
+    for index in available_indexes
+        .get(table_reference.table.get_name())
+        .unwrap_or(&Vec::new())
+    {
+        if partial_index_is_usable(index, table_reference, where_clause)? {
+            candidates.push(ConstraintUseCandidate {
+                index: Some(index.clone()),
+                refs: Vec::new(),
+                skip_scan_refs: Vec::new(),
+            });
+        }
+    }
+    let mut cs = TableConstraints {
+        table_id: table_reference.internal_id,
+        constraints: Vec::new(),
+        candidates,
+        hash_join_candidates: Vec::new(),
+        or_candidates: Vec::new(),
+    };
+    // Add a candidate for the rowid index, which is always available when the table has a rowid alias.
+    cs.candidates.push(ConstraintUseCandidate {
+        index: None,
+        refs: Vec::new(),
+        skip_scan_refs: Vec::new(),
+    });
+
+    // The expressions of the columns of the usable indexes on expressions, bound to the table reference,
+    // along with their index and their position in it.
+    let mut index_exprs = Vec::new();
+    if let Some(btree_table) = table_reference.btree() {
+        for index in cs.candidates.iter().filter_map(|c| c.index.as_ref()) {
+            for (pos_in_index, column) in index.columns.iter().enumerate() {
+                if let Some(expr) = &column.expr {
+                    let expr = bind_index_expr(expr, &btree_table, table_reference.internal_id)?;
+                    index_exprs.push((index.clone(), pos_in_index, expr));
                 }
             }
+        }
+    }
+    let is_index_expr = |expr: &ast::Expr| {
+        index_exprs
+            .iter()
+            .any(|(_, _, index_expr)| exprs_are_equivalent(expr, index_expr))
+    };
 
-            // If either the LHS or RHS of the constraint is a column from the table, add the constraint.
-            match lhs {
-                ast::Expr::Column { table, column, .. } => {
-                    if *table == table_reference.internal_id {
-                        let table_column = &table_reference.table.columns()[*column];
-                        cs.constraints.push(Constraint {
-                            where_clause_pos: (i, BinaryExprSide::Rhs),
-                            operator,
-                            table_col_pos: *column,
-                            lhs_mask: table_mask_from_expr(rhs, table_references)?,
-                            selectivity: estimate_selectivity(table_column, operator),
-                        });
-                    }
+    for (i, term) in where_clause.iter().enumerate() {
+        // Constraints originating from a LEFT JOIN must always be evaluated in that join's RHS table's loop,
+        // regardless of which tables the constraint references.
+        if let Some(outer_join_tbl) = term.from_outer_join {
+            if outer_join_tbl != table_reference.internal_id {
+                continue;
+            }
+        }
+        // A likely(), unlikely() or likelihood() call around the term overrides the estimated selectivity.
+        let selectivity_hint = as_likelihood_hint(&term.expr).map(|(_, probability)| probability);
+
+        let Some((lhs, operator, rhs)) = as_binary_components(&term.expr)? else {
+            if let Some((column, prefix_range)) =
+                prefix_range_from_like(&term.expr, table_reference, case_sensitive_like)?
+            {
+                cs.constraints.push(Constraint {
+                    where_clause_pos: (i, BinaryExprSide::Rhs),
+                    operator: ast::Operator::GreaterEquals,
+                    table_col_pos: Some(column),
+                    lhs_mask: TableMask::new(),
+                    selectivity: selectivity_hint.unwrap_or(SELECTIVITY_PREFIX_RANGE),
+                    prefix_range: Some(prefix_range),
+                });
+            }
+            continue;
+        };
+
+        // If either the LHS or RHS of the constraint is a column from the table, add the constraint.
+        match lhs {
+            ast::Expr::Column { table, column, .. } => {
+                if *table == table_reference.internal_id {
+                    let table_column = &table_reference.table.columns()[*column];
+                    cs.constraints.push(Constraint {
+                        where_clause_pos: (i, BinaryExprSide::Rhs),
+                        operator,
+                        table_col_pos: Some(*column),
+                        lhs_mask: table_mask_from_expr(rhs, table_references)?,
+                        selectivity: selectivity_hint
+                            .unwrap_or_else(|| estimate_selectivity(Some(table_column), operator)),
+                        prefix_range: None,
+                    });
                 }
-                ast::Expr::RowId { table, .. } => {
-                    // A rowid alias column must exist for the 'rowid' keyword to be considered a valid reference.
-                    // This should be a parse error at an earlier stage of the query compilation, but nevertheless,
-                    // we check it here.
-                    if *table == table_reference.internal_id && rowid_alias_column.is_some() {
-                        let table_column =
-                            &table_reference.table.columns()[rowid_alias_column.unwrap()];
-                        cs.constraints.push(Constraint {
-                            where_clause_pos: (i, BinaryExprSide::Rhs),
-                            operator,
-                            table_col_pos: rowid_alias_column.unwrap(),
-                            lhs_mask: table_mask_from_expr(rhs, table_references)?,
-                            selectivity: estimate_selectivity(table_column, operator),
-                        });
-                    }
+            }
+            ast::Expr::RowId { table, .. } => {
+                // A rowid alias column must exist for the 'rowid' keyword to be considered a valid reference.
+                // This should be a parse error at an earlier stage of the query compilation, but nevertheless,
+                // we check it here.
+                if *table == table_reference.internal_id && rowid_alias_column.is_some() {
+                    let table_column =
+                        &table_reference.table.columns()[rowid_alias_column.unwrap()];
+                    cs.constraints.push(Constraint {
+                        where_clause_pos: (i, BinaryExprSide::Rhs),
+                        operator,
+                        table_col_pos: rowid_alias_column,
+                        lhs_mask: table_mask_from_expr(rhs, table_references)?,
+                        selectivity: selectivity_hint
+                            .unwrap_or_else(|| estimate_selectivity(Some(table_column), operator)),
+                        prefix_range: None,
+                    });
                 }
-                _ => {}
-            };
-            match rhs {
-                ast::Expr::Column { table, column, .. } => {
-                    if *table == table_reference.internal_id {
-                        let table_column = &table_reference.table.columns()[*column];
-                        cs.constraints.push(Constraint {
-                            where_clause_pos: (i, BinaryExprSide::Lhs),
-                            operator: opposite_cmp_op(operator),
-                            table_col_pos: *column,
-                            lhs_mask: table_mask_from_expr(lhs, table_references)?,
-                            selectivity: estimate_selectivity(table_column, operator),
-                        });
-                    }
+            }
+            _ if is_index_expr(lhs) => {
+                cs.constraints.push(Constraint {
+                    where_clause_pos: (i, BinaryExprSide::Rhs),
+                    operator,
+                    table_col_pos: None,
+                    lhs_mask: table_mask_from_expr(rhs, table_references)?,
+                    selectivity: selectivity_hint
+                        .unwrap_or_else(|| estimate_selectivity(None, operator)),
+                    prefix_range: None,
+                });
+            }
+            _ => {}
+        };
+        match rhs {
+            ast::Expr::Column { table, column, .. } => {
+                if *table == table_reference.internal_id {
+                    let table_column = &table_reference.table.columns()[*column];
+                    cs.constraints.push(Constraint {
+                        where_clause_pos: (i, BinaryExprSide::Lhs),
+                        operator: opposite_cmp_op(operator),
+                        table_col_pos: Some(*column),
+                        lhs_mask: table_mask_from_expr(lhs, table_references)?,
+                        selectivity: selectivity_hint
+                            .unwrap_or_else(|| estimate_selectivity(Some(table_column), operator)),
+                        prefix_range: None,
+                    });
                 }
-                ast::Expr::RowId { table, .. } => {
-                    if *table == table_reference.internal_id && rowid_alias_column.is_some() {
-                        let table_column =
-                            &table_reference.table.columns()[rowid_alias_column.unwrap()];
-                        cs.constraints.push(Constraint {
-                            where_clause_pos: (i, BinaryExprSide::Lhs),
-                            operator: opposite_cmp_op(operator),
-                            table_col_pos: rowid_alias_column.unwrap(),
-                            lhs_mask: table_mask_from_expr(lhs, table_references)?,
-                            selectivity: estimate_selectivity(table_column, operator),
-                        });
-                    }
+            }
+            ast::Expr::RowId { table, .. } => {
+                if *table == table_reference.internal_id && rowid_alias_column.is_some() {
+                    let table_column =
+                        &table_reference.table.columns()[rowid_alias_column.unwrap()];
+                    cs.constraints.push(Constraint {
+                        where_clause_pos: (i, BinaryExprSide::Lhs),
+                        operator: opposite_cmp_op(operator),
+                        table_col_pos: rowid_alias_column,
+                        lhs_mask: table_mask_from_expr(lhs, table_references)?,
+                        selectivity: selectivity_hint
+                            .unwrap_or_else(|| estimate_selectivity(Some(table_column), operator)),
+                        prefix_range: None,
+                    });
                 }
-                _ => {}
-            };
-        }
-        // sort equalities first so that index keys will be properly constructed.
-        // see e.g.: https://www.solarwinds.com/blog/the-left-prefix-index-rule
-        cs.constraints.sort_by(|a, b| {
-            if a.operator == ast::Operator::Equals {
-                Ordering::Less
-            } else if b.operator == ast::Operator::Equals {
-                Ordering::Greater
-            } else {
-                Ordering::Equal
             }
-        });
+            _ if is_index_expr(rhs) => {
+                cs.constraints.push(Constraint {
+                    where_clause_pos: (i, BinaryExprSide::Lhs),
+                    operator: opposite_cmp_op(operator),
+                    table_col_pos: None,
+                    lhs_mask: table_mask_from_expr(lhs, table_references)?,
+                    selectivity: selectivity_hint
+                        .unwrap_or_else(|| estimate_selectivity(None, operator)),
+                    prefix_range: None,
+                });
+            }
+            _ => {}
+        };
+    }
+    // sort equalities first so that index keys will be properly constructed.
+    // see e.g.: https://www.solarwinds.com/blog/the-left-prefix-index-rule
+    cs.constraints.sort_by(|a, b| {
+        if a.operator == ast::Operator::Equals {
+            Ordering::Less
+        } else if b.operator == ast::Operator::Equals {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    });
 
-        // For each constraint we found, add a reference to it for each index that may be able to use it.
-        for (i, constraint) in cs.constraints.iter().enumerate() {
-            if rowid_alias_column.map_or(false, |idx| constraint.table_col_pos == idx) {
-                let rowid_candidate = cs
+    // For each constraint we found, add a reference to it for each index that may be able to use it.
+    for (i, constraint) in cs.constraints.iter().enumerate() {
+        let Some(table_col_pos) = constraint.table_col_pos else {
+            // A constraint on an expression can be used by the indexes on that expression.
+            let constrained_expr = constraint.get_constrained_expr(where_clause);
+            for (index, pos_in_index, index_expr) in index_exprs.iter() {
+                if !exprs_are_equivalent(&constrained_expr, index_expr) {
+                    continue;
+                }
+                let index_candidate = cs
                     .candidates
                     .iter_mut()
-                    .find_map(|candidate| {
-                        if candidate.index.is_none() {
-                            Some(candidate)
-                        } else {
-                            None
-                        }
+                    .find(|candidate| {
+                        candidate
+                            .index
+                            .as_ref()
+                            .is_some_and(|i| Arc::ptr_eq(index, i))
                     })
-                    .unwrap();
-                rowid_candidate.refs.push(ConstraintRef {
+                    .expect("indexes on expressions are taken from the candidates");
+                index_candidate.refs.push(ConstraintRef {
                     constraint_vec_pos: i,
-                    index_col_pos: 0,
-                    sort_order: SortOrder::Asc,
+                    index_col_pos: *pos_in_index,
+                    sort_order: index.columns[*pos_in_index].order,
                 });
             }
-            for index in available_indexes
-                .get(table_reference.table.get_name())
-                .unwrap_or(&Vec::new())
-            {
-                if let Some(position_in_index) =
-                    index.column_table_pos_to_index_pos(constraint.table_col_pos)
+            continue;
+        };
+        if rowid_alias_column.map_or(false, |idx| table_col_pos == idx) {
+            let rowid_candidate = cs
+                .candidates
+                .iter_mut()
+                .find_map(|candidate| {
+                    if candidate.index.is_none() {
+                        Some(candidate)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap();
+            rowid_candidate.refs.push(ConstraintRef {
+                constraint_vec_pos: i,
+                index_col_pos: 0,
+                sort_order: SortOrder::Asc,
+            });
+        }
+        for index in available_indexes
+            .get(table_reference.table.get_name())
+            .unwrap_or(&Vec::new())
+        {
+            if let Some(position_in_index) = index.column_table_pos_to_index_pos(table_col_pos) {
+                if constraint
+                    .prefix_range
+                    .as_ref()
+                    .is_some_and(|prefix_range| {
+                        index.columns[position_in_index]
+                            .collation
+                            .unwrap_or_default()
+                            != prefix_range.collation
+                    })
                 {
-                    let index_candidate = cs
-                        .candidates
-                        .iter_mut()
-                        .find_map(|candidate| {
-                            if candidate
-                                .index
-                                .as_ref()
-                                .map_or(false, |i| Arc::ptr_eq(index, i))
-                            {
-                                Some(candidate)
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap();
-                    index_candidate.refs.push(ConstraintRef {
-                        constraint_vec_pos: i,
-                        index_col_pos: position_in_index,
-                        sort_order: index.columns[position_in_index].order,
-                    });
+                    continue;
                 }
+                // Partial indexes that can't be used have no candidate.
+                let Some(index_candidate) = cs.candidates.iter_mut().find_map(|candidate| {
+                    if candidate
+                        .index
+                        .as_ref()
+                        .map_or(false, |i| Arc::ptr_eq(index, i))
+                    {
+                        Some(candidate)
+                    } else {
+                        None
+                    }
+                }) else {
+                    continue;
+                };
+                index_candidate.refs.push(ConstraintRef {
+                    constraint_vec_pos: i,
+                    index_col_pos: position_in_index,
+                    sort_order: index.columns[position_in_index].order,
+                });
             }
         }
+    }
 
-        for candidate in cs.candidates.iter_mut() {
-            // Sort by index_col_pos, ascending -- index columns must be consumed in contiguous order.
-            candidate.refs.sort_by_key(|cref| cref.index_col_pos);
-            // Deduplicate by position, keeping first occurrence (which will be equality if one exists, since the constraints vec is sorted that way)
-            candidate.refs.dedup_by_key(|cref| cref.index_col_pos);
-            // Truncate at first gap in positions -- again, index columns must be consumed in contiguous order.
-            let contiguous_len = candidate
-                .refs
-                .iter()
-                .enumerate()
-                .take_while(|(i, cref)| cref.index_col_pos == *i)
-                .count();
-            candidate.refs.truncate(contiguous_len);
-
-            // Truncate after the first inequality, since the left-prefix rule of indexes requires that all constraints but the last one must be equalities;
-            // again see: https://www.solarwinds.com/blog/the-left-prefix-index-rule
-            if let Some(first_inequality) = candidate.refs.iter().position(|cref| {
-                cs.constraints[cref.constraint_vec_pos].operator != ast::Operator::Equals
-            }) {
-                candidate.refs.truncate(first_inequality + 1);
-            }
+    cs.hash_join_candidates = (0..cs.constraints.len())
+        .filter(|&i| {
+            is_hash_join_key(
+                &cs.constraints[i],
+                table_reference,
+                where_clause,
+                table_references,
+            )
+        })
+        .collect();
+
+    for candidate in cs.candidates.iter_mut() {
+        // Sort by index_col_pos, ascending -- index columns must be consumed in contiguous order.
+        candidate.refs.sort_by_key(|cref| cref.index_col_pos);
+        // Deduplicate by position, keeping first occurrence (which will be equality if one exists, since the constraints vec is sorted that way)
+        candidate.refs.dedup_by_key(|cref| cref.index_col_pos);
+        // A skip-scan treats the leading column of the index as if it was constrained by an equality,
+        // so the constraints usable by it are the ones starting from the second column.
+        let can_skip_scan = candidate.index.as_ref().is_some_and(|index| {
+            !index.ephemeral
+                && index.columns.len() > 1
+                && candidate.refs.first().map(|cref| cref.index_col_pos) == Some(1)
+        });
+        if can_skip_scan {
+            candidate.skip_scan_refs = candidate.refs.clone();
+            truncate_to_seekable_prefix(&mut candidate.skip_scan_refs, 1, &cs.constraints);
         }
-        constraints.push(cs);
+        truncate_to_seekable_prefix(&mut candidate.refs, 0, &cs.constraints);
     }
+    Ok(cs)
+}
 
-    Ok(constraints)
+/// Truncate a list of [ConstraintRef]s, sorted and deduplicated by index column position, to the constraints
+/// that can form a seek key whose first column is the index column at `first_index_col_pos`.
+fn truncate_to_seekable_prefix(
+    refs: &mut Vec<ConstraintRef>,
+    first_index_col_pos: usize,
+    constraints: &[Constraint],
+) {
+    // Truncate at first gap in positions -- again, index columns must be consumed in contiguous order.
+    let contiguous_len = refs
+        .iter()
+        .enumerate()
+        .take_while(|(i, cref)| cref.index_col_pos == first_index_col_pos + *i)
+        .count();
+    refs.truncate(contiguous_len);
+
+    // Truncate after the first inequality, since the left-prefix rule of indexes requires that all constraints but the last one must be equalities;
+    // again see: https://www.solarwinds.com/blog/the-left-prefix-index-rule
+    if let Some(first_inequality) = refs
+        .iter()
+        .position(|cref| constraints[cref.constraint_vec_pos].operator != ast::Operator::Equals)
+    {
+        refs.truncate(first_inequality + 1);
+    }
+}
+
+/// Whether a constraint can be used as a key of a hash join, i.e. whether the rows of the table matching
+/// the constraint can be found by looking up the value of the constraining expression in a hash table.
+///
+/// This requires an equality between a column of the table and a column of another table, where the
+/// comparison applies no type conversions and uses the BINARY collation. Under those conditions two values
+/// compare equal exactly when they are the same value, which is what hashing can check.
+fn is_hash_join_key(
+    constraint: &Constraint,
+    table_reference: &JoinedTable,
+    where_clause: &[WhereTerm],
+    table_references: &TableReferences,
+) -> bool {
+    if constraint.operator != ast::Operator::Equals {
+        return false;
+    }
+    let Some(btree) = table_reference.btree() else {
+        return false;
+    };
+    if !btree.has_rowid {
+        return false;
+    }
+    // For the right table of a LEFT JOIN, a WHERE term must also filter out the row of NULLs that is
+    // emitted when there is no match, so only the terms of the ON clause can be checked by the probe.
+    let is_outer = table_reference
+        .join_info
+        .as_ref()
+        .is_some_and(|join_info| join_info.outer);
+    if is_outer
+        && where_clause[constraint.where_clause_pos.0].from_outer_join
+            != Some(table_reference.internal_id)
+    {
+        return false;
+    }
+    let ast::Expr::Column {
+        table: other_table_id,
+        column: other_column,
+        ..
+    } = constraint.get_constraining_expr(where_clause)
+    else {
+        return false;
+    };
+    if other_table_id == table_reference.internal_id {
+        return false;
+    }
+    let Some(other_table) = table_references.find_joined_table_by_internal_id(other_table_id)
+    else {
+        return false;
+    };
+    let Some(other_column) = other_table.columns().get(other_column) else {
+        return false;
+    };
+    let Some(table_col_pos) = constraint.table_col_pos else {
+        return false;
+    };
+    let column = &btree.columns[table_col_pos];
+
+    let is_binary = |c: &Column| c.collation.map_or(true, |c| c == CollationSeq::Binary);
+    let is_numeric =
+        |a: Affinity| matches!(a, Affinity::Integer | Affinity::Real | Affinity::Numeric);
+    let (affinity, other_affinity) = (column.affinity(), other_column.affinity());
+    is_binary(column)
+        && is_binary(other_column)
+        && (affinity == other_affinity || (is_numeric(affinity) && is_numeric(other_affinity)))
 }
 
 /// Find which [Constraint]s are usable for a given join order.
@@ -402,6 +867,29 @@ pub fn usable_constraints_for_join_order<'a>(
     &refs[..usable_until]
 }
 
+/// Find which hash join key candidates (see [TableConstraints::hash_join_candidates]) are usable for a given join order.
+/// Unlike index seek keys, hash join keys can be any subset of the candidates, so every usable candidate is returned.
+pub fn usable_hash_join_constraints(
+    constraints: &[Constraint],
+    hash_join_candidates: &[usize],
+    join_order: &[JoinOrderMember],
+) -> Vec<usize> {
+    let lhs_mask = TableMask::from_table_number_iter(
+        join_order
+            .iter()
+            .take(join_order.len() - 1)
+            .map(|j| j.original_idx),
+    );
+    hash_join_candidates
+        .iter()
+        .copied()
+        .filter(|&pos| {
+            let constraint = &constraints[pos];
+            lhs_mask.contains_all(&constraint.lhs_mask)
+        })
+        .collect()
+}
+
 fn opposite_cmp_op(op: ast::Operator) -> ast::Operator {
     match op {
         ast::Operator::Equals => ast::Operator::Equals,
@@ -1,3 +1,7 @@
+use crate::{
+    storage::page_cache::DEFAULT_PAGE_CACHE_SIZE_IN_PAGES, vdbe::hash_table::NUM_PARTITIONS,
+};
+
 use super::constraints::{Constraint, ConstraintRef};
 
 /// A simple newtype wrapper over a f64 that represents the cost of an operation.
@@ -70,3 +74,55 @@ pub fn estimate_cost_for_scan_or_seek(
             * covering_multiplier,
     )
 }
+
+/// Like SQLite, a skip-scan is only considered when sqlite_stat1 says that each value of the
+/// leading column of the index has at least this many rows. Seeking past fewer rows costs more than
+/// reading them, and without statistics there is no telling how many rows share a value.
+pub const SKIP_SCAN_MIN_ROWS_PER_LEADING_VALUE: u64 = 18;
+
+/// Estimate the cost of a skip-scan of an index.
+///
+/// A skip-scan seeks the index once per distinct value of its leading column, so it costs as much as
+/// a seek using the constraints on the other columns, plus one page fetch per distinct leading value.
+pub fn estimate_cost_for_skip_scan(
+    index_info: IndexInfo,
+    constraints: &[Constraint],
+    usable_constraint_refs: &[ConstraintRef],
+    distinct_leading_values: f64,
+    input_cardinality: f64,
+) -> Cost {
+    estimate_cost_for_scan_or_seek(
+        Some(index_info),
+        constraints,
+        usable_constraint_refs,
+        input_cardinality,
+    ) + Cost(distinct_leading_values * input_cardinality)
+}
+
+/// Estimated size of a row, derived from [ESTIMATED_HARDCODED_ROWS_PER_PAGE] and a 4096 byte page.
+const ESTIMATED_HARDCODED_ROW_SIZE: usize = 4096 / ESTIMATED_HARDCODED_ROWS_PER_PAGE;
+/// The memory budget of a hash table, which is the size of the default page cache.
+const ESTIMATED_HASH_TABLE_MEMORY_BUDGET: usize = DEFAULT_PAGE_CACHE_SIZE_IN_PAGES * 4096;
+/// The cost of probing a hash table that fits in memory, in page fetches.
+/// Hashing is cheap but not free, so that a hash join never wins over a plain scan of a single row.
+const HASH_TABLE_PROBE_COST: f64 = 0.01;
+
+/// Estimate the cost of a hash join.
+///
+/// The table is scanned once to build the hash table, regardless of the input cardinality,
+/// and then probed once per input row. If the hash table does not fit in its memory budget,
+/// part of it is spilled to disk, and a probe that hits a spilled partition has to read the
+/// partition back.
+pub fn estimate_cost_for_hash_join(input_cardinality: f64) -> Cost {
+    let build_cost = estimate_page_io_cost(ESTIMATED_HARDCODED_ROWS_PER_TABLE as f64);
+
+    let hash_table_size =
+        (ESTIMATED_HARDCODED_ROWS_PER_TABLE * ESTIMATED_HARDCODED_ROW_SIZE) as f64;
+    let spilled_fraction =
+        (1.0 - ESTIMATED_HASH_TABLE_MEMORY_BUDGET as f64 / hash_table_size).max(0.0);
+    let partition_read_cost =
+        estimate_page_io_cost(ESTIMATED_HARDCODED_ROWS_PER_TABLE as f64 / NUM_PARTITIONS as f64);
+    let probe_cost = HASH_TABLE_PROBE_COST + spilled_fraction * *partition_read_cost;
+
+    build_cost + Cost(input_cardinality * probe_cost)
+}
@@ -502,7 +502,11 @@ mod tests {
     use crate::{
         schema::{BTreeTable, Column, Index, IndexColumn, Table, Type},
         translate::{
-            optimizer::constraints::{constraints_from_where_clause, BinaryExprSide},
+            collate::CollationSeq,
+            optimizer::{
+                constraints::{constraints_from_where_clause, BinaryExprSide},
+                order::{ColumnOrder, EliminatesSort, OrderTarget},
+            },
             plan::{
                 ColumnUsedMask, IterationDirection, JoinInfo, Operation, TableReferences, WhereTerm,
             },
@@ -530,9 +534,13 @@ mod tests {
         let where_clause = vec![];
 
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         let result = compute_best_join_order(
             table_references.joined_tables(),
@@ -559,9 +567,13 @@ mod tests {
         let where_clause = vec![];
 
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         // SELECT * from test_table
         // expecting best_best_plan() not to do any work due to empty where clause.
@@ -599,9 +611,13 @@ mod tests {
         let table_references = TableReferences::new(joined_tables, vec![]);
         let access_methods_arena = RefCell::new(Vec::new());
         let available_indexes = HashMap::new();
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         // SELECT * FROM test_table WHERE id = 42
         // expecting a RowidEq access method because id is a rowid alias.
@@ -658,17 +674,24 @@ mod tests {
                 pos_in_table: 0,
                 collation: None,
                 default: None,
+                expr: None,
             }],
             unique: true,
             ephemeral: false,
             root_page: 1,
             has_rowid: true,
+            where_clause: None,
+            stat: None,
         });
         available_indexes.insert("test_table".to_string(), vec![index]);
 
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
         // SELECT * FROM test_table WHERE id = 42
         // expecting an IndexScan access method because id is a primary key with an index
         let result = compute_best_join_order(
@@ -726,11 +749,14 @@ mod tests {
                 pos_in_table: 0,
                 collation: None,
                 default: None,
+                expr: None,
             }],
             unique: true,
             ephemeral: false,
             root_page: 1,
             has_rowid: true,
+            where_clause: None,
+            stat: None,
         });
         available_indexes.insert("table1".to_string(), vec![index1]);
 
@@ -744,9 +770,13 @@ mod tests {
 
         let table_references = TableReferences::new(joined_tables, vec![]);
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         let result = compute_best_join_order(
             table_references.joined_tables(),
@@ -842,11 +872,14 @@ mod tests {
                         pos_in_table: 0,
                         collation: None,
                         default: None,
+                        expr: None,
                     }],
                     unique: true,
                     ephemeral: false,
                     root_page: 1,
                     has_rowid: true,
+                    where_clause: None,
+                    stat: None,
                 });
                 available_indexes.insert(table_name.to_string(), vec![index]);
             });
@@ -859,11 +892,14 @@ mod tests {
                 pos_in_table: 1,
                 collation: None,
                 default: None,
+                expr: None,
             }],
             unique: false,
             ephemeral: false,
             root_page: 1,
             has_rowid: true,
+            where_clause: None,
+            stat: None,
         });
         let order_id_idx = Arc::new(Index {
             name: "order_items_order_id_idx".to_string(),
@@ -874,11 +910,14 @@ mod tests {
                 pos_in_table: 1,
                 collation: None,
                 default: None,
+                expr: None,
             }],
             unique: false,
             ephemeral: false,
             root_page: 1,
             has_rowid: true,
+            where_clause: None,
+            stat: None,
         });
 
         available_indexes
@@ -916,9 +955,13 @@ mod tests {
 
         let table_references = TableReferences::new(joined_tables, vec![]);
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         let result = compute_best_join_order(
             table_references.joined_tables(),
@@ -1025,9 +1068,13 @@ mod tests {
         let table_references = TableReferences::new(joined_tables, vec![]);
         let available_indexes = HashMap::new();
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
             table_references.joined_tables(),
@@ -1131,9 +1178,13 @@ mod tests {
         let table_references = TableReferences::new(joined_tables, vec![]);
         let access_methods_arena = RefCell::new(Vec::new());
         let available_indexes = HashMap::new();
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         let result = compute_best_join_order(
             table_references.joined_tables(),
@@ -1215,9 +1266,13 @@ mod tests {
 
         let table_references = TableReferences::new(joined_tables, vec![]);
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         // Run the optimizer
         let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
@@ -1263,9 +1318,10 @@ mod tests {
         }
     }
 
-    #[test]
-    /// Test that [compute_best_join_order] figures out that the index can't be used when only the second column is referenced
-    fn test_index_second_column_only() {
+    /// Returns the access method [compute_best_join_order] picks for `SELECT * FROM t1 WHERE y = 5`
+    /// with an index on (x,y) that has the sqlite_stat1 statistics `stat`: whether it is a skip-scan,
+    /// the index it uses, and the index columns its constraints are on.
+    fn _second_column_access_method(stat: Option<Vec<u64>>) -> (bool, Option<String>, Vec<usize>) {
         let mut joined_tables = Vec::new();
 
         let mut table_id_counter = TableRefIdCounter::new();
@@ -1284,6 +1340,7 @@ mod tests {
                     pos_in_table: 0,
                     collation: None,
                     default: None,
+                    expr: None,
                 },
                 IndexColumn {
                     name: "y".to_string(),
@@ -1291,12 +1348,15 @@ mod tests {
                     pos_in_table: 1,
                     collation: None,
                     default: None,
+                    expr: None,
                 },
             ],
             unique: false,
             root_page: 2,
             ephemeral: false,
             has_rowid: true,
+            where_clause: None,
+            stat,
         });
 
         let mut available_indexes = HashMap::new();
@@ -1312,6 +1372,7 @@ mod tests {
             identifier: "t1".to_string(),
             join_info: None,
             col_used_mask: ColumnUsedMask::new(),
+            index_hint: None,
         });
 
         // Create where clause that only references second column
@@ -1332,9 +1393,13 @@ mod tests {
 
         let table_references = TableReferences::new(joined_tables, vec![]);
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
             table_references.joined_tables(),
@@ -1345,9 +1410,193 @@ mod tests {
         .unwrap()
         .unwrap();
 
-        // Verify access method is a scan, not a seek, because the index can't be used when only the second column is referenced
         let access_method = &access_methods_arena.borrow()[best_plan.data[0].1];
-        assert!(access_method.is_scan());
+        (
+            access_method.skip_scan,
+            access_method.index.as_ref().map(|index| index.name.clone()),
+            access_method
+                .constraint_refs
+                .iter()
+                .map(|cref| cref.index_col_pos)
+                .collect(),
+        )
+    }
+
+    #[test]
+    /// Test that [compute_best_join_order] uses a skip-scan of the index when only the second column is referenced,
+    /// and each value of the first column has many rows
+    fn test_index_second_column_only() {
+        // 20 distinct values of x, with 1000 rows each.
+        let (skip_scan, index, constraint_cols) =
+            _second_column_access_method(Some(vec![20000, 1000, 1]));
+        assert!(skip_scan);
+        assert_eq!(index.as_deref(), Some("idx_xy"));
+        assert_eq!(constraint_cols, vec![1]);
+    }
+
+    #[test]
+    /// Test that [compute_best_join_order] scans the table instead of skip-scanning an index whose first column
+    /// has few rows per value, or has no statistics
+    fn test_index_second_column_only_without_repeated_values() {
+        // x is unique, so a skip-scan would seek once per row.
+        assert_eq!(
+            _second_column_access_method(Some(vec![20000, 1, 1])),
+            (false, None, vec![])
+        );
+        // Just under the 18 rows per value a skip-scan needs.
+        assert_eq!(
+            _second_column_access_method(Some(vec![20000, 17, 1])),
+            (false, None, vec![])
+        );
+        assert_eq!(_second_column_access_method(None), (false, None, vec![]));
+    }
+
+    /// Returns the index [compute_best_join_order] searches for `SELECT * FROM t1 WHERE x <op> <pattern>`,
+    /// where x has the type `ty` and there is an index on x with the BINARY collation and one with NOCASE,
+    /// or None if the table is scanned.
+    fn _like_prefix_access_method(
+        ty: Type,
+        op: ast::LikeOperator,
+        pattern: &str,
+        case_sensitive_like: bool,
+    ) -> Option<String> {
+        let mut table_id_counter = TableRefIdCounter::new();
+        let table = _create_btree_table("t1", _create_column_list(&["x"], ty));
+        let index = |name: &str, collation| {
+            Arc::new(Index {
+                name: name.to_string(),
+                table_name: "t1".to_string(),
+                columns: vec![IndexColumn {
+                    name: "x".to_string(),
+                    order: SortOrder::Asc,
+                    pos_in_table: 0,
+                    collation,
+                    default: None,
+                    expr: None,
+                }],
+                unique: false,
+                root_page: 2,
+                ephemeral: false,
+                has_rowid: true,
+                where_clause: None,
+                stat: None,
+            })
+        };
+        let mut available_indexes = HashMap::new();
+        available_indexes.insert(
+            "t1".to_string(),
+            vec![
+                index("idx_binary", None),
+                index("idx_nocase", Some(CollationSeq::NoCase)),
+            ],
+        );
+
+        let joined_tables = vec![_create_table_reference(
+            table,
+            None,
+            table_id_counter.next(),
+        )];
+        let where_clause = vec![WhereTerm {
+            expr: Expr::Like {
+                lhs: Box::new(_create_column_expr(joined_tables[0].internal_id, 0, false)),
+                not: false,
+                op,
+                rhs: Box::new(Expr::Literal(ast::Literal::String(format!("'{pattern}'")))),
+                escape: None,
+            },
+            from_outer_join: None,
+            consumed: Cell::new(false),
+        }];
+
+        let table_references = TableReferences::new(joined_tables, vec![]);
+        let access_methods_arena = RefCell::new(Vec::new());
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            case_sensitive_like,
+        )
+        .unwrap();
+
+        let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
+            table_references.joined_tables(),
+            None,
+            &table_constraints,
+            &access_methods_arena,
+        )
+        .unwrap()
+        .unwrap();
+
+        let access_method = &access_methods_arena.borrow()[best_plan.data[0].1];
+        if access_method.is_scan() {
+            return None;
+        }
+        access_method.index.as_ref().map(|index| index.name.clone())
+    }
+
+    #[test]
+    /// Test that a LIKE prefix searches the NOCASE index, and a GLOB prefix or a LIKE prefix under
+    /// `PRAGMA case_sensitive_like=ON` searches the BINARY index
+    fn test_like_prefix_collation() {
+        use ast::LikeOperator::{Glob, Like};
+        assert_eq!(
+            _like_prefix_access_method(Type::Text, Like, "ab%", false).as_deref(),
+            Some("idx_nocase")
+        );
+        assert_eq!(
+            _like_prefix_access_method(Type::Text, Like, "ab%", true).as_deref(),
+            Some("idx_binary")
+        );
+        assert_eq!(
+            _like_prefix_access_method(Type::Text, Glob, "ab*", false).as_deref(),
+            Some("idx_binary")
+        );
+        // NOCASE only folds ASCII characters, but LIKE folds every character.
+        assert_eq!(
+            _like_prefix_access_method(Type::Text, Like, "é%", false),
+            None
+        );
+        assert_eq!(
+            _like_prefix_access_method(Type::Text, Glob, "é*", false).as_deref(),
+            Some("idx_binary")
+        );
+        // No constant prefix.
+        assert_eq!(
+            _like_prefix_access_method(Type::Text, Like, "%ab", false),
+            None
+        );
+    }
+
+    #[test]
+    /// Test that a LIKE or GLOB prefix is used on a column without TEXT affinity,
+    /// as long as the bounds of its range don't look like numbers
+    fn test_like_prefix_affinity() {
+        use ast::LikeOperator::{Glob, Like};
+        for ty in [Type::Null, Type::Blob, Type::Integer] {
+            assert_eq!(
+                _like_prefix_access_method(ty, Like, "ab%", false).as_deref(),
+                Some("idx_nocase")
+            );
+            assert_eq!(
+                _like_prefix_access_method(ty, Glob, "ab*", false).as_deref(),
+                Some("idx_binary")
+            );
+            // '12' and '13' would compare as numbers.
+            assert_eq!(_like_prefix_access_method(ty, Like, "12%", false), None);
+            assert_eq!(_like_prefix_access_method(ty, Glob, "-*", false), None);
+            // '1e' isn't a number, but its upper bound '1f' isn't either.
+            assert_eq!(
+                _like_prefix_access_method(ty, Glob, "1e*", false).as_deref(),
+                Some("idx_binary")
+            );
+            // The upper bound of '1.' is '1/', but the lower bound is a number.
+            assert_eq!(_like_prefix_access_method(ty, Glob, "1.*", false), None);
+        }
+        // On a TEXT column, the bounds stay text.
+        assert_eq!(
+            _like_prefix_access_method(Type::Text, Glob, "12*", false).as_deref(),
+            Some("idx_binary")
+        );
     }
 
     #[test]
@@ -1370,6 +1619,7 @@ mod tests {
                     pos_in_table: 0,
                     collation: None,
                     default: None,
+                    expr: None,
                 },
                 IndexColumn {
                     name: "c2".to_string(),
@@ -1377,6 +1627,7 @@ mod tests {
                     pos_in_table: 1,
                     collation: None,
                     default: None,
+                    expr: None,
                 },
                 IndexColumn {
                     name: "c3".to_string(),
@@ -1384,12 +1635,15 @@ mod tests {
                     pos_in_table: 2,
                     collation: None,
                     default: None,
+                    expr: None,
                 },
             ],
             unique: false,
             root_page: 2,
             ephemeral: false,
             has_rowid: true,
+            where_clause: None,
+            stat: None,
         });
         available_indexes.insert("t1".to_string(), vec![index]);
 
@@ -1403,6 +1657,7 @@ mod tests {
             identifier: "t1".to_string(),
             join_info: None,
             col_used_mask: ColumnUsedMask::new(),
+            index_hint: None,
         });
 
         // Create where clause that references first and third columns
@@ -1439,9 +1694,13 @@ mod tests {
 
         let table_references = TableReferences::new(joined_tables, vec![]);
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
             table_references.joined_tables(),
@@ -1463,7 +1722,7 @@ mod tests {
         let constraint =
             &table_constraints[0].constraints[access_method.constraint_refs[0].constraint_vec_pos];
         assert!(constraint.operator == ast::Operator::Equals);
-        assert!(constraint.table_col_pos == 0); // c1
+        assert!(constraint.table_col_pos == Some(0)); // c1
     }
 
     #[test]
@@ -1486,6 +1745,7 @@ mod tests {
                     pos_in_table: 0,
                     collation: None,
                     default: None,
+                    expr: None,
                 },
                 IndexColumn {
                     name: "c2".to_string(),
@@ -1493,6 +1753,7 @@ mod tests {
                     pos_in_table: 1,
                     collation: None,
                     default: None,
+                    expr: None,
                 },
                 IndexColumn {
                     name: "c3".to_string(),
@@ -1500,12 +1761,15 @@ mod tests {
                     pos_in_table: 2,
                     collation: None,
                     default: None,
+                    expr: None,
                 },
             ],
             root_page: 2,
             ephemeral: false,
             has_rowid: true,
+            where_clause: None,
             unique: false,
+            stat: None,
         });
         available_indexes.insert("t1".to_string(), vec![index]);
 
@@ -1519,6 +1783,7 @@ mod tests {
             identifier: "t1".to_string(),
             join_info: None,
             col_used_mask: ColumnUsedMask::new(),
+            index_hint: None,
         });
 
         // Create where clause: c1 = 5 AND c2 > 10 AND c3 = 7
@@ -1569,9 +1834,13 @@ mod tests {
 
         let table_references = TableReferences::new(joined_tables, vec![]);
         let access_methods_arena = RefCell::new(Vec::new());
-        let table_constraints =
-            constraints_from_where_clause(&where_clause, &table_references, &available_indexes)
-                .unwrap();
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
 
         let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
             table_references.joined_tables(),
@@ -1594,11 +1863,298 @@ mod tests {
         let constraint =
             &table_constraints[0].constraints[access_method.constraint_refs[0].constraint_vec_pos];
         assert!(constraint.operator == ast::Operator::Equals);
-        assert!(constraint.table_col_pos == 0); // c1
+        assert!(constraint.table_col_pos == Some(0)); // c1
         let constraint =
             &table_constraints[0].constraints[access_method.constraint_refs[1].constraint_vec_pos];
         assert!(constraint.operator == ast::Operator::Greater);
-        assert!(constraint.table_col_pos == 1); // c2
+        assert!(constraint.table_col_pos == Some(1)); // c2
+    }
+
+    /// Returns the access method [compute_best_join_order] picks for the inner table of
+    /// `SELECT * FROM t1 JOIN t2 ON t1.a = t2.x`, optionally with an index on t2.x.
+    fn _hash_join_access_method(
+        with_index: bool,
+    ) -> (Vec<usize>, Option<Vec<usize>>, Option<String>) {
+        let t1 = _create_btree_table("t1", _create_column_list(&["id", "a"], Type::Integer));
+        let t2 = _create_btree_table("t2", _create_column_list(&["id", "x"], Type::Integer));
+        let mut table_id_counter = TableRefIdCounter::new();
+        let joined_tables = vec![
+            _create_table_reference(t1, None, table_id_counter.next()),
+            _create_table_reference(
+                t2,
+                Some(JoinInfo {
+                    outer: false,
+                    using: None,
+                }),
+                table_id_counter.next(),
+            ),
+        ];
+        let where_clause = vec![_create_binary_expr(
+            _create_column_expr(joined_tables[0].internal_id, 1, false),
+            ast::Operator::Equals,
+            _create_column_expr(joined_tables[1].internal_id, 1, false),
+        )];
+        let mut available_indexes = HashMap::new();
+        if with_index {
+            available_indexes.insert(
+                "t2".to_string(),
+                vec![_create_index("t2_x", "t2", &[("x", 1, SortOrder::Asc)])],
+            );
+        }
+
+        let table_references = TableReferences::new(joined_tables, vec![]);
+        let access_methods_arena = RefCell::new(Vec::new());
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
+        let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
+            table_references.joined_tables(),
+            None,
+            &table_constraints,
+            &access_methods_arena,
+        )
+        .unwrap()
+        .unwrap();
+
+        let table_numbers = best_plan.table_numbers().collect::<Vec<_>>();
+        let access_method = &access_methods_arena.borrow()[best_plan.data[1].1];
+        let hash_join_where_terms = access_method.hash_join_constraints.as_ref().map(|keys| {
+            keys.iter()
+                .map(|&pos| {
+                    table_constraints[table_numbers[1]].constraints[pos]
+                        .where_clause_pos
+                        .0
+                })
+                .collect()
+        });
+        (
+            table_numbers,
+            hash_join_where_terms,
+            access_method.index.as_ref().map(|index| index.name.clone()),
+        )
+    }
+
+    #[test]
+    /// Test that [compute_best_join_order] probes a hash table built from the inner table
+    /// for an equijoin on columns without an index.
+    fn test_compute_best_join_order_hash_join() {
+        let (_, hash_join_where_terms, index) = _hash_join_access_method(false);
+        assert_eq!(hash_join_where_terms, Some(vec![0]));
+        assert!(index.is_none());
+    }
+
+    #[test]
+    /// Test that [compute_best_join_order] seeks an index on the join column instead of
+    /// building a hash table.
+    fn test_compute_best_join_order_hash_join_loses_to_index() {
+        let (table_numbers, hash_join_where_terms, index) = _hash_join_access_method(true);
+        assert_eq!(table_numbers, vec![0, 1]);
+        assert!(hash_join_where_terms.is_none());
+        assert_eq!(index.as_deref(), Some("t2_x"));
+    }
+
+    /// Returns the indexes [compute_best_join_order] searches for each disjunct of
+    /// `SELECT * FROM t1 WHERE <where_clause>`, where t1 has a rowid alias `id`, indexes on a
+    /// and b, and no index on c, or None if the OR term is not evaluated with one search per
+    /// disjunct. The rowid is searched with the index `rowid`.
+    fn _multi_index_or_access_method(
+        where_clause: impl FnOnce(TableInternalId) -> Expr,
+    ) -> Option<Vec<String>> {
+        let mut columns = vec![_create_column_rowid_alias("id")];
+        columns.extend(_create_column_list(&["a", "b", "c"], Type::Integer));
+        let t1 = _create_btree_table("t1", columns);
+        let mut table_id_counter = TableRefIdCounter::new();
+        let joined_tables = vec![_create_table_reference(t1, None, table_id_counter.next())];
+        let where_clause = vec![WhereTerm {
+            expr: where_clause(joined_tables[0].internal_id),
+            from_outer_join: None,
+            consumed: Cell::new(false),
+        }];
+        let mut available_indexes = HashMap::new();
+        available_indexes.insert(
+            "t1".to_string(),
+            vec![
+                _create_index("t1_a", "t1", &[("a", 1, SortOrder::Asc)]),
+                _create_index("t1_b", "t1", &[("b", 2, SortOrder::Asc)]),
+            ],
+        );
+
+        let table_references = TableReferences::new(joined_tables, vec![]);
+        let access_methods_arena = RefCell::new(Vec::new());
+        let table_constraints = constraints_from_where_clause(
+            &where_clause,
+            &table_references,
+            &available_indexes,
+            false,
+        )
+        .unwrap();
+        let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
+            table_references.joined_tables(),
+            None,
+            &table_constraints,
+            &access_methods_arena,
+        )
+        .unwrap()
+        .unwrap();
+
+        let access_method = &access_methods_arena.borrow()[best_plan.data[0].1];
+        let (_, branches) = access_method.or_branches.as_ref()?;
+        Some(
+            branches
+                .iter()
+                .map(|branch| {
+                    assert!(!branch.constraint_refs.is_empty());
+                    branch
+                        .index
+                        .as_ref()
+                        .map_or("rowid".to_string(), |index| index.name.clone())
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    /// Test that [compute_best_join_order] evaluates an OR term with one search per disjunct
+    /// when each disjunct can use an index or the rowid.
+    fn test_compute_best_join_order_multi_index_or() {
+        let eq = |table, column, value| {
+            Expr::Binary(
+                Box::new(_create_column_expr(table, column, column == 0)),
+                ast::Operator::Equals,
+                Box::new(_create_numeric_literal(value)),
+            )
+        };
+        let or = |lhs, rhs| Expr::Binary(Box::new(lhs), ast::Operator::Or, Box::new(rhs));
+        let and = |lhs, rhs| Expr::Binary(Box::new(lhs), ast::Operator::And, Box::new(rhs));
+
+        // a = 3 OR b = 7
+        assert_eq!(
+            _multi_index_or_access_method(|t| or(eq(t, 1, "3"), eq(t, 2, "7"))),
+            Some(vec!["t1_a".to_string(), "t1_b".to_string()])
+        );
+        // b = 7 OR id = 1500 OR (a = 3 AND c = 1)
+        assert_eq!(
+            _multi_index_or_access_method(|t| or(
+                or(eq(t, 2, "7"), eq(t, 0, "1500")),
+                and(eq(t, 1, "3"), eq(t, 3, "1"))
+            )),
+            Some(vec![
+                "t1_b".to_string(),
+                "rowid".to_string(),
+                "t1_a".to_string()
+            ])
+        );
+        // a = 3 OR c = 1: c has no index, so the table is scanned.
+        assert_eq!(
+            _multi_index_or_access_method(|t| or(eq(t, 1, "3"), eq(t, 3, "1"))),
+            None
+        );
+    }
+
+    /// Returns the index and direction [compute_best_join_order] picks to visit the rows of
+    /// `t(a, b)` in the order that answers `min(<column>)` (ascending) or `max(<column>)`
+    /// (descending), with an index on a and a descending index on b.
+    fn _min_max_access_method(
+        column: usize,
+        order: SortOrder,
+    ) -> (Option<String>, IterationDirection) {
+        let t = _create_btree_table("t", _create_column_list(&["a", "b"], Type::Integer));
+        let mut table_id_counter = TableRefIdCounter::new();
+        let joined_tables = vec![_create_table_reference(t, None, table_id_counter.next())];
+        let order_target = OrderTarget(
+            vec![ColumnOrder {
+                table_id: joined_tables[0].internal_id,
+                column_no: Some(column),
+                expr: _create_column_expr(joined_tables[0].internal_id, column, false),
+                order,
+            }],
+            EliminatesSort::OrderBy,
+        );
+        let mut available_indexes = HashMap::new();
+        available_indexes.insert(
+            "t".to_string(),
+            vec![
+                _create_index("t_a", "t", &[("a", 0, SortOrder::Asc)]),
+                _create_index("t_b_desc", "t", &[("b", 1, SortOrder::Desc)]),
+            ],
+        );
+
+        let table_references = TableReferences::new(joined_tables, vec![]);
+        let access_methods_arena = RefCell::new(Vec::new());
+        let table_constraints =
+            constraints_from_where_clause(&[], &table_references, &available_indexes, false)
+                .unwrap();
+        let BestJoinOrderResult { best_plan, .. } = compute_best_join_order(
+            table_references.joined_tables(),
+            Some(&order_target),
+            &table_constraints,
+            &access_methods_arena,
+        )
+        .unwrap()
+        .unwrap();
+
+        let access_method = &access_methods_arena.borrow()[best_plan.data[0].1];
+        assert!(access_method.is_scan());
+        (
+            access_method.index.as_ref().map(|index| index.name.clone()),
+            access_method.iter_dir,
+        )
+    }
+
+    #[test]
+    /// Test that [compute_best_join_order] scans an index on the argument of min() or max()
+    /// in the direction that visits the answer first.
+    fn test_compute_best_join_order_min_max() {
+        let index = |name: &str, iter_dir| (Some(name.to_string()), iter_dir);
+        assert_eq!(
+            _min_max_access_method(0, SortOrder::Asc),
+            index("t_a", IterationDirection::Forwards)
+        );
+        assert_eq!(
+            _min_max_access_method(0, SortOrder::Desc),
+            index("t_a", IterationDirection::Backwards)
+        );
+        assert_eq!(
+            _min_max_access_method(1, SortOrder::Asc),
+            index("t_b_desc", IterationDirection::Backwards)
+        );
+        assert_eq!(
+            _min_max_access_method(1, SortOrder::Desc),
+            index("t_b_desc", IterationDirection::Forwards)
+        );
+    }
+
+    /// Creates an index on `columns`, given as (name, position in table, order).
+    fn _create_index(
+        name: &str,
+        table_name: &str,
+        columns: &[(&str, usize, SortOrder)],
+    ) -> Arc<Index> {
+        Arc::new(Index {
+            name: name.to_string(),
+            table_name: table_name.to_string(),
+            columns: columns
+                .iter()
+                .map(|&(name, pos_in_table, order)| IndexColumn {
+                    name: name.to_string(),
+                    order,
+                    pos_in_table,
+                    collation: None,
+                    default: None,
+                    expr: None,
+                })
+                .collect(),
+            unique: false,
+            ephemeral: false,
+            root_page: 2,
+            has_rowid: true,
+            where_clause: None,
+            stat: None,
+        })
     }
 
     fn _create_column(c: &TestColumn) -> Column {
@@ -1647,6 +2203,8 @@ mod tests {
             has_rowid: true,
             is_strict: false,
             unique_sets: None,
+            is_temp: false,
+            has_autoincrement: false,
         })
     }
 
@@ -1667,6 +2225,7 @@ mod tests {
             internal_id,
             join_info,
             col_used_mask: ColumnUsedMask::new(),
+            index_hint: None,
         }
     }
 
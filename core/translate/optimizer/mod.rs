@@ -1,5 +1,6 @@
 use std::{cell::RefCell, cmp::Ordering, collections::HashMap, sync::Arc};
 
+use access_method::AccessMethod;
 use constraints::{
     constraints_from_where_clause, usable_constraints_for_join_order, Constraint, ConstraintRef,
 };
@@ -23,8 +24,9 @@ use crate::{
 use super::{
     emitter::Resolver,
     plan::{
-        DeletePlan, GroupBy, IterationDirection, JoinOrderMember, JoinedTable, Operation, Plan,
-        Search, SeekDef, SeekKey, SelectPlan, TableReferences, UpdatePlan, WhereTerm,
+        DeletePlan, GroupBy, HashJoin, IterationDirection, JoinOrderMember, JoinedTable,
+        MultiIndexOr, Operation, Plan, Search, SeekDef, SeekKey, SelectPlan, TableReferences,
+        UpdatePlan, WhereTerm,
     },
 };
 
@@ -36,17 +38,17 @@ pub(crate) mod lift_common_subexpressions;
 pub(crate) mod order;
 
 #[tracing::instrument(skip_all, level = tracing::Level::DEBUG)]
-pub fn optimize_plan(plan: &mut Plan, schema: &Schema) -> Result<()> {
+pub fn optimize_plan(plan: &mut Plan, schema: &Schema, case_sensitive_like: bool) -> Result<()> {
     match plan {
-        Plan::Select(plan) => optimize_select_plan(plan, schema)?,
+        Plan::Select(plan) => optimize_select_plan(plan, schema, case_sensitive_like)?,
         Plan::Delete(plan) => optimize_delete_plan(plan, schema)?,
         Plan::Update(plan) => optimize_update_plan(plan, schema)?,
         Plan::CompoundSelect {
             left, right_most, ..
         } => {
-            optimize_select_plan(right_most, schema)?;
+            optimize_select_plan(right_most, schema, case_sensitive_like)?;
             for (plan, _) in left {
-                optimize_select_plan(plan, schema)?;
+                optimize_select_plan(plan, schema, case_sensitive_like)?;
             }
         }
     }
@@ -60,8 +62,12 @@ pub fn optimize_plan(plan: &mut Plan, schema: &Schema) -> Result<()> {
  * TODO: these could probably be done in less passes,
  * but having them separate makes them easier to understand
  */
-pub fn optimize_select_plan(plan: &mut SelectPlan, schema: &Schema) -> Result<()> {
-    optimize_subqueries(plan, schema)?;
+pub fn optimize_select_plan(
+    plan: &mut SelectPlan,
+    schema: &Schema,
+    case_sensitive_like: bool,
+) -> Result<()> {
+    optimize_subqueries(plan, schema, case_sensitive_like)?;
     rewrite_exprs_select(plan)?;
     if let ConstantConditionEliminationResult::ImpossibleCondition =
         eliminate_constant_conditions(&mut plan.where_clause)?
@@ -70,21 +76,83 @@ pub fn optimize_select_plan(plan: &mut SelectPlan, schema: &Schema) -> Result<()
         return Ok(());
     }
 
+    // For `SELECT min(x) FROM t` or max(x), ask for an access method that visits the rows ordered
+    // by x, so that the aggregate is answered by the first non-NULL row.
+    let min_max_order_target = plan.min_max_order_target();
+    let mut order_by = match &min_max_order_target {
+        Some(target) => Some(vec![target.clone()]),
+        None => plan.order_by.take(),
+    };
+
+    let available_indexes = available_indexes(&plan.table_references, schema);
     let best_join_order = optimize_table_access(
         &mut plan.table_references,
-        &schema.indexes,
+        &available_indexes,
         &mut plan.where_clause,
-        &mut plan.order_by,
+        &mut order_by,
         &mut plan.group_by,
+        case_sensitive_like,
     )?;
 
     if let Some(best_join_order) = best_join_order {
         plan.join_order = best_join_order;
     }
 
+    match min_max_order_target {
+        Some((_, sort_order)) => {
+            if order_by.is_none() {
+                plan.min_max_optimized = true;
+                optimize_min_max_scan(plan, sort_order);
+            }
+        }
+        None => plan.order_by = order_by,
+    }
+
     Ok(())
 }
 
+/// When min() is answered by a full index scan, NULLs sort first in the index and would all be visited
+/// before the first non-NULL value, so turn the scan into a seek past the NULL entries.
+/// The seek key is a NULL pad, which compares lower than any non-NULL value in index key comparisons.
+fn optimize_min_max_scan(plan: &mut SelectPlan, sort_order: SortOrder) {
+    if sort_order != SortOrder::Asc {
+        return;
+    }
+    let table = &mut plan.table_references.joined_tables_mut()[0];
+    let Operation::Scan {
+        iter_dir,
+        index: Some(index),
+    } = &table.op
+    else {
+        return;
+    };
+    // A descending index is laid out in reverse order, so its NULLs are at the end and are skipped
+    // by seeking backwards from the end.
+    let op = match iter_dir {
+        IterationDirection::Forwards => SeekOp::GT,
+        IterationDirection::Backwards => SeekOp::LT,
+    };
+    let seek_def = SeekDef {
+        key: vec![(
+            ast::Expr::Literal(ast::Literal::Null),
+            index.columns[0].order,
+        )],
+        iter_dir: *iter_dir,
+        termination_last_key: None,
+        seek: Some(SeekKey {
+            len: 0,
+            null_pad: true,
+            op,
+        }),
+        termination: None,
+    };
+    table.op = Operation::Search(Search::Seek {
+        index: Some(index.clone()),
+        seek_def,
+        skip_scan: false,
+    });
+}
+
 fn optimize_delete_plan(plan: &mut DeletePlan, _schema: &Schema) -> Result<()> {
     rewrite_exprs_delete(plan)?;
     if let ConstantConditionEliminationResult::ImpossibleCondition =
@@ -130,16 +198,44 @@ fn optimize_update_plan(plan: &mut UpdatePlan, _schema: &Schema) -> Result<()> {
     Ok(())
 }
 
-fn optimize_subqueries(plan: &mut SelectPlan, schema: &Schema) -> Result<()> {
+fn optimize_subqueries(
+    plan: &mut SelectPlan,
+    schema: &Schema,
+    case_sensitive_like: bool,
+) -> Result<()> {
     for table in plan.table_references.joined_tables_mut() {
         if let Table::FromClauseSubquery(from_clause_subquery) = &mut table.table {
-            optimize_select_plan(&mut from_clause_subquery.plan, schema)?;
+            optimize_select_plan(&mut from_clause_subquery.plan, schema, case_sensitive_like)?;
         }
     }
 
     Ok(())
 }
 
+/// Collects the indexes of the btree tables referenced by the plan, keyed by table name.
+/// The indexes are looked up in the database of each table, and a name that refers to tables of
+/// both the main and the temp database gets no indexes, as the key can't tell them apart.
+fn available_indexes(
+    table_references: &TableReferences,
+    schema: &Schema,
+) -> HashMap<String, Vec<Arc<Index>>> {
+    let mut dbs = HashMap::new();
+    let mut available_indexes = HashMap::new();
+    for table in table_references.joined_tables() {
+        let Some(btree) = table.table.btree() else {
+            continue;
+        };
+        if *dbs.entry(btree.name.clone()).or_insert(btree.db()) != btree.db() {
+            available_indexes.insert(btree.name.clone(), Vec::new());
+            continue;
+        }
+        available_indexes
+            .entry(btree.name.clone())
+            .or_insert_with(|| schema.get_table_indices(&btree).to_vec());
+    }
+    available_indexes
+}
+
 /// Optimize the join order and index selection for a query.
 ///
 /// This function does the following:
@@ -157,11 +253,16 @@ fn optimize_table_access(
     where_clause: &mut Vec<WhereTerm>,
     order_by: &mut Option<Vec<(ast::Expr, SortOrder)>>,
     group_by: &mut Option<GroupBy>,
+    case_sensitive_like: bool,
 ) -> Result<Option<Vec<JoinOrderMember>>> {
     let access_methods_arena = RefCell::new(Vec::new());
     let maybe_order_target = compute_order_target(order_by, group_by.as_mut());
-    let constraints_per_table =
-        constraints_from_where_clause(where_clause, &table_references, available_indexes)?;
+    let constraints_per_table = constraints_from_where_clause(
+        where_clause,
+        table_references,
+        available_indexes,
+        case_sensitive_like,
+    )?;
     let Some(best_join_order_result) = compute_best_join_order(
         table_references.joined_tables_mut(),
         maybe_order_target.as_ref(),
@@ -240,6 +341,47 @@ fn optimize_table_access(
     for (i, join_order_member) in best_join_order.iter().enumerate() {
         let table_idx = join_order_member.original_idx;
         let access_method = &access_methods_arena.borrow()[best_access_methods[i]];
+        if let Some(hash_join_constraints) = &access_method.hash_join_constraints {
+            let constraints = &constraints_per_table[table_idx].constraints;
+            let mut build_columns = Vec::with_capacity(hash_join_constraints.len());
+            let mut probe_exprs = Vec::with_capacity(hash_join_constraints.len());
+            for &constraint_vec_pos in hash_join_constraints.iter() {
+                let constraint = &constraints[constraint_vec_pos];
+                assert!(
+                    !where_clause[constraint.where_clause_pos.0].consumed.get(),
+                    "trying to consume a where clause term twice: {:?}",
+                    where_clause[constraint.where_clause_pos.0]
+                );
+                where_clause[constraint.where_clause_pos.0]
+                    .consumed
+                    .set(true);
+                build_columns.push(
+                    constraint
+                        .table_col_pos
+                        .expect("hash join keys are constraints on columns"),
+                );
+                probe_exprs.push(constraint.get_constraining_expr(where_clause));
+            }
+            joined_tables[table_idx].op = Operation::HashJoin(HashJoin {
+                build_columns,
+                probe_exprs,
+            });
+            continue;
+        }
+        if let Some((or_candidate_pos, branches)) = &access_method.or_branches {
+            // The OR term is not consumed: each branch only finds candidate rows for its disjunct,
+            // and the whole term is still evaluated for every row found.
+            let or_candidate = &constraints_per_table[table_idx].or_candidates[*or_candidate_pos];
+            let branches = branches
+                .iter()
+                .zip(or_candidate.branches.iter())
+                .map(|(branch, (branch_terms, branch_constraints))| {
+                    build_search(branch, &branch_constraints.constraints, branch_terms)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            joined_tables[table_idx].op = Operation::MultiIndexOr(MultiIndexOr { branches });
+            continue;
+        }
         if access_method.is_scan() {
             #[cfg(feature = "index_experimental")]
             let try_to_build_ephemeral_index = {
@@ -249,7 +391,14 @@ fn optimize_table_access(
                     &joined_tables[table_idx].table,
                     Table::FromClauseSubquery(_)
                 );
-                !is_leftmost_table && !uses_index && !source_table_is_from_clause_subquery
+                let is_not_indexed = matches!(
+                    joined_tables[table_idx].index_hint,
+                    Some(crate::translate::plan::IndexHint::NotIndexed)
+                );
+                !is_leftmost_table
+                    && !uses_index
+                    && !source_table_is_from_clause_subquery
+                    && !is_not_indexed
             };
             #[cfg(not(feature = "index_experimental"))]
             let try_to_build_ephemeral_index = false;
@@ -273,11 +422,19 @@ fn optimize_table_access(
                 };
                 continue;
             };
-            let temp_constraint_refs = (0..table_constraints.constraints.len())
-                .map(|i| ConstraintRef {
-                    constraint_vec_pos: i,
-                    index_col_pos: table_constraints.constraints[i].table_col_pos,
-                    sort_order: SortOrder::Asc,
+            // Only the constraints on columns can be used, since the ephemeral index has no expressions.
+            // The range of a LIKE or GLOB prefix is not used either, since it depends on the collation of the index.
+            let temp_constraint_refs = table_constraints
+                .constraints
+                .iter()
+                .enumerate()
+                .filter(|(_, constraint)| constraint.prefix_range.is_none())
+                .filter_map(|(i, constraint)| {
+                    Some(ConstraintRef {
+                        constraint_vec_pos: i,
+                        index_col_pos: constraint.table_col_pos?,
+                        sort_order: SortOrder::Asc,
+                    })
                 })
                 .collect::<Vec<_>>();
             let usable_constraint_refs = usable_constraints_for_join_order(
@@ -306,6 +463,7 @@ fn optimize_table_access(
                     access_method.iter_dir,
                     where_clause,
                 )?,
+                skip_scan: false,
             });
         } else {
             let constraint_refs = access_method.constraint_refs;
@@ -313,6 +471,10 @@ fn optimize_table_access(
             for cref in constraint_refs.iter() {
                 let constraint =
                     &constraints_per_table[table_idx].constraints[cref.constraint_vec_pos];
+                // A LIKE or GLOB term is still evaluated for the rows in the range of its prefix.
+                if constraint.prefix_range.is_some() {
+                    continue;
+                }
                 assert!(
                     !where_clause[constraint.where_clause_pos.0].consumed.get(),
                     "trying to consume a where clause term twice: {:?}",
@@ -322,45 +484,71 @@ fn optimize_table_access(
                     .consumed
                     .set(true);
             }
-            if let Some(index) = &access_method.index {
-                joined_tables[table_idx].op = Operation::Search(Search::Seek {
-                    index: Some(index.clone()),
-                    seek_def: build_seek_def_from_constraints(
-                        &constraints_per_table[table_idx].constraints,
-                        &constraint_refs,
-                        access_method.iter_dir,
-                        where_clause,
-                    )?,
-                });
-                continue;
-            }
-            assert!(
-                constraint_refs.len() == 1,
-                "expected exactly one constraint for rowid seek, got {:?}",
-                constraint_refs
-            );
-            let constraint = &constraints_per_table[table_idx].constraints
-                [constraint_refs[0].constraint_vec_pos];
-            joined_tables[table_idx].op = match constraint.operator {
-                ast::Operator::Equals => Operation::Search(Search::RowidEq {
-                    cmp_expr: constraint.get_constraining_expr(where_clause),
-                }),
-                _ => Operation::Search(Search::Seek {
-                    index: None,
-                    seek_def: build_seek_def_from_constraints(
-                        &constraints_per_table[table_idx].constraints,
-                        &constraint_refs,
-                        access_method.iter_dir,
-                        where_clause,
-                    )?,
-                }),
-            };
+            joined_tables[table_idx].op = Operation::Search(build_search(
+                access_method,
+                &constraints_per_table[table_idx].constraints,
+                where_clause,
+            )?);
         }
     }
 
     Ok(Some(best_join_order))
 }
 
+/// Build the [Search] of an access method that looks up the table by its rowid or by an index,
+/// using at least one constraint.
+fn build_search(
+    access_method: &AccessMethod,
+    constraints: &[Constraint],
+    where_clause: &[WhereTerm],
+) -> Result<Search> {
+    let constraint_refs = access_method.constraint_refs;
+    if let Some(index) = &access_method.index {
+        let seek_def = if access_method.skip_scan {
+            build_skip_scan_seek_def(
+                index,
+                constraints,
+                constraint_refs,
+                access_method.iter_dir,
+                where_clause,
+            )?
+        } else {
+            build_seek_def_from_constraints(
+                constraints,
+                constraint_refs,
+                access_method.iter_dir,
+                where_clause,
+            )?
+        };
+        return Ok(Search::Seek {
+            index: Some(index.clone()),
+            seek_def,
+            skip_scan: access_method.skip_scan,
+        });
+    }
+    assert!(
+        constraint_refs.len() == 1,
+        "expected exactly one constraint for rowid seek, got {:?}",
+        constraint_refs
+    );
+    let constraint = &constraints[constraint_refs[0].constraint_vec_pos];
+    Ok(match constraint.operator {
+        ast::Operator::Equals => Search::RowidEq {
+            cmp_expr: constraint.get_constraining_expr(where_clause),
+        },
+        _ => Search::Seek {
+            index: None,
+            seek_def: build_seek_def_from_constraints(
+                constraints,
+                constraint_refs,
+                access_method.iter_dir,
+                where_clause,
+            )?,
+            skip_scan: false,
+        },
+    })
+}
+
 #[derive(Debug, PartialEq, Clone)]
 enum ConstantConditionEliminationResult {
     Continue,
@@ -783,6 +971,7 @@ fn ephemeral_index_build(
             pos_in_table: i,
             collation: c.collation,
             default: c.default.clone(),
+            expr: None,
         })
         // only include columns that are used in the query
         .filter(|c| table_reference.column_is_used(c.pos_in_table))
@@ -792,11 +981,11 @@ fn ephemeral_index_build(
         let a_constraint = constraint_refs
             .iter()
             .enumerate()
-            .find(|(_, c)| constraints[c.constraint_vec_pos].table_col_pos == a.pos_in_table);
+            .find(|(_, c)| constraints[c.constraint_vec_pos].table_col_pos == Some(a.pos_in_table));
         let b_constraint = constraint_refs
             .iter()
             .enumerate()
-            .find(|(_, c)| constraints[c.constraint_vec_pos].table_col_pos == b.pos_in_table);
+            .find(|(_, c)| constraints[c.constraint_vec_pos].table_col_pos == Some(b.pos_in_table));
         match (a_constraint, b_constraint) {
             (Some(_), None) => Ordering::Less,
             (None, Some(_)) => Ordering::Greater,
@@ -819,6 +1008,8 @@ fn ephemeral_index_build(
             .table
             .btree()
             .map_or(false, |btree| btree.has_rowid),
+        where_clause: None,
+        stat: None,
     };
 
     ephemeral_index
@@ -840,13 +1031,75 @@ pub fn build_seek_def_from_constraints(
         .iter()
         .map(|cref| cref.as_seek_key_column(constraints, where_clause))
         .collect();
+    let last_constraint = &constraints[constraint_refs.last().unwrap().constraint_vec_pos];
+    build_seek_def_from_key(key, last_constraint, iter_dir)
+}
 
+/// Build the [SeekDef] of a skip-scan of an index, for a given list of [Constraint]s on the columns after
+/// its leading column. See [Search::Seek] for more details about skip-scans.
+fn build_skip_scan_seek_def(
+    index: &Index,
+    constraints: &[Constraint],
+    constraint_refs: &[ConstraintRef],
+    iter_dir: IterationDirection,
+    where_clause: &[WhereTerm],
+) -> Result<SeekDef> {
+    assert!(
+        !constraint_refs.is_empty(),
+        "cannot build skip-scan seek def from empty list of constraint refs"
+    );
+    // The value of the leading column is read from the index for each of its distinct values,
+    // so its key expression is only a placeholder.
+    let key = std::iter::once((
+        ast::Expr::Literal(ast::Literal::Null),
+        index.columns[0].order,
+    ))
+    .chain(
+        constraint_refs
+            .iter()
+            .map(|cref| cref.as_seek_key_column(constraints, where_clause)),
+    )
+    .collect();
+    let last_constraint = &constraints[constraint_refs.last().unwrap().constraint_vec_pos];
+    build_seek_def_from_key(key, last_constraint, iter_dir)
+}
+
+/// Build a [SeekDef] for an index key whose last column is constrained by `last_constraint`.
+fn build_seek_def_from_key(
+    key: Vec<(ast::Expr, SortOrder)>,
+    last_constraint: &Constraint,
+    iter_dir: IterationDirection,
+) -> Result<SeekDef> {
     // We know all but potentially the last term is an equality, so we can use the operator of the last term
     // to form the SeekOp
-    let op = constraints[constraint_refs.last().unwrap().constraint_vec_pos].operator;
+    let op = last_constraint.operator;
 
-    let seek_def = build_seek_def(op, iter_dir, key)?;
-    Ok(seek_def)
+    let Some(prefix_range) = &last_constraint.prefix_range else {
+        return build_seek_def(op, iter_dir, key);
+    };
+    // The range of a LIKE or GLOB prefix has two bounds: build the seek definitions of each bound on its own,
+    // and combine the seek of the bound the scan starts from with the termination of the bound it ends at.
+    // A scan forwards on an ascending index starts from the lower bound, and each of iterating backwards
+    // and the index column being descending flips that.
+    let mut upper_key: Vec<(ast::Expr, SortOrder)> = key.clone();
+    let sort_order = upper_key.last().unwrap().1;
+    upper_key.last_mut().unwrap().0 = prefix_range.upper.clone();
+    let lower_seek_def = build_seek_def(ast::Operator::GreaterEquals, iter_dir, key)?;
+    let upper_seek_def = build_seek_def(ast::Operator::Less, iter_dir, upper_key)?;
+    let starts_from_lower =
+        (iter_dir == IterationDirection::Forwards) == (sort_order == SortOrder::Asc);
+    let (start, end, end_bound) = if starts_from_lower {
+        (lower_seek_def, upper_seek_def, &prefix_range.upper)
+    } else {
+        (upper_seek_def, lower_seek_def, &prefix_range.lower)
+    };
+    Ok(SeekDef {
+        key: start.key,
+        seek: start.seek,
+        termination: end.termination,
+        iter_dir,
+        termination_last_key: Some(end_bound.clone()),
+    })
 }
 
 /// Build a [SeekDef] for a given comparison operator and index key.
@@ -897,6 +1150,7 @@ fn build_seek_def(
         (IterationDirection::Forwards, ast::Operator::Equals) => SeekDef {
             key,
             iter_dir,
+            termination_last_key: None,
             seek: Some(SeekKey {
                 len: key_len,
                 null_pad: false,
@@ -931,6 +1185,7 @@ fn build_seek_def(
             SeekDef {
                 key,
                 iter_dir,
+                termination_last_key: None,
                 seek: if seek_key_len > 0 {
                     Some(SeekKey {
                         len: seek_key_len,
@@ -979,6 +1234,7 @@ fn build_seek_def(
             SeekDef {
                 key,
                 iter_dir,
+                termination_last_key: None,
                 seek: if seek_key_len > 0 {
                     Some(SeekKey {
                         len: seek_key_len,
@@ -1027,6 +1283,7 @@ fn build_seek_def(
             SeekDef {
                 key,
                 iter_dir,
+                termination_last_key: None,
                 seek: if seek_key_len > 0 {
                     Some(SeekKey {
                         len: seek_key_len,
@@ -1070,6 +1327,7 @@ fn build_seek_def(
             SeekDef {
                 key,
                 iter_dir,
+                termination_last_key: None,
                 seek: if seek_key_len > 0 {
                     Some(SeekKey {
                         len: seek_key_len,
@@ -1098,6 +1356,7 @@ fn build_seek_def(
         (IterationDirection::Backwards, ast::Operator::Equals) => SeekDef {
             key,
             iter_dir,
+            termination_last_key: None,
             seek: Some(SeekKey {
                 len: key_len,
                 op: SeekOp::LE { eq_only: true },
@@ -1137,6 +1396,7 @@ fn build_seek_def(
             SeekDef {
                 key,
                 iter_dir,
+                termination_last_key: None,
                 seek: if seek_key_len > 0 {
                     Some(SeekKey {
                         len: seek_key_len,
@@ -1185,6 +1445,7 @@ fn build_seek_def(
             SeekDef {
                 key,
                 iter_dir,
+                termination_last_key: None,
                 seek: if seek_key_len > 0 {
                     Some(SeekKey {
                         len: seek_key_len,
@@ -1233,6 +1494,7 @@ fn build_seek_def(
             SeekDef {
                 key,
                 iter_dir,
+                termination_last_key: None,
                 seek: if seek_key_len > 0 {
                     Some(SeekKey {
                         len: seek_key_len,
@@ -1281,6 +1543,7 @@ fn build_seek_def(
             SeekDef {
                 key,
                 iter_dir,
+                termination_last_key: None,
                 seek: if seek_key_len > 0 {
                     Some(SeekKey {
                         len: seek_key_len,
@@ -3,8 +3,15 @@ use std::cell::RefCell;
 use limbo_sqlite3_parser::ast::{self, SortOrder, TableInternalId};
 
 use crate::{
-    translate::plan::{GroupBy, IterationDirection, JoinedTable},
+    function::Func,
+    schema::IndexColumn,
+    translate::{
+        expr::{walk_expr, WalkControl},
+        index::bind_index_expr,
+        plan::{GroupBy, IterationDirection, JoinedTable},
+    },
     util::exprs_are_equivalent,
+    Result,
 };
 
 use super::{access_method::AccessMethod, join::JoinN};
@@ -13,10 +20,57 @@ use super::{access_method::AccessMethod, join::JoinN};
 /// A convenience struct for representing a (table_no, column_no, [SortOrder]) tuple.
 pub struct ColumnOrder {
     pub table_id: TableInternalId,
-    pub column_no: usize,
+    /// The column ordered by, or None if the term is an expression over the columns of the table,
+    /// whose order can only be provided by an index on that expression.
+    pub column_no: Option<usize>,
+    /// The expression of the term, e.g. `t.x` or `lower(t.x)`.
+    pub expr: ast::Expr,
     pub order: SortOrder,
 }
 
+impl ColumnOrder {
+    /// Whether `index_col`, a column of an index on `table_reference`, has the values of this term.
+    pub fn is_index_column(&self, index_col: &IndexColumn, table_reference: &JoinedTable) -> bool {
+        match (&index_col.expr, self.column_no) {
+            (None, Some(column_no)) => index_col.pos_in_table == column_no,
+            (Some(index_expr), None) => table_reference.btree().is_some_and(|btree_table| {
+                bind_index_expr(index_expr, &btree_table, table_reference.internal_id)
+                    .is_ok_and(|index_expr| exprs_are_equivalent(&index_expr, &self.expr))
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// Returns the table a term of an [OrderTarget] orders by, if it is a column or a deterministic expression
+/// over the columns of a single table, which an index could provide the order of.
+fn order_term_table(expr: &ast::Expr) -> Option<TableInternalId> {
+    let mut table_id = None;
+    let mut orderable = true;
+    walk_expr(expr, &mut |expr: &ast::Expr| -> Result<WalkControl> {
+        match expr {
+            ast::Expr::Column { table, .. } | ast::Expr::RowId { table, .. } => {
+                orderable &= table_id.is_none_or(|table_id| table_id == *table);
+                table_id = Some(*table);
+            }
+            ast::Expr::FunctionCall { name, args, .. } => {
+                orderable &=
+                    Func::resolve_function(&name.0, args.as_ref().map_or(0, |args| args.len()))
+                        .is_ok_and(|func| func.is_deterministic());
+            }
+            ast::Expr::FunctionCallStar { .. }
+            | ast::Expr::Exists(_)
+            | ast::Expr::InSelect { .. }
+            | ast::Expr::Subquery(_)
+            | ast::Expr::Variable(_) => orderable = false,
+            _ => {}
+        }
+        Ok(WalkControl::Continue)
+    })
+    .ok()?;
+    table_id.filter(|_| orderable)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 /// If an [OrderTarget] is satisfied, then [EliminatesSort] describes which part of the query no longer requires sorting.
 pub enum EliminatesSort {
@@ -39,26 +93,25 @@ impl OrderTarget {
         if list.clone().count() == 0 {
             return None;
         }
-        if list
-            .clone()
-            .any(|(expr, _)| !matches!(expr, ast::Expr::Column { .. }))
-        {
-            return None;
-        }
-        Some(OrderTarget(
-            list.map(|(expr, order)| {
-                let ast::Expr::Column { table, column, .. } = expr else {
-                    unreachable!();
-                };
-                ColumnOrder {
+        let mut column_orders = Vec::new();
+        for (expr, order) in list {
+            let column_order = match expr {
+                ast::Expr::Column { table, column, .. } => ColumnOrder {
                     table_id: *table,
-                    column_no: *column,
+                    column_no: Some(*column),
+                    expr: expr.clone(),
                     order,
-                }
-            })
-            .collect(),
-            eliminates_sort,
-        ))
+                },
+                _ => ColumnOrder {
+                    table_id: order_term_table(expr)?,
+                    column_no: None,
+                    expr: expr.clone(),
+                    order,
+                },
+            };
+            column_orders.push(column_order);
+        }
+        Some(OrderTarget(column_orders, eliminates_sort))
     }
 }
 
@@ -172,6 +225,11 @@ pub fn plan_satisfies_order_target(
 
         // Check if this table has an access method that provides the right ordering.
         let access_method = &access_methods_arena.borrow()[*access_method_index];
+        // Hash joins make no guarantees about the order of the rows matching a probe,
+        // and a multi-index OR visits the rows of each disjunct in turn.
+        if access_method.hash_join_constraints.is_some() || access_method.or_branches.is_some() {
+            return false;
+        }
         let iter_dir = access_method.iter_dir;
         let index = access_method.index.as_ref();
         match index {
@@ -185,7 +243,7 @@ pub fn plan_satisfies_order_target(
                 let Some(rowid_alias_col) = rowid_alias_col else {
                     return false;
                 };
-                let correct_column = target_col.column_no == rowid_alias_col;
+                let correct_column = target_col.column_no == Some(rowid_alias_col);
                 if !correct_column {
                     return false;
                 }
@@ -209,7 +267,7 @@ pub fn plan_satisfies_order_target(
                 // All of the index columns must match the next required columns in the order target.
                 for index_col in index.columns.iter() {
                     let target_col = &order_target.0[target_col_idx];
-                    let correct_column = target_col.column_no == index_col.pos_in_table;
+                    let correct_column = target_col.is_index_column(index_col, table_ref);
                     if !correct_column {
                         return false;
                     }
@@ -442,6 +442,9 @@ pub struct SelectPlan {
     pub distinctness: Distinctness,
     /// values: https://sqlite.org/syntax/select-core.html
     pub values: Vec<Vec<Expr>>,
+    /// whether the single min() or max() aggregate of the query is answered by the first
+    /// non-NULL row of an access method ordered by its argument, see [SelectPlan::min_max_order_target].
+    pub min_max_optimized: bool,
 }
 
 impl SelectPlan {
@@ -467,6 +470,8 @@ impl SelectPlan {
             || self.table_references.outer_query_refs().len() != 0
             || self.result_columns.len() != 1
             || self.group_by.is_some()
+            || self.limit.is_some()
+            || self.offset.is_some()
             || self.contains_constant_false_condition
         // TODO: (pedrocarlo) maybe can optimize to use the count optmization with more columns
         {
@@ -498,6 +503,42 @@ impl SelectPlan {
         }
         true
     }
+
+    /// Checks to see if the query is of the format `SELECT min(<col>) FROM <tbl> [WHERE ...]` (or max),
+    /// and if so returns the ordering that makes the first non-NULL row visited hold the answer:
+    /// ascending on the argument for min() and descending for max().
+    pub fn min_max_order_target(&self) -> Option<(ast::Expr, SortOrder)> {
+        if self.aggregates.len() != 1
+            || self.group_by.is_some()
+            || self.order_by.is_some()
+            || self.table_references.joined_tables().len() != 1
+            || self.contains_constant_false_condition
+        {
+            return None;
+        }
+        let table_ref = self.table_references.joined_tables().first().unwrap();
+        if !matches!(table_ref.table, Table::BTree(..)) {
+            return None;
+        }
+        let agg = self.aggregates.first().unwrap();
+        let sort_order = match agg.func {
+            AggFunc::Min => SortOrder::Asc,
+            AggFunc::Max => SortOrder::Desc,
+            _ => return None,
+        };
+        if agg.is_distinct() || agg.args.len() != 1 {
+            return None;
+        }
+        let arg = agg.args.first().unwrap();
+        match arg {
+            ast::Expr::Column { table, .. } | ast::Expr::RowId { table, .. }
+                if *table == table_ref.internal_id =>
+            {
+                Some((arg.clone(), sort_order))
+            }
+            _ => None,
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -614,6 +655,21 @@ pub struct JoinedTable {
     /// Bitmask of columns that are referenced in the query.
     /// Used to decide whether a covering index can be used.
     pub col_used_mask: ColumnUsedMask,
+    /// The INDEXED BY or NOT INDEXED clause given for this table reference, if any.
+    pub index_hint: Option<IndexHint>,
+}
+
+/// Restricts how the query planner may access a table, e.g.
+/// ```sql
+/// SELECT * FROM users INDEXED BY users_age_idx WHERE age > 30;
+/// SELECT * FROM users NOT INDEXED WHERE age > 30;
+/// ```
+#[derive(Debug, Clone)]
+pub enum IndexHint {
+    /// The table must be accessed using this index; the statement fails if the index cannot be used.
+    IndexedBy(Arc<Index>),
+    /// The table must be accessed without an index, i.e. with a scan or a rowid lookup.
+    NotIndexed,
 }
 
 #[derive(Debug, Clone)]
@@ -875,6 +931,14 @@ pub enum Operation {
     // This operation is used to search for a row in a table using an index
     // (i.e. a primary key or a secondary index)
     Search(Search),
+    // Hash join operation
+    // This operation is used to look up the rows of a table matching equality constraints
+    // in a hash table built from the table, instead of scanning it for every row of the outer loops.
+    HashJoin(HashJoin),
+    // Multi-index OR operation
+    // This operation is used to evaluate a WHERE term of the form `x OR y OR ...` by running one
+    // search per disjunct and visiting each matching row once.
+    MultiIndexOr(MultiIndexOr),
 }
 
 impl Operation {
@@ -883,6 +947,8 @@ impl Operation {
             Operation::Scan { index, .. } => index.as_ref(),
             Operation::Search(Search::RowidEq { .. }) => None,
             Operation::Search(Search::Seek { index, .. }) => index.as_ref(),
+            Operation::HashJoin(_) => None,
+            Operation::MultiIndexOr(_) => None,
         }
     }
 }
@@ -941,6 +1007,7 @@ impl JoinedTable {
             internal_id,
             join_info,
             col_used_mask: ColumnUsedMask::new(),
+            index_hint: None,
         }
     }
 
@@ -964,6 +1031,33 @@ impl JoinedTable {
     ) -> Result<(Option<CursorID>, Option<CursorID>)> {
         let index = self.op.index();
         match &self.table {
+            Table::BTree(btree) if matches!(self.op, Operation::HashJoin(_)) => {
+                // The rows of a hash joined table are read from the hash table, which is keyed like a table cursor.
+                // The cursor used to build the hash table is opened ad-hoc when needed.
+                let table_cursor_id = program.alloc_cursor_id_keyed(
+                    CursorKey::table(self.internal_id),
+                    CursorType::HashTable(btree.clone()),
+                );
+                Ok((Some(table_cursor_id), None))
+            }
+            Table::BTree(btree) if matches!(self.op, Operation::MultiIndexOr(_)) => {
+                let Operation::MultiIndexOr(multi_index_or) = &self.op else {
+                    unreachable!();
+                };
+                // The rows found by the branches are always read from the table, so that the
+                // cursors of the branch indexes don't need to be tracked past each branch.
+                let table_cursor_id = program.alloc_cursor_id_keyed(
+                    CursorKey::table(self.internal_id),
+                    CursorType::BTreeTable(btree.clone()),
+                );
+                for index in multi_index_or.indexes() {
+                    program.alloc_cursor_id_keyed(
+                        CursorKey::index(self.internal_id, index.clone()),
+                        CursorType::BTreeIndex(index.clone()),
+                    );
+                }
+                Ok((Some(table_cursor_id), None))
+            }
             Table::BTree(btree) => {
                 let use_covering_index = self.utilizes_covering_index();
                 let index_is_ephemeral = index.map_or(false, |index| index.ephemeral);
@@ -1022,7 +1116,8 @@ impl JoinedTable {
             return false;
         }
         let mut index_cols_mask = ColumnUsedMask::new();
-        for col in index.columns.iter() {
+        // The values of the columns an indexed expression refers to are not in the index.
+        for col in index.columns.iter().filter(|col| col.expr.is_none()) {
             index_cols_mask.set(col.pos_in_table);
         }
 
@@ -1077,6 +1172,13 @@ pub struct SeekDef {
     pub termination: Option<TerminationKey>,
     /// The direction of the scan that follows the seek.
     pub iter_dir: IterationDirection,
+    /// The value of the last column of the termination key, if it differs from the one in [SeekDef::key].
+    /// This is the case for the range of a LIKE or GLOB pattern with a constant prefix, e.g. given:
+    /// - CREATE INDEX i ON t (x COLLATE NOCASE)
+    /// - SELECT * FROM t WHERE x LIKE 'ab%'
+    ///
+    /// The key is [('ab', ASC)], and the termination key is [('ac', ASC)].
+    pub termination_last_key: Option<ast::Expr>,
 }
 
 /// A condition to use when seeking.
@@ -1122,9 +1224,59 @@ pub enum Search {
     Seek {
         index: Option<Arc<Index>>,
         seek_def: SeekDef,
+        /// Whether the seek is a skip-scan of the index, i.e. whether the first column of [SeekDef::key]
+        /// is not constrained by the query, and the seek is instead repeated for each distinct value of
+        /// the leading column of the index. For example, given:
+        /// - CREATE INDEX i ON t (tenant_id, created_at)
+        /// - SELECT * FROM t WHERE created_at > 100
+        ///
+        /// The key is [(<tenant_id>, ASC), (100, ASC)], and the scan seeks to GT(tenant_id:1, created_at:100),
+        /// then GT(tenant_id:2, created_at:100) and so on, for each value of tenant_id found in the index.
+        skip_scan: bool,
     },
 }
 
+/// A hash join of a table with the tables to its left in the join order.
+///
+/// The table is read once into a hash table keyed on [HashJoin::build_columns] (the build phase),
+/// which is then probed with the values of [HashJoin::probe_exprs] for each row of the outer loops.
+#[derive(Clone, Debug)]
+pub struct HashJoin {
+    /// The positions of the key columns in the table.
+    pub build_columns: Vec<usize>,
+    /// The expressions whose values are looked up in the hash table, one per key column.
+    /// These only reference tables to the left of the table in the join order.
+    pub probe_exprs: Vec<ast::Expr>,
+}
+
+/// A union of searches over a table, one per disjunct of an OR term in the WHERE clause.
+///
+/// Each branch is a [Search] on the rowid or on a secondary index. The rowids found by the branches
+/// are collected in a RowSet so that a row matching several disjuncts is only visited once.
+/// The OR term itself is kept as a filter on the visited rows.
+#[derive(Clone, Debug)]
+pub struct MultiIndexOr {
+    pub branches: Vec<Search>,
+}
+
+impl MultiIndexOr {
+    /// The distinct secondary indexes used by the branches, in order of first use.
+    pub fn indexes(&self) -> Vec<&Arc<Index>> {
+        let mut indexes: Vec<&Arc<Index>> = vec![];
+        for branch in self.branches.iter() {
+            if let Search::Seek {
+                index: Some(index), ..
+            } = branch
+            {
+                if !indexes.iter().any(|i| Arc::ptr_eq(i, index)) {
+                    indexes.push(index);
+                }
+            }
+        }
+        indexes
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Aggregate {
     pub func: AggFunc,
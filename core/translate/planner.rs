@@ -3,7 +3,7 @@ use std::cell::Cell;
 use super::{
     expr::walk_expr,
     plan::{
-        Aggregate, ColumnUsedMask, Distinctness, EvalAt, IterationDirection, JoinInfo,
+        Aggregate, ColumnUsedMask, Distinctness, EvalAt, IndexHint, IterationDirection, JoinInfo,
         JoinOrderMember, JoinedTable, Operation, OuterQueryReference, Plan, QueryDestination,
         ResultSetColumn, TableReferences, WhereTerm,
     },
@@ -249,21 +249,21 @@ fn parse_from_clause_table<'a>(
     table_ref_counter: &mut TableRefIdCounter,
 ) -> Result<()> {
     match table {
-        ast::SelectTable::Table(qualified_name, maybe_alias, _) => {
+        ast::SelectTable::Table(qualified_name, maybe_alias, indexed) => {
             let normalized_qualified_name = normalize_ident(qualified_name.name.0.as_str());
             // Check if the FROM clause table is referring to a CTE in the current scope.
-            if let Some(cte_idx) = ctes
-                .iter()
-                .position(|cte| cte.identifier == normalized_qualified_name)
-            {
+            if let Some(cte_idx) = ctes.iter().position(|cte| {
+                qualified_name.db_name.is_none() && cte.identifier == normalized_qualified_name
+            }) {
                 // TODO: what if the CTE is referenced multiple times?
-                let cte_table = ctes.remove(cte_idx);
+                let mut cte_table = ctes.remove(cte_idx);
+                cte_table.index_hint = resolve_index_hint(schema, &cte_table.table, indexed)?;
                 table_references.add_joined_table(cte_table);
                 return Ok(());
             };
 
             // Check if our top level schema has this table.
-            if let Some(table) = schema.get_table(&normalized_qualified_name) {
+            if let Some(table) = schema.get_qualified_table(&qualified_name)? {
                 let alias = maybe_alias
                     .map(|a| match a {
                         ast::As::As(id) => id,
@@ -279,6 +279,7 @@ fn parse_from_clause_table<'a>(
                         "Table type not supported".to_string(),
                     ));
                 };
+                let index_hint = resolve_index_hint(schema, &tbl_ref, indexed)?;
                 table_references.add_joined_table(JoinedTable {
                     op: Operation::Scan {
                         iter_dir: IterationDirection::Forwards,
@@ -289,6 +290,7 @@ fn parse_from_clause_table<'a>(
                     internal_id: table_ref_counter.next(),
                     join_info: None,
                     col_used_mask: ColumnUsedMask::new(),
+                    index_hint,
                 });
                 return Ok(());
             };
@@ -314,6 +316,7 @@ fn parse_from_clause_table<'a>(
                         internal_id: table_ref_counter.next(),
                         join_info: None,
                         col_used_mask: ColumnUsedMask::new(),
+                        index_hint: resolve_index_hint(schema, &outer_ref.table, indexed)?,
                     });
                     return Ok(());
                 }
@@ -372,6 +375,7 @@ fn parse_from_clause_table<'a>(
                 identifier: alias,
                 internal_id: table_ref_counter.next(),
                 col_used_mask: ColumnUsedMask::new(),
+                index_hint: None,
             });
 
             Ok(())
@@ -380,6 +384,28 @@ fn parse_from_clause_table<'a>(
     }
 }
 
+/// Resolves the INDEXED BY or NOT INDEXED clause given for a table reference.
+pub fn resolve_index_hint(
+    schema: &Schema,
+    table: &Table,
+    indexed: Option<ast::Indexed>,
+) -> Result<Option<IndexHint>> {
+    match indexed {
+        None => Ok(None),
+        Some(ast::Indexed::NotIndexed) => Ok(Some(IndexHint::NotIndexed)),
+        Some(ast::Indexed::IndexedBy(name)) => {
+            let index_name = normalize_ident(name.0.as_str());
+            let indexes = table
+                .btree()
+                .map_or(&[] as &[_], |table| schema.get_table_indices(&table));
+            match indexes.iter().find(|index| index.name == index_name) {
+                Some(index) => Ok(Some(IndexHint::IndexedBy(index.clone()))),
+                None => crate::bail_parse_error!("no such index: {}", index_name),
+            }
+        }
+    }
+}
+
 pub fn parse_from<'a>(
     schema: &Schema,
     mut from: Option<FromClause>,
@@ -5,8 +5,10 @@ use limbo_sqlite3_parser::ast::PragmaName;
 use limbo_sqlite3_parser::ast::{self, Expr};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::fast_lock::SpinLock;
+use crate::memory::MemoryTracker;
 use crate::schema::Schema;
 use crate::storage::pager::AutoVacuumMode;
 use crate::storage::sqlite3_ondisk::{DatabaseHeader, MIN_PAGE_CACHE_SIZE};
@@ -14,10 +16,11 @@ use crate::storage::wal::CheckpointMode;
 use crate::util::{normalize_ident, parse_signed_number};
 use crate::vdbe::builder::{ProgramBuilder, ProgramBuilderOpts, QueryMode};
 use crate::vdbe::insn::{Cookie, Insn};
-use crate::{bail_parse_error, LimboError, Pager, Value};
+use crate::{bail_parse_error, LimboError, Pager, TempStore, Value};
 use std::str::FromStr;
 use strum::IntoEnumIterator;
 
+use super::expr::sanitize_string;
 use super::integrity_check::translate_integrity_check;
 
 fn list_pragmas(program: &mut ProgramBuilder) {
@@ -71,6 +74,96 @@ pub fn translate_pragma(
             )?;
         }
         Some(ast::PragmaBody::Equals(value) | ast::PragmaBody::Call(value)) => match pragma {
+            // The busy timeout is a setting of the connection, taking a write transaction to
+            // change it would fail while another connection holds the write lock.
+            PragmaName::BusyTimeout => {
+                update_pragma(
+                    pragma,
+                    schema,
+                    value,
+                    database_header.clone(),
+                    pager,
+                    connection,
+                    &mut program,
+                )?;
+            }
+            // The memory mapping belongs to the database file, not to a transaction.
+            PragmaName::MmapSize => {
+                update_pragma(
+                    pragma,
+                    schema,
+                    value,
+                    database_header.clone(),
+                    pager,
+                    connection,
+                    &mut program,
+                )?;
+            }
+            // Memory limits apply to the process and the connection, not to the database.
+            PragmaName::HardHeapLimit | PragmaName::ShrinkMemory | PragmaName::SoftHeapLimit => {
+                update_pragma(
+                    pragma,
+                    schema,
+                    value,
+                    database_header.clone(),
+                    pager,
+                    connection,
+                    &mut program,
+                )?;
+            }
+            // The temp store is a setting of the connection, and cannot change within a
+            // transaction anyway.
+            PragmaName::TempStore => {
+                update_pragma(
+                    pragma,
+                    schema,
+                    value,
+                    database_header.clone(),
+                    pager,
+                    connection,
+                    &mut program,
+                )?;
+            }
+            // A checkpoint takes the locks it needs itself: a transaction of the connection
+            // would keep the WAL from being restarted.
+            PragmaName::WalCheckpoint => {
+                query_pragma(
+                    pragma,
+                    schema,
+                    Some(value),
+                    database_header.clone(),
+                    pager,
+                    connection,
+                    &mut program,
+                )?;
+                program.epilogue(super::emitter::TransactionMode::None);
+                return Ok(program);
+            }
+            // LIKE is a function of the connection, and has nothing to do with the database.
+            // Like in SQLite, it is replaced when the pragma is compiled.
+            PragmaName::CaseSensitiveLike => {
+                update_pragma(
+                    pragma,
+                    schema,
+                    value,
+                    database_header.clone(),
+                    pager,
+                    connection,
+                    &mut program,
+                )?;
+            }
+            // The key is needed to read the database, so it is set before the transaction.
+            PragmaName::Key | PragmaName::Rekey => {
+                update_pragma(
+                    pragma,
+                    schema,
+                    value,
+                    database_header.clone(),
+                    pager,
+                    connection,
+                    &mut program,
+                )?;
+            }
             PragmaName::TableInfo => {
                 query_pragma(
                     pragma,
@@ -114,6 +207,24 @@ fn update_pragma(
     program: &mut ProgramBuilder,
 ) -> crate::Result<()> {
     match pragma {
+        PragmaName::BusyTimeout => {
+            let timeout = match parse_signed_number(&value)? {
+                Value::Integer(ms) => ms,
+                Value::Float(ms) => ms as i64,
+                _ => bail_parse_error!("Invalid value for busy_timeout pragma"),
+            };
+            connection.busy_timeout(Duration::from_millis(timeout.max(0) as u64));
+            query_pragma(
+                PragmaName::BusyTimeout,
+                schema,
+                None,
+                header,
+                pager,
+                connection,
+                program,
+            )?;
+            Ok(())
+        }
         PragmaName::CacheSize => {
             let cache_size = match parse_signed_number(&value)? {
                 Value::Integer(size) => size,
@@ -123,6 +234,87 @@ fn update_pragma(
             update_cache_size(cache_size, header, pager, connection)?;
             Ok(())
         }
+        PragmaName::HardHeapLimit => {
+            // As in SQLite, the hard heap limit can only be lowered.
+            if let Value::Integer(limit) = parse_signed_number(&value)? {
+                let memory = MemoryTracker::global();
+                let current = memory.hard_limit();
+                if limit > 0 && (current == 0 || (limit as usize) < current) {
+                    memory.set_hard_limit(limit as usize);
+                }
+            }
+            query_pragma(
+                PragmaName::HardHeapLimit,
+                schema,
+                None,
+                header,
+                pager,
+                connection,
+                program,
+            )?;
+            Ok(())
+        }
+        PragmaName::SoftHeapLimit => {
+            if let Value::Integer(limit) = parse_signed_number(&value)? {
+                if limit >= 0 {
+                    MemoryTracker::global().set_soft_limit(limit as usize);
+                }
+            }
+            query_pragma(
+                PragmaName::SoftHeapLimit,
+                schema,
+                None,
+                header,
+                pager,
+                connection,
+                program,
+            )?;
+            Ok(())
+        }
+        PragmaName::ShrinkMemory => {
+            program.emit_insn(Insn::ShrinkMemory);
+            Ok(())
+        }
+        PragmaName::CaseSensitiveLike => {
+            connection.set_case_sensitive_like(parse_pragma_bool(&value)?);
+            Ok(())
+        }
+        PragmaName::TempStore => {
+            let temp_store = match &value {
+                Expr::Literal(ast::Literal::String(name)) => {
+                    TempStore::from_name(&sanitize_string(name))
+                }
+                Expr::Id(ast::Id(name))
+                | Expr::Name(ast::Name(name))
+                | Expr::Literal(ast::Literal::Keyword(name)) => {
+                    TempStore::from_name(name.trim_matches('"'))
+                }
+                _ => match parse_signed_number(&value)? {
+                    Value::Integer(value) => TempStore::from_i64(value),
+                    _ => None,
+                },
+            };
+            // Like in SQLite, unknown values select the default.
+            connection.set_temp_store(temp_store.unwrap_or_default())?;
+            Ok(())
+        }
+        PragmaName::MmapSize => {
+            if let Value::Integer(size) = parse_signed_number(&value)? {
+                if size >= 0 {
+                    pager.set_mmap_size(size as usize)?;
+                }
+            }
+            query_pragma(
+                PragmaName::MmapSize,
+                schema,
+                None,
+                header,
+                pager,
+                connection,
+                program,
+            )?;
+            Ok(())
+        }
         PragmaName::JournalMode => {
             query_pragma(
                 PragmaName::JournalMode,
@@ -135,6 +327,16 @@ fn update_pragma(
             )?;
             Ok(())
         }
+        PragmaName::Key => {
+            connection.apply_encryption_key(&encryption_key(value)?)?;
+            // The schema could not be read without the key.
+            program.emit_insn(Insn::ParseSchema {
+                db: 0,
+                where_clause: None,
+            });
+            Ok(())
+        }
+        PragmaName::Rekey => connection.rekey(&encryption_key(value)?),
         PragmaName::LegacyFileFormat => Ok(()),
         PragmaName::WalCheckpoint => {
             query_pragma(
@@ -177,8 +379,20 @@ fn update_pragma(
             Ok(())
         }
         PragmaName::SchemaVersion => {
-            // TODO: Implement updating schema_version
-            todo!("updating schema_version not yet implemented")
+            let data = parse_signed_number(&value)?;
+            let version_value = match data {
+                Value::Integer(i) => i as i32,
+                Value::Float(f) => f as i32,
+                _ => unreachable!(),
+            };
+
+            program.emit_insn(Insn::SetCookie {
+                db: 0,
+                cookie: Cookie::SchemaVersion,
+                value: version_value,
+                p5: 1,
+            });
+            Ok(())
         }
         PragmaName::TableInfo => {
             // because we need control over the write parameter for the transaction,
@@ -249,6 +463,32 @@ fn update_pragma(
     }
 }
 
+/// Reads a boolean pragma value the way SQLite does: `ON`, `TRUE`, `YES` or a non-zero number
+/// are true, `OFF`, `FALSE`, `NO` or zero are false.
+fn parse_pragma_bool(value: &Expr) -> crate::Result<bool> {
+    let name = match value {
+        Expr::Literal(ast::Literal::String(name)) => sanitize_string(name),
+        Expr::Id(ast::Id(name))
+        | Expr::Name(ast::Name(name))
+        | Expr::Literal(ast::Literal::Keyword(name)) => name.trim_matches('"').to_string(),
+        _ => {
+            return match parse_signed_number(value)? {
+                Value::Integer(value) => Ok(value != 0),
+                Value::Float(value) => Ok(value != 0.0),
+                _ => bail_parse_error!("Invalid boolean pragma value"),
+            }
+        }
+    };
+    match name.to_ascii_lowercase().as_str() {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => match name.parse::<i64>() {
+            Ok(value) => Ok(value != 0),
+            Err(_) => bail_parse_error!("Invalid boolean pragma value"),
+        },
+    }
+}
+
 fn query_pragma(
     pragma: PragmaName,
     schema: &Schema,
@@ -260,6 +500,11 @@ fn query_pragma(
 ) -> crate::Result<()> {
     let register = program.alloc_register();
     match pragma {
+        PragmaName::BusyTimeout => {
+            program.emit_int(connection.get_busy_timeout().as_millis() as i64, register);
+            program.emit_result_row(register, 1);
+            program.add_pragma_result_column("timeout".into());
+        }
         PragmaName::CacheSize => {
             program.emit_int(connection.get_cache_size() as i64, register);
             program.emit_result_row(register, 1);
@@ -270,7 +515,33 @@ fn query_pragma(
             program.emit_result_row(register, 1);
             program.add_pragma_result_column(pragma.to_string());
         }
-        PragmaName::LegacyFileFormat => {}
+        PragmaName::HardHeapLimit => {
+            program.emit_int(MemoryTracker::global().hard_limit() as i64, register);
+            program.emit_result_row(register, 1);
+            program.add_pragma_result_column(pragma.to_string());
+        }
+        PragmaName::SoftHeapLimit => {
+            program.emit_int(MemoryTracker::global().soft_limit() as i64, register);
+            program.emit_result_row(register, 1);
+            program.add_pragma_result_column(pragma.to_string());
+        }
+        PragmaName::ShrinkMemory => {
+            program.emit_insn(Insn::ShrinkMemory);
+        }
+        PragmaName::MmapSize => {
+            program.emit_int(pager.mmap_size() as i64, register);
+            program.emit_result_row(register, 1);
+            program.add_pragma_result_column(pragma.to_string());
+        }
+        PragmaName::TempStore => {
+            program.emit_int(connection.temp_store().as_i64(), register);
+            program.emit_result_row(register, 1);
+            program.add_pragma_result_column(pragma.to_string());
+        }
+        PragmaName::Key
+        | PragmaName::Rekey
+        | PragmaName::LegacyFileFormat
+        | PragmaName::CaseSensitiveLike => {}
         PragmaName::WalCheckpoint => {
             // Checkpoint uses 3 registers: P1, P2, P3. Ref Insn::Checkpoint for more info.
             // Allocate two more here as one was allocated at the top.
@@ -284,12 +555,6 @@ fn query_pragma(
                 _ => CheckpointMode::Passive,
             };
 
-            if !matches!(mode, CheckpointMode::Passive) {
-                return Err(LimboError::ParseError(
-                    "only Passive mode supported".to_string(),
-                ));
-            }
-
             program.alloc_registers(2);
             program.emit_insn(Insn::Checkpoint {
                 database: 0,
@@ -411,6 +676,18 @@ fn update_auto_vacuum_mode(
     Ok(())
 }
 
+/// Returns the key of `PRAGMA key` and `PRAGMA rekey`, keeping a raw key in its `x'...'` form.
+fn encryption_key(value: ast::Expr) -> crate::Result<String> {
+    match value {
+        Expr::Literal(ast::Literal::String(key)) => Ok(sanitize_string(&key)),
+        Expr::Literal(ast::Literal::Blob(key)) => Ok(format!("x'{}'", key)),
+        Expr::Id(ast::Id(key)) | Expr::Name(ast::Name(key)) => {
+            Ok(key.trim_matches('"').to_string())
+        }
+        _ => bail_parse_error!("Invalid value for encryption key"),
+    }
+}
+
 fn update_cache_size(
     value: i64,
     header: Arc<SpinLock<DatabaseHeader>>,
@@ -436,6 +713,12 @@ fn update_cache_size(
     pager
         .change_page_cache_size(cache_size)
         .expect("couldn't update page cache size");
+    // a negative cache size is a limit on the memory used by the cache
+    let max_bytes =
+        (cache_size_unformatted < 0).then(|| cache_size_unformatted.unsigned_abs() as usize * 1024);
+    pager
+        .set_page_cache_max_bytes(max_bytes)
+        .expect("couldn't update page cache memory limit");
 
     Ok(())
 }
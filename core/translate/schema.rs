@@ -9,13 +9,15 @@ use crate::schema::Column;
 use crate::schema::Schema;
 use crate::schema::Table;
 use crate::schema::Type;
+use crate::schema::{create_table, sqlite_schema_table};
+use crate::schema::{MAIN_DB, TEMP_DB};
 use crate::storage::pager::CreateBTreeFlags;
 use crate::translate::ProgramBuilder;
 use crate::translate::ProgramBuilderOpts;
 use crate::translate::QueryMode;
 use crate::util::PRIMARY_KEY_AUTOMATIC_INDEX_NAME_PREFIX;
 use crate::vdbe::builder::CursorType;
-use crate::vdbe::insn::{CmpInsFlags, InsertFlags, Insn};
+use crate::vdbe::insn::{CmpInsFlags, Cookie, InsertFlags, Insn};
 use crate::LimboError;
 use crate::SymbolTable;
 use crate::{bail_parse_error, Result};
@@ -32,9 +34,16 @@ pub fn translate_create_table(
     schema: &Schema,
     mut program: ProgramBuilder,
 ) -> Result<ProgramBuilder> {
-    if temporary {
-        bail_parse_error!("TEMPORARY table not supported yet");
-    }
+    let db = if temporary
+        || tbl_name
+            .db_name
+            .as_ref()
+            .is_some_and(|db_name| db_name.0.eq_ignore_ascii_case("temp"))
+    {
+        TEMP_DB
+    } else {
+        MAIN_DB
+    };
     let opts = ProgramBuilderOpts {
         query_mode,
         num_cursors: 1,
@@ -42,7 +51,8 @@ pub fn translate_create_table(
         approx_num_labels: 1,
     };
     program.extend(&opts);
-    if schema.get_table(tbl_name.name.0.as_str()).is_some() {
+    // A TEMP table may hide a main table of the same name and the other way around.
+    if schema.get_table_in(db, tbl_name.name.0.as_str()).is_some() {
         if if_not_exists {
             program.epilogue(crate::translate::emitter::TransactionMode::Write);
 
@@ -51,6 +61,19 @@ pub fn translate_create_table(
         bail_parse_error!("Table {} already exists", tbl_name);
     }
 
+    let has_autoincrement = match &body {
+        ast::CreateTableBody::ColumnsAndConstraints { .. } => {
+            create_table(tbl_name.clone(), body.clone(), 0)?.has_autoincrement
+        }
+        ast::CreateTableBody::AsSelect(_) => false,
+    };
+    if has_autoincrement && db == TEMP_DB {
+        bail_parse_error!("AUTOINCREMENT is not supported for TEMP tables yet");
+    }
+    // Like in SQLite, sqlite_sequence is created along with the first AUTOINCREMENT table.
+    let create_sequence_table =
+        has_autoincrement && schema.get_btree_table(SQLITE_SEQUENCE_TABLEID).is_none();
+
     let sql = create_table_body_to_str(&tbl_name, &body);
 
     let parse_schema_label = program.allocate_label();
@@ -62,7 +85,7 @@ pub fn translate_create_table(
     // Create the table B-tree
     let table_root_reg = program.alloc_register();
     program.emit_insn(Insn::CreateBtree {
-        db: 0,
+        db,
         root: table_root_reg,
         flags: CreateBTreeFlags::new_table(),
     });
@@ -98,19 +121,32 @@ pub fn translate_create_table(
         }
         for index_reg in index_regs.clone() {
             program.emit_insn(Insn::CreateBtree {
-                db: 0,
+                db,
                 root: index_reg,
                 flags: CreateBTreeFlags::new_index(),
             });
         }
     }
 
-    let table = schema.get_btree_table(SQLITE_TABLEID).unwrap();
+    let sequence_root_reg = if create_sequence_table {
+        let sequence_root_reg = program.alloc_register();
+        program.emit_insn(Insn::CreateBtree {
+            db,
+            root: sequence_root_reg,
+            flags: CreateBTreeFlags::new_table(),
+        });
+        Some(sequence_root_reg)
+    } else {
+        None
+    };
+
+    let table = schema_table(schema, db);
     let sqlite_schema_cursor_id = program.alloc_cursor_id(CursorType::BTreeTable(table.clone()));
     program.emit_insn(Insn::OpenWrite {
         cursor_id: sqlite_schema_cursor_id,
         root_page: 1usize.into(),
         name: tbl_name.name.0.clone(),
+        db,
     });
 
     // Add the table entry to sqlite_schema
@@ -145,13 +181,35 @@ pub fn translate_create_table(
         }
     }
 
+    if let Some(sequence_root_reg) = sequence_root_reg {
+        emit_schema_entry(
+            &mut program,
+            sqlite_schema_cursor_id,
+            SchemaEntryType::Table,
+            SQLITE_SEQUENCE_TABLEID,
+            SQLITE_SEQUENCE_TABLEID,
+            sequence_root_reg,
+            Some(format!(
+                "CREATE TABLE {}(name,seq)",
+                SQLITE_SEQUENCE_TABLEID
+            )),
+        );
+    }
+
     program.resolve_label(parse_schema_label, program.offset());
-    // TODO: SetCookie
-    //
+    emit_schema_cookie_change(&mut program, schema, db);
+
     // TODO: remove format, it sucks for performance but is convenient
-    let parse_schema_where_clause = format!("tbl_name = '{}' AND type != 'trigger'", tbl_name);
+    let parse_schema_where_clause = if create_sequence_table {
+        format!(
+            "tbl_name IN ('{}', '{}') AND type != 'trigger'",
+            tbl_name.name.0, SQLITE_SEQUENCE_TABLEID
+        )
+    } else {
+        format!("tbl_name = '{}' AND type != 'trigger'", tbl_name.name.0)
+    };
     program.emit_insn(Insn::ParseSchema {
-        db: sqlite_schema_cursor_id,
+        db,
         where_clause: Some(parse_schema_where_clause),
     });
 
@@ -176,6 +234,103 @@ impl SchemaEntryType {
     }
 }
 pub const SQLITE_TABLEID: &str = "sqlite_schema";
+pub const SQLITE_TEMP_TABLEID: &str = "sqlite_temp_schema";
+pub const SQLITE_SEQUENCE_TABLEID: &str = "sqlite_sequence";
+
+/// Returns the schema table of database `db`. The temp one is not in the schema until the
+/// connection opened its temp database.
+pub fn schema_table(schema: &Schema, db: usize) -> Rc<BTreeTable> {
+    if db != TEMP_DB {
+        return schema.get_btree_table(SQLITE_TABLEID).unwrap();
+    }
+    schema
+        .get_btree_table(SQLITE_TEMP_TABLEID)
+        .unwrap_or_else(|| {
+            Rc::new(BTreeTable {
+                name: SQLITE_TEMP_TABLEID.to_string(),
+                is_temp: true,
+                ..sqlite_schema_table()
+            })
+        })
+}
+
+/// Bumps the schema cookie, which tells other connections and their statement caches that
+/// the schema changed. The temp database has no other connections and the statement cache is
+/// cleared whenever the temp schema changes, so its cookie is left alone.
+pub fn emit_schema_cookie_change(program: &mut ProgramBuilder, schema: &Schema, db: usize) {
+    if db == TEMP_DB {
+        return;
+    }
+    program.emit_insn(Insn::SetCookie {
+        db: 0,
+        cookie: Cookie::SchemaVersion,
+        value: schema.schema_version.wrapping_add(1) as i32,
+        p5: 0,
+    });
+}
+
+/// Deletes the `sqlite_sequence` row of the AUTOINCREMENT table `table_name`, or renames it
+/// to `new_name`.
+pub fn emit_sequence_row_update(
+    program: &mut ProgramBuilder,
+    schema: &Schema,
+    table_name: &str,
+    new_name: Option<&str>,
+) {
+    let Some(sequence_table) = schema.get_btree_table(SQLITE_SEQUENCE_TABLEID) else {
+        return;
+    };
+    let cursor_id = program.alloc_cursor_id(CursorType::BTreeTable(sequence_table.clone()));
+    program.emit_insn(Insn::OpenWrite {
+        cursor_id,
+        root_page: sequence_table.root_page.into(),
+        name: SQLITE_SEQUENCE_TABLEID.to_string(),
+        db: MAIN_DB,
+    });
+    let table_name_reg = program.emit_string8_new_reg(table_name.to_string());
+    let name_reg = program.alloc_register();
+    // The new row: name, seq
+    let row_start_reg = program.alloc_registers(2);
+    let record_reg = program.alloc_register();
+    program.cursor_loop(cursor_id, |program, rowid| {
+        let next_label = program.allocate_label();
+        program.emit_column(cursor_id, 0, name_reg);
+        program.emit_insn(Insn::Ne {
+            lhs: name_reg,
+            rhs: table_name_reg,
+            target_pc: next_label,
+            flags: CmpInsFlags::default(),
+            collation: program.curr_collation(),
+        });
+        match new_name {
+            Some(new_name) => {
+                program.emit_string8(new_name.to_string(), row_start_reg);
+                program.emit_column(cursor_id, 1, row_start_reg + 1);
+                program.emit_insn(Insn::MakeRecord {
+                    start_reg: row_start_reg,
+                    count: 2,
+                    dest_reg: record_reg,
+                    index_name: None,
+                });
+                program.emit_insn(Insn::Insert {
+                    cursor: cursor_id,
+                    key_reg: rowid,
+                    record_reg,
+                    flag: InsertFlags::new(),
+                    table_name: SQLITE_SEQUENCE_TABLEID.to_string(),
+                });
+            }
+            None => {
+                program.emit_insn(Insn::Delete {
+                    cursor_id,
+                    table_name: SQLITE_SEQUENCE_TABLEID.to_string(),
+                    is_part_of_update: false,
+                });
+            }
+        }
+        program.resolve_label(next_label, program.offset());
+    });
+}
 
 pub fn emit_schema_entry(
     program: &mut ProgramBuilder,
@@ -533,7 +688,14 @@ pub fn translate_create_virtual_table(
     if !vtab_module.module_kind.eq(&VTabKind::VirtualTable) {
         bail_parse_error!("module {} is not a virtual table", module_name_str);
     };
-    if schema.get_table(&table_name).is_some() {
+    if tbl_name
+        .db_name
+        .as_ref()
+        .is_some_and(|db_name| db_name.0.eq_ignore_ascii_case("temp"))
+    {
+        bail_parse_error!("TEMP virtual tables are not supported yet");
+    }
+    if schema.get_table_in(MAIN_DB, &table_name).is_some() {
         if *if_not_exists {
             program.epilogue(crate::translate::emitter::TransactionMode::Write);
             return Ok(program);
@@ -582,6 +744,7 @@ pub fn translate_create_virtual_table(
         cursor_id: sqlite_schema_cursor_id,
         root_page: 1usize.into(),
         name: table_name.clone(),
+        db: MAIN_DB,
     });
 
     let sql = create_vtable_body_to_str(&vtab, vtab_module.clone());
@@ -595,9 +758,10 @@ pub fn translate_create_virtual_table(
         Some(sql),
     );
 
+    emit_schema_cookie_change(&mut program, schema, MAIN_DB);
     let parse_schema_where_clause = format!("tbl_name = '{}' AND type != 'trigger'", table_name);
     program.emit_insn(Insn::ParseSchema {
-        db: sqlite_schema_cursor_id,
+        db: MAIN_DB,
         where_clause: Some(parse_schema_where_clause),
     });
 
@@ -628,7 +792,7 @@ pub fn translate_drop_table(
         approx_num_labels: 4,
     };
     program.extend(&opts);
-    let table = schema.get_table(tbl_name.name.0.as_str());
+    let table = schema.get_qualified_table(&tbl_name)?;
     if table.is_none() {
         if if_exists {
             program.epilogue(crate::translate::emitter::TransactionMode::Write);
@@ -639,6 +803,7 @@ pub fn translate_drop_table(
     }
 
     let table = table.unwrap(); // safe since we just checked for None
+    let db = table.db();
 
     let null_reg = program.alloc_register(); //  r1
     program.emit_null(null_reg, None);
@@ -649,7 +814,7 @@ pub fn translate_drop_table(
     program.mark_last_insn_constant();
     let row_id_reg = program.alloc_register(); //  r5
 
-    let schema_table = schema.get_btree_table(SQLITE_TABLEID).unwrap();
+    let schema_table = schema_table(schema, db);
     let sqlite_schema_cursor_id_0 = program.alloc_cursor_id(
         //  cursor 0
         CursorType::BTreeTable(schema_table.clone()),
@@ -658,6 +823,7 @@ pub fn translate_drop_table(
         cursor_id: sqlite_schema_cursor_id_0,
         root_page: 1usize.into(),
         name: SQLITE_TABLEID.to_string(),
+        db,
     });
 
     //  1. Remove all entries from the schema table related to the table we are dropping, except for triggers
@@ -702,6 +868,8 @@ pub fn translate_drop_table(
     });
     program.emit_insn(Insn::Delete {
         cursor_id: sqlite_schema_cursor_id_0,
+        table_name: SQLITE_TABLEID.to_string(),
+        is_part_of_update: false,
     });
 
     program.resolve_label(next_label, program.offset());
@@ -712,13 +880,20 @@ pub fn translate_drop_table(
     program.preassign_label_to_next_insn(end_metadata_label);
     //  end of loop on schema table
 
+    //  Forget the largest rowid of an AUTOINCREMENT table
+    if table.btree().is_some_and(|table| table.has_autoincrement) {
+        emit_sequence_row_update(&mut program, schema, &tbl_name.name.0, None);
+    }
+
     //  2. Destroy the indices within a loop
-    let indices = schema.get_indices(&tbl_name.name.0);
+    let indices = table
+        .btree()
+        .map_or(&[] as &[_], |table| schema.get_table_indices(&table));
     for index in indices {
         program.emit_insn(Insn::Destroy {
             root: index.root_page,
             former_root_reg: 0, //  no autovacuum (https://www.sqlite.org/opcode.html#Destroy)
-            is_temp: 0,
+            is_temp: db,
         });
 
         //  3. TODO: Open an ephemeral table, and read over triggers from schema table into ephemeral table
@@ -734,7 +909,7 @@ pub fn translate_drop_table(
             program.emit_insn(Insn::Destroy {
                 root: table.root_page,
                 former_root_reg: table_name_and_root_page_register,
-                is_temp: 0,
+                is_temp: db,
             });
         }
         Table::Virtual(vtab) => {
@@ -785,6 +960,8 @@ pub fn translate_drop_table(
             }],
             is_strict: false,
             unique_sets: None,
+            is_temp: false,
+            has_autoincrement: false,
         });
         //  cursor id 2
         let ephemeral_cursor_id = program.alloc_cursor_id(CursorType::BTreeTable(simple_table_rc));
@@ -801,6 +978,7 @@ pub fn translate_drop_table(
         program.emit_insn(Insn::OpenRead {
             cursor_id: sqlite_schema_cursor_id_1,
             root_page: 1usize.into(),
+            db,
         });
 
         let schema_column_0_register = program.alloc_register();
@@ -840,7 +1018,7 @@ pub fn translate_drop_table(
             key_reg: schema_row_id_register,
             record_reg: schema_data_register,
             flag: InsertFlags::new(),
-            table_name: "scratch_table".to_string(),
+            table_name: "".to_string(),
         });
 
         program.resolve_label(next_label, program.offset());
@@ -858,6 +1036,7 @@ pub fn translate_drop_table(
             cursor_id: sqlite_schema_cursor_id_1,
             root_page: 1usize.into(),
             name: SQLITE_TABLEID.to_string(),
+            db,
         });
 
         //  Loop to copy over row id's from the ephemeral table and then re-insert into the schema table with the correct root page
@@ -900,6 +1079,8 @@ pub fn translate_drop_table(
         });
         program.emit_insn(Insn::Delete {
             cursor_id: sqlite_schema_cursor_id_1,
+            table_name: SQLITE_TABLEID.to_string(),
+            is_part_of_update: false,
         });
         program.emit_insn(Insn::Insert {
             cursor: sqlite_schema_cursor_id_1,
@@ -918,9 +1099,11 @@ pub fn translate_drop_table(
         //  End loop to copy over row id's from the ephemeral table and then re-insert into the schema table with the correct root page
     }
 
+    emit_schema_cookie_change(&mut program, schema, db);
+
     //  Drop the in-memory structures for the table
     program.emit_insn(Insn::DropTable {
-        db: 0,
+        db,
         _p2: 0,
         _p3: 0,
         table_name: tbl_name.name.0,
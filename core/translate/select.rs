@@ -40,7 +40,7 @@ pub fn translate_select(
         &mut program.table_reference_counter,
         query_destination,
     )?;
-    optimize_plan(&mut select_plan, schema)?;
+    optimize_plan(&mut select_plan, schema, syms.case_sensitive_like.get())?;
     let num_result_cols;
     let opts = match &select_plan {
         Plan::Select(select) => {
@@ -277,6 +277,7 @@ fn prepare_one_select_plan<'a>(
                 query_destination,
                 distinctness: Distinctness::from_ast(distinctness.as_ref()),
                 values: vec![],
+                min_max_optimized: false,
             };
 
             let mut aggregate_expressions = Vec::new();
@@ -604,6 +605,7 @@ fn prepare_one_select_plan<'a>(
                 query_destination,
                 distinctness: Distinctness::NonDistinct,
                 values,
+                min_max_optimized: false,
             };
 
             Ok(plan)
@@ -645,7 +647,11 @@ fn count_plan_required_cursors(plan: &SelectPlan) -> usize {
             Operation::Search(search) => match search {
                 Search::RowidEq { .. } => 1,
                 Search::Seek { index, .. } => 1 + index.is_some() as usize,
-            }
+            },
+            // The hash table, and the table cursor that builds it.
+            Operation::HashJoin(_) => 2,
+            // The table cursor, and one cursor per branch index.
+            Operation::MultiIndexOr(multi_index_or) => 1 + multi_index_or.indexes().len(),
         } + if let Table::FromClauseSubquery(from_clause_subquery) = &t.table {
             count_plan_required_cursors(&from_clause_subquery.plan)
         } else {
@@ -665,6 +671,8 @@ fn estimate_num_instructions(select: &SelectPlan) -> usize {
         .map(|t| match &t.op {
             Operation::Scan { .. } => 10,
             Operation::Search(_) => 15,
+            Operation::HashJoin(_) => 20,
+            Operation::MultiIndexOr(multi_index_or) => 10 + 15 * multi_index_or.branches.len(),
         } + if let Table::FromClauseSubquery(from_clause_subquery) = &t.table {
             10 + estimate_num_instructions(&from_clause_subquery.plan)
         } else {
@@ -694,6 +702,8 @@ fn estimate_num_labels(select: &SelectPlan) -> usize {
         .map(|t| match &t.op {
             Operation::Scan { .. } => 3,
             Operation::Search(_) => 3,
+            Operation::HashJoin(_) => 5,
+            Operation::MultiIndexOr(multi_index_or) => 3 + 3 * multi_index_or.branches.len(),
         } + if let Table::FromClauseSubquery(from_clause_subquery) = &t.table {
             3 + estimate_num_labels(&from_clause_subquery.plan)
         } else {
@@ -71,6 +71,8 @@ pub fn emit_subquery<'a>(
         label_main_loop_end: None,
         meta_group_by: None,
         meta_left_joins: (0..plan.joined_tables().len()).map(|_| None).collect(),
+        meta_multi_index_ors: (0..plan.joined_tables().len()).map(|_| None).collect(),
+        meta_skip_scans: (0..plan.joined_tables().len()).map(|_| None).collect(),
         meta_sort: None,
         reg_agg_start: None,
         reg_nonagg_emit_once_flag: None,
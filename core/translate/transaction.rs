@@ -23,8 +23,8 @@ pub fn translate_tx_begin(
             });
         }
         TransactionType::Immediate | TransactionType::Exclusive => {
-            program.emit_insn(Insn::Transaction { write: true });
-            // TODO: Emit transaction instruction on temporary tables when we support them.
+            // The temp database joins the transaction when a statement first uses it.
+            program.emit_insn(Insn::Transaction { db: 0, write: true });
             program.emit_insn(Insn::AutoCommit {
                 auto_commit: false,
                 rollback: false,
@@ -52,3 +52,21 @@ pub fn translate_tx_commit(
     program.epilogue(super::emitter::TransactionMode::None);
     Ok(program)
 }
+
+pub fn translate_tx_rollback(
+    _tx_name: Option<Name>,
+    mut program: ProgramBuilder,
+) -> Result<ProgramBuilder> {
+    program.extend(&ProgramBuilderOpts {
+        query_mode: QueryMode::Normal,
+        num_cursors: 0,
+        approx_num_insns: 0,
+        approx_num_labels: 0,
+    });
+    program.emit_insn(Insn::AutoCommit {
+        auto_commit: true,
+        rollback: true,
+    });
+    program.epilogue(super::emitter::TransactionMode::None);
+    Ok(program)
+}
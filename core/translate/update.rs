@@ -10,13 +10,15 @@ use crate::{
 use limbo_sqlite3_parser::ast::{self, Expr, ResultColumn, SortOrder, Update};
 
 use super::emitter::emit_program;
+use super::expr::{walk_expr, WalkControl};
+use super::index::{bind_partial_index_predicate, index_column_dependencies};
 use super::optimizer::optimize_plan;
 use super::plan::{
     ColumnUsedMask, IterationDirection, JoinedTable, Plan, ResultSetColumn, TableReferences,
     UpdatePlan,
 };
 use super::planner::bind_column_references;
-use super::planner::{parse_limit, parse_where};
+use super::planner::{parse_limit, parse_where, resolve_index_hint};
 /*
 * Update is simple. By default we scan the table, and for each row, we check the WHERE
 * clause. If it evaluates to true, we build the new record with the updated value and insert.
@@ -54,7 +56,7 @@ pub fn translate_update(
     mut program: ProgramBuilder,
 ) -> crate::Result<ProgramBuilder> {
     let mut plan = prepare_update_plan(schema, body, &mut program.table_reference_counter)?;
-    optimize_plan(&mut plan, schema)?;
+    optimize_plan(&mut plan, schema, syms.case_sensitive_like.get())?;
     // TODO: freestyling these numbers
     let opts = ProgramBuilderOpts {
         query_mode,
@@ -76,7 +78,7 @@ pub fn translate_update_with_after(
     after: impl FnOnce(&mut ProgramBuilder),
 ) -> crate::Result<ProgramBuilder> {
     let mut plan = prepare_update_plan(schema, body, &mut program.table_reference_counter)?;
-    optimize_plan(&mut plan, schema)?;
+    optimize_plan(&mut plan, schema, syms.case_sensitive_like.get())?;
     // TODO: freestyling these numbers
     let opts = ProgramBuilderOpts {
         query_mode,
@@ -111,7 +113,7 @@ pub fn prepare_update_plan(
             );
         }
     }
-    let table = match schema.get_table(table_name.0.as_str()) {
+    let table = match schema.get_qualified_table(&body.tbl_name)? {
         Some(table) => table,
         None => bail_parse_error!("Parse error: no such table: {}", table_name),
     };
@@ -127,6 +129,7 @@ pub fn prepare_update_plan(
             })
         })
         .unwrap_or(IterationDirection::Forwards);
+    let index_hint = resolve_index_hint(schema, &table, body.indexed.clone())?;
     let joined_tables = vec![JoinedTable {
         table: match table.as_ref() {
             Table::Virtual(vtab) => Table::Virtual(vtab.clone()),
@@ -141,6 +144,7 @@ pub fn prepare_update_plan(
         },
         join_info: None,
         col_used_mask: ColumnUsedMask::new(),
+        index_hint,
     }];
     let mut table_references = TableReferences::new(joined_tables, vec![]);
     let set_clauses = body
@@ -214,19 +218,52 @@ pub fn prepare_update_plan(
         .unwrap_or(Ok((None, None)))?;
 
     // Check what indexes will need to be updated by checking set_clauses and see
-    // if a column is contained in an index.
-    let indexes = schema.get_indices(&table_name.0);
-    let indexes_to_update = indexes
-        .iter()
-        .filter(|index| {
-            index.columns.iter().any(|index_column| {
-                set_clauses
-                    .iter()
-                    .any(|(set_index_column, _)| index_column.pos_in_table == *set_index_column)
-            })
-        })
-        .cloned()
-        .collect();
+    // if a column is contained in an index, in one of its expressions, or in the WHERE clause of a partial index.
+    let indexes = table
+        .btree()
+        .map_or(&[] as &[_], |btree| schema.get_table_indices(&btree));
+    let mut indexes_to_update = Vec::new();
+    for index in indexes {
+        let mut needs_update = false;
+        for index_column in index.columns.iter() {
+            let dependencies = match table.btree() {
+                Some(btree_table) => index_column_dependencies(&btree_table, index_column)?,
+                None => vec![index_column.pos_in_table],
+            };
+            needs_update |= set_clauses
+                .iter()
+                .any(|(set_index_column, _)| dependencies.contains(set_index_column));
+        }
+        if let (false, Some(where_clause), Some(btree_table)) =
+            (needs_update, &index.where_clause, table.btree())
+        {
+            let predicate = bind_partial_index_predicate(
+                where_clause,
+                &btree_table,
+                table_references.joined_tables()[0].internal_id,
+            )?;
+            walk_expr(
+                &predicate,
+                &mut |expr: &Expr| -> crate::Result<WalkControl> {
+                    match expr {
+                        Expr::Column { column, .. } => {
+                            needs_update |= set_clauses.iter().any(|(i, _)| i == column);
+                        }
+                        Expr::RowId { .. } => {
+                            needs_update |= set_clauses
+                                .iter()
+                                .any(|(i, _)| btree_table.columns[*i].is_rowid_alias);
+                        }
+                        _ => {}
+                    }
+                    Ok(WalkControl::Continue)
+                },
+            )?;
+        }
+        if needs_update {
+            indexes_to_update.push(index.clone());
+        }
+    }
 
     Ok(Plan::Update(UpdatePlan {
         table_references,
@@ -11,6 +11,7 @@ use crate::storage::btree::BTreeCursor;
 use crate::storage::sqlite3_ondisk::write_varint;
 use crate::translate::collate::CollationSeq;
 use crate::translate::plan::IterationDirection;
+use crate::vdbe::hash_table::HashTable;
 use crate::vdbe::sorter::Sorter;
 use crate::vdbe::Register;
 use crate::vtab::VirtualTableCursor;
@@ -1023,6 +1024,14 @@ impl Clone for ImmutableRecord {
                 RefValue::Null => RefValue::Null,
                 RefValue::Integer(i) => RefValue::Integer(*i),
                 RefValue::Float(f) => RefValue::Float(*f),
+                // Empty values may not point into the payload at all.
+                RefValue::Text(text_ref) if text_ref.value.len == 0 => RefValue::Text(TextRef {
+                    value: RawSlice::new(std::ptr::null(), 0),
+                    subtype: text_ref.subtype.clone(),
+                }),
+                RefValue::Blob(raw_slice) if raw_slice.len == 0 => {
+                    RefValue::Blob(RawSlice::new(std::ptr::null(), 0))
+                }
                 RefValue::Text(text_ref) => {
                     // let's update pointer
                     let ptr_start = self.payload.as_ptr() as usize;
@@ -1484,6 +1493,7 @@ pub enum Cursor {
     Pseudo(PseudoCursor),
     Sorter(Sorter),
     Virtual(VirtualTableCursor),
+    HashTable(HashTable),
 }
 
 impl Cursor {
@@ -1499,6 +1509,10 @@ impl Cursor {
         Self::Sorter(cursor)
     }
 
+    pub fn new_hash_table(cursor: HashTable) -> Self {
+        Self::HashTable(cursor)
+    }
+
     pub fn as_btree_mut(&mut self) -> &mut BTreeCursor {
         match self {
             Self::BTree(cursor) => cursor,
@@ -1526,6 +1540,13 @@ impl Cursor {
             _ => panic!("Cursor is not a virtual cursor"),
         }
     }
+
+    pub fn as_hash_table_mut(&mut self) -> &mut HashTable {
+        match self {
+            Self::HashTable(cursor) => cursor,
+            _ => panic!("Cursor is not a hash table cursor"),
+        }
+    }
 }
 
 #[derive(Debug)]
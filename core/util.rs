@@ -172,6 +172,40 @@ pub fn parse_schema_rows(
     Ok(())
 }
 
+/// Reads the rows of `SELECT tbl, idx, stat FROM sqlite_stat1` into the statistics of the
+/// indexes of `schema`.
+pub fn parse_stat1_rows(
+    rows: Option<Statement>,
+    schema: &mut Schema,
+    io: Arc<dyn IO>,
+) -> Result<()> {
+    let Some(mut rows) = rows else {
+        return Ok(());
+    };
+    loop {
+        match rows.step()? {
+            StepResult::Row => {
+                let row = rows.row().unwrap();
+                // The row of a table without indexes has a NULL idx.
+                let (Ok(table_name), Ok(index_name), Ok(stat)) =
+                    (row.get::<&str>(0), row.get::<&str>(1), row.get::<&str>(2))
+                else {
+                    continue;
+                };
+                // Keywords such as `unordered` may follow the numbers.
+                let stat = stat
+                    .split_ascii_whitespace()
+                    .map_while(|n| n.parse().ok())
+                    .collect();
+                schema.set_index_stat(table_name, index_name, stat);
+            }
+            StepResult::IO => io.run_once()?,
+            StepResult::Interrupt | StepResult::Done | StepResult::Busy => break,
+        }
+    }
+    Ok(())
+}
+
 fn cmp_numeric_strings(num_str: &str, other: &str) -> bool {
     match (num_str.parse::<f64>(), other.parse::<f64>()) {
         (Ok(num), Ok(other)) => num == other,
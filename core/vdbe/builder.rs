@@ -1,4 +1,4 @@
-use std::{cell::Cell, cmp::Ordering, rc::Rc, sync::Arc};
+use std::{cmp::Ordering, rc::Rc, sync::Arc};
 
 use limbo_sqlite3_parser::ast::{self, TableInternalId};
 use tracing::{instrument, Level};
@@ -7,7 +7,7 @@ use crate::{
     fast_lock::SpinLock,
     numeric::Numeric,
     parameters::Parameters,
-    schema::{BTreeTable, Index, PseudoTable, Table},
+    schema::{BTreeTable, Index, PseudoTable, Table, TEMP_DB},
     storage::sqlite3_ondisk::DatabaseHeader,
     translate::{
         collate::CollationSeq,
@@ -111,6 +111,9 @@ pub struct ProgramBuilder {
     nested_level: usize,
     init_label: BranchOffset,
     start_offset: BranchOffset,
+    /// Whether the program touches a b-tree of the temp database, so that it needs a
+    /// transaction on it too.
+    uses_temp_db: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -120,6 +123,8 @@ pub enum CursorType {
     Pseudo(Rc<PseudoTable>),
     Sorter,
     VirtualTable(Rc<VirtualTable>),
+    /// The hash table of a hash join, holding rows of the given table.
+    HashTable(Rc<BTreeTable>),
 }
 
 impl CursorType {
@@ -174,6 +179,7 @@ impl ProgramBuilder {
             // These labels will be filled when `prologue()` is called
             init_label: BranchOffset::Placeholder,
             start_offset: BranchOffset::Placeholder,
+            uses_temp_db: false,
         }
     }
 
@@ -296,6 +302,19 @@ impl ProgramBuilder {
     #[instrument(skip(self), level = Level::TRACE)]
     pub fn emit_insn(&mut self, insn: Insn) {
         let function = insn.to_function();
+        if matches!(
+            insn,
+            Insn::OpenRead { db: TEMP_DB, .. }
+                | Insn::OpenWrite { db: TEMP_DB, .. }
+                | Insn::CreateBtree { db: TEMP_DB, .. }
+                | Insn::Destroy {
+                    is_temp: TEMP_DB,
+                    ..
+                }
+                | Insn::Clear { db: TEMP_DB, .. }
+        ) {
+            self.uses_temp_db = true;
+        }
         // This seemingly empty trace here is needed so that a function span is emmited with it
         tracing::trace!("");
         self.insns.push((insn, function, self.insns.len()));
@@ -586,6 +605,15 @@ impl ProgramBuilder {
                 Insn::SorterSort { pc_if_empty, .. } => {
                     resolve(pc_if_empty, "SorterSort");
                 }
+                Insn::HashProbe { target_pc, .. } => {
+                    resolve(target_pc, "HashProbe");
+                }
+                Insn::HashNext { pc_if_next, .. } => {
+                    resolve(pc_if_next, "HashNext");
+                }
+                Insn::RowSetTest { target_pc, .. } => {
+                    resolve(target_pc, "RowSetTest");
+                }
                 Insn::NotNull {
                     reg: _reg,
                     target_pc,
@@ -750,10 +778,16 @@ impl ProgramBuilder {
             self.emit_halt();
             self.preassign_label_to_next_insn(self.init_label);
 
-            match txn_mode {
-                TransactionMode::Read => self.emit_insn(Insn::Transaction { write: false }),
-                TransactionMode::Write => self.emit_insn(Insn::Transaction { write: true }),
-                TransactionMode::None => {}
+            let write = match txn_mode {
+                TransactionMode::Read => Some(false),
+                TransactionMode::Write => Some(true),
+                TransactionMode::None => None,
+            };
+            if let Some(write) = write {
+                self.emit_insn(Insn::Transaction { db: 0, write });
+                if self.uses_temp_db {
+                    self.emit_insn(Insn::Transaction { db: TEMP_DB, write });
+                }
             }
 
             self.emit_constant_insns();
@@ -807,7 +841,9 @@ impl ProgramBuilder {
 
         let default = 'value: {
             let default = match cursor_type {
-                CursorType::BTreeTable(btree) => &btree.columns[column].default,
+                CursorType::BTreeTable(btree) | CursorType::HashTable(btree) => {
+                    &btree.columns[column].default
+                }
                 CursorType::BTreeIndex(index) => &index.columns[column].default,
                 _ => break 'value None,
             };
@@ -867,12 +903,12 @@ impl ProgramBuilder {
             cursor_ref: self.cursor_ref,
             database_header,
             comments: self.comments,
-            connection,
+            connection: Arc::downgrade(&connection),
             parameters: self.parameters,
-            n_change: Cell::new(0),
             change_cnt_on,
             result_columns: self.result_columns,
             table_references: self.table_references,
+            case_sensitive_like: connection.case_sensitive_like(),
         }
     }
 }
@@ -1,12 +1,13 @@
 #![allow(unused_variables)]
 use crate::function::AlterTableFunc;
 use crate::numeric::{NullableInteger, Numeric};
-use crate::schema::Schema;
+use crate::schema::{Index, Schema, MAIN_DB, TEMP_DB};
 use crate::storage::btree::{integrity_check, IntegrityCheckError, IntegrityCheckState};
 use crate::storage::database::FileMemoryStorage;
-use crate::storage::page_cache::DumbLruPageCache;
 use crate::storage::pager::CreateBTreeFlags;
+use crate::storage::s3fifo::S3FifoPageCache;
 use crate::storage::wal::DummyWAL;
+use crate::translate::alter::rename_column_references;
 use crate::translate::collate::CollationSeq;
 use crate::types::{ImmutableRecord, Text};
 use crate::util::normalize_ident;
@@ -56,6 +57,7 @@ use crate::{
 
 use super::{
     insn::{Cookie, RegisterOrLiteral},
+    rowset::RowSet,
     CommitState,
 };
 use fallible_iterator::FallibleIterator;
@@ -66,6 +68,7 @@ use parking_lot::RwLock;
 use rand::thread_rng;
 
 use super::{
+    hash_table::HashTable,
     likeop::{construct_like_escape_arg, exec_glob, exec_like_with_escape},
     sorter::Sorter,
 };
@@ -86,8 +89,8 @@ use crate::{
 use super::{get_new_rowid, make_record, Program, ProgramState, Register};
 use crate::vdbe::insn::InsertFlags;
 use crate::{
-    bail_constraint_error, must_be_btree_cursor, resolve_ext_path, MvStore, Pager, Result,
-    DATABASE_VERSION,
+    bail_constraint_error, must_be_btree_cursor, resolve_ext_path, Connection, MvStore, Pager,
+    Result, UpdateKind, DATABASE_VERSION,
 };
 
 macro_rules! return_if_io {
@@ -213,11 +216,16 @@ pub fn op_drop_index(
     pager: &Rc<Pager>,
     mv_store: Option<&Rc<MvStore>>,
 ) -> Result<InsnFunctionStepResult> {
-    let Insn::DropIndex { index, db: _ } = insn else {
+    let Insn::DropIndex { index, db } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    let mut schema = program.connection.schema.write();
-    schema.remove_index(&index);
+    let conn = program.connection();
+    if *db == TEMP_DB {
+        conn.temp_database()?.schema().write().remove_index(index);
+        conn.temp_schema_changed();
+    } else {
+        conn.schema.write().remove_index(index);
+    }
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
 }
@@ -304,13 +312,13 @@ pub fn op_checkpoint(
 ) -> Result<InsnFunctionStepResult> {
     let Insn::Checkpoint {
         database: _,
-        checkpoint_mode: _,
+        checkpoint_mode,
         dest,
     } = insn
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    let result = program.connection.checkpoint();
+    let result = program.connection().checkpoint_with_mode(*checkpoint_mode);
     match result {
         Ok(CheckpointResult {
             num_wal_frames: num_wal_pages,
@@ -365,7 +373,10 @@ pub fn op_null_row(
     let Insn::NullRow { cursor_id } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    {
+    if let (_, CursorType::HashTable(_)) = &program.cursor_ref[*cursor_id] {
+        let mut cursor = state.get_cursor(*cursor_id);
+        cursor.as_hash_table_mut().set_null_flag(true);
+    } else {
         let mut cursor = must_be_btree_cursor!(*cursor_id, program.cursor_ref, state, "NullRow");
         let cursor = cursor.as_btree_mut();
         cursor.set_null_flag(true);
@@ -864,6 +875,47 @@ pub fn op_if_not(
     Ok(InsnFunctionStepResult::Step)
 }
 
+/// Returns the pager of database `db` of the connection running `program`.
+fn database_pager(program: &Program, db: usize, pager: &Rc<Pager>) -> Result<Rc<Pager>> {
+    if db == TEMP_DB {
+        Ok(program.connection().temp_database()?.pager().clone())
+    } else {
+        Ok(pager.clone())
+    }
+}
+
+/// Returns the collations of the columns of `index`, which is in database `db`.
+fn index_collations(program: &Program, db: usize, index: &Index) -> Result<Vec<CollationSeq>> {
+    let conn = program.connection();
+    let temp;
+    let schema = if db == TEMP_DB {
+        temp = conn.temp_database()?;
+        temp.schema().try_read()
+    } else {
+        conn.schema.try_read()
+    }
+    .ok_or(LimboError::SchemaLocked)?;
+    let table = schema
+        .get_table(&index.table_name)
+        .and_then(|table| table.btree());
+    Ok(table.map_or(Vec::new(), |table| {
+        index
+            .columns
+            .iter()
+            .map(|c| match c.expr {
+                // A column on an expression has no table column to take the collation from.
+                Some(_) => c.collation.unwrap_or_default(),
+                None => table
+                    .columns
+                    .get(c.pos_in_table)
+                    .unwrap()
+                    .collation
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }))
+}
+
 pub fn op_open_read(
     program: &Program,
     state: &mut ProgramState,
@@ -874,10 +926,12 @@ pub fn op_open_read(
     let Insn::OpenRead {
         cursor_id,
         root_page,
+        db,
     } = insn
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
+    let pager = &database_pager(program, *db, pager)?;
     let (_, cursor_type) = program.cursor_ref.get(*cursor_id).unwrap();
     let mv_cursor = match state.mv_tx_id {
         Some(tx_id) => {
@@ -900,25 +954,7 @@ pub fn op_open_read(
                 .replace(Cursor::new_btree(cursor));
         }
         CursorType::BTreeIndex(index) => {
-            let conn = program.connection.clone();
-            let schema = conn.schema.try_read().ok_or(LimboError::SchemaLocked)?;
-            let table = schema
-                .get_table(&index.table_name)
-                .map_or(None, |table| table.btree());
-            let collations = table.map_or(Vec::new(), |table| {
-                index
-                    .columns
-                    .iter()
-                    .map(|c| {
-                        table
-                            .columns
-                            .get(c.pos_in_table)
-                            .unwrap()
-                            .collation
-                            .unwrap_or_default()
-                    })
-                    .collect()
-            });
+            let collations = index_collations(program, *db, index)?;
             let cursor = BTreeCursor::new_index(
                 mv_cursor,
                 pager.clone(),
@@ -940,6 +976,9 @@ pub fn op_open_read(
         CursorType::VirtualTable(_) => {
             panic!("OpenRead on virtual table cursor, use Insn:VOpen instead");
         }
+        CursorType::HashTable(_) => {
+            panic!("OpenRead on hash table cursor, use Insn::HashOpen instead");
+        }
     }
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
@@ -959,7 +998,7 @@ pub fn op_vopen(
     let CursorType::VirtualTable(virtual_table) = cursor_type else {
         panic!("VOpen on non-virtual table cursor");
     };
-    let cursor = virtual_table.open(program.connection.clone())?;
+    let cursor = virtual_table.open(program.connection())?;
     state
         .cursors
         .borrow_mut()
@@ -998,7 +1037,7 @@ pub fn op_vcreate(
     } else {
         vec![]
     };
-    let conn = program.connection.clone();
+    let conn = program.connection();
     let table =
         crate::VirtualTable::table(Some(&table_name), &module_name, args, &conn.syms.borrow())?;
     {
@@ -1119,7 +1158,7 @@ pub fn op_vupdate(
         Ok(Some(new_rowid)) => {
             if *conflict_action == 5 {
                 // ResolveType::Replace
-                program.connection.update_last_rowid(new_rowid);
+                program.connection().update_last_rowid(new_rowid);
             }
             state.pc += 1;
         }
@@ -1175,7 +1214,7 @@ pub fn op_vdestroy(
     let Insn::VDestroy { db, table_name } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    let conn = program.connection.clone();
+    let conn = program.connection();
     {
         let Some(vtab) = conn.syms.borrow_mut().vtabs.remove(table_name) else {
             return Err(crate::LimboError::InternalError(
@@ -1386,6 +1425,21 @@ pub fn op_column(
         CursorType::VirtualTable(_) => {
             panic!("Insn:Column on virtual table cursor, use Insn:VColumn instead");
         }
+        CursorType::HashTable(_) => {
+            let value = {
+                let mut cursor = state.get_cursor(*cursor_id);
+                let cursor = cursor.as_hash_table_mut();
+                match cursor.record() {
+                    Some(_) if cursor.get_null_flag() => Value::Null,
+                    Some(record) => match record.get_value_opt(*column) {
+                        Some(val) => val.to_owned(),
+                        None => default.clone().unwrap_or(Value::Null),
+                    },
+                    None => Value::Null,
+                }
+            };
+            state.registers[*dest] = Register::Value(value);
+        }
     }
 
     state.pc += 1;
@@ -1677,11 +1731,17 @@ pub fn op_transaction(
     pager: &Rc<Pager>,
     mv_store: Option<&Rc<MvStore>>,
 ) -> Result<InsnFunctionStepResult> {
-    let Insn::Transaction { write } = insn else {
+    let Insn::Transaction { db, write } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    let connection = program.connection.clone();
-    if *write && connection._db.open_flags.contains(OpenFlags::ReadOnly) {
+    let connection = program.connection();
+    if *db == TEMP_DB {
+        // The temp database is private to the connection, even a read-only one.
+        connection.temp_database()?.begin_tx(*write)?;
+        state.pc += 1;
+        return Ok(InsnFunctionStepResult::Step);
+    }
+    if *write && connection._db.is_read_only() {
         return Err(LimboError::ReadOnly);
     }
     if let Some(mv_store) = &mv_store {
@@ -1703,25 +1763,46 @@ pub fn op_transaction(
 
         if updated && matches!(current_state, TransactionState::None) {
             if let LimboResult::Busy = pager.begin_read_tx()? {
-                return Ok(InsnFunctionStepResult::Busy);
+                return Ok(invoke_busy_handler(&connection, state));
             }
         }
 
         if updated && matches!(new_transaction_state, TransactionState::Write) {
             if let LimboResult::Busy = pager.begin_write_tx()? {
-                pager.end_read_tx()?;
+                // The read lock of a transaction that already read is kept for the retry.
+                if matches!(current_state, TransactionState::None) {
+                    pager.end_read_tx()?;
+                }
                 tracing::trace!("begin_write_tx busy");
-                return Ok(InsnFunctionStepResult::Busy);
+                return Ok(invoke_busy_handler(&connection, state));
             }
         }
         if updated {
             connection.transaction_state.replace(new_transaction_state);
         }
+        state.busy_retries = 0;
     }
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
 }
 
+/// Asks the connection's busy handler what to do about a lock that is held by another
+/// connection. If the handler wants to retry, it waits out the handler's delay and the
+/// instruction yields with IO, so the lock is taken again on the next step; otherwise the
+/// statement returns busy.
+fn invoke_busy_handler(
+    connection: &Connection,
+    state: &mut ProgramState,
+) -> InsnFunctionStepResult {
+    if connection.wait_for_busy_lock(state.busy_retries) {
+        state.busy_retries += 1;
+        InsnFunctionStepResult::IO
+    } else {
+        state.busy_retries = 0;
+        InsnFunctionStepResult::Busy
+    }
+}
+
 pub fn op_auto_commit(
     program: &Program,
     state: &mut ProgramState,
@@ -1736,7 +1817,7 @@ pub fn op_auto_commit(
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    let conn = program.connection.clone();
+    let conn = program.connection();
     if state.commit_state == CommitState::Committing {
         return match program.commit_txn(pager.clone(), state, mv_store)? {
             super::StepResult::Done => Ok(InsnFunctionStepResult::Done),
@@ -1749,7 +1830,7 @@ pub fn op_auto_commit(
 
     if *auto_commit != conn.auto_commit.get() {
         if *rollback {
-            todo!("Rollback is not implemented");
+            conn.rollback()?;
         } else {
             conn.auto_commit.replace(*auto_commit);
         }
@@ -1989,7 +2070,10 @@ pub fn op_row_id(
     }
     let mut cursors = state.cursors.borrow_mut();
     if let Some(Cursor::BTree(btree_cursor)) = cursors.get_mut(*cursor_id).unwrap() {
-        if let Some(ref rowid) = return_if_io!(btree_cursor.rowid()) {
+        // A cursor set to a NULL row by NullRow may still be positioned on a row.
+        if btree_cursor.get_null_flag() {
+            state.registers[*dest] = Register::Value(Value::Null);
+        } else if let Some(ref rowid) = return_if_io!(btree_cursor.rowid()) {
             state.registers[*dest] = Register::Value(Value::Integer(*rowid as i64));
         } else {
             state.registers[*dest] = Register::Value(Value::Null);
@@ -2001,9 +2085,14 @@ pub fn op_row_id(
         } else {
             state.registers[*dest] = Register::Value(Value::Null);
         }
+    } else if let Some(Cursor::HashTable(hash_table)) = cursors.get_mut(*cursor_id).unwrap() {
+        state.registers[*dest] = Register::Value(match hash_table.rowid() {
+            Some(rowid) if !hash_table.get_null_flag() => Value::Integer(rowid),
+            _ => Value::Null,
+        });
     } else {
         return Err(LimboError::InternalError(
-            "RowId: cursor is not a table or virtual cursor".to_string(),
+            "RowId: cursor is not a table, virtual or hash table cursor".to_string(),
         ));
     }
     state.pc += 1;
@@ -2543,28 +2632,8 @@ pub fn op_agg_step(
             AggFunc::Count | AggFunc::Count0 => {
                 Register::Aggregate(AggContext::Count(Value::Integer(0)))
             }
-            AggFunc::Max => {
-                let col = state.registers[*col].get_owned_value();
-                match col {
-                    Value::Integer(_) => Register::Aggregate(AggContext::Max(None)),
-                    Value::Float(_) => Register::Aggregate(AggContext::Max(None)),
-                    Value::Text(_) => Register::Aggregate(AggContext::Max(None)),
-                    _ => {
-                        unreachable!();
-                    }
-                }
-            }
-            AggFunc::Min => {
-                let col = state.registers[*col].get_owned_value();
-                match col {
-                    Value::Integer(_) => Register::Aggregate(AggContext::Min(None)),
-                    Value::Float(_) => Register::Aggregate(AggContext::Min(None)),
-                    Value::Text(_) => Register::Aggregate(AggContext::Min(None)),
-                    _ => {
-                        unreachable!();
-                    }
-                }
-            }
+            AggFunc::Max => Register::Aggregate(AggContext::Max(None)),
+            AggFunc::Min => Register::Aggregate(AggContext::Min(None)),
             AggFunc::GroupConcat | AggFunc::StringAgg => {
                 Register::Aggregate(AggContext::GroupConcat(Value::build_text("")))
             }
@@ -2659,6 +2728,8 @@ pub fn op_agg_step(
             };
 
             match (acc.as_mut(), col.get_owned_value()) {
+                // NULLs are ignored by max()
+                (_, Value::Null) => {}
                 (None, value) => {
                     *acc = Some(value.clone());
                 }
@@ -2695,6 +2766,8 @@ pub fn op_agg_step(
             };
 
             match (acc.as_mut(), col.get_owned_value()) {
+                // NULLs are ignored by min()
+                (_, Value::Null) => {}
                 (None, value) => {
                     *acc.borrow_mut() = Some(value.clone());
                 }
@@ -2966,6 +3039,7 @@ pub fn op_sorter_open(
             .iter()
             .map(|collation| collation.unwrap_or_default())
             .collect(),
+        program.connection().memory.clone(),
     );
     let mut cursors = state.cursors.borrow_mut();
     cursors
@@ -3033,7 +3107,7 @@ pub fn op_sorter_insert(
             Register::Record(record) => record,
             _ => unreachable!("SorterInsert on non-record register"),
         };
-        cursor.insert(record);
+        cursor.insert(record)?;
     }
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
@@ -3099,6 +3173,204 @@ pub fn op_sorter_next(
     Ok(InsnFunctionStepResult::Step)
 }
 
+pub fn op_hash_open(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::HashOpen { cursor_id } = insn else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    // Like the sorter in SQLite, the hash table may use as much memory as the page cache.
+    let cache_size = program.connection().get_cache_size() as i64;
+    let memory_budget = if cache_size < 0 {
+        cache_size.unsigned_abs() as usize * 1024
+    } else {
+        let page_size = program.database_header.lock().get_page_size() as usize;
+        cache_size as usize * page_size
+    };
+    let cursor = HashTable::new(memory_budget, pager.io.clone());
+    let mut cursors = state.cursors.borrow_mut();
+    cursors
+        .get_mut(*cursor_id)
+        .unwrap()
+        .replace(Cursor::new_hash_table(cursor));
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
+pub fn op_hash_insert(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::HashInsert {
+        cursor_id,
+        key_start_reg,
+        num_keys,
+        record_reg,
+        rowid_reg,
+    } = insn
+    else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    {
+        let key = state.registers[*key_start_reg..*key_start_reg + *num_keys]
+            .iter()
+            .map(|reg| reg.get_owned_value().clone())
+            .collect::<Vec<_>>();
+        let Value::Integer(rowid) = state.registers[*rowid_reg].get_owned_value() else {
+            return Err(LimboError::InternalError(
+                "HashInsert: rowid is not an integer".to_string(),
+            ));
+        };
+        let rowid = *rowid;
+        let Register::Record(record) = &state.registers[*record_reg] else {
+            return Err(LimboError::InternalError(
+                "HashInsert on non-record register".to_string(),
+            ));
+        };
+        let record = record.clone();
+        let mut cursor = state.get_cursor(*cursor_id);
+        cursor.as_hash_table_mut().insert(&key, rowid, &record)?;
+    }
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
+pub fn op_hash_probe(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::HashProbe {
+        cursor_id,
+        key_start_reg,
+        num_keys,
+        target_pc,
+    } = insn
+    else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    assert!(target_pc.is_offset());
+    let found = {
+        let key = state.registers[*key_start_reg..*key_start_reg + *num_keys]
+            .iter()
+            .map(|reg| reg.get_owned_value().clone())
+            .collect::<Vec<_>>();
+        let mut cursor = state.get_cursor(*cursor_id);
+        cursor.as_hash_table_mut().probe(&key)?
+    };
+    if found {
+        state.pc += 1;
+    } else {
+        state.pc = target_pc.to_offset_int();
+    }
+    Ok(InsnFunctionStepResult::Step)
+}
+
+pub fn op_hash_next(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::HashNext {
+        cursor_id,
+        pc_if_next,
+    } = insn
+    else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    assert!(pc_if_next.is_offset());
+    let has_more = {
+        let mut cursor = state.get_cursor(*cursor_id);
+        cursor.as_hash_table_mut().next()
+    };
+    if has_more {
+        state.pc = pc_if_next.to_offset_int();
+    } else {
+        state.pc += 1;
+    }
+    Ok(InsnFunctionStepResult::Step)
+}
+
+pub fn op_rowset_add(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::RowSetAdd {
+        rowset_reg,
+        rowid_reg,
+    } = insn
+    else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    let Value::Integer(rowid) = *state.registers[*rowid_reg].get_owned_value() else {
+        return Err(LimboError::InternalError(
+            "RowSetAdd: rowid is not an integer".to_string(),
+        ));
+    };
+    if !matches!(state.registers[*rowset_reg], Register::RowSet(_)) {
+        state.registers[*rowset_reg] = Register::RowSet(RowSet::default());
+    }
+    let Register::RowSet(rowset) = &mut state.registers[*rowset_reg] else {
+        unreachable!();
+    };
+    rowset.insert(rowid);
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
+pub fn op_rowset_test(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::RowSetTest {
+        rowset_reg,
+        target_pc,
+        rowid_reg,
+        set_number,
+    } = insn
+    else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    assert!(target_pc.is_offset());
+    let Value::Integer(rowid) = *state.registers[*rowid_reg].get_owned_value() else {
+        return Err(LimboError::InternalError(
+            "RowSetTest: rowid is not an integer".to_string(),
+        ));
+    };
+    if !matches!(state.registers[*rowset_reg], Register::RowSet(_)) {
+        state.registers[*rowset_reg] = Register::RowSet(RowSet::default());
+    }
+    let Register::RowSet(rowset) = &mut state.registers[*rowset_reg] else {
+        unreachable!();
+    };
+    if rowset.contains(rowid) {
+        state.pc = target_pc.to_offset_int();
+        return Ok(InsnFunctionStepResult::Step);
+    }
+    if *set_number >= 0 {
+        rowset.insert(rowid);
+    }
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
 pub fn op_function(
     program: &Program,
     state: &mut ProgramState,
@@ -3407,7 +3679,7 @@ pub fn op_function(
                 state.registers[*dest] = Register::Value(result);
             }
             ScalarFunc::Changes => {
-                let res = &program.connection.last_change;
+                let res = &program.connection().last_change;
                 let changes = res.get();
                 state.registers[*dest] = Register::Value(Value::Integer(changes));
             }
@@ -3429,7 +3701,15 @@ pub fn op_function(
             ScalarFunc::Glob => {
                 let pattern = &state.registers[*start_reg];
                 let text = &state.registers[*start_reg + 1];
-                let result = match (pattern.get_owned_value(), text.get_owned_value()) {
+                let pattern = match pattern.get_owned_value() {
+                    Value::Text(_) => pattern.get_owned_value(),
+                    _ => &pattern.get_owned_value().exec_cast("TEXT"),
+                };
+                let text = match text.get_owned_value() {
+                    Value::Text(_) => text.get_owned_value(),
+                    _ => &text.get_owned_value().exec_cast("TEXT"),
+                };
+                let result = match (pattern, text) {
                     (Value::Text(pattern), Value::Text(text)) => {
                         let cache = if *constant_mask > 0 {
                             Some(&mut state.regex_cache.glob)
@@ -3438,8 +3718,9 @@ pub fn op_function(
                         };
                         Value::Integer(exec_glob(cache, pattern.as_str(), text.as_str()) as i64)
                     }
+                    (Value::Null, _) | (_, Value::Null) => Value::Null,
                     _ => {
-                        unreachable!("Like on non-text registers");
+                        unreachable!("Glob failed");
                     }
                 };
                 state.registers[*dest] = Register::Value(result);
@@ -3455,8 +3736,9 @@ pub fn op_function(
                 state.registers[*dest] = Register::Value(result);
             }
             ScalarFunc::LastInsertRowid => {
-                state.registers[*dest] =
-                    Register::Value(Value::Integer(program.connection.last_insert_rowid() as i64));
+                state.registers[*dest] = Register::Value(Value::Integer(
+                    program.connection().last_insert_rowid() as i64,
+                ));
             }
             ScalarFunc::Like => {
                 let pattern = &state.registers[*start_reg];
@@ -3484,6 +3766,7 @@ pub fn op_function(
                             pattern.as_str(),
                             match_expression.as_str(),
                             escape,
+                            program.case_sensitive_like,
                         ) as i64)
                     }
                     (Value::Text(pattern), Value::Text(match_expression)) => {
@@ -3496,6 +3779,7 @@ pub fn op_function(
                             cache,
                             pattern.as_str(),
                             match_expression.as_str(),
+                            program.case_sensitive_like,
                         ) as i64)
                     }
                     (Value::Null, _) | (_, Value::Null) => Value::Null,
@@ -3661,7 +3945,7 @@ pub fn op_function(
                 }
             }
             ScalarFunc::TotalChanges => {
-                let res = &program.connection.total_changes;
+                let res = &program.connection().total_changes;
                 let total_changes = res.get();
                 state.registers[*dest] = Register::Value(Value::Integer(total_changes));
             }
@@ -3722,7 +4006,7 @@ pub fn op_function(
             ScalarFunc::LoadExtension => {
                 let extension = &state.registers[*start_reg];
                 let ext = resolve_ext_path(&extension.get_owned_value().to_string())?;
-                program.connection.load_extension(ext)?;
+                program.connection().load_extension(ext)?;
             }
             ScalarFunc::StrfTime => {
                 let result = exec_strftime(&state.registers[*start_reg..*start_reg + arg_count]);
@@ -3732,7 +4016,7 @@ pub fn op_function(
                 let result = exec_printf(&state.registers[*start_reg..*start_reg + arg_count])?;
                 state.registers[*dest] = Register::Value(result);
             }
-            ScalarFunc::Likely => {
+            ScalarFunc::Likely | ScalarFunc::Unlikely => {
                 let value = &state.registers[*start_reg].borrow_mut();
                 let result = value.get_owned_value().exec_likely();
                 state.registers[*dest] = Register::Value(result);
@@ -4021,21 +4305,25 @@ pub fn op_function(
                                 idx_name,
                                 tbl_name,
                                 mut columns,
-                                where_clause,
+                                mut where_clause,
                             } => {
                                 if table != normalize_ident(&tbl_name.0) {
                                     break 'sql None;
                                 }
 
                                 for column in &mut columns {
-                                    match &mut column.expr {
-                                        ast::Expr::Id(ast::Id(id))
-                                            if normalize_ident(&id) == rename_from =>
-                                        {
-                                            *id = rename_to.clone();
-                                        }
-                                        _ => {}
-                                    }
+                                    rename_column_references(
+                                        &mut column.expr,
+                                        &rename_from,
+                                        &rename_to,
+                                    )?;
+                                }
+                                if let Some(where_clause) = &mut where_clause {
+                                    rename_column_references(
+                                        where_clause,
+                                        &rename_from,
+                                        &rename_to,
+                                    )?;
                                 }
 
                                 Some(
@@ -4228,11 +4516,13 @@ pub fn op_insert(
         key_reg,
         record_reg,
         flag,
-        table_name: _,
+        table_name,
     } = insn
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
+    let db = cursor_db(program, *cursor);
+    let mut inserted = false;
     {
         let mut cursor = state.get_cursor(*cursor);
         let cursor = cursor.as_btree_mut();
@@ -4248,20 +4538,43 @@ pub fn op_insert(
         // Only update last_insert_rowid for regular table inserts, not schema modifications
         if cursor.root_page() != 1 {
             if let Some(rowid) = return_if_io!(cursor.rowid()) {
-                program.connection.update_last_rowid(rowid);
+                let conn = program.connection();
+                conn.update_last_rowid(rowid);
 
                 // n_change is increased when Insn::Delete is executed, so we can skip for Insn::Insert
-                if !flag.has(InsertFlags::UPDATE) {
-                    let prev_changes = program.n_change.get();
-                    program.n_change.set(prev_changes + 1);
+                inserted = !flag.has(InsertFlags::UPDATE);
+
+                let kind = if flag.has(InsertFlags::UPDATE) {
+                    UpdateKind::Update
+                } else {
+                    UpdateKind::Insert
+                };
+                conn.hooks.borrow().row_changed(kind, db, table_name, rowid);
+
+                // Sessions only record the tables of the main database.
+                let sessions = conn.sessions.borrow();
+                if db == MAIN_DB && sessions.is_recording(table_name) {
+                    let values = record.get_values().iter().map(|v| v.to_owned()).collect();
+                    sessions.record_insert(&conn.schema.read(), table_name, rowid, values);
                 }
             }
         }
     }
+    if inserted {
+        state.n_change += 1;
+    }
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
 }
 
+/// Returns the database of the table a cursor was opened on, i.e. the `db` of its OpenWrite.
+fn cursor_db(program: &Program, cursor_id: usize) -> usize {
+    match &program.cursor_ref[cursor_id].1 {
+        CursorType::BTreeTable(table) => table.db(),
+        _ => MAIN_DB,
+    }
+}
+
 pub fn op_int_64(
     program: &Program,
     state: &mut ProgramState,
@@ -4290,16 +4603,54 @@ pub fn op_delete(
     pager: &Rc<Pager>,
     mv_store: Option<&Rc<MvStore>>,
 ) -> Result<InsnFunctionStepResult> {
-    let Insn::Delete { cursor_id } = insn else {
+    let Insn::Delete {
+        cursor_id,
+        table_name,
+        is_part_of_update,
+    } = insn
+    else {
         unreachable!("unexpected Insn {:?}", insn)
     };
+    let conn = program.connection();
+    // The row is gone once it is deleted, so remember what the update hook and the sessions
+    // need before the delete starts. The old row of an UPDATE is reported to the update hook
+    // by the insert that follows, not as a delete.
+    let db = cursor_db(program, *cursor_id);
+    let is_recorded = db == MAIN_DB && conn.sessions.borrow().is_recording(table_name);
+    let has_update_hook = conn.hooks.borrow().update.is_some() && !is_part_of_update;
+    if (is_recorded || has_update_hook) && state.op_delete_row.is_none() {
+        let row = {
+            let mut cursor = state.get_cursor(*cursor_id);
+            let cursor = cursor.as_btree_mut();
+            let rowid = return_if_io!(cursor.rowid());
+            let values = if is_recorded {
+                return_if_io!(cursor.record())
+                    .map(|record| record.get_values().iter().map(|v| v.to_owned()).collect())
+            } else {
+                None
+            };
+            rowid.map(|rowid| (rowid, values))
+        };
+        state.op_delete_row = row;
+    }
     {
         let mut cursor = state.get_cursor(*cursor_id);
         let cursor = cursor.as_btree_mut();
         return_if_io!(cursor.delete());
     }
-    let prev_changes = program.n_change.get();
-    program.n_change.set(prev_changes + 1);
+    if let Some((rowid, values)) = state.op_delete_row.take() {
+        if has_update_hook {
+            conn.hooks
+                .borrow()
+                .row_changed(UpdateKind::Delete, db, table_name, rowid);
+        }
+        if let Some(values) = values {
+            conn.sessions
+                .borrow()
+                .record_delete(&conn.schema.read(), table_name, rowid, values);
+        }
+    }
+    state.n_change += 1;
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
 }
@@ -4377,8 +4728,7 @@ pub fn op_idx_delete(
                     let cursor = cursor.as_btree_mut();
                     return_if_io!(cursor.delete());
                 }
-                let n_change = program.n_change.get();
-                program.n_change.set(n_change + 1);
+                state.n_change += 1;
                 state.pc += 1;
                 state.op_idx_delete_state = None;
                 return Ok(InsnFunctionStepResult::Step);
@@ -4473,18 +4823,34 @@ pub fn op_new_rowid(
     mv_store: Option<&Rc<MvStore>>,
 ) -> Result<InsnFunctionStepResult> {
     let Insn::NewRowid {
-        cursor, rowid_reg, ..
+        cursor,
+        rowid_reg,
+        prev_largest_reg,
     } = insn
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    let rowid = {
+    let mut rowid = {
         let mut cursor = state.get_cursor(*cursor);
         let cursor = cursor.as_btree_mut();
         // TODO: make io handle rng
         let rowid = return_if_io!(get_new_rowid(cursor, thread_rng()));
         rowid
     };
+    if *prev_largest_reg > 0 {
+        // AUTOINCREMENT: never reuse a rowid, even one of a deleted row. Once the largest
+        // possible rowid was handed out, the table is full.
+        let prev_largest = match state.registers[*prev_largest_reg].get_owned_value() {
+            Value::Integer(prev_largest) => *prev_largest,
+            _ => 0,
+        };
+        if prev_largest >= rowid {
+            rowid = prev_largest
+                .checked_add(1)
+                .ok_or(LimboError::DatabaseFull)?;
+        }
+        state.registers[*prev_largest_reg] = Register::Value(Value::Integer(rowid));
+    }
     state.registers[*rowid_reg] = Register::Value(Value::Integer(rowid));
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
@@ -4525,6 +4891,29 @@ pub fn op_must_be_int(
     Ok(InsnFunctionStepResult::Step)
 }
 
+pub fn op_mem_max(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::MemMax { dest_reg, src_reg } = insn else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    let current = match state.registers[*dest_reg].get_owned_value() {
+        Value::Integer(value) => *value,
+        _ => 0,
+    };
+    if let Value::Integer(value) = state.registers[*src_reg].get_owned_value() {
+        if *value > current {
+            state.registers[*dest_reg] = Register::Value(Value::Integer(*value));
+        }
+    }
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
 pub fn op_soft_null(
     program: &Program,
     state: &mut ProgramState,
@@ -4679,11 +5068,13 @@ pub fn op_open_write(
     let Insn::OpenWrite {
         cursor_id,
         root_page,
+        db,
         ..
     } = insn
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
+    let pager = &database_pager(program, *db, pager)?;
     let root_page = match root_page {
         RegisterOrLiteral::Literal(lit) => *lit as u64,
         RegisterOrLiteral::Register(reg) => match &state.registers[*reg].get_owned_value() {
@@ -4713,25 +5104,7 @@ pub fn op_open_write(
         None => None,
     };
     if let Some(index) = maybe_index {
-        let conn = program.connection.clone();
-        let schema = conn.schema.try_read().ok_or(LimboError::SchemaLocked)?;
-        let table = schema
-            .get_table(&index.table_name)
-            .map_or(None, |table| table.btree());
-        let collations = table.map_or(Vec::new(), |table| {
-            index
-                .columns
-                .iter()
-                .map(|c| {
-                    table
-                        .columns
-                        .get(c.pos_in_table)
-                        .unwrap()
-                        .collation
-                        .unwrap_or_default()
-                })
-                .collect()
-        });
+        let collations = index_collations(program, *db, index)?;
         let cursor = BTreeCursor::new_index(
             mv_cursor,
             pager.clone(),
@@ -4786,10 +5159,7 @@ pub fn op_create_btree(
     let Insn::CreateBtree { db, root, flags } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    if *db > 0 {
-        // TODO: implement temp databases
-        todo!("temp databases not implemented yet");
-    }
+    let pager = database_pager(program, *db, pager)?;
     // FIXME: handle page cache is full
     let root_page = return_if_io!(pager.btree_create(flags));
     state.registers[*root] = Register::Value(Value::Integer(root_page as i64));
@@ -4812,9 +5182,7 @@ pub fn op_destroy(
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    if *is_temp == 1 {
-        todo!("temp databases not implemented yet.");
-    }
+    let pager = &database_pager(program, *is_temp, pager)?;
     // TODO not sure if should be BTreeCursor::new_table or BTreeCursor::new_index here or neither and just pass an emtpy vec
     let mut cursor = BTreeCursor::new(None, pager.clone(), *root, Vec::new());
     let former_root_page_result = cursor.btree_destroy()?;
@@ -4826,6 +5194,26 @@ pub fn op_destroy(
     Ok(InsnFunctionStepResult::Step)
 }
 
+pub fn op_clear(
+    program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::Clear { root, db } = insn else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    let pager = &database_pager(program, *db, pager)?;
+    let cursor = state
+        .op_clear_cursor
+        .get_or_insert_with(|| BTreeCursor::new(None, pager.clone(), *root, Vec::new()));
+    return_if_io!(cursor.btree_clear());
+    state.op_clear_cursor = None;
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
 pub fn op_drop_table(
     program: &Program,
     state: &mut ProgramState,
@@ -4836,11 +5224,14 @@ pub fn op_drop_table(
     let Insn::DropTable { db, table_name, .. } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    if *db > 0 {
-        todo!("temp databases not implemented yet");
-    }
-    let conn = program.connection.clone();
-    {
+    let conn = program.connection();
+    if *db == TEMP_DB {
+        let temp = conn.temp_database()?;
+        let mut schema = temp.schema().write();
+        schema.remove_indices_for_table(table_name);
+        schema.remove_table(table_name);
+        conn.temp_schema_changed();
+    } else {
         let mut schema = conn.schema.write();
         schema.remove_indices_for_table(table_name);
         schema.remove_table(table_name);
@@ -4893,16 +5284,28 @@ pub fn op_page_count(
     let Insn::PageCount { db, dest } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    if *db > 0 {
-        // TODO: implement temp databases
-        todo!("temp databases not implemented yet");
-    }
+    let pager = database_pager(program, *db, pager)?;
     let count = pager.db_header.lock().database_size.into();
     state.registers[*dest] = Register::Value(Value::Integer(count));
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
 }
 
+pub fn op_shrink_memory(
+    _program: &Program,
+    state: &mut ProgramState,
+    insn: &Insn,
+    pager: &Rc<Pager>,
+    _mv_store: Option<&Rc<MvStore>>,
+) -> Result<InsnFunctionStepResult> {
+    let Insn::ShrinkMemory = insn else {
+        unreachable!("unexpected Insn {:?}", insn)
+    };
+    pager.shrink_memory();
+    state.pc += 1;
+    Ok(InsnFunctionStepResult::Step)
+}
+
 pub fn op_parse_schema(
     program: &Program,
     state: &mut ProgramState,
@@ -4910,14 +5313,17 @@ pub fn op_parse_schema(
     pager: &Rc<Pager>,
     mv_store: Option<&Rc<MvStore>>,
 ) -> Result<InsnFunctionStepResult> {
-    let Insn::ParseSchema {
-        db: _,
-        where_clause,
-    } = insn
-    else {
+    let Insn::ParseSchema { db, where_clause } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    let conn = program.connection.clone();
+    let conn = program.connection();
+    if *db == TEMP_DB {
+        conn.temp_database()?
+            .parse_schema(where_clause.as_deref())?;
+        conn.temp_schema_changed();
+        state.pc += 1;
+        return Ok(InsnFunctionStepResult::Step);
+    }
 
     if let Some(where_clause) = where_clause {
         let stmt = conn.prepare(format!(
@@ -4951,9 +5357,10 @@ pub fn op_parse_schema(
                 state.mv_tx_id,
             )?;
         }
+        new.schema_version = pager.db_header.lock().schema_cookie;
 
-        let mut schema = conn.schema.write();
-        *schema = new;
+        *conn.schema.write() = new;
+        conn.load_index_stats()?;
     }
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
@@ -4969,10 +5376,7 @@ pub fn op_read_cookie(
     let Insn::ReadCookie { db, dest, cookie } = insn else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    if *db > 0 {
-        // TODO: implement temp databases
-        todo!("temp databases not implemented yet");
-    }
+    let pager = database_pager(program, *db, pager)?;
     let cookie_value = match cookie {
         Cookie::UserVersion => pager.db_header.lock().user_version.into(),
         Cookie::SchemaVersion => pager.db_header.lock().schema_cookie.into(),
@@ -5002,10 +5406,18 @@ pub fn op_set_cookie(
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    if *db > 0 {
-        todo!("temp databases not implemented yet");
-    }
+    let pager = database_pager(program, *db, pager)?;
     match cookie {
+        Cookie::SchemaVersion => {
+            let mut header_guard = pager.db_header.lock();
+            header_guard.schema_cookie = *value as u32;
+            pager.write_database_header(&header_guard)?;
+            if *db == TEMP_DB {
+                program.connection().temp_schema_changed();
+            } else {
+                program.connection().schema.write().schema_version = *value as u32;
+            }
+        }
         Cookie::UserVersion => {
             let mut header_guard = pager.db_header.lock();
             header_guard.user_version = *value;
@@ -5203,7 +5615,7 @@ pub fn op_open_ephemeral(
         _ => unreachable!("unexpected Insn {:?}", insn),
     };
 
-    let conn = program.connection.clone();
+    let conn = program.connection();
     let io = conn.pager.io.get_memory_io();
 
     let file = io.open_file("", OpenFlags::Create, true)?;
@@ -5211,8 +5623,11 @@ pub fn op_open_ephemeral(
     let db_file = Arc::new(FileMemoryStorage::new(file));
 
     let db_header = Pager::begin_open(db_file.clone())?;
-    let buffer_pool = Rc::new(BufferPool::new(db_header.lock().get_page_size() as usize));
-    let page_cache = Arc::new(RwLock::new(DumbLruPageCache::default()));
+    let buffer_pool = Rc::new(BufferPool::with_memory(
+        db_header.lock().get_page_size() as usize,
+        conn.memory.clone(),
+    ));
+    let page_cache = Arc::new(RwLock::new(S3FifoPageCache::default()));
 
     let pager = Rc::new(Pager::finish_open(
         db_header,
@@ -5286,6 +5701,9 @@ pub fn op_open_ephemeral(
         CursorType::VirtualTable(_) => {
             panic!("OpenEphemeral on virtual table cursor, use Insn::VOpen instead");
         }
+        CursorType::HashTable(_) => {
+            panic!("OpenEphemeral on hash table cursor, use Insn::HashOpen instead");
+        }
     }
 
     state.pc += 1;
@@ -6308,19 +6726,20 @@ impl Value {
         regex_cache: Option<&mut HashMap<String, Regex>>,
         pattern: &str,
         text: &str,
+        case_sensitive: bool,
     ) -> bool {
         if let Some(cache) = regex_cache {
             match cache.get(pattern) {
                 Some(re) => re.is_match(text),
                 None => {
-                    let re = construct_like_regex(pattern);
+                    let re = construct_like_regex(pattern, case_sensitive);
                     let res = re.is_match(text);
                     cache.insert(pattern.to_string(), re);
                     res
                 }
             }
         } else {
-            let re = construct_like_regex(pattern);
+            let re = construct_like_regex(pattern, case_sensitive);
             re.is_match(text)
         }
     }
@@ -6386,7 +6805,7 @@ fn exec_char(values: &[Register]) -> Value {
     Value::build_text(&result)
 }
 
-fn construct_like_regex(pattern: &str) -> Regex {
+fn construct_like_regex(pattern: &str, case_sensitive: bool) -> Regex {
     let mut regex_pattern = String::with_capacity(pattern.len() * 2);
 
     regex_pattern.push('^');
@@ -6408,7 +6827,7 @@ fn construct_like_regex(pattern: &str) -> Regex {
     regex_pattern.push('$');
 
     RegexBuilder::new(&regex_pattern)
-        .case_insensitive(true)
+        .case_insensitive(!case_sensitive)
         .dot_matches_new_line(true)
         .build()
         .unwrap()
@@ -7663,34 +8082,41 @@ mod tests {
 
     #[test]
     fn test_like_with_escape_or_regexmeta_chars() {
-        assert!(Value::exec_like(None, r#"\%A"#, r#"\A"#));
-        assert!(Value::exec_like(None, "%a%a", "aaaa"));
+        assert!(Value::exec_like(None, r#"\%A"#, r#"\A"#, false));
+        assert!(Value::exec_like(None, "%a%a", "aaaa", false));
     }
 
     #[test]
     fn test_like_no_cache() {
-        assert!(Value::exec_like(None, "a%", "aaaa"));
-        assert!(Value::exec_like(None, "%a%a", "aaaa"));
-        assert!(!Value::exec_like(None, "%a.a", "aaaa"));
-        assert!(!Value::exec_like(None, "a.a%", "aaaa"));
-        assert!(!Value::exec_like(None, "%a.ab", "aaaa"));
+        assert!(Value::exec_like(None, "a%", "aaaa", false));
+        assert!(Value::exec_like(None, "%a%a", "aaaa", false));
+        assert!(!Value::exec_like(None, "%a.a", "aaaa", false));
+        assert!(!Value::exec_like(None, "a.a%", "aaaa", false));
+        assert!(!Value::exec_like(None, "%a.ab", "aaaa", false));
     }
 
     #[test]
     fn test_like_with_cache() {
         let mut cache = HashMap::new();
-        assert!(Value::exec_like(Some(&mut cache), "a%", "aaaa"));
-        assert!(Value::exec_like(Some(&mut cache), "%a%a", "aaaa"));
-        assert!(!Value::exec_like(Some(&mut cache), "%a.a", "aaaa"));
-        assert!(!Value::exec_like(Some(&mut cache), "a.a%", "aaaa"));
-        assert!(!Value::exec_like(Some(&mut cache), "%a.ab", "aaaa"));
+        assert!(Value::exec_like(Some(&mut cache), "a%", "aaaa", false));
+        assert!(Value::exec_like(Some(&mut cache), "%a%a", "aaaa", false));
+        assert!(!Value::exec_like(Some(&mut cache), "%a.a", "aaaa", false));
+        assert!(!Value::exec_like(Some(&mut cache), "a.a%", "aaaa", false));
+        assert!(!Value::exec_like(Some(&mut cache), "%a.ab", "aaaa", false));
 
         // again after values have been cached
-        assert!(Value::exec_like(Some(&mut cache), "a%", "aaaa"));
-        assert!(Value::exec_like(Some(&mut cache), "%a%a", "aaaa"));
-        assert!(!Value::exec_like(Some(&mut cache), "%a.a", "aaaa"));
-        assert!(!Value::exec_like(Some(&mut cache), "a.a%", "aaaa"));
-        assert!(!Value::exec_like(Some(&mut cache), "%a.ab", "aaaa"));
+        assert!(Value::exec_like(Some(&mut cache), "a%", "aaaa", false));
+        assert!(Value::exec_like(Some(&mut cache), "%a%a", "aaaa", false));
+        assert!(!Value::exec_like(Some(&mut cache), "%a.a", "aaaa", false));
+        assert!(!Value::exec_like(Some(&mut cache), "a.a%", "aaaa", false));
+        assert!(!Value::exec_like(Some(&mut cache), "%a.ab", "aaaa", false));
+    }
+
+    #[test]
+    fn test_like_case_sensitive() {
+        assert!(Value::exec_like(None, "A%", "abc", false));
+        assert!(!Value::exec_like(None, "A%", "abc", true));
+        assert!(Value::exec_like(None, "a%", "abc", true));
     }
 
     #[test]
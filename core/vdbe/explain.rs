@@ -20,6 +20,7 @@ pub fn insn_to_str(
             CursorType::Pseudo(_) => "pseudo",
             CursorType::VirtualTable(virtual_table) => &virtual_table.name,
             CursorType::Sorter => "sorter",
+            CursorType::HashTable(table) => &table.name,
         }
     };
     let (opcode, p1, p2, p3, p4, p5, comment): (&str, i32, i32, i32, Value, u16, String) =
@@ -357,11 +358,12 @@ pub fn insn_to_str(
             Insn::OpenRead {
                 cursor_id,
                 root_page,
+                db,
             } => (
                 "OpenRead",
                 *cursor_id as i32,
                 *root_page as i32,
-                0,
+                *db as i32,
                 Value::build_text(""),
                 0,
                 {
@@ -531,7 +533,7 @@ pub fn insn_to_str(
             } => {
                 let cursor_type = &program.cursor_ref[*cursor_id].1;
                 let column_name: Option<&String> = match cursor_type {
-                    CursorType::BTreeTable(table) => {
+                    CursorType::BTreeTable(table) | CursorType::HashTable(table) => {
                         let name = table.columns.get(*column).and_then(|v| v.name.as_ref());
                         name
                     }
@@ -648,9 +650,9 @@ pub fn insn_to_str(
                 0,
                 "".to_string(),
             ),
-            Insn::Transaction { write } => (
+            Insn::Transaction { db, write } => (
                 "Transaction",
-                0,
+                *db as i32,
                 *write as i32,
                 0,
                 Value::build_text(""),
@@ -1029,6 +1031,98 @@ pub fn insn_to_str(
                 0,
                 "".to_string(),
             ),
+            Insn::HashOpen { cursor_id } => (
+                "HashOpen",
+                *cursor_id as i32,
+                0,
+                0,
+                Value::build_text(""),
+                0,
+                format!("table={}", get_table_or_index_name(*cursor_id)),
+            ),
+            Insn::HashInsert {
+                cursor_id,
+                key_start_reg,
+                num_keys,
+                record_reg,
+                rowid_reg,
+            } => (
+                "HashInsert",
+                *cursor_id as i32,
+                *record_reg as i32,
+                *key_start_reg as i32,
+                Value::build_text(""),
+                0,
+                format!(
+                    "key=r[{}..{}] data=r[{}] rowid=r[{}]",
+                    key_start_reg,
+                    key_start_reg + num_keys - 1,
+                    record_reg,
+                    rowid_reg
+                ),
+            ),
+            Insn::HashProbe {
+                cursor_id,
+                key_start_reg,
+                num_keys,
+                target_pc,
+            } => (
+                "HashProbe",
+                *cursor_id as i32,
+                target_pc.to_debug_int(),
+                *key_start_reg as i32,
+                Value::build_text(""),
+                0,
+                format!(
+                    "key=r[{}..{}]; if no match goto {}",
+                    key_start_reg,
+                    key_start_reg + num_keys - 1,
+                    target_pc.to_debug_int()
+                ),
+            ),
+            Insn::HashNext {
+                cursor_id,
+                pc_if_next,
+            } => (
+                "HashNext",
+                *cursor_id as i32,
+                pc_if_next.to_debug_int(),
+                0,
+                Value::build_text(""),
+                0,
+                "".to_string(),
+            ),
+            Insn::RowSetAdd {
+                rowset_reg,
+                rowid_reg,
+            } => (
+                "RowSetAdd",
+                *rowset_reg as i32,
+                *rowid_reg as i32,
+                0,
+                Value::build_text(""),
+                0,
+                format!("rowset(r[{}]).add(r[{}])", rowset_reg, rowid_reg),
+            ),
+            Insn::RowSetTest {
+                rowset_reg,
+                target_pc,
+                rowid_reg,
+                set_number,
+            } => (
+                "RowSetTest",
+                *rowset_reg as i32,
+                target_pc.to_debug_int(),
+                *rowid_reg as i32,
+                Value::Integer(*set_number as i64),
+                0,
+                format!(
+                    "if r[{}] in rowset(r[{}]) goto {}",
+                    rowid_reg,
+                    rowset_reg,
+                    target_pc.to_debug_int()
+                ),
+            ),
             Insn::Function {
                 constant_mask,
                 start_reg,
@@ -1110,13 +1204,17 @@ pub fn insn_to_str(
                 flag.0 as u16,
                 format!("intkey=r[{}] data=r[{}]", key_reg, record_reg),
             ),
-            Insn::Delete { cursor_id } => (
+            Insn::Delete {
+                cursor_id,
+                table_name,
+                is_part_of_update,
+            } => (
                 "Delete",
                 *cursor_id as i32,
                 0,
                 0,
-                Value::build_text(""),
-                0,
+                Value::build_text(table_name),
+                *is_part_of_update as u16,
                 "".to_string(),
             ),
             Insn::IdxDelete {
@@ -1154,6 +1252,15 @@ pub fn insn_to_str(
                 0,
                 "".to_string(),
             ),
+            Insn::MemMax { dest_reg, src_reg } => (
+                "MemMax",
+                *dest_reg as i32,
+                *src_reg as i32,
+                0,
+                Value::build_text(""),
+                0,
+                format!("r[{}]=max(r[{}],r[{}])", dest_reg, dest_reg, src_reg),
+            ),
             Insn::SoftNull { reg } => (
                 "SoftNull",
                 *reg as i32,
@@ -1217,7 +1324,7 @@ pub fn insn_to_str(
                 cursor_id,
                 root_page,
                 name,
-                ..
+                db,
             } => (
                 "OpenWrite",
                 *cursor_id as i32,
@@ -1225,7 +1332,7 @@ pub fn insn_to_str(
                     RegisterOrLiteral::Literal(i) => *i as _,
                     RegisterOrLiteral::Register(i) => *i as _,
                 },
-                0,
+                *db as i32,
                 Value::build_text(""),
                 0,
                 format!("root={}; {}", root_page, name),
@@ -1268,6 +1375,15 @@ pub fn insn_to_str(
                     root, former_root_reg, is_temp
                 ),
             ),
+            Insn::Clear { root, db } => (
+                "Clear",
+                *root as i32,
+                *db as i32,
+                0,
+                Value::build_text(""),
+                0,
+                format!("root iDb={} db={}", root, db),
+            ),
             Insn::DropTable {
                 db,
                 _p2,
@@ -1427,6 +1543,15 @@ pub fn insn_to_str(
                 0,
                 "".to_string(),
             ),
+            Insn::ShrinkMemory => (
+                "ShrinkMemory",
+                0,
+                0,
+                0,
+                Value::build_text(""),
+                0,
+                "".to_string(),
+            ),
             Insn::ReadCookie { db, dest, cookie } => (
                 "ReadCookie",
                 *db as i32,
@@ -0,0 +1,542 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::RandomState, HashMap},
+    hash::BuildHasher,
+    pin::Pin,
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::{
+    io::{Buffer, Completion, File, OpenFlags, ReadCompletion, WriteCompletion, IO},
+    storage::sqlite3_ondisk::read_record,
+    types::{ImmutableRecord, Value},
+    LimboError, Result,
+};
+
+/// The number of partitions the entries of a [HashTable] are split into.
+/// A partition is the unit that is spilled to disk when the table outgrows its memory budget.
+pub(crate) const NUM_PARTITIONS: usize = 32;
+
+/// Rough per-entry bookkeeping overhead that is counted against the memory budget,
+/// on top of the size of the key and the record payload.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// A single hash key value, normalized so that two values are equal exactly when
+/// they compare equal with `=` under the BINARY collation.
+/// Reals with an integral value are stored as integers, so that e.g. `1 = 1.0` matches.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum KeyValue {
+    Integer(i64),
+    Float(u64),
+    Text(Vec<u8>),
+    Blob(Vec<u8>),
+}
+
+impl KeyValue {
+    /// Normalize a value into a key value. Returns None for NULL, which never matches anything.
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => None,
+            Value::Integer(i) => Some(Self::Integer(*i)),
+            Value::Float(f) => {
+                if f.fract() == 0.0 && *f >= -9223372036854775808.0 && *f < 9223372036854775808.0 {
+                    Some(Self::Integer(*f as i64))
+                } else {
+                    Some(Self::Float(f.to_bits()))
+                }
+            }
+            Value::Text(text) => Some(Self::Text(text.value.clone())),
+            Value::Blob(blob) => Some(Self::Blob(blob.clone())),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Integer(_) | Self::Float(_) => 8,
+            Self::Text(bytes) | Self::Blob(bytes) => bytes.len(),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Integer(i) => {
+                buf.push(0);
+                buf.extend_from_slice(&i.to_le_bytes());
+            }
+            Self::Float(bits) => {
+                buf.push(1);
+                buf.extend_from_slice(&bits.to_le_bytes());
+            }
+            Self::Text(bytes) | Self::Blob(bytes) => {
+                buf.push(if matches!(self, Self::Text(_)) { 2 } else { 3 });
+                buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+                buf.extend_from_slice(bytes);
+            }
+        }
+    }
+
+    fn decode(buf: &[u8], pos: &mut usize) -> Result<Self> {
+        let tag = read_bytes::<1>(buf, pos)?[0];
+        match tag {
+            0 => Ok(Self::Integer(i64::from_le_bytes(read_bytes(buf, pos)?))),
+            1 => Ok(Self::Float(u64::from_le_bytes(read_bytes(buf, pos)?))),
+            2 | 3 => {
+                let len = u32::from_le_bytes(read_bytes(buf, pos)?) as usize;
+                let bytes = read_slice(buf, pos, len)?.to_vec();
+                Ok(if tag == 2 {
+                    Self::Text(bytes)
+                } else {
+                    Self::Blob(bytes)
+                })
+            }
+            _ => Err(LimboError::Corrupt(format!(
+                "invalid hash table key tag: {}",
+                tag
+            ))),
+        }
+    }
+}
+
+type HashKey = Vec<KeyValue>;
+
+/// A row of the build side of the hash join.
+#[derive(Debug, Clone)]
+struct Entry {
+    rowid: i64,
+    record: ImmutableRecord,
+}
+
+type Buckets = HashMap<HashKey, Rc<Vec<Entry>>>;
+
+#[derive(Default)]
+struct Partition {
+    /// The entries of the partition that are in memory, grouped by key.
+    buckets: Buckets,
+    /// The number of bytes of memory accounted to `buckets`.
+    size: usize,
+    /// The (offset, length) ranges of the spill file that hold the spilled entries of the partition.
+    runs: Vec<(usize, usize)>,
+}
+
+impl Partition {
+    fn is_spilled(&self) -> bool {
+        !self.runs.is_empty()
+    }
+}
+
+/// The hash table of a hash join.
+///
+/// The table is filled with the rows of the build side during the build phase, and is then probed
+/// with a key for each row of the outer loops; the rows matching the key are iterated like a cursor.
+///
+/// Entries are hash partitioned. Whenever the memory used by the table exceeds its budget, the largest
+/// partition held in memory is written to a temporary spill file. When a spilled partition is probed,
+/// it is read back in full; only one spilled partition is held in memory at a time.
+pub struct HashTable {
+    /// The number of key columns, needed to decode spilled entries.
+    num_keys: usize,
+    memory_budget: usize,
+    memory_used: usize,
+    hasher: RandomState,
+    partitions: Vec<Partition>,
+    io: Arc<dyn IO>,
+    spill_file: Option<SpillFile>,
+    /// Whether the build phase is over, i.e. the table has been probed at least once.
+    probing: bool,
+    /// The spilled partition that is currently read back into memory.
+    loaded: Option<(usize, Buckets)>,
+    /// The entries matching the last probe.
+    matches: Option<Rc<Vec<Entry>>>,
+    current: usize,
+    null_flag: bool,
+}
+
+impl HashTable {
+    pub fn new(memory_budget: usize, io: Arc<dyn IO>) -> Self {
+        Self {
+            num_keys: 0,
+            memory_budget,
+            memory_used: 0,
+            hasher: RandomState::new(),
+            partitions: (0..NUM_PARTITIONS).map(|_| Partition::default()).collect(),
+            io,
+            spill_file: None,
+            probing: false,
+            loaded: None,
+            matches: None,
+            current: 0,
+            null_flag: false,
+        }
+    }
+
+    /// Insert a row into the table. Rows with a NULL in any of the key columns are ignored,
+    /// since they can never match a probe.
+    pub fn insert(&mut self, key: &[Value], rowid: i64, record: &ImmutableRecord) -> Result<()> {
+        assert!(
+            !self.probing,
+            "cannot insert into a hash table that was probed"
+        );
+        let Some(key) = normalize_key(key) else {
+            return Ok(());
+        };
+        self.num_keys = key.len();
+        let size = key.iter().map(KeyValue::size).sum::<usize>()
+            + record.get_payload().len()
+            + ENTRY_OVERHEAD;
+        let partition_idx = self.partition_of(&key);
+        let partition = &mut self.partitions[partition_idx];
+        let bucket = partition.buckets.entry(key).or_default();
+        Rc::make_mut(bucket).push(Entry {
+            rowid,
+            record: record.clone(),
+        });
+        partition.size += size;
+        self.memory_used += size;
+
+        while self.memory_used > self.memory_budget {
+            let Some(largest) = (0..NUM_PARTITIONS)
+                .filter(|&p| self.partitions[p].size > 0)
+                .max_by_key(|&p| self.partitions[p].size)
+            else {
+                break;
+            };
+            self.spill_partition(largest)?;
+        }
+        Ok(())
+    }
+
+    /// Look up the rows matching a key and position the table on the first of them.
+    /// Returns false if there are no matching rows.
+    pub fn probe(&mut self, key: &[Value]) -> Result<bool> {
+        if !self.probing {
+            self.finish_build()?;
+        }
+        self.null_flag = false;
+        self.current = 0;
+        self.matches = None;
+        let Some(key) = normalize_key(key) else {
+            return Ok(false);
+        };
+        let partition_idx = self.partition_of(&key);
+        let buckets = if self.partitions[partition_idx].is_spilled() {
+            if !matches!(self.loaded, Some((loaded_idx, _)) if loaded_idx == partition_idx) {
+                let buckets = self.load_partition(partition_idx)?;
+                self.loaded = Some((partition_idx, buckets));
+            }
+            &self.loaded.as_ref().unwrap().1
+        } else {
+            &self.partitions[partition_idx].buckets
+        };
+        self.matches = buckets.get(&key).cloned();
+        Ok(self.matches.is_some())
+    }
+
+    /// Advance to the next row matching the last probe. Returns false if there are no more rows.
+    pub fn next(&mut self) -> bool {
+        self.current += 1;
+        self.has_row()
+    }
+
+    fn has_row(&self) -> bool {
+        self.matches
+            .as_ref()
+            .is_some_and(|matches| self.current < matches.len())
+    }
+
+    fn current_entry(&self) -> Option<&Entry> {
+        self.matches
+            .as_ref()
+            .and_then(|matches| matches.get(self.current))
+    }
+
+    pub fn record(&self) -> Option<&ImmutableRecord> {
+        self.current_entry().map(|entry| &entry.record)
+    }
+
+    pub fn rowid(&self) -> Option<i64> {
+        self.current_entry().map(|entry| entry.rowid)
+    }
+
+    pub fn set_null_flag(&mut self, flag: bool) {
+        self.null_flag = flag;
+    }
+
+    pub fn get_null_flag(&self) -> bool {
+        self.null_flag
+    }
+
+    /// Whether any partition of the table was spilled to disk.
+    pub fn has_spilled(&self) -> bool {
+        self.spill_file.is_some()
+    }
+
+    fn partition_of(&self, key: &HashKey) -> usize {
+        (self.hasher.hash_one(key) % NUM_PARTITIONS as u64) as usize
+    }
+
+    /// Write the remaining in-memory entries of spilled partitions to disk, so that every
+    /// spilled partition can be read back in full from the spill file.
+    fn finish_build(&mut self) -> Result<()> {
+        for p in 0..NUM_PARTITIONS {
+            if self.partitions[p].is_spilled() && self.partitions[p].size > 0 {
+                self.spill_partition(p)?;
+            }
+        }
+        self.probing = true;
+        Ok(())
+    }
+
+    fn spill_partition(&mut self, partition_idx: usize) -> Result<()> {
+        if self.spill_file.is_none() {
+            self.spill_file = Some(SpillFile::open(&self.io)?);
+        }
+        let partition = &mut self.partitions[partition_idx];
+        let mut buf = Vec::with_capacity(partition.size);
+        for (key, entries) in partition.buckets.drain() {
+            for entry in entries.iter() {
+                for key_value in key.iter() {
+                    key_value.encode(&mut buf);
+                }
+                buf.extend_from_slice(&entry.rowid.to_le_bytes());
+                let payload = entry.record.get_payload();
+                buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+                buf.extend_from_slice(payload);
+            }
+        }
+        let run = self.spill_file.as_mut().unwrap().append(&self.io, buf)?;
+        partition.runs.push(run);
+        self.memory_used -= partition.size;
+        partition.size = 0;
+        Ok(())
+    }
+
+    fn load_partition(&self, partition_idx: usize) -> Result<Buckets> {
+        let spill_file = self.spill_file.as_ref().unwrap();
+        let mut buckets = Buckets::new();
+        for &(offset, len) in self.partitions[partition_idx].runs.iter() {
+            let buf = spill_file.read(&self.io, offset, len)?;
+            let mut pos = 0;
+            while pos < buf.len() {
+                let key = (0..self.num_keys)
+                    .map(|_| KeyValue::decode(&buf, &mut pos))
+                    .collect::<Result<HashKey>>()?;
+                let rowid = i64::from_le_bytes(read_bytes(&buf, &mut pos)?);
+                let payload_len = u32::from_le_bytes(read_bytes(&buf, &mut pos)?) as usize;
+                let payload = read_slice(&buf, &mut pos, payload_len)?;
+                let mut record = ImmutableRecord::new(payload_len, 0);
+                read_record(payload, &mut record)?;
+                Rc::make_mut(buckets.entry(key).or_default()).push(Entry { rowid, record });
+            }
+        }
+        Ok(buckets)
+    }
+}
+
+fn normalize_key(key: &[Value]) -> Option<HashKey> {
+    key.iter().map(KeyValue::from_value).collect()
+}
+
+fn read_bytes<const N: usize>(buf: &[u8], pos: &mut usize) -> Result<[u8; N]> {
+    Ok(read_slice(buf, pos, N)?.try_into().unwrap())
+}
+
+fn read_slice<'a>(buf: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let Some(slice) = buf.get(*pos..*pos + len) else {
+        return Err(LimboError::Corrupt(
+            "hash table spill file is truncated".to_string(),
+        ));
+    };
+    *pos += len;
+    Ok(slice)
+}
+
+/// A temporary file holding the spilled partitions of a [HashTable]. It is removed when dropped.
+struct SpillFile {
+    file: Arc<dyn File>,
+    path: String,
+    size: usize,
+}
+
+impl SpillFile {
+    fn open(io: &Arc<dyn IO>) -> Result<Self> {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "limbo-hash-join-{:016x}",
+                io.generate_random_number() as u64
+            ))
+            .to_string_lossy()
+            .into_owned();
+        let file = io.open_file(&path, OpenFlags::Create, false)?;
+        Ok(Self {
+            file,
+            path,
+            size: 0,
+        })
+    }
+
+    /// Append data to the end of the file, returning the (offset, length) range it was written to.
+    fn append(&mut self, io: &Arc<dyn IO>, data: Vec<u8>) -> Result<(usize, usize)> {
+        let offset = self.size;
+        let len = data.len();
+        #[allow(clippy::arc_with_non_send_sync)]
+        let buffer = Arc::new(RefCell::new(Buffer::new(Pin::new(data), Rc::new(|_| {}))));
+        #[allow(clippy::arc_with_non_send_sync)]
+        let completion = Arc::new(Completion::Write(WriteCompletion::new(Box::new(|_| {}))));
+        self.file.pwrite(offset, buffer, completion.clone())?;
+        while !completion.is_completed() {
+            io.run_once()?;
+        }
+        self.size += len;
+        Ok((offset, len))
+    }
+
+    fn read(&self, io: &Arc<dyn IO>, offset: usize, len: usize) -> Result<Vec<u8>> {
+        #[allow(clippy::arc_with_non_send_sync)]
+        let buffer = Arc::new(RefCell::new(Buffer::allocate(len, Rc::new(|_| {}))));
+        #[allow(clippy::arc_with_non_send_sync)]
+        let completion = Arc::new(Completion::Read(ReadCompletion::new(
+            buffer.clone(),
+            Box::new(|_| {}),
+        )));
+        self.file.pread(offset, completion.clone())?;
+        while !completion.is_completed() {
+            io.run_once()?;
+        }
+        let data = buffer.borrow().as_slice().to_vec();
+        Ok(data)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{io::MemoryIO, vdbe::Register, PlatformIO};
+
+    fn record(rowid: i64, text: &str) -> ImmutableRecord {
+        ImmutableRecord::from_registers(&[
+            Register::Value(Value::Integer(rowid)),
+            Register::Value(Value::build_text(text)),
+        ])
+    }
+
+    /// Probe the table and collect the rowids and the text column of the matching rows.
+    fn probe_all(table: &mut HashTable, key: &[Value]) -> Vec<(i64, String)> {
+        let mut rows = Vec::new();
+        if !table.probe(key).unwrap() {
+            return rows;
+        }
+        loop {
+            let text = table.record().unwrap().get_value(1).to_owned().to_string();
+            rows.push((table.rowid().unwrap(), text));
+            if !table.next() {
+                break;
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn test_probe_matches_equal_keys() {
+        let mut table = HashTable::new(usize::MAX, Arc::new(MemoryIO::new()));
+        table
+            .insert(&[Value::Integer(1)], 1, &record(1, "a"))
+            .unwrap();
+        table
+            .insert(&[Value::Float(1.0)], 2, &record(2, "b"))
+            .unwrap();
+        table
+            .insert(&[Value::build_text("1")], 3, &record(3, "c"))
+            .unwrap();
+        table.insert(&[Value::Null], 4, &record(4, "d")).unwrap();
+        table
+            .insert(&[Value::Float(1.5)], 5, &record(5, "e"))
+            .unwrap();
+
+        assert_eq!(
+            probe_all(&mut table, &[Value::Integer(1)]),
+            vec![(1, "a".to_string()), (2, "b".to_string())]
+        );
+        assert_eq!(
+            probe_all(&mut table, &[Value::Float(1.0)]),
+            vec![(1, "a".to_string()), (2, "b".to_string())]
+        );
+        assert_eq!(
+            probe_all(&mut table, &[Value::build_text("1")]),
+            vec![(3, "c".to_string())]
+        );
+        assert_eq!(
+            probe_all(&mut table, &[Value::Float(1.5)]),
+            vec![(5, "e".to_string())]
+        );
+        // NULL never matches, not even another NULL.
+        assert!(probe_all(&mut table, &[Value::Null]).is_empty());
+        assert!(probe_all(&mut table, &[Value::Integer(2)]).is_empty());
+        assert!(!table.has_spilled());
+    }
+
+    #[test]
+    fn test_multi_column_keys() {
+        let mut table = HashTable::new(usize::MAX, Arc::new(MemoryIO::new()));
+        for i in 0..100 {
+            let key = [
+                Value::Integer(i % 10),
+                Value::build_text(format!("{}", i % 3)),
+            ];
+            table.insert(&key, i, &record(i, "x")).unwrap();
+        }
+        for a in 0..10 {
+            for b in 0..3 {
+                let key = [Value::Integer(a), Value::build_text(format!("{}", b))];
+                let expected = (0..100)
+                    .filter(|i| i % 10 == a && i % 3 == b)
+                    .map(|i| (i, "x".to_string()))
+                    .collect::<Vec<_>>();
+                assert_eq!(probe_all(&mut table, &key), expected);
+            }
+        }
+    }
+
+    fn check_spilling(io: Arc<dyn IO>) {
+        let mut table = HashTable::new(4096, io);
+        for i in 0..2000 {
+            let text = "t".repeat((i % 50) as usize);
+            table
+                .insert(&[Value::Integer(i % 100)], i, &record(i, &text))
+                .unwrap();
+        }
+        assert!(table.has_spilled());
+        // Probe in an order that reloads spilled partitions several times.
+        for round in 0..2 {
+            for key in (0..110).rev() {
+                let expected = (0..2000)
+                    .filter(|i| i % 100 == key)
+                    .map(|i| (i, "t".repeat((i % 50) as usize)))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    probe_all(&mut table, &[Value::Integer(key)]),
+                    expected,
+                    "round {} key {}",
+                    round,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_spill_to_memory_file() {
+        check_spilling(Arc::new(MemoryIO::new()));
+    }
+
+    #[test]
+    fn test_spill_to_temporary_file() {
+        check_spilling(Arc::new(PlatformIO::new().unwrap()));
+    }
+}
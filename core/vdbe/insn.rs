@@ -324,6 +324,8 @@ pub enum Insn {
     OpenRead {
         cursor_id: CursorID,
         root_page: PageIdx,
+        /// The database holding the b-tree, main (0) or temp (1) (P3).
+        db: usize,
     },
 
     /// Open a cursor for a virtual table.
@@ -453,6 +455,8 @@ pub enum Insn {
 
     /// Start a transaction.
     Transaction {
+        /// The database to start the transaction on, main (0) or temp (1) (P1).
+        db: usize,
         write: bool,
     },
 
@@ -691,6 +695,54 @@ pub enum Insn {
         pc_if_next: BranchOffset,
     },
 
+    /// Open an empty hash table for a hash join. The memory budget of the table is the page cache size;
+    /// beyond it, the table spills to a temporary file.
+    HashOpen {
+        cursor_id: CursorID,
+    },
+
+    /// Insert the record in record_reg with the rowid in rowid_reg into the hash table, keyed on the
+    /// num_keys registers starting at key_start_reg. Rows with a NULL key are not inserted.
+    HashInsert {
+        cursor_id: CursorID,
+        key_start_reg: usize,
+        num_keys: usize,
+        record_reg: usize,
+        rowid_reg: usize,
+    },
+
+    /// Probe the hash table with the num_keys registers starting at key_start_reg, positioning the cursor on the
+    /// first matching row. If there is no matching row, jump to target_pc.
+    HashProbe {
+        cursor_id: CursorID,
+        key_start_reg: usize,
+        num_keys: usize,
+        target_pc: BranchOffset,
+    },
+
+    /// Advance to the next row of the hash table matching the last probe, jumping to pc_if_next if there is one.
+    HashNext {
+        cursor_id: CursorID,
+        pc_if_next: BranchOffset,
+    },
+
+    /// Insert the integer in rowid_reg into the RowSet held in rowset_reg.
+    /// If rowset_reg does not hold a RowSet yet, an empty one is created first.
+    RowSetAdd {
+        rowset_reg: usize,
+        rowid_reg: usize,
+    },
+
+    /// If the RowSet in rowset_reg contains the integer in rowid_reg, jump to target_pc.
+    /// Otherwise the integer is inserted into the RowSet, unless set_number is -1 which
+    /// marks the last test against the set.
+    RowSetTest {
+        rowset_reg: usize,
+        target_pc: BranchOffset,
+        rowid_reg: usize,
+        set_number: i32,
+    },
+
     /// Function
     Function {
         constant_mask: i32, // P1
@@ -731,6 +783,10 @@ pub enum Insn {
 
     Delete {
         cursor_id: CursorID,
+        table_name: String,
+        /// The row is deleted to be inserted again by an UPDATE, so the update hook is not
+        /// called for it.
+        is_part_of_update: bool,
     },
 
     IdxDelete {
@@ -742,13 +798,20 @@ pub enum Insn {
     NewRowid {
         cursor: CursorID,        // P1
         rowid_reg: usize,        // P2  Destination register to store the new rowid
-        prev_largest_reg: usize, // P3 Previous largest rowid in the table (Not used for now)
+        prev_largest_reg: usize, // P3 If not 0, largest rowid ever generated for an AUTOINCREMENT table
     },
 
     MustBeInt {
         reg: usize,
     },
 
+    /// Set the value of register P1 to the maximum of its current value and the value in
+    /// register P2. Both must hold integers.
+    MemMax {
+        dest_reg: usize, // P1
+        src_reg: usize,  // P2
+    },
+
     SoftNull {
         reg: usize,
     },
@@ -781,6 +844,8 @@ pub enum Insn {
         cursor_id: CursorID,
         root_page: RegisterOrLiteral<PageIdx>,
         name: String,
+        /// The database holding the b-tree, main (0) or temp (1) (P3).
+        db: usize,
     },
 
     Copy {
@@ -809,6 +874,15 @@ pub enum Insn {
         is_temp: usize,
     },
 
+    /// Deletes all the contents of the table or index whose root page in the database file is given by P1,
+    /// but keeps the table or index itself.
+    Clear {
+        /// The root page of the table/index to clear
+        root: usize,
+        /// The database within which the table/index lives
+        db: usize,
+    },
+
     ///  Drop a table
     DropTable {
         ///  The database within which this b-tree needs to be dropped (P1).
@@ -901,6 +975,9 @@ pub enum Insn {
         db: usize,
         dest: usize,
     },
+    /// Free as much memory as possible by evicting the clean pages of the page cache and
+    /// freeing the unused page buffers.
+    ShrinkMemory,
     /// Read cookie number P3 from database P1 and write it into register P2
     ReadCookie {
         db: usize,
@@ -1062,6 +1139,12 @@ impl Insn {
             Insn::SorterSort { .. } => execute::op_sorter_sort,
             Insn::SorterData { .. } => execute::op_sorter_data,
             Insn::SorterNext { .. } => execute::op_sorter_next,
+            Insn::HashOpen { .. } => execute::op_hash_open,
+            Insn::HashInsert { .. } => execute::op_hash_insert,
+            Insn::HashProbe { .. } => execute::op_hash_probe,
+            Insn::HashNext { .. } => execute::op_hash_next,
+            Insn::RowSetAdd { .. } => execute::op_rowset_add,
+            Insn::RowSetTest { .. } => execute::op_rowset_test,
             Insn::Function { .. } => execute::op_function,
             Insn::InitCoroutine { .. } => execute::op_init_coroutine,
             Insn::EndCoroutine { .. } => execute::op_end_coroutine,
@@ -1072,6 +1155,7 @@ impl Insn {
             Insn::Delete { .. } => execute::op_delete,
             Insn::NewRowid { .. } => execute::op_new_rowid,
             Insn::MustBeInt { .. } => execute::op_must_be_int,
+            Insn::MemMax { .. } => execute::op_mem_max,
             Insn::SoftNull { .. } => execute::op_soft_null,
             Insn::NoConflict { .. } => execute::op_no_conflict,
             Insn::NotExists { .. } => execute::op_not_exists,
@@ -1080,6 +1164,7 @@ impl Insn {
             Insn::Copy { .. } => execute::op_copy,
             Insn::CreateBtree { .. } => execute::op_create_btree,
             Insn::Destroy { .. } => execute::op_destroy,
+            Insn::Clear { .. } => execute::op_clear,
 
             Insn::DropTable { .. } => execute::op_drop_table,
             Insn::Close { .. } => execute::op_close,
@@ -1095,6 +1180,7 @@ impl Insn {
             Insn::Or { .. } => execute::op_or,
             Insn::Noop => execute::op_noop,
             Insn::PageCount { .. } => execute::op_page_count,
+            Insn::ShrinkMemory => execute::op_shrink_memory,
             Insn::ReadCookie { .. } => execute::op_read_cookie,
             Insn::SetCookie { .. } => execute::op_set_cookie,
             Insn::OpenEphemeral { .. } | Insn::OpenAutoindex { .. } => execute::op_open_ephemeral,
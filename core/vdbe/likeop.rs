@@ -22,11 +22,16 @@ pub fn construct_like_escape_arg(escape_value: &Value) -> Result<char, LimboErro
 }
 
 // Implements LIKE pattern matching with escape
-pub fn exec_like_with_escape(pattern: &str, text: &str, escape: char) -> bool {
-    construct_like_regex_with_escape(pattern, escape).is_match(text)
+pub fn exec_like_with_escape(
+    pattern: &str,
+    text: &str,
+    escape: char,
+    case_sensitive: bool,
+) -> bool {
+    construct_like_regex_with_escape(pattern, escape, case_sensitive).is_match(text)
 }
 
-fn construct_like_regex_with_escape(pattern: &str, escape: char) -> Regex {
+fn construct_like_regex_with_escape(pattern: &str, escape: char, case_sensitive: bool) -> Regex {
     let mut regex_pattern = String::with_capacity(pattern.len() * 2);
 
     regex_pattern.push('^');
@@ -57,7 +62,7 @@ fn construct_like_regex_with_escape(pattern: &str, escape: char) -> Regex {
     regex_pattern.push('$');
 
     RegexBuilder::new(&regex_pattern)
-        .case_insensitive(true)
+        .case_insensitive(!case_sensitive)
         .dot_matches_new_line(true)
         .build()
         .unwrap()
@@ -187,20 +192,20 @@ mod test {
 
     #[test]
     fn test_exec_like_with_escape() {
-        assert!(exec_like_with_escape("abcX%", "abc%", 'X'));
-        assert!(!exec_like_with_escape("abcX%", "abc5", 'X'));
-        assert!(!exec_like_with_escape("abcX%", "abc", 'X'));
-        assert!(!exec_like_with_escape("abcX%", "abcX%", 'X'));
-        assert!(!exec_like_with_escape("abcX%", "abc%%", 'X'));
-        assert!(exec_like_with_escape("abcX_", "abc_", 'X'));
-        assert!(!exec_like_with_escape("abcX_", "abc5", 'X'));
-        assert!(!exec_like_with_escape("abcX_", "abc", 'X'));
-        assert!(!exec_like_with_escape("abcX_", "abcX_", 'X'));
-        assert!(!exec_like_with_escape("abcX_", "abc__", 'X'));
-        assert!(exec_like_with_escape("abcXX", "abcX", 'X'));
-        assert!(!exec_like_with_escape("abcXX", "abc5", 'X'));
-        assert!(!exec_like_with_escape("abcXX", "abc", 'X'));
-        assert!(!exec_like_with_escape("abcXX", "abcXX", 'X'));
+        assert!(exec_like_with_escape("abcX%", "abc%", 'X', false));
+        assert!(!exec_like_with_escape("abcX%", "abc5", 'X', false));
+        assert!(!exec_like_with_escape("abcX%", "abc", 'X', false));
+        assert!(!exec_like_with_escape("abcX%", "abcX%", 'X', false));
+        assert!(!exec_like_with_escape("abcX%", "abc%%", 'X', false));
+        assert!(exec_like_with_escape("abcX_", "abc_", 'X', false));
+        assert!(!exec_like_with_escape("abcX_", "abc5", 'X', false));
+        assert!(!exec_like_with_escape("abcX_", "abc", 'X', false));
+        assert!(!exec_like_with_escape("abcX_", "abcX_", 'X', false));
+        assert!(!exec_like_with_escape("abcX_", "abc__", 'X', false));
+        assert!(exec_like_with_escape("abcXX", "abcX", 'X', false));
+        assert!(!exec_like_with_escape("abcXX", "abc5", 'X', false));
+        assert!(!exec_like_with_escape("abcXX", "abc", 'X', false));
+        assert!(!exec_like_with_escape("abcXX", "abcXX", 'X', false));
     }
 
     #[test]
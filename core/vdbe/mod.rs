@@ -20,8 +20,10 @@
 pub mod builder;
 pub mod execute;
 pub mod explain;
+pub mod hash_table;
 pub mod insn;
 pub mod likeop;
+pub mod rowset;
 pub mod sorter;
 
 use crate::{
@@ -41,7 +43,9 @@ use crate::{
 
 #[cfg(feature = "json")]
 use crate::json::JsonCacheCell;
-use crate::{Connection, MvStore, Result, TransactionState};
+use crate::memory::MemoryTracker;
+use crate::progress::TIMEOUT_CHECK_PERIOD;
+use crate::{Connection, Instant, MvStore, Result, TransactionState};
 use builder::CursorKey;
 use execute::{InsnFunction, InsnFunctionStepResult, OpIdxDeleteState, OpIntegrityCheckState};
 
@@ -51,11 +55,12 @@ use rand::{
 };
 use regex::Regex;
 use std::{
-    cell::{Cell, RefCell},
+    cell::RefCell,
     collections::HashMap,
     num::NonZero,
     rc::Rc,
-    sync::Arc,
+    sync::{Arc, Weak},
+    time::Duration,
 };
 use tracing::{instrument, Level};
 
@@ -219,6 +224,7 @@ pub enum Register {
     Value(Value),
     Aggregate(AggContext),
     Record(ImmutableRecord),
+    RowSet(rowset::RowSet),
 }
 
 /// A row is a the list of registers that hold the values for a filtered row. This row is a pointer, therefore
@@ -249,10 +255,30 @@ pub struct ProgramState {
     json_cache: JsonCacheCell,
     op_idx_delete_state: Option<OpIdxDeleteState>,
     op_integrity_check_state: OpIntegrityCheckState,
+    /// Cursor of the b-tree being cleared by Insn::Clear, kept while waiting for IO.
+    op_clear_cursor: Option<BTreeCursor>,
+    /// Number of times the busy handler has been invoked for the lock being acquired.
+    busy_retries: u32,
+    /// Rowid and, if a session records it, values of the row being deleted by Insn::Delete,
+    /// kept for the update hook and the sessions.
+    op_delete_row: Option<(i64, Option<Vec<Value>>)>,
+    /// Number of rows changed by the statement so far.
+    n_change: i64,
+    /// Number of instructions executed so far, for the progress handler and the timeout.
+    n_insns: u64,
+    /// Timeout of the statement, overriding the timeout of the connection.
+    pub(crate) timeout: Option<Duration>,
+    /// When the statement times out, set on its first step.
+    deadline: Option<Instant>,
 }
 
 impl ProgramState {
-    pub fn new(max_registers: usize, max_cursors: usize) -> Self {
+    /// Creates the state of a program run, whose JSON cache is charged to `memory`.
+    pub fn new(
+        max_registers: usize,
+        max_cursors: usize,
+        #[cfg_attr(not(feature = "json"), allow(unused_variables))] memory: Arc<MemoryTracker>,
+    ) -> Self {
         let cursors: RefCell<Vec<Option<Cursor>>> =
             RefCell::new((0..max_cursors).map(|_| None).collect());
         let registers = vec![Register::Value(Value::Null); max_registers];
@@ -271,9 +297,16 @@ impl ProgramState {
             parameters: HashMap::new(),
             commit_state: CommitState::Ready,
             #[cfg(feature = "json")]
-            json_cache: JsonCacheCell::new(),
+            json_cache: JsonCacheCell::with_memory(memory),
             op_idx_delete_state: None,
             op_integrity_check_state: OpIntegrityCheckState::Start,
+            op_clear_cursor: None,
+            busy_retries: 0,
+            op_delete_row: None,
+            n_change: 0,
+            n_insns: 0,
+            timeout: None,
+            deadline: None,
         }
     }
 
@@ -313,6 +346,12 @@ impl ProgramState {
         self.regex_cache.like.clear();
         self.interrupted = false;
         self.parameters.clear();
+        self.busy_retries = 0;
+        self.op_delete_row = None;
+        self.op_clear_cursor = None;
+        self.n_change = 0;
+        self.n_insns = 0;
+        self.deadline = None;
         #[cfg(feature = "json")]
         self.json_cache.clear()
     }
@@ -347,6 +386,7 @@ macro_rules! must_be_btree_cursor {
             CursorType::Pseudo(_) => panic!("{} on pseudo cursor", $insn_name),
             CursorType::Sorter => panic!("{} on sorter cursor", $insn_name),
             CursorType::VirtualTable(_) => panic!("{} on virtual table cursor", $insn_name),
+            CursorType::HashTable(_) => panic!("{} on hash table cursor", $insn_name),
         };
         cursor
     }};
@@ -359,20 +399,46 @@ pub struct Program {
     pub database_header: Arc<SpinLock<DatabaseHeader>>,
     pub comments: Option<Vec<(InsnReference, &'static str)>>,
     pub parameters: crate::parameters::Parameters,
-    pub connection: Arc<Connection>,
-    pub n_change: Cell<i64>,
+    /// Weak, so that the statement cache of the connection can hold on to its programs.
+    pub connection: Weak<Connection>,
     pub change_cnt_on: bool,
     pub result_columns: Vec<ResultSetColumn>,
     pub table_references: TableReferences,
+    /// Whether LIKE was case-sensitive when the program was compiled. Its plan may rely on it.
+    pub case_sensitive_like: bool,
 }
 
 impl Program {
+    /// Connection the program was compiled for. A program only runs while a statement, which
+    /// holds its connection, is alive.
+    pub fn connection(&self) -> Arc<Connection> {
+        self.connection
+            .upgrade()
+            .expect("program used after its connection was dropped")
+    }
+
     pub fn step(
         &self,
         state: &mut ProgramState,
         mv_store: Option<Rc<MvStore>>,
         pager: Rc<Pager>,
     ) -> Result<StepResult> {
+        let connection = self.connection();
+        let (progress_period, timeout) = {
+            let progress = connection.progress.borrow();
+            (progress.period(), state.timeout.unwrap_or(progress.timeout))
+        };
+        if state.deadline.is_none() && !timeout.is_zero() {
+            state.deadline = Some(pager.io.now().add_duration(timeout));
+        }
+        // A commit that started writing the WAL must finish.
+        if state.commit_state == CommitState::Ready
+            && state
+                .deadline
+                .is_some_and(|deadline| pager.io.now() >= deadline)
+        {
+            return self.abort_interrupted(state, &connection);
+        }
         loop {
             if state.is_interrupted() {
                 return Ok(StepResult::Interrupt);
@@ -383,7 +449,23 @@ impl Program {
             trace_insn(self, state.pc as InsnReference, insn);
             let res = insn_function(self, state, insn, &pager, mv_store.as_ref())?;
             match res {
-                InsnFunctionStepResult::Step => {}
+                InsnFunctionStepResult::Step => {
+                    state.n_insns += 1;
+                    if progress_period > 0 && state.n_insns % progress_period == 0 {
+                        // The callback may replace the progress handler of the connection.
+                        let callback = connection.progress.borrow().callback();
+                        if callback.is_some_and(|callback| callback()) {
+                            return self.abort_interrupted(state, &connection);
+                        }
+                    }
+                    if state.n_insns % TIMEOUT_CHECK_PERIOD == 0
+                        && state
+                            .deadline
+                            .is_some_and(|deadline| pager.io.now() >= deadline)
+                    {
+                        return self.abort_interrupted(state, &connection);
+                    }
+                }
                 InsnFunctionStepResult::Done => return Ok(StepResult::Done),
                 InsnFunctionStepResult::IO => return Ok(StepResult::IO),
                 InsnFunctionStepResult::Row => return Ok(StepResult::Row),
@@ -393,6 +475,24 @@ impl Program {
         }
     }
 
+    /// Stops a statement interrupted by the progress handler or its timeout. Like in SQLite,
+    /// the transaction is rolled back if the statement may have written to the database, or
+    /// if it was started by the statement. The statement then stays interrupted until it is
+    /// reset, like after [ProgramState::interrupt].
+    fn abort_interrupted(
+        &self,
+        state: &mut ProgramState,
+        connection: &Arc<Connection>,
+    ) -> Result<StepResult> {
+        if connection.auto_commit.get()
+            || connection.transaction_state.get() == TransactionState::Write
+        {
+            connection.rollback()?;
+        }
+        state.interrupt();
+        Ok(StepResult::Interrupt)
+    }
+
     #[instrument(skip_all, level = Level::TRACE)]
     pub fn commit_txn(
         &self,
@@ -401,7 +501,7 @@ impl Program {
         mv_store: Option<&Rc<MvStore>>,
     ) -> Result<StepResult> {
         if let Some(mv_store) = mv_store {
-            let conn = self.connection.clone();
+            let conn = self.connection();
             let auto_commit = conn.auto_commit.get();
             if auto_commit {
                 let mut mv_transactions = conn.mv_transactions.borrow_mut();
@@ -409,32 +509,52 @@ impl Program {
                     mv_store.commit_tx(*tx_id).unwrap();
                 }
                 mv_transactions.clear();
+                conn.sessions.borrow().commit();
+                conn.commit_temp()?;
             }
             Ok(StepResult::Done)
         } else {
-            let connection = self.connection.clone();
+            let connection = self.connection();
             let auto_commit = connection.auto_commit.get();
             tracing::trace!("Halt auto_commit {}", auto_commit);
             if program_state.commit_state == CommitState::Committing {
-                self.step_end_write_txn(&pager, &mut program_state.commit_state, &connection)
+                self.step_end_write_txn(
+                    &pager,
+                    &mut program_state.commit_state,
+                    program_state.n_change,
+                    &connection,
+                )
             } else if auto_commit {
                 let current_state = connection.transaction_state.get();
                 match current_state {
-                    TransactionState::Write => self.step_end_write_txn(
-                        &pager,
-                        &mut program_state.commit_state,
-                        &connection,
-                    ),
+                    TransactionState::Write => {
+                        if !connection.hooks.borrow().allow_commit() {
+                            connection.rollback()?;
+                            return Err(LimboError::Constraint(
+                                "commit hook requested a rollback".to_string(),
+                            ));
+                        }
+                        self.step_end_write_txn(
+                            &pager,
+                            &mut program_state.commit_state,
+                            program_state.n_change,
+                            &connection,
+                        )
+                    }
                     TransactionState::Read => {
                         connection.transaction_state.replace(TransactionState::None);
                         pager.end_read_tx()?;
+                        connection.commit_temp()?;
+                        Ok(StepResult::Done)
+                    }
+                    TransactionState::None => {
+                        connection.commit_temp()?;
                         Ok(StepResult::Done)
                     }
-                    TransactionState::None => Ok(StepResult::Done),
                 }
             } else {
                 if self.change_cnt_on {
-                    self.connection.set_changes(self.n_change.get());
+                    connection.set_changes(program_state.n_change);
                 }
                 Ok(StepResult::Done)
             }
@@ -446,15 +566,18 @@ impl Program {
         &self,
         pager: &Rc<Pager>,
         commit_state: &mut CommitState,
+        n_change: i64,
         connection: &Connection,
     ) -> Result<StepResult> {
         let cacheflush_status = pager.end_tx()?;
         match cacheflush_status {
             PagerCacheflushStatus::Done(_) => {
                 if self.change_cnt_on {
-                    self.connection.set_changes(self.n_change.get());
+                    connection.set_changes(n_change);
                 }
                 connection.transaction_state.replace(TransactionState::None);
+                connection.sessions.borrow().commit();
+                connection.commit_temp()?;
                 *commit_state = CommitState::Ready;
             }
             PagerCacheflushStatus::IO => {
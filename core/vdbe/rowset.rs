@@ -0,0 +1,48 @@
+use std::collections::BTreeSet;
+
+/// A set of rowids held in a register, used by the RowSetAdd and RowSetTest instructions.
+///
+/// The main user is the multi-index OR access method, which seeks several indexes in turn
+/// and uses a [RowSet] to make sure a row matched by more than one index is only emitted once.
+#[derive(Debug, Clone, Default)]
+pub struct RowSet {
+    entries: BTreeSet<i64>,
+}
+
+impl RowSet {
+    /// Add a rowid to the set. Adding a rowid that is already present is a no-op.
+    pub fn insert(&mut self, rowid: i64) {
+        self.entries.insert(rowid);
+    }
+
+    /// Returns true if the rowid is present in the set.
+    pub fn contains(&self, rowid: i64) -> bool {
+        self.entries.contains(&rowid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RowSet;
+
+    #[test]
+    fn test_rowset_insert_and_contains() {
+        let mut rowset = RowSet::default();
+        assert!(rowset.is_empty());
+        rowset.insert(3);
+        rowset.insert(-1);
+        rowset.insert(3);
+        assert_eq!(rowset.len(), 2);
+        assert!(rowset.contains(3));
+        assert!(rowset.contains(-1));
+        assert!(!rowset.contains(0));
+    }
+}
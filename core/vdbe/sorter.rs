@@ -1,8 +1,12 @@
+use std::sync::Arc;
+
 use limbo_sqlite3_parser::ast::SortOrder;
 
 use crate::{
+    memory::MemoryTracker,
     translate::collate::CollationSeq,
-    types::{compare_immutable, ImmutableRecord, IndexKeySortOrder},
+    types::{compare_immutable, ImmutableRecord, IndexKeySortOrder, RefValue},
+    Result,
 };
 
 pub struct Sorter {
@@ -11,16 +15,26 @@ pub struct Sorter {
     order: IndexKeySortOrder,
     key_len: usize,
     collations: Vec<CollationSeq>,
+    /// Tracker the records are charged to while they are in the sorter.
+    memory: Arc<MemoryTracker>,
+    /// Bytes charged for the records not returned yet.
+    charged: usize,
 }
 
 impl Sorter {
-    pub fn new(order: &[SortOrder], collations: Vec<CollationSeq>) -> Self {
+    pub fn new(
+        order: &[SortOrder],
+        collations: Vec<CollationSeq>,
+        memory: Arc<MemoryTracker>,
+    ) -> Self {
         Self {
             records: Vec::new(),
             current: None,
             key_len: order.len(),
             order: IndexKeySortOrder::from_list(order),
             collations,
+            memory,
+            charged: 0,
         }
     }
     pub fn is_empty(&self) -> bool {
@@ -46,12 +60,33 @@ impl Sorter {
     }
     pub fn next(&mut self) {
         self.current = self.records.pop();
+        if let Some(record) = &self.current {
+            let size = record_size(record);
+            self.charged -= size;
+            self.memory.release(size);
+        }
     }
     pub fn record(&self) -> Option<&ImmutableRecord> {
         self.current.as_ref()
     }
 
-    pub fn insert(&mut self, record: &ImmutableRecord) {
+    /// Adds a record, failing with [crate::LimboError::OutOfMemory] if it does not fit in the
+    /// hard heap limit.
+    pub fn insert(&mut self, record: &ImmutableRecord) -> Result<()> {
+        let size = record_size(record);
+        self.memory.try_allocate(size)?;
+        self.charged += size;
         self.records.push(record.clone());
+        Ok(())
     }
 }
+
+impl Drop for Sorter {
+    fn drop(&mut self) {
+        self.memory.release(self.charged);
+    }
+}
+
+fn record_size(record: &ImmutableRecord) -> usize {
+    record.get_payload().len() + record.values.len() * std::mem::size_of::<RefValue>()
+}
@@ -157,6 +157,7 @@ impl SimValue {
                     None,
                     other.0.to_string().as_str(),
                     self.0.to_string().as_str(),
+                    false,
                 )
             }
             ast::LikeOperator::Match => todo!(),
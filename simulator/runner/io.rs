@@ -1,4 +1,4 @@
-use std::{cell::RefCell, sync::Arc};
+use std::{cell::RefCell, sync::Arc, time::Duration};
 
 use limbo_core::{Clock, Instant, OpenFlags, PlatformIO, Result, IO};
 use rand::{RngCore, SeedableRng};
@@ -12,6 +12,8 @@ pub(crate) struct SimulatorIO {
     pub(crate) files: RefCell<Vec<Arc<SimulatorFile>>>,
     pub(crate) rng: RefCell<ChaCha8Rng>,
     pub(crate) nr_run_once_faults: RefCell<usize>,
+    pub(crate) nr_run_once_calls: RefCell<u64>,
+    pub(crate) slept: RefCell<Duration>,
     pub(crate) page_size: usize,
 }
 
@@ -25,12 +27,15 @@ impl SimulatorIO {
         let files = RefCell::new(Vec::new());
         let rng = RefCell::new(ChaCha8Rng::seed_from_u64(seed));
         let nr_run_once_faults = RefCell::new(0);
+        let nr_run_once_calls = RefCell::new(0);
         Ok(Self {
             inner,
             fault,
             files,
             rng,
             nr_run_once_faults,
+            nr_run_once_calls,
+            slept: RefCell::new(Duration::ZERO),
             page_size,
         })
     }
@@ -52,10 +57,14 @@ impl SimulatorIO {
 
 impl Clock for SimulatorIO {
     fn now(&self) -> Instant {
+        // Simulated time advances by a millisecond on every run_once and by the time slept, so
+        // that busy timeouts expire deterministically.
         Instant {
             secs: 1704067200, // 2024-01-01 00:00:00 UTC
             micros: 0,
         }
+        .add_duration(Duration::from_millis(*self.nr_run_once_calls.borrow()))
+        .add_duration(*self.slept.borrow())
     }
 }
 
@@ -89,6 +98,7 @@ impl IO for SimulatorIO {
     }
 
     fn run_once(&self) -> Result<()> {
+        *self.nr_run_once_calls.borrow_mut() += 1;
         if *self.fault.borrow() {
             *self.nr_run_once_faults.borrow_mut() += 1;
             return Err(limbo_core::LimboError::InternalError(
@@ -103,6 +113,10 @@ impl IO for SimulatorIO {
         self.rng.borrow_mut().next_u64() as i64
     }
 
+    fn sleep(&self, duration: Duration) {
+        *self.slept.borrow_mut() += duration;
+    }
+
     fn get_memory_io(&self) -> Arc<limbo_core::MemoryIO> {
         todo!()
     }
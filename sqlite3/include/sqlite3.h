@@ -13,6 +13,8 @@
 
 #define SQLITE_NOMEM 7
 
+#define SQLITE_READONLY 8
+
 #define SQLITE_INTERRUPT 9
 
 #define SQLITE_NOTFOUND 12
@@ -41,10 +43,28 @@
 
 #define SQLITE_CHECKPOINT_TRUNCATE 3
 
+#define SQLITE_SERIALIZE_NOCOPY 1
+
+#define SQLITE_DESERIALIZE_FREEONCLOSE 1
+
+#define SQLITE_DESERIALIZE_RESIZEABLE 2
+
+#define SQLITE_DESERIALIZE_READONLY 4
+
+#define SQLITE_DELETE 9
+
+#define SQLITE_INSERT 18
+
+#define SQLITE_UPDATE 23
+
 typedef struct sqlite3 sqlite3;
 
 typedef struct sqlite3_stmt sqlite3_stmt;
 
+typedef struct sqlite3_backup sqlite3_backup;
+
+typedef struct sqlite3_blob sqlite3_blob;
+
 typedef int (*exec_callback)(void *context, int n_column, char **argv, char **colv);
 
 #ifdef __cplusplus
@@ -68,9 +88,29 @@ int sqlite3_trace_v2(sqlite3 *_db,
                      void (*_callback)(unsigned int, void*, void*, void*),
                      void *_context);
 
-int sqlite3_progress_handler(sqlite3 *_db, int _n, int (*_callback)(void), void *_context);
+void sqlite3_progress_handler(sqlite3 *db, int n, int (*callback)(void *), void *context);
 
-int sqlite3_busy_timeout(sqlite3 *_db, int _ms);
+int64_t sqlite3_soft_heap_limit64(int64_t n);
+
+int64_t sqlite3_hard_heap_limit64(int64_t n);
+
+int64_t sqlite3_memory_used(void);
+
+int64_t sqlite3_memory_highwater(int reset);
+
+int sqlite3_db_release_memory(sqlite3 *db);
+
+int sqlite3_busy_timeout(sqlite3 *db, int ms);
+
+int sqlite3_busy_handler(sqlite3 *db, int (*callback)(void *, int), void *context);
+
+void *sqlite3_update_hook(sqlite3 *db,
+                          void (*callback)(void *, int, const char *, const char *, int64_t),
+                          void *context);
+
+void *sqlite3_commit_hook(sqlite3 *db, int (*callback)(void *), void *context);
+
+void *sqlite3_rollback_hook(sqlite3 *db, void (*callback)(void *), void *context);
 
 int sqlite3_set_authorizer(sqlite3 *_db, int (*_callback)(void), void *_context);
 
@@ -92,9 +132,14 @@ int sqlite3_stmt_readonly(sqlite3_stmt *_stmt);
 
 int sqlite3_stmt_busy(sqlite3_stmt *_stmt);
 
-int sqlite3_serialize(sqlite3 *_db, const char *_schema, void **_out, int *_out_bytes, unsigned int _flags);
+unsigned char *sqlite3_serialize(sqlite3 *db, const char *schema, int64_t *out_size, unsigned int flags);
 
-int sqlite3_deserialize(sqlite3 *_db, const char *_schema, const void *_in_, int _in_bytes, unsigned int _flags);
+int sqlite3_deserialize(sqlite3 *db,
+                        const char *schema,
+                        unsigned char *data,
+                        int64_t db_size,
+                        int64_t _buf_size,
+                        unsigned int flags);
 
 int sqlite3_get_autocommit(sqlite3 *_db);
 
@@ -112,9 +157,9 @@ void sqlite3_sleep(int _ms);
 
 int sqlite3_limit(sqlite3 *_db, int _id, int _new_value);
 
-void *sqlite3_malloc64(int _n);
+void *sqlite3_malloc64(uint64_t n);
 
-void sqlite3_free(void *_ptr);
+void sqlite3_free(void *ptr);
 
 int sqlite3_errcode(sqlite3 *_db);
 
@@ -122,15 +167,18 @@ const char *sqlite3_errstr(int _err);
 
 void *sqlite3_user_data(void *_context);
 
-void *sqlite3_backup_init(sqlite3 *_dest_db, const char *_dest_name, sqlite3 *_source_db, const char *_source_name);
+sqlite3_backup *sqlite3_backup_init(sqlite3 *dest_db,
+                                    const char *dest_name,
+                                    sqlite3 *source_db,
+                                    const char *source_name);
 
-int sqlite3_backup_step(void *_backup, int _n_pages);
+int sqlite3_backup_step(sqlite3_backup *backup, int n_pages);
 
-int sqlite3_backup_remaining(void *_backup);
+int sqlite3_backup_remaining(sqlite3_backup *backup);
 
-int sqlite3_backup_pagecount(void *_backup);
+int sqlite3_backup_pagecount(sqlite3_backup *backup);
 
-int sqlite3_backup_finish(void *_backup);
+int sqlite3_backup_finish(sqlite3_backup *backup);
 
 char *sqlite3_expanded_sql(sqlite3_stmt *_stmt);
 
@@ -204,21 +252,23 @@ void sqlite3_result_error(void *_context, const char *_err, int _len);
 
 void *sqlite3_aggregate_context(void *_context, int _n);
 
-int sqlite3_blob_open(sqlite3 *_db,
-                      const char *_db_name,
-                      const char *_table_name,
-                      const char *_column_name,
-                      int64_t _rowid,
-                      int _flags,
-                      void **_blob_out);
+int sqlite3_blob_open(sqlite3 *db,
+                      const char *db_name,
+                      const char *table_name,
+                      const char *column_name,
+                      int64_t rowid,
+                      int flags,
+                      sqlite3_blob **blob_out);
+
+int sqlite3_blob_read(sqlite3_blob *blob, void *data, int n, int offset);
 
-int sqlite3_blob_read(void *_blob, void *_data, int _n, int _offset);
+int sqlite3_blob_write(sqlite3_blob *blob, const void *data, int n, int offset);
 
-int sqlite3_blob_write(void *_blob, const void *_data, int _n, int _offset);
+int sqlite3_blob_reopen(sqlite3_blob *blob, int64_t rowid);
 
-int sqlite3_blob_bytes(void *_blob);
+int sqlite3_blob_bytes(sqlite3_blob *blob);
 
-int sqlite3_blob_close(void *_blob);
+int sqlite3_blob_close(sqlite3_blob *blob);
 
 int sqlite3_stricmp(const char *_a, const char *_b);
 
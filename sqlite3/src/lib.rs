@@ -6,6 +6,7 @@ use std::ffi::{self, CStr, CString};
 use tracing::trace;
 
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 macro_rules! stub {
     () => {
@@ -18,8 +19,10 @@ pub const SQLITE_ERROR: ffi::c_int = 1;
 pub const SQLITE_ABORT: ffi::c_int = 4;
 pub const SQLITE_BUSY: ffi::c_int = 5;
 pub const SQLITE_NOMEM: ffi::c_int = 7;
+pub const SQLITE_READONLY: ffi::c_int = 8;
 pub const SQLITE_INTERRUPT: ffi::c_int = 9;
 pub const SQLITE_NOTFOUND: ffi::c_int = 12;
+pub const SQLITE_FULL: ffi::c_int = 13;
 pub const SQLITE_CANTOPEN: ffi::c_int = 14;
 pub const SQLITE_MISUSE: ffi::c_int = 21;
 pub const SQLITE_ROW: ffi::c_int = 100;
@@ -34,6 +37,16 @@ pub const SQLITE_CHECKPOINT_FULL: ffi::c_int = 1;
 pub const SQLITE_CHECKPOINT_RESTART: ffi::c_int = 2;
 pub const SQLITE_CHECKPOINT_TRUNCATE: ffi::c_int = 3;
 
+pub const SQLITE_SERIALIZE_NOCOPY: ffi::c_uint = 0x001;
+
+pub const SQLITE_DESERIALIZE_FREEONCLOSE: ffi::c_uint = 1;
+pub const SQLITE_DESERIALIZE_RESIZEABLE: ffi::c_uint = 2;
+pub const SQLITE_DESERIALIZE_READONLY: ffi::c_uint = 4;
+
+pub const SQLITE_DELETE: ffi::c_int = 9;
+pub const SQLITE_INSERT: ffi::c_int = 18;
+pub const SQLITE_UPDATE: ffi::c_int = 23;
+
 pub struct sqlite3 {
     pub(crate) inner: Arc<Mutex<sqlite3Inner>>,
 }
@@ -47,6 +60,9 @@ struct sqlite3Inner {
     pub(crate) malloc_failed: bool,
     pub(crate) e_open_state: u8,
     pub(crate) p_err: *mut ffi::c_void,
+    pub(crate) update_hook_context: *mut ffi::c_void,
+    pub(crate) commit_hook_context: *mut ffi::c_void,
+    pub(crate) rollback_hook_context: *mut ffi::c_void,
 }
 
 impl sqlite3 {
@@ -64,6 +80,9 @@ impl sqlite3 {
             malloc_failed: false,
             e_open_state: SQLITE_STATE_OPEN,
             p_err: std::ptr::null_mut(),
+            update_hook_context: std::ptr::null_mut(),
+            commit_hook_context: std::ptr::null_mut(),
+            rollback_hook_context: std::ptr::null_mut(),
         };
         #[allow(clippy::arc_with_non_send_sync)]
         let inner = Arc::new(Mutex::new(inner));
@@ -82,6 +101,32 @@ impl sqlite3_stmt {
     }
 }
 
+pub struct sqlite3_backup {
+    pub(crate) backup: limbo_core::Backup,
+    /// Result code of the last call to `sqlite3_backup_step()`.
+    pub(crate) rc: ffi::c_int,
+}
+
+impl sqlite3_backup {
+    pub fn new(backup: limbo_core::Backup) -> Self {
+        Self {
+            backup,
+            rc: SQLITE_OK,
+        }
+    }
+}
+
+pub struct sqlite3_blob {
+    pub(crate) db: *mut sqlite3,
+    pub(crate) blob: limbo_core::Blob,
+}
+
+impl sqlite3_blob {
+    pub fn new(db: *mut sqlite3, blob: limbo_core::Blob) -> Self {
+        Self { db, blob }
+    }
+}
+
 static INIT_DONE: std::sync::Once = std::sync::Once::new();
 
 #[no_mangle]
@@ -180,17 +225,161 @@ pub unsafe extern "C" fn sqlite3_trace_v2(
 
 #[no_mangle]
 pub unsafe extern "C" fn sqlite3_progress_handler(
-    _db: *mut sqlite3,
-    _n: ffi::c_int,
-    _callback: Option<unsafe extern "C" fn() -> ffi::c_int>,
-    _context: *mut ffi::c_void,
+    db: *mut sqlite3,
+    n: ffi::c_int,
+    callback: Option<unsafe extern "C" fn(*mut ffi::c_void) -> ffi::c_int>,
+    context: *mut ffi::c_void,
+) {
+    if db.is_null() {
+        return;
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let db = db.inner.lock().unwrap();
+    let callback = callback.map(|callback| {
+        Box::new(move || callback(context) != 0) as Box<limbo_core::ProgressCallback>
+    });
+    db.conn.progress_handler(n.max(0) as u64, callback);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_soft_heap_limit64(n: i64) -> i64 {
+    let memory = limbo_core::MemoryTracker::global();
+    if n < 0 {
+        return memory.soft_limit() as i64;
+    }
+    memory.set_soft_limit(n as usize) as i64
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_hard_heap_limit64(n: i64) -> i64 {
+    let memory = limbo_core::MemoryTracker::global();
+    if n < 0 {
+        return memory.hard_limit() as i64;
+    }
+    memory.set_hard_limit(n as usize) as i64
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_memory_used() -> i64 {
+    limbo_core::MemoryTracker::global().used() as i64
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_memory_highwater(reset: ffi::c_int) -> i64 {
+    limbo_core::MemoryTracker::global().highwater(reset != 0) as i64
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_db_release_memory(db: *mut sqlite3) -> ffi::c_int {
+    if db.is_null() {
+        return SQLITE_MISUSE;
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let db = db.inner.lock().unwrap();
+    db.conn.shrink_memory();
+    SQLITE_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_busy_timeout(db: *mut sqlite3, ms: ffi::c_int) -> ffi::c_int {
+    if db.is_null() {
+        return SQLITE_MISUSE;
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let db = db.inner.lock().unwrap();
+    db.conn
+        .busy_timeout(Duration::from_millis(ms.max(0) as u64));
+    SQLITE_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_busy_handler(
+    db: *mut sqlite3,
+    callback: Option<unsafe extern "C" fn(*mut ffi::c_void, ffi::c_int) -> ffi::c_int>,
+    context: *mut ffi::c_void,
 ) -> ffi::c_int {
-    stub!();
+    if db.is_null() {
+        return SQLITE_MISUSE;
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let db = db.inner.lock().unwrap();
+    let callback = callback.map(|callback| {
+        Box::new(move |count: u32| callback(context, count as ffi::c_int) != 0)
+            as Box<limbo_core::BusyCallback>
+    });
+    db.conn.busy_handler(callback);
+    SQLITE_OK
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sqlite3_busy_timeout(_db: *mut sqlite3, _ms: ffi::c_int) -> ffi::c_int {
-    stub!();
+pub unsafe extern "C" fn sqlite3_update_hook(
+    db: *mut sqlite3,
+    callback: Option<
+        unsafe extern "C" fn(
+            *mut ffi::c_void,
+            ffi::c_int,
+            *const ffi::c_char,
+            *const ffi::c_char,
+            i64,
+        ),
+    >,
+    context: *mut ffi::c_void,
+) -> *mut ffi::c_void {
+    if db.is_null() {
+        return std::ptr::null_mut();
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let mut db = db.inner.lock().unwrap();
+    let hook = callback.map(|callback| {
+        Box::new(
+            move |kind: limbo_core::UpdateKind, db_name: &str, table_name: &str, rowid: i64| {
+                let op = match kind {
+                    limbo_core::UpdateKind::Insert => SQLITE_INSERT,
+                    limbo_core::UpdateKind::Update => SQLITE_UPDATE,
+                    limbo_core::UpdateKind::Delete => SQLITE_DELETE,
+                };
+                let db_name = CString::new(db_name).unwrap();
+                let table_name = CString::new(table_name).unwrap();
+                callback(context, op, db_name.as_ptr(), table_name.as_ptr(), rowid);
+            },
+        ) as Box<limbo_core::UpdateHook>
+    });
+    db.conn.update_hook(hook);
+    std::mem::replace(&mut db.update_hook_context, context)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_commit_hook(
+    db: *mut sqlite3,
+    callback: Option<unsafe extern "C" fn(*mut ffi::c_void) -> ffi::c_int>,
+    context: *mut ffi::c_void,
+) -> *mut ffi::c_void {
+    if db.is_null() {
+        return std::ptr::null_mut();
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let mut db = db.inner.lock().unwrap();
+    let hook = callback
+        .map(|callback| Box::new(move || callback(context) != 0) as Box<limbo_core::CommitHook>);
+    db.conn.commit_hook(hook);
+    std::mem::replace(&mut db.commit_hook_context, context)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_rollback_hook(
+    db: *mut sqlite3,
+    callback: Option<unsafe extern "C" fn(*mut ffi::c_void)>,
+    context: *mut ffi::c_void,
+) -> *mut ffi::c_void {
+    if db.is_null() {
+        return std::ptr::null_mut();
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let mut db = db.inner.lock().unwrap();
+    let hook = callback
+        .map(|callback| Box::new(move || callback(context)) as Box<limbo_core::RollbackHook>);
+    db.conn.rollback_hook(hook);
+    std::mem::replace(&mut db.rollback_hook_context, context)
 }
 
 #[no_mangle]
@@ -248,8 +437,8 @@ pub unsafe extern "C" fn sqlite3_step(stmt: *mut sqlite3_stmt) -> ffi::c_int {
     let db = &mut *stmt.db;
     loop {
         let db = db.inner.lock().unwrap();
-        if let Ok(result) = stmt.stmt.step() {
-            match result {
+        match stmt.stmt.step() {
+            Ok(result) => match result {
                 limbo_core::StepResult::IO => {
                     let io = db.io.clone();
                     io.run_once().unwrap();
@@ -259,9 +448,10 @@ pub unsafe extern "C" fn sqlite3_step(stmt: *mut sqlite3_stmt) -> ffi::c_int {
                 limbo_core::StepResult::Interrupt => return SQLITE_INTERRUPT,
                 limbo_core::StepResult::Row => return SQLITE_ROW,
                 limbo_core::StepResult::Busy => return SQLITE_BUSY,
-            }
-        } else {
-            return SQLITE_ERROR;
+            },
+            Err(limbo_core::LimboError::OutOfMemory) => return SQLITE_NOMEM,
+            Err(limbo_core::LimboError::DatabaseFull) => return SQLITE_FULL,
+            Err(_) => return SQLITE_ERROR,
         }
     }
 }
@@ -322,26 +512,93 @@ pub unsafe extern "C" fn sqlite3_stmt_busy(_stmt: *mut sqlite3_stmt) -> ffi::c_i
     stub!();
 }
 
+/// Returns the content of the database as a buffer with the layout of a database file.
+///
+/// # Returns
+///
+/// A buffer obtained from `sqlite3_malloc64()` that the caller must release with
+/// `sqlite3_free()`, or NULL on failure. Since the database is not stored in contiguous
+/// memory, NULL is also returned when `SQLITE_SERIALIZE_NOCOPY` is set.
 #[no_mangle]
 pub unsafe extern "C" fn sqlite3_serialize(
-    _db: *mut sqlite3,
-    _schema: *const ffi::c_char,
-    _out: *mut *mut ffi::c_void,
-    _out_bytes: *mut ffi::c_int,
-    _flags: ffi::c_uint,
-) -> ffi::c_int {
-    stub!();
+    db: *mut sqlite3,
+    schema: *const ffi::c_char,
+    out_size: *mut i64,
+    flags: ffi::c_uint,
+) -> *mut ffi::c_uchar {
+    if db.is_null() || !is_main_schema(schema) {
+        return std::ptr::null_mut();
+    }
+    if flags & SQLITE_SERIALIZE_NOCOPY != 0 {
+        return std::ptr::null_mut();
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let mut db = db.inner.lock().unwrap();
+    let image = match db.conn.serialize() {
+        Ok(image) => image,
+        Err(e) => {
+            trace!("error serializing database: {:?}", e);
+            db.err_code = SQLITE_ERROR;
+            return std::ptr::null_mut();
+        }
+    };
+    let out = sqlite3_malloc64(image.len() as u64) as *mut ffi::c_uchar;
+    if out.is_null() {
+        db.malloc_failed = true;
+        return std::ptr::null_mut();
+    }
+    std::ptr::copy_nonoverlapping(image.as_ptr(), out, image.len());
+    if !out_size.is_null() {
+        *out_size = image.len() as i64;
+    }
+    out
 }
 
+/// Replaces the database of the connection with an in-memory copy of `data`.
+///
+/// The first `db_size` bytes of `data` must hold a database image such as the one returned by
+/// `sqlite3_serialize()`. With `SQLITE_DESERIALIZE_FREEONCLOSE`, `data` is released with
+/// `sqlite3_free()` once it has been copied.
 #[no_mangle]
 pub unsafe extern "C" fn sqlite3_deserialize(
-    _db: *mut sqlite3,
-    _schema: *const ffi::c_char,
-    _in_: *const ffi::c_void,
-    _in_bytes: ffi::c_int,
-    _flags: ffi::c_uint,
+    db: *mut sqlite3,
+    schema: *const ffi::c_char,
+    data: *mut ffi::c_uchar,
+    db_size: i64,
+    _buf_size: i64,
+    flags: ffi::c_uint,
 ) -> ffi::c_int {
-    stub!();
+    if db.is_null() || data.is_null() || db_size < 0 {
+        return SQLITE_MISUSE;
+    }
+    let result = if is_main_schema(schema) {
+        let image = std::slice::from_raw_parts(data, db_size as usize);
+        limbo_core::Database::deserialize(image)
+    } else {
+        Err(limbo_core::LimboError::InvalidArgument(
+            "only the main schema can be deserialized".to_string(),
+        ))
+    };
+    if flags & SQLITE_DESERIALIZE_FREEONCLOSE != 0 {
+        sqlite3_free(data as *mut ffi::c_void);
+    }
+    let db: &mut sqlite3 = &mut *db;
+    let mut db = db.inner.lock().unwrap();
+    let conn = result.and_then(|(io, database)| Ok((io, database.connect()?, database)));
+    match conn {
+        Ok((io, conn, database)) => {
+            db.io = io;
+            db._db = database;
+            db.conn = conn;
+            db.err_code = SQLITE_OK;
+            SQLITE_OK
+        }
+        Err(e) => {
+            trace!("error deserializing database: {:?}", e);
+            db.err_code = SQLITE_ERROR;
+            SQLITE_ERROR
+        }
+    }
 }
 
 #[no_mangle]
@@ -389,13 +646,16 @@ pub unsafe extern "C" fn sqlite3_limit(
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sqlite3_malloc64(_n: ffi::c_int) -> *mut ffi::c_void {
-    stub!();
+pub unsafe extern "C" fn sqlite3_malloc64(n: u64) -> *mut ffi::c_void {
+    if n == 0 {
+        return std::ptr::null_mut();
+    }
+    libc::malloc(n as usize)
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sqlite3_free(_ptr: *mut ffi::c_void) {
-    stub!();
+pub unsafe extern "C" fn sqlite3_free(ptr: *mut ffi::c_void) {
+    libc::free(ptr);
 }
 
 /// Returns the error code for the most recent failed API call to connection.
@@ -427,35 +687,88 @@ pub unsafe extern "C" fn sqlite3_user_data(_context: *mut ffi::c_void) -> *mut f
 
 #[no_mangle]
 pub unsafe extern "C" fn sqlite3_backup_init(
-    _dest_db: *mut sqlite3,
-    _dest_name: *const ffi::c_char,
-    _source_db: *mut sqlite3,
-    _source_name: *const ffi::c_char,
-) -> *mut ffi::c_void {
-    stub!();
+    dest_db: *mut sqlite3,
+    dest_name: *const ffi::c_char,
+    source_db: *mut sqlite3,
+    source_name: *const ffi::c_char,
+) -> *mut sqlite3_backup {
+    if dest_db.is_null() || source_db.is_null() || std::ptr::eq(dest_db, source_db) {
+        return std::ptr::null_mut();
+    }
+    if !is_main_schema(dest_name) || !is_main_schema(source_name) {
+        return std::ptr::null_mut();
+    }
+    let mut dest = (*dest_db).inner.lock().unwrap();
+    let source = (*source_db).inner.lock().unwrap();
+    match limbo_core::Backup::new(&source.conn, &dest.conn) {
+        Ok(backup) => {
+            dest.err_code = SQLITE_OK;
+            Box::leak(Box::new(sqlite3_backup::new(backup)))
+        }
+        Err(e) => {
+            trace!("error initializing backup: {:?}", e);
+            dest.err_code = SQLITE_ERROR;
+            std::ptr::null_mut()
+        }
+    }
+}
+
+unsafe fn is_main_schema(name: *const ffi::c_char) -> bool {
+    name.is_null() || CStr::from_ptr(name).to_bytes() == b"main"
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn sqlite3_backup_step(
-    _backup: *mut ffi::c_void,
-    _n_pages: ffi::c_int,
+    backup: *mut sqlite3_backup,
+    n_pages: ffi::c_int,
 ) -> ffi::c_int {
-    stub!();
+    if backup.is_null() {
+        return SQLITE_MISUSE;
+    }
+    let backup = &mut *backup;
+    let max_pages = if n_pages < 0 {
+        None
+    } else {
+        Some(n_pages as usize)
+    };
+    backup.rc = match backup.backup.step(max_pages) {
+        Ok(limbo_core::BackupStepResult::More) => SQLITE_OK,
+        Ok(limbo_core::BackupStepResult::Done) => SQLITE_DONE,
+        Ok(limbo_core::BackupStepResult::Busy) => SQLITE_BUSY,
+        Err(e) => {
+            trace!("error during backup step: {:?}", e);
+            SQLITE_ERROR
+        }
+    };
+    backup.rc
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sqlite3_backup_remaining(_backup: *mut ffi::c_void) -> ffi::c_int {
-    stub!();
+pub unsafe extern "C" fn sqlite3_backup_remaining(backup: *mut sqlite3_backup) -> ffi::c_int {
+    if backup.is_null() {
+        return 0;
+    }
+    (*backup).backup.remaining() as ffi::c_int
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sqlite3_backup_pagecount(_backup: *mut ffi::c_void) -> ffi::c_int {
-    stub!();
+pub unsafe extern "C" fn sqlite3_backup_pagecount(backup: *mut sqlite3_backup) -> ffi::c_int {
+    if backup.is_null() {
+        return 0;
+    }
+    (*backup).backup.pagecount() as ffi::c_int
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sqlite3_backup_finish(_backup: *mut ffi::c_void) -> ffi::c_int {
-    stub!();
+pub unsafe extern "C" fn sqlite3_backup_finish(backup: *mut sqlite3_backup) -> ffi::c_int {
+    if backup.is_null() {
+        return SQLITE_OK;
+    }
+    let backup = Box::from_raw(backup);
+    match backup.rc {
+        SQLITE_OK | SQLITE_DONE | SQLITE_BUSY => SQLITE_OK,
+        rc => rc,
+    }
 }
 
 #[no_mangle]
@@ -861,45 +1174,148 @@ pub unsafe extern "C" fn sqlite3_aggregate_context(
 
 #[no_mangle]
 pub unsafe extern "C" fn sqlite3_blob_open(
-    _db: *mut sqlite3,
-    _db_name: *const ffi::c_char,
-    _table_name: *const ffi::c_char,
-    _column_name: *const ffi::c_char,
-    _rowid: i64,
-    _flags: ffi::c_int,
-    _blob_out: *mut *mut ffi::c_void,
+    db: *mut sqlite3,
+    db_name: *const ffi::c_char,
+    table_name: *const ffi::c_char,
+    column_name: *const ffi::c_char,
+    rowid: i64,
+    flags: ffi::c_int,
+    blob_out: *mut *mut sqlite3_blob,
 ) -> ffi::c_int {
-    stub!();
+    if blob_out.is_null() {
+        return SQLITE_MISUSE;
+    }
+    *blob_out = std::ptr::null_mut();
+    if db.is_null() || table_name.is_null() || column_name.is_null() {
+        return SQLITE_MISUSE;
+    }
+    let mut inner = (*db).inner.lock().unwrap();
+    if !is_main_schema(db_name) {
+        inner.err_code = SQLITE_ERROR;
+        return SQLITE_ERROR;
+    }
+    let (Ok(table_name), Ok(column_name)) = (
+        CStr::from_ptr(table_name).to_str(),
+        CStr::from_ptr(column_name).to_str(),
+    ) else {
+        return SQLITE_MISUSE;
+    };
+    match inner
+        .conn
+        .blob_open(table_name, column_name, rowid, flags != 0)
+    {
+        Ok(blob) => {
+            inner.err_code = SQLITE_OK;
+            *blob_out = Box::leak(Box::new(sqlite3_blob::new(db, blob)));
+            SQLITE_OK
+        }
+        Err(e) => {
+            trace!("error opening blob: {:?}", e);
+            inner.err_code = blob_error_code(&e);
+            inner.err_code
+        }
+    }
+}
+
+fn blob_error_code(e: &limbo_core::LimboError) -> ffi::c_int {
+    match e {
+        limbo_core::LimboError::BlobExpired => SQLITE_ABORT,
+        limbo_core::LimboError::ReadOnly => SQLITE_READONLY,
+        limbo_core::LimboError::Busy => SQLITE_BUSY,
+        _ => SQLITE_ERROR,
+    }
+}
+
+/// Records the result of an operation on `blob` as the error code of its database.
+unsafe fn blob_result(blob: &sqlite3_blob, result: limbo_core::Result<()>) -> ffi::c_int {
+    let rc = match result {
+        Ok(()) => SQLITE_OK,
+        Err(e) => {
+            trace!("blob error: {:?}", e);
+            blob_error_code(&e)
+        }
+    };
+    (*blob.db).inner.lock().unwrap().err_code = rc;
+    rc
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn sqlite3_blob_read(
-    _blob: *mut ffi::c_void,
-    _data: *mut ffi::c_void,
-    _n: ffi::c_int,
-    _offset: ffi::c_int,
+    blob: *mut sqlite3_blob,
+    data: *mut ffi::c_void,
+    n: ffi::c_int,
+    offset: ffi::c_int,
 ) -> ffi::c_int {
-    stub!();
+    if blob.is_null() || (data.is_null() && n > 0) {
+        return SQLITE_MISUSE;
+    }
+    let blob = &*blob;
+    if n < 0 || offset < 0 {
+        return blob_result(
+            blob,
+            Err(limbo_core::LimboError::InvalidArgument(
+                "negative size or offset".to_string(),
+            )),
+        );
+    }
+    if n == 0 {
+        return blob_result(blob, blob.blob.read_at(&mut [], offset as usize));
+    }
+    let buf = std::slice::from_raw_parts_mut(data as *mut u8, n as usize);
+    blob_result(blob, blob.blob.read_at(buf, offset as usize))
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn sqlite3_blob_write(
-    _blob: *mut ffi::c_void,
-    _data: *const ffi::c_void,
-    _n: ffi::c_int,
-    _offset: ffi::c_int,
+    blob: *mut sqlite3_blob,
+    data: *const ffi::c_void,
+    n: ffi::c_int,
+    offset: ffi::c_int,
 ) -> ffi::c_int {
-    stub!();
+    if blob.is_null() || (data.is_null() && n > 0) {
+        return SQLITE_MISUSE;
+    }
+    let blob = &*blob;
+    if n < 0 || offset < 0 {
+        return blob_result(
+            blob,
+            Err(limbo_core::LimboError::InvalidArgument(
+                "negative size or offset".to_string(),
+            )),
+        );
+    }
+    if n == 0 {
+        return blob_result(blob, blob.blob.write_at(&[], offset as usize));
+    }
+    let data = std::slice::from_raw_parts(data as *const u8, n as usize);
+    blob_result(blob, blob.blob.write_at(data, offset as usize))
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sqlite3_blob_bytes(_blob: *mut ffi::c_void) -> ffi::c_int {
-    stub!();
+pub unsafe extern "C" fn sqlite3_blob_reopen(blob: *mut sqlite3_blob, rowid: i64) -> ffi::c_int {
+    if blob.is_null() {
+        return SQLITE_MISUSE;
+    }
+    let blob = &mut *blob;
+    let result = blob.blob.reopen(rowid);
+    blob_result(blob, result)
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn sqlite3_blob_close(_blob: *mut ffi::c_void) -> ffi::c_int {
-    stub!();
+pub unsafe extern "C" fn sqlite3_blob_bytes(blob: *mut sqlite3_blob) -> ffi::c_int {
+    if blob.is_null() {
+        return 0;
+    }
+    (*blob).blob.size() as ffi::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sqlite3_blob_close(blob: *mut sqlite3_blob) -> ffi::c_int {
+    if blob.is_null() {
+        return SQLITE_OK;
+    }
+    drop(Box::from_raw(blob));
+    SQLITE_OK
 }
 
 #[no_mangle]
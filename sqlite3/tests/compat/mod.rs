@@ -1,6 +1,7 @@
 #![allow(non_camel_case_types)]
 #![allow(dead_code)]
 
+use std::ffi::CStr;
 use std::ptr;
 
 #[repr(C)]
@@ -13,6 +14,16 @@ struct sqlite3_stmt {
     _private: [u8; 0],
 }
 
+#[repr(C)]
+struct sqlite3_backup {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct sqlite3_blob {
+    _private: [u8; 0],
+}
+
 #[cfg_attr(not(feature = "sqlite3"), link(name = "limbo_sqlite3"))]
 #[cfg_attr(feature = "sqlite3", link(name = "sqlite3"))]
 extern "C" {
@@ -29,6 +40,45 @@ extern "C" {
     ) -> i32;
     fn sqlite3_step(stmt: *mut sqlite3_stmt) -> i32;
     fn sqlite3_finalize(stmt: *mut sqlite3_stmt) -> i32;
+    fn sqlite3_busy_timeout(db: *mut sqlite3, ms: i32) -> i32;
+    fn sqlite3_busy_handler(
+        db: *mut sqlite3,
+        callback: Option<unsafe extern "C" fn(*mut libc::c_void, i32) -> i32>,
+        context: *mut libc::c_void,
+    ) -> i32;
+    fn sqlite3_progress_handler(
+        db: *mut sqlite3,
+        n: i32,
+        callback: Option<unsafe extern "C" fn(*mut libc::c_void) -> i32>,
+        context: *mut libc::c_void,
+    );
+    fn sqlite3_soft_heap_limit64(n: i64) -> i64;
+    fn sqlite3_memory_used() -> i64;
+    fn sqlite3_memory_highwater(reset: i32) -> i64;
+    fn sqlite3_db_release_memory(db: *mut sqlite3) -> i32;
+    fn sqlite3_update_hook(
+        db: *mut sqlite3,
+        callback: Option<
+            unsafe extern "C" fn(
+                *mut libc::c_void,
+                i32,
+                *const libc::c_char,
+                *const libc::c_char,
+                i64,
+            ),
+        >,
+        context: *mut libc::c_void,
+    ) -> *mut libc::c_void;
+    fn sqlite3_commit_hook(
+        db: *mut sqlite3,
+        callback: Option<unsafe extern "C" fn(*mut libc::c_void) -> i32>,
+        context: *mut libc::c_void,
+    ) -> *mut libc::c_void;
+    fn sqlite3_rollback_hook(
+        db: *mut sqlite3,
+        callback: Option<unsafe extern "C" fn(*mut libc::c_void)>,
+        context: *mut libc::c_void,
+    ) -> *mut libc::c_void;
     fn sqlite3_wal_checkpoint(db: *mut sqlite3, db_name: *const libc::c_char) -> i32;
     fn sqlite3_wal_checkpoint_v2(
         db: *mut sqlite3,
@@ -37,6 +87,55 @@ extern "C" {
         log_size: *mut i32,
         checkpoint_count: *mut i32,
     ) -> i32;
+    fn sqlite3_backup_init(
+        dest_db: *mut sqlite3,
+        dest_name: *const libc::c_char,
+        source_db: *mut sqlite3,
+        source_name: *const libc::c_char,
+    ) -> *mut sqlite3_backup;
+    fn sqlite3_backup_step(backup: *mut sqlite3_backup, n_pages: i32) -> i32;
+    fn sqlite3_backup_remaining(backup: *mut sqlite3_backup) -> i32;
+    fn sqlite3_backup_pagecount(backup: *mut sqlite3_backup) -> i32;
+    fn sqlite3_backup_finish(backup: *mut sqlite3_backup) -> i32;
+    fn sqlite3_serialize(
+        db: *mut sqlite3,
+        schema: *const libc::c_char,
+        out_size: *mut i64,
+        flags: u32,
+    ) -> *mut u8;
+    fn sqlite3_deserialize(
+        db: *mut sqlite3,
+        schema: *const libc::c_char,
+        data: *mut u8,
+        db_size: i64,
+        buf_size: i64,
+        flags: u32,
+    ) -> i32;
+    fn sqlite3_free(ptr: *mut libc::c_void);
+    fn sqlite3_blob_open(
+        db: *mut sqlite3,
+        db_name: *const libc::c_char,
+        table_name: *const libc::c_char,
+        column_name: *const libc::c_char,
+        rowid: i64,
+        flags: i32,
+        blob_out: *mut *mut sqlite3_blob,
+    ) -> i32;
+    fn sqlite3_blob_read(
+        blob: *mut sqlite3_blob,
+        data: *mut libc::c_void,
+        n: i32,
+        offset: i32,
+    ) -> i32;
+    fn sqlite3_blob_write(
+        blob: *mut sqlite3_blob,
+        data: *const libc::c_void,
+        n: i32,
+        offset: i32,
+    ) -> i32;
+    fn sqlite3_blob_reopen(blob: *mut sqlite3_blob, rowid: i64) -> i32;
+    fn sqlite3_blob_bytes(blob: *mut sqlite3_blob) -> i32;
+    fn sqlite3_blob_close(blob: *mut sqlite3_blob) -> i32;
     fn libsql_wal_frame_count(db: *mut sqlite3, p_frame_count: *mut u32) -> i32;
     fn libsql_wal_get_frame(
         db: *mut sqlite3,
@@ -47,7 +146,10 @@ extern "C" {
 }
 
 const SQLITE_OK: i32 = 0;
+const SQLITE_ERROR: i32 = 1;
+const SQLITE_INTERRUPT: i32 = 9;
 const SQLITE_CANTOPEN: i32 = 14;
+const SQLITE_ROW: i32 = 100;
 const SQLITE_DONE: i32 = 101;
 
 const SQLITE_CHECKPOINT_PASSIVE: i32 = 0;
@@ -55,6 +157,12 @@ const SQLITE_CHECKPOINT_FULL: i32 = 1;
 const SQLITE_CHECKPOINT_RESTART: i32 = 2;
 const SQLITE_CHECKPOINT_TRUNCATE: i32 = 3;
 
+const SQLITE_DESERIALIZE_FREEONCLOSE: u32 = 1;
+
+const SQLITE_DELETE: i32 = 9;
+const SQLITE_INSERT: i32 = 18;
+const SQLITE_UPDATE: i32 = 23;
+
 #[cfg(not(target_os = "windows"))]
 mod tests {
     use super::*;
@@ -125,6 +233,203 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_busy_timeout_and_handler() {
+        unsafe extern "C" fn busy_callback(_context: *mut libc::c_void, count: i32) -> i32 {
+            (count < 3) as i32
+        }
+
+        unsafe {
+            let mut db = ptr::null_mut();
+            assert_eq!(
+                sqlite3_open(c"../testing/testing_clone.db".as_ptr(), &mut db),
+                SQLITE_OK
+            );
+            assert_eq!(sqlite3_busy_timeout(db, 100), SQLITE_OK);
+            assert_eq!(sqlite3_busy_timeout(db, 0), SQLITE_OK);
+            assert_eq!(
+                sqlite3_busy_handler(db, Some(busy_callback), ptr::null_mut()),
+                SQLITE_OK
+            );
+            assert_eq!(sqlite3_busy_handler(db, None, ptr::null_mut()), SQLITE_OK);
+            assert_eq!(sqlite3_close(db), SQLITE_OK);
+        }
+    }
+
+    #[test]
+    fn test_progress_handler() {
+        unsafe extern "C" fn progress_callback(context: *mut libc::c_void) -> i32 {
+            let calls = &mut *(context as *mut i32);
+            *calls += 1;
+            (*calls >= 3) as i32
+        }
+
+        unsafe {
+            let mut db = ptr::null_mut();
+            assert_eq!(sqlite3_open(c":memory:".as_ptr(), &mut db), SQLITE_OK);
+            for sql in [
+                c"CREATE TABLE t (x)",
+                c"INSERT INTO t VALUES (1), (2), (3), (4), (5), (6), (7), (8), (9), (10)",
+            ] {
+                let mut stmt = ptr::null_mut();
+                assert_eq!(
+                    sqlite3_prepare_v2(db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()),
+                    SQLITE_OK
+                );
+                assert_eq!(sqlite3_step(stmt), SQLITE_DONE);
+                assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+            }
+            let mut calls = 0;
+            sqlite3_progress_handler(
+                db,
+                100,
+                Some(progress_callback),
+                &mut calls as *mut i32 as *mut libc::c_void,
+            );
+            let sql = c"SELECT count(*) FROM t a, t b, t c";
+            let mut stmt = ptr::null_mut();
+            assert_eq!(
+                sqlite3_prepare_v2(db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()),
+                SQLITE_OK
+            );
+            assert_eq!(sqlite3_step(stmt), SQLITE_INTERRUPT);
+            assert_eq!(calls, 3);
+            assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+
+            sqlite3_progress_handler(db, 0, None, ptr::null_mut());
+            assert_eq!(sqlite3_close(db), SQLITE_OK);
+        }
+    }
+
+    #[test]
+    fn test_memory_used() {
+        unsafe {
+            let mut db = ptr::null_mut();
+            assert_eq!(sqlite3_open(c":memory:".as_ptr(), &mut db), SQLITE_OK);
+            for sql in [
+                c"CREATE TABLE t (x)",
+                c"INSERT INTO t VALUES (randomblob(10000)), (randomblob(10000))",
+            ] {
+                let mut stmt = ptr::null_mut();
+                assert_eq!(
+                    sqlite3_prepare_v2(db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()),
+                    SQLITE_OK
+                );
+                assert_eq!(sqlite3_step(stmt), SQLITE_DONE);
+                assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+            }
+            assert!(sqlite3_memory_used() > 0);
+            assert!(sqlite3_memory_highwater(0) >= sqlite3_memory_used());
+            assert!(sqlite3_soft_heap_limit64(-1) >= 0);
+            assert_eq!(sqlite3_db_release_memory(db), SQLITE_OK);
+            assert_eq!(sqlite3_close(db), SQLITE_OK);
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        #[derive(Default)]
+        struct Calls {
+            updates: Vec<(i32, String, String, i64)>,
+            commits: usize,
+            rollbacks: usize,
+            veto_commit: bool,
+        }
+
+        unsafe extern "C" fn update_callback(
+            context: *mut libc::c_void,
+            op: i32,
+            db_name: *const libc::c_char,
+            table_name: *const libc::c_char,
+            rowid: i64,
+        ) {
+            let calls = &mut *(context as *mut Calls);
+            calls.updates.push((
+                op,
+                CStr::from_ptr(db_name).to_str().unwrap().to_string(),
+                CStr::from_ptr(table_name).to_str().unwrap().to_string(),
+                rowid,
+            ));
+        }
+
+        unsafe extern "C" fn commit_callback(context: *mut libc::c_void) -> i32 {
+            let calls = &mut *(context as *mut Calls);
+            calls.commits += 1;
+            calls.veto_commit as i32
+        }
+
+        unsafe extern "C" fn rollback_callback(context: *mut libc::c_void) {
+            let calls = &mut *(context as *mut Calls);
+            calls.rollbacks += 1;
+        }
+
+        unsafe fn exec(db: *mut sqlite3, sql: &CStr) -> i32 {
+            let mut stmt = ptr::null_mut();
+            assert_eq!(
+                sqlite3_prepare_v2(db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()),
+                SQLITE_OK
+            );
+            let rc = sqlite3_step(stmt);
+            assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+            rc
+        }
+
+        unsafe {
+            let temp_file = tempfile::NamedTempFile::with_suffix(".db").unwrap();
+            let path = std::ffi::CString::new(temp_file.path().to_str().unwrap()).unwrap();
+            let mut db = ptr::null_mut();
+            assert_eq!(sqlite3_open(path.as_ptr(), &mut db), SQLITE_OK);
+            assert_eq!(
+                exec(db, c"CREATE TABLE test (id INTEGER PRIMARY KEY, val TEXT)"),
+                SQLITE_DONE
+            );
+
+            let mut calls = Calls::default();
+            let context = &mut calls as *mut Calls as *mut libc::c_void;
+            assert!(sqlite3_update_hook(db, Some(update_callback), context).is_null());
+            assert!(sqlite3_commit_hook(db, Some(commit_callback), context).is_null());
+            assert!(sqlite3_rollback_hook(db, Some(rollback_callback), context).is_null());
+
+            assert_eq!(
+                exec(db, c"INSERT INTO test (id, val) VALUES (1, 'one')"),
+                SQLITE_DONE
+            );
+            assert_eq!(
+                exec(db, c"UPDATE test SET val = 'uno' WHERE id = 1"),
+                SQLITE_DONE
+            );
+            assert_eq!(exec(db, c"DELETE FROM test WHERE id = 1"), SQLITE_DONE);
+            let expected = [SQLITE_INSERT, SQLITE_UPDATE, SQLITE_DELETE]
+                .into_iter()
+                .map(|op| (op, "main".to_string(), "test".to_string(), 1))
+                .collect::<Vec<_>>();
+            assert_eq!(calls.updates, expected);
+            assert_eq!(calls.commits, 3);
+            assert_eq!(calls.rollbacks, 0);
+
+            calls.veto_commit = true;
+            assert_eq!(
+                exec(db, c"INSERT INTO test (id, val) VALUES (2, 'two')"),
+                SQLITE_ERROR
+            );
+            assert_eq!(calls.commits, 4);
+            assert_eq!(calls.rollbacks, 1);
+
+            assert_eq!(sqlite3_update_hook(db, None, ptr::null_mut()), context);
+            assert_eq!(sqlite3_commit_hook(db, None, ptr::null_mut()), context);
+            assert_eq!(sqlite3_rollback_hook(db, None, ptr::null_mut()), context);
+
+            // The vetoed row was rolled back, so inserting it again doesn't conflict.
+            assert_eq!(
+                exec(db, c"INSERT INTO test (id, val) VALUES (2, 'two')"),
+                SQLITE_DONE
+            );
+            assert_eq!(calls.commits, 4);
+
+            assert_eq!(sqlite3_close(db), SQLITE_OK);
+        }
+    }
+
     #[test]
     fn test_wal_checkpoint() {
         unsafe {
@@ -201,6 +506,201 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_backup() {
+        unsafe {
+            let source_file = tempfile::NamedTempFile::with_suffix(".db").unwrap();
+            let source_path = std::ffi::CString::new(source_file.path().to_str().unwrap()).unwrap();
+            let dest_file = tempfile::NamedTempFile::with_suffix(".db").unwrap();
+            let dest_path = std::ffi::CString::new(dest_file.path().to_str().unwrap()).unwrap();
+
+            let mut source = ptr::null_mut();
+            assert_eq!(sqlite3_open(source_path.as_ptr(), &mut source), SQLITE_OK);
+            for sql in [
+                c"CREATE TABLE test (id INTEGER PRIMARY KEY, val TEXT)",
+                c"INSERT INTO test (id, val) VALUES (1, 'one')",
+                c"INSERT INTO test (id, val) VALUES (2, 'two')",
+            ] {
+                let mut stmt = ptr::null_mut();
+                assert_eq!(
+                    sqlite3_prepare_v2(source, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()),
+                    SQLITE_OK
+                );
+                assert_eq!(sqlite3_step(stmt), SQLITE_DONE);
+                assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+            }
+
+            let mut dest = ptr::null_mut();
+            assert_eq!(sqlite3_open(dest_path.as_ptr(), &mut dest), SQLITE_OK);
+            let backup = sqlite3_backup_init(dest, c"main".as_ptr(), source, c"main".as_ptr());
+            assert!(!backup.is_null());
+            assert_eq!(sqlite3_backup_step(backup, 1), SQLITE_OK);
+            assert_eq!(sqlite3_backup_pagecount(backup), 2);
+            assert_eq!(sqlite3_backup_remaining(backup), 1);
+            assert_eq!(sqlite3_backup_step(backup, -1), SQLITE_DONE);
+            assert_eq!(sqlite3_backup_remaining(backup), 0);
+            assert_eq!(sqlite3_backup_finish(backup), SQLITE_OK);
+
+            let mut stmt = ptr::null_mut();
+            assert_eq!(
+                sqlite3_prepare_v2(
+                    dest,
+                    c"SELECT id FROM test ORDER BY id".as_ptr(),
+                    -1,
+                    &mut stmt,
+                    ptr::null_mut()
+                ),
+                SQLITE_OK
+            );
+            assert_eq!(sqlite3_step(stmt), SQLITE_ROW);
+            assert_eq!(sqlite3_step(stmt), SQLITE_ROW);
+            assert_eq!(sqlite3_step(stmt), SQLITE_DONE);
+            assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+
+            assert_eq!(sqlite3_close(dest), SQLITE_OK);
+            assert_eq!(sqlite3_close(source), SQLITE_OK);
+        }
+    }
+
+    #[test]
+    fn test_serialize_deserialize() {
+        unsafe {
+            let source_file = tempfile::NamedTempFile::with_suffix(".db").unwrap();
+            let source_path = std::ffi::CString::new(source_file.path().to_str().unwrap()).unwrap();
+
+            let mut source = ptr::null_mut();
+            assert_eq!(sqlite3_open(source_path.as_ptr(), &mut source), SQLITE_OK);
+            for sql in [
+                c"CREATE TABLE test (id INTEGER PRIMARY KEY)",
+                c"INSERT INTO test (id) VALUES (1)",
+            ] {
+                let mut stmt = ptr::null_mut();
+                assert_eq!(
+                    sqlite3_prepare_v2(source, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()),
+                    SQLITE_OK
+                );
+                assert_eq!(sqlite3_step(stmt), SQLITE_DONE);
+                assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+            }
+
+            let mut size = 0;
+            let image = sqlite3_serialize(source, c"main".as_ptr(), &mut size, 0);
+            assert!(!image.is_null());
+            assert_eq!(size, 2 * 4096);
+            assert_eq!(
+                std::slice::from_raw_parts(image, 16),
+                b"SQLite format 3\0".as_slice()
+            );
+
+            let mut dest = ptr::null_mut();
+            assert_eq!(sqlite3_open(c":memory:".as_ptr(), &mut dest), SQLITE_OK);
+            assert_eq!(
+                sqlite3_deserialize(
+                    dest,
+                    c"main".as_ptr(),
+                    image,
+                    size,
+                    size,
+                    SQLITE_DESERIALIZE_FREEONCLOSE
+                ),
+                SQLITE_OK
+            );
+
+            let mut stmt = ptr::null_mut();
+            assert_eq!(
+                sqlite3_prepare_v2(
+                    dest,
+                    c"SELECT id FROM test".as_ptr(),
+                    -1,
+                    &mut stmt,
+                    ptr::null_mut()
+                ),
+                SQLITE_OK
+            );
+            assert_eq!(sqlite3_step(stmt), SQLITE_ROW);
+            assert_eq!(sqlite3_step(stmt), SQLITE_DONE);
+            assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+
+            assert_eq!(sqlite3_close(dest), SQLITE_OK);
+            assert_eq!(sqlite3_close(source), SQLITE_OK);
+        }
+    }
+
+    #[test]
+    fn test_blob_io() {
+        unsafe {
+            let temp_file = tempfile::NamedTempFile::with_suffix(".db").unwrap();
+            let path = std::ffi::CString::new(temp_file.path().to_str().unwrap()).unwrap();
+            let mut db = ptr::null_mut();
+            assert_eq!(sqlite3_open(path.as_ptr(), &mut db), SQLITE_OK);
+            for sql in [
+                c"CREATE TABLE test (id INTEGER PRIMARY KEY, data BLOB)",
+                c"INSERT INTO test (id, data) VALUES (1, zeroblob(10000))",
+                c"INSERT INTO test (id, data) VALUES (2, zeroblob(10))",
+            ] {
+                let mut stmt = ptr::null_mut();
+                assert_eq!(
+                    sqlite3_prepare_v2(db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()),
+                    SQLITE_OK
+                );
+                assert_eq!(sqlite3_step(stmt), SQLITE_DONE);
+                assert_eq!(sqlite3_finalize(stmt), SQLITE_OK);
+            }
+
+            let mut blob = ptr::null_mut();
+            assert_eq!(
+                sqlite3_blob_open(
+                    db,
+                    c"main".as_ptr(),
+                    c"test".as_ptr(),
+                    c"data".as_ptr(),
+                    1,
+                    1,
+                    &mut blob
+                ),
+                SQLITE_OK
+            );
+            assert_eq!(sqlite3_blob_bytes(blob), 10000);
+
+            let data = b"hello";
+            assert_eq!(
+                sqlite3_blob_write(blob, data.as_ptr() as *const _, 5, 9000),
+                SQLITE_OK
+            );
+            let mut buf = [0u8; 7];
+            assert_eq!(
+                sqlite3_blob_read(blob, buf.as_mut_ptr() as *mut _, 7, 8999),
+                SQLITE_OK
+            );
+            assert_eq!(&buf, b"\0hello\0");
+            assert_eq!(
+                sqlite3_blob_read(blob, buf.as_mut_ptr() as *mut _, 7, 9995),
+                SQLITE_ERROR
+            );
+
+            assert_eq!(sqlite3_blob_reopen(blob, 2), SQLITE_OK);
+            assert_eq!(sqlite3_blob_bytes(blob), 10);
+            assert_eq!(sqlite3_blob_reopen(blob, 3), SQLITE_ERROR);
+            assert_eq!(sqlite3_blob_close(blob), SQLITE_OK);
+
+            assert_eq!(
+                sqlite3_blob_open(
+                    db,
+                    c"main".as_ptr(),
+                    c"test".as_ptr(),
+                    c"id".as_ptr(),
+                    1,
+                    0,
+                    &mut blob
+                ),
+                SQLITE_ERROR
+            );
+            assert!(blob.is_null());
+
+            assert_eq!(sqlite3_close(db), SQLITE_OK);
+        }
+    }
+
     #[cfg(not(feature = "sqlite3"))]
     mod libsql_ext {
         use super::*;
@@ -0,0 +1,201 @@
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, TempDatabase};
+use limbo_core::LimboError;
+use rusqlite::types::Value;
+use tempfile::TempDir;
+
+fn sequence(
+    tmp_db: &TempDatabase,
+    conn: &std::sync::Arc<limbo_core::Connection>,
+) -> Vec<Vec<Value>> {
+    limbo_exec_rows(
+        tmp_db,
+        conn,
+        "SELECT name, seq FROM sqlite_sequence ORDER BY name",
+    )
+}
+
+#[test]
+fn test_autoincrement_never_reuses_rowids() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (id INTEGER PRIMARY KEY AUTOINCREMENT, v TEXT)",
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT sql FROM sqlite_schema WHERE name = 'sqlite_sequence'"
+        ),
+        vec![vec![Value::Text(
+            "CREATE TABLE sqlite_sequence(name,seq)".to_string()
+        )]]
+    );
+    assert_eq!(sequence(&tmp_db, &conn), Vec::<Vec<Value>>::new());
+
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t (v) VALUES ('a'), ('b'), ('c')",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t WHERE id = 3");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t (v) VALUES ('d')");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id, v FROM t"),
+        vec![
+            vec![Value::Integer(1), Value::Text("a".to_string())],
+            vec![Value::Integer(2), Value::Text("b".to_string())],
+            vec![Value::Integer(4), Value::Text("d".to_string())],
+        ]
+    );
+
+    // Explicit rowids move the sequence forward, but never back.
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (100, 'e')");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (50, 'f')");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t (v) VALUES ('g')");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM t"),
+        vec![vec![Value::Integer(101)]]
+    );
+    assert_eq!(
+        sequence(&tmp_db, &conn),
+        vec![vec![Value::Text("t".to_string()), Value::Integer(101)]]
+    );
+}
+
+#[test]
+fn test_autoincrement_full() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (id INTEGER PRIMARY KEY AUTOINCREMENT)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (9223372036854775807)");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t");
+    assert!(matches!(
+        limbo_exec_rows_error(&tmp_db, &conn, "INSERT INTO t VALUES (NULL)"),
+        Err(LimboError::DatabaseFull)
+    ));
+}
+
+#[test]
+fn test_autoincrement_drop_and_rename() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE a (id INTEGER PRIMARY KEY AUTOINCREMENT)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE b (id INTEGER PRIMARY KEY AUTOINCREMENT)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO a VALUES (5)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO b VALUES (7)");
+
+    limbo_exec_rows(&tmp_db, &conn, "ALTER TABLE a RENAME TO c");
+    assert_eq!(
+        sequence(&tmp_db, &conn),
+        vec![
+            vec![Value::Text("b".to_string()), Value::Integer(7)],
+            vec![Value::Text("c".to_string()), Value::Integer(5)],
+        ]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO c VALUES (NULL)");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM c"),
+        vec![vec![Value::Integer(5)], vec![Value::Integer(6)]]
+    );
+
+    limbo_exec_rows(&tmp_db, &conn, "DROP TABLE b");
+    assert_eq!(
+        sequence(&tmp_db, &conn),
+        vec![vec![Value::Text("c".to_string()), Value::Integer(6)]]
+    );
+    // A new table with the name of the dropped one starts over.
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE b (id INTEGER PRIMARY KEY AUTOINCREMENT)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO b VALUES (NULL)");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM b"),
+        vec![vec![Value::Integer(1)]]
+    );
+}
+
+#[test]
+fn test_autoincrement_requires_integer_primary_key() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    assert!(limbo_exec_rows_error(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (id TEXT PRIMARY KEY AUTOINCREMENT)"
+    )
+    .is_err());
+    assert!(limbo_exec_rows_error(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (id INTEGER PRIMARY KEY AUTOINCREMENT) WITHOUT ROWID"
+    )
+    .is_err());
+}
+
+#[test]
+fn test_autoincrement_with_sqlite() {
+    let mut path = TempDir::new().unwrap().keep();
+    path.push("test.db");
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.pragma_update(None, "journal_mode", "wal").unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY AUTOINCREMENT, v);
+             INSERT INTO t (v) VALUES (1), (2), (3);
+             DELETE FROM t WHERE id = 3;",
+        )
+        .unwrap();
+    }
+
+    {
+        let tmp_db = TempDatabase::new_with_existent(&path);
+        let conn = tmp_db.connect_limbo();
+        limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t (v) VALUES (4)");
+        assert_eq!(
+            limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM t"),
+            vec![
+                vec![Value::Integer(1)],
+                vec![Value::Integer(2)],
+                vec![Value::Integer(4)]
+            ]
+        );
+        conn.close().unwrap();
+    }
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let seq: i64 = conn
+        .query_row(
+            "SELECT seq FROM sqlite_sequence WHERE name = 't'",
+            (),
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(seq, 4);
+    conn.execute("INSERT INTO t (v) VALUES (5)", ()).unwrap();
+    let id: i64 = conn
+        .query_row("SELECT max(id) FROM t", (), |row| row.get(0))
+        .unwrap();
+    assert_eq!(id, 5);
+}
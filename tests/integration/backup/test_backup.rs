@@ -0,0 +1,158 @@
+use crate::common::{limbo_exec_rows, maybe_setup_tracing, sqlite_exec_rows, TempDatabase};
+use limbo_core::{Backup, BackupStepResult};
+use rusqlite::types::Value;
+
+fn populate(tmp_db: &TempDatabase, conn: &std::sync::Arc<limbo_core::Connection>, rows: usize) {
+    limbo_exec_rows(
+        tmp_db,
+        conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y BLOB)",
+    );
+    for i in 0..rows {
+        limbo_exec_rows(
+            tmp_db,
+            conn,
+            &format!("INSERT INTO t VALUES ({}, randomblob(3000))", i),
+        );
+    }
+}
+
+#[test]
+fn test_backup_all_pages() {
+    maybe_setup_tracing();
+    let src_db = TempDatabase::new_empty();
+    let src_conn = src_db.connect_limbo();
+    populate(&src_db, &src_conn, 1000);
+
+    let dest_db = TempDatabase::new_empty();
+    let dest_conn = dest_db.connect_limbo();
+    let mut backup = Backup::new(&src_conn, &dest_conn).unwrap();
+    assert_eq!(backup.step(None).unwrap(), BackupStepResult::Done);
+    assert_eq!(backup.remaining(), 0);
+    assert!(backup.pagecount() > 1000);
+    drop(backup);
+
+    let expected = limbo_exec_rows(&src_db, &src_conn, "SELECT x, y FROM t ORDER BY x");
+    let actual = limbo_exec_rows(&dest_db, &dest_conn, "SELECT x, y FROM t ORDER BY x");
+    assert_eq!(actual.len(), 1000);
+    assert_eq!(actual, expected);
+
+    dest_conn.close().unwrap();
+    let sqlite_conn = rusqlite::Connection::open(&dest_db.path).unwrap();
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "PRAGMA integrity_check"),
+        vec![vec![Value::Text("ok".to_string())]]
+    );
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(1000)]]
+    );
+}
+
+#[test]
+fn test_backup_incremental_restarts_on_source_change() {
+    maybe_setup_tracing();
+    let src_db = TempDatabase::new_empty();
+    let src_conn = src_db.connect_limbo();
+    populate(&src_db, &src_conn, 100);
+
+    let dest_db = TempDatabase::new_empty();
+    let dest_conn = dest_db.connect_limbo();
+    let mut backup = Backup::new(&src_conn, &dest_conn).unwrap();
+    assert_eq!(backup.step(Some(10)).unwrap(), BackupStepResult::More);
+    let pagecount = backup.pagecount();
+    assert_eq!(backup.remaining(), pagecount - 10);
+
+    limbo_exec_rows(
+        &src_db,
+        &src_conn,
+        "INSERT INTO t VALUES (100, randomblob(3000))",
+    );
+
+    // The source changed: the next step starts over from the first page.
+    assert_eq!(backup.step(Some(10)).unwrap(), BackupStepResult::More);
+    assert!(backup.pagecount() > pagecount);
+    assert_eq!(backup.remaining(), backup.pagecount() - 10);
+
+    loop {
+        match backup.step(Some(10)).unwrap() {
+            BackupStepResult::More => {}
+            BackupStepResult::Done => break,
+            BackupStepResult::Busy => unreachable!(),
+        }
+    }
+    drop(backup);
+
+    let rows = limbo_exec_rows(&dest_db, &dest_conn, "SELECT count(*), max(x) FROM t");
+    assert_eq!(rows, vec![vec![Value::Integer(101), Value::Integer(100)]]);
+}
+
+#[test]
+fn test_backup_overwrites_destination() {
+    maybe_setup_tracing();
+    let src_db = TempDatabase::new_empty();
+    let src_conn = src_db.connect_limbo();
+    limbo_exec_rows(&src_db, &src_conn, "CREATE TABLE a (x)");
+    limbo_exec_rows(&src_db, &src_conn, "INSERT INTO a VALUES (1)");
+
+    let dest_db = TempDatabase::new_empty();
+    let dest_conn = dest_db.connect_limbo();
+    populate(&dest_db, &dest_conn, 100);
+
+    let mut backup = Backup::new(&src_conn, &dest_conn).unwrap();
+    assert_eq!(backup.step(None).unwrap(), BackupStepResult::Done);
+    drop(backup);
+
+    let rows = limbo_exec_rows(&dest_db, &dest_conn, "SELECT x FROM a");
+    assert_eq!(rows, vec![vec![Value::Integer(1)]]);
+    assert!(dest_conn.prepare("SELECT * FROM t").is_err());
+}
+
+#[test]
+fn test_backup_dropped_midway_leaves_destination_unchanged() {
+    maybe_setup_tracing();
+    let src_db = TempDatabase::new_empty();
+    let src_conn = src_db.connect_limbo();
+    populate(&src_db, &src_conn, 1000);
+
+    let dest_db = TempDatabase::new_empty();
+    let dest_conn = dest_db.connect_limbo();
+    populate(&dest_db, &dest_conn, 100);
+
+    let mut backup = Backup::new(&src_conn, &dest_conn).unwrap();
+    // Enough pages for some of them to be spilled to the destination WAL.
+    assert_eq!(backup.step(Some(600)).unwrap(), BackupStepResult::More);
+
+    // Readers of the destination don't see the pages copied so far.
+    let reader = dest_db.connect_limbo();
+    let rows = limbo_exec_rows(&dest_db, &reader, "SELECT count(*), max(x) FROM t");
+    assert_eq!(rows, vec![vec![Value::Integer(100), Value::Integer(99)]]);
+    drop(backup);
+
+    let rows = limbo_exec_rows(&dest_db, &dest_conn, "SELECT count(*), max(x) FROM t");
+    assert_eq!(rows, vec![vec![Value::Integer(100), Value::Integer(99)]]);
+    limbo_exec_rows(
+        &dest_db,
+        &dest_conn,
+        "INSERT INTO t VALUES (100, randomblob(3000))",
+    );
+    dest_conn.close().unwrap();
+    reader.close().unwrap();
+
+    // The frames of the dropped backup that were not written over are not part of the WAL.
+    let dest_db = TempDatabase::new_with_existent(&dest_db.path);
+    let dest_conn = dest_db.connect_limbo();
+    let rows = limbo_exec_rows(&dest_db, &dest_conn, "SELECT count(*), max(x) FROM t");
+    assert_eq!(rows, vec![vec![Value::Integer(101), Value::Integer(100)]]);
+    dest_conn.close().unwrap();
+
+    let sqlite_conn = rusqlite::Connection::open(&dest_db.path).unwrap();
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "PRAGMA integrity_check"),
+        vec![vec![Value::Text("ok".to_string())]]
+    );
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(101)]]
+    );
+}
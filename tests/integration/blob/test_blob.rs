@@ -0,0 +1,155 @@
+use crate::common::{limbo_exec_rows, maybe_setup_tracing, sqlite_exec_rows, TempDatabase};
+use limbo_core::LimboError;
+use rusqlite::types::Value;
+
+const BLOB_SIZE: usize = 100_000;
+
+fn blob_value(tmp_db: &TempDatabase, conn: &std::sync::Arc<limbo_core::Connection>) -> Vec<u8> {
+    let rows = limbo_exec_rows(tmp_db, conn, "SELECT data FROM files WHERE id = 2");
+    match &rows[0][0] {
+        Value::Blob(data) => data.clone(),
+        v => panic!("expected a blob, got {:?}", v),
+    }
+}
+
+fn setup(tmp_db: &TempDatabase, conn: &std::sync::Arc<limbo_core::Connection>) {
+    limbo_exec_rows(
+        tmp_db,
+        conn,
+        "CREATE TABLE files (id INTEGER PRIMARY KEY, name TEXT, size INTEGER, data BLOB)",
+    );
+    for i in 1..=3 {
+        limbo_exec_rows(
+            tmp_db,
+            conn,
+            &format!(
+                "INSERT INTO files VALUES ({}, 'file-{}', {}, randomblob({}))",
+                i, i, BLOB_SIZE, BLOB_SIZE
+            ),
+        );
+    }
+}
+
+#[test]
+fn test_blob_read_in_chunks() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    setup(&tmp_db, &conn);
+    let expected = blob_value(&tmp_db, &conn);
+
+    let blob = conn.blob_open("files", "data", 2, false).unwrap();
+    assert_eq!(blob.size(), BLOB_SIZE);
+    let mut actual = vec![0; BLOB_SIZE];
+    for (i, chunk) in actual.chunks_mut(3000).enumerate() {
+        blob.read_at(chunk, i * 3000).unwrap();
+    }
+    assert_eq!(actual, expected);
+
+    let mut buf = vec![0; 10];
+    blob.read_at(&mut buf, BLOB_SIZE - 10).unwrap();
+    assert_eq!(buf, expected[BLOB_SIZE - 10..]);
+    assert!(blob.read_at(&mut buf, BLOB_SIZE - 5).is_err());
+
+    let text = conn.blob_open("files", "name", 3, false).unwrap();
+    let mut buf = vec![0; text.size()];
+    text.read_at(&mut buf, 0).unwrap();
+    assert_eq!(buf, b"file-3");
+}
+
+#[test]
+fn test_blob_write_across_overflow_pages() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    setup(&tmp_db, &conn);
+    let mut expected = blob_value(&tmp_db, &conn);
+
+    let blob = conn.blob_open("files", "data", 2, true).unwrap();
+    // Spans the end of the local payload and several overflow pages.
+    let patch: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+    blob.write_at(&patch, 1000).unwrap();
+    expected[1000..21_000].copy_from_slice(&patch);
+    blob.write_at(b"end", BLOB_SIZE - 3).unwrap();
+    expected[BLOB_SIZE - 3..].copy_from_slice(b"end");
+    assert!(blob.write_at(b"too far", BLOB_SIZE - 3).is_err());
+
+    // Writes within an explicit transaction are committed with it.
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    blob.write_at(b"in transaction", 50_000).unwrap();
+    expected[50_000..50_014].copy_from_slice(b"in transaction");
+    limbo_exec_rows(&tmp_db, &conn, "COMMIT");
+    drop(blob);
+
+    assert_eq!(blob_value(&tmp_db, &conn), expected);
+    let rows = limbo_exec_rows(&tmp_db, &conn, "SELECT name, size FROM files WHERE id = 2");
+    assert_eq!(
+        rows,
+        vec![vec![
+            Value::Text("file-2".to_string()),
+            Value::Integer(BLOB_SIZE as i64)
+        ]]
+    );
+
+    conn.close().unwrap();
+    let sqlite_conn = rusqlite::Connection::open(&tmp_db.path).unwrap();
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "PRAGMA integrity_check"),
+        vec![vec![Value::Text("ok".to_string())]]
+    );
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "SELECT data FROM files WHERE id = 2"),
+        vec![vec![Value::Blob(expected)]]
+    );
+}
+
+#[test]
+fn test_blob_reopen() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    setup(&tmp_db, &conn);
+
+    let mut blob = conn.blob_open("files", "name", 1, false).unwrap();
+    let mut buf = vec![0; 6];
+    for rowid in 1..=3 {
+        blob.reopen(rowid).unwrap();
+        assert_eq!(blob.rowid(), rowid);
+        blob.read_at(&mut buf, 0).unwrap();
+        assert_eq!(buf, format!("file-{}", rowid).as_bytes());
+    }
+    assert!(blob.reopen(4).is_err());
+    assert_eq!(blob.rowid(), 3);
+
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM files WHERE id = 3");
+    assert!(matches!(
+        blob.read_at(&mut buf, 0),
+        Err(LimboError::BlobExpired)
+    ));
+}
+
+#[test]
+fn test_blob_open_errors() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    setup(&tmp_db, &conn);
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO files VALUES (4, NULL, 0, NULL)",
+    );
+
+    assert!(conn.blob_open("missing", "data", 1, false).is_err());
+    assert!(conn.blob_open("files", "missing", 1, false).is_err());
+    assert!(conn.blob_open("files", "data", 100, false).is_err());
+    assert!(conn.blob_open("files", "size", 1, false).is_err());
+    assert!(conn.blob_open("files", "id", 1, false).is_err());
+    assert!(conn.blob_open("files", "data", 4, false).is_err());
+
+    let blob = conn.blob_open("files", "data", 1, false).unwrap();
+    assert!(matches!(
+        blob.write_at(b"data", 0),
+        Err(LimboError::ReadOnly)
+    ));
+}
@@ -218,6 +218,22 @@ pub(crate) fn limbo_exec_rows_error(
     }
 }
 
+/// Runs `query`, which reads the entries of an index, with SQLite after checking the database
+/// with it. Each row is the first column of the query.
+#[cfg(feature = "index_experimental")]
+pub(crate) fn sqlite_index_entries(tmp_db: &TempDatabase, query: &str) -> Vec<String> {
+    let conn = rusqlite::Connection::open(&tmp_db.path).unwrap();
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", (), |row| row.get(0))
+        .unwrap();
+    assert_eq!(integrity, "ok");
+    let mut stmt = conn.prepare(query).unwrap();
+    stmt.query_map((), |row| row.get(0))
+        .unwrap()
+        .collect::<Result<Vec<String>, _>>()
+        .unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use std::vec;
@@ -0,0 +1,97 @@
+use crate::common::{limbo_exec_rows, maybe_setup_tracing, TempDatabase};
+use limbo_core::{CompressedIO, Database, IO};
+use rusqlite::types::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+fn open_compressed(path: &Path) -> (Arc<CompressedIO>, TempDatabase) {
+    let io = Arc::new(CompressedIO::new(Arc::new(
+        limbo_core::PlatformIO::new().unwrap(),
+    )));
+    let db = Database::open_file(io.clone(), path.to_str().unwrap(), false).unwrap();
+    let tmp_db = TempDatabase {
+        path: path.to_path_buf(),
+        io: io.clone() as Arc<dyn IO + Send>,
+        db,
+    };
+    (io, tmp_db)
+}
+
+#[test]
+fn test_compressed_database() {
+    maybe_setup_tracing();
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("compressed.db");
+    let (io, tmp_db) = open_compressed(&path);
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    for i in 0..2000 {
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            &format!(
+                "INSERT INTO t VALUES ({}, 'archived log line {} of a highly compressible text')",
+                i,
+                i % 10
+            ),
+        );
+    }
+    limbo_exec_rows(&tmp_db, &conn, "COMMIT");
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA wal_checkpoint");
+    let query = "SELECT count(*), sum(length(y)) FROM t";
+    let expected = limbo_exec_rows(&tmp_db, &conn, query);
+    assert_eq!(expected[0][0], Value::Integer(2000));
+
+    let stats = io.stats(path.to_str().unwrap()).unwrap().unwrap();
+    let page_count = limbo_exec_rows(&tmp_db, &conn, "PRAGMA page_count");
+    assert_eq!(
+        vec![vec![Value::Integer(stats.logical_bytes as i64 / 4096)]],
+        page_count
+    );
+    assert!(stats.compressed_bytes < stats.file_bytes);
+    assert!(stats.ratio() > 2.0, "{:?}", stats);
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), stats.file_bytes);
+    conn.close().unwrap();
+    drop(conn);
+    drop(tmp_db);
+    std::fs::remove_file(format!("{}-wal", path.to_str().unwrap())).unwrap();
+
+    // The file is not a plain database file anymore.
+    let bytes = std::fs::read(&path).unwrap();
+    assert!(!bytes.starts_with(b"SQLite format 3\0"));
+
+    let (_, tmp_db) = open_compressed(&path);
+    let conn = tmp_db.connect_limbo();
+    assert_eq!(limbo_exec_rows(&tmp_db, &conn, query), expected);
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t WHERE x % 2 = 0");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(1000)]]
+    );
+}
+
+#[test]
+fn test_compressed_vfs_by_name() {
+    maybe_setup_tracing();
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("compressed.db");
+    let (io, db) = Database::open_new(path.to_str().unwrap(), "compressed").unwrap();
+    let tmp_db = TempDatabase {
+        path: path.clone(),
+        io: io as Arc<dyn IO + Send>,
+        db,
+    };
+    let conn = tmp_db.connect_limbo();
+    assert!(conn.list_vfs().contains(&"compressed".to_string()));
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT * FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+}
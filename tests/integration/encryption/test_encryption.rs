@@ -0,0 +1,307 @@
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, maybe_setup_tracing, TempDatabase};
+use limbo_core::{Database, LimboError, IO};
+use rusqlite::types::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const KEY: &str = "x'000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f'";
+const OTHER_KEY: &str = "x'1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100'";
+const MARKER: &str = "plaintext-marker";
+
+/// Returns the path of a new, empty database.
+fn new_path() -> PathBuf {
+    TempDatabase::new_empty().path
+}
+
+fn open_with_key(path: &Path, key: &str) -> TempDatabase {
+    let io: Arc<dyn IO + Send> = Arc::new(limbo_core::PlatformIO::new().unwrap());
+    let db = Database::open_file_with_key(io.clone(), path.to_str().unwrap(), key, false).unwrap();
+    TempDatabase {
+        path: path.to_path_buf(),
+        io,
+        db,
+    }
+}
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle.as_bytes())
+}
+
+fn count_rows(db: &TempDatabase, conn: &Arc<limbo_core::Connection>) -> Vec<Vec<Value>> {
+    limbo_exec_rows(db, conn, "SELECT count(*), sum(length(y)) FROM t")
+}
+
+#[test]
+fn test_encrypted_files_contain_no_plaintext() {
+    maybe_setup_tracing();
+    let path = new_path();
+    let tmp_db = open_with_key(&path, KEY);
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    // Enough commits to checkpoint the WAL into the database file, and rows large enough to
+    // spill to overflow pages.
+    for i in 0..1200 {
+        let y = MARKER.repeat(if i % 100 == 0 { 1000 } else { 4 });
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            &format!("INSERT INTO t VALUES ({}, '{}')", i, y),
+        );
+    }
+    let expected = count_rows(&tmp_db, &conn);
+    assert_eq!(expected[0][0], Value::Integer(1200));
+
+    let wal_path = format!("{}-wal", path.to_str().unwrap());
+    for file in [path.to_str().unwrap(), wal_path.as_str()] {
+        let bytes = std::fs::read(file).unwrap();
+        assert!(bytes.len() > 100 * 4096);
+        assert!(!contains(&bytes, MARKER), "{} contains plaintext", file);
+        assert!(
+            !contains(&bytes, "SQLite format 3"),
+            "{} contains plaintext",
+            file
+        );
+        assert!(
+            !contains(&bytes, "CREATE TABLE"),
+            "{} contains plaintext",
+            file
+        );
+    }
+    conn.close().unwrap();
+    drop(tmp_db);
+
+    let tmp_db = open_with_key(&path, KEY);
+    let conn = tmp_db.connect_limbo();
+    assert_eq!(count_rows(&tmp_db, &conn), expected);
+}
+
+#[test]
+fn test_open_encrypted_database_without_key() {
+    maybe_setup_tracing();
+    let path = new_path();
+    let tmp_db = open_with_key(&path, KEY);
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1, 'a')");
+    conn.close().unwrap();
+    drop(tmp_db);
+
+    let tmp_db = TempDatabase::new_with_existent(&path);
+    let conn = tmp_db.connect_limbo();
+    assert!(matches!(
+        limbo_exec_rows_error(&tmp_db, &conn, "SELECT * FROM sqlite_schema"),
+        Err(LimboError::NotADB)
+    ));
+    assert!(matches!(
+        conn.set_encryption_key(OTHER_KEY),
+        Err(LimboError::NotADB)
+    ));
+    assert!(matches!(
+        conn.set_encryption_key("a passphrase"),
+        Err(LimboError::NotADB)
+    ));
+    conn.set_encryption_key(KEY).unwrap();
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT * FROM t"),
+        vec![vec![Value::Integer(1), Value::Text("a".to_string())]]
+    );
+    // The key is shared by all connections to the database.
+    let other = tmp_db.connect_limbo();
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &other, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+}
+
+#[test]
+fn test_pragma_key() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "PRAGMA key = 'correct horse battery staple'",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        &format!("INSERT INTO t VALUES (1, '{}')", MARKER),
+    );
+    let bytes = std::fs::read(format!("{}-wal", tmp_db.path.to_str().unwrap())).unwrap();
+    assert!(!contains(&bytes, MARKER));
+    conn.close().unwrap();
+    let path = tmp_db.path.clone();
+    drop(tmp_db);
+
+    let tmp_db = TempDatabase::new_with_existent(&path);
+    let conn = tmp_db.connect_limbo();
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "PRAGMA key = 'wrong'").is_err());
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "PRAGMA key = 'correct horse battery staple'",
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT * FROM t"),
+        vec![vec![Value::Integer(1), Value::Text(MARKER.to_string())]]
+    );
+}
+
+#[test]
+fn test_rekey() {
+    maybe_setup_tracing();
+    let path = new_path();
+    let tmp_db = open_with_key(&path, KEY);
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    for i in 0..100 {
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            &format!("INSERT INTO t VALUES ({}, '{}')", i, MARKER.repeat(50)),
+        );
+    }
+    let expected = count_rows(&tmp_db, &conn);
+    limbo_exec_rows(&tmp_db, &conn, &format!("PRAGMA rekey = \"{}\"", OTHER_KEY));
+    assert_eq!(count_rows(&tmp_db, &conn), expected);
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (100, 'after rekey')");
+    conn.close().unwrap();
+    drop(tmp_db);
+
+    let tmp_db = TempDatabase::new_with_existent(&path);
+    let conn = tmp_db.connect_limbo();
+    assert!(conn.set_encryption_key(KEY).is_err());
+    conn.set_encryption_key(OTHER_KEY).unwrap();
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT y FROM t WHERE x = 100"),
+        vec![vec![Value::Text("after rekey".to_string())]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(101)]]
+    );
+}
+
+#[test]
+fn test_only_empty_database_can_be_encrypted() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    assert!(matches!(
+        conn.set_encryption_key(KEY),
+        Err(LimboError::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        conn.rekey(KEY),
+        Err(LimboError::InvalidArgument(_))
+    ));
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT * FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+}
+
+#[test]
+fn test_tampered_page_is_detected() {
+    maybe_setup_tracing();
+    let path = new_path();
+    let tmp_db = open_with_key(&path, KEY);
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    for i in 0..20 {
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            &format!("INSERT INTO t VALUES ({}, '{}')", i, MARKER.repeat(10)),
+        );
+    }
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA wal_checkpoint");
+    conn.close().unwrap();
+    drop(tmp_db);
+    std::fs::remove_file(format!("{}-wal", path.to_str().unwrap())).unwrap();
+
+    // Flip a bit of page 2, the root page of t.
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[4096 + 100] ^= 1;
+    std::fs::write(&path, bytes).unwrap();
+
+    let tmp_db = open_with_key(&path, KEY);
+    let conn = tmp_db.connect_limbo();
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "SELECT count(*) FROM t").is_err());
+}
+
+/// Copies a database and the first `frames` frames of its WAL as they are on disk, as if the
+/// process crashed.
+fn copy_files(path: &Path, frames: usize) -> PathBuf {
+    let copy = new_path();
+    std::fs::copy(path, &copy).unwrap();
+    let wal = std::fs::read(format!("{}-wal", path.to_str().unwrap())).unwrap();
+    let len = 32 + frames * (24 + 4096);
+    std::fs::write(format!("{}-wal", copy.to_str().unwrap()), &wal[..len]).unwrap();
+    copy
+}
+
+#[test]
+fn test_rekey_crash_recovery() {
+    maybe_setup_tracing();
+    let path = new_path();
+    let tmp_db = open_with_key(&path, KEY);
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    for i in 0..100 {
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            &format!("INSERT INTO t VALUES ({}, '{}')", i, MARKER.repeat(50)),
+        );
+    }
+    let expected = count_rows(&tmp_db, &conn);
+    limbo_exec_rows(&tmp_db, &conn, &format!("PRAGMA rekey = \"{}\"", OTHER_KEY));
+    // The rekey starts the WAL over with one frame per page, the last one being the commit frame.
+    let pages = std::fs::metadata(&path).unwrap().len() as usize / 4096;
+
+    // A crash after the commit frame reached the WAL: the database uses the new key.
+    let committed = copy_files(&path, pages);
+    // A crash before the commit frame was written: the database still uses the old key.
+    let uncommitted = copy_files(&path, pages - 1);
+    for (copy, key, wrong_key) in [(committed, OTHER_KEY, KEY), (uncommitted, KEY, OTHER_KEY)] {
+        let tmp_db = TempDatabase::new_with_existent(&copy);
+        let conn = tmp_db.connect_limbo();
+        assert!(matches!(
+            conn.set_encryption_key(wrong_key),
+            Err(LimboError::NotADB)
+        ));
+        conn.set_encryption_key(key).unwrap();
+        assert_eq!(count_rows(&tmp_db, &conn), expected);
+    }
+}
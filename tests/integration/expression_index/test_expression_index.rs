@@ -0,0 +1,207 @@
+#![cfg(feature = "index_experimental")]
+
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, sqlite_index_entries, TempDatabase};
+use rusqlite::types::Value;
+use tempfile::TempDir;
+
+fn int(i: i64) -> Vec<Value> {
+    vec![Value::Integer(i)]
+}
+
+#[test]
+fn test_expression_index_maintenance() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO users VALUES (1, 'Alice@X.com'), (2, 'bob@x.com'), (3, 'CAROL@x.com')",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE INDEX users_email ON users (lower(email))",
+    );
+
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO users VALUES (4, 'Dave@X.com')");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "UPDATE users SET email = 'Bobby@x.com' WHERE id = 2",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM users WHERE id = 3");
+
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM users WHERE lower(email) = 'bobby@x.com'"
+        ),
+        vec![int(2)]
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM users WHERE lower(email) = 'bob@x.com'"
+        ),
+        Vec::<Vec<Value>>::new()
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM users WHERE lower(email) > 'b' ORDER BY lower(email) DESC"
+        ),
+        vec![int(4), int(2)]
+    );
+    assert_eq!(
+        sqlite_index_entries(
+            &tmp_db,
+            "SELECT lower(email) FROM users INDEXED BY users_email WHERE lower(email) > ''"
+        ),
+        vec![
+            "alice@x.com".to_string(),
+            "bobby@x.com".to_string(),
+            "dave@x.com".to_string()
+        ]
+    );
+}
+
+#[test]
+fn test_unique_expression_index() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE docs (doc TEXT)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE UNIQUE INDEX docs_id ON docs (json_extract(doc, '$.id'))",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        r#"INSERT INTO docs VALUES ('{"id": 1}'), ('{"id": 2}')"#,
+    );
+    assert!(limbo_exec_rows_error(
+        &tmp_db,
+        &conn,
+        r#"INSERT INTO docs VALUES ('{"id": 1, "x": 0}')"#
+    )
+    .is_err());
+    assert!(limbo_exec_rows_error(
+        &tmp_db,
+        &conn,
+        r#"UPDATE docs SET doc = '{"id": 2}' WHERE json_extract(doc, '$.id') = 1"#
+    )
+    .is_err());
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT json_extract(doc, '$.id') FROM docs WHERE json_extract(doc, '$.id') >= 1"
+        ),
+        vec![int(1), int(2)]
+    );
+}
+
+#[test]
+fn test_expression_index_invalid_expression() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (a, b)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE u (c)");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "CREATE INDEX i ON t (a + c)").is_err());
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "CREATE INDEX i ON t (random())").is_err());
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "CREATE INDEX i ON t (a + ?)").is_err());
+    assert!(
+        limbo_exec_rows_error(&tmp_db, &conn, "CREATE INDEX i ON t ((SELECT c FROM u))").is_err()
+    );
+}
+
+#[test]
+fn test_expression_index_created_by_sqlite() {
+    let mut path = TempDir::new().unwrap().keep();
+    path.push("test.db");
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.pragma_update(None, "journal_mode", "wal").unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (a, b);
+             CREATE INDEX t_sum ON t (a + b, a DESC);
+             INSERT INTO t VALUES (1, 5), (2, 15), (3, 25);",
+        )
+        .unwrap();
+    }
+
+    let tmp_db = TempDatabase::new_with_existent(&path);
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (4, 13), (5, 30)");
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET b = 100 WHERE a = 1");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t WHERE a = 3");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT a FROM t WHERE a + b = 17"),
+        vec![int(4), int(2)]
+    );
+    conn.close().unwrap();
+    assert_eq!(
+        sqlite_index_entries(
+            &tmp_db,
+            "SELECT CAST(a AS TEXT) FROM t INDEXED BY t_sum WHERE a + b > 0"
+        ),
+        vec![
+            "4".to_string(),
+            "2".to_string(),
+            "5".to_string(),
+            "1".to_string()
+        ]
+    );
+}
+
+#[test]
+fn test_alter_table_with_expression_index() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (a, b)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_lower ON t (lower(b))");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1, 'X'), (2, 'y')");
+
+    limbo_exec_rows(&tmp_db, &conn, "ALTER TABLE t RENAME COLUMN b TO name");
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT sql FROM sqlite_schema WHERE name = 't_lower'"
+        ),
+        vec![vec![Value::Text(
+            "CREATE INDEX t_lower ON t (lower (name))".to_string()
+        )]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (3, 'Z')");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT a FROM t WHERE lower(name) = 'z'"),
+        vec![int(3)]
+    );
+
+    // The index would be left with an expression on a column that no longer exists.
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "ALTER TABLE t DROP COLUMN name").is_err());
+    limbo_exec_rows(&tmp_db, &conn, "ALTER TABLE t ADD COLUMN c");
+    limbo_exec_rows(&tmp_db, &conn, "ALTER TABLE t DROP COLUMN c");
+    conn.close().unwrap();
+    assert_eq!(
+        sqlite_index_entries(
+            &tmp_db,
+            "SELECT name FROM t INDEXED BY t_lower WHERE lower(name) > ''"
+        ),
+        vec!["X".to_string(), "y".to_string(), "Z".to_string()]
+    );
+}
@@ -0,0 +1,155 @@
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, TempDatabase};
+use limbo_core::UpdateKind;
+use rusqlite::types::Value;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+#[test]
+fn test_update_hook() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    conn.update_hook(Some(Box::new({
+        let changes = changes.clone();
+        move |kind, db: &str, table: &str, rowid| {
+            changes
+                .borrow_mut()
+                .push((kind, db.to_string(), table.to_string(), rowid))
+        }
+    })));
+
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1, 'a'), (2, 'b')");
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET y = 'c' WHERE x = 2");
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET x = 3 WHERE x = 1");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t WHERE x = 2");
+    // Schema changes are not reported.
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE u (x)");
+    limbo_exec_rows(&tmp_db, &conn, "DROP TABLE u");
+
+    let expected = [
+        (UpdateKind::Insert, 1),
+        (UpdateKind::Insert, 2),
+        (UpdateKind::Update, 2),
+        (UpdateKind::Update, 3),
+        (UpdateKind::Delete, 2),
+    ]
+    .map(|(kind, rowid)| (kind, "main".to_string(), "t".to_string(), rowid));
+    assert_eq!(*changes.borrow(), expected);
+
+    assert!(conn.update_hook(None).is_some());
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (4, 'd')");
+    assert_eq!(changes.borrow().len(), expected.len());
+}
+
+#[test]
+fn test_commit_hook() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x INTEGER PRIMARY KEY)");
+
+    let commits = Rc::new(Cell::new(0));
+    conn.commit_hook(Some(Box::new({
+        let commits = commits.clone();
+        move || {
+            commits.set(commits.get() + 1);
+            false
+        }
+    })));
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (2)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (3)");
+    limbo_exec_rows(&tmp_db, &conn, "COMMIT");
+    // Read-only statements don't commit anything.
+    limbo_exec_rows(&tmp_db, &conn, "SELECT * FROM t");
+    assert_eq!(commits.get(), 2);
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(3)]]
+    );
+}
+
+#[test]
+fn test_commit_hook_veto() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x INTEGER PRIMARY KEY)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+
+    let rollbacks = Rc::new(Cell::new(0));
+    conn.rollback_hook(Some(Box::new({
+        let rollbacks = rollbacks.clone();
+        move || rollbacks.set(rollbacks.get() + 1)
+    })));
+    conn.commit_hook(Some(Box::new(|| true)));
+
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "INSERT INTO t VALUES (2)").is_err());
+    assert_eq!(rollbacks.get(), 1);
+
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE u (x)");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "COMMIT").is_err());
+    assert_eq!(rollbacks.get(), 2);
+    assert!(conn.get_auto_commit());
+
+    conn.commit_hook(None);
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+    assert!(conn.prepare("SELECT * FROM u").is_err());
+
+    // The rolled back changes are gone for other connections too.
+    let other = tmp_db.connect_limbo();
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &other, "SELECT x FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+}
+
+#[test]
+fn test_rollback() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x INTEGER PRIMARY KEY)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+
+    let rollbacks = Rc::new(Cell::new(0));
+    conn.rollback_hook(Some(Box::new({
+        let rollbacks = rollbacks.clone();
+        move || rollbacks.set(rollbacks.get() + 1)
+    })));
+
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (2)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE u (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO u VALUES (1)");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(2)]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "ROLLBACK");
+    assert_eq!(rollbacks.get(), 1);
+    assert!(conn.get_auto_commit());
+
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+    assert!(conn.prepare("SELECT * FROM u").is_err());
+
+    // The connection keeps working after the rollback.
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (3)");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM t"),
+        vec![vec![Value::Integer(1)], vec![Value::Integer(3)]]
+    );
+    assert_eq!(rollbacks.get(), 1);
+}
@@ -0,0 +1,179 @@
+#![cfg(feature = "index_experimental")]
+
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, TempDatabase};
+use rusqlite::types::Value;
+use std::sync::Arc;
+
+fn ints(values: &[i64]) -> Vec<Vec<Value>> {
+    values.iter().map(|&i| vec![Value::Integer(i)]).collect()
+}
+
+/// Creates a table whose rowid order differs from the order of its indexes, so that the order
+/// of the rows returned by a query without ORDER BY shows how the table was accessed.
+fn create_table(tmp_db: &TempDatabase, conn: &Arc<limbo_core::Connection>) {
+    limbo_exec_rows(
+        tmp_db,
+        conn,
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, a INTEGER, b INTEGER)",
+    );
+    limbo_exec_rows(
+        tmp_db,
+        conn,
+        "INSERT INTO t VALUES (1, 1, 3), (2, 1, 1), (3, 1, 2), (4, 2, 4)",
+    );
+    limbo_exec_rows(tmp_db, conn, "CREATE INDEX t_a ON t (a)");
+    limbo_exec_rows(tmp_db, conn, "CREATE INDEX t_b ON t (b)");
+    limbo_exec_rows(
+        tmp_db,
+        conn,
+        "CREATE INDEX t_b_partial ON t (b) WHERE a = 2",
+    );
+}
+
+#[test]
+fn test_indexed_by() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    // The index on b is used even though it has no usable constraint.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM t INDEXED BY t_b"),
+        ints(&[2, 3, 1, 4])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM t INDEXED BY t_b WHERE a = 1"
+        ),
+        ints(&[2, 3, 1])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM t INDEXED BY t_b_partial WHERE a = 2 AND b > 0"
+        ),
+        ints(&[4])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT t1.id, t2.id FROM t AS t1 JOIN t AS t2 INDEXED BY t_a ON t2.a = t1.b WHERE t1.a = 1"
+        ),
+        vec![
+            vec![Value::Integer(2), Value::Integer(1)],
+            vec![Value::Integer(2), Value::Integer(2)],
+            vec![Value::Integer(2), Value::Integer(3)],
+            vec![Value::Integer(3), Value::Integer(4)],
+        ]
+    );
+}
+
+#[test]
+fn test_not_indexed() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM t WHERE b > 0"),
+        ints(&[2, 3, 1, 4])
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM t NOT INDEXED WHERE b > 0"),
+        ints(&[1, 2, 3, 4])
+    );
+    // A rowid lookup is still allowed.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT b FROM t NOT INDEXED WHERE id = 3"),
+        ints(&[2])
+    );
+}
+
+#[test]
+fn test_index_hint_errors() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE u (x INTEGER)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX u_x ON u (x)");
+
+    for (query, expected_error) in [
+        ("SELECT * FROM t INDEXED BY nope", "no such index: nope"),
+        ("SELECT * FROM t INDEXED BY u_x", "no such index: u_x"),
+        // The partial index can't be used unless the query implies its WHERE clause.
+        (
+            "SELECT * FROM t INDEXED BY t_b_partial WHERE b > 0",
+            "no query solution",
+        ),
+        (
+            "DELETE FROM t INDEXED BY nope WHERE a = 1",
+            "no such index: nope",
+        ),
+        (
+            "UPDATE t INDEXED BY nope SET b = 0 WHERE a = 1",
+            "no such index: nope",
+        ),
+    ] {
+        let error = limbo_exec_rows_error(&tmp_db, &conn, query).unwrap_err();
+        assert!(
+            error.to_string().contains(expected_error),
+            "{query}: unexpected error {error}"
+        );
+    }
+
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "UPDATE t INDEXED BY t_a SET b = 0 WHERE a = 2",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t NOT INDEXED WHERE id = 1");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT b FROM t NOT INDEXED"),
+        ints(&[1, 2, 0])
+    );
+}
+
+#[test]
+fn test_likelihood_hints() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    // Without hints, the equality on a is assumed to be the most selective constraint.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM t WHERE a = 1 AND b > 0"),
+        ints(&[1, 2, 3])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM t WHERE a = 1 AND likelihood(b > 0, 0.001)"
+        ),
+        ints(&[2, 3, 1])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM t WHERE likely(a = 1) AND unlikely(b > 0)"
+        ),
+        ints(&[2, 3, 1])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM t WHERE unlikely(a = 1) AND likely(b > 0)"
+        ),
+        ints(&[1, 2, 3])
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT unlikely(42)"),
+        ints(&[42])
+    );
+}
@@ -0,0 +1,139 @@
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, TempDatabase};
+use limbo_core::{LimboError, StepResult};
+use rusqlite::types::Value;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Creates a table of 200 rows that each take about one page.
+fn create_table(tmp_db: &TempDatabase, conn: &std::sync::Arc<limbo_core::Connection>) {
+    limbo_exec_rows(tmp_db, conn, "CREATE TABLE t (id INTEGER PRIMARY KEY, x)");
+    for _ in 0..4 {
+        let values = (0..50)
+            .map(|_| "(NULL, randomblob(3000))")
+            .collect::<Vec<_>>();
+        limbo_exec_rows(
+            tmp_db,
+            conn,
+            &format!("INSERT INTO t VALUES {}", values.join(", ")),
+        );
+    }
+}
+
+#[test]
+fn test_memory_used_and_shrink_memory() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT sum(length(x)) FROM t"),
+        vec![vec![Value::Integer(600000)]]
+    );
+    let used = conn.memory_used();
+    assert!(used >= 200 * PAGE_SIZE, "used {used}");
+    assert!(conn.memory_highwater(false) >= used);
+
+    let released = conn.shrink_memory();
+    assert!(released > 0);
+    assert_eq!(conn.memory_used(), used - released);
+    assert!(conn.memory_used() < 10 * PAGE_SIZE);
+
+    // The connection still works once its memory has been released.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(200)]]
+    );
+}
+
+#[test]
+fn test_soft_heap_limit_evicts_pages() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+    conn.shrink_memory();
+
+    let limit = 20 * PAGE_SIZE;
+    assert_eq!(conn.set_soft_heap_limit(limit), 0);
+    assert_eq!(conn.soft_heap_limit(), limit);
+    let evictions = conn.page_cache_stats().evictions;
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT sum(length(x)) FROM t"),
+        vec![vec![Value::Integer(600000)]]
+    );
+    assert!(conn.page_cache_stats().evictions > evictions);
+    // The pages in use by the statement can take the connection slightly over the limit.
+    assert!(conn.memory_used() <= limit + 4 * PAGE_SIZE);
+}
+
+#[test]
+fn test_hard_heap_limit_fails_allocations() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+    conn.shrink_memory();
+
+    conn.set_hard_heap_limit(conn.memory_used() + 64 * 1024);
+    // The hard limit also caps the soft limit, so scans evict pages and succeed.
+    assert!(conn.soft_heap_limit() > 0);
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(200)]]
+    );
+    // Sorting the table needs more memory than the limit allows.
+    assert!(matches!(
+        limbo_exec_rows_error(&tmp_db, &conn, "SELECT id FROM t ORDER BY x"),
+        Err(LimboError::OutOfMemory)
+    ));
+
+    conn.set_hard_heap_limit(0);
+    conn.set_soft_heap_limit(0);
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT count(*) FROM (SELECT id FROM t ORDER BY x)"
+        ),
+        vec![vec![Value::Integer(200)]]
+    );
+}
+
+#[test]
+fn test_heap_limit_pragmas() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA hard_heap_limit"),
+        vec![vec![Value::Integer(0)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA soft_heap_limit = 1073741824"),
+        vec![vec![Value::Integer(1073741824)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA soft_heap_limit"),
+        vec![vec![Value::Integer(1073741824)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA soft_heap_limit = 0"),
+        vec![vec![Value::Integer(0)]]
+    );
+
+    // The pragma shrinks memory when it runs, not when it is prepared, so a prepared one
+    // shrinks it every time.
+    let mut stmt = conn.prepare("PRAGMA shrink_memory").unwrap();
+    for _ in 0..2 {
+        limbo_exec_rows(&tmp_db, &conn, "SELECT sum(length(x)) FROM t");
+        let used = conn.memory_used();
+        loop {
+            match stmt.step().unwrap() {
+                StepResult::IO => tmp_db.io.run_once().unwrap(),
+                StepResult::Done => break,
+                r => panic!("unexpected result {:?}", r),
+            }
+        }
+        assert!(conn.memory_used() < used);
+        stmt.reset();
+    }
+}
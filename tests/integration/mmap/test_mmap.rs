@@ -0,0 +1,81 @@
+use crate::common::{limbo_exec_rows, TempDatabase};
+use rusqlite::types::Value;
+
+fn insert_rows(tmp_db: &TempDatabase, conn: &std::sync::Arc<limbo_core::Connection>, n: usize) {
+    let values = (0..n)
+        .map(|_| "(NULL, randomblob(1000))")
+        .collect::<Vec<_>>();
+    limbo_exec_rows(
+        tmp_db,
+        conn,
+        &format!("INSERT INTO t VALUES {}", values.join(", ")),
+    );
+}
+
+#[test]
+fn test_mmap_size_pragma() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA mmap_size"),
+        vec![vec![Value::Integer(0)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA mmap_size = 1048576"),
+        vec![vec![Value::Integer(1048576)]]
+    );
+    // Negative sizes leave the limit unchanged.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA mmap_size = -1"),
+        vec![vec![Value::Integer(1048576)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA mmap_size = 1099511627776"),
+        vec![vec![Value::Integer(0x7fff0000)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA mmap_size = 0"),
+        vec![vec![Value::Integer(0)]]
+    );
+}
+
+#[test]
+fn test_mmap_reads_follow_checkpoints() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (id INTEGER PRIMARY KEY, x)");
+    insert_rows(&tmp_db, &conn, 50);
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA wal_checkpoint");
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA mmap_size = 268435456");
+
+    let reader = tmp_db.connect_limbo();
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &reader, "SELECT count(*), sum(length(x)) FROM t"),
+        vec![vec![Value::Integer(50), Value::Integer(50000)]]
+    );
+
+    // Pages appended to the database file by a checkpoint are read past the old mapping.
+    insert_rows(&tmp_db, &conn, 100);
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET x = zeroblob(10) WHERE id = 1");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &reader, "SELECT count(*), sum(length(x)) FROM t"),
+        vec![vec![Value::Integer(150), Value::Integer(149010)]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA wal_checkpoint");
+    let reader = tmp_db.connect_limbo();
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &reader, "SELECT count(*), sum(length(x)) FROM t"),
+        vec![vec![Value::Integer(150), Value::Integer(149010)]]
+    );
+
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA mmap_size = 0");
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &reader,
+            "SELECT max(id), length(x) FROM t WHERE id = 1"
+        ),
+        vec![vec![Value::Integer(1), Value::Integer(10)]]
+    );
+}
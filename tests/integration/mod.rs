@@ -1,5 +1,22 @@
+mod autoincrement;
+mod backup;
+mod blob;
 mod common;
+mod compression;
+mod encryption;
+mod expression_index;
 mod functions;
 mod fuzz;
+mod hooks;
+mod index_hints;
+mod memory;
+mod mmap;
+mod partial_index;
+mod progress;
 mod query_processing;
+mod replication;
+mod serialize;
+mod session;
+mod statement_cache;
+mod temp;
 mod wal;
@@ -0,0 +1,235 @@
+#![cfg(feature = "index_experimental")]
+
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, sqlite_index_entries, TempDatabase};
+use rusqlite::types::Value;
+use tempfile::TempDir;
+
+fn text(s: &str) -> Vec<Value> {
+    vec![Value::Text(s.to_string())]
+}
+
+#[test]
+fn test_partial_index_maintenance() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, a TEXT, active INTEGER)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t VALUES (1, 'x', 1), (2, 'y', 0), (3, 'z', 1), (4, 'w', NULL)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE INDEX t_active_a ON t (a) WHERE active = 1",
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT sql FROM sqlite_schema WHERE name = 't_active_a'"
+        ),
+        vec![text("CREATE INDEX t_active_a ON t (a) WHERE active = 1")]
+    );
+
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t VALUES (5, 'v', 1), (6, 'u', 2)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET active = 1 WHERE id = 2");
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET active = 0 WHERE id = 3");
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET a = 'xx' WHERE id = 1");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t WHERE id IN (4, 5)");
+
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT a FROM t WHERE a > '' AND active = 1"
+        ),
+        vec![text("xx"), text("y")]
+    );
+    assert_eq!(
+        sqlite_index_entries(
+            &tmp_db,
+            "SELECT a FROM t INDEXED BY t_active_a WHERE active = 1 AND a > ''"
+        ),
+        vec!["xx".to_string(), "y".to_string()]
+    );
+}
+
+#[test]
+fn test_partial_index_used_only_when_implied() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (a, b)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t VALUES (1, NULL), (2, 'b'), (3, NULL), (4, 'd')",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE INDEX t_a ON t (a) WHERE b IS NOT NULL",
+    );
+
+    // The index only has the rows where b is not NULL, so it can't answer these.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT b FROM t WHERE a > 0 ORDER BY a"),
+        vec![vec![Value::Null], text("b"), vec![Value::Null], text("d")]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t WHERE a = 3"),
+        vec![vec![Value::Integer(1)]]
+    );
+
+    // A comparison on b implies that b is not NULL.
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT a FROM t WHERE a > 1 AND b < 'z' ORDER BY a"
+        ),
+        vec![vec![Value::Integer(2)], vec![Value::Integer(4)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT a FROM t WHERE b IS NOT NULL AND a = 4"
+        ),
+        vec![vec![Value::Integer(4)]]
+    );
+}
+
+#[test]
+fn test_unique_partial_index() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (name, deleted)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE UNIQUE INDEX t_name ON t (name) WHERE deleted = 0",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES ('a', 0), ('a', 1)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES ('a', 1)");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "INSERT INTO t VALUES ('a', 0)").is_err());
+
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET deleted = 1 WHERE deleted = 0");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES ('a', 0)");
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT count(*) FROM t WHERE name = 'a' AND deleted = 0"
+        ),
+        vec![vec![Value::Integer(1)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t WHERE name = 'a'"),
+        vec![vec![Value::Integer(4)]]
+    );
+}
+
+#[test]
+fn test_partial_index_invalid_where_clause() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (a, b)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE u (c)");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "CREATE INDEX i ON t (a) WHERE c = 1").is_err());
+    assert!(limbo_exec_rows_error(
+        &tmp_db,
+        &conn,
+        "CREATE INDEX i ON t (a) WHERE b IN (SELECT c FROM u)"
+    )
+    .is_err());
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "CREATE INDEX i ON t (a) WHERE b = ?").is_err());
+}
+
+#[test]
+fn test_partial_index_created_by_sqlite() {
+    let mut path = TempDir::new().unwrap().keep();
+    path.push("test.db");
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.pragma_update(None, "journal_mode", "wal").unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (a, b);
+             CREATE INDEX t_a ON t (a) WHERE b BETWEEN 10 AND 20;
+             INSERT INTO t VALUES (1, 5), (2, 15), (3, 25);",
+        )
+        .unwrap();
+    }
+
+    let tmp_db = TempDatabase::new_with_existent(&path);
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (4, 10), (5, 30)");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t WHERE a = 2");
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT a FROM t WHERE a > 0 AND b BETWEEN 10 AND 20"
+        ),
+        vec![vec![Value::Integer(4)]]
+    );
+    conn.close().unwrap();
+    assert_eq!(
+        sqlite_index_entries(
+            &tmp_db,
+            "SELECT CAST(a AS TEXT) FROM t INDEXED BY t_a WHERE b BETWEEN 10 AND 20 AND a > 0"
+        ),
+        vec!["4".to_string()]
+    );
+}
+
+#[test]
+fn test_alter_table_with_partial_index() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (a, b, c)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_active ON t (a) WHERE b > 0");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t VALUES ('x', 1, 0), ('y', 0, 0)",
+    );
+
+    limbo_exec_rows(&tmp_db, &conn, "ALTER TABLE t RENAME COLUMN b TO active");
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT sql FROM sqlite_schema WHERE name = 't_active'"
+        ),
+        vec![vec![Value::Text(
+            "CREATE INDEX t_active ON t (a) WHERE active > 0".to_string()
+        )]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES ('z', 2, 0)");
+
+    // The WHERE clause of the index would refer to a column that no longer exists.
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "ALTER TABLE t DROP COLUMN active").is_err());
+    limbo_exec_rows(&tmp_db, &conn, "ALTER TABLE t DROP COLUMN c");
+    conn.close().unwrap();
+    assert_eq!(
+        sqlite_index_entries(
+            &tmp_db,
+            "SELECT a FROM t INDEXED BY t_active WHERE active > 0"
+        ),
+        vec!["x".to_string(), "z".to_string()]
+    );
+}
@@ -0,0 +1,214 @@
+use crate::common::{limbo_exec_rows, TempDatabase};
+use limbo_core::{Statement, StepResult};
+use rusqlite::types::Value;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SLOW_QUERY: &str = "SELECT count(*) FROM t a, t b, t c";
+
+fn create_table(tmp_db: &TempDatabase, conn: &std::sync::Arc<limbo_core::Connection>) {
+    limbo_exec_rows(tmp_db, conn, "CREATE TABLE t (x)");
+    let values = (0..50).map(|i| format!("({i})")).collect::<Vec<_>>();
+    limbo_exec_rows(
+        tmp_db,
+        conn,
+        &format!("INSERT INTO t VALUES {}", values.join(", ")),
+    );
+}
+
+/// Steps `stmt` until it is done or interrupted, skipping its rows.
+fn run(tmp_db: &TempDatabase, stmt: &mut Statement) -> StepResult {
+    loop {
+        match stmt.step().unwrap() {
+            StepResult::IO => tmp_db.io.run_once().unwrap(),
+            StepResult::Row => {}
+            result => return result,
+        }
+    }
+}
+
+fn exec(tmp_db: &TempDatabase, conn: &Arc<limbo_core::Connection>, sql: &str) -> StepResult {
+    run(tmp_db, &mut conn.prepare(sql).unwrap())
+}
+
+fn count(tmp_db: &TempDatabase, conn: &std::sync::Arc<limbo_core::Connection>) -> Vec<Vec<Value>> {
+    limbo_exec_rows(tmp_db, conn, "SELECT count(*) FROM t")
+}
+
+#[test]
+fn test_progress_handler() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    let calls = Rc::new(Cell::new(0));
+    conn.progress_handler(
+        100,
+        Some(Box::new({
+            let calls = calls.clone();
+            move || {
+                calls.set(calls.get() + 1);
+                calls.get() == 10
+            }
+        })),
+    );
+    assert!(matches!(
+        exec(&tmp_db, &conn, SLOW_QUERY),
+        StepResult::Interrupt
+    ));
+    assert_eq!(calls.get(), 10);
+
+    // A handler that never interrupts is called while the statement runs.
+    conn.progress_handler(1000, Some(Box::new(|| false)));
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, SLOW_QUERY),
+        vec![vec![Value::Integer(125000)]]
+    );
+    conn.progress_handler(0, Some(Box::new(|| true)));
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, SLOW_QUERY),
+        vec![vec![Value::Integer(125000)]]
+    );
+}
+
+#[test]
+fn test_progress_handler_rolls_back_writes() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+    let insert = "INSERT INTO t SELECT a.x FROM t a, t b";
+
+    let interrupt = Rc::new(Cell::new(true));
+    conn.progress_handler(
+        1000,
+        Some(Box::new({
+            let interrupt = interrupt.clone();
+            move || interrupt.get()
+        })),
+    );
+    assert!(matches!(
+        exec(&tmp_db, &conn, insert),
+        StepResult::Interrupt
+    ));
+    interrupt.set(false);
+    assert_eq!(count(&tmp_db, &conn), vec![vec![Value::Integer(50)]]);
+
+    // An interrupted write rolls back the whole transaction.
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (50)");
+    interrupt.set(true);
+    assert!(matches!(
+        exec(&tmp_db, &conn, insert),
+        StepResult::Interrupt
+    ));
+    interrupt.set(false);
+    assert!(conn.get_auto_commit());
+    assert_eq!(count(&tmp_db, &conn), vec![vec![Value::Integer(50)]]);
+
+    // The locks were released.
+    let other = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &other, "INSERT INTO t VALUES (51)");
+    assert_eq!(count(&tmp_db, &conn), vec![vec![Value::Integer(51)]]);
+}
+
+#[test]
+fn test_statement_timeout() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    conn.statement_timeout(Duration::from_micros(1));
+    assert_eq!(conn.get_statement_timeout(), Duration::from_micros(1));
+    assert!(matches!(
+        exec(&tmp_db, &conn, SLOW_QUERY),
+        StepResult::Interrupt
+    ));
+
+    // A statement can lift the timeout of its connection.
+    let mut stmt = conn.prepare(SLOW_QUERY).unwrap();
+    stmt.set_timeout(Duration::ZERO);
+    let mut rows = 0;
+    loop {
+        match stmt.step().unwrap() {
+            limbo_core::StepResult::Row => rows += 1,
+            limbo_core::StepResult::IO => tmp_db.io.run_once().unwrap(),
+            limbo_core::StepResult::Done => break,
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+    assert_eq!(rows, 1);
+
+    conn.statement_timeout(Duration::from_secs(600));
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, SLOW_QUERY),
+        vec![vec![Value::Integer(125000)]]
+    );
+    conn.statement_timeout(Duration::ZERO);
+    assert_eq!(conn.get_statement_timeout(), Duration::ZERO);
+}
+
+#[test]
+fn test_interrupted_statement_stays_interrupted() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    // The progress handler interrupts a statement like Statement::interrupt does: it keeps
+    // returning Interrupt until it is reset.
+    conn.progress_handler(100, Some(Box::new(|| true)));
+    let mut by_handler = conn.prepare(SLOW_QUERY).unwrap();
+    assert!(matches!(
+        run(&tmp_db, &mut by_handler),
+        StepResult::Interrupt
+    ));
+    conn.progress_handler(0, None);
+    assert!(matches!(
+        run(&tmp_db, &mut by_handler),
+        StepResult::Interrupt
+    ));
+
+    let mut by_statement = conn.prepare(SLOW_QUERY).unwrap();
+    by_statement.interrupt();
+    assert!(matches!(
+        run(&tmp_db, &mut by_statement),
+        StepResult::Interrupt
+    ));
+    assert!(matches!(
+        run(&tmp_db, &mut by_statement),
+        StepResult::Interrupt
+    ));
+
+    for stmt in [&mut by_handler, &mut by_statement] {
+        stmt.reset();
+        assert!(matches!(run(&tmp_db, stmt), StepResult::Done));
+    }
+}
+
+#[test]
+fn test_progress_handler_replaced_by_itself() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    create_table(&tmp_db, &conn);
+
+    // The handler may change the handler of its connection while it runs.
+    let calls = Rc::new(Cell::new(0));
+    conn.progress_handler(
+        100,
+        Some(Box::new({
+            let conn = Arc::downgrade(&conn);
+            let calls = calls.clone();
+            move || {
+                calls.set(calls.get() + 1);
+                conn.upgrade().unwrap().progress_handler(0, None);
+                false
+            }
+        })),
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, SLOW_QUERY),
+        vec![vec![Value::Integer(125000)]]
+    );
+    assert_eq!(calls.get(), 1);
+}
@@ -1,2 +1,8 @@
+mod test_hash_join;
+mod test_like_prefix;
+mod test_min_max;
+mod test_multi_index_or;
 mod test_read_path;
+mod test_reindex;
+mod test_skip_scan;
 mod test_write_path;
@@ -0,0 +1,136 @@
+use crate::common::{limbo_exec_rows, TempDatabase};
+use rusqlite::types::Value;
+
+#[test]
+fn test_hash_join_keys() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t1 (id INTEGER PRIMARY KEY, a)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t2 (id INTEGER PRIMARY KEY, x)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t1 VALUES (1, 1), (2, NULL), (3, 2.0), (4, '1'), (5, 7)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t2 VALUES (10, 1.0), (11, 1), (12, NULL), (13, 2), (14, '1')",
+    );
+    let pairs = |pairs: &[(i64, Option<i64>)]| {
+        pairs
+            .iter()
+            .map(|&(id1, id2)| vec![Value::Integer(id1), id2.map_or(Value::Null, Value::Integer)])
+            .collect::<Vec<_>>()
+    };
+
+    // Integers and reals with the same value have the same key, text and NULL never match them.
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT t1.id, t2.id FROM t1 JOIN t2 ON t1.a = t2.x ORDER BY 1, 2"
+        ),
+        pairs(&[(1, Some(10)), (1, Some(11)), (3, Some(13)), (4, Some(14))])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT t1.id, t2.id FROM t1 JOIN t2 ON t1.a = t2.x WHERE t2.id > 10 ORDER BY 1, 2"
+        ),
+        pairs(&[(1, Some(11)), (3, Some(13)), (4, Some(14))])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT t1.id, t2.id FROM t1 LEFT JOIN t2 ON t1.a = t2.x ORDER BY 1, 2"
+        ),
+        pairs(&[
+            (1, Some(10)),
+            (1, Some(11)),
+            (2, None),
+            (3, Some(13)),
+            (4, Some(14)),
+            (5, None)
+        ])
+    );
+}
+
+#[test]
+fn test_hash_join_spills_with_small_cache() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t1 (id INTEGER PRIMARY KEY, a INT, s TEXT)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t2 (id INTEGER PRIMARY KEY, x INT, s TEXT)",
+    );
+    let t1 = (0..2000)
+        .map(|id| (id, id % 200))
+        .collect::<Vec<(i64, i64)>>();
+    let t2 = (0..2000)
+        .map(|id| (id, id % 300))
+        .collect::<Vec<(i64, i64)>>();
+    for (table, rows) in [("t1", &t1), ("t2", &t2)] {
+        let values = rows
+            .iter()
+            .map(|(id, key)| format!("({id}, {key}, '{}')", "s".repeat(100)))
+            .collect::<Vec<_>>();
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            &format!("INSERT INTO {table} VALUES {}", values.join(", ")),
+        );
+    }
+
+    // A ten page budget is far smaller than the build side, so most
+    // partitions have to be written to the spill file and read back.
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA cache_size = 10");
+
+    let (mut count, mut sum1, mut sum2, mut unmatched) = (0, 0, 0, 0);
+    for &(id1, a) in t1.iter() {
+        let matches = t2.iter().filter(|(_, x)| *x == a).collect::<Vec<_>>();
+        count += matches.len() as i64;
+        sum1 += id1 * matches.len() as i64;
+        sum2 += matches.iter().map(|(id2, _)| id2).sum::<i64>();
+        unmatched += matches.is_empty() as i64;
+    }
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT count(*), sum(t1.id), sum(t2.id) FROM t1 JOIN t2 ON t1.a = t2.x"
+        ),
+        vec![vec![
+            Value::Integer(count),
+            Value::Integer(sum1),
+            Value::Integer(sum2)
+        ]]
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT count(*), count(t2.id) FROM t1 LEFT JOIN t2 ON t1.a = t2.x"
+        ),
+        vec![vec![
+            Value::Integer(count + unmatched),
+            Value::Integer(count)
+        ]]
+    );
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "index_experimental")]
+
+use crate::common::{limbo_exec_rows, TempDatabase};
+use rusqlite::types::Value;
+
+fn ids(rows: Vec<Vec<Value>>) -> Vec<i64> {
+    rows.into_iter()
+        .map(|row| match row[0] {
+            Value::Integer(id) => id,
+            _ => panic!("expected an integer id, got {:?}", row[0]),
+        })
+        .collect()
+}
+
+#[test]
+fn test_like_prefix_searches_index() {
+    let db = TempDatabase::new_with_rusqlite(
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, name COLLATE NOCASE, code);",
+    );
+    {
+        let conn = rusqlite::Connection::open(db.path.clone()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO t VALUES
+                (1, 'abc', 'abc'), (2, 'ABD', 'ABD'), (3, 'a@', 'a@'), (4, 'a[', 'a['),
+                (5, 'b', 'b'), (6, 12, 12), (7, 'aé', 'aé'), (8, NULL, NULL);
+             CREATE INDEX t_name ON t (name);
+             CREATE INDEX t_code ON t (code);",
+        )
+        .unwrap();
+    }
+    let db = TempDatabase::new_with_existent(&db.path);
+    let conn = db.connect_limbo();
+
+    // The columns have no type, but the prefixes don't look like numbers, so both indexes are searched.
+    let like = "SELECT id FROM t WHERE name LIKE 'ab%' ORDER BY id";
+    let glob = "SELECT id FROM t WHERE code GLOB 'a*' ORDER BY id";
+    assert!(conn.prepare(like).unwrap().explain().contains("SeekGE"));
+    assert!(conn.prepare(glob).unwrap().explain().contains("SeekGE"));
+    assert_eq!(ids(limbo_exec_rows(&db, &conn, like)), vec![1, 2]);
+    assert_eq!(ids(limbo_exec_rows(&db, &conn, glob)), vec![1, 3, 4, 7]);
+    assert_eq!(
+        ids(limbo_exec_rows(
+            &db,
+            &conn,
+            "SELECT id FROM t WHERE name LIKE 'a@%' ORDER BY id"
+        )),
+        vec![3]
+    );
+
+    // A prefix that looks like a number can't be used on a column without TEXT affinity.
+    let numeric = "SELECT id FROM t WHERE code GLOB '12*'";
+    assert!(!conn.prepare(numeric).unwrap().explain().contains("SeekGE"));
+    assert_eq!(ids(limbo_exec_rows(&db, &conn, numeric)), vec![6]);
+
+    // With a case-sensitive LIKE, the NOCASE index on name can't be used, but the index on code can.
+    conn.execute("PRAGMA case_sensitive_like = ON").unwrap();
+    assert!(!conn.prepare(like).unwrap().explain().contains("SeekGE"));
+    assert_eq!(ids(limbo_exec_rows(&db, &conn, like)), vec![1]);
+    let code_like = "SELECT id FROM t WHERE code LIKE 'AB%' ORDER BY id";
+    assert!(conn
+        .prepare(code_like)
+        .unwrap()
+        .explain()
+        .contains("SeekGE"));
+    assert_eq!(ids(limbo_exec_rows(&db, &conn, code_like)), vec![2]);
+
+    conn.execute("PRAGMA case_sensitive_like = OFF").unwrap();
+    assert_eq!(ids(limbo_exec_rows(&db, &conn, code_like)), vec![1, 2]);
+}
@@ -0,0 +1,132 @@
+use crate::common::{limbo_exec_rows, TempDatabase};
+use rusqlite::types::Value;
+
+#[cfg(feature = "index_experimental")]
+#[test]
+fn test_min_max_index_seek() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, a INT, b INT, c INT)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t VALUES (3, NULL, 5, 1), (6, -2, NULL, 1), (9, 8, -1, 2), (12, 4, 7, 2), (15, NULL, NULL, 1)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_a ON t (a)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_b_desc ON t (b DESC)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_c_a ON t (c, a)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE empty (x INTEGER PRIMARY KEY, y INT)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX empty_y ON empty (y)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE nulls (x INT)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO nulls VALUES (NULL), (NULL)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX nulls_x ON nulls (x)");
+
+    let int = |value: i64| vec![vec![Value::Integer(value)]];
+    let null = || vec![vec![Value::Null]];
+    for (query, expected) in [
+        ("SELECT min(id) FROM t", int(3)),
+        ("SELECT max(rowid) FROM t", int(15)),
+        ("SELECT max(id) FROM t WHERE id < 10", int(9)),
+        // NULLs sort first in an ascending index and last in a descending one.
+        ("SELECT min(a) FROM t", int(-2)),
+        ("SELECT max(a) FROM t", int(8)),
+        ("SELECT min(b) FROM t", int(-1)),
+        ("SELECT max(b) FROM t", int(7)),
+        ("SELECT min(a) FROM t WHERE c = 1", int(-2)),
+        ("SELECT max(a) FROM t WHERE c = 2", int(8)),
+        ("SELECT min(a) FROM t WHERE a > 0", int(4)),
+        ("SELECT max(a) FROM t WHERE b > 0", int(4)),
+        ("SELECT max(a) + 1 FROM t", int(9)),
+        (
+            "SELECT m + 1 FROM (SELECT max(a) AS m FROM t WHERE c = 1)",
+            int(-1),
+        ),
+        ("SELECT min(a) FROM t WHERE a > 100", null()),
+        ("SELECT min(y) FROM empty", null()),
+        ("SELECT max(x) FROM empty", null()),
+        ("SELECT min(x) FROM nulls", null()),
+        ("SELECT max(x) FROM nulls", null()),
+    ] {
+        assert_eq!(
+            limbo_exec_rows(&tmp_db, &conn, query),
+            expected,
+            "query: {}",
+            query
+        );
+    }
+}
+
+#[test]
+fn test_count_star_with_limit_and_offset() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, a INT)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE empty (x)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t VALUES (1, NULL), (2, 5), (3, NULL), (4, 7)",
+    );
+
+    let int = |value: i64| vec![vec![Value::Integer(value)]];
+    for (query, expected) in [
+        ("SELECT count(*) FROM t", int(4)),
+        ("SELECT count(*) FROM empty", int(0)),
+        ("SELECT count(*) FROM t WHERE a IS NULL", int(2)),
+        ("SELECT count(*) FROM t LIMIT 1", int(4)),
+        // The single row of an aggregate is skipped by an offset, like any other row.
+        ("SELECT count(*) FROM t LIMIT 0", vec![]),
+        ("SELECT count(*) FROM t LIMIT 1 OFFSET 1", vec![]),
+        ("SELECT sum(id) FROM t LIMIT 1 OFFSET 1", vec![]),
+        ("SELECT count(*) FROM t LIMIT 1 OFFSET 0", int(4)),
+    ] {
+        assert_eq!(
+            limbo_exec_rows(&tmp_db, &conn, query),
+            expected,
+            "query: {}",
+            query
+        );
+    }
+}
+
+#[cfg(feature = "index_experimental")]
+#[test]
+fn test_min_max_ignore_nulls() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x INTEGER, y TEXT)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t VALUES (NULL, 'a'), (4, 'b'), (NULL, 'c'), (2, 'd'), (7, 'e')",
+    );
+
+    // Without an index on x, the aggregates are computed by a full scan.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT min(x), max(x) FROM t"),
+        vec![vec![Value::Integer(2), Value::Integer(7)]]
+    );
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_x ON t (x)");
+    // With an index on x, the other columns come from the row holding the minimum or maximum.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT min(x), y FROM t"),
+        vec![vec![Value::Integer(2), Value::Text("d".to_string())]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT max(x), y FROM t"),
+        vec![vec![Value::Integer(7), Value::Text("e".to_string())]]
+    );
+}
@@ -0,0 +1,113 @@
+#![cfg(feature = "index_experimental")]
+
+use crate::common::{limbo_exec_rows, TempDatabase};
+use rusqlite::types::Value;
+
+#[test]
+fn test_multi_index_or_visits_each_row_once() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (id INTEGER PRIMARY KEY, a INTEGER, b INTEGER)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t VALUES (1, 2, 1), (2, 1, 9), (3, 1, 1), (4, 5, 5)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_a ON t (a)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_b ON t (b)");
+
+    // The rows matching the first disjunct come first, in the order of the index on a,
+    // and the row matching both disjuncts is only returned once.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT id FROM t WHERE a = 1 OR b = 1"),
+        vec![
+            vec![Value::Integer(2)],
+            vec![Value::Integer(3)],
+            vec![Value::Integer(1)],
+        ]
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT id FROM t WHERE id = 4 OR b = 1 OR a = 1"
+        ),
+        vec![
+            vec![Value::Integer(4)],
+            vec![Value::Integer(1)],
+            vec![Value::Integer(3)],
+            vec![Value::Integer(2)],
+        ]
+    );
+}
+
+#[test]
+fn test_multi_index_or_join() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t1 (id INTEGER PRIMARY KEY, a INTEGER, b INTEGER, c INTEGER)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t2 (id INTEGER PRIMARY KEY, x INTEGER, y INTEGER)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t1 VALUES (1, 1, 2, 0), (2, 2, 1, 1), (3, 3, 3, 0), (4, NULL, 1, 1)",
+    );
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO t2 VALUES (10, 1, 1), (11, 3, 9), (12, 9, 9)",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t1_a ON t1 (a)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t1_b ON t1 (b)");
+    let pairs = |pairs: &[(i64, Option<i64>)]| {
+        pairs
+            .iter()
+            .map(|&(id2, id1)| vec![Value::Integer(id2), id1.map_or(Value::Null, Value::Integer)])
+            .collect::<Vec<_>>()
+    };
+
+    // Each row of t2 searches t1 once per disjunct, rows of t1 matching both are joined once.
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT t2.id, t1.id FROM t2 JOIN t1 ON t1.a = t2.x OR t1.b = t2.y ORDER BY 1, 2"
+        ),
+        pairs(&[(10, Some(1)), (10, Some(2)), (10, Some(4)), (11, Some(3))])
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT t2.id, t1.id FROM t2 LEFT JOIN t1 ON t1.a = t2.x OR t1.b = t2.y ORDER BY 1, 2"
+        ),
+        pairs(&[
+            (10, Some(1)),
+            (10, Some(2)),
+            (10, Some(4)),
+            (11, Some(3)),
+            (12, None)
+        ])
+    );
+    // The rest of the WHERE clause filters the rows found by the searches.
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT count(*) FROM t1 WHERE c = 1 AND (a = 1 OR b = 1)"
+        ),
+        vec![vec![Value::Integer(2)]]
+    );
+}
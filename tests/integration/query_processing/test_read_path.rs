@@ -1,4 +1,4 @@
-use crate::common::TempDatabase;
+use crate::common::{limbo_exec_rows, TempDatabase};
 use limbo_core::{StepResult, Value};
 
 #[test]
@@ -693,3 +693,27 @@ fn test_bind_parameters_delete_rowid_alias_seek_out_of_order() -> anyhow::Result
     assert_eq!(ins.parameters().count(), 4);
     Ok(())
 }
+
+#[test]
+fn test_count_with_evicted_pages() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (id INTEGER PRIMARY KEY, x)");
+    for _ in 0..4 {
+        let values = (0..50)
+            .map(|_| "(NULL, randomblob(3000))")
+            .collect::<Vec<_>>();
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            &format!("INSERT INTO t VALUES {}", values.join(", ")),
+        );
+    }
+
+    // Counting resumes after reading back the interior pages evicted while visiting leaves.
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA cache_size = 10");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![rusqlite::types::Value::Integer(200)]]
+    );
+}
@@ -0,0 +1,141 @@
+#![cfg(feature = "index_experimental")]
+
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, sqlite_exec_rows, TempDatabase};
+use rusqlite::types::Value;
+
+fn setup_reindex_db() -> TempDatabase {
+    let db = TempDatabase::new_with_rusqlite(
+        "CREATE TABLE t(id INTEGER PRIMARY KEY, a TEXT COLLATE NOCASE, b INT, c TEXT UNIQUE);",
+    );
+    {
+        let conn = rusqlite::Connection::open(db.path.clone()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE u(y TEXT);
+             INSERT INTO t VALUES (1, 'b1', 3, 'c1'), (2, 'B1', 17, 'c2'), (3, 'a2', 17, 'c3'), (4, 'D', 3, 'c4');
+             INSERT INTO u VALUES ('y1  '), ('y1'), ('y2 ');
+             CREATE INDEX ta ON t(a);
+             CREATE INDEX tbc ON t(b, c);
+             CREATE INDEX tb_partial ON t(b) WHERE b > 15;
+             CREATE INDEX uy ON u(y COLLATE RTRIM);",
+        )
+        .unwrap();
+    }
+    TempDatabase::new_with_existent(&db.path)
+}
+
+#[test]
+fn test_reindex_rebuilds_indexes() {
+    let db = setup_reindex_db();
+    let conn = db.connect_limbo();
+
+    // Rebuilding all the indexes, the indexes of a table, a single index or the indexes
+    // using a collation leaves the same contents in them.
+    for reindex in [
+        "REINDEX",
+        "REINDEX t",
+        "REINDEX main.u",
+        "REINDEX tbc",
+        "REINDEX main.tb_partial",
+        "REINDEX nocase",
+        "REINDEX rtrim",
+        "REINDEX binary",
+    ] {
+        limbo_exec_rows(&db, &conn, reindex);
+        assert_eq!(
+            limbo_exec_rows(&db, &conn, "SELECT id FROM t WHERE a = 'b1' ORDER BY id"),
+            vec![vec![Value::Integer(1)], vec![Value::Integer(2)]],
+            "{}",
+            reindex
+        );
+        assert_eq!(
+            limbo_exec_rows(
+                &db,
+                &conn,
+                "SELECT count(*) FROM u WHERE y COLLATE RTRIM = 'y1'"
+            ),
+            vec![vec![Value::Integer(2)]],
+            "{}",
+            reindex
+        );
+        let sqlite_conn = rusqlite::Connection::open(db.path.clone()).unwrap();
+        assert_eq!(
+            sqlite_exec_rows(&sqlite_conn, "PRAGMA integrity_check"),
+            vec![vec![Value::Text("ok".to_string())]],
+            "{}",
+            reindex
+        );
+    }
+}
+
+#[test]
+fn test_reindex_unique_index_with_duplicates() {
+    let db = TempDatabase::new_with_rusqlite("CREATE TABLE t(x INT, y INT);");
+    {
+        // Turn a regular index over duplicate keys into a unique one behind SQLite's back.
+        let conn = rusqlite::Connection::open(db.path.clone()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO t VALUES (1, 1), (2, NULL), (1, 2), (3, NULL);
+             CREATE INDEX tx ON t(x);
+             CREATE INDEX ty ON t(y);
+             PRAGMA writable_schema = ON;
+             UPDATE sqlite_schema SET sql = replace(sql, 'CREATE INDEX', 'CREATE UNIQUE INDEX');
+             PRAGMA writable_schema = OFF;",
+        )
+        .unwrap();
+    }
+    let db = TempDatabase::new_with_existent(&db.path);
+    let conn = db.connect_limbo();
+
+    // Like CREATE UNIQUE INDEX, rebuilding a unique index fails on rows with the same key,
+    // but NULLs are distinct from each other.
+    for (sql, error) in [
+        ("REINDEX tx", Some("UNIQUE constraint failed: t.x")),
+        ("REINDEX ty", None),
+        (
+            "CREATE UNIQUE INDEX tx2 ON t(x)",
+            Some("UNIQUE constraint failed: t.x"),
+        ),
+        ("CREATE UNIQUE INDEX ty2 ON t(y)", None),
+    ] {
+        let result = limbo_exec_rows_error(&db, &conn, sql);
+        match error {
+            Some(error) => assert!(
+                result
+                    .as_ref()
+                    .is_err_and(|e| e.to_string().contains(error)),
+                "{}: {:?}",
+                sql,
+                result
+            ),
+            None => assert!(result.is_ok(), "{}: {:?}", sql, result),
+        }
+    }
+}
+
+#[test]
+fn test_reindex_unknown_object() {
+    let db = setup_reindex_db();
+    let conn = db.connect_limbo();
+
+    for (reindex, error) in [
+        (
+            "REINDEX nothere",
+            "unable to identify the object to be reindexed",
+        ),
+        (
+            "REINDEX temp.t",
+            "unable to identify the object to be reindexed",
+        ),
+        ("REINDEX other.t", "unknown database other"),
+    ] {
+        let result = limbo_exec_rows_error(&db, &conn, reindex);
+        assert!(
+            result
+                .as_ref()
+                .is_err_and(|e| e.to_string().contains(error)),
+            "{}: {:?}",
+            reindex,
+            result
+        );
+    }
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "index_experimental")]
+
+use crate::common::{limbo_exec_rows, sqlite_exec_rows, TempDatabase};
+use rusqlite::{params, types::Value};
+
+/// Creates `e(k, c)` with an index on (k, c), with `rows` rows whose k cycles through
+/// `distinct_k` values, and runs ANALYZE to fill sqlite_stat1.
+fn setup_analyzed_db(rows: i64, distinct_k: i64) -> TempDatabase {
+    let db = TempDatabase::new_with_rusqlite("CREATE TABLE e (k INT, c INT);");
+    {
+        let conn = rusqlite::Connection::open(db.path.clone()).unwrap();
+        conn.execute_batch("BEGIN").unwrap();
+        for i in 0..rows {
+            conn.execute(
+                "INSERT INTO e VALUES (?, ?)",
+                params![i % distinct_k, i % 97],
+            )
+            .unwrap();
+        }
+        conn.execute_batch("COMMIT; CREATE INDEX ikc ON e (k, c); ANALYZE;")
+            .unwrap();
+    }
+    TempDatabase::new_with_existent(&db.path)
+}
+
+#[test]
+fn test_skip_scan_uses_sqlite_stat1() {
+    let query = "SELECT k, c FROM e WHERE c = 7 ORDER BY k";
+
+    // 5 values of k with 4000 rows each: the index is skip-scanned, seeking once per value of k.
+    let db = setup_analyzed_db(20000, 5);
+    let conn = db.connect_limbo();
+    assert!(conn.prepare(query).unwrap().explain().contains("SeekGE"));
+    let sqlite_conn = rusqlite::Connection::open(db.path.clone()).unwrap();
+    assert_eq!(
+        limbo_exec_rows(&db, &conn, query),
+        sqlite_exec_rows(&sqlite_conn, query)
+    );
+
+    // k is unique, so the index is scanned instead.
+    let db = setup_analyzed_db(20000, 20000);
+    let conn = db.connect_limbo();
+    assert!(!conn.prepare(query).unwrap().explain().contains("SeekGE"));
+}
+
+#[test]
+fn test_skip_scan_visits_null_leading_values() {
+    let db = TempDatabase::new_with_rusqlite("CREATE TABLE t (a INTEGER, b INTEGER);");
+    {
+        let conn = rusqlite::Connection::open(db.path.clone()).unwrap();
+        conn.execute_batch(
+            "INSERT INTO t VALUES (NULL, 1), (2, 1), (NULL, 2), (1, 1), (2, 2), (3, 3);
+             CREATE INDEX t_ab ON t (a, b);
+             ANALYZE;
+             UPDATE sqlite_stat1 SET stat = '60000 20000 1' WHERE idx = 't_ab';",
+        )
+        .unwrap();
+    }
+    let tmp_db = TempDatabase::new_with_existent(&db.path);
+    let conn = tmp_db.connect_limbo();
+    let query = "SELECT a FROM t WHERE b = 1";
+    assert!(conn.prepare(query).unwrap().explain().contains("SeekGE"));
+
+    // The rows are visited in the order of the index, starting with the NULLs.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, query),
+        vec![
+            vec![Value::Null],
+            vec![Value::Integer(1)],
+            vec![Value::Integer(2)],
+        ]
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT a, b FROM t WHERE b >= 2 ORDER BY a DESC"
+        ),
+        vec![
+            vec![Value::Integer(3), Value::Integer(3)],
+            vec![Value::Integer(2), Value::Integer(2)],
+            vec![Value::Null, Value::Integer(2)],
+        ]
+    );
+}
@@ -0,0 +1,331 @@
+use crate::common::{
+    limbo_exec_rows, limbo_exec_rows_error, maybe_setup_tracing, sqlite_exec_rows, TempDatabase,
+};
+use limbo_core::{LimboError, Replicator};
+use rusqlite::types::Value;
+use std::sync::Arc;
+
+fn read_frame(conn: &Arc<limbo_core::Connection>, db: &TempDatabase, frame_no: u32) -> Vec<u8> {
+    let mut frame = vec![0; 4096 + 24];
+    let c = conn
+        .wal_get_frame(frame_no, frame.as_mut_ptr(), frame.len() as u32)
+        .unwrap();
+    db.io.wait_for_completion(c).unwrap();
+    frame
+}
+
+#[test]
+fn test_replicator_sync() {
+    maybe_setup_tracing();
+    let primary_db = TempDatabase::new_empty();
+    let primary = primary_db.connect_limbo();
+    let replica_db = TempDatabase::new_empty();
+    let replica = replica_db.connect_limbo();
+    let reader = replica_db.connect_limbo();
+    let replicator = Replicator::new(&primary, &replica).unwrap();
+    assert_eq!(replicator.sync().unwrap(), 0);
+
+    limbo_exec_rows(
+        &primary_db,
+        &primary,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    limbo_exec_rows(
+        &primary_db,
+        &primary,
+        "INSERT INTO t VALUES (1, 'a'), (2, 'b')",
+    );
+    assert_eq!(
+        replicator.sync().unwrap(),
+        primary.wal_frame_count().unwrap()
+    );
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &reader, "SELECT * FROM t"),
+        vec![
+            vec![Value::Integer(1), Value::Text("a".to_string())],
+            vec![Value::Integer(2), Value::Text("b".to_string())],
+        ]
+    );
+
+    limbo_exec_rows(&primary_db, &primary, "UPDATE t SET y = 'B' WHERE x = 2");
+    limbo_exec_rows(&primary_db, &primary, "DELETE FROM t WHERE x = 1");
+    limbo_exec_rows(&primary_db, &primary, "CREATE TABLE u (z)");
+    limbo_exec_rows(&primary_db, &primary, "INSERT INTO u VALUES (3)");
+    // Nothing is visible on the replica before the next sync.
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &reader, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(2)]]
+    );
+    assert!(replicator.sync().unwrap() > 0);
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &reader, "SELECT * FROM t"),
+        vec![vec![Value::Integer(2), Value::Text("B".to_string())]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &reader, "SELECT * FROM u"),
+        vec![vec![Value::Integer(3)]]
+    );
+    assert_eq!(
+        replica.wal_frame_count().unwrap(),
+        primary.wal_frame_count().unwrap()
+    );
+    // Only the replicator writes to the replica.
+    for conn in [&replica, &reader] {
+        assert!(matches!(
+            limbo_exec_rows_error(&replica_db, conn, "INSERT INTO u VALUES (4)"),
+            Err(LimboError::ReadOnly)
+        ));
+    }
+    assert!(matches!(
+        limbo_exec_rows_error(&replica_db, &reader, "CREATE TABLE v (w)"),
+        Err(LimboError::ReadOnly)
+    ));
+
+    // The replica's WAL is a valid copy of the primary's.
+    drop(replicator);
+    reader.close().unwrap();
+    replica.close().unwrap();
+    let sqlite_conn = rusqlite::Connection::open(&replica_db.path).unwrap();
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "PRAGMA integrity_check"),
+        vec![vec![Value::Text("ok".to_string())]]
+    );
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "SELECT * FROM u"),
+        vec![vec![Value::Integer(3)]]
+    );
+}
+
+#[test]
+fn test_replicator_large_transactions() {
+    maybe_setup_tracing();
+    let primary_db = TempDatabase::new_empty();
+    let primary = primary_db.connect_limbo();
+    let replica_db = TempDatabase::new_empty();
+    let replica = replica_db.connect_limbo();
+    let replicator = Replicator::new(&primary, &replica).unwrap();
+
+    limbo_exec_rows(
+        &primary_db,
+        &primary,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y BLOB)",
+    );
+    for batch in 0..4 {
+        limbo_exec_rows(&primary_db, &primary, "BEGIN");
+        for i in 0..100 {
+            limbo_exec_rows(
+                &primary_db,
+                &primary,
+                &format!(
+                    "INSERT INTO t VALUES ({}, randomblob(3000))",
+                    batch * 100 + i
+                ),
+            );
+        }
+        limbo_exec_rows(&primary_db, &primary, "COMMIT");
+        replicator.sync().unwrap();
+    }
+    let query = "SELECT x, y FROM t ORDER BY x";
+    let rows = limbo_exec_rows(&replica_db, &replica, query);
+    assert_eq!(rows.len(), 400);
+    assert_eq!(rows, limbo_exec_rows(&primary_db, &primary, query));
+}
+
+#[test]
+fn test_replica_keeps_cached_pages_not_replicated() {
+    maybe_setup_tracing();
+    let primary_db = TempDatabase::new_empty();
+    let primary = primary_db.connect_limbo();
+    let replica_db = TempDatabase::new_empty();
+    let replica = replica_db.connect_limbo();
+    let reader = replica_db.connect_limbo();
+    let replicator = Replicator::new(&primary, &replica).unwrap();
+
+    limbo_exec_rows(
+        &primary_db,
+        &primary,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y BLOB)",
+    );
+    limbo_exec_rows(&primary_db, &primary, "CREATE TABLE u (z)");
+    for i in 0..50 {
+        limbo_exec_rows(
+            &primary_db,
+            &primary,
+            &format!("INSERT INTO t VALUES ({}, randomblob(3000))", i),
+        );
+    }
+    replicator.sync().unwrap();
+    let query = "SELECT count(*) FROM t";
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &reader, query),
+        vec![vec![Value::Integer(50)]]
+    );
+
+    // Only the pages written by the replicated frames are read again.
+    limbo_exec_rows(&primary_db, &primary, "INSERT INTO u VALUES (1)");
+    assert!(replicator.sync().unwrap() > 0);
+    let misses = reader.page_cache_stats().misses;
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &reader, query),
+        vec![vec![Value::Integer(50)]]
+    );
+    assert_eq!(reader.page_cache_stats().misses, misses);
+
+    limbo_exec_rows(&primary_db, &primary, "INSERT INTO t VALUES (50, x'00')");
+    assert!(replicator.sync().unwrap() > 0);
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &reader, query),
+        vec![vec![Value::Integer(51)]]
+    );
+    assert!(reader.page_cache_stats().misses > misses);
+}
+
+#[test]
+fn test_replicator_sync_after_wal_restart() {
+    maybe_setup_tracing();
+    let primary_db = TempDatabase::new_empty();
+    let primary = primary_db.connect_limbo();
+    let replica_db = TempDatabase::new_empty();
+    let replica = replica_db.connect_limbo();
+    let replicator = Replicator::new(&primary, &replica).unwrap();
+
+    limbo_exec_rows(
+        &primary_db,
+        &primary,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY)",
+    );
+    limbo_exec_rows(&primary_db, &primary, "INSERT INTO t VALUES (0)");
+    replicator.sync().unwrap();
+    // Each checkpoint restarts the primary's WAL, whose new frames then replace the replica's.
+    for (i, mode) in ["RESTART", "TRUNCATE"].iter().enumerate() {
+        limbo_exec_rows(
+            &primary_db,
+            &primary,
+            &format!("PRAGMA wal_checkpoint({})", mode),
+        );
+        assert_eq!(primary.wal_frame_count().unwrap(), 0);
+        limbo_exec_rows(
+            &primary_db,
+            &primary,
+            &format!("INSERT INTO t VALUES ({})", i + 1),
+        );
+        assert!(replicator.sync().unwrap() > 0, "{}", mode);
+        assert_eq!(
+            replica.wal_frame_count().unwrap(),
+            primary.wal_frame_count().unwrap()
+        );
+        assert_eq!(
+            limbo_exec_rows(&replica_db, &replica, "SELECT count(*) FROM t"),
+            vec![vec![Value::Integer(i as i64 + 2)]]
+        );
+    }
+
+    drop(replicator);
+    replica.close().unwrap();
+    let sqlite_conn = rusqlite::Connection::open(&replica_db.path).unwrap();
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "PRAGMA integrity_check"),
+        vec![vec![Value::Text("ok".to_string())]]
+    );
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(3)]]
+    );
+}
+
+#[test]
+fn test_wal_insert_frame_validation() {
+    maybe_setup_tracing();
+    let primary_db = TempDatabase::new_empty();
+    let primary = primary_db.connect_limbo();
+    let replica_db = TempDatabase::new_empty();
+    let replica = replica_db.connect_limbo();
+    limbo_exec_rows(&primary_db, &primary, "CREATE TABLE t (x)");
+    limbo_exec_rows(&primary_db, &primary, "INSERT INTO t VALUES (1)");
+    let frame_count = primary.wal_frame_count().unwrap() as u32;
+    let frames: Vec<_> = (1..=frame_count)
+        .map(|frame_no| read_frame(&primary, &primary_db, frame_no))
+        .collect();
+
+    replica.wal_insert_begin(&primary.wal_get_header()).unwrap();
+    // Frames must be inserted in order.
+    assert!(matches!(
+        replica.wal_insert_frame(2, &frames[1]),
+        Err(LimboError::InvalidArgument(_))
+    ));
+    // A frame of another WAL has different salts.
+    let other_db = TempDatabase::new_empty();
+    let other = other_db.connect_limbo();
+    limbo_exec_rows(&other_db, &other, "CREATE TABLE t (x)");
+    let other_frame = read_frame(&other, &other_db, 1);
+    assert!(matches!(
+        replica.wal_insert_frame(1, &other_frame),
+        Err(LimboError::InvalidArgument(_))
+    ));
+    // A corrupted frame fails the checksum.
+    let mut corrupted = frames[0].clone();
+    corrupted[100] ^= 1;
+    assert!(matches!(
+        replica.wal_insert_frame(1, &corrupted),
+        Err(LimboError::Corrupt(_))
+    ));
+    // Insertion resumes where the previous one stopped.
+    let (first, rest) = frames.split_at(1);
+    replica.wal_insert_frame(1, &first[0]).unwrap();
+    replica.wal_insert_end().unwrap();
+    assert_eq!(replica.wal_frame_count().unwrap(), 1);
+    replica.wal_insert_begin(&primary.wal_get_header()).unwrap();
+    for (i, frame) in rest.iter().enumerate() {
+        replica.wal_insert_frame(i as u64 + 2, frame).unwrap();
+    }
+    replica.wal_insert_end().unwrap();
+    assert_eq!(replica.wal_frame_count().unwrap(), frame_count as u64);
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &replica, "SELECT * FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+
+    // Writing to the replica makes it diverge from the primary.
+    limbo_exec_rows(&replica_db, &replica, "INSERT INTO t VALUES (2)");
+    limbo_exec_rows(&primary_db, &primary, "INSERT INTO t VALUES (3)");
+    let replicator = Replicator::new(&primary, &replica).unwrap();
+    assert!(replicator.sync().is_err());
+    // The WAL of a database with frames can't take over another header.
+    assert!(other.wal_insert_begin(&primary.wal_get_header()).is_err());
+}
+
+#[test]
+fn test_wal_insert_begin_waits_for_writer() {
+    maybe_setup_tracing();
+    let primary_db = TempDatabase::new_empty();
+    let primary = primary_db.connect_limbo();
+    let replica_db = TempDatabase::new_empty();
+    let replica = replica_db.connect_limbo();
+    let writer = replica_db.connect_limbo();
+    limbo_exec_rows(&primary_db, &primary, "CREATE TABLE t (x)");
+    limbo_exec_rows(&primary_db, &primary, "INSERT INTO t VALUES (1)");
+    limbo_exec_rows(&replica_db, &writer, "BEGIN");
+    limbo_exec_rows(&replica_db, &writer, "CREATE TABLE u (x)");
+
+    assert!(matches!(
+        replica.wal_insert_begin(&primary.wal_get_header()),
+        Err(LimboError::Busy)
+    ));
+    replica.busy_handler(Some(Box::new(move |count| {
+        if count == 0 {
+            writer.execute("ROLLBACK").unwrap();
+        }
+        true
+    })));
+    replica.wal_insert_begin(&primary.wal_get_header()).unwrap();
+    let frame_count = primary.wal_frame_count().unwrap() as u32;
+    for frame_no in 1..=frame_count {
+        let frame = read_frame(&primary, &primary_db, frame_no);
+        replica.wal_insert_frame(frame_no as u64, &frame).unwrap();
+    }
+    replica.wal_insert_end().unwrap();
+    assert_eq!(
+        limbo_exec_rows(&replica_db, &replica, "SELECT * FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+}
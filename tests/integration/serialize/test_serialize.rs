@@ -0,0 +1,110 @@
+use crate::common::{limbo_exec_rows, maybe_setup_tracing, sqlite_exec_rows, TempDatabase};
+use limbo_core::{Database, LimboError, StepResult};
+use rusqlite::types::Value;
+use std::sync::Arc;
+
+fn query_rows(
+    io: &Arc<dyn limbo_core::IO>,
+    conn: &Arc<limbo_core::Connection>,
+    sql: &str,
+) -> Vec<Vec<limbo_core::Value>> {
+    let mut stmt = conn.prepare(sql).unwrap();
+    let mut rows = Vec::new();
+    loop {
+        match stmt.step().unwrap() {
+            StepResult::Row => rows.push(stmt.row().unwrap().get_values().cloned().collect()),
+            StepResult::IO => io.run_once().unwrap(),
+            StepResult::Done => break,
+            r => panic!("unexpected step result {:?}", r),
+        }
+    }
+    rows
+}
+
+#[test]
+fn test_serialize_includes_wal_content() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TABLE t (x INTEGER PRIMARY KEY, y TEXT)",
+    );
+    for i in 0..100 {
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            &format!("INSERT INTO t VALUES ({}, 'value-{}')", i, i),
+        );
+    }
+
+    let image = conn.serialize().unwrap();
+    assert_eq!(image.len() % 4096, 0);
+    assert_eq!(&image[0..16], b"SQLite format 3\0");
+
+    let mut path = tmp_db.path.clone();
+    path.set_file_name("serialized.db");
+    std::fs::write(&path, &image).unwrap();
+    let sqlite_conn = rusqlite::Connection::open(&path).unwrap();
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "PRAGMA integrity_check"),
+        vec![vec![Value::Text("ok".to_string())]]
+    );
+    assert_eq!(
+        sqlite_exec_rows(&sqlite_conn, "SELECT count(*), max(y) FROM t"),
+        vec![vec![
+            Value::Integer(100),
+            Value::Text("value-99".to_string())
+        ]]
+    );
+}
+
+#[test]
+fn test_deserialize_roundtrip() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1), (2), (3)");
+    let image = tmp_db.db.serialize().unwrap();
+
+    let (io, db) = Database::deserialize(&image).unwrap();
+    let copy = db.connect().unwrap();
+    assert_eq!(
+        query_rows(&io, &copy, "SELECT sum(x) FROM t"),
+        vec![vec![limbo_core::Value::Integer(6)]]
+    );
+
+    copy.execute("INSERT INTO t VALUES (4)").unwrap();
+    assert_eq!(
+        query_rows(&io, &copy, "SELECT sum(x) FROM t"),
+        vec![vec![limbo_core::Value::Integer(10)]]
+    );
+    // The source database is not affected by writes to the copy.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT sum(x) FROM t"),
+        vec![vec![Value::Integer(6)]]
+    );
+    // Serializing the copy picks up its own changes.
+    let (io, db) = Database::deserialize(&db.serialize().unwrap()).unwrap();
+    assert_eq!(
+        query_rows(&io, &db.connect().unwrap(), "SELECT count(*) FROM t"),
+        vec![vec![limbo_core::Value::Integer(4)]]
+    );
+}
+
+#[test]
+fn test_deserialize_invalid_image() {
+    assert!(matches!(
+        Database::deserialize(b"not a database"),
+        Err(LimboError::NotADB)
+    ));
+    let mut image = vec![0u8; 4096 + 10];
+    image[0..16].copy_from_slice(b"SQLite format 3\0");
+    image[16..18].copy_from_slice(&4096u16.to_be_bytes());
+    assert!(matches!(
+        Database::deserialize(&image),
+        Err(LimboError::Corrupt(_))
+    ));
+}
@@ -0,0 +1,353 @@
+use crate::common::{limbo_exec_rows, TempDatabase};
+use limbo_core::{Changeset, ConflictAction, ConflictType, UpdateKind};
+use rusqlite::types::Value;
+use std::sync::Arc;
+
+const SCHEMA: [&str; 3] = [
+    "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT, score REAL)",
+    "CREATE TABLE u (k INTEGER PRIMARY KEY, v BLOB)",
+    "CREATE TABLE nopk (x, y)",
+];
+
+const INITIAL_ROWS: [&str; 2] = [
+    "INSERT INTO t VALUES (1, 'one', 1.5), (2, 'two', 2.5), (3, 'three', 3.5)",
+    "INSERT INTO u VALUES (1, x'01'), (2, NULL)",
+];
+
+fn setup() -> (TempDatabase, Arc<limbo_core::Connection>) {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    for sql in SCHEMA.iter().chain(&INITIAL_ROWS) {
+        limbo_exec_rows(&tmp_db, &conn, sql);
+    }
+    (tmp_db, conn)
+}
+
+fn rows(db: &TempDatabase, conn: &Arc<limbo_core::Connection>) -> Vec<Vec<Value>> {
+    let mut rows = limbo_exec_rows(db, conn, "SELECT * FROM t ORDER BY id");
+    rows.extend(limbo_exec_rows(db, conn, "SELECT * FROM u ORDER BY k"));
+    rows
+}
+
+fn make_changes(db: &TempDatabase, conn: &Arc<limbo_core::Connection>) {
+    for sql in [
+        "INSERT INTO t VALUES (4, 'four', 4.5)",
+        "UPDATE t SET name = 'TWO' WHERE id = 2",
+        "DELETE FROM t WHERE id = 3",
+        // Inserted and deleted again, so there is nothing to record.
+        "INSERT INTO t VALUES (5, 'five', 5.5)",
+        "DELETE FROM t WHERE id = 5",
+        // Changing the primary key deletes the old row and inserts a new one.
+        "UPDATE u SET k = 3 WHERE k = 2",
+        "INSERT INTO nopk VALUES (1, 2)",
+    ] {
+        limbo_exec_rows(db, conn, sql);
+    }
+}
+
+fn no_conflicts(
+    kind: ConflictType,
+    table: &str,
+    _: &limbo_core::Change,
+    _: Option<&[limbo_core::Value]>,
+) -> ConflictAction {
+    panic!("unexpected {:?} conflict on {}", kind, table)
+}
+
+#[test]
+fn test_session_changeset() {
+    let (src_db, src) = setup();
+    let (dst_db, dst) = setup();
+    let session = src.create_session();
+    session.attach(None);
+    assert!(session.is_empty());
+
+    make_changes(&src_db, &src);
+    assert!(!session.is_empty());
+
+    let changeset = Changeset::parse(&session.changeset()).unwrap();
+    assert!(!changeset.patchset);
+    let summary: Vec<_> = changeset
+        .tables
+        .iter()
+        .map(|table| {
+            let ops: Vec<_> = table.changes.iter().map(|change| change.op).collect();
+            (table.name.as_str(), table.primary_key.clone(), ops)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "t",
+                vec![1, 0, 0],
+                vec![UpdateKind::Insert, UpdateKind::Update, UpdateKind::Delete]
+            ),
+            (
+                "u",
+                vec![1, 0],
+                vec![UpdateKind::Delete, UpdateKind::Insert]
+            ),
+        ]
+    );
+    let update = &changeset.tables[0].changes[1];
+    assert_eq!(
+        update.old,
+        vec![
+            Some(limbo_core::Value::Integer(2)),
+            Some(limbo_core::Value::build_text("two")),
+            None
+        ]
+    );
+    assert_eq!(
+        update.new,
+        vec![None, Some(limbo_core::Value::build_text("TWO")), None]
+    );
+
+    dst.apply_changeset(&session.changeset(), no_conflicts)
+        .unwrap();
+    assert_eq!(rows(&dst_db, &dst), rows(&src_db, &src));
+    assert_eq!(
+        limbo_exec_rows(&dst_db, &dst, "SELECT count(*) FROM nopk"),
+        vec![vec![Value::Integer(0)]]
+    );
+}
+
+#[test]
+fn test_session_patchset() {
+    let (src_db, src) = setup();
+    let (dst_db, dst) = setup();
+    let session = src.create_session();
+    session.attach(None);
+    make_changes(&src_db, &src);
+
+    let patchset = session.patchset();
+    assert!(patchset.len() < session.changeset().len());
+    assert!(Changeset::parse(&patchset).unwrap().patchset);
+
+    dst.apply_changeset(&patchset, no_conflicts).unwrap();
+    assert_eq!(rows(&dst_db, &dst), rows(&src_db, &src));
+}
+
+#[test]
+fn test_session_attach_and_enable() {
+    let (db, conn) = setup();
+    let session = conn.create_session();
+    session.attach(Some("u"));
+    limbo_exec_rows(&db, &conn, "INSERT INTO t VALUES (4, 'four', 4.5)");
+    assert!(session.is_empty());
+
+    session.set_enabled(false);
+    assert!(!session.is_enabled());
+    limbo_exec_rows(&db, &conn, "INSERT INTO u VALUES (3, NULL)");
+    assert!(session.is_empty());
+
+    session.set_enabled(true);
+    limbo_exec_rows(&db, &conn, "INSERT INTO u VALUES (4, NULL)");
+    let changeset = Changeset::parse(&session.changeset()).unwrap();
+    assert_eq!(changeset.tables.len(), 1);
+    assert_eq!(changeset.tables[0].name, "u");
+    assert_eq!(changeset.tables[0].changes.len(), 1);
+}
+
+#[test]
+fn test_session_disabled_mid_transaction() {
+    let (db, conn) = setup();
+    let session = conn.create_session();
+    session.attach(Some("t"));
+
+    limbo_exec_rows(&db, &conn, "BEGIN");
+    limbo_exec_rows(&db, &conn, "INSERT INTO t VALUES (4, 'four', 4.5)");
+    session.set_enabled(false);
+    // Neither new rows nor rows that were already changed are recorded.
+    limbo_exec_rows(&db, &conn, "INSERT INTO t VALUES (5, 'five', 5.5)");
+    limbo_exec_rows(&db, &conn, "UPDATE t SET name = 'FOUR' WHERE id = 4");
+    session.set_enabled(true);
+    limbo_exec_rows(&db, &conn, "UPDATE t SET score = 1.0 WHERE id = 1");
+    limbo_exec_rows(&db, &conn, "COMMIT");
+
+    let changeset = Changeset::parse(&session.changeset()).unwrap();
+    let changes = &changeset.tables[0].changes;
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].op, UpdateKind::Insert);
+    assert_eq!(
+        changes[0].new[1],
+        Some(limbo_core::Value::build_text("four"))
+    );
+    assert_eq!(changes[1].op, UpdateKind::Update);
+    assert_eq!(changes[1].new[2], Some(limbo_core::Value::Float(1.0)));
+
+    // A rollback discards what was recorded before the session was disabled.
+    limbo_exec_rows(&db, &conn, "BEGIN");
+    limbo_exec_rows(&db, &conn, "DELETE FROM t WHERE id = 2");
+    session.set_enabled(false);
+    limbo_exec_rows(&db, &conn, "DELETE FROM t WHERE id = 3");
+    limbo_exec_rows(&db, &conn, "ROLLBACK");
+    session.set_enabled(true);
+    assert_eq!(Changeset::parse(&session.changeset()).unwrap(), changeset);
+}
+
+#[test]
+fn test_session_rollback() {
+    let (db, conn) = setup();
+    let session = conn.create_session();
+    session.attach(None);
+
+    limbo_exec_rows(&db, &conn, "BEGIN");
+    limbo_exec_rows(&db, &conn, "INSERT INTO t VALUES (4, 'four', 4.5)");
+    limbo_exec_rows(&db, &conn, "UPDATE t SET name = 'ONE' WHERE id = 1");
+    limbo_exec_rows(&db, &conn, "ROLLBACK");
+    assert!(session.is_empty());
+
+    limbo_exec_rows(&db, &conn, "UPDATE t SET name = 'ONE' WHERE id = 1");
+    limbo_exec_rows(&db, &conn, "BEGIN");
+    limbo_exec_rows(&db, &conn, "UPDATE t SET name = 'uno' WHERE id = 1");
+    limbo_exec_rows(&db, &conn, "ROLLBACK");
+    let changeset = Changeset::parse(&session.changeset()).unwrap();
+    let update = &changeset.tables[0].changes[0];
+    assert_eq!(update.new[1], Some(limbo_core::Value::build_text("ONE")));
+}
+
+#[test]
+fn test_apply_changeset_conflicts() {
+    let (src_db, src) = setup();
+    let session = src.create_session();
+    session.attach(Some("t"));
+    make_changes(&src_db, &src);
+    let changeset = session.changeset();
+
+    let diverge = |db: &TempDatabase, conn: &Arc<limbo_core::Connection>| {
+        // Conflicts with the insert of 4, the update of 2 and the delete of 3.
+        limbo_exec_rows(db, conn, "INSERT INTO t VALUES (4, 'four?', 0.0)");
+        limbo_exec_rows(db, conn, "UPDATE t SET name = 'two?' WHERE id = 2");
+        limbo_exec_rows(db, conn, "DELETE FROM t WHERE id = 3");
+    };
+
+    // Omitting the conflicting changes leaves the rows as they are.
+    let (dst_db, dst) = setup();
+    diverge(&dst_db, &dst);
+    let mut conflicts = Vec::new();
+    dst.apply_changeset(&changeset, |kind, table, change, current| {
+        conflicts.push((kind, table.to_string(), change.op, current.is_some()));
+        ConflictAction::Omit
+    })
+    .unwrap();
+    assert_eq!(
+        conflicts,
+        vec![
+            (
+                ConflictType::Conflict,
+                "t".to_string(),
+                UpdateKind::Insert,
+                true
+            ),
+            (
+                ConflictType::Data,
+                "t".to_string(),
+                UpdateKind::Update,
+                true
+            ),
+            (
+                ConflictType::NotFound,
+                "t".to_string(),
+                UpdateKind::Delete,
+                false
+            ),
+        ]
+    );
+    assert_eq!(
+        limbo_exec_rows(&dst_db, &dst, "SELECT * FROM t ORDER BY id"),
+        vec![
+            vec![
+                Value::Integer(1),
+                Value::Text("one".into()),
+                Value::Real(1.5)
+            ],
+            vec![
+                Value::Integer(2),
+                Value::Text("two?".into()),
+                Value::Real(2.5)
+            ],
+            vec![
+                Value::Integer(4),
+                Value::Text("four?".into()),
+                Value::Real(0.0)
+            ],
+        ]
+    );
+
+    // Replacing overwrites the conflicting rows.
+    let (dst_db, dst) = setup();
+    diverge(&dst_db, &dst);
+    dst.apply_changeset(&changeset, |kind, _, _, _| match kind {
+        ConflictType::NotFound => ConflictAction::Omit,
+        _ => ConflictAction::Replace,
+    })
+    .unwrap();
+    assert_eq!(
+        limbo_exec_rows(&dst_db, &dst, "SELECT * FROM t ORDER BY id"),
+        vec![
+            vec![
+                Value::Integer(1),
+                Value::Text("one".into()),
+                Value::Real(1.5)
+            ],
+            vec![
+                Value::Integer(2),
+                Value::Text("TWO".into()),
+                Value::Real(2.5)
+            ],
+            vec![
+                Value::Integer(4),
+                Value::Text("four".into()),
+                Value::Real(4.5)
+            ],
+        ]
+    );
+
+    // Aborting rolls back the changes applied before the conflict.
+    let (dst_db, dst) = setup();
+    limbo_exec_rows(&dst_db, &dst, "DELETE FROM t WHERE id = 3");
+    let before = rows(&dst_db, &dst);
+    assert!(dst
+        .apply_changeset(&changeset, |_, _, _, _| ConflictAction::Abort)
+        .is_err());
+    assert_eq!(rows(&dst_db, &dst), before);
+    assert!(dst.get_auto_commit());
+
+    // Only data and insert conflicts can be replaced.
+    assert!(dst
+        .apply_changeset(&changeset, |_, _, _, _| ConflictAction::Replace)
+        .is_err());
+    assert_eq!(rows(&dst_db, &dst), before);
+}
+
+#[test]
+#[cfg(feature = "index_experimental")]
+fn test_session_composite_primary_key() {
+    let (src_db, src) = setup();
+    let (dst_db, dst) = setup();
+    for (db, conn) in [(&src_db, &src), (&dst_db, &dst)] {
+        limbo_exec_rows(
+            db,
+            conn,
+            "CREATE TABLE c (a TEXT, b INTEGER, v, PRIMARY KEY (a, b))",
+        );
+        limbo_exec_rows(db, conn, "INSERT INTO c VALUES ('a', 1, 1), ('a', 2, 2)");
+    }
+    let session = src.create_session();
+    session.attach(Some("c"));
+    limbo_exec_rows(&src_db, &src, "UPDATE c SET v = 3 WHERE a = 'a' AND b = 2");
+    limbo_exec_rows(&src_db, &src, "DELETE FROM c WHERE a = 'a' AND b = 1");
+    limbo_exec_rows(&src_db, &src, "INSERT INTO c VALUES ('b', 1, NULL)");
+
+    let changeset = Changeset::parse(&session.changeset()).unwrap();
+    assert_eq!(changeset.tables[0].primary_key, vec![1, 2, 0]);
+    dst.apply_changeset(&session.changeset(), no_conflicts)
+        .unwrap();
+    let query = "SELECT * FROM c ORDER BY a, b";
+    assert_eq!(
+        limbo_exec_rows(&dst_db, &dst, query),
+        limbo_exec_rows(&src_db, &src, query)
+    );
+}
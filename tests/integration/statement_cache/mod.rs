@@ -0,0 +1 @@
+mod test_statement_cache;
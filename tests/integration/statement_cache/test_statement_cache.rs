@@ -0,0 +1,182 @@
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, maybe_setup_tracing, TempDatabase};
+use limbo_core::StatementCacheStats;
+use rusqlite::types::Value;
+
+#[test]
+fn test_statement_cache_reuses_programs() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x INTEGER PRIMARY KEY, y)");
+    let before = conn.statement_cache_stats();
+    let total_changes = conn.total_changes();
+
+    for i in 0..10 {
+        limbo_exec_rows(&tmp_db, &conn, &format!("INSERT INTO t VALUES ({i}, {i})"));
+        limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET y = y + 1");
+    }
+    // A reused program counts the changes of its own execution only.
+    assert_eq!(
+        conn.total_changes() - total_changes,
+        10 + (1..=10).sum::<i64>()
+    );
+    let stats = conn.statement_cache_stats();
+    // Every INSERT has different SQL, the UPDATE is compiled once.
+    assert_eq!(stats.misses - before.misses, 11);
+    assert_eq!(stats.hits - before.hits, 9);
+
+    let query = "SELECT count(*), sum(y) FROM t";
+    for _ in 0..3 {
+        assert_eq!(
+            limbo_exec_rows(&tmp_db, &conn, query),
+            vec![vec![Value::Integer(10), Value::Integer(100)]]
+        );
+    }
+    assert_eq!(conn.statement_cache_stats().hits - stats.hits, 2);
+}
+
+#[test]
+fn test_statement_cache_concurrent_statements() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1), (2), (3)");
+
+    // Two statements running the same cached program keep their own state.
+    let query = "SELECT x FROM t ORDER BY x";
+    let mut first = conn.prepare(query).unwrap();
+    let mut second = conn.prepare(query).unwrap();
+    let step = |stmt: &mut limbo_core::Statement| loop {
+        match stmt.step().unwrap() {
+            limbo_core::StepResult::Row => {
+                return stmt.row().unwrap().get::<i64>(0).unwrap();
+            }
+            limbo_core::StepResult::IO => tmp_db.io.run_once().unwrap(),
+            r => panic!("unexpected result {:?}", r),
+        }
+    };
+    assert_eq!(step(&mut first), 1);
+    assert_eq!(step(&mut first), 2);
+    assert_eq!(step(&mut second), 1);
+    assert_eq!(step(&mut first), 3);
+    assert_eq!(step(&mut second), 2);
+}
+
+#[test]
+fn test_statement_cache_invalidated_by_schema_change() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    let other = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+
+    let query = "SELECT * FROM t";
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, query),
+        vec![vec![Value::Integer(1)]]
+    );
+    let stats = conn.statement_cache_stats();
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, query),
+        vec![vec![Value::Integer(1)]]
+    );
+    assert_eq!(conn.statement_cache_stats().hits, stats.hits + 1);
+
+    // A schema change on another connection bumps the schema cookie.
+    limbo_exec_rows(&tmp_db, &other, "ALTER TABLE t ADD COLUMN y DEFAULT 2");
+    let stats = conn.statement_cache_stats();
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, query),
+        vec![vec![Value::Integer(1), Value::Integer(2)]]
+    );
+    assert_eq!(conn.statement_cache_stats().misses, stats.misses + 1);
+
+    limbo_exec_rows(&tmp_db, &other, "DROP TABLE t");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, query).is_err());
+
+    let cookie = limbo_exec_rows(&tmp_db, &conn, "PRAGMA schema_version");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE u (x)");
+    let bumped = limbo_exec_rows(&tmp_db, &conn, "PRAGMA schema_version");
+    assert_ne!(cookie, bumped);
+}
+
+#[test]
+fn test_statement_cache_capacity() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    conn.set_statement_cache_capacity(2);
+    conn.clear_statement_cache();
+    assert_eq!(conn.statement_cache_stats().size, 0);
+
+    limbo_exec_rows(&tmp_db, &conn, "SELECT 1 FROM t");
+    limbo_exec_rows(&tmp_db, &conn, "SELECT 2 FROM t");
+    // Uses the first query, so the second one is evicted by the third.
+    limbo_exec_rows(&tmp_db, &conn, "SELECT 1 FROM t");
+    limbo_exec_rows(&tmp_db, &conn, "SELECT 3 FROM t");
+    let stats = conn.statement_cache_stats();
+    assert_eq!((stats.size, stats.capacity, stats.evictions), (2, 2, 1));
+    let hits = stats.hits;
+    limbo_exec_rows(&tmp_db, &conn, "SELECT 1 FROM t");
+    assert_eq!(conn.statement_cache_stats().hits, hits + 1);
+    limbo_exec_rows(&tmp_db, &conn, "SELECT 2 FROM t");
+    assert_eq!(conn.statement_cache_stats().hits, hits + 1);
+
+    conn.set_statement_cache_capacity(0);
+    let stats = conn.statement_cache_stats();
+    assert_eq!((stats.size, stats.capacity), (0, 0));
+    limbo_exec_rows(&tmp_db, &conn, "SELECT 1 FROM t");
+    assert_eq!(
+        conn.statement_cache_stats(),
+        StatementCacheStats {
+            misses: stats.misses + 1,
+            ..stats
+        }
+    );
+}
+
+#[test]
+fn test_statement_cache_invalidated_by_schema_version_pragma() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    let query = "SELECT * FROM t";
+    limbo_exec_rows(&tmp_db, &conn, query);
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA schema_version = 100");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA schema_version"),
+        vec![vec![Value::Integer(100)]]
+    );
+    let stats = conn.statement_cache_stats();
+    limbo_exec_rows(&tmp_db, &conn, query);
+    assert_eq!(conn.statement_cache_stats().misses, stats.misses + 1);
+}
+
+#[test]
+fn test_statement_cache_counts_cacheable_statements() {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    let stats = conn.statement_cache_stats();
+
+    // Statements that are never cached are neither hits nor misses.
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA cache_size");
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA cache_size");
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    limbo_exec_rows(&tmp_db, &conn, "COMMIT");
+    limbo_exec_rows(&tmp_db, &conn, "DROP TABLE IF EXISTS u");
+    assert_eq!(conn.statement_cache_stats(), stats);
+
+    limbo_exec_rows(&tmp_db, &conn, "SELECT * FROM t");
+    limbo_exec_rows(&tmp_db, &conn, "SELECT * FROM t");
+    let after = conn.statement_cache_stats();
+    assert_eq!(
+        (after.hits, after.misses),
+        (stats.hits + 1, stats.misses + 1)
+    );
+}
@@ -0,0 +1,345 @@
+use crate::common::{limbo_exec_rows, limbo_exec_rows_error, TempDatabase};
+use limbo_core::UpdateKind;
+use rusqlite::types::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn test_temp_table() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE t (x INTEGER, y TEXT)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1, 'a'), (2, 'b')");
+    limbo_exec_rows(&tmp_db, &conn, "UPDATE t SET y = 'c' WHERE x = 2");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM t WHERE x = 1");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT * FROM t"),
+        vec![vec![Value::Integer(2), Value::Text("c".to_string())]]
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT type, name, tbl_name, sql FROM sqlite_temp_master"
+        ),
+        vec![vec![
+            Value::Text("table".to_string()),
+            Value::Text("t".to_string()),
+            Value::Text("t".to_string()),
+            Value::Text("CREATE TABLE t (x INTEGER, y TEXT)".to_string()),
+        ]]
+    );
+    // The temp table is not in the schema of the main database.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM sqlite_schema"),
+        vec![vec![Value::Integer(0)]]
+    );
+
+    limbo_exec_rows(&tmp_db, &conn, "DROP TABLE t");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "SELECT * FROM t").is_err());
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM sqlite_temp_schema"),
+        vec![vec![Value::Integer(0)]]
+    );
+}
+
+#[test]
+fn test_temp_table_qualified_name() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE temp.t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT name FROM sqlite_temp_schema"),
+        vec![vec![Value::Text("t".to_string())]]
+    );
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "CREATE TEMP TABLE t (y)").is_err());
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE IF NOT EXISTS t (y)");
+}
+
+#[test]
+fn test_temp_table_hides_main_table() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES ('main')");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES ('temp')");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM t"),
+        vec![vec![Value::Text("temp".to_string())]]
+    );
+
+    // Dropping the temp table uncovers the main one.
+    limbo_exec_rows(&tmp_db, &conn, "DROP TABLE t");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM t"),
+        vec![vec![Value::Text("main".to_string())]]
+    );
+}
+
+#[test]
+fn test_qualified_names_resolve_in_their_database() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (x)");
+    #[cfg(feature = "index_experimental")]
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX main_x ON t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO main.t VALUES ('main')");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO temp.t VALUES ('temp')");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES ('unqualified')");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM main.t"),
+        vec![vec![Value::Text("main".to_string())]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM temp.t ORDER BY x"),
+        vec![
+            vec![Value::Text("temp".to_string())],
+            vec![Value::Text("unqualified".to_string())],
+        ]
+    );
+    // The index of main.t is not used for, nor maintained by, statements on temp.t.
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM main.t WHERE x = 'main'"),
+        vec![vec![Value::Text("main".to_string())]]
+    );
+    assert!(limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM main.t WHERE x = 'temp'").is_empty());
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA integrity_check"),
+        vec![vec![Value::Text("ok".to_string())]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM temp.t WHERE x = 'temp'"),
+        vec![vec![Value::Text("temp".to_string())]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM main.t");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(2)]]
+    );
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "SELECT x FROM aux.t").is_err());
+
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO main.t VALUES ('main')");
+    limbo_exec_rows(&tmp_db, &conn, "DROP TABLE main.t");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM sqlite_schema"),
+        vec![vec![Value::Integer(0)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(2)]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "DROP TABLE temp.t");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "SELECT * FROM t").is_err());
+}
+
+#[test]
+fn test_temp_table_joined_with_main_table() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE m (id INTEGER PRIMARY KEY, v)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO m VALUES (1, 'one'), (2, 'two')",
+    );
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE ids (id)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "INSERT INTO ids SELECT id FROM m WHERE id = 2",
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT m.v FROM ids JOIN m ON m.id = ids.id"
+        ),
+        vec![vec![Value::Text("two".to_string())]]
+    );
+}
+
+#[test]
+fn test_temp_table_is_private_to_connection() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    let other = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+    assert!(limbo_exec_rows_error(&tmp_db, &other, "SELECT * FROM t").is_err());
+
+    // Each connection has its own temp tables.
+    limbo_exec_rows(&tmp_db, &other, "CREATE TEMP TABLE t (x)");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &other, "SELECT count(*) FROM t"),
+        vec![vec![Value::Integer(0)]]
+    );
+
+    // Temp tables are deleted with their connection.
+    conn.close().unwrap();
+    let conn = tmp_db.connect_limbo();
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "SELECT * FROM t").is_err());
+}
+
+#[test]
+fn test_temp_table_transactions() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE m (x)");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE t (x)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1)");
+
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (2)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO m VALUES (2)");
+    limbo_exec_rows(&tmp_db, &conn, "ROLLBACK");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM t"),
+        vec![vec![Value::Integer(1)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM m"),
+        vec![vec![Value::Integer(0)]]
+    );
+
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (3)");
+    limbo_exec_rows(&tmp_db, &conn, "COMMIT");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM t"),
+        vec![vec![Value::Integer(1)], vec![Value::Integer(3)]]
+    );
+
+    // A temp table created in a rolled back transaction is gone.
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE u (x)");
+    limbo_exec_rows(&tmp_db, &conn, "ROLLBACK");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "SELECT * FROM u").is_err());
+}
+
+#[test]
+fn test_temp_store_pragma() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA temp_store"),
+        vec![vec![Value::Integer(0)]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA temp_store = MEMORY");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA temp_store"),
+        vec![vec![Value::Integer(2)]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA temp_store = 1");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA temp_store"),
+        vec![vec![Value::Integer(1)]]
+    );
+
+    // With FILE, the temp database is kept in a temporary file.
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE t (x)");
+    for i in 1..=1000 {
+        limbo_exec_rows(&tmp_db, &conn, &format!("INSERT INTO t VALUES ({})", i));
+    }
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*), sum(x) FROM t"),
+        vec![vec![Value::Integer(1000), Value::Integer(500500)]]
+    );
+
+    // Changing the store is not allowed within a transaction, and deletes the temp tables.
+    limbo_exec_rows(&tmp_db, &conn, "BEGIN");
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "PRAGMA temp_store = 2").is_err());
+    limbo_exec_rows(&tmp_db, &conn, "COMMIT");
+    limbo_exec_rows(&tmp_db, &conn, "PRAGMA temp_store = DEFAULT");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "PRAGMA temp_store"),
+        vec![vec![Value::Integer(0)]]
+    );
+    assert!(limbo_exec_rows_error(&tmp_db, &conn, "SELECT * FROM t").is_err());
+}
+
+#[cfg(feature = "index_experimental")]
+#[test]
+fn test_temp_index() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TEMP TABLE t (x, y)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1, 'a'), (2, 'b')");
+    limbo_exec_rows(&tmp_db, &conn, "CREATE INDEX t_y ON t (y)");
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (3, 'c')");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT x FROM t WHERE y = 'c'"),
+        vec![vec![Value::Integer(3)]]
+    );
+    assert_eq!(
+        limbo_exec_rows(
+            &tmp_db,
+            &conn,
+            "SELECT type, name FROM sqlite_temp_schema WHERE type = 'index'"
+        ),
+        vec![vec![
+            Value::Text("index".to_string()),
+            Value::Text("t_y".to_string())
+        ]]
+    );
+    limbo_exec_rows(&tmp_db, &conn, "DROP INDEX t_y");
+    limbo_exec_rows(&tmp_db, &conn, "DROP TABLE t");
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM sqlite_temp_schema"),
+        vec![vec![Value::Integer(0)]]
+    );
+}
+
+#[test]
+fn test_temp_table_update_hook_and_session() {
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    limbo_exec_rows(&tmp_db, &conn, "CREATE TABLE t (id INTEGER PRIMARY KEY, x)");
+    limbo_exec_rows(
+        &tmp_db,
+        &conn,
+        "CREATE TEMP TABLE t (id INTEGER PRIMARY KEY, x)",
+    );
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    conn.update_hook(Some(Box::new({
+        let changes = changes.clone();
+        move |kind, db: &str, table: &str, rowid| {
+            changes
+                .borrow_mut()
+                .push((kind, db.to_string(), table.to_string(), rowid))
+        }
+    })));
+    let session = conn.create_session();
+    session.attach(None);
+
+    // The rows go to the temp table, which hides the main one of the same name.
+    limbo_exec_rows(&tmp_db, &conn, "INSERT INTO t VALUES (1, 'a')");
+    limbo_exec_rows(&tmp_db, &conn, "DELETE FROM temp.t WHERE id = 1");
+    assert_eq!(
+        *changes.borrow(),
+        [(UpdateKind::Insert, 1), (UpdateKind::Delete, 1)].map(|(kind, rowid)| (
+            kind,
+            "temp".to_string(),
+            "t".to_string(),
+            rowid
+        ))
+    );
+    // Changes to temp tables are not recorded by sessions.
+    assert!(session.is_empty());
+    assert_eq!(
+        limbo_exec_rows(&tmp_db, &conn, "SELECT count(*) FROM main.t"),
+        vec![vec![Value::Integer(0)]]
+    );
+}
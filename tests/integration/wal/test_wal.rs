@@ -1,5 +1,5 @@
 use crate::common::{do_flush, maybe_setup_tracing, TempDatabase};
-use limbo_core::{Connection, LimboError, Result, StepResult};
+use limbo_core::{CheckpointMode, Connection, LimboError, Result, StepResult};
 use std::cell::RefCell;
 use std::ops::Deref;
 use std::rc::Rc;
@@ -172,3 +172,291 @@ pub(crate) fn execute_and_get_ints(
     }
     Ok(result)
 }
+
+/// Steps `stmt` until it finishes or gives up on a busy lock, calling `on_io` whenever the
+/// statement yields.
+fn step_until_busy_or_done(
+    tmp_db: &TempDatabase,
+    stmt: &mut limbo_core::Statement,
+    mut on_io: impl FnMut(),
+) -> Result<StepResult> {
+    loop {
+        match stmt.step()? {
+            StepResult::IO => {
+                on_io();
+                tmp_db.io.run_once()?;
+            }
+            StepResult::Row => {}
+            result => return Ok(result),
+        }
+    }
+}
+
+fn setup_locked_table(tmp_db: &TempDatabase) -> Result<(Arc<Connection>, Arc<Connection>)> {
+    let writer = tmp_db.connect_limbo();
+    let other = tmp_db.connect_limbo();
+    writer.execute("CREATE TABLE t (x)")?;
+    writer.execute("BEGIN")?;
+    writer.execute("INSERT INTO t VALUES (1)")?;
+    Ok((writer, other))
+}
+
+#[test]
+fn test_busy_without_handler() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let (_writer, other) = setup_locked_table(&tmp_db)?;
+
+    let mut stmt = other.prepare("INSERT INTO t VALUES (2)")?;
+    let result = step_until_busy_or_done(&tmp_db, &mut stmt, || {})?;
+    assert!(matches!(result, StepResult::Busy));
+    assert!(matches!(
+        other.execute("INSERT INTO t VALUES (2)"),
+        Err(LimboError::Busy)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_busy_timeout_gives_up_after_timeout() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let (_writer, other) = setup_locked_table(&tmp_db)?;
+
+    let timeout = std::time::Duration::from_millis(50);
+    other.busy_timeout(timeout);
+    assert_eq!(other.get_busy_timeout(), timeout);
+    let mut stmt = other.prepare("INSERT INTO t VALUES (2)")?;
+    let start = std::time::Instant::now();
+    let result = step_until_busy_or_done(&tmp_db, &mut stmt, || {})?;
+    assert!(matches!(result, StepResult::Busy));
+    assert!(start.elapsed() >= timeout);
+    Ok(())
+}
+
+#[test]
+fn test_busy_timeout_retries_until_lock_is_released() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let (writer, other) = setup_locked_table(&tmp_db)?;
+
+    other.busy_timeout(std::time::Duration::from_secs(10));
+    let mut stmt = other.prepare("INSERT INTO t VALUES (2)")?;
+    let mut committed = false;
+    let result = step_until_busy_or_done(&tmp_db, &mut stmt, || {
+        if !committed {
+            writer.execute("COMMIT").unwrap();
+            committed = true;
+        }
+    })?;
+    assert!(matches!(result, StepResult::Done));
+    assert!(committed);
+
+    let res = execute_and_get_ints(&tmp_db, &other, "SELECT x FROM t ORDER BY x")?;
+    assert_eq!(res, vec![1, 2]);
+    Ok(())
+}
+
+#[test]
+fn test_busy_handler_callback() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let (_writer, other) = setup_locked_table(&tmp_db)?;
+
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    other.busy_handler(Some(Box::new({
+        let calls = calls.clone();
+        move |count| {
+            calls.borrow_mut().push(count);
+            count < 2
+        }
+    })));
+    let mut stmt = other.prepare("INSERT INTO t VALUES (2)")?;
+    let result = step_until_busy_or_done(&tmp_db, &mut stmt, || {})?;
+    assert!(matches!(result, StepResult::Busy));
+    assert_eq!(*calls.borrow(), vec![0, 1, 2]);
+
+    // The retry count starts over for the next attempt.
+    calls.borrow_mut().clear();
+    let result = step_until_busy_or_done(&tmp_db, &mut stmt, || {})?;
+    assert!(matches!(result, StepResult::Busy));
+    assert_eq!(*calls.borrow(), vec![0, 1, 2]);
+    Ok(())
+}
+
+#[test]
+fn test_busy_handler_deferred_transaction_commits() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let (writer, other) = setup_locked_table(&tmp_db)?;
+
+    // The transaction read before the write lock was released, so it keeps its read lock
+    // while it waits to upgrade.
+    other.execute("BEGIN")?;
+    assert_eq!(
+        execute_and_get_ints(&tmp_db, &other, "SELECT count(*) FROM t")?,
+        vec![0]
+    );
+    other.busy_handler(Some(Box::new(move |count| {
+        if count == 0 {
+            writer.execute("ROLLBACK").unwrap();
+        }
+        true
+    })));
+    let mut stmt = other.prepare("INSERT INTO t VALUES (2)")?;
+    let result = step_until_busy_or_done(&tmp_db, &mut stmt, || {})?;
+    assert!(matches!(result, StepResult::Done));
+    other.execute("COMMIT")?;
+
+    let res = execute_and_get_ints(&tmp_db, &other, "SELECT x FROM t")?;
+    assert_eq!(res, vec![2]);
+    Ok(())
+}
+
+#[test]
+fn test_busy_timeout_checkpoint() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let (writer, other) = setup_locked_table(&tmp_db)?;
+
+    let timeout = std::time::Duration::from_millis(50);
+    other.busy_timeout(timeout);
+    let start = std::time::Instant::now();
+    assert!(matches!(
+        other.checkpoint_with_mode(CheckpointMode::Full),
+        Err(LimboError::Busy)
+    ));
+    assert!(start.elapsed() >= timeout);
+
+    // The checkpoint goes ahead once the writer commits.
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    other.busy_handler(Some(Box::new({
+        let calls = calls.clone();
+        move |count| {
+            calls.borrow_mut().push(count);
+            writer.execute("COMMIT").unwrap();
+            true
+        }
+    })));
+    let res = execute_and_get_ints(&tmp_db, &other, "PRAGMA wal_checkpoint(RESTART)")?;
+    assert_eq!(res[0], 0);
+    assert_eq!(*calls.borrow(), vec![0]);
+    assert_eq!(other.wal_frame_count()?, 0);
+    let res = execute_and_get_ints(&tmp_db, &other, "SELECT x FROM t")?;
+    assert_eq!(res, vec![1]);
+    Ok(())
+}
+
+#[test]
+fn test_pragma_busy_timeout() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+
+    let res = execute_and_get_ints(&tmp_db, &conn, "PRAGMA busy_timeout")?;
+    assert_eq!(res, vec![0]);
+    let res = execute_and_get_ints(&tmp_db, &conn, "PRAGMA busy_timeout = 250")?;
+    assert_eq!(res, vec![250]);
+    assert_eq!(
+        conn.get_busy_timeout(),
+        std::time::Duration::from_millis(250)
+    );
+    let res = execute_and_get_ints(&tmp_db, &conn, "PRAGMA busy_timeout = -1")?;
+    assert_eq!(res, vec![0]);
+    Ok(())
+}
+
+#[test]
+fn test_wal_checkpoint_restart_and_truncate() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    conn.execute("CREATE TABLE t (x INTEGER PRIMARY KEY, y)")?;
+    for i in 0..10 {
+        conn.execute(format!("INSERT INTO t VALUES ({}, randomblob(1000))", i))?;
+    }
+    let frames = conn.wal_frame_count()? as i64;
+    assert!(frames > 0);
+
+    let res = execute_and_get_ints(&tmp_db, &conn, "PRAGMA wal_checkpoint(FULL)")?;
+    assert_eq!(res, vec![0, frames, frames]);
+    assert_eq!(conn.wal_frame_count()? as i64, frames);
+
+    // The restarted WAL starts over from its first frame.
+    let res = execute_and_get_ints(&tmp_db, &conn, "PRAGMA wal_checkpoint(RESTART)")?;
+    assert_eq!(res, vec![0, frames, frames]);
+    assert_eq!(conn.wal_frame_count()?, 0);
+    conn.execute("INSERT INTO t VALUES (10, randomblob(1000))")?;
+    assert!(conn.wal_frame_count()? > 0);
+    assert!(conn.wal_frame_count()? < frames as u64);
+
+    let mut wal_path = tmp_db.path.clone().into_os_string();
+    wal_path.push("-wal");
+    let wal_size = std::fs::metadata(&wal_path).unwrap().len();
+    execute_and_get_ints(&tmp_db, &conn, "PRAGMA wal_checkpoint(TRUNCATE)")?;
+    assert_eq!(conn.wal_frame_count()?, 0);
+    let truncated_size = std::fs::metadata(&wal_path).unwrap().len();
+    assert!(truncated_size < wal_size);
+    assert_eq!(truncated_size, 32);
+
+    conn.execute("INSERT INTO t VALUES (11, randomblob(1000))")?;
+    let res = execute_and_get_ints(&tmp_db, &conn, "SELECT count(*) FROM t")?;
+    assert_eq!(res, vec![12]);
+    conn.close()?;
+
+    let sqlite_conn = rusqlite::Connection::open(&tmp_db.path).unwrap();
+    let integrity: String = sqlite_conn
+        .query_row("PRAGMA integrity_check", (), |row| row.get(0))
+        .unwrap();
+    assert_eq!(integrity, "ok");
+    let count: i64 = sqlite_conn
+        .query_row("SELECT count(*) FROM t", (), |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 12);
+    Ok(())
+}
+
+#[test]
+fn test_wal_checkpoint_restart_waits_for_readers_and_writers() -> Result<()> {
+    maybe_setup_tracing();
+    let tmp_db = TempDatabase::new_empty();
+    let conn = tmp_db.connect_limbo();
+    let other = tmp_db.connect_limbo();
+    conn.execute("CREATE TABLE t (x)")?;
+    conn.execute("INSERT INTO t VALUES (1)")?;
+    let frames = conn.wal_frame_count()?;
+
+    // A reader of the WAL keeps it from being restarted, but not from being checkpointed.
+    other.execute("BEGIN")?;
+    execute_and_get_ints(&tmp_db, &other, "SELECT count(*) FROM t")?;
+    assert!(matches!(
+        conn.checkpoint_with_mode(CheckpointMode::Restart),
+        Err(LimboError::Busy)
+    ));
+    assert_eq!(conn.wal_frame_count()?, frames);
+    assert!(conn.checkpoint_with_mode(CheckpointMode::Full).is_ok());
+    other.execute("COMMIT")?;
+
+    // A writer keeps the WAL from being checkpointed.
+    other.execute("BEGIN")?;
+    other.execute("INSERT INTO t VALUES (2)")?;
+    for mode in [
+        CheckpointMode::Full,
+        CheckpointMode::Restart,
+        CheckpointMode::Truncate,
+    ] {
+        assert!(matches!(
+            conn.checkpoint_with_mode(mode),
+            Err(LimboError::Busy)
+        ));
+    }
+    let res = execute_and_get_strings(&tmp_db, &conn, "PRAGMA wal_checkpoint(RESTART)")?;
+    assert_eq!(res[0], "1");
+    other.execute("COMMIT")?;
+
+    conn.checkpoint_with_mode(CheckpointMode::Restart)?;
+    assert_eq!(conn.wal_frame_count()?, 0);
+    let res = execute_and_get_ints(&tmp_db, &other, "SELECT count(*) FROM t")?;
+    assert_eq!(res, vec![2]);
+    Ok(())
+}
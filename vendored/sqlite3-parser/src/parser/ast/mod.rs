@@ -1728,22 +1728,40 @@ pub type PragmaValue = Expr; // TODO
 pub enum PragmaName {
     /// set the autovacuum mode
     AutoVacuum,
+    /// `busy_timeout` pragma
+    BusyTimeout,
     /// `cache_size` pragma
     CacheSize,
+    /// `case_sensitive_like` pragma
+    CaseSensitiveLike,
+    /// `hard_heap_limit` pragma
+    HardHeapLimit,
     /// Run integrity check on the database file
     IntegrityCheck,
     /// `journal_mode` pragma
     JournalMode,
+    /// Set the encryption key of the database
+    Key,
     /// Noop as per SQLite docs
     LegacyFileFormat,
+    /// `mmap_size` pragma
+    MmapSize,
     /// Return the total number of pages in the database file.
     PageCount,
     /// Return the page size of the database in bytes.
     PageSize,
+    /// Re-encrypt the database with a new key
+    Rekey,
     /// Returns schema version of the database file.
     SchemaVersion,
+    /// Free as much memory as possible from the connection
+    ShrinkMemory,
+    /// `soft_heap_limit` pragma
+    SoftHeapLimit,
     /// returns information about the columns of a table
     TableInfo,
+    /// `temp_store` pragma
+    TempStore,
     /// Returns the user version of the database file.
     UserVersion,
     /// trigger a checkpoint to run on database(s) if WAL is enabled